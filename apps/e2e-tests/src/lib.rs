@@ -5,7 +5,8 @@
 //! - Run actual flaglite CLI commands as subprocesses
 //! - Parse command output to verify results
 //!
-//! The test harness and utilities are defined in `tests/common/` module.
+//! The test harness is the `flaglite-test-harness` crate, re-exported with
+//! test-only checks from the `tests/common/` module.
 //!
 //! ## Running Tests
 //!
@@ -26,4 +27,4 @@
 //! - `FLAGLITE_E2E_SERVER_TIMEOUT_SECS` - Server startup timeout (default: 30)
 
 // This lib.rs is intentionally minimal.
-// The actual test utilities are in tests/common/ module.
+// The tests import the harness through tests/common/.
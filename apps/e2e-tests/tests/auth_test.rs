@@ -120,6 +120,365 @@ async fn test_login_with_correct_password() {
     );
 }
 
+/// Test logging in with only an API key, no password.
+#[tokio::test]
+async fn test_login_with_api_key() {
+    let harness = TestHarness::new("login_api_key")
+        .await
+        .expect("Failed to create test harness");
+
+    let username = unique_username();
+    let user = harness.create_user("kim");
+    let info = user
+        .signup(Some(&username), TEST_PASSWORD)
+        .expect("Signup failed");
+    user.logout().expect("Logout failed");
+
+    // Keys are checked before they're saved
+    let result = user.exec(&["login", "--api-key", "flg_not_a_real_key"]);
+    assert_eq!(result.exit_code(), Some(2), "stderr: {}", result.stderr());
+    assert!(user.whoami().is_err(), "Rejected key must not be stored");
+
+    let result = user.exec(&["login", "--api-key", "not-a-user-key"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    let result = user.exec(&["login", "--api-key", &info.api_key]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    assert!(result.stdout().contains(&username));
+
+    // The stored key authenticates later commands
+    let whoami = user.whoami().expect("Whoami failed after API key login");
+    assert_eq!(whoami.username, username);
+
+    let result = user.exec_json(&["whoami", "--local"]);
+    let local: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(local["username"], username.as_str());
+    assert_eq!(local["api_key_source"], "credentials");
+}
+
+/// Test that scoped keys can only make the requests their scope covers.
+#[tokio::test]
+async fn test_api_key_scopes() {
+    let harness = TestHarness::new("api_key_scopes")
+        .await
+        .expect("Failed to create test harness");
+
+    let owner = harness.create_user("noa");
+    owner
+        .signup(Some(&unique_username()), TEST_PASSWORD)
+        .expect("Signup failed");
+
+    let create_key = |scope: &str| {
+        let result = owner.exec_json(&["keys", "create", "--name", scope, "--scope", scope]);
+        assert!(result.succeeded(), "stderr: {}", result.stderr());
+        let key: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+        assert_eq!(key["scope"], scope);
+        key["key"].as_str().unwrap().to_string()
+    };
+    let read_key = create_key("read");
+    let write_key = create_key("write");
+
+    let result = owner.exec(&["keys", "create", "--scope", "owner"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    let result = owner.exec_json(&["keys", "list"]);
+    let keys: Vec<serde_json::Value> = serde_json::from_str(&result.stdout()).unwrap();
+    let scopes: Vec<&str> = keys.iter().filter_map(|k| k["scope"].as_str()).collect();
+    assert_eq!(scopes, ["write", "read", "admin"]);
+
+    // A read key can look but not change anything
+    let reader = harness.create_user("noa-dashboard");
+    let result = reader.exec(&["login", "--api-key", &read_key]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    assert_eq!(reader.projects_list().expect("List failed").len(), 1);
+
+    let result = reader.exec(&["projects", "create", "from-read-key"]);
+    assert_eq!(result.exit_code(), Some(2), "stderr: {}", result.stderr());
+    assert!(
+        result.stderr().contains("scope is read"),
+        "stderr: {}",
+        result.stderr()
+    );
+
+    // A write key can change projects, but not manage keys
+    let writer = harness.create_user("noa-ci");
+    let result = writer.exec(&["login", "--api-key", &write_key]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    writer
+        .projects_create("from-write-key", None)
+        .expect("Write key should create projects");
+
+    let result = writer.exec(&["keys", "create"]);
+    assert_eq!(result.exit_code(), Some(2), "stderr: {}", result.stderr());
+    assert!(writer.exec(&["keys", "list"]).succeeded());
+}
+
+/// Test that parallel logins leave a valid credentials file, and that a
+/// damaged one is reported and replaced by the next login.
+#[tokio::test]
+async fn test_credentials_survive_parallel_writes() {
+    let harness = TestHarness::new("credentials_parallel")
+        .await
+        .expect("Failed to create test harness");
+
+    let username = unique_username();
+    let user = harness.create_user("lee");
+    let info = user
+        .signup(Some(&username), TEST_PASSWORD)
+        .expect("Signup failed");
+
+    std::thread::scope(|scope| {
+        let logins: Vec<_> = (0..8)
+            .map(|_| scope.spawn(|| user.exec(&["login", "--api-key", &info.api_key])))
+            .collect();
+        for login in logins {
+            let result = login.join().expect("Login thread panicked");
+            assert!(result.succeeded(), "stderr: {}", result.stderr());
+        }
+    });
+
+    let credentials = user.home_dir.join(".flaglite/credentials.json");
+    let content = std::fs::read_to_string(&credentials).expect("No credentials file");
+    serde_json::from_str::<serde_json::Value>(&content).expect("Credentials file is not JSON");
+    assert_eq!(user.whoami().expect("Whoami failed").username, username);
+
+    std::fs::write(&credentials, "{\"api_key\": \"flg_").unwrap();
+    let result = user.exec(&["whoami"]);
+    assert!(!result.succeeded());
+    assert!(
+        result.stdout().contains("flaglite login"),
+        "stdout: {}",
+        result.stdout()
+    );
+
+    let result = user.exec(&["login", "--api-key", &info.api_key]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    assert_eq!(user.whoami().expect("Whoami failed").username, username);
+}
+
+#[tokio::test]
+async fn test_verbose_logs_requests() {
+    let harness = TestHarness::new("verbose")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("val");
+    let info = user
+        .signup(Some(&unique_username()), TEST_PASSWORD)
+        .expect("Signup failed");
+
+    let result = user.exec(&["--verbose", "whoami"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+
+    let log = result.stderr();
+    assert!(log.contains("GET"), "stderr: {log}");
+    assert!(log.contains("/v1/auth/me"), "stderr: {log}");
+    assert!(log.contains("200 OK"), "stderr: {log}");
+    assert!(log.contains("Authorization: Bearer"), "stderr: {log}");
+    assert!(!log.contains(&info.api_key), "Credentials must be redacted");
+    assert!(!result.stdout().contains("/v1/auth/me"));
+}
+
+#[tokio::test]
+async fn test_timings_summarize_requests() {
+    let harness = TestHarness::new("timings")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("tim");
+    user.signup(Some(&unique_username()), TEST_PASSWORD)
+        .expect("Signup failed");
+
+    let result = user.exec_json(&["--timings", "whoami"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+
+    let summary = result.stderr();
+    assert!(
+        summary.contains("Timings: 1 API call(s)"),
+        "stderr: {summary}"
+    );
+    assert!(summary.contains("GET /v1/auth/me"), "stderr: {summary}");
+    // The JSON result stays parseable
+    let _: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+
+    // Failed commands still report what they called
+    let result = user.exec(&["--timings", "flags", "get", "no-such-flag", "-p", "nope"]);
+    assert!(result.failed());
+    assert!(
+        result.stderr().contains("failed"),
+        "stderr: {}",
+        result.stderr()
+    );
+}
+
+/// The token in the last email logged for `to` (the server has no SMTP here)
+fn emailed_token(harness: &TestHarness, to: &str) -> String {
+    let log = harness.server_stdout();
+    let email = log
+        .rfind(&format!("Email to {to} "))
+        .map(|start| &log[start..])
+        .unwrap_or_else(|| panic!("No email to {to} in server log"));
+    let token = &email[email.find("tok_").expect("No token in email")..];
+    token
+        .chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .collect()
+}
+
+/// Test verifying an email address and resetting the password through it.
+#[tokio::test]
+async fn test_email_verification_and_password_reset() {
+    let harness = TestHarness::new("password_reset")
+        .await
+        .expect("Failed to create test harness");
+
+    let username = unique_username();
+    let email = format!("{username}@example.com");
+    let user = harness.create_user("lena");
+    user.signup(Some(&username), TEST_PASSWORD)
+        .expect("Signup failed");
+
+    // Without a verified email nothing is sent, but the request still succeeds
+    let result = user.exec(&["account", "request-reset", "--username", &username]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    assert!(!harness
+        .server_stdout()
+        .contains(&format!("Email to {email} ")));
+
+    user.exec(&["account", "set-email", &email])
+        .success_or_err("account set-email")
+        .expect("set-email failed");
+    user.exec(&["account", "verify-email"])
+        .success_or_err("account verify-email")
+        .expect("verify-email failed");
+    let token = emailed_token(&harness, &email);
+
+    let result = user.exec_json(&["account", "verify-email", "--token", &token]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let verified: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(verified["email_verified"], true);
+
+    user.exec(&["account", "request-reset", "--username", &username])
+        .success_or_err("account request-reset")
+        .expect("request-reset failed");
+    let token = emailed_token(&harness, &email);
+
+    // Too-short passwords are rejected without using up the token
+    let result = user.exec(&["account", "reset", "--token", &token, "--password", "short"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    // A session and an API key someone may have taken with the old password
+    let client = reqwest::Client::new();
+    let me_url = format!("{}/v1/auth/me", harness.server_url);
+    let login: serde_json::Value = client
+        .post(format!("{}/v1/auth/login", harness.server_url))
+        .json(&serde_json::json!({"username": username, "password": TEST_PASSWORD}))
+        .send()
+        .await
+        .expect("Login request failed")
+        .json()
+        .await
+        .expect("Invalid login JSON");
+    let session = login["token"].as_str().expect("Missing token").to_string();
+    let result = user.exec_json(&["keys", "create", "--name", "laptop"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let key: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    let key = key["key"].as_str().unwrap().to_string();
+    for credential in [&session, &key] {
+        let resp = client.get(&me_url).bearer_auth(credential).send().await;
+        assert_eq!(resp.expect("Request failed").status().as_u16(), 200);
+    }
+
+    let new_password = "a-new-password-123";
+    let result = user.exec(&[
+        "account",
+        "reset",
+        "--token",
+        &token,
+        "--password",
+        new_password,
+        "--revoke-api-keys",
+    ]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+
+    // Both stop working
+    for credential in [&session, &key] {
+        let resp = client.get(&me_url).bearer_auth(credential).send().await;
+        assert_eq!(resp.expect("Request failed").status().as_u16(), 401);
+    }
+
+    // Tokens work once
+    let result = user.exec(&[
+        "account",
+        "reset",
+        "--token",
+        &token,
+        "--password",
+        new_password,
+    ]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    user.logout().expect("Logout failed");
+    assert!(user.login(&username, TEST_PASSWORD).is_err());
+    user.login(&username, new_password)
+        .expect("Login with the new password failed");
+    // Sessions from after the reset work
+    user.whoami().expect("Whoami after the reset failed");
+}
+
+/// Test renaming: the old username stays reserved and still finds the user,
+/// and a second rename has to wait out the cooldown.
+#[tokio::test]
+async fn test_account_rename() {
+    let harness = TestHarness::new("account_rename")
+        .await
+        .expect("Failed to create test harness");
+
+    let old_name = unique_username();
+    let new_name = unique_username();
+    let user = harness.create_user("oscar");
+    user.signup(Some(&old_name), TEST_PASSWORD)
+        .expect("Signup failed");
+
+    let json = user
+        .exec_json(&["account", "rename", &new_name])
+        .success()
+        .expect("account rename failed");
+    let renamed: serde_json::Value = serde_json::from_str(&json).expect("Invalid user JSON");
+    assert_eq!(renamed["username"], new_name.as_str());
+    assert_eq!(user.whoami().expect("Whoami failed").username, new_name);
+
+    let result = user.exec(&["account", "rename", &unique_username()]);
+    assert!(result.failed(), "A second rename should hit the cooldown");
+    assert!(
+        result.stderr().contains("changed again in 30 day"),
+        "stderr: {}",
+        result.stderr()
+    );
+
+    // Nobody else can take the old name
+    let other = harness.create_user("peggy");
+    assert!(other.signup(Some(&old_name), TEST_PASSWORD).is_err());
+    other.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    // But it still finds the renamed user
+    other
+        .exec(&["orgs", "create", "Renamers"])
+        .success()
+        .expect("orgs create failed");
+    let json = other
+        .exec_json(&["orgs", "add-member", &old_name])
+        .success()
+        .expect("orgs add-member by the old username failed");
+    let member: serde_json::Value = serde_json::from_str(&json).expect("Invalid member JSON");
+    assert_eq!(member["username"], new_name.as_str());
+
+    user.logout().expect("Logout failed");
+    assert!(user.login(&old_name, TEST_PASSWORD).is_err());
+    user.login(&new_name, TEST_PASSWORD)
+        .expect("Login with the new username failed");
+}
+
 /// Test login with wrong password is rejected.
 #[tokio::test]
 async fn test_login_with_wrong_password() {
@@ -149,6 +508,46 @@ async fn test_login_with_wrong_password() {
     );
 }
 
+/// Test that repeated failed logins lock the username out, even for the right password.
+#[tokio::test]
+async fn test_login_lockout_after_failures() {
+    let harness = TestHarness::with_env("login_lockout", &[("LOGIN_MAX_FAILURES", "2")])
+        .await
+        .expect("Failed to create test harness");
+
+    let username = unique_username();
+    let user = harness.create_user("mallory");
+    user.signup(Some(&username), TEST_PASSWORD)
+        .expect("Signup failed");
+    let _ = user.logout();
+
+    let err = user
+        .login(&username, "wrongpassword")
+        .expect_err("Login with a wrong password should fail");
+    assert!(!err.contains("try again"), "Locked out too early: {err}");
+
+    let err = user
+        .login(&username, "wrongpassword")
+        .expect_err("Login with a wrong password should fail");
+    assert!(err.contains("try again"), "Expected a lockout, got: {err}");
+
+    let err = user
+        .login(&username, TEST_PASSWORD)
+        .expect_err("Login should be refused while locked out");
+    assert!(err.contains("try again"), "Expected a lockout, got: {err}");
+
+    // Other users are unaffected
+    let other_name = unique_username();
+    let other = harness.create_user("trent");
+    other
+        .signup(Some(&other_name), TEST_PASSWORD)
+        .expect("Signup failed");
+    let _ = other.logout();
+    other
+        .login(&other_name, TEST_PASSWORD)
+        .expect("Login for another user failed");
+}
+
 /// Test whoami fails without authentication.
 #[tokio::test]
 async fn test_whoami_requires_auth() {
@@ -164,6 +563,36 @@ async fn test_whoami_requires_auth() {
     assert!(result.is_err(), "Whoami should fail without auth");
 }
 
+/// Test commands that need input fail fast instead of prompting without a terminal.
+#[tokio::test]
+async fn test_prompts_fail_fast_without_input() {
+    let harness = TestHarness::new("prompts_fail_fast")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("grace");
+
+    // stdin is not a terminal here, so this would otherwise wait for a password
+    let result = user.exec(&["signup"]);
+    assert!(result.failed(), "Signup without --password should fail");
+    assert_eq!(result.exit_code(), Some(4));
+    assert!(
+        result.stderr().contains("--password"),
+        "Error should name the flag to use: {}",
+        result.stderr()
+    );
+
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    let result = user.exec(&["--no-input", "login"]);
+    assert!(result.failed(), "Login without credentials should fail");
+    assert!(
+        result.stderr().contains("--no-input"),
+        "Error should explain why it can't prompt: {}",
+        result.stderr()
+    );
+}
+
 /// Test duplicate signup is rejected.
 #[tokio::test]
 async fn test_signup_duplicate_username_rejected() {
@@ -223,3 +652,194 @@ async fn test_logout_clears_auth() {
         "Should not be authenticated after logout"
     );
 }
+
+/// Test that signup is rejected when SIGNUP_MODE=disabled.
+#[tokio::test]
+async fn test_signup_disabled() {
+    let harness = TestHarness::with_env("signup_disabled", &[("SIGNUP_MODE", "disabled")])
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("dave");
+    let result = user.signup(None, TEST_PASSWORD);
+
+    let err = result.expect_err("Signup should fail when disabled");
+    assert!(
+        err.contains("Signup is disabled"),
+        "Expected disabled error, got: {err}"
+    );
+}
+
+/// Test that SIGNUP_MODE=invite requires a valid, unused invite code.
+#[tokio::test]
+async fn test_signup_invite_only() {
+    let harness = TestHarness::with_env(
+        "signup_invite_only",
+        &[
+            ("SIGNUP_MODE", "invite"),
+            ("ADMIN_TOKEN", "test-admin-token"),
+        ],
+    )
+    .await
+    .expect("Failed to create test harness");
+
+    // Without a code
+    let user = harness.create_user("erin");
+    assert!(
+        user.signup(None, TEST_PASSWORD).is_err(),
+        "Signup without an invite code should fail"
+    );
+
+    // With a bogus code
+    let result = user.exec(&[
+        "signup",
+        "--password",
+        TEST_PASSWORD,
+        "--invite-code",
+        "inv_bogus",
+    ]);
+    assert!(result.failed(), "Signup with an unknown code should fail");
+
+    // With a code from the admin command
+    let code = harness
+        .exec_api(&["invite"])
+        .success()
+        .expect("flaglite-api invite failed")
+        .trim()
+        .to_string();
+    assert!(code.starts_with("inv_"), "Unexpected invite code: {code}");
+
+    let result = user.exec(&[
+        "signup",
+        "--password",
+        TEST_PASSWORD,
+        "--invite-code",
+        &code,
+    ]);
+    assert!(
+        result.succeeded(),
+        "Signup with invite code failed: {}",
+        result.stderr()
+    );
+
+    // Codes are single-use
+    let other = harness.create_user("frank");
+    let result = other.exec(&[
+        "signup",
+        "--password",
+        TEST_PASSWORD,
+        "--invite-code",
+        &code,
+    ]);
+    assert!(result.failed(), "Invite code should not be reusable");
+
+    // Only organization owners can invite others
+    let result = user.exec(&["invite"]);
+    assert!(
+        result.failed(),
+        "A user without a team org should not invite"
+    );
+    assert!(
+        result.stderr().contains("organization owners"),
+        "Unexpected error: {}",
+        result.stderr()
+    );
+
+    user.exec(&["orgs", "create", "Inviters"])
+        .success()
+        .expect("orgs create failed");
+    let json = user
+        .exec_json(&["invite"])
+        .success()
+        .expect("flaglite invite failed");
+    let invite: serde_json::Value = serde_json::from_str(&json).expect("Invalid invite JSON");
+    let code = invite["code"].as_str().expect("Missing invite code");
+
+    let result = other.exec(&["signup", "--password", TEST_PASSWORD, "--invite-code", code]);
+    assert!(
+        result.succeeded(),
+        "Signup with user-created invite failed: {}",
+        result.stderr()
+    );
+
+    // And the operator, with ADMIN_TOKEN
+    let resp = reqwest::Client::new()
+        .post(format!("{}/v1/invites", harness.server_url))
+        .bearer_auth("test-admin-token")
+        .send()
+        .await
+        .expect("Invite request failed");
+    assert_eq!(resp.status().as_u16(), 200);
+    let invite: serde_json::Value = resp.json().await.expect("Invalid invite JSON");
+    assert!(invite["code"].as_str().unwrap_or("").starts_with("inv_"));
+}
+
+/// Test that preferences saved with the account are applied when logging in
+/// on another machine.
+#[tokio::test]
+async fn test_login_applies_saved_preferences() {
+    let harness = TestHarness::new("login_preferences")
+        .await
+        .expect("Failed to create test harness");
+
+    let username = unique_username();
+    let user = harness.create_user("ada");
+    user.signup(Some(&username), TEST_PASSWORD)
+        .expect("Signup failed");
+    let project = user
+        .projects_create("Prefs App", None)
+        .expect("Projects create failed");
+
+    for (key, value) in [
+        ("project", project.slug.as_str()),
+        ("environment", "staging"),
+        ("display_name", "Ada L."),
+        ("format", "JSON"),
+    ] {
+        let result = user.exec(&["account", "set-preference", key, value]);
+        assert!(result.succeeded(), "{key}: {}", result.stderr());
+    }
+
+    // Bad values are rejected before anything is saved
+    let result = user.exec(&["account", "set-preference", "format", "yaml"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+    let result = user.exec(&["account", "set-preference", "theme", "dark"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    let result = user.exec_json(&["account", "preferences"]);
+    let preferences: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    // The project is kept by ID, whatever it was set with
+    assert_eq!(preferences["project_id"], project.id.as_str());
+    assert_eq!(preferences["environment"], "staging");
+    assert_eq!(preferences["format"], "json");
+
+    // A fresh machine picks them all up on login
+    let laptop = harness.create_user("ada-laptop");
+    let result = laptop.exec(&[
+        "login",
+        "--username",
+        &username,
+        "--password",
+        TEST_PASSWORD,
+    ]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    assert!(result.stdout().contains("Ada L."), "{}", result.stdout());
+
+    // The format preference already makes JSON the default output
+    let config_value = |key: &str| {
+        let result = laptop.exec(&["config", "get", key]);
+        let value: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+        value["value"].as_str().unwrap_or_default().to_string()
+    };
+    assert_eq!(config_value("environment"), "staging");
+    assert_eq!(config_value("project"), project.id);
+    assert_eq!(config_value("format"), "json");
+
+    // Clearing one leaves the others
+    let result = user.exec(&["account", "set-preference", "environment", ""]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let result = user.exec_json(&["account", "preferences"]);
+    let preferences: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert!(preferences["environment"].is_null());
+    assert_eq!(preferences["display_name"], "Ada L.");
+}
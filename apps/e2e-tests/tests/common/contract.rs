@@ -0,0 +1,176 @@
+//! Checks API responses against the shapes the server documents in `/llms.json`.
+//!
+//! Documented shapes are JSON with field types as values: `string`, `uuid`,
+//! `datetime`, `date`, `int`, `bool`, `object`, `json` (anything), a
+//! `|`-separated list of allowed strings, or the name of a shared schema
+//! (`Project`, `Project[]`). A trailing `?` allows null or a missing field.
+//! An object key like `<env>` stands for every key, and an array documents
+//! the shape of its elements. Fields a response has but the docs don't list
+//! are reported too, so the docs can't fall behind the handlers.
+
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// The documented endpoints and shared schemas of one server
+pub struct Contract {
+    endpoints: Vec<Value>,
+    schemas: Map<String, Value>,
+}
+
+impl Contract {
+    /// Fetch the documentation from a running server
+    pub async fn fetch(server_url: &str) -> Self {
+        let doc: Value = reqwest::get(format!("{server_url}/llms.json"))
+            .await
+            .expect("Failed to fetch /llms.json")
+            .json()
+            .await
+            .expect("/llms.json is not JSON");
+
+        Contract {
+            endpoints: doc["endpoints"]
+                .as_array()
+                .cloned()
+                .expect("/llms.json has no endpoints"),
+            schemas: doc["schemas"]
+                .as_object()
+                .cloned()
+                .expect("/llms.json has no schemas"),
+        }
+    }
+
+    /// Documented response of `method path`, with the path in `{param}` form
+    pub fn response(&self, method: &str, path: &str) -> &Value {
+        let endpoint = self
+            .endpoints
+            .iter()
+            .find(|e| e["method"] == method && e["path"] == path)
+            .unwrap_or_else(|| panic!("{method} {path} is not documented"));
+        assert!(
+            !endpoint["response"].is_null(),
+            "{method} {path} documents no response"
+        );
+        &endpoint["response"]
+    }
+
+    /// A shared schema by name
+    pub fn schema(&self, name: &str) -> Option<&Value> {
+        self.schemas.get(name)
+    }
+
+    /// Every way `actual` differs from the documented response
+    pub fn check(&self, method: &str, path: &str, actual: &Value) -> Vec<String> {
+        let mut errors = Vec::new();
+        self.check_shape(self.response(method, path), actual, "$", &mut errors);
+        errors
+    }
+
+    /// Panic unless `actual` matches the documented response
+    pub fn assert_matches(&self, method: &str, path: &str, actual: &Value) {
+        let errors = self.check(method, path, actual);
+        assert!(
+            errors.is_empty(),
+            "{method} {path} doesn't match its documentation:\n  {}\nResponse: {actual}",
+            errors.join("\n  ")
+        );
+    }
+
+    fn check_shape(&self, shape: &Value, actual: &Value, at: &str, errors: &mut Vec<String>) {
+        match shape {
+            Value::String(spec) => self.check_spec(spec, actual, at, errors),
+            Value::Object(fields) => {
+                let Some(object) = actual.as_object() else {
+                    errors.push(format!("{at}: expected an object, got {actual}"));
+                    return;
+                };
+                if let Some((_, value_shape)) = fields.iter().find(|(k, _)| k.starts_with('<')) {
+                    for (key, value) in object {
+                        self.check_shape(value_shape, value, &format!("{at}.{key}"), errors);
+                    }
+                    return;
+                }
+                for (key, field_shape) in fields {
+                    match object.get(key) {
+                        Some(value) => {
+                            self.check_shape(field_shape, value, &format!("{at}.{key}"), errors)
+                        }
+                        None if optional(field_shape) => {}
+                        None => errors.push(format!("{at}.{key}: missing")),
+                    }
+                }
+                for key in object.keys().filter(|k| !fields.contains_key(*k)) {
+                    errors.push(format!("{at}.{key}: not documented"));
+                }
+            }
+            Value::Array(items) => {
+                let Some(array) = actual.as_array() else {
+                    errors.push(format!("{at}: expected an array, got {actual}"));
+                    return;
+                };
+                if let Some(item) = items.first() {
+                    for (i, value) in array.iter().enumerate() {
+                        self.check_shape(item, value, &format!("{at}[{i}]"), errors);
+                    }
+                }
+            }
+            literal => {
+                if actual != literal {
+                    errors.push(format!("{at}: expected {literal}, got {actual}"));
+                }
+            }
+        }
+    }
+
+    fn check_spec(&self, spec: &str, actual: &Value, at: &str, errors: &mut Vec<String>) {
+        let (spec, nullable) = match spec.strip_suffix('?') {
+            Some(spec) => (spec, true),
+            None => (spec, false),
+        };
+        if actual.is_null() {
+            if !nullable {
+                errors.push(format!("{at}: null, but documented as {spec}"));
+            }
+            return;
+        }
+
+        if let Some(schema) = spec
+            .strip_suffix("[]")
+            .and_then(|name| self.schemas.get(name))
+        {
+            self.check_shape(&Value::Array(vec![schema.clone()]), actual, at, errors);
+            return;
+        }
+        if let Some(schema) = self.schemas.get(spec) {
+            self.check_shape(schema, actual, at, errors);
+            return;
+        }
+
+        let matches = match spec {
+            "string" => actual.is_string(),
+            "uuid" => actual.as_str().is_some_and(|s| Uuid::parse_str(s).is_ok()),
+            // RFC 3339, as chrono serializes it
+            "datetime" => actual
+                .as_str()
+                .is_some_and(|s| s.len() >= 20 && s.as_bytes()[10] == b'T'),
+            "date" => actual
+                .as_str()
+                .is_some_and(|s| s.len() == 10 && s.as_bytes()[4] == b'-'),
+            "int" => actual.is_i64() || actual.is_u64(),
+            "number" => actual.is_number(),
+            "bool" => actual.is_boolean(),
+            "object" => actual.is_object(),
+            "json" => true,
+            choices => actual
+                .as_str()
+                .is_some_and(|s| choices.split('|').any(|c| c == s)),
+        };
+        if !matches {
+            errors.push(format!("{at}: expected {spec}, got {actual}"));
+        }
+    }
+}
+
+/// Documented as nullable, so it may also be left out
+fn optional(shape: &Value) -> bool {
+    shape.as_str().is_some_and(|s| s.ends_with('?'))
+}
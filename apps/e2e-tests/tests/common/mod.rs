@@ -1,16 +1,12 @@
 //! Common test utilities for e2e tests
 //!
-//! This module provides a black-box test harness that:
-//! - Spawns actual server processes (flaglite-api)
-//! - Runs actual CLI commands (flaglite) as subprocesses
-//! - Parses command output to verify results
+//! The harness that spawns flaglite-api and runs the flaglite CLI lives in
+//! the `flaglite-test-harness` crate; this module re-exports it next to the
+//! checks only these tests use.
 
 #![allow(dead_code)]
 
-pub mod harness;
-pub mod utils;
+pub mod contract;
 
 #[allow(unused_imports)]
-pub use harness::{TestHarness, TestUser};
-#[allow(unused_imports)]
-pub use utils::*;
+pub use flaglite_test_harness::*;
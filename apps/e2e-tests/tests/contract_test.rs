@@ -0,0 +1,454 @@
+//! Contract tests for the two route families that serve flags
+//!
+//! Management routes (`/v1/projects/:id/flags/...`, user credentials) and SDK
+//! routes (`/v1/flags`, `/v1/changes`, SDK keys) read the same stored flags.
+//! These tests check both against the response shapes the server documents in
+//! `/llms.json`, and that they report the same state for the same flag.
+//!
+//! The version 1 shapes are also pinned in `fixtures/api-v1.json`, so a
+//! response change can't slip into version 1 through its documentation; it
+//! has to be a new API version whose shim gives old clients the pinned shape.
+
+mod common;
+
+use common::contract::Contract;
+use common::*;
+use reqwest::Method;
+use serde_json::{json, Value};
+
+/// A signed-up user's credentials against one server
+struct Api {
+    client: reqwest::Client,
+    server_url: String,
+    token: String,
+    project_id: String,
+    /// SDK key of the production environment
+    sdk_key: String,
+}
+
+impl Api {
+    async fn signup(harness: &TestHarness, contract: &Contract) -> Self {
+        let client = reqwest::Client::new();
+        let signup: Value = client
+            .post(format!("{}/v1/auth/signup", harness.server_url))
+            .json(&json!({ "password": TEST_PASSWORD }))
+            .send()
+            .await
+            .expect("Signup request failed")
+            .json()
+            .await
+            .expect("Invalid signup JSON");
+        contract.assert_matches("POST", "/v1/auth/signup", &signup);
+
+        let sdk_key = signup["environments"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|env| env["name"] == "production")
+            .and_then(|env| env["api_key"].as_str())
+            .expect("Signup returned no production environment")
+            .to_string();
+
+        Api {
+            client,
+            server_url: harness.server_url.clone(),
+            token: signup["token"].as_str().expect("No token").to_string(),
+            project_id: signup["project"]["id"]
+                .as_str()
+                .expect("No project")
+                .to_string(),
+            sdk_key,
+        }
+    }
+
+    /// Call with the user's token and return the successful JSON response
+    async fn user(&self, method: Method, path: &str, body: Option<Value>) -> Value {
+        self.call(method, path, &self.token, body).await
+    }
+
+    /// Call with the production SDK key
+    async fn sdk(&self, path: &str) -> Value {
+        self.call(Method::GET, path, &self.sdk_key, None).await
+    }
+
+    /// Call with the production SDK key and return the successful response
+    async fn sdk_raw(&self, method: Method, path: &str) -> reqwest::Response {
+        let response = self
+            .client
+            .request(method.clone(), format!("{}{path}", self.server_url))
+            .bearer_auth(&self.sdk_key)
+            .send()
+            .await
+            .expect("Request failed");
+        assert!(
+            response.status().is_success(),
+            "{method} {path}: {}",
+            response.status()
+        );
+        response
+    }
+
+    async fn call(&self, method: Method, path: &str, auth: &str, body: Option<Value>) -> Value {
+        let mut request = self
+            .client
+            .request(method.clone(), format!("{}{path}", self.server_url))
+            .bearer_auth(auth);
+        if let Some(body) = body {
+            request = request.json(&body);
+        }
+        let response = request.send().await.expect("Request failed");
+        let status = response.status();
+        let text = response.text().await.expect("Failed to read response");
+        assert!(status.is_success(), "{method} {path}: {status} {text}");
+        serde_json::from_str(&text).unwrap_or_else(|e| panic!("{method} {path}: {e}: {text}"))
+    }
+}
+
+/// Test that every flag-related response matches its documented shape.
+#[tokio::test]
+async fn test_responses_match_documented_shapes() {
+    let harness = TestHarness::new("contract_shapes")
+        .await
+        .expect("Failed to create test harness");
+    let contract = Contract::fetch(&harness.server_url).await;
+    let api = Api::signup(&harness, &contract).await;
+    let project = format!("/v1/projects/{}", api.project_id);
+    let key = unique_flag_key();
+
+    // Management routes
+    let projects = api.user(Method::GET, "/v1/projects", None).await;
+    contract.assert_matches("GET", "/v1/projects", &projects);
+
+    let got = api.user(Method::GET, &project, None).await;
+    contract.assert_matches("GET", "/v1/projects/{project_id}", &got);
+
+    let updated = api
+        .user(
+            Method::PATCH,
+            &project,
+            Some(json!({"require_change_reason": false})),
+        )
+        .await;
+    contract.assert_matches("PATCH", "/v1/projects/{project_id}", &updated);
+
+    let environments = api
+        .user(Method::GET, &format!("{project}/environments"), None)
+        .await;
+    contract.assert_matches(
+        "GET",
+        "/v1/projects/{project_id}/environments",
+        &environments,
+    );
+
+    let created = api
+        .user(
+            Method::POST,
+            &format!("{project}/flags"),
+            Some(json!({ "key": key, "name": "Contract flag" })),
+        )
+        .await;
+    contract.assert_matches("POST", "/v1/projects/{project_id}/flags", &created);
+
+    let state = api
+        .user(
+            Method::PUT,
+            &format!("{project}/flags/{key}/state"),
+            Some(json!({ "enabled": true, "environment": "production", "confirm": true })),
+        )
+        .await;
+    contract.assert_matches("PUT", "/v1/projects/{project_id}/flags/{key}/state", &state);
+
+    let batch = api
+        .user(
+            Method::PATCH,
+            &format!("{project}/flags"),
+            Some(json!({
+                "environment": "production",
+                "keys": [key],
+                "rollout_percentage": 40,
+                "confirm": true,
+            })),
+        )
+        .await;
+    contract.assert_matches("PATCH", "/v1/projects/{project_id}/flags", &batch);
+
+    let flags = api
+        .user(Method::GET, &format!("{project}/flags"), None)
+        .await;
+    contract.assert_matches("GET", "/v1/projects/{project_id}/flags", &flags);
+
+    let flag = api
+        .user(Method::GET, &format!("{project}/flags/{key}"), None)
+        .await;
+    contract.assert_matches("GET", "/v1/projects/{project_id}/flags/{key}", &flag);
+
+    let history = api
+        .user(
+            Method::GET,
+            &format!("{project}/flags/{key}/history?environment=production"),
+            None,
+        )
+        .await;
+    contract.assert_matches(
+        "GET",
+        "/v1/projects/{project_id}/flags/{key}/history",
+        &history,
+    );
+
+    let templates = api
+        .user(Method::GET, &format!("{project}/templates"), None)
+        .await;
+    contract.assert_matches("GET", "/v1/projects/{project_id}/templates", &templates);
+
+    let stats = api
+        .user(Method::GET, &format!("{project}/stats"), None)
+        .await;
+    contract.assert_matches("GET", "/v1/projects/{project_id}/stats", &stats);
+
+    let api_key = api
+        .user(
+            Method::POST,
+            "/v1/auth/api-keys",
+            Some(json!({"name": "dashboard", "scope": "read"})),
+        )
+        .await;
+    contract.assert_matches("POST", "/v1/auth/api-keys", &api_key);
+    let api_keys = api.user(Method::GET, "/v1/auth/api-keys", None).await;
+    contract.assert_matches("GET", "/v1/auth/api-keys", &api_keys);
+
+    // SDK routes
+    let evaluation = api
+        .sdk(&format!("/v1/flags/{key}/evaluate?user_id=user-1"))
+        .await;
+    contract.assert_matches("GET", "/v1/flags/{key}/evaluate", &evaluation);
+
+    let config = api.sdk("/v1/flags").await;
+    contract.assert_matches("GET", "/v1/flags", &config);
+
+    let changes = api.sdk("/v1/changes?since=0").await;
+    contract.assert_matches("GET", "/v1/changes", &changes);
+
+    let signed = api.sdk("/v1/flags/signed").await;
+    contract.assert_matches("GET", "/v1/flags/signed", &signed);
+
+    let keys = api.sdk("/v1/flags/signing-keys").await;
+    contract.assert_matches("GET", "/v1/flags/signing-keys", &keys);
+}
+
+/// Test that management and SDK routes report the same state for a flag.
+#[tokio::test]
+async fn test_route_families_agree_on_flag_state() {
+    let harness = TestHarness::new("contract_agreement")
+        .await
+        .expect("Failed to create test harness");
+    let contract = Contract::fetch(&harness.server_url).await;
+    let api = Api::signup(&harness, &contract).await;
+    let project = format!("/v1/projects/{}", api.project_id);
+
+    let on = unique_flag_key();
+    let partial = unique_flag_key();
+    let off = unique_flag_key();
+    for key in [&on, &partial, &off] {
+        api.user(
+            Method::POST,
+            &format!("{project}/flags"),
+            Some(json!({ "key": key, "name": key })),
+        )
+        .await;
+    }
+    api.user(
+        Method::PATCH,
+        &format!("{project}/flags"),
+        Some(json!({
+            "environment": "production",
+            "keys": [on, partial],
+            "enabled": true,
+            "confirm": true,
+        })),
+    )
+    .await;
+    api.user(
+        Method::PATCH,
+        &format!("{project}/flags"),
+        Some(json!({
+            "environment": "production",
+            "keys": [partial],
+            "rollout_percentage": 25,
+            "confirm": true,
+        })),
+    )
+    .await;
+
+    let config = api.sdk("/v1/flags").await;
+    let changes = api.sdk("/v1/changes?since=0").await;
+    assert_eq!(
+        config["flags"], changes["flags"],
+        "/v1/flags and /v1/changes disagree"
+    );
+    assert_eq!(config["project_id"], api.project_id.as_str());
+
+    for key in [&on, &partial, &off] {
+        let managed = api
+            .user(
+                Method::GET,
+                &format!("{project}/flags/{key}?environment=production"),
+                None,
+            )
+            .await;
+        let production = &managed["environments"]["production"];
+        let served = config["flags"]
+            .as_array()
+            .and_then(|flags| flags.iter().find(|f| f["key"] == key.as_str()))
+            .unwrap_or_else(|| panic!("{key} missing from /v1/flags: {config}"));
+
+        assert_eq!(managed["enabled"], production["enabled"], "{key}");
+        assert_eq!(served["value"]["enabled"], production["enabled"], "{key}");
+        assert_eq!(
+            served["value"]["rollout_percentage"], production["rollout"],
+            "{key}"
+        );
+        assert_eq!(
+            served["default_enabled"], managed["default_enabled"],
+            "{key}"
+        );
+        assert_eq!(served["expires_at"], managed["expires_at"], "{key}");
+    }
+
+    // Fully rolled out and off flags evaluate the same for every user
+    for (key, expected) in [(&on, true), (&off, false)] {
+        let evaluation = api
+            .sdk(&format!("/v1/flags/{key}/evaluate?user_id=user-1"))
+            .await;
+        assert_eq!(evaluation["enabled"], expected, "{key}");
+    }
+
+    // The plain-text check answers like the JSON evaluation
+    for key in [&on, &partial, &off] {
+        let evaluation = api
+            .sdk(&format!("/v1/flags/{key}/evaluate?user_id=user-1"))
+            .await;
+        let path = format!("/v1/flags/{key}/enabled?user_id=user-1");
+        let response = api.sdk_raw(Method::GET, &path).await;
+        let headers = response.headers().clone();
+        let text = response.text().await.expect("Failed to read response");
+        assert_eq!(text, evaluation["enabled"].to_string(), "{key}");
+        assert_eq!(headers["content-type"], "text/plain", "{key}");
+        assert!(
+            headers["cache-control"]
+                .to_str()
+                .unwrap()
+                .contains("max-age="),
+            "{key}"
+        );
+
+        let head = api.sdk_raw(Method::HEAD, &path).await;
+        assert_eq!(head.headers()["x-flag-enabled"], text.as_str(), "{key}");
+    }
+
+    // Without a user ID a partial rollout is random, so it isn't cached
+    let response = api
+        .sdk_raw(Method::GET, &format!("/v1/flags/{partial}/enabled"))
+        .await;
+    assert_eq!(response.headers()["cache-control"], "no-store");
+}
+
+/// Test that the documented version 1 shapes still match the pinned ones.
+#[tokio::test]
+async fn test_v1_shapes_are_pinned() {
+    let harness = TestHarness::new("contract_v1_pinned")
+        .await
+        .expect("Failed to create test harness");
+    let contract = Contract::fetch(&harness.server_url).await;
+
+    let pinned: Value = serde_json::from_str(include_str!("fixtures/api-v1.json"))
+        .expect("Invalid fixtures/api-v1.json");
+    let hint = "version 1 shapes are pinned: make the change in a new API version (see versioning.rs) instead";
+
+    for (endpoint, shape) in pinned["endpoints"].as_object().expect("No endpoints") {
+        let (method, path) = endpoint.split_once(' ').expect("Expected 'METHOD path'");
+        assert_eq!(contract.response(method, path), shape, "{endpoint}: {hint}");
+    }
+    for (name, shape) in pinned["schemas"].as_object().expect("No schemas") {
+        assert_eq!(contract.schema(name), Some(shape), "Schema {name}: {hint}");
+    }
+}
+
+/// Test that clients get the API version they ask for, and version 1 otherwise.
+#[tokio::test]
+async fn test_api_versions() {
+    let harness = TestHarness::new("contract_versions")
+        .await
+        .expect("Failed to create test harness");
+    let contract = Contract::fetch(&harness.server_url).await;
+    let api = Api::signup(&harness, &contract).await;
+    let key = unique_flag_key();
+
+    api.user(
+        Method::POST,
+        &format!("/v1/projects/{}/flags", api.project_id),
+        Some(json!({ "key": key, "name": key })),
+    )
+    .await;
+    api.user(
+        Method::PUT,
+        &format!("/v1/projects/{}/flags/{key}/state", api.project_id),
+        Some(json!({ "enabled": true, "environment": "staging", "reason": "launch" })),
+    )
+    .await;
+
+    let get = |version: &str, accept: Option<&str>| {
+        let mut request = api
+            .client
+            .get(format!(
+                "{}/{version}/projects/{}/flags/{key}",
+                api.server_url, api.project_id
+            ))
+            .bearer_auth(&api.token);
+        if let Some(accept) = accept {
+            request = request.header("Accept", accept);
+        }
+        async move {
+            let response = request.send().await.expect("Request failed");
+            let status = response.status();
+            let version = response
+                .headers()
+                .get("x-flaglite-api-version")
+                .map(|v| v.to_str().unwrap().to_string());
+            let body: Value = response.json().await.expect("Invalid JSON");
+            (status, version, body)
+        }
+    };
+
+    // Version 1 by default, exactly as documented
+    let (_, version, v1) = get("v1", None).await;
+    assert_eq!(version.as_deref(), Some("1"));
+    contract.assert_matches("GET", "/v1/projects/{project_id}/flags/{key}", &v1);
+    assert!(v1["environments"]["staging"].get("reason").is_none());
+
+    // Version 2 by path or by Accept header
+    let (_, version, v2) = get("v2", None).await;
+    assert_eq!(version.as_deref(), Some("2"));
+    assert_eq!(v2["environments"]["staging"]["reason"], "launch");
+    assert!(v2["environments"]["staging"]["updated_at"].is_string());
+    assert!(v2["environments"]["production"]["reason"].is_null());
+
+    let (_, version, accepted) = get("v1", Some("application/vnd.flaglite.v2+json")).await;
+    assert_eq!(version.as_deref(), Some("2"));
+    assert_eq!(accepted, v2);
+
+    // Apart from the changes, the versions agree
+    let mut downgraded = v2.clone();
+    for state in downgraded["environments"]
+        .as_object_mut()
+        .unwrap()
+        .values_mut()
+    {
+        let state = state.as_object_mut().unwrap();
+        state.remove("reason");
+        state.remove("updated_at");
+    }
+    assert_eq!(downgraded, v1);
+
+    let (status, _, _) = get("v1", Some("application/vnd.flaglite.v9+json")).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+}
@@ -7,7 +7,7 @@
 
 mod common;
 
-use common::{unique_flag_key, TestHarness, TEST_PASSWORD};
+use common::{find_available_port, unique_flag_key, TestHarness, TEST_PASSWORD};
 
 /// Helper to setup a user with a selected project.
 async fn setup_user_with_project(harness: &TestHarness, name: &str) -> common::TestUser {
@@ -94,6 +94,65 @@ async fn test_get_flag() {
     assert_eq!(flag.name, "Get Test Flag");
 }
 
+/// Test showing a flag in another environment and in all environments at once.
+#[tokio::test]
+async fn test_get_flag_environments() {
+    let harness = TestHarness::new("get_flag_environments")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "chloe").await;
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("flags create failed");
+    user.exec(&[
+        "flags",
+        "rollout",
+        &flag_key,
+        "--percentage",
+        "30",
+        "-e",
+        "staging",
+    ])
+    .success_or_err("flags rollout")
+    .expect("flags rollout failed");
+    user.exec(&["flags", "enable", &flag_key, "-e", "staging"])
+        .success_or_err("flags enable")
+        .expect("flags enable failed");
+
+    // -e overrides the configured environment for one invocation
+    let output = user
+        .exec(&["flags", "get", &flag_key, "-e", "staging"])
+        .success()
+        .expect("flags get -e failed");
+    assert!(output.contains("ENABLED"), "stdout: {output}");
+    assert!(output.contains("in staging"), "stdout: {output}");
+
+    let json = user
+        .exec_json(&["flags", "get", &flag_key, "--all-envs"])
+        .success()
+        .expect("flags get --all-envs failed");
+    let states: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    let states = states.as_array().expect("Expected an array");
+    let names: Vec<_> = states
+        .iter()
+        .map(|s| s["environment"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["development", "staging", "production"]);
+    assert_eq!(states[1]["enabled"], true);
+    assert_eq!(states[1]["rollout"], 30);
+    assert_eq!(states[2]["enabled"], false);
+
+    let csv = user
+        .exec(&["flags", "get", &flag_key, "--all-envs", "--format", "csv"])
+        .success()
+        .expect("flags get --all-envs --format csv failed");
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines[0], "environment,enabled,rollout,value");
+    assert_eq!(lines[2], "staging,true,30,");
+}
+
 /// Test toggling a flag.
 #[tokio::test]
 async fn test_toggle_flag() {
@@ -139,157 +198,2347 @@ async fn test_toggle_flag() {
     );
 }
 
-/// Test getting a non-existent flag returns error.
+/// Test that production requires explicit confirmation to toggle.
 #[tokio::test]
-async fn test_get_nonexistent_flag() {
-    let harness = TestHarness::new("get_nonexistent_flag")
+async fn test_toggle_protected_environment() {
+    let harness = TestHarness::new("toggle_protected")
         .await
         .expect("Failed to create test harness");
 
-    let user = setup_user_with_project(&harness, "eve").await;
+    let user = setup_user_with_project(&harness, "peggy").await;
 
-    let result = user.flags_get("nonexistent_flag_key");
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("flags create failed");
+
+    // Unconfirmed change is rejected with the validation exit code
+    let result = user.exec(&["flags", "toggle", &flag_key, "-e", "production"]);
     assert!(
-        result.is_err(),
-        "flags get should fail for nonexistent flag"
+        result.failed(),
+        "Toggle in production should need confirmation"
+    );
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    let result = user.exec(&[
+        "flags",
+        "toggle",
+        &flag_key,
+        "-e",
+        "production",
+        "--confirm-production",
+    ]);
+    assert!(
+        result.succeeded(),
+        "Confirmed toggle failed: {}",
+        result.stderr()
+    );
+
+    // Once unprotected, no confirmation is needed
+    user.exec(&["envs", "unprotect", "production"])
+        .success_or_err("envs unprotect")
+        .expect("envs unprotect failed");
+    let result = user.exec(&["flags", "toggle", &flag_key, "-e", "production"]);
+    assert!(
+        result.succeeded(),
+        "Toggle after unprotect failed: {}",
+        result.stderr()
     );
 }
 
-/// Test creating flags with different types.
-/// Note: The API currently stores all flags as boolean internally.
-/// This test verifies that flag creation works with different type arguments.
+/// Test that a project can require a reason for production changes, and
+/// that the reason is kept in the flag's history.
 #[tokio::test]
-async fn test_create_flag_types() {
-    let harness = TestHarness::new("create_flag_types")
+async fn test_change_reason_required() {
+    let harness = TestHarness::new("change_reason")
         .await
         .expect("Failed to create test harness");
 
-    let user = setup_user_with_project(&harness, "frank").await;
+    let user = setup_user_with_project(&harness, "rupert").await;
 
-    let types = [
-        ("boolean", "bool"),
-        ("string", "str"),
-        ("number", "num"),
-        ("json", "json"),
-    ];
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("flags create failed");
 
-    for (flag_type, suffix) in types {
-        let flag_key = format!("typed_flag_{}_{}", suffix, unique_flag_key());
-        let result = user.flags_create(
-            &flag_key,
-            Some(&format!("{flag_type} Flag")),
-            Some(flag_type),
-            false,
-        );
+    user.exec(&["projects", "update", "--require-change-reason", "true"])
+        .success_or_err("projects update")
+        .expect("projects update failed");
+
+    // Confirmed but without a reason
+    let result = user.exec(&[
+        "flags",
+        "enable",
+        &flag_key,
+        "-e",
+        "production",
+        "--confirm-production",
+    ]);
+    assert!(result.failed(), "Change without a reason should fail");
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+    assert!(
+        result.stderr().contains("--reason"),
+        "stderr: {}",
+        result.stderr()
+    );
+
+    let result = user.exec(&[
+        "flags",
+        "enable",
+        &flag_key,
+        "-e",
+        "production",
+        "--confirm-production",
+        "--reason",
+        "Launch day",
+    ]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+
+    // Unprotected environments don't need one
+    let result = user.exec(&["flags", "toggle", &flag_key, "-e", "staging"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+
+    let result = user.exec_json(&["flags", "history", &flag_key, "-e", "production"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let history: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(history["versions"][0]["reason"], "Launch day");
+}
+
+/// Test that a frozen environment rejects flag changes until unfrozen.
+#[tokio::test]
+async fn test_frozen_environment_rejects_changes() {
+    let harness = TestHarness::new("frozen_env")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "frieda").await;
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("flags create failed");
+
+    user.exec(&["envs", "freeze", "staging"])
+        .success_or_err("envs freeze")
+        .expect("envs freeze failed");
 
+    let result = user.exec_json(&["envs", "list"]);
+    let envs: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    let staging = envs
+        .as_array()
+        .and_then(|envs| envs.iter().find(|e| e["name"] == "staging"))
+        .expect("No staging environment");
+    assert_eq!(staging["frozen"], true);
+
+    for args in [
+        vec!["flags", "toggle", flag_key.as_str(), "-e", "staging"],
+        vec!["flags", "enable", flag_key.as_str(), "-e", "staging"],
+        vec!["flags", "delete", flag_key.as_str(), "--yes"],
+    ] {
+        let result = user.exec(&args);
+        assert_eq!(result.exit_code(), Some(4), "{args:?}: {}", result.stderr());
         assert!(
-            result.is_ok(),
-            "flags create for {} failed: {:?}",
-            flag_type,
-            result.err()
+            result.stderr().contains("frozen"),
+            "{args:?}: {}",
+            result.stderr()
         );
+    }
 
-        // Verify flag was created with correct key
-        let flag = result.unwrap();
-        assert_eq!(flag.key, flag_key);
+    // Other environments are unaffected
+    let result = user.exec(&["flags", "toggle", &flag_key, "-e", "development"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
 
-        // Note: API currently returns all flags as "boolean" type
-        // regardless of the requested type. This is a known limitation.
-        // Once the API supports flag types properly, update this assertion:
-        // assert_eq!(flag.flag_type.to_lowercase(), flag_type);
-    }
+    user.exec(&["envs", "unfreeze", "staging"])
+        .success_or_err("envs unfreeze")
+        .expect("envs unfreeze failed");
+    let result = user.exec(&["flags", "toggle", &flag_key, "-e", "staging"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
 }
 
-/// Test creating multiple flags in same project.
+/// Test that enable/disable set an explicit state and honour expected versions.
 #[tokio::test]
-async fn test_create_multiple_flags() {
-    let harness = TestHarness::new("create_multiple_flags")
+async fn test_enable_disable_flag() {
+    let harness = TestHarness::new("enable_disable_flag")
         .await
         .expect("Failed to create test harness");
 
-    let user = setup_user_with_project(&harness, "grace").await;
+    let user = setup_user_with_project(&harness, "rita").await;
 
-    // Create 5 flags
-    let mut created_keys = Vec::new();
-    for i in 0..5 {
-        let flag_key = format!("multi_flag_{}_{}", i, unique_flag_key());
-        user.flags_create(&flag_key, Some(&format!("Flag {i}")), None, i % 2 == 0)
-            .expect("flags create failed");
-        created_keys.push(flag_key);
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("flags create failed");
+
+    // Enabling twice leaves the flag enabled, unlike toggle
+    for _ in 0..2 {
+        user.exec(&["flags", "enable", &flag_key, "-e", "development"])
+            .success_or_err("flags enable")
+            .expect("flags enable failed");
     }
+    let json = user
+        .exec_json(&["flags", "get", &flag_key, "-e", "development"])
+        .success()
+        .expect("flags get failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+    assert_eq!(flag["enabled"], true);
+    let version = flag["environments"]["development"]["version"]
+        .as_i64()
+        .expect("Missing version");
 
-    // List and verify all exist
-    let flags = user.flags_list().expect("flags list failed");
+    // A stale version is rejected without changing the flag
+    let stale = (version - 1).to_string();
+    let result = user.exec(&[
+        "flags",
+        "disable",
+        &flag_key,
+        "-e",
+        "development",
+        "--expected-version",
+        &stale,
+    ]);
+    assert!(result.failed(), "Stale expected version should conflict");
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
 
-    for key in &created_keys {
-        let found = flags.iter().any(|f| f.key == *key);
-        assert!(found, "Flag {key} not found in list");
-    }
+    let current = version.to_string();
+    user.exec(&[
+        "flags",
+        "disable",
+        &flag_key,
+        "-e",
+        "development",
+        "--expected-version",
+        &current,
+    ])
+    .success_or_err("flags disable")
+    .expect("flags disable failed");
+
+    let info = user.flags_get(&flag_key).expect("flags get failed");
+    assert!(!info.enabled, "Flag should be disabled");
 }
 
-/// Test flags are isolated between projects.
+/// Test that a toggle based on a stale version is rejected as a conflict.
 #[tokio::test]
-async fn test_flags_isolated_between_projects() {
-    let harness = TestHarness::new("flags_isolated")
+async fn test_toggle_with_stale_version_conflicts() {
+    let harness = TestHarness::new("toggle_stale_version")
         .await
         .expect("Failed to create test harness");
 
-    let user = harness.create_user("henry");
-    user.signup(None, TEST_PASSWORD).expect("Signup failed");
-
-    // Get initial project
-    let projects = user.projects_list().expect("Projects list failed");
-    let project1_id = projects[0].id.clone();
+    let user = setup_user_with_project(&harness, "sam").await;
 
-    // Select project 1 and create a flag
-    user.projects_use(&project1_id)
-        .expect("Projects use failed");
     let flag_key = unique_flag_key();
-    user.flags_create(&flag_key, Some("Project 1 Flag"), None, true)
+    user.flags_create(&flag_key, None, None, false)
         .expect("flags create failed");
 
-    // Create a new project
-    let project2 = user
-        .projects_create("Second Project", None)
-        .expect("Projects create failed");
+    // Version 1 on creation; this toggle moves it to 2
+    user.exec(&["flags", "toggle", &flag_key, "--expected-version", "1"])
+        .success_or_err("flags toggle")
+        .expect("flags toggle failed");
 
-    // Select project 2
-    user.projects_use(&project2.id)
-        .expect("Projects use failed");
+    // A second writer still holding version 1 must not clobber the change
+    let result = user.exec(&["flags", "toggle", &flag_key, "--expected-version", "1"]);
+    assert!(result.failed(), "Stale toggle should conflict");
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+    assert!(
+        result.stderr().contains("Conflict"),
+        "Expected conflict error, got: {}",
+        result.stderr()
+    );
 
-    // List flags in project 2 - should not contain the flag from project 1
-    let project2_flags = user.flags_list().expect("flags list failed");
-    let has_flag = project2_flags.iter().any(|f| f.key == flag_key);
+    let info = user.flags_get(&flag_key).expect("flags get failed");
+    assert!(info.enabled, "First toggle should have stuck");
+}
+
+/// Test that invalid flag input reports every rejected field.
+#[tokio::test]
+async fn test_create_flag_reports_invalid_fields() {
+    let harness = TestHarness::new("create_flag_invalid_fields")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "quinn").await;
+
+    let long_name = "x".repeat(300);
+    let result = user.exec(&["flags", "create", "bad key!", "--name", &long_name]);
+    assert!(result.failed(), "Invalid flag should be rejected");
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    let stderr = result.stderr();
+    assert!(stderr.contains("key:"), "Missing key error: {stderr}");
+    assert!(stderr.contains("name:"), "Missing name error: {stderr}");
+
+    // JSON output lists the fields for scripts
+    let result = user.exec_json(&["flags", "create", "bad key!"]);
+    let json: serde_json::Value =
+        serde_json::from_str(&result.stdout()).expect("Invalid error JSON");
+    assert_eq!(json["fields"][0]["field"], "key");
+    assert_eq!(json["fields"][0]["constraint"], "charset");
+}
 
+/// Test that expiry dates must be in the future and show up in flag output.
+#[tokio::test]
+async fn test_create_flag_with_expiry() {
+    let harness = TestHarness::new("create_flag_expiry")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "ruth").await;
+
+    let result = user.exec(&[
+        "flags",
+        "create",
+        &unique_flag_key(),
+        "--expires",
+        "2020-01-01",
+    ]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
     assert!(
-        !has_flag,
-        "Flag from project 1 should not be visible in project 2"
+        result.stderr().contains("expires_at:"),
+        "stderr: {}",
+        result.stderr()
     );
+
+    let flag_key = unique_flag_key();
+    let result = user.exec_json(&["flags", "create", &flag_key, "--expires", "2099-01-01"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let json: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    assert_eq!(json["expires_at"], "2099-01-01T00:00:00Z");
+    assert_eq!(json["expired"], false);
+
+    // Nothing has expired yet
+    let result = user.exec_json(&["flags", "expired"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    assert_eq!(result.stdout().trim(), "[]");
 }
 
-/// Test flag with duplicate key is rejected.
+/// Test setting a flag's owner and code references and filtering by them.
 #[tokio::test]
-async fn test_create_duplicate_flag_rejected() {
-    let harness = TestHarness::new("duplicate_flag")
+async fn test_flag_ownership_metadata() {
+    let harness = TestHarness::new("flag_ownership")
         .await
         .expect("Failed to create test harness");
 
-    let user = setup_user_with_project(&harness, "ivy").await;
+    let user = setup_user_with_project(&harness, "otto").await;
 
-    let flag_key = unique_flag_key();
+    let owned = unique_flag_key();
+    let result = user.exec_json(&[
+        "flags",
+        "create",
+        &owned,
+        "--owner",
+        "payments",
+        "--repo",
+        "acme/web",
+        "--code-path",
+        "src/checkout.rs",
+    ]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let json: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    assert_eq!(json["owner"], "payments");
+    assert_eq!(json["code_path"], "src/checkout.rs");
 
-    // Create first flag
-    let result1 = user.flags_create(&flag_key, Some("First Flag"), None, true);
+    let other = unique_flag_key();
+    let result = user.exec(&["flags", "create", &other]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+
+    // Owner filter is case-insensitive
+    let result = user.exec_json(&["flags", "list", "--owner", "Payments"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let json: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    let keys: Vec<&str> = json
+        .as_array()
+        .expect("Expected array")
+        .iter()
+        .filter_map(|f| f["key"].as_str())
+        .collect();
+    assert_eq!(keys, vec![owned.as_str()]);
+
+    // Hand the other flag to a team; an empty value clears a field
+    let result = user.exec_json(&["flags", "update", &other, "--owner", "growth"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let result = user.exec_json(&["flags", "update", &owned, "--code-path", ""]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+
+    let result = user.exec_json(&["flags", "get", &owned]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let json: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    assert_eq!(json["owner"], "payments");
+    assert_eq!(json["repository"], "acme/web");
+    assert!(json["code_path"].is_null());
+
+    let result = user.exec_json(&["flags", "list", "--owner", "growth"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    assert!(result.stdout().contains(&other));
+    assert!(!result.stdout().contains(&owned));
+}
+
+/// Test that json flag values are checked against the flag's JSON Schema.
+#[tokio::test]
+async fn test_json_flag_schema_validation() {
+    let harness = TestHarness::new("json_flag_schema")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "sasha").await;
+
+    let schema_path = harness.test_dir().join("banner.schema.json");
+    std::fs::write(
+        &schema_path,
+        r#"{"type": "object", "properties": {"text": {"type": "string"}, "max_views": {"type": "integer", "minimum": 1}}, "required": ["text"]}"#,
+    )
+    .expect("Failed to write schema");
+    let schema_path = schema_path.to_str().expect("Non-UTF-8 temp path");
+
+    // A schema only makes sense on a json flag
+    let result = user.exec(&[
+        "flags",
+        "create",
+        &unique_flag_key(),
+        "--schema",
+        schema_path,
+    ]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    let key = unique_flag_key();
+    let result = user.exec_json(&[
+        "flags",
+        "create",
+        &key,
+        "--flag-type",
+        "json",
+        "--schema",
+        schema_path,
+    ]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let json: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    assert_eq!(json["flag_type"], "json");
+    assert_eq!(json["json_schema"]["required"][0], "text");
+
+    // Each violation is reported with its path, and nothing is saved
+    let result = user.exec_json(&[
+        "flags",
+        "enable",
+        &key,
+        "--value",
+        r#"{"text": 5, "max_views": 0}"#,
+    ]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+    // JSON errors go to stdout
     assert!(
-        result1.is_ok(),
-        "First flag create failed: {:?}",
-        result1.err()
+        result.stdout().contains("value/text"),
+        "stdout: {}",
+        result.stdout()
+    );
+    assert!(
+        result.stdout().contains("value/max_views"),
+        "stdout: {}",
+        result.stdout()
     );
 
-    // Try to create second flag with same key
-    let result2 = user.flags_create(&flag_key, Some("Duplicate Flag"), None, false);
+    let result = user.exec_json(&["flags", "get", &key]);
+    let json: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    assert_eq!(json["enabled"], false);
+    assert!(json["value"].is_null());
+
+    let result = user.exec_json(&[
+        "flags",
+        "enable",
+        &key,
+        "--value",
+        r#"{"text": "Spring sale", "max_views": 3}"#,
+    ]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let json: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    assert_eq!(json["enabled"], true);
+    assert_eq!(json["value"]["text"], "Spring sale");
+}
+
+/// Test that flag defaults are set at creation, updated, and validated.
+#[tokio::test]
+async fn test_flag_default_value() {
+    let harness = TestHarness::new("flag_default_value")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "noor").await;
+
+    let key = unique_flag_key();
+    let result = user.exec_json(&[
+        "flags",
+        "create",
+        &key,
+        "--flag-type",
+        "json",
+        "--default-on",
+        "--default-value",
+        r#"{"text": "Welcome"}"#,
+    ]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let json: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    assert_eq!(json["default_enabled"], true);
+    assert_eq!(json["default_value"]["text"], "Welcome");
+
+    let result = user.exec(&[
+        "flags",
+        "create",
+        &unique_flag_key(),
+        "--default-value",
+        "{",
+    ]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    let result = user.exec_json(&[
+        "flags",
+        "update",
+        &key,
+        "--default-off",
+        "--clear-default-value",
+    ]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let json: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    assert_eq!(json["default_enabled"], false);
+    assert!(json["default_value"].is_null());
+
+    let result = user.exec(&["flags", "update", &key, "--default-on", "--default-off"]);
     assert!(
-        result2.is_err(),
-        "Second flag create should have failed for duplicate key"
+        result.failed(),
+        "--default-on and --default-off should conflict"
+    );
+}
+
+/// Test that flags created from a template start in its per-environment state.
+#[tokio::test]
+async fn test_flag_templates() {
+    let harness = TestHarness::new("flag_templates")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "ines").await;
+
+    let json = user
+        .exec_json(&["templates", "list"])
+        .success()
+        .expect("templates list failed");
+    let templates: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    let names: Vec<&str> = templates
+        .as_array()
+        .expect("Expected an array")
+        .iter()
+        .filter_map(|t| t["name"].as_str())
+        .collect();
+    assert_eq!(names, ["experiment", "kill-switch"]);
+
+    let key = unique_flag_key();
+    user.exec(&["flags", "create", &key, "--template", "experiment"])
+        .success_or_err("flags create --template")
+        .expect("flags create --template failed");
+    let json = user
+        .exec_json(&["flags", "get", &key])
+        .success()
+        .expect("flags get failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+    assert_eq!(flag["environments"]["development"]["enabled"], true);
+    assert_eq!(flag["environments"]["development"]["rollout"], 100);
+    assert_eq!(flag["environments"]["production"]["enabled"], true);
+    assert_eq!(flag["environments"]["production"]["rollout"], 0);
+
+    // A stored template, with a default for environments it doesn't name
+    user.exec(&[
+        "templates",
+        "set",
+        "release",
+        "--default",
+        "on:10%",
+        "--state",
+        "production=off",
+    ])
+    .success_or_err("templates set")
+    .expect("templates set failed");
+    let key = unique_flag_key();
+    user.exec(&["flags", "create", &key, "--template", "release"])
+        .success_or_err("flags create --template")
+        .expect("flags create --template failed");
+    let json = user
+        .exec_json(&["flags", "get", &key])
+        .success()
+        .expect("flags get failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+    assert_eq!(flag["default_enabled"], true);
+    assert_eq!(flag["environments"]["development"]["rollout"], 10);
+    assert_eq!(flag["environments"]["production"]["enabled"], false);
+
+    let result = user.exec(&[
+        "templates",
+        "set",
+        "release",
+        "--state",
+        "production=sometimes",
+    ]);
+    assert!(result.failed(), "An invalid state should be rejected");
+
+    user.exec(&["templates", "delete", "release"])
+        .success_or_err("templates delete")
+        .expect("templates delete failed");
+    let result = user.exec(&[
+        "flags",
+        "create",
+        &unique_flag_key(),
+        "--template",
+        "release",
+    ]);
+    assert!(result.failed(), "A deleted template should not be found");
+    let result = user.exec(&["templates", "delete", "experiment"]);
+    assert!(result.failed(), "Built-in templates can't be deleted");
+}
+
+/// Test that assignment exports are stable CSV, one row per user.
+#[tokio::test]
+async fn test_flag_assignments_export() {
+    let harness = TestHarness::new("flag_assignments")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "ada").await;
+
+    let key = unique_flag_key();
+    user.flags_create(&key, None, None, true)
+        .expect("flags create failed");
+
+    let users_path = harness.test_dir().join("users.txt");
+    std::fs::write(&users_path, "user-1\nuser-2\n\nuser-3\n").expect("Failed to write users");
+    let users_path = users_path.to_str().expect("Non-UTF-8 temp path");
+
+    let result = user.exec(&["flags", "assignments", &key, "--users-file", users_path]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let csv = result.stdout();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines[0], "user_id,bucket,enabled,reason,value");
+    assert_eq!(lines.len(), 4, "stdout: {csv}");
+    assert!(lines[1].starts_with("user-1,"));
+    assert!(lines[3].ends_with(",true,full_rollout,"), "stdout: {csv}");
+
+    // Buckets are deterministic
+    let again = user.exec(&["flags", "assignments", &key, "--users-file", users_path]);
+    assert_eq!(again.stdout(), csv);
+
+    let result = user.exec(&[
+        "flags",
+        "assignments",
+        &key,
+        "--users-file",
+        "/nonexistent/users.txt",
+    ]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+}
+
+/// Test that evaluation fixtures cover the requested samples and regenerate identically.
+#[tokio::test]
+async fn test_flag_evaluation_fixtures() {
+    let harness = TestHarness::new("flag_fixtures")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "barbara").await;
+
+    let key = unique_flag_key();
+    user.flags_create(&key, None, None, true)
+        .expect("flags create failed");
+    user.exec(&["flags", "rollout", &key, "--percentage", "30"])
+        .success()
+        .expect("flags rollout failed");
+
+    let out = harness.test_dir().join("fixture.json");
+    let out = out.to_str().expect("Non-UTF-8 temp path");
+    let result = user.exec(&["flags", "fixtures", &key, "--samples", "500", "--out", out]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let written = std::fs::read_to_string(out).expect("Fixture wasn't written");
+    let fixture: serde_json::Value = serde_json::from_str(&written).expect("Invalid fixture");
+    assert_eq!(fixture["key"], key.as_str());
+    assert_eq!(fixture["rollout_percentage"], 30);
+    let assignments = fixture["assignments"].as_array().unwrap();
+    assert_eq!(assignments.len(), 500);
+    assert_eq!(assignments[0]["user_id"], "user-1");
+    let enabled = fixture["enabled"].as_u64().unwrap();
+    assert!((100..=200).contains(&enabled), "{enabled} of 500 enabled");
+    assert!(assignments
+        .iter()
+        .all(|a| { a["enabled"].as_bool().unwrap() == (a["bucket"].as_i64().unwrap() < 30) }));
+
+    // Without --out, the same fixture goes to stdout
+    let again = user.exec(&["flags", "fixtures", &key, "--samples", "500"]);
+    assert_eq!(again.stdout(), written.trim_end());
+}
+
+/// Test that allowlisted and denylisted users get their result whatever the rollout.
+#[tokio::test]
+async fn test_flag_allowlist_and_denylist() {
+    let harness = TestHarness::new("flag_user_lists")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "margaret").await;
+
+    let key = unique_flag_key();
+    user.flags_create(&key, None, None, true)
+        .expect("flags create failed");
+    user.exec(&["flags", "rollout", &key, "--percentage", "0"])
+        .success()
+        .expect("flags rollout failed");
+
+    let ids = harness.test_dir().join("ids.txt");
+    std::fs::write(&ids, "beta-2\n\nbeta-3\n").unwrap();
+    let ids = ids.to_str().expect("Non-UTF-8 temp path");
+    let result = user.exec(&[
+        "flags",
+        "allowlist",
+        "add",
+        &key,
+        "--user",
+        "beta-1",
+        "--file",
+        ids,
+    ]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+
+    let result = user.exec_json(&["flags", "allowlist", "list", &key]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let list: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(list["list"], "allowlist");
+    assert_eq!(
+        list["user_ids"],
+        serde_json::json!(["beta-1", "beta-2", "beta-3"])
     );
+
+    // Denylisting moves a user off the allowlist
+    user.exec(&["flags", "denylist", "add", &key, "--user", "beta-3"])
+        .success()
+        .expect("flags denylist add failed");
+
+    let users = harness.test_dir().join("users.txt");
+    std::fs::write(&users, "beta-1\nbeta-3\nsomeone\n").unwrap();
+    let users = users.to_str().expect("Non-UTF-8 temp path");
+    let result = user.exec(&["flags", "assignments", &key, "--users-file", users]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let rows: Vec<String> = result
+        .stdout()
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').take(4).collect::<Vec<_>>().join(","))
+        .collect();
+    assert_eq!(rows.len(), 3);
+    assert!(rows[0].starts_with("beta-1,") && rows[0].ends_with(",true,allowlisted"));
+    assert!(rows[1].starts_with("beta-3,") && rows[1].ends_with(",false,denylisted"));
+    assert!(rows[2].ends_with(",false,zero_rollout"), "{}", rows[2]);
+
+    user.exec(&["flags", "allowlist", "remove", &key, "--user", "beta-1"])
+        .success()
+        .expect("flags allowlist remove failed");
+    let result = user.exec_json(&["flags", "allowlist", "clear", &key]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let list: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(list["removed"], 1);
+    assert_eq!(list["user_ids"], serde_json::json!([]));
+
+    // Nothing to change
+    let result = user.exec(&["flags", "denylist", "add", &key]);
+    assert!(!result.succeeded());
+}
+
+/// Test turning every matching flag off and setting their rollout in one batch.
+#[tokio::test]
+async fn test_bulk_toggle_and_rollout() {
+    let harness = TestHarness::new("bulk_toggle")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "grace").await;
+
+    for key in ["checkout-v1", "checkout-v2", "search-v1"] {
+        user.flags_create(key, None, None, true)
+            .expect("flags create failed");
+    }
+
+    // Without a terminal, the affected list can't be confirmed
+    let result = user.exec(&["flags", "toggle", "--match", "checkout-*", "--off"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+    assert!(user.flags_get("checkout-v1").unwrap().enabled);
+
+    let result = user.exec_json(&["flags", "toggle", "--match", "checkout-*", "--off", "--yes"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let resp: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(resp["flags"].as_array().unwrap().len(), 2);
+    assert!(!user.flags_get("checkout-v1").unwrap().enabled);
+    assert!(!user.flags_get("checkout-v2").unwrap().enabled);
+    assert!(user.flags_get("search-v1").unwrap().enabled);
+
+    // Repeating is a no-op
+    let result = user.exec_json(&["flags", "toggle", "--match", "checkout-*", "--off", "--yes"]);
+    let resp: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert!(resp["flags"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|f| f["changed"] == false));
+
+    let result = user.exec_json(&[
+        "flags",
+        "rollout",
+        "--match",
+        "^(checkout|search)-v1$",
+        "--regex",
+        "--percentage",
+        "25",
+        "--yes",
+    ]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let resp: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    let rollouts: Vec<_> = resp["flags"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|f| (f["key"].as_str().unwrap(), f["rollout"].as_i64().unwrap()))
+        .collect();
+    assert_eq!(rollouts, vec![("checkout-v1", 25), ("search-v1", 25)]);
+
+    // Protected environments still need confirmation
+    let result = user.exec(&[
+        "flags",
+        "toggle",
+        "--match",
+        "*",
+        "--on",
+        "--yes",
+        "-e",
+        "production",
+    ]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    let result = user.exec(&["flags", "toggle", "--match", "nothing-*", "--on", "--yes"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    let result = user.exec(&["flags", "toggle", "--match", "checkout-*", "--yes"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+}
+
+#[tokio::test]
+async fn test_flag_history_and_rollback() {
+    let harness = TestHarness::new("flag_rollback")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "heidi").await;
+    user.flags_create("new-checkout", None, None, true)
+        .expect("flags create failed");
+
+    let result = user.exec(&["flags", "rollout", "new-checkout", "--percentage", "25"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let result = user.exec(&["flags", "disable", "new-checkout"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+
+    let result = user.exec_json(&["flags", "history", "new-checkout"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let history: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    let versions: Vec<_> = history["versions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|v| {
+            (
+                v["version"].as_i64().unwrap(),
+                v["enabled"].as_bool().unwrap(),
+                v["rollout_percentage"].as_i64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        versions,
+        vec![(3, false, 25), (2, true, 25), (1, true, 100)]
+    );
+
+    // Without --to, the last change is undone
+    let result = user.exec_json(&["flags", "rollback", "new-checkout"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let resp: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(resp["restored_version"], 2);
+    assert_eq!(resp["version"], 4);
+    assert_eq!(resp["rollout_percentage"], 25);
+    assert!(user.flags_get("new-checkout").unwrap().enabled);
+
+    // A stale expected version is rejected
+    let result = user.exec(&[
+        "flags",
+        "rollback",
+        "new-checkout",
+        "--to",
+        "1",
+        "--expected-version",
+        "3",
+    ]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    let result = user.exec_json(&["flags", "rollback", "new-checkout", "--to", "1"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let resp: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(resp["version"], 5);
+    assert_eq!(resp["rollout_percentage"], 100);
+
+    let result = user.exec(&["flags", "rollback", "new-checkout", "--to", "99"]);
+    assert_eq!(result.exit_code(), Some(3), "stderr: {}", result.stderr());
+
+    // Protected environments still need confirmation
+    let result = user.exec(&["flags", "rollback", "new-checkout", "-e", "production"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+}
+
+/// History comes a page at a time, following `next_cursor`, or all at once as
+/// an NDJSON export.
+#[tokio::test]
+async fn test_flag_history_pages_and_export() {
+    let harness = TestHarness::new("flag_history_pages")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "ivan").await;
+    user.flags_create("new-checkout", None, None, true)
+        .expect("flags create failed");
+    for percentage in ["10", "20", "30", "40"] {
+        let result = user.exec(&[
+            "flags",
+            "rollout",
+            "new-checkout",
+            "--percentage",
+            percentage,
+        ]);
+        assert!(result.succeeded(), "stderr: {}", result.stderr());
+    }
+
+    let page = |cursor: Option<&str>| {
+        let mut args = vec!["flags", "history", "new-checkout", "--limit", "2"];
+        if let Some(cursor) = cursor {
+            args.extend(["--cursor", cursor]);
+        }
+        let result = user.exec_json(&args);
+        assert!(result.succeeded(), "stderr: {}", result.stderr());
+        let page: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+        let versions: Vec<i64> = page["versions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["version"].as_i64().unwrap())
+            .collect();
+        (versions, page["next_cursor"].as_str().map(str::to_string))
+    };
+    let (versions, cursor) = page(None);
+    assert_eq!(versions, vec![5, 4]);
+    let (versions, cursor) = page(cursor.as_deref());
+    assert_eq!(versions, vec![3, 2]);
+    let (versions, cursor) = page(cursor.as_deref());
+    assert_eq!(versions, vec![1]);
+    assert_eq!(cursor, None);
+
+    let result = user.exec(&["flags", "history", "new-checkout", "--export"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let lines: Vec<serde_json::Value> = result
+        .stdout()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[0]["key"], "new-checkout");
+    assert_eq!(lines[0]["environment"], "development");
+    assert_eq!(lines[0]["rollout_percentage"], 40);
+    assert_eq!(lines[4]["version"], 1);
+
+    let result = user.exec(&["flags", "history", "new-checkout", "--limit", "0"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+}
+
+/// Test getting a non-existent flag returns error.
+#[tokio::test]
+async fn test_get_nonexistent_flag() {
+    let harness = TestHarness::new("get_nonexistent_flag")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "eve").await;
+
+    let result = user.flags_get("nonexistent_flag_key");
+    assert!(
+        result.is_err(),
+        "flags get should fail for nonexistent flag"
+    );
+}
+
+/// Test the short command aliases and "did you mean" for mistyped keys.
+#[tokio::test]
+async fn test_aliases_and_key_suggestions() {
+    let harness = TestHarness::new("aliases_suggestions")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "tess").await;
+
+    let result = user.exec(&["f", "create", "dark-mode"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+
+    let result = user.exec_json(&["f", "ls"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    assert!(result.stdout().contains("dark-mode"));
+
+    let result = user.exec(&["t", "dark-mode"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    assert!(result.stdout().to_lowercase().contains("enabled"));
+
+    let result = user.exec(&["flags", "get", "drak-mode"]);
+    assert_eq!(result.exit_code(), Some(3), "stderr: {}", result.stderr());
+    assert!(
+        result.stderr().contains("Did you mean: dark-mode?"),
+        "stderr: {}",
+        result.stderr()
+    );
+    assert!(
+        result
+            .stderr()
+            .contains("run: flaglite flags get dark-mode"),
+        "stderr: {}",
+        result.stderr()
+    );
+
+    let result = user.exec_json(&["flags", "toggle", "drak-mode"]);
+    let json: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    assert_eq!(json["suggestions"], serde_json::json!(["dark-mode"]));
+    assert_eq!(
+        json["next_steps"][2]["command"],
+        "flaglite flags create drak-mode"
+    );
+
+    // Logged out, the error points at signing up too
+    let result = harness.create_user("tess-new").exec(&["flags", "list"]);
+    assert_eq!(result.exit_code(), Some(2), "stderr: {}", result.stderr());
+    assert!(
+        result.stderr().contains("run: flaglite signup"),
+        "stderr: {}",
+        result.stderr()
+    );
+
+    // Usage errors are invalid input, not an authentication failure
+    let result = user.exec(&["flags", "list", "--no-such-option"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+    let result = user.exec(&["flags", "--help"]);
+    assert_eq!(result.exit_code(), Some(0), "stderr: {}", result.stderr());
+}
+
+/// Test creating flags with different types.
+/// Note: The API currently stores all flags as boolean internally.
+/// This test verifies that flag creation works with different type arguments.
+#[tokio::test]
+async fn test_create_flag_types() {
+    let harness = TestHarness::new("create_flag_types")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "frank").await;
+
+    let types = [
+        ("boolean", "bool"),
+        ("string", "str"),
+        ("number", "num"),
+        ("json", "json"),
+    ];
+
+    for (flag_type, suffix) in types {
+        let flag_key = format!("typed_flag_{}_{}", suffix, unique_flag_key());
+        let result = user.flags_create(
+            &flag_key,
+            Some(&format!("{flag_type} Flag")),
+            Some(flag_type),
+            false,
+        );
+
+        assert!(
+            result.is_ok(),
+            "flags create for {} failed: {:?}",
+            flag_type,
+            result.err()
+        );
+
+        // Verify flag was created with correct key
+        let flag = result.unwrap();
+        assert_eq!(flag.key, flag_key);
+
+        // Note: API currently returns all flags as "boolean" type
+        // regardless of the requested type. This is a known limitation.
+        // Once the API supports flag types properly, update this assertion:
+        // assert_eq!(flag.flag_type.to_lowercase(), flag_type);
+    }
+}
+
+/// Test creating multiple flags in same project.
+#[tokio::test]
+async fn test_create_multiple_flags() {
+    let harness = TestHarness::new("create_multiple_flags")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "grace").await;
+
+    // Create 5 flags
+    let mut created_keys = Vec::new();
+    for i in 0..5 {
+        let flag_key = format!("multi_flag_{}_{}", i, unique_flag_key());
+        user.flags_create(&flag_key, Some(&format!("Flag {i}")), None, i % 2 == 0)
+            .expect("flags create failed");
+        created_keys.push(flag_key);
+    }
+
+    // List and verify all exist
+    let flags = user.flags_list().expect("flags list failed");
+
+    for key in &created_keys {
+        let found = flags.iter().any(|f| f.key == *key);
+        assert!(found, "Flag {key} not found in list");
+    }
+}
+
+/// Test flags are isolated between projects.
+#[tokio::test]
+async fn test_flags_isolated_between_projects() {
+    let harness = TestHarness::new("flags_isolated")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("henry");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    // Get initial project
+    let projects = user.projects_list().expect("Projects list failed");
+    let project1_id = projects[0].id.clone();
+
+    // Select project 1 and create a flag
+    user.projects_use(&project1_id)
+        .expect("Projects use failed");
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, Some("Project 1 Flag"), None, true)
+        .expect("flags create failed");
+
+    // Create a new project
+    let project2 = user
+        .projects_create("Second Project", None)
+        .expect("Projects create failed");
+
+    // Select project 2
+    user.projects_use(&project2.id)
+        .expect("Projects use failed");
+
+    // List flags in project 2 - should not contain the flag from project 1
+    let project2_flags = user.flags_list().expect("flags list failed");
+    let has_flag = project2_flags.iter().any(|f| f.key == flag_key);
+
+    assert!(
+        !has_flag,
+        "Flag from project 1 should not be visible in project 2"
+    );
+}
+
+/// Test flag with duplicate key is rejected.
+#[tokio::test]
+async fn test_create_duplicate_flag_rejected() {
+    let harness = TestHarness::new("duplicate_flag")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "ivy").await;
+
+    let flag_key = unique_flag_key();
+
+    // Create first flag
+    let result1 = user.flags_create(&flag_key, Some("First Flag"), None, true);
+    assert!(
+        result1.is_ok(),
+        "First flag create failed: {:?}",
+        result1.err()
+    );
+
+    // Try to create second flag with same key
+    let result2 = user.flags_create(&flag_key, Some("Duplicate Flag"), None, false);
+    assert!(
+        result2.is_err(),
+        "Second flag create should have failed for duplicate key"
+    );
+}
+
+/// Test `--sdk-port` serves evaluation on its own port and everything else
+/// only on the main one
+#[tokio::test]
+async fn test_sdk_port_splits_the_api() {
+    let sdk_port = find_available_port().expect("No free port").to_string();
+    let harness = TestHarness::with_env(
+        "sdk_port",
+        &[("SDK_PORT", &sdk_port), ("SDK_HOST", "127.0.0.1")],
+    )
+    .await
+    .expect("Failed to create test harness");
+    let sdk_url = format!("http://127.0.0.1:{sdk_port}");
+
+    // The CLI works against the management port as usual
+    let user = harness.create_user("sdk_port");
+    let info = user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("Flag create failed");
+
+    let client = reqwest::Client::new();
+    let status = |url: String| {
+        let client = client.clone();
+        let api_key = info.api_key.clone();
+        async move {
+            client
+                .get(url)
+                .bearer_auth(api_key)
+                .send()
+                .await
+                .expect("Request failed")
+                .status()
+                .as_u16()
+        }
+    };
+
+    let evaluate = format!("/v1/flags/{flag_key}/evaluate");
+    assert_eq!(status(format!("{sdk_url}{evaluate}")).await, 200);
+    assert_eq!(
+        status(format!("{}{evaluate}", harness.server_url)).await,
+        404
+    );
+
+    assert_eq!(status(format!("{sdk_url}/v1/projects")).await, 404);
+    assert_eq!(
+        status(format!("{}/v1/projects", harness.server_url)).await,
+        200
+    );
+
+    assert_eq!(status(format!("{sdk_url}/health")).await, 200);
+    assert_eq!(status(format!("{}/health", harness.server_url)).await, 200);
+}
+
+/// Test that SDKs polling /v1/changes get only the flags changed since their revision.
+#[tokio::test]
+async fn test_changes_since_revision() {
+    let harness = TestHarness::new("flag_changes")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("changes");
+    let info = user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+    let kept = unique_flag_key();
+    let removed = unique_flag_key();
+    for key in [&kept, &removed] {
+        user.flags_create(key, None, None, false)
+            .expect("Flag create failed");
+    }
+
+    let client = reqwest::Client::new();
+    let changes = |since: i64| {
+        let client = client.clone();
+        let url = format!("{}/v1/changes", harness.server_url);
+        let api_key = info.api_key.clone();
+        async move {
+            let changes: serde_json::Value = client
+                .get(url)
+                .bearer_auth(api_key)
+                .query(&[("since", since)])
+                .send()
+                .await
+                .expect("Changes request failed")
+                .json()
+                .await
+                .expect("Invalid changes JSON");
+            changes
+        }
+    };
+
+    let all = changes(0).await;
+    assert_eq!(all["full"], true, "Unexpected changes: {all}");
+    assert_eq!(all["flags"].as_array().map(Vec::len), Some(2));
+    let revision = all["revision"].as_i64().expect("Missing revision");
+
+    let none = changes(revision).await;
+    assert_eq!(none["full"], false);
+    assert_eq!(none["flags"].as_array().map(Vec::len), Some(0));
+    assert_eq!(none["revision"], revision);
+
+    user.exec(&["flags", "enable", &kept, "-e", "development"])
+        .success_or_err("flags enable")
+        .expect("flags enable failed");
+    user.exec(&["flags", "delete", &removed, "--yes"])
+        .success_or_err("flags delete")
+        .expect("flags delete failed");
+
+    let some = changes(revision).await;
+    assert_eq!(some["full"], false);
+    let keys: Vec<&str> = some["flags"]
+        .as_array()
+        .expect("Missing flags")
+        .iter()
+        .filter_map(|f| f["key"].as_str())
+        .collect();
+    assert_eq!(keys, [kept.as_str()]);
+    assert_eq!(some["deleted"][0], removed.as_str());
+    assert!(some["revision"].as_i64().unwrap_or(0) > revision);
+
+    // A revision the server never issued gets everything again
+    let reset = changes(revision + 1000).await;
+    assert_eq!(reset["full"], true);
+    assert_eq!(reset["flags"].as_array().map(Vec::len), Some(1));
+}
+
+/// Test the relay answers evaluations from upstream's flags, and keeps
+/// answering after upstream goes away.
+#[tokio::test]
+async fn test_relay_serves_last_known_flags() {
+    let mut harness = TestHarness::isolated("relay")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("relay");
+    let info = user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+
+    // The user API key evaluates against production
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("Flag create failed");
+    user.exec(&[
+        "flags",
+        "enable",
+        &flag_key,
+        "-e",
+        "production",
+        "--confirm-production",
+    ])
+    .success_or_err("flags enable")
+    .expect("flags enable failed");
+
+    let relay = harness
+        .start_relay(&info.api_key)
+        .await
+        .expect("Failed to start relay");
+
+    let client = reqwest::Client::new();
+    let evaluate = |key: &str| {
+        client
+            .get(format!("{}/v1/flags/{key}/evaluate", relay.url))
+            .bearer_auth(&info.api_key)
+            .send()
+    };
+
+    let body: serde_json::Value = evaluate(&flag_key)
+        .await
+        .expect("Relay request failed")
+        .json()
+        .await
+        .expect("Invalid evaluation JSON");
+    assert_eq!(body["enabled"], true, "Unexpected evaluation: {body}");
+
+    let resp = evaluate("no-such-flag")
+        .await
+        .expect("Relay request failed");
+    assert_eq!(resp.status().as_u16(), 404);
+
+    let resp = client
+        .get(format!("{}/v1/flags/{flag_key}/evaluate", relay.url))
+        .send()
+        .await
+        .expect("Relay request failed");
+    assert_eq!(resp.status().as_u16(), 401, "Relay must require the key");
+
+    // Upstream down: the relay keeps serving what it fetched last
+    harness.stop_server();
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+
+    let body: serde_json::Value = evaluate(&flag_key)
+        .await
+        .expect("Relay request failed")
+        .json()
+        .await
+        .expect("Invalid evaluation JSON");
+    assert_eq!(body["enabled"], true, "Unexpected evaluation: {body}");
+}
+
+/// Test that a proposed change only applies once another project admin approves it.
+#[tokio::test]
+async fn test_change_request_approval() {
+    let harness = TestHarness::new("change_request_approval")
+        .await
+        .expect("Failed to create test harness");
+
+    let owner = harness.create_user("olga");
+    let owner_info = owner.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let member = harness.create_user("mike");
+    let member_info = member.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    owner
+        .exec(&["orgs", "create", "Review"])
+        .success()
+        .expect("orgs create failed");
+    owner
+        .exec(&["orgs", "add-member", &member_info.username])
+        .success()
+        .expect("orgs add-member failed");
+    let project = owner
+        .projects_create(&common::unique_project_name(), None)
+        .expect("projects create failed");
+    for user in [&owner, &member] {
+        user.projects_use(&project.id).expect("Projects use failed");
+    }
+
+    let flag_key = unique_flag_key();
+    owner
+        .flags_create(&flag_key, None, None, false)
+        .expect("Flag create failed");
+
+    let propose = |user: &common::TestUser, args: &[&str]| -> serde_json::Value {
+        let mut full = vec!["-e", "production", "flags", "propose", flag_key.as_str()];
+        full.extend_from_slice(args);
+        let json = user
+            .exec_json(&full)
+            .success()
+            .expect("flags propose failed");
+        serde_json::from_str(&json).expect("Invalid change request JSON")
+    };
+    let production_state = || -> serde_json::Value {
+        let json = owner
+            .exec_json(&["-e", "production", "flags", "get", &flag_key])
+            .success()
+            .expect("flags get failed");
+        let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+        flag["environments"]["production"].clone()
+    };
+
+    let change = propose(
+        &member,
+        &[
+            "--enable",
+            "--percentage",
+            "25",
+            "-c",
+            "Launch to a quarter",
+        ],
+    );
+    let id = change["id"].as_str().unwrap().to_string();
+    assert_eq!(change["status"], "pending");
+    assert_eq!(change["proposed_by"], member_info.username.as_str());
+    assert_eq!(
+        production_state()["enabled"],
+        false,
+        "Nothing changes before approval"
+    );
+
+    let json = owner
+        .exec_json(&["change-requests", "list", "--status", "pending"])
+        .success()
+        .expect("change-requests list failed");
+    let pending: Vec<serde_json::Value> = serde_json::from_str(&json).expect("Invalid list JSON");
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0]["comment"], "Launch to a quarter");
+
+    // Members can't approve, and nobody approves their own request
+    let result = member.exec(&["change-requests", "approve", &id]);
+    assert!(result.failed(), "A member should not be able to approve");
+
+    let own = propose(&owner, &["--disable"]);
+    let own_id = own["id"].as_str().unwrap();
+    let result = owner.exec(&["change-requests", "approve", own_id]);
+    assert!(result.failed(), "Self-approval should be refused");
+    assert!(
+        result.stderr().contains("own change request"),
+        "Unexpected error: {}",
+        result.stderr()
+    );
+    owner
+        .exec(&["change-requests", "reject", own_id])
+        .success()
+        .expect("Withdrawing own request failed");
+
+    let json = owner
+        .exec_json(&["change-requests", "approve", &id, "--comment", "Go"])
+        .success()
+        .expect("change-requests approve failed");
+    let approved: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert_eq!(approved["status"], "approved");
+    assert_eq!(approved["reviewed_by"], owner_info.username.as_str());
+    let state = production_state();
+    assert_eq!(state["enabled"], true, "Approval should apply the change");
+    assert_eq!(state["rollout"], 25);
+
+    let result = owner.exec(&["change-requests", "approve", &id]);
+    assert!(
+        result.failed(),
+        "A reviewed request can't be approved again"
+    );
+
+    // A request made before another change to the flag is stale
+    let stale = propose(&member, &["--disable"]);
+    owner
+        .exec(&[
+            "-e",
+            "production",
+            "flags",
+            "rollout",
+            &flag_key,
+            "--percentage",
+            "50",
+            "--confirm-production",
+        ])
+        .success()
+        .expect("flags rollout failed");
+    let result = owner.exec(&["change-requests", "approve", stale["id"].as_str().unwrap()]);
+    assert!(result.failed(), "A stale request should not apply");
+    assert!(
+        result.stderr().contains("changed since"),
+        "Unexpected error: {}",
+        result.stderr()
+    );
+    assert_eq!(production_state()["enabled"], true);
+
+    member
+        .exec(&["change-requests", "reject", stale["id"].as_str().unwrap()])
+        .success()
+        .expect("change-requests reject failed");
+    let json = member
+        .exec_json(&["change-requests", "list"])
+        .success()
+        .expect("change-requests list failed");
+    let all: Vec<serde_json::Value> = serde_json::from_str(&json).expect("Invalid list JSON");
+    let statuses: Vec<&str> = all.iter().map(|c| c["status"].as_str().unwrap()).collect();
+    assert_eq!(statuses, ["rejected", "rejected", "approved"]);
+}
+
+/// Test that an environment requiring approval only takes changes through change requests.
+#[tokio::test]
+async fn test_environment_requires_approval() {
+    let harness = TestHarness::new("environment_requires_approval")
+        .await
+        .expect("Failed to create test harness");
+
+    let owner = harness.create_user("olga");
+    owner.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let member = harness.create_user("mike");
+    let member_info = member.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    owner
+        .exec(&["orgs", "create", "Regulated"])
+        .success()
+        .expect("orgs create failed");
+    owner
+        .exec(&["orgs", "add-member", &member_info.username])
+        .success()
+        .expect("orgs add-member failed");
+    let project = owner
+        .projects_create(&common::unique_project_name(), None)
+        .expect("projects create failed");
+    for user in [&owner, &member] {
+        user.projects_use(&project.id).expect("Projects use failed");
+    }
+
+    let flag_key = unique_flag_key();
+    owner
+        .flags_create(&flag_key, None, None, false)
+        .expect("Flag create failed");
+
+    // Only owners can turn the requirement on or off
+    let result = member.exec(&["envs", "approval", "production", "--required", "true"]);
+    assert!(
+        result.failed(),
+        "A member should not be able to require approval"
+    );
+    let json = owner
+        .exec_json(&["envs", "approval", "production", "--required", "true"])
+        .success()
+        .expect("envs approval failed");
+    let approval: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert_eq!(approval["required"], true);
+    let json = member
+        .exec_json(&["envs", "approval", "production"])
+        .success()
+        .expect("envs approval failed");
+    let approval: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert_eq!(approval["required"], true);
+
+    // Direct changes are refused, even for owners, with a pointer to proposing
+    for user in [&member, &owner] {
+        let result = user.exec(&[
+            "-e",
+            "production",
+            "flags",
+            "toggle",
+            &flag_key,
+            "--confirm-production",
+        ]);
+        assert!(result.failed(), "A direct change should be refused");
+        assert!(
+            result.stderr().contains("flags propose"),
+            "Unexpected error: {}",
+            result.stderr()
+        );
+    }
+    // Other environments are unaffected
+    member
+        .exec(&["-e", "development", "flags", "toggle", &flag_key])
+        .success()
+        .expect("flags toggle in development failed");
+
+    let json = member
+        .exec_json(&[
+            "-e",
+            "production",
+            "flags",
+            "propose",
+            &flag_key,
+            "--enable",
+        ])
+        .success()
+        .expect("flags propose failed");
+    let change: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    owner
+        .exec(&["change-requests", "approve", change["id"].as_str().unwrap()])
+        .success()
+        .expect("change-requests approve failed");
+    let json = owner
+        .exec_json(&["-e", "production", "flags", "get", &flag_key])
+        .success()
+        .expect("flags get failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+    assert_eq!(
+        flag["environments"]["production"]["enabled"], true,
+        "Approval should apply the change"
+    );
+
+    owner
+        .exec(&["envs", "approval", "production", "--required", "false"])
+        .success()
+        .expect("envs approval failed");
+    member
+        .exec(&[
+            "-e",
+            "production",
+            "flags",
+            "toggle",
+            &flag_key,
+            "--confirm-production",
+        ])
+        .success()
+        .expect("Direct changes should work again");
+}
+
+type LiveSocket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// Next JSON text message on a live events socket, skipping pings.
+async fn next_event(socket: &mut LiveSocket) -> serde_json::Value {
+    use futures_util::StreamExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    loop {
+        let message = tokio::time::timeout(std::time::Duration::from_secs(5), socket.next())
+            .await
+            .expect("Timed out waiting for a message")
+            .expect("Socket closed")
+            .expect("Socket error");
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).expect("Invalid message JSON");
+        }
+    }
+}
+
+/// Test a WebSocket subscriber sees flag changes and audit events in its
+/// project, and nothing from before it subscribed.
+#[tokio::test]
+async fn test_websocket_live_events() {
+    use futures_util::SinkExt;
+    use tokio_tungstenite::tungstenite::Message;
+
+    let harness = TestHarness::new("websocket_live_events")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("live");
+    let info = user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    let project_id = projects[0].id.clone();
+    user.projects_use(&project_id).expect("Projects use failed");
+
+    let ws_url = format!("{}/v1/ws", harness.server_url.replacen("http", "ws", 1));
+    assert!(
+        tokio_tungstenite::connect_async(&ws_url).await.is_err(),
+        "The handshake must require a token"
+    );
+    let (mut socket, _) =
+        tokio_tungstenite::connect_async(format!("{ws_url}?token={}", info.api_key))
+            .await
+            .expect("WebSocket connect failed");
+
+    // Made before subscribing, so never delivered
+    let early = unique_flag_key();
+    user.flags_create(&early, None, None, false)
+        .expect("Flag create failed");
+
+    let send = |message: serde_json::Value| Message::Text(message.to_string());
+    socket
+        .send(send(serde_json::json!({"type": "ping"})))
+        .await
+        .expect("Send failed");
+    assert_eq!(next_event(&mut socket).await["type"], "pong");
+
+    socket
+        .send(send(
+            serde_json::json!({"type": "subscribe", "project_id": "no-such-project"}),
+        ))
+        .await
+        .expect("Send failed");
+    assert_eq!(next_event(&mut socket).await["type"], "error");
+
+    socket
+        .send(send(
+            serde_json::json!({"type": "subscribe", "project_id": project_id}),
+        ))
+        .await
+        .expect("Send failed");
+    let reply = next_event(&mut socket).await;
+    assert_eq!(reply["type"], "subscribed", "Unexpected reply: {reply}");
+    assert_eq!(reply["project_id"], project_id.as_str());
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("Flag create failed");
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "flag_changed", "Unexpected event: {event}");
+    assert_eq!(event["project_id"], project_id.as_str());
+    assert_eq!(event["keys"][0], flag_key.as_str());
+    assert_eq!(event["deleted"], false);
+
+    user.exec(&["envs", "freeze", "staging"])
+        .success_or_err("envs freeze")
+        .expect("envs freeze failed");
+    let event = next_event(&mut socket).await;
+    assert_eq!(event["type"], "audit", "Unexpected event: {event}");
+    assert_eq!(event["action"], "environment.frozen");
+    assert_eq!(event["actor"], info.username.as_str());
+}
+
+/// Test that `search` finds flags across projects, with their state.
+#[tokio::test]
+async fn test_search_flags_across_projects() {
+    let harness = TestHarness::new("search_flags")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "quinn").await;
+    let first = unique_flag_key();
+    user.flags_create(&first, None, None, false)
+        .expect("flags create failed");
+
+    let other = user
+        .projects_create("Billing", None)
+        .expect("projects create failed");
+    user.projects_use(&other.slug).expect("projects use failed");
+    let second = format!("{first}-v2");
+    user.flags_create(&second, None, None, false)
+        .expect("flags create failed");
+    user.exec(&["flags", "enable", &second, "-e", "staging"])
+        .success_or_err("flags enable")
+        .expect("flags enable failed");
+
+    let json = user
+        .exec_json(&["search", &first.to_uppercase()])
+        .success()
+        .expect("search failed");
+    let results: Vec<serde_json::Value> = serde_json::from_str(&json).expect("Invalid JSON");
+    let found: Vec<(&str, &str)> = results
+        .iter()
+        .map(|r| {
+            (
+                r["key"].as_str().unwrap(),
+                r["project_slug"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    // The exact key first, then the longer one in the other project
+    assert_eq!(found.len(), 2, "results: {results:?}");
+    assert_eq!(found[0].0, first);
+    assert_eq!(found[1], (second.as_str(), other.slug.as_str()));
+    assert_eq!(results[1]["environments"]["staging"]["enabled"], true);
+    assert_eq!(results[1]["environments"]["production"]["enabled"], false);
+
+    let none = user.exec(&["search", "no-such-flag-anywhere"]);
+    assert!(none.succeeded(), "search failed: {}", none.stderr());
+    assert!(
+        none.stdout().contains("No flags match"),
+        "{}",
+        none.stdout()
+    );
+}
+
+/// Test that scanning source code reports flags used but deleted, and flags
+/// nothing uses.
+#[tokio::test]
+async fn test_flags_scan_compares_code_with_server() {
+    let harness = TestHarness::new("flags_scan")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "scarlett").await;
+    for key in [
+        "new-checkout",
+        "ruby-banner",
+        "legacy-search",
+        "from-config",
+    ] {
+        user.flags_create(key, None, None, false)
+            .expect("flags create failed");
+    }
+
+    let repo = user.home_dir.join("scan-repo");
+    let src = repo.join("src");
+    std::fs::create_dir_all(src.join("node_modules")).expect("Failed to create repo");
+    std::fs::write(
+        src.join("main.rs"),
+        "fn main() {\n    if client.enabled(\"new-checkout\") {}\n    if client.enabled(\"old-banner\") {}\n}\n",
+    )
+    .unwrap();
+    std::fs::write(
+        src.join("flags.ts"),
+        "export const SEARCH = 'from-config';\n",
+    )
+    .unwrap();
+    std::fs::write(src.join("app.rb"), "flags.enabled?(\"ruby-banner\")\n").unwrap();
+    // Dependencies aren't the project's code
+    std::fs::write(
+        src.join("node_modules").join("dep.js"),
+        "isEnabled('legacy-search')\n",
+    )
+    .unwrap();
+    std::fs::write(
+        repo.join(".flaglite.toml"),
+        "[scan_patterns]\nrb = ['flags\\.enabled\\?\\(\"([A-Za-z0-9_-]+)\"\\)']\n",
+    )
+    .unwrap();
+
+    let result = user.exec_in(
+        &repo,
+        &["--format", "json", "flags", "scan", "--src", "src"],
+    );
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let report: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    let keys = |field: &str| -> Vec<String> {
+        report[field]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|f| f["key"].as_str().or(f.as_str()).unwrap().to_string())
+            .collect()
+    };
+
+    assert_eq!(report["files_scanned"], 3);
+    assert_eq!(keys("referenced"), ["new-checkout", "ruby-banner"]);
+    assert_eq!(keys("missing_on_server"), ["old-banner"]);
+    assert_eq!(
+        report["missing_on_server"][0]["locations"][0],
+        serde_json::json!({"path": "main.rs", "line": 3})
+    );
+    // A quoted key counts as used even outside an SDK call
+    assert_eq!(keys("unreferenced"), ["legacy-search"]);
+}
+
+/// Test that sync writes an environment's flags to a file and only rewrites
+/// it when a flag changed.
+#[tokio::test]
+async fn test_sync_writes_flag_snapshot() {
+    let harness = TestHarness::new("flags_sync")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "oscar").await;
+    user.flags_create("new-checkout", None, None, false)
+        .expect("flags create failed");
+    user.flags_create("dark-mode", None, None, false)
+        .expect("flags create failed");
+
+    let out = user.home_dir.join("app").join("flags.json");
+    std::fs::create_dir_all(out.parent().unwrap()).unwrap();
+    let out_arg = out.to_str().unwrap();
+    let sync = || {
+        let result = user.exec(&["sync", "-e", "development", "--out", out_arg, "--once"]);
+        assert!(result.succeeded(), "stderr: {}", result.stderr());
+        result
+    };
+    let snapshot = || -> serde_json::Value {
+        serde_json::from_str(&std::fs::read_to_string(&out).unwrap()).unwrap()
+    };
+
+    assert!(sync().stdout().contains("Wrote 2 flag(s)"));
+    let flags = &snapshot()["flags"];
+    assert_eq!(snapshot()["environment"], "development");
+    assert_eq!(flags["new-checkout"]["enabled"], false);
+    assert_eq!(flags["dark-mode"]["rollout_percentage"], 100);
+
+    // Nothing changed, nothing written
+    assert!(sync().stdout().contains("up to date"));
+
+    let result = user.exec(&["flags", "toggle", "new-checkout", "-e", "development"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    assert!(sync().stdout().contains("Wrote 2 flag(s)"));
+    assert_eq!(snapshot()["flags"]["new-checkout"]["enabled"], true);
+    // The temporary file was renamed into place
+    assert_eq!(std::fs::read_dir(out.parent().unwrap()).unwrap().count(), 1);
+
+    let result = user.exec(&["sync", "--out", out_arg, "--interval", "5d"]);
+    assert!(result.failed());
+}
+
+/// Test that registering flags defined in code creates only the missing ones.
+#[tokio::test]
+async fn test_register_flags_creates_missing_flags() {
+    let harness = TestHarness::new("register_flags")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("register");
+    let info = user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+    user.flags_create("dark-mode", None, None, false)
+        .expect("Flag create failed");
+
+    let client = reqwest::Client::new();
+    let register = |flags: serde_json::Value| {
+        let client = client.clone();
+        let url = format!(
+            "{}/v1/projects/{}/flags",
+            harness.server_url, projects[0].id
+        );
+        let api_key = info.api_key.clone();
+        async move {
+            let resp = client
+                .put(url)
+                .bearer_auth(api_key)
+                .json(&serde_json::json!({ "flags": flags }))
+                .send()
+                .await
+                .expect("Register request failed");
+            let status = resp.status().as_u16();
+            let body: serde_json::Value = resp.json().await.expect("Invalid register JSON");
+            (status, body)
+        }
+    };
+
+    let flags = serde_json::json!([
+        {"key": "dark-mode", "name": "dark-mode", "flag_type": "boolean", "enabled": true},
+        {"key": "max-items", "name": "max-items", "flag_type": "number", "enabled": true,
+         "default_enabled": true, "default_value": 10},
+    ]);
+    let (status, body) = register(flags.clone()).await;
+    assert_eq!(status, 200, "{body}");
+    assert_eq!(body["created"], serde_json::json!(["max-items"]));
+    assert_eq!(body["existing"], serde_json::json!(["dark-mode"]));
+
+    // The existing flag was left alone; the new one serves its default
+    let result = user.exec_json(&["flags", "get", "dark-mode"]);
+    let flag: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(flag["enabled"], false);
+    let result = user.exec_json(&["flags", "get", "max-items"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let flag: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(flag["flag_type"], "number");
+    assert_eq!(flag["enabled"], true);
+    assert_eq!(flag["default_value"], 10);
+    let evaluation: serde_json::Value = client
+        .get(format!(
+            "{}/v1/flags/max-items/evaluate",
+            harness.server_url
+        ))
+        .bearer_auth(&info.api_key)
+        .send()
+        .await
+        .expect("Evaluate request failed")
+        .json()
+        .await
+        .expect("Invalid evaluation JSON");
+    assert_eq!(evaluation["value"], 10, "{evaluation}");
+
+    let (_, body) = register(flags).await;
+    assert_eq!(body["created"], serde_json::json!([]));
+
+    let (status, _) = register(serde_json::json!([])).await;
+    assert_eq!(status, 422);
+}
+
+/// Test that the `[confirm]` config decides which prompts run and which of
+/// them --yes may skip.
+#[tokio::test]
+async fn test_confirm_policy_limits_yes() {
+    let harness = TestHarness::new("confirm_policy")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "quinn").await;
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("flags create failed");
+
+    let config_path = user.home_dir.join(".config/flaglite/config.toml");
+    let base_config = std::fs::read_to_string(&config_path).unwrap_or_default();
+    let set_policy = |policy: &str| {
+        std::fs::create_dir_all(config_path.parent().unwrap()).unwrap();
+        std::fs::write(
+            &config_path,
+            format!("{base_config}\n[confirm]\n{policy}\n"),
+        )
+        .unwrap();
+    };
+
+    // --yes can't skip a prompt the allowlist leaves out
+    set_policy("yes = []");
+    let result = user.exec(&["flags", "delete", &flag_key, "--yes"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+    assert!(
+        result.stderr().contains("delete_flag"),
+        "stderr: {}",
+        result.stderr()
+    );
+
+    // Nor the protected environment prompt, unless allowed
+    let result = user.exec(&["flags", "toggle", &flag_key, "-e", "production", "--yes"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+    set_policy(r#"yes = ["protected_environment"]"#);
+    let result = user.exec(&["flags", "toggle", &flag_key, "-e", "production", "--yes"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+
+    // An operation that doesn't ask needs no --yes at all
+    set_policy("delete_flag = false");
+    let result = user.exec(&["flags", "delete", &flag_key]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+}
+
+/// Test that `--format json-v2` wraps results and errors in one envelope.
+#[tokio::test]
+async fn test_json_v2_envelope() {
+    let harness = TestHarness::new("json_v2_envelope")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "rosa").await;
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("flags create failed");
+
+    let envelope = |args: &[&str]| {
+        let mut args = args.to_vec();
+        args.extend(["--format", "json-v2"]);
+        let result = user.exec(&args);
+        let envelope: serde_json::Value = serde_json::from_str(&result.stdout())
+            .unwrap_or_else(|e| panic!("{args:?}: {e}: {}", result.stdout()));
+        (result.exit_code(), envelope)
+    };
+
+    let (code, list) = envelope(&["flags", "list"]);
+    assert_eq!(code, Some(0));
+    assert_eq!(list["ok"], true);
+    assert!(list["error"].is_null());
+    assert_eq!(list["data"][0]["key"], flag_key.as_str());
+    assert_eq!(list["meta"]["command"], "flags list");
+    assert!(list["meta"]["duration_ms"].is_u64());
+
+    // Errors are an envelope on stdout too
+    let (code, missing) = envelope(&["flags", "get", "no-such-flag"]);
+    assert_eq!(code, Some(3));
+    assert_eq!(missing["ok"], false);
+    assert!(missing["data"].is_null());
+    assert_eq!(missing["error"]["code"], "not_found");
+    assert_eq!(missing["error"]["exit_code"], 3);
+
+    // Commands without a result still report success
+    let (code, deleted) = envelope(&["flags", "delete", &flag_key, "--yes"]);
+    assert_eq!(code, Some(0));
+    assert_eq!(deleted["ok"], true);
+    assert!(deleted["data"].is_null());
+}
+
+/// Test that `flags notify` checks the keys it watches before running unattended.
+#[tokio::test]
+async fn test_notify_rejects_unknown_keys() {
+    let harness = TestHarness::new("notify_unknown_keys")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "notifier").await;
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("flags create failed");
+
+    let result = user.exec(&["flags", "notify", &flag_key, "missing-flag"]);
+    assert_eq!(result.exit_code(), Some(3), "stderr: {}", result.stderr());
+    assert!(
+        result.stderr().contains("missing-flag"),
+        "stderr: {}",
+        result.stderr()
+    );
+}
+
+/// Test starting a rollout ramp and pausing, resuming and aborting it.
+#[tokio::test]
+async fn test_ramp_lifecycle() {
+    let harness = TestHarness::new("ramp_lifecycle")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "rampart").await;
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("flags create failed");
+
+    // Production is protected, so the ramp needs the same confirmation
+    let result = user.exec(&["flags", "ramp", &flag_key, "-e", "production"]);
+    assert_eq!(result.exit_code(), Some(4), "stderr: {}", result.stderr());
+
+    let json = user
+        .exec_json(&[
+            "flags",
+            "ramp",
+            &flag_key,
+            "-e",
+            "production",
+            "--to",
+            "50",
+            "--step",
+            "20",
+            "--interval",
+            "10m",
+            "--confirm-production",
+            "--reason",
+            "gradual launch",
+        ])
+        .success()
+        .expect("flags ramp failed");
+    let ramp: serde_json::Value = serde_json::from_str(&json).expect("Invalid ramp JSON");
+    let id = ramp["id"].as_str().expect("No ramp id").to_string();
+    assert_eq!(ramp["status"], "active");
+    assert_eq!(ramp["current_percentage"], 20);
+    assert_eq!(ramp["target_percentage"], 50);
+    assert_eq!(ramp["interval_secs"], 600);
+    assert!(ramp["next_step_at"].is_string());
+
+    // The first step is taken right away
+    let json = user
+        .exec_json(&["flags", "get", &flag_key, "-e", "production"])
+        .success()
+        .expect("flags get failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+    assert_eq!(flag["enabled"], true);
+    assert_eq!(flag["environments"]["production"]["rollout"], 20);
+
+    // One ramp per flag and environment at a time
+    let result = user.exec(&[
+        "flags",
+        "ramp",
+        &flag_key,
+        "-e",
+        "production",
+        "--confirm-production",
+    ]);
+    assert!(result.failed(), "A second ramp should be rejected");
+    assert!(result.stderr().contains(&id), "stderr: {}", result.stderr());
+
+    let json = user
+        .exec_json(&["ramps", "pause", &id])
+        .success()
+        .expect("ramps pause failed");
+    let ramp: serde_json::Value = serde_json::from_str(&json).expect("Invalid ramp JSON");
+    assert_eq!(ramp["status"], "paused");
+    assert!(ramp["next_step_at"].is_null());
+
+    let result = user.exec(&["ramps", "pause", &id]);
+    assert!(result.failed(), "Pausing twice should fail");
+
+    let json = user
+        .exec_json(&["ramps", "resume", &id])
+        .success()
+        .expect("ramps resume failed");
+    let ramp: serde_json::Value = serde_json::from_str(&json).expect("Invalid ramp JSON");
+    assert_eq!(ramp["status"], "active");
+
+    user.exec(&["ramps", "abort", &id])
+        .success_or_err("ramps abort")
+        .expect("ramps abort failed");
+    let json = user
+        .exec_json(&["ramps", "list", "--status", "aborted"])
+        .success()
+        .expect("ramps list failed");
+    let ramps: serde_json::Value = serde_json::from_str(&json).expect("Invalid ramps JSON");
+    assert_eq!(ramps.as_array().map(Vec::len), Some(1));
+    assert_eq!(ramps[0]["flag_key"], flag_key.as_str());
+    assert_eq!(ramps[0]["current_percentage"], 20);
+
+    let status = user
+        .exec(&["ramps", "status", &id])
+        .success()
+        .expect("ramps status failed");
+    assert!(status.contains("aborted"), "status: {status}");
+}
+
+#[tokio::test]
+async fn test_guard_reverts_toggle() {
+    let harness = TestHarness::new("guard_reverts_toggle")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "guarded").await;
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("flags create failed");
+
+    let json = user
+        .exec_json(&[
+            "flags",
+            "toggle",
+            &flag_key,
+            "-e",
+            "development",
+            "--guard",
+            "--guard-window",
+            "10m",
+        ])
+        .success()
+        .expect("flags toggle --guard failed");
+    let guard: serde_json::Value = serde_json::from_str(&json).expect("Invalid guard JSON");
+    let token = guard["token"].as_str().expect("No guard token").to_string();
+    assert!(token.starts_with("ffl_guard_"), "token: {token}");
+    assert_eq!(guard["status"], "watching");
+    assert_eq!(guard["window_secs"], 600);
+    assert_eq!(guard["revert_enabled"], false);
+
+    // A healthy report changes nothing
+    user.exec(&["guards", "report", &token, "--healthy"])
+        .success_or_err("guards report --healthy")
+        .expect("guards report --healthy failed");
+    let json = user
+        .exec_json(&["flags", "get", &flag_key, "-e", "development"])
+        .success()
+        .expect("flags get failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+    assert_eq!(flag["enabled"], true);
+
+    let json = user
+        .exec_json(&["guards", "report", &token, "--message", "error spike"])
+        .success()
+        .expect("guards report failed");
+    let guard: serde_json::Value = serde_json::from_str(&json).expect("Invalid guard JSON");
+    assert_eq!(guard["status"], "tripped");
+    assert_eq!(guard["message"], "error spike");
+    assert!(guard.get("token").is_none());
+
+    // Reverted, with the trip recorded as the reason
+    let json = user
+        .exec_json(&["flags", "history", &flag_key, "-e", "development"])
+        .success()
+        .expect("flags history failed");
+    let history: serde_json::Value = serde_json::from_str(&json).expect("Invalid history JSON");
+    let latest = &history["versions"][0];
+    assert_eq!(latest["enabled"], false);
+    assert!(
+        latest["reason"]
+            .as_str()
+            .is_some_and(|r| r.ends_with("tripped: error spike")),
+        "history: {history}"
+    );
+
+    // A guard trips once
+    let result = user.exec(&["guards", "report", &token]);
+    assert!(result.failed(), "A tripped guard should reject reports");
+
+    let json = user
+        .exec_json(&["guards", "list", "--status", "tripped"])
+        .success()
+        .expect("guards list failed");
+    let guards: serde_json::Value = serde_json::from_str(&json).expect("Invalid guards JSON");
+    assert_eq!(guards.as_array().map(Vec::len), Some(1));
+    assert_eq!(guards[0]["flag_key"], flag_key.as_str());
+
+    // Cancelled guards leave the flag alone
+    let json = user
+        .exec_json(&["flags", "toggle", &flag_key, "-e", "development", "--guard"])
+        .success()
+        .expect("flags toggle --guard failed");
+    let guard: serde_json::Value = serde_json::from_str(&json).expect("Invalid guard JSON");
+    let id = guard["id"].as_str().expect("No guard id").to_string();
+    let token = guard["token"].as_str().expect("No guard token").to_string();
+    user.exec(&["guards", "cancel", &id])
+        .success_or_err("guards cancel")
+        .expect("guards cancel failed");
+    let result = user.exec(&["guards", "report", &token]);
+    assert!(result.failed(), "A cancelled guard should reject reports");
+    let json = user
+        .exec_json(&["flags", "get", &flag_key, "-e", "development"])
+        .success()
+        .expect("flags get failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+    assert_eq!(flag["enabled"], true);
+}
+
+#[tokio::test]
+async fn test_guard_aborts_ramp() {
+    let harness = TestHarness::new("guard_aborts_ramp")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = setup_user_with_project(&harness, "guardramp").await;
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("flags create failed");
+
+    let json = user
+        .exec_json(&[
+            "flags",
+            "ramp",
+            &flag_key,
+            "-e",
+            "development",
+            "--step",
+            "25",
+            "--guard",
+            "https://health.invalid/checkout",
+        ])
+        .success()
+        .expect("flags ramp --guard failed");
+    let ramp: serde_json::Value = serde_json::from_str(&json).expect("Invalid ramp JSON");
+    let id = ramp["id"].as_str().expect("No ramp id").to_string();
+    let guard = &ramp["guard"];
+    assert_eq!(guard["ramp_id"], id.as_str());
+    assert_eq!(guard["url"], "https://health.invalid/checkout");
+    // The window only starts once the ramp is over
+    assert!(guard["watching_until"].is_null());
+    let token = guard["token"].as_str().expect("No guard token").to_string();
+
+    user.exec(&["guards", "report", &token, "--message", "p99 latency up"])
+        .success_or_err("guards report")
+        .expect("guards report failed");
+
+    let json = user
+        .exec_json(&["ramps", "status", &id])
+        .success()
+        .expect("ramps status failed");
+    let ramp: serde_json::Value = serde_json::from_str(&json).expect("Invalid ramp JSON");
+    assert_eq!(ramp["status"], "aborted");
+
+    let json = user
+        .exec_json(&["flags", "get", &flag_key, "-e", "development"])
+        .success()
+        .expect("flags get failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+    assert_eq!(flag["enabled"], false);
 }
@@ -7,7 +7,7 @@
 
 mod common;
 
-use common::{unique_project_name, TestHarness, TEST_PASSWORD};
+use common::{unique_flag_key, unique_project_name, TestHarness, TEST_PASSWORD};
 
 /// Test listing projects returns at least one project (created on signup).
 #[tokio::test]
@@ -64,6 +64,38 @@ async fn test_create_project() {
     assert!(!project.slug.is_empty(), "Project slug should not be empty");
 }
 
+/// Test that slugs are unique per user and address projects in place of ids.
+#[tokio::test]
+async fn test_project_slugs() {
+    let harness = TestHarness::new("project_slugs")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("slugs");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    let name = format!("Checkout {}", unique_project_name());
+    let first = user
+        .projects_create(&name, None)
+        .expect("projects create failed");
+    let second = user
+        .projects_create(&name, None)
+        .expect("projects create failed");
+    assert_eq!(second.slug, format!("{}-2", first.slug));
+
+    user.projects_use(&second.slug)
+        .expect("projects use by slug failed");
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, true)
+        .expect("Flag create failed");
+
+    // Routes resolve the slug server-side
+    let result = user.exec_json(&["flags", "get", &flag_key, "--project", &second.slug]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let result = user.exec_json(&["flags", "get", &flag_key, "--project", &first.slug]);
+    assert!(!result.succeeded(), "Flag found in the other project");
+}
+
 /// Test project has default environments.
 #[tokio::test]
 async fn test_project_has_default_environments() {
@@ -235,3 +267,1454 @@ async fn test_projects_isolated_between_users() {
         "Projects should be isolated between users"
     );
 }
+
+/// Test cloning a project copies its flags and environments.
+#[tokio::test]
+async fn test_projects_clone() {
+    let harness = TestHarness::new("projects_clone")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("frank");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    let projects = user.projects_list().expect("Projects list failed");
+    let source_id = projects[0].id.clone();
+    user.projects_use(&source_id).expect("Projects use failed");
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, Some("Cloned Flag"), None, true)
+        .expect("flags create failed");
+
+    let clone_name = unique_project_name();
+    let cloned = user
+        .projects_clone(&source_id, &clone_name, true)
+        .expect("projects clone failed");
+    assert_eq!(cloned.name, clone_name);
+    assert_ne!(cloned.id, source_id, "Clone should be a new project");
+
+    user.projects_use(&cloned.id).expect("Projects use failed");
+
+    let envs = user.envs_list().expect("envs list failed");
+    assert!(
+        envs.iter().any(|e| e.name == "production"),
+        "Cloned project should have the source environments"
+    );
+
+    let flag = user.flags_get(&flag_key).expect("flags get failed");
+    assert!(
+        flag.enabled,
+        "Flag value should be copied with --with-values"
+    );
+}
+
+/// Test that an exported project imports into another account with its
+/// flags, values and allowlists.
+#[tokio::test]
+async fn test_projects_export_and_import() {
+    let harness = TestHarness::new("projects_archive")
+        .await
+        .expect("Failed to create test harness");
+
+    let alice = harness.create_user("alice");
+    alice.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = alice.projects_list().expect("Projects list failed");
+    alice
+        .projects_use(&projects[0].id)
+        .expect("Projects use failed");
+
+    let flag_key = unique_flag_key();
+    alice
+        .flags_create(&flag_key, Some("Exported Flag"), None, true)
+        .expect("flags create failed");
+    alice
+        .exec(&["flags", "allowlist", "add", &flag_key, "--user", "beta-1"])
+        .success()
+        .expect("flags allowlist add failed");
+
+    let archive = harness.test_dir().join("project.tar.gz");
+    let archive = archive.to_str().expect("Non-UTF-8 temp path");
+    let result = alice.exec_json(&["projects", "export", "--file", archive]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let exported: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(exported["slug"], projects[0].slug.as_str());
+
+    let bob = harness.create_user("bob");
+    bob.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let name = unique_project_name();
+    let result = bob.exec_json(&["projects", "import", archive, "--name", &name]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let imported: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(imported["name"], name.as_str());
+    let imported_id = imported["id"].as_str().unwrap();
+    assert_ne!(
+        imported_id, projects[0].id,
+        "Import should be a new project"
+    );
+
+    bob.projects_use(imported_id).expect("Projects use failed");
+    let envs = bob.envs_list().expect("envs list failed");
+    assert!(envs.iter().any(|e| e.name == "production"));
+    let flag = bob.flags_get(&flag_key).expect("flags get failed");
+    assert_eq!(flag.name, "Exported Flag");
+    assert!(flag.enabled, "Flag values should be imported");
+
+    let result = bob.exec_json(&["flags", "allowlist", "list", &flag_key]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let list: serde_json::Value = serde_json::from_str(&result.stdout()).unwrap();
+    assert_eq!(list["user_ids"], serde_json::json!(["beta-1"]));
+
+    // Anything but an archive is refused without creating a project
+    let garbage = harness.test_dir().join("garbage.tar.gz");
+    std::fs::write(&garbage, "not an archive").unwrap();
+    let result = bob.exec(&["projects", "import", garbage.to_str().unwrap()]);
+    assert!(result.failed());
+    assert!(
+        result.stderr().contains("Not a project archive"),
+        "stderr: {}",
+        result.stderr()
+    );
+    assert_eq!(bob.projects_list().expect("Projects list failed").len(), 2);
+}
+
+/// Test that members of an organization share its projects.
+#[tokio::test]
+async fn test_org_members_share_projects() {
+    let harness = TestHarness::new("org_members_share_projects")
+        .await
+        .expect("Failed to create test harness");
+
+    let owner = harness.create_user("grace");
+    let owner_info = owner.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    let member = harness.create_user("heidi");
+    let member_info = member.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    let json = owner
+        .exec_json(&["orgs", "create", "Acme"])
+        .success()
+        .expect("orgs create failed");
+    let org: serde_json::Value = serde_json::from_str(&json).expect("Invalid org JSON");
+    assert_eq!(org["role"], "owner");
+
+    // Created in the org we just switched to
+    let project_name = unique_project_name();
+    let project = owner
+        .projects_create(&project_name, None)
+        .expect("projects create failed");
+
+    assert!(
+        member
+            .projects_list()
+            .expect("Projects list failed")
+            .iter()
+            .all(|p| p.name != project_name),
+        "Non-members should not see the org's projects"
+    );
+
+    owner
+        .exec(&["orgs", "add-member", &member_info.username])
+        .success()
+        .expect("orgs add-member failed");
+
+    let projects = member.projects_list().expect("Projects list failed");
+    assert!(
+        projects.iter().any(|p| p.name == project_name),
+        "Members should see the org's projects"
+    );
+
+    member
+        .projects_use(&project.id)
+        .expect("Projects use failed");
+    member
+        .flags_create(&unique_flag_key(), None, None, false)
+        .expect("Members should be able to manage the org's flags");
+
+    // Only owners change members, and the org always keeps one
+    member
+        .exec(&["orgs", "switch", "Acme"])
+        .success()
+        .expect("orgs switch failed");
+    assert!(member
+        .exec(&["orgs", "members", "remove", &owner_info.username])
+        .failed());
+    let result = owner.exec(&["orgs", "members", "remove", &owner_info.username]);
+    assert!(result.failed(), "The last owner shouldn't be removable");
+    assert!(
+        result.stderr().contains("at least one owner"),
+        "stderr: {}",
+        result.stderr()
+    );
+    assert!(owner
+        .exec(&[
+            "orgs",
+            "members",
+            "set-role",
+            &owner_info.username,
+            "member"
+        ])
+        .failed());
+
+    let json = owner
+        .exec_json(&[
+            "orgs",
+            "members",
+            "set-role",
+            &member_info.username,
+            "owner",
+        ])
+        .success()
+        .expect("orgs members set-role failed");
+    let promoted: serde_json::Value = serde_json::from_str(&json).expect("Invalid member JSON");
+    assert_eq!(promoted["role"], "owner");
+    owner
+        .exec(&[
+            "orgs",
+            "members",
+            "set-role",
+            &member_info.username,
+            "member",
+        ])
+        .success()
+        .expect("orgs members set-role failed");
+
+    owner
+        .exec(&["orgs", "members", "remove", &member_info.username])
+        .success()
+        .expect("orgs members remove failed");
+    assert!(
+        member
+            .flags_create(&unique_flag_key(), None, None, false)
+            .is_err(),
+        "Removed members should lose the org's projects"
+    );
+}
+
+/// Test sharing a project through an invite link, and revoking the link.
+#[tokio::test]
+async fn test_project_invite_links() {
+    let harness = TestHarness::new("project_invite_links")
+        .await
+        .expect("Failed to create test harness");
+
+    let owner = harness.create_user("ivan");
+    owner.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let project_name = unique_project_name();
+    let project = owner
+        .projects_create(&project_name, None)
+        .expect("projects create failed");
+
+    let json = owner
+        .exec_json(&["projects", "share", &project.id, "--expires", "2d"])
+        .success()
+        .expect("projects share failed");
+    let link: serde_json::Value = serde_json::from_str(&json).expect("Invalid link JSON");
+    assert_eq!(link["role"], "member");
+    assert_eq!(link["status"], "active");
+    let token = link["token"].as_str().expect("Link should have a token");
+    let id = link["id"].as_str().expect("Link should have an id");
+
+    let member = harness.create_user("judy");
+    member.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let url = format!("{}/v1/join/{token}", harness.server_url);
+    member.exec(&["join", &url]).success().expect("join failed");
+    assert!(
+        member
+            .projects_list()
+            .expect("Projects list failed")
+            .iter()
+            .any(|p| p.name == project_name),
+        "Joined projects should be listed"
+    );
+    // `join` switches to the project
+    member
+        .flags_create(&unique_flag_key(), None, None, false)
+        .expect("Members should be able to manage a shared project's flags");
+
+    let json = owner
+        .exec_json(&["projects", "links", &project.id])
+        .success()
+        .expect("projects links failed");
+    let links: serde_json::Value = serde_json::from_str(&json).expect("Invalid links JSON");
+    assert_eq!(links[0]["uses"], 1);
+    assert!(
+        links[0].get("token").is_none(),
+        "Tokens are only shown once"
+    );
+
+    assert!(
+        member.exec(&["projects", "share", &project.id]).failed(),
+        "Members shouldn't be able to create invite links"
+    );
+
+    owner
+        .exec(&["projects", "revoke-link", id, "--project", &project.id])
+        .success()
+        .expect("projects revoke-link failed");
+
+    let latecomer = harness.create_user("ken");
+    latecomer
+        .signup(None, TEST_PASSWORD)
+        .expect("Signup failed");
+    let result = latecomer.exec(&["join", token]);
+    assert!(result.failed(), "Revoked links shouldn't work");
+    assert!(result.stderr().contains("revoked"), "{}", result.stderr());
+
+    assert!(
+        member
+            .projects_list()
+            .expect("Projects list failed")
+            .iter()
+            .any(|p| p.name == project_name),
+        "Revoking a link shouldn't remove users who joined with it"
+    );
+
+    // Owners can see who joined and take their access back
+    let json = owner
+        .exec_json(&["projects", "members", &project.id])
+        .success()
+        .expect("projects members failed");
+    let members: serde_json::Value = serde_json::from_str(&json).expect("Invalid members JSON");
+    assert_eq!(members.as_array().map(Vec::len), Some(1));
+    assert_eq!(members[0]["role"], "member");
+    let username = members[0]["username"].as_str().unwrap().to_string();
+    assert!(
+        member.exec(&["projects", "members", &project.id]).failed(),
+        "Members shouldn't be able to list members"
+    );
+
+    owner
+        .exec(&["projects", "remove-member", &username, "-p", &project.id])
+        .success()
+        .expect("projects remove-member failed");
+    assert!(
+        member
+            .projects_list()
+            .expect("Projects list failed")
+            .iter()
+            .all(|p| p.name != project_name),
+        "Removed members shouldn't have the project"
+    );
+    assert!(owner
+        .exec(&["projects", "remove-member", &username, "-p", &project.id])
+        .failed());
+}
+
+/// Test that `flaglite init` pins a repo to a project for commands run anywhere inside it.
+#[tokio::test]
+async fn test_init_repo_config() {
+    let harness = TestHarness::new("init_repo_config")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("ivan");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    let project = user
+        .projects_create(&unique_project_name(), None)
+        .expect("projects create failed");
+
+    let repo = user.home_dir.join("repo");
+    let subdir = repo.join("src");
+    std::fs::create_dir_all(repo.join(".git")).expect("Failed to create repo");
+    std::fs::create_dir_all(&subdir).expect("Failed to create subdir");
+
+    user.exec_in(&subdir, &["init", "-p", &project.id, "-e", "staging"])
+        .success()
+        .expect("init failed");
+    assert!(
+        repo.join(".flaglite.toml").is_file(),
+        "init should write .flaglite.toml at the repo root"
+    );
+
+    let config = |dir: &std::path::Path| -> serde_json::Value {
+        let json = user
+            .exec_in(dir, &["--format", "json", "config"])
+            .success()
+            .expect("config failed");
+        serde_json::from_str(&json).expect("Invalid config JSON")
+    };
+
+    let inside = config(&subdir);
+    assert_eq!(inside["project_id"], project.id.as_str());
+    assert_eq!(inside["environment"], "staging");
+
+    let outside = config(&user.home_dir);
+    assert_ne!(
+        outside["project_id"],
+        project.id.as_str(),
+        "Repo config should not apply outside the repo"
+    );
+}
+
+/// Test that `flaglite config set/get/unset` edit the repo and user config.
+#[tokio::test]
+async fn test_config_set_get_unset() {
+    let harness = TestHarness::new("config_set_get_unset")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("judy");
+    let repo = user.home_dir.join("repo");
+    std::fs::create_dir_all(repo.join(".git")).expect("Failed to create repo");
+
+    user.exec_in(&repo, &["config", "set", "environment", "staging"])
+        .success()
+        .expect("config set failed");
+    assert!(
+        repo.join(".flaglite.toml").is_file(),
+        "config set should create .flaglite.toml at the repo root"
+    );
+
+    let get = |dir: &std::path::Path, key: &str| -> String {
+        user.exec_in(dir, &["config", "get", key])
+            .success()
+            .expect("config get failed")
+            .trim()
+            .to_string()
+    };
+    assert_eq!(get(&repo, "environment"), "staging");
+    assert_eq!(get(&user.home_dir, "environment"), "");
+
+    // A global format preference applies without --format
+    user.exec(&["config", "set", "format", "json", "--global"])
+        .success()
+        .expect("config set --global failed");
+    let json = user.exec(&["config"]).success().expect("config failed");
+    let config: serde_json::Value = serde_json::from_str(&json).expect("config should print JSON");
+    assert_eq!(config["format"], "json");
+
+    for args in [
+        ["config", "set", "token", "secret"],
+        ["config", "set", "format", "xml"],
+        ["config", "set", "api_url", "ftp://example.com"],
+    ] {
+        assert_eq!(
+            user.exec_in(&repo, &args).exit_code(),
+            Some(4),
+            "{args:?} should fail validation"
+        );
+    }
+
+    user.exec_in(&repo, &["config", "unset", "environment"])
+        .success()
+        .expect("config unset failed");
+    let json = user
+        .exec_in(&repo, &["config", "get", "environment"])
+        .success()
+        .expect("config get failed");
+    let value: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert!(value["value"].is_null());
+}
+
+/// Test the readiness probe and the admin stats and jobs endpoints.
+#[tokio::test]
+async fn test_readiness_and_admin_stats() {
+    let harness = TestHarness::with_env("admin_stats", &[("ADMIN_TOKEN", "test-admin-token")])
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("stats");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+    user.flags_create(&unique_flag_key(), None, None, false)
+        .expect("Flag create failed");
+
+    let client = reqwest::Client::new();
+    let resp = client
+        .get(format!("{}/ready", harness.server_url))
+        .send()
+        .await
+        .expect("Readiness request failed");
+    assert_eq!(resp.status().as_u16(), 200);
+
+    let stats_url = format!("{}/v1/admin/stats", harness.server_url);
+    let resp = client
+        .get(&stats_url)
+        .send()
+        .await
+        .expect("Admin request failed");
+    assert_eq!(resp.status().as_u16(), 401);
+
+    let stats: serde_json::Value = client
+        .get(&stats_url)
+        .bearer_auth("test-admin-token")
+        .send()
+        .await
+        .expect("Admin request failed")
+        .json()
+        .await
+        .expect("Invalid stats JSON");
+
+    let backend = if harness.database_url().starts_with("postgres") {
+        "postgres"
+    } else {
+        "sqlite"
+    };
+    assert_eq!(stats["backend"], backend, "Unexpected stats: {stats}");
+    assert!(stats["users"].as_i64().unwrap_or(0) >= 1);
+    assert!(stats["projects"].as_i64().unwrap_or(0) >= 1);
+    assert!(stats["flags"].as_i64().unwrap_or(0) >= 1);
+    assert!(stats["database_bytes"].as_i64().unwrap_or(0) > 0);
+
+    let jobs: Vec<serde_json::Value> = client
+        .get(format!("{}/v1/admin/jobs", harness.server_url))
+        .bearer_auth("test-admin-token")
+        .send()
+        .await
+        .expect("Admin request failed")
+        .json()
+        .await
+        .expect("Invalid jobs JSON");
+    let flush = jobs
+        .iter()
+        .find(|j| j["name"] == "flush-usage")
+        .unwrap_or_else(|| panic!("Missing flush-usage job: {jobs:?}"));
+    assert_eq!(flush["interval_secs"], 10);
+    assert!(
+        flush["runs"].as_u64().unwrap_or(0) >= 1,
+        "Unexpected job: {flush}"
+    );
+}
+
+/// Test that an admin-set quota turns away SDK requests over the limit.
+#[tokio::test]
+async fn test_project_quota_limits_sdk_keys() {
+    let harness = TestHarness::with_env("project_quota", &[("ADMIN_TOKEN", "test-admin-token")])
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("quota");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("Flag create failed");
+
+    let result = user.exec_json(&["envs", "list"]);
+    let envs: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    let env_key = envs[0]["api_key"]
+        .as_str()
+        .expect("Environment has no API key")
+        .to_string();
+
+    let client = reqwest::Client::new();
+    let quota_url = format!(
+        "{}/v1/admin/projects/{}/quota",
+        harness.server_url, projects[0].id
+    );
+
+    let resp = client
+        .put(&quota_url)
+        .bearer_auth("wrong-token")
+        .json(&serde_json::json!({"per_key_requests_per_minute": 2}))
+        .send()
+        .await
+        .expect("Admin request failed");
+    assert_eq!(resp.status().as_u16(), 401);
+
+    let resp = client
+        .put(&quota_url)
+        .bearer_auth("test-admin-token")
+        .json(&serde_json::json!({"per_key_requests_per_minute": 2}))
+        .send()
+        .await
+        .expect("Admin request failed");
+    assert!(
+        resp.status().is_success(),
+        "Set quota failed: {}",
+        resp.status()
+    );
+
+    let evaluate = || {
+        client
+            .get(format!(
+                "{}/v1/flags/{flag_key}/evaluate",
+                harness.server_url
+            ))
+            .bearer_auth(&env_key)
+            .send()
+    };
+    for _ in 0..2 {
+        let resp = evaluate().await.expect("Evaluate request failed");
+        assert!(
+            resp.status().is_success(),
+            "Evaluate failed: {}",
+            resp.status()
+        );
+    }
+
+    // The minute may roll over between requests, so allow one more window
+    let mut limited = None;
+    for _ in 0..3 {
+        let resp = evaluate().await.expect("Evaluate request failed");
+        if resp.status().as_u16() == 429 {
+            limited = Some(resp);
+            break;
+        }
+    }
+    let resp = limited.expect("Quota was never enforced");
+    assert!(resp.headers().contains_key("retry-after"));
+}
+
+/// Test that signed snapshots switch keys on rotation while the old key stays published.
+#[tokio::test]
+async fn test_signed_snapshot_key_rotation() {
+    let harness = TestHarness::new("signed_snapshot")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("signer");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+
+    let result = user.exec_json(&["envs", "list"]);
+    let envs: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    let env = envs
+        .as_array()
+        .expect("Expected array")
+        .iter()
+        .find(|e| e["name"] == "staging")
+        .expect("No staging environment");
+    let env_key = env["api_key"].as_str().expect("No API key").to_string();
+
+    let client = reqwest::Client::new();
+    let fetch = |path: &'static str| {
+        client
+            .get(format!("{}{path}", harness.server_url))
+            .bearer_auth(&env_key)
+            .send()
+    };
+
+    let signed: serde_json::Value = fetch("/v1/flags/signed")
+        .await
+        .expect("Signed snapshot request failed")
+        .json()
+        .await
+        .expect("Invalid JSON");
+    assert_eq!(signed["jws"].as_str().unwrap().split('.').count(), 3);
+    let first_kid = signed["kid"].as_str().unwrap().to_string();
+
+    let result = user.exec_json(&["envs", "rotate-signing-key", "staging"]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let rotated: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    let new_kid = rotated["keys"][0]["kid"].as_str().unwrap().to_string();
+    assert_ne!(new_kid, first_kid);
+
+    let signed: serde_json::Value = fetch("/v1/flags/signed")
+        .await
+        .expect("Signed snapshot request failed")
+        .json()
+        .await
+        .expect("Invalid JSON");
+    assert_eq!(signed["kid"], new_kid.as_str());
+
+    // Snapshots signed before the rotation still verify
+    let jwks: serde_json::Value = fetch("/v1/flags/signing-keys")
+        .await
+        .expect("Signing keys request failed")
+        .json()
+        .await
+        .expect("Invalid JSON");
+    let kids: Vec<&str> = jwks["keys"]
+        .as_array()
+        .expect("Expected array")
+        .iter()
+        .filter_map(|k| k["kid"].as_str())
+        .collect();
+    assert_eq!(kids, vec![new_kid.as_str(), first_kid.as_str()]);
+    assert_eq!(jwks["keys"][0]["crv"], "Ed25519");
+}
+
+/// Test that an environment's cache settings drive the Cache-Control of its
+/// SDK responses.
+#[tokio::test]
+async fn test_environment_cache_headers() {
+    let harness = TestHarness::new("env_cache")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("cacher");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("Flag create failed");
+
+    let result = user.exec_json(&["envs", "list"]);
+    let envs: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    let env = envs
+        .as_array()
+        .expect("Expected array")
+        .iter()
+        .find(|e| e["name"] == "staging")
+        .expect("No staging environment");
+    let env_key = env["api_key"].as_str().expect("No API key").to_string();
+
+    let client = reqwest::Client::new();
+    let cache_control = |path: String| {
+        let request = client
+            .get(format!("{}{path}", harness.server_url))
+            .bearer_auth(&env_key)
+            .send();
+        async move {
+            let resp = request.await.expect("SDK request failed");
+            assert!(resp.status().is_success(), "{path}: {}", resp.status());
+            resp.headers()
+                .get("cache-control")
+                .map(|v| v.to_str().unwrap().to_string())
+        }
+    };
+    let evaluate = format!("/v1/flags/{flag_key}/evaluate?user_id=u1");
+    let enabled = format!("/v1/flags/{flag_key}/enabled?user_id=u1");
+
+    // Without settings, only the plain-text endpoint is cacheable
+    assert_eq!(cache_control(evaluate.clone()).await, None);
+    assert_eq!(cache_control("/v1/flags".to_string()).await, None);
+    assert!(cache_control(enabled.clone())
+        .await
+        .is_some_and(|c| c.contains("max-age=10")));
+
+    let json = user
+        .exec_json(&[
+            "envs",
+            "update",
+            "staging",
+            "--cache-max-age",
+            "60",
+            "--cache-stale",
+            "300",
+        ])
+        .success()
+        .expect("envs update failed");
+    let env: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert_eq!(env["cache_max_age"], 60);
+    assert_eq!(env["cache_stale_while_revalidate"], 300);
+
+    let expected = "public, max-age=60, stale-while-revalidate=300, stale-if-error=3600";
+    for path in [evaluate.clone(), enabled.clone(), "/v1/flags".to_string()] {
+        assert_eq!(cache_control(path).await.as_deref(), Some(expected));
+    }
+
+    user.exec(&["envs", "update", "staging", "--cache-max-age", "0"])
+        .success_or_err("envs update")
+        .expect("envs update failed");
+    assert_eq!(cache_control(enabled).await.as_deref(), Some("no-store"));
+
+    let result = user.exec(&["envs", "update", "staging", "--cache-max-age", "100000"]);
+    assert!(result.failed(), "More than a day should be rejected");
+}
+
+/// Test keyless evaluation of public flags through a public environment.
+#[tokio::test]
+async fn test_public_environment_evaluation() {
+    let harness = TestHarness::new("env_public")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("marketer");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+
+    let public_key = unique_flag_key();
+    let private_key = unique_flag_key();
+    for key in [&public_key, &private_key] {
+        user.flags_create(key, None, None, false)
+            .expect("Flag create failed");
+        user.exec(&["flags", "enable", key, "-e", "staging"])
+            .success_or_err("flags enable")
+            .expect("flags enable failed");
+    }
+    let json = user
+        .exec_json(&["flags", "update", &public_key, "--public"])
+        .success()
+        .expect("flags update --public failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert_eq!(flag["public"], true);
+
+    let json = user
+        .exec_json(&["envs", "publish", "staging"])
+        .success()
+        .expect("envs publish failed");
+    let env: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    let client_id = env["public_client_id"]
+        .as_str()
+        .expect("No public client ID")
+        .to_string();
+    assert!(client_id.starts_with("ffl_pub_"), "client ID: {client_id}");
+
+    let client = reqwest::Client::new();
+    let get = |path: String| client.get(format!("{}{path}", harness.server_url)).send();
+
+    let resp = get(format!("/v1/public/{client_id}/flags?user_id=u1"))
+        .await
+        .expect("Public request failed");
+    assert!(resp.status().is_success(), "{}", resp.status());
+    let flags: serde_json::Value = resp.json().await.expect("Invalid JSON");
+    let flags = flags.as_array().expect("Expected array");
+    assert_eq!(flags.len(), 1, "Only the public flag is visible: {flags:?}");
+    assert_eq!(flags[0]["key"], public_key.as_str());
+    assert_eq!(flags[0]["enabled"], true);
+
+    let resp = get(format!("/v1/public/{client_id}/flags/{public_key}"))
+        .await
+        .expect("Public request failed");
+    let evaluation: serde_json::Value = resp.json().await.expect("Invalid JSON");
+    assert_eq!(evaluation["enabled"], true);
+
+    let resp = get(format!("/v1/public/{client_id}/flags/{private_key}"))
+        .await
+        .expect("Public request failed");
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // Making it private again revokes the client ID
+    let json = user
+        .exec_json(&["envs", "unpublish", "staging"])
+        .success()
+        .expect("envs unpublish failed");
+    let env: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert!(env["public_client_id"].is_null());
+    let resp = get(format!("/v1/public/{client_id}/flags"))
+        .await
+        .expect("Public request failed");
+    assert_eq!(resp.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+/// Test that environments list in their sort order and carry display metadata.
+#[tokio::test]
+async fn test_environment_order_and_display() {
+    let harness = TestHarness::new("env_order")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("orderly");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+
+    let env_names = |json: &str| -> Vec<String> {
+        let envs: serde_json::Value = serde_json::from_str(json).expect("Invalid JSON");
+        envs.as_array()
+            .expect("Expected array")
+            .iter()
+            .map(|e| e["name"].as_str().unwrap().to_string())
+            .collect()
+    };
+
+    let json = user
+        .exec_json(&["envs", "list"])
+        .success()
+        .expect("envs list failed");
+    assert_eq!(env_names(&json), ["development", "staging", "production"]);
+
+    let json = user
+        .exec_json(&[
+            "envs", "update", "staging", "--color", "#EAB308", "--emoji", "🧪",
+        ])
+        .success()
+        .expect("envs update failed");
+    let env: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert_eq!(env["color"], "#eab308");
+    assert_eq!(env["emoji"], "🧪");
+
+    let result = user.exec(&["envs", "update", "staging", "--color", "yellow"]);
+    assert!(
+        result.failed(),
+        "A color that isn't #rrggbb should be rejected"
+    );
+
+    // Unlisted environments follow the listed ones in their current order
+    let json = user
+        .exec_json(&["envs", "reorder", "production"])
+        .success()
+        .expect("envs reorder failed");
+    assert_eq!(env_names(&json), ["production", "development", "staging"]);
+
+    let result = user.exec(&["envs", "reorder", "qa"]);
+    assert!(result.failed(), "An unknown environment should be rejected");
+
+    let json = user
+        .exec_json(&["envs", "list"])
+        .success()
+        .expect("envs list failed");
+    assert_eq!(env_names(&json), ["production", "development", "staging"]);
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("Flag create failed");
+    let json = user
+        .exec_json(&["flags", "get", &flag_key, "--all-envs"])
+        .success()
+        .expect("flags get --all-envs failed");
+    let states: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    let names: Vec<_> = states
+        .as_array()
+        .expect("Expected array")
+        .iter()
+        .map(|s| s["environment"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["production", "development", "staging"]);
+
+    // Clearing the color keeps the emoji
+    let json = user
+        .exec_json(&["envs", "update", "staging", "--color", ""])
+        .success()
+        .expect("envs update failed");
+    let env: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert!(env.get("color").is_none_or(|c| c.is_null()));
+    assert_eq!(env["emoji"], "🧪");
+}
+
+/// Test that a new environment can start from another one's flag values.
+#[tokio::test]
+async fn test_create_environment_copying_values() {
+    let harness = TestHarness::new("env_create")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("creator");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("Flag create failed");
+    user.exec(&["flags", "enable", &flag_key, "-e", "staging"])
+        .success_or_err("flags enable")
+        .expect("flags enable failed");
+    user.exec(&[
+        "flags",
+        "rollout",
+        &flag_key,
+        "--percentage",
+        "30",
+        "-e",
+        "staging",
+    ])
+    .success_or_err("flags rollout")
+    .expect("flags rollout failed");
+
+    let json = user
+        .exec_json(&["envs", "create", "qa", "--copy-from", "staging"])
+        .success()
+        .expect("envs create failed");
+    let env: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert_eq!(env["name"], "qa");
+    assert!(env["api_key"].as_str().unwrap().starts_with("ffl_env_"));
+    assert_eq!(env["protected"], false);
+
+    user.exec(&["envs", "create", "preview"])
+        .success_or_err("envs create")
+        .expect("envs create failed");
+
+    let json = user
+        .exec_json(&["flags", "get", &flag_key])
+        .success()
+        .expect("flags get failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+    assert_eq!(flag["environments"]["qa"]["enabled"], true);
+    assert_eq!(flag["environments"]["qa"]["rollout"], 30);
+    assert_eq!(flag["environments"]["preview"]["enabled"], false);
+
+    // New environments list last, in the order they were added
+    let json = user
+        .exec_json(&["envs", "list"])
+        .success()
+        .expect("envs list failed");
+    let envs: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    let names: Vec<_> = envs
+        .as_array()
+        .expect("Expected array")
+        .iter()
+        .map(|e| e["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        ["development", "staging", "production", "qa", "preview"]
+    );
+
+    for args in [
+        &["envs", "create", "qa"][..],
+        &["envs", "create", "Load Test"],
+        &["envs", "create", "perf", "--copy-from", "nowhere"],
+    ] {
+        assert!(user.exec(args).failed(), "{args:?} should fail");
+    }
+}
+
+/// Test deleting an environment and restoring it with its key and flag values.
+#[tokio::test]
+async fn test_delete_and_restore_environment() {
+    let harness = TestHarness::new("env_delete")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("deleter");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, false)
+        .expect("Flag create failed");
+    let json = user
+        .exec_json(&["envs", "create", "qa"])
+        .success()
+        .expect("envs create failed");
+    let created: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    user.exec(&["flags", "enable", &flag_key, "-e", "qa"])
+        .success_or_err("flags enable")
+        .expect("flags enable failed");
+
+    let json = user
+        .exec_json(&["envs", "delete", "qa"])
+        .success()
+        .expect("envs delete failed");
+    let deleted: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert_eq!(deleted["name"], "qa");
+    assert_eq!(deleted["flag_values"], 1);
+    assert!(user.envs_list().unwrap().iter().all(|e| e.name != "qa"));
+
+    let json = user
+        .exec_json(&["envs", "deleted"])
+        .success()
+        .expect("envs deleted failed");
+    let listed: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert_eq!(listed[0]["name"], "qa");
+
+    let json = user
+        .exec_json(&["envs", "restore", "qa"])
+        .success()
+        .expect("envs restore failed");
+    let restored: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert_eq!(restored["api_key"], created["api_key"]);
+    let json = user
+        .exec_json(&["flags", "get", &flag_key])
+        .success()
+        .expect("flags get failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+    assert_eq!(flag["environments"]["qa"]["enabled"], true);
+    assert!(user.exec(&["envs", "restore", "qa"]).failed());
+
+    // Production is protected: deleting it takes --force
+    let result = user.exec(&["envs", "delete", "production", "--yes"]);
+    assert!(result.failed());
+    assert!(
+        result.stderr().contains("--force"),
+        "stderr: {}",
+        result.stderr()
+    );
+    user.exec_json(&["envs", "delete", "production", "--force"])
+        .success()
+        .expect("envs delete --force failed");
+    assert!(user
+        .envs_list()
+        .unwrap()
+        .iter()
+        .all(|e| e.name != "production"));
+}
+
+/// Test that only project owners can lift an environment's protection or freeze.
+#[tokio::test]
+async fn test_only_owners_unprotect_environments() {
+    let harness = TestHarness::new("env_unprotect")
+        .await
+        .expect("Failed to create test harness");
+
+    let owner = harness.create_user("oona");
+    owner.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let member = harness.create_user("milo");
+    let member_info = member.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    owner
+        .exec(&["orgs", "create", "Guarded"])
+        .success()
+        .expect("orgs create failed");
+    owner
+        .exec(&["orgs", "add-member", &member_info.username])
+        .success()
+        .expect("orgs add-member failed");
+    let project = owner
+        .projects_create(&unique_project_name(), None)
+        .expect("projects create failed");
+    for user in [&owner, &member] {
+        user.projects_use(&project.id).expect("Projects use failed");
+    }
+
+    // Otherwise a member could unprotect production and then delete it
+    let result = member.exec(&["envs", "unprotect", "production"]);
+    assert!(result.failed(), "A member should not be able to unprotect");
+    assert!(member
+        .exec(&["envs", "delete", "production", "--force"])
+        .failed());
+    let json = member
+        .exec_json(&["envs", "list"])
+        .success()
+        .expect("envs list failed");
+    let envs: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    let production = envs
+        .as_array()
+        .expect("Expected array")
+        .iter()
+        .find(|e| e["name"] == "production")
+        .expect("production was deleted");
+    assert_eq!(production["protected"], true);
+
+    // Protecting is still open to members
+    member
+        .exec(&["envs", "protect", "staging"])
+        .success()
+        .expect("envs protect failed");
+    owner
+        .exec(&["envs", "unprotect", "staging"])
+        .success()
+        .expect("envs unprotect failed");
+
+    // Likewise freezing, but not lifting the freeze
+    member
+        .exec(&["envs", "freeze", "staging"])
+        .success()
+        .expect("envs freeze failed");
+    let result = member.exec(&["envs", "unfreeze", "staging"]);
+    assert!(result.failed(), "A member should not be able to unfreeze");
+    assert!(
+        result.stderr().contains("project owners"),
+        "Unexpected error: {}",
+        result.stderr()
+    );
+    owner
+        .exec(&["envs", "unfreeze", "staging"])
+        .success()
+        .expect("envs unfreeze failed");
+
+    // Publishing is for owners, unpublishing for anyone
+    let result = member.exec(&["envs", "publish", "staging"]);
+    assert!(result.failed(), "A member should not be able to publish");
+    assert!(
+        result.stderr().contains("project owners"),
+        "Unexpected error: {}",
+        result.stderr()
+    );
+    owner
+        .exec(&["envs", "publish", "staging"])
+        .success()
+        .expect("envs publish failed");
+    member
+        .exec(&["envs", "unpublish", "staging"])
+        .success()
+        .expect("envs unpublish failed");
+
+    // And requiring change reasons, but not dropping the requirement
+    member
+        .exec(&["projects", "update", "--require-change-reason", "true"])
+        .success()
+        .expect("projects update failed");
+    let result = member.exec(&["projects", "update", "--require-change-reason", "false"]);
+    assert!(
+        result.failed(),
+        "A member should not be able to stop requiring change reasons"
+    );
+    assert!(
+        result.stderr().contains("project owners"),
+        "Unexpected error: {}",
+        result.stderr()
+    );
+    owner
+        .exec(&["projects", "update", "--require-change-reason", "false"])
+        .success()
+        .expect("projects update failed");
+}
+
+/// Test that maintenance mode rejects writes while reads and evaluation keep working.
+#[tokio::test]
+async fn test_read_only_maintenance_mode() {
+    let harness = TestHarness::with_env("read_only", &[("ADMIN_TOKEN", "test-admin-token")])
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("maint");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, true)
+        .expect("Flag create failed");
+
+    let result = user.exec_json(&["envs", "list"]);
+    let envs: serde_json::Value = serde_json::from_str(&result.stdout()).expect("Invalid JSON");
+    let env_key = envs[0]["api_key"]
+        .as_str()
+        .expect("Environment has no API key")
+        .to_string();
+
+    let client = reqwest::Client::new();
+    let maintenance_url = format!("{}/v1/admin/maintenance", harness.server_url);
+    let set_read_only = |read_only: bool| {
+        client
+            .put(&maintenance_url)
+            .bearer_auth("test-admin-token")
+            .json(&serde_json::json!({"read_only": read_only, "message": "Upgrading the database"}))
+            .send()
+    };
+
+    let resp = set_read_only(true).await.expect("Admin request failed");
+    assert!(
+        resp.status().is_success(),
+        "Set maintenance failed: {}",
+        resp.status()
+    );
+
+    let result = user.exec(&["flags", "toggle", &flag_key]);
+    assert!(!result.succeeded());
+    assert!(
+        result.stderr().contains("Upgrading the database"),
+        "stderr: {}",
+        result.stderr()
+    );
+
+    // Reads and evaluation are unaffected
+    assert!(user.flags_get(&flag_key).unwrap().enabled);
+    let resp = client
+        .get(format!(
+            "{}/v1/flags/{flag_key}/evaluate",
+            harness.server_url
+        ))
+        .bearer_auth(&env_key)
+        .send()
+        .await
+        .expect("Evaluate request failed");
+    assert!(
+        resp.status().is_success(),
+        "Evaluate failed: {}",
+        resp.status()
+    );
+
+    let resp = set_read_only(false).await.expect("Admin request failed");
+    assert!(resp.status().is_success());
+
+    let result = user.exec(&["flags", "toggle", &flag_key]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+}
+
+/// Test backing up a running server's SQLite database and restoring it.
+#[tokio::test]
+async fn test_sqlite_backup_and_restore() {
+    let mut harness = TestHarness::with_env("backup", &[("ADMIN_TOKEN", "test-admin-token")])
+        .await
+        .expect("Failed to create test harness");
+    if !harness.database_url().starts_with("sqlite:") {
+        return;
+    }
+
+    let user = harness.create_user("backup");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+    let projects = user.projects_list().expect("Projects list failed");
+    user.projects_use(&projects[0].id)
+        .expect("Projects use failed");
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, None, None, true)
+        .expect("Flag create failed");
+
+    let backup_path = harness.test_dir().join("backup.db");
+    let backup = backup_path.to_str().unwrap();
+    let result = harness.exec_api(&["backup", "--output", backup]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    let result = harness.exec_api(&["backup", "--output", backup]);
+    assert!(!result.succeeded(), "Backup overwrote an existing file");
+
+    let resp = reqwest::Client::new()
+        .get(format!("{}/v1/admin/backup", harness.server_url))
+        .bearer_auth("test-admin-token")
+        .send()
+        .await
+        .expect("Admin request failed");
+    assert!(
+        resp.status().is_success(),
+        "Snapshot failed: {}",
+        resp.status()
+    );
+    let snapshot = resp.bytes().await.expect("Failed to read snapshot");
+    assert!(snapshot.starts_with(b"SQLite format 3"));
+
+    assert!(!user.flags_toggle(&flag_key).expect("Toggle failed"));
+
+    harness.stop_server();
+    let result = harness.exec_api(&["restore", "--input", backup]);
+    assert!(result.succeeded(), "stderr: {}", result.stderr());
+    harness
+        .start_server()
+        .await
+        .expect("Failed to restart server");
+
+    assert!(user.flags_get(&flag_key).unwrap().enabled);
+}
+
+/// Test that `projects stats` counts flags per environment.
+#[tokio::test]
+async fn test_project_stats() {
+    let harness = TestHarness::new("project_stats")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("sam");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    let on = unique_flag_key();
+    let rollout = unique_flag_key();
+    for key in [&on, &rollout] {
+        user.flags_create(key, None, None, false)
+            .expect("Flag create failed");
+        user.exec(&["flags", "enable", key, "-e", "staging"])
+            .success_or_err("flags enable")
+            .expect("flags enable failed");
+    }
+    user.exec(&[
+        "flags",
+        "rollout",
+        &rollout,
+        "--percentage",
+        "25",
+        "-e",
+        "staging",
+    ])
+    .success_or_err("flags rollout")
+    .expect("flags rollout failed");
+
+    let json = user
+        .exec_json(&["projects", "stats"])
+        .success()
+        .expect("projects stats failed");
+    let stats: serde_json::Value = serde_json::from_str(&json).expect("Invalid JSON");
+    assert_eq!(stats["flags"], 2);
+    assert!(stats["last_changed_at"].is_string());
+
+    let env = |name: &str| {
+        stats["environments"]
+            .as_array()
+            .expect("No environments")
+            .iter()
+            .find(|e| e["name"] == name)
+            .cloned()
+            .unwrap_or_else(|| panic!("No {name} environment"))
+    };
+    let staging = env("staging");
+    assert_eq!(staging["enabled"], 2);
+    assert_eq!(staging["enabled_percentage"], 100.0);
+    assert_eq!(staging["partial_rollout"], 1);
+    let production = env("production");
+    assert_eq!(production["enabled"], 0);
+    assert_eq!(production["enabled_percentage"], 0.0);
+    assert_eq!(production["partial_rollout"], 0);
+
+    let pretty = user.exec(&["projects", "stats"]);
+    assert!(
+        pretty.succeeded(),
+        "projects stats failed: {}",
+        pretty.stderr()
+    );
+    assert!(pretty.stdout().contains("2 (100%)"), "{}", pretty.stdout());
+}
+
+/// Test copying an environment's flag values into another project, with a report of what didn't match.
+#[tokio::test]
+async fn test_envs_copy_across_projects() {
+    let harness = TestHarness::new("envs_copy_across_projects")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("judy");
+    user.signup(None, TEST_PASSWORD).expect("Signup failed");
+
+    let shared = unique_flag_key();
+    let typed = unique_flag_key();
+    let only_source = unique_flag_key();
+    let only_target = unique_flag_key();
+
+    let source = user
+        .projects_create(&unique_project_name(), None)
+        .expect("projects create failed");
+    user.projects_use(&source.id).expect("Projects use failed");
+    for key in [&shared, &typed, &only_source] {
+        user.flags_create(key, None, None, false)
+            .expect("flags create failed");
+    }
+    user.exec(&["flags", "toggle", &shared, "-e", "staging"])
+        .success()
+        .expect("flags toggle failed");
+
+    let target = user
+        .projects_create(&unique_project_name(), None)
+        .expect("projects create failed");
+    user.projects_use(&target.id).expect("Projects use failed");
+    user.flags_create(&shared, None, None, false)
+        .expect("flags create failed");
+    user.flags_create(&typed, None, Some("string"), false)
+        .expect("flags create failed");
+    user.flags_create(&only_target, None, None, false)
+        .expect("flags create failed");
+
+    let from = format!("{}/staging", source.slug);
+    let to = format!("{}/staging", target.slug);
+    let copy = |extra: &[&str]| -> serde_json::Value {
+        let mut args = vec!["envs", "copy", "--from", &from, "--to", &to];
+        args.extend(extra);
+        let json = user.exec_json(&args).success().expect("envs copy failed");
+        serde_json::from_str(&json).expect("Invalid copy JSON")
+    };
+
+    let preview = copy(&["--dry-run"]);
+    assert_eq!(preview["dry_run"], true);
+    assert_eq!(preview["flags"][0]["key"], shared.as_str());
+    assert_eq!(preview["flags"][0]["changed"], true);
+    assert_eq!(
+        preview["missing_in_target"],
+        serde_json::json!([only_source])
+    );
+    assert_eq!(
+        preview["missing_in_source"],
+        serde_json::json!([only_target])
+    );
+    assert_eq!(preview["skipped"][0]["key"], typed.as_str());
+
+    let copied = copy(&["--yes"]);
+    assert_eq!(copied["dry_run"], false);
+    assert_eq!(copied["flags"][0]["enabled"], true);
+
+    let json = user
+        .exec_json(&["flags", "get", &shared, "-e", "staging"])
+        .success()
+        .expect("flags get failed");
+    let flag: serde_json::Value = serde_json::from_str(&json).expect("Invalid flag JSON");
+    assert_eq!(
+        flag["enabled"], true,
+        "Copied flag should be on in the target"
+    );
+
+    // Copying again changes nothing
+    let again = copy(&["--dry-run"]);
+    assert_eq!(again["flags"][0]["changed"], false);
+
+    let result = user.exec(&["envs", "copy", "--from", &source.slug, "--to", &to]);
+    assert!(
+        result.failed(),
+        "A source without an environment should be rejected"
+    );
+}
@@ -177,3 +177,48 @@ async fn test_signup_enables_all_flag_operations() {
     let flag_after = user.flags_get(&flag_key).expect("flags get failed");
     assert!(!flag_after.enabled, "Flag should remain disabled");
 }
+
+/// Test that scripts can capture single values with --output and --quiet.
+#[tokio::test]
+async fn test_script_mode_output_and_quiet() {
+    let harness = TestHarness::new("signup_script_mode")
+        .await
+        .expect("Failed to create test harness");
+
+    let user = harness.create_user("erin");
+
+    // The key alone, ready for API_KEY=$(flaglite signup --output .api_key)
+    let signup = user.exec(&[
+        "signup",
+        "--password",
+        TEST_PASSWORD,
+        "--output",
+        ".api_key",
+    ]);
+    assert!(signup.succeeded(), "signup failed: {}", signup.stderr());
+    let api_key = signup.stdout();
+    assert!(api_key.starts_with("flg_"), "stdout: {api_key}");
+    assert_eq!(api_key.lines().count(), 1, "stdout: {api_key}");
+
+    let flag_key = unique_flag_key();
+    user.flags_create(&flag_key, Some("Script Flag"), None, true)
+        .expect("flags create failed");
+
+    let enabled = user.exec(&["flags", "get", &flag_key, "--output", ".enabled"]);
+    assert!(
+        enabled.succeeded(),
+        "flags get failed: {}",
+        enabled.stderr()
+    );
+    assert_eq!(enabled.stdout(), "true");
+
+    // A path the result doesn't have fails, with nothing on stdout
+    let missing = user.exec(&["flags", "get", &flag_key, "--output", ".no_such_field"]);
+    assert_eq!(missing.exit_code(), Some(4));
+    assert!(missing.stdout().is_empty(), "stdout: {}", missing.stdout());
+    assert!(missing.stderr().contains(".no_such_field"));
+
+    let toggled = user.exec(&["--quiet", "flags", "toggle", &flag_key]);
+    assert!(toggled.succeeded(), "toggle failed: {}", toggled.stderr());
+    assert!(toggled.stdout().is_empty(), "stdout: {}", toggled.stdout());
+}
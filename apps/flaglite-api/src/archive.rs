@@ -0,0 +1,287 @@
+//! Project archives - a project's settings, environments and flags as a
+//! portable `.tar.gz`
+//!
+//! An archive is a directory named after the project's slug holding one JSON
+//! file per kind of record. Records refer to each other by flag key and
+//! environment name rather than id, so an archive can be imported into any
+//! FlagLite instance. Keys and secrets are never included: an imported
+//! project gets new ones.
+
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+use crate::templates::TemplateState;
+
+/// Version of the archive layout; archives from newer servers are refused
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Most bytes read out of an archive, so a small upload can't expand without
+/// bound
+const MAX_UNPACKED_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: u32,
+    /// Version of the server that wrote the archive
+    pub flaglite_version: String,
+    pub exported_at: DateTime<Utc>,
+    pub project_id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedProject {
+    pub name: String,
+    pub slug: String,
+    #[serde(default)]
+    pub require_change_reason: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedEnvironment {
+    pub name: String,
+    #[serde(default)]
+    pub protected: bool,
+    #[serde(default)]
+    pub sort_order: i32,
+    pub color: Option<String>,
+    pub emoji: Option<String>,
+    pub cache_max_age: Option<i32>,
+    pub cache_stale_while_revalidate: Option<i32>,
+    /// Served without a key; the import gets a client ID of its own
+    #[serde(default)]
+    pub public: bool,
+    #[serde(default)]
+    pub require_approval: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedFlag {
+    pub key: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub owner: Option<String>,
+    pub repository: Option<String>,
+    pub code_path: Option<String>,
+    pub flag_type: String,
+    pub json_schema: Option<serde_json::Value>,
+    #[serde(default)]
+    pub default_enabled: bool,
+    pub default_value: Option<serde_json::Value>,
+    #[serde(default)]
+    pub public: bool,
+}
+
+/// A flag's current state in one environment; history isn't archived
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedValue {
+    pub flag: String,
+    pub environment: String,
+    pub enabled: bool,
+    pub rollout_percentage: i32,
+    pub value: Option<serde_json::Value>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A user on a flag's allowlist or denylist in one environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedListedUser {
+    pub flag: String,
+    pub environment: String,
+    pub user_id: String,
+    /// `allow` or `deny`
+    pub list: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedTemplate {
+    pub name: String,
+    pub description: Option<String>,
+    pub default_enabled: bool,
+    pub default_rollout: i32,
+    /// By environment name
+    #[serde(default)]
+    pub environments: BTreeMap<String, TemplateState>,
+}
+
+/// Everything in an archive
+#[derive(Debug, Clone)]
+pub struct ProjectArchive {
+    pub manifest: Manifest,
+    pub project: ArchivedProject,
+    pub environments: Vec<ArchivedEnvironment>,
+    pub flags: Vec<ArchivedFlag>,
+    pub values: Vec<ArchivedValue>,
+    pub user_lists: Vec<ArchivedListedUser>,
+    pub templates: Vec<ArchivedTemplate>,
+}
+
+impl ProjectArchive {
+    /// The archive as a gzipped tarball
+    pub fn to_tar_gz(&self) -> std::io::Result<Vec<u8>> {
+        let files = [
+            ("manifest.json", serde_json::to_vec_pretty(&self.manifest)?),
+            ("project.json", serde_json::to_vec_pretty(&self.project)?),
+            (
+                "environments.json",
+                serde_json::to_vec_pretty(&self.environments)?,
+            ),
+            ("flags.json", serde_json::to_vec_pretty(&self.flags)?),
+            ("values.json", serde_json::to_vec_pretty(&self.values)?),
+            (
+                "user_lists.json",
+                serde_json::to_vec_pretty(&self.user_lists)?,
+            ),
+            (
+                "templates.json",
+                serde_json::to_vec_pretty(&self.templates)?,
+            ),
+        ];
+
+        let mut tar = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, contents) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(contents.len() as u64);
+            header.set_mode(0o644);
+            header.set_mtime(self.manifest.exported_at.timestamp().max(0) as u64);
+            header.set_cksum();
+            tar.append_data(
+                &mut header,
+                format!("{}/{name}", self.project.slug),
+                contents.as_slice(),
+            )?;
+        }
+        tar.into_inner()?.finish()
+    }
+
+    /// Read an archive written by [`ProjectArchive::to_tar_gz`]
+    ///
+    /// Files are found by name whatever directory they are in, and unknown
+    /// files are ignored, so a repacked archive still reads.
+    pub fn from_tar_gz(bytes: &[u8]) -> Result<Self, String> {
+        let invalid = |e: std::io::Error| format!("Not a project archive (.tar.gz): {e}");
+        let mut files = HashMap::new();
+        let mut tar = tar::Archive::new(GzDecoder::new(bytes).take(MAX_UNPACKED_BYTES));
+        for entry in tar.entries().map_err(invalid)? {
+            let mut entry = entry.map_err(invalid)?;
+            let path = entry.path().map_err(invalid)?;
+            let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let name = name.to_string();
+            let mut contents = Vec::new();
+            entry.read_to_end(&mut contents).map_err(invalid)?;
+            files.insert(name, contents);
+        }
+
+        let manifest: Manifest = file(&files, "manifest.json")?;
+        if manifest.format > FORMAT_VERSION {
+            return Err(format!(
+                "The archive has format {}, but this server reads up to {FORMAT_VERSION}; upgrade it first",
+                manifest.format
+            ));
+        }
+        Ok(ProjectArchive {
+            manifest,
+            project: file(&files, "project.json")?,
+            environments: file(&files, "environments.json")?,
+            flags: file(&files, "flags.json")?,
+            values: file(&files, "values.json")?,
+            user_lists: optional_file(&files, "user_lists.json")?,
+            templates: optional_file(&files, "templates.json")?,
+        })
+    }
+}
+
+fn file<T: DeserializeOwned>(files: &HashMap<String, Vec<u8>>, name: &str) -> Result<T, String> {
+    let contents = files
+        .get(name)
+        .ok_or_else(|| format!("The archive has no {name}"))?;
+    serde_json::from_slice(contents).map_err(|e| format!("Invalid {name}: {e}"))
+}
+
+/// A file a hand-written archive may leave out, read as empty
+fn optional_file<T: DeserializeOwned>(
+    files: &HashMap<String, Vec<u8>>,
+    name: &str,
+) -> Result<Vec<T>, String> {
+    if files.contains_key(name) {
+        file(files, name)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn archive() -> ProjectArchive {
+        let now = Utc::now();
+        ProjectArchive {
+            manifest: Manifest {
+                format: FORMAT_VERSION,
+                flaglite_version: "0.1.1".to_string(),
+                exported_at: now,
+                project_id: "p1".to_string(),
+            },
+            project: ArchivedProject {
+                name: "Checkout".to_string(),
+                slug: "checkout".to_string(),
+                require_change_reason: true,
+            },
+            environments: vec![ArchivedEnvironment {
+                name: "production".to_string(),
+                protected: true,
+                sort_order: 2,
+                color: Some("#ff0000".to_string()),
+                emoji: None,
+                cache_max_age: Some(30),
+                cache_stale_while_revalidate: None,
+                public: false,
+                require_approval: true,
+            }],
+            flags: vec![],
+            values: vec![],
+            user_lists: vec![ArchivedListedUser {
+                flag: "new-checkout".to_string(),
+                environment: "production".to_string(),
+                user_id: "u1".to_string(),
+                list: "allow".to_string(),
+            }],
+            templates: vec![],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let bytes = archive().to_tar_gz().unwrap();
+        let read = ProjectArchive::from_tar_gz(&bytes).unwrap();
+        assert_eq!(read.project.name, "Checkout");
+        assert!(read.project.require_change_reason);
+        assert_eq!(read.environments[0].cache_max_age, Some(30));
+        assert_eq!(read.user_lists[0].user_id, "u1");
+    }
+
+    #[test]
+    fn test_newer_format_is_refused() {
+        let mut newer = archive();
+        newer.manifest.format = FORMAT_VERSION + 1;
+        let bytes = newer.to_tar_gz().unwrap();
+        let err = ProjectArchive::from_tar_gz(&bytes).unwrap_err();
+        assert!(err.contains("upgrade"), "{err}");
+    }
+
+    #[test]
+    fn test_garbage_is_not_an_archive() {
+        let err = ProjectArchive::from_tar_gz(b"{\"name\": \"x\"}").unwrap_err();
+        assert!(err.starts_with("Not a project archive"), "{err}");
+    }
+}
@@ -1,5 +1,9 @@
 use crate::error::{AppError, Result};
-use crate::models::{is_user_api_key, AppState, Claims, Environment, Project, User};
+use crate::jwt::JwtKeys;
+use crate::models::{
+    is_user_api_key, personal_org_id, ApiKey, ApiKeyScope, AppState, Claims, Environment,
+    OrgMember, Project, User,
+};
 use argon2::{
     password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2,
@@ -10,12 +14,11 @@ use axum::{
     http::{header::AUTHORIZATION, request::Parts},
 };
 use chrono::Utc;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use sha2::{Digest, Sha256};
 
 const JWT_EXPIRY_DAYS: i64 = 7;
 
-pub fn create_jwt(user: &User, secret: &str) -> Result<String> {
+pub fn create_jwt(user: &User, keys: &JwtKeys) -> Result<String> {
     let now = Utc::now().timestamp();
     let expiry = now + (JWT_EXPIRY_DAYS * 24 * 60 * 60);
 
@@ -24,25 +27,26 @@ pub fn create_jwt(user: &User, secret: &str) -> Result<String> {
         username: user.username.clone(),
         iat: now,
         exp: expiry,
+        epoch: user.session_epoch,
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    )?;
-
-    Ok(token)
+    keys.sign(&claims)
 }
 
-pub fn verify_jwt(token: &str, secret: &str) -> Result<Claims> {
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(secret.as_bytes()),
-        &Validation::default(),
-    )?;
+pub fn verify_jwt(token: &str, keys: &JwtKeys) -> Result<Claims> {
+    keys.verify(token)
+}
 
-    Ok(token_data.claims)
+/// The user a session (JWT) belongs to; sessions issued before the user's
+/// `session_epoch` last moved on are rejected
+async fn session_user(state: &AppState, token: &str) -> Result<User> {
+    let claims = verify_jwt(token, &state.jwt_keys)?;
+    state
+        .storage
+        .get_user_by_id(&claims.sub)
+        .await?
+        .filter(|user| user.session_epoch == claims.epoch)
+        .ok_or(AppError::Unauthorized)
 }
 
 /// Hash a password using Argon2id
@@ -75,32 +79,50 @@ pub fn hash_api_key(key: &str) -> String {
 
 // ============ Extractors ============
 
-/// Extracts the authenticated user from JWT
-pub struct AuthUser(pub User);
+/// Scope the matched route needs; the router attaches it to every request
+fn required_scope(parts: &Parts) -> ApiKeyScope {
+    // Without it (a route built elsewhere), only full access will do
+    parts
+        .extensions
+        .get::<ApiKeyScope>()
+        .copied()
+        .unwrap_or(ApiKeyScope::Admin)
+}
 
-#[async_trait]
-impl FromRequestParts<AppState> for AuthUser {
-    type Rejection = AppError;
+/// Reject a user API key whose scope doesn't cover the request
+fn check_scope(scope: ApiKeyScope, parts: &Parts) -> Result<()> {
+    let required = required_scope(parts);
+    if scope < required {
+        return Err(AppError::InsufficientScope { scope, required });
+    }
+    Ok(())
+}
 
-    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self> {
-        let auth_header = parts
-            .headers
-            .get(AUTHORIZATION)
-            .and_then(|v| v.to_str().ok())
-            .ok_or(AppError::Unauthorized)?;
+/// The user API key `token` is, if it exists
+async fn user_api_key(state: &AppState, token: &str) -> Result<ApiKey> {
+    state
+        .storage
+        .get_api_key_by_hash(&hash_api_key(token))
+        .await?
+        .ok_or(AppError::InvalidApiKey)
+}
 
-        let token = auth_header
-            .strip_prefix("Bearer ")
-            .ok_or(AppError::Unauthorized)?;
+/// Extracts the authenticated user from JWT or user API key
+pub struct AuthUser {
+    pub user: User,
+    /// Org an org-scoped API key is limited to; None reaches all of the user's orgs
+    pub org_scope: Option<String>,
+    /// What an API key may do; sessions (JWTs) have full access
+    pub scope: ApiKeyScope,
+}
 
+impl AuthUser {
+    /// The user a JWT or user API key belongs to, for credentials that don't
+    /// arrive in the Authorization header
+    pub async fn from_token(state: &AppState, token: &str) -> Result<Self> {
         // Check if it's a user API key (flg_ prefix)
         if is_user_api_key(token) {
-            let key_hash = hash_api_key(token);
-            let api_key = state
-                .storage
-                .get_api_key_by_hash(&key_hash)
-                .await?
-                .ok_or(AppError::InvalidApiKey)?;
+            let api_key = user_api_key(state, token).await?;
 
             let user = state
                 .storage
@@ -108,19 +130,148 @@ impl FromRequestParts<AppState> for AuthUser {
                 .await?
                 .ok_or(AppError::Unauthorized)?;
 
-            return Ok(AuthUser(user));
+            return Ok(AuthUser {
+                user,
+                scope: api_key.scope(),
+                org_scope: api_key.org_id,
+            });
         }
 
         // Otherwise treat as JWT
-        let claims = verify_jwt(token, &state.jwt_secret)?;
+        let user = session_user(state, token).await?;
+
+        Ok(AuthUser {
+            user,
+            org_scope: None,
+            scope: ApiKeyScope::Admin,
+        })
+    }
 
-        let user = state
+    /// The caller's membership in an org, or NotFound if they can't reach it
+    pub async fn org_member(&self, state: &AppState, org_id: &str) -> Result<OrgMember> {
+        if matches!(&self.org_scope, Some(scope) if scope != org_id) {
+            return Err(AppError::NotFound("Organization not found".to_string()));
+        }
+        state
             .storage
-            .get_user_by_id(&claims.sub)
+            .get_org_member(org_id, &self.user.id)
+            .await?
+            .ok_or_else(|| AppError::NotFound("Organization not found".to_string()))
+    }
+
+    /// Org new projects go into: the requested one, else the key's org, else the personal org
+    pub async fn target_org(&self, state: &AppState, requested: Option<&str>) -> Result<String> {
+        let org_id = match (requested, &self.org_scope) {
+            (Some(org_id), _) => org_id.to_string(),
+            (None, Some(scope)) => scope.clone(),
+            (None, None) => personal_org_id(&self.user.id),
+        };
+        self.org_member(state, &org_id).await?;
+        Ok(org_id)
+    }
+
+    /// Projects in every org the caller can reach and those shared with them,
+    /// or just `org_id`'s when given, newest first
+    pub async fn projects(&self, state: &AppState, org_id: Option<&str>) -> Result<Vec<Project>> {
+        let org_ids = match org_id {
+            Some(org_id) => vec![self.org_member(state, org_id).await?.org_id],
+            None => state
+                .storage
+                .list_organizations_by_user(&self.user.id)
+                .await?
+                .into_iter()
+                .map(|org| org.id)
+                .filter(|id| self.org_scope.as_ref().is_none_or(|scope| scope == id))
+                .collect(),
+        };
+
+        let mut projects = Vec::new();
+        for org_id in &org_ids {
+            projects.extend(state.storage.list_projects_by_org(org_id).await?);
+        }
+        if org_id.is_none() && self.org_scope.is_none() {
+            for project in state
+                .storage
+                .list_projects_shared_with(&self.user.id)
+                .await?
+            {
+                if !projects.iter().any(|p| p.id == project.id) {
+                    projects.push(project);
+                }
+            }
+        }
+        projects.sort_by_key(|p| std::cmp::Reverse(p.created_at));
+        Ok(projects)
+    }
+
+    /// Load a project the caller may access, by id or slug; NotFound (not
+    /// Forbidden) otherwise, so project ids can't be probed
+    pub async fn project(&self, state: &AppState, project_id: &str) -> Result<Project> {
+        let project = match state.storage.get_project_by_id(project_id).await? {
+            Some(project) => project,
+            None => return self.project_by_slug(state, project_id).await,
+        };
+
+        let allowed = match &project.org_id {
+            Some(org_id) => self.org_member(state, org_id).await.is_ok(),
+            None => self.org_scope.is_none() && project.user_id == self.user.id,
+        };
+        // Projects shared by invite link aren't in any org a key is limited to
+        let allowed = allowed
+            || (self.org_scope.is_none()
+                && state
+                    .storage
+                    .get_project_member(&project.id, &self.user.id)
+                    .await?
+                    .is_some());
+        if !allowed {
+            return Err(AppError::NotFound("Project not found".to_string()));
+        }
+
+        Ok(project)
+    }
+
+    /// Slugs are unique per creator, so the caller's own project wins over
+    /// org members' projects with the same slug
+    async fn project_by_slug(&self, state: &AppState, slug: &str) -> Result<Project> {
+        let mut matches: Vec<Project> = self
+            .projects(state, None)
             .await?
+            .into_iter()
+            .filter(|p| p.slug == slug)
+            .collect();
+
+        if let Some(i) = matches.iter().position(|p| p.user_id == self.user.id) {
+            return Ok(matches.swap_remove(i));
+        }
+        match matches.len() {
+            0 => Err(AppError::NotFound("Project not found".to_string())),
+            1 => Ok(matches.remove(0)),
+            _ => Err(AppError::BadRequest(format!(
+                "Several projects use the slug '{slug}'; address the project by its id"
+            ))),
+        }
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self> {
+        let auth_header = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or(AppError::Unauthorized)?;
+
+        let token = auth_header
+            .strip_prefix("Bearer ")
             .ok_or(AppError::Unauthorized)?;
 
-        Ok(AuthUser(user))
+        let auth = AuthUser::from_token(state, token).await?;
+        check_scope(auth.scope, parts)?;
+        Ok(auth)
     }
 }
 
@@ -156,12 +307,8 @@ impl FromRequestParts<AppState> for AuthProject {
 
         // Check if it's a user API key (flg_ prefix)
         if is_user_api_key(token) {
-            let key_hash = hash_api_key(token);
-            let api_key = state
-                .storage
-                .get_api_key_by_hash(&key_hash)
-                .await?
-                .ok_or(AppError::InvalidApiKey)?;
+            let api_key = user_api_key(state, token).await?;
+            check_scope(api_key.scope(), parts)?;
 
             let project = state
                 .storage
@@ -173,11 +320,11 @@ impl FromRequestParts<AppState> for AuthProject {
         }
 
         // Otherwise treat as JWT and get user's first project
-        let claims = verify_jwt(token, &state.jwt_secret)?;
+        let user = session_user(state, token).await?;
 
         let project = state
             .storage
-            .get_first_project_by_user(&claims.sub)
+            .get_first_project_by_user(&user.id)
             .await?
             .ok_or(AppError::NotFound("No project found".to_string()))?;
 
@@ -231,7 +378,7 @@ impl FromRequestParts<AppState> for AuthEnvironment {
 #[allow(dead_code)] // Kept for future SDK use
 pub enum FlexAuth {
     Project(Project),
-    Environment(Environment, Project),
+    Environment(Box<Environment>, Project),
 }
 
 #[async_trait]
@@ -274,17 +421,13 @@ impl FromRequestParts<AppState> for FlexAuth {
                     "Project not found for environment".to_string(),
                 ))?;
 
-            return Ok(FlexAuth::Environment(env, project));
+            return Ok(FlexAuth::Environment(Box::new(env), project));
         }
 
         // Check if it's a user API key (flg_ prefix)
         if is_user_api_key(token) {
-            let key_hash = hash_api_key(token);
-            let api_key = state
-                .storage
-                .get_api_key_by_hash(&key_hash)
-                .await?
-                .ok_or(AppError::InvalidApiKey)?;
+            let api_key = user_api_key(state, token).await?;
+            check_scope(api_key.scope(), parts)?;
 
             let project = state
                 .storage
@@ -296,14 +439,44 @@ impl FromRequestParts<AppState> for FlexAuth {
         }
 
         // JWT auth
-        let claims = verify_jwt(token, &state.jwt_secret)?;
+        let user = session_user(state, token).await?;
 
         let project = state
             .storage
-            .get_first_project_by_user(&claims.sub)
+            .get_first_project_by_user(&user.id)
             .await?
             .ok_or(AppError::NotFound("No project found".to_string()))?;
 
         Ok(FlexAuth::Project(project))
     }
 }
+
+/// Instance operator, authenticated with ADMIN_TOKEN
+pub struct AuthAdmin;
+
+#[async_trait]
+impl FromRequestParts<AppState> for AuthAdmin {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> Result<Self> {
+        let admin_token = state.admin_token.as_deref().ok_or_else(|| {
+            AppError::Forbidden(
+                "The admin API is disabled; set ADMIN_TOKEN to enable it".to_string(),
+            )
+        })?;
+
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or(AppError::Unauthorized)?;
+
+        // Compare digests so the comparison time doesn't depend on the token
+        if hash_api_key(token) != hash_api_key(admin_token) {
+            return Err(AppError::InvalidApiKey);
+        }
+
+        Ok(AuthAdmin)
+    }
+}
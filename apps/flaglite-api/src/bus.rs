@@ -0,0 +1,291 @@
+//! Domain event bus
+//!
+//! Handlers publish what happened to a project (flags changed, an audited
+//! action) as a [`DomainEvent`] on an in-process broadcast channel, and don't
+//! know who reads it. Side effects subscribe instead: each [`Subscriber`] in
+//! [`subscribers`] runs on its own task and sees events in publish order, and
+//! WebSocket connections (`/v1/ws`) read the same channel. A new side effect
+//! is a new subscriber rather than a change to every handler.
+//!
+//! Like quotas, the bus is per API instance: subscribers see the events of
+//! the instance they run in. A subscriber that falls more than
+//! [`CHANNEL_CAPACITY`] events behind misses the oldest; handled, failed and
+//! missed events are counted per subscriber on `GET /metrics`. That makes the
+//! bus fine for optional side effects but not for records that must be kept:
+//! [`audit`] writes to the log itself, before publishing.
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+use crate::models::AppState;
+
+/// Events buffered for a slow subscriber before it starts missing them
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DomainEvent {
+    /// Flags were created, changed or deleted; SDK polls see a new revision
+    FlagChanged {
+        project_id: String,
+        keys: Vec<String>,
+        deleted: bool,
+        at: DateTime<Utc>,
+    },
+    /// Someone did something worth recording, such as freezing an environment
+    Audit {
+        project_id: String,
+        /// Dotted name, e.g. `environment.frozen`
+        action: String,
+        /// Username of whoever did it
+        actor: String,
+        message: String,
+        at: DateTime<Utc>,
+    },
+}
+
+impl DomainEvent {
+    pub fn project_id(&self) -> &str {
+        match self {
+            DomainEvent::FlagChanged { project_id, .. } => project_id,
+            DomainEvent::Audit { project_id, .. } => project_id,
+        }
+    }
+}
+
+/// A side effect of domain events, run by [`EventBus::start`]
+#[async_trait]
+pub trait Subscriber: Send + Sync {
+    /// Names the subscriber in logs and metrics
+    fn name(&self) -> &'static str;
+
+    /// Act on one event. An error is logged and counted; the subscriber
+    /// carries on with the next event.
+    async fn handle(&self, state: &AppState, event: &DomainEvent) -> anyhow::Result<()>;
+}
+
+/// Every subscriber the server runs
+pub fn subscribers() -> Vec<Arc<dyn Subscriber>> {
+    Vec::new()
+}
+
+/// What happened to the events one subscriber was sent
+#[derive(Debug, Clone, Copy, Default)]
+struct Delivery {
+    handled: u64,
+    failed: u64,
+    /// Dropped because the subscriber fell too far behind
+    missed: u64,
+}
+
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<DomainEvent>,
+    deliveries: Arc<Mutex<BTreeMap<&'static str, Delivery>>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            sender,
+            deliveries: Default::default(),
+        }
+    }
+}
+
+impl EventBus {
+    pub fn publish(&self, event: DomainEvent) {
+        // Nobody listening is fine; the event is simply dropped
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<DomainEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Run each subscriber on a task of its own until the server exits;
+    /// subscribed before returning, so no event published after is missed
+    pub fn start(state: &AppState, subscribers: Vec<Arc<dyn Subscriber>>) {
+        for subscriber in subscribers {
+            let mut events = state.bus.subscribe();
+            let state = state.clone();
+            tokio::spawn(async move {
+                let name = subscriber.name();
+                loop {
+                    match events.recv().await {
+                        Ok(event) => {
+                            let result = subscriber.handle(&state, &event).await;
+                            if let Err(e) = &result {
+                                tracing::warn!("Event subscriber {name} failed: {e:#}");
+                            }
+                            state.bus.record(name, |d| match result {
+                                Ok(()) => d.handled += 1,
+                                Err(_) => d.failed += 1,
+                            });
+                        }
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            tracing::warn!(
+                                "Event subscriber {name} fell behind and missed {missed} event(s)"
+                            );
+                            state.bus.record(name, |d| d.missed += missed);
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    }
+                }
+            });
+        }
+    }
+
+    fn record(&self, subscriber: &'static str, update: impl FnOnce(&mut Delivery)) {
+        update(
+            self.deliveries
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(subscriber)
+                .or_default(),
+        );
+    }
+
+    /// Per-subscriber event counts in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let deliveries = self
+            .deliveries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
+        let name = "flaglite_event_subscriber_events_total";
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP {name} Domain events sent to each subscriber, by outcome"
+        );
+        let _ = writeln!(out, "# TYPE {name} counter");
+        for (subscriber, delivery) in &deliveries {
+            for (outcome, count) in [
+                ("handled", delivery.handled),
+                ("failed", delivery.failed),
+                ("missed", delivery.missed),
+            ] {
+                let _ = writeln!(
+                    out,
+                    "{name}{{subscriber=\"{subscriber}\",outcome=\"{outcome}\"}} {count}"
+                );
+            }
+        }
+        out
+    }
+}
+
+/// Record an action on a project: written to the log before returning, so a
+/// lagging subscriber or a shutdown can't lose it, then published as an audit
+/// event for WebSocket clients and subscribers
+pub fn audit(state: &AppState, project_id: &str, action: &str, actor: &str, message: String) {
+    tracing::info!("{message}");
+    state.bus.publish(DomainEvent::Audit {
+        project_id: project_id.to_string(),
+        action: action.to_string(),
+        actor: actor.to_string(),
+        message,
+        at: Utc::now(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SignupMode;
+    use crate::jobs::JobMetrics;
+    use crate::jwt::JwtKeys;
+    use crate::lockout::{LockoutPolicy, LoginThrottle};
+    use crate::mailer::LogMailer;
+    use crate::maintenance::Maintenance;
+    use crate::public::{PublicLimiter, PublicRateLimit};
+    use crate::quota::QuotaLimiter;
+    use crate::retention::RetentionPolicy;
+    use crate::storage::memory::MemoryStorage;
+    use crate::usage::UsageRecorder;
+    use tokio::sync::mpsc;
+
+    fn state() -> AppState {
+        AppState {
+            storage: Arc::new(MemoryStorage::new()),
+            jwt_keys: Arc::new(JwtKeys::random()),
+            usage: Arc::new(UsageRecorder::new()),
+            signup_mode: SignupMode::Open,
+            events: None,
+            quotas: Arc::new(QuotaLimiter::new()),
+            admin_token: None,
+            mailer: Arc::new(LogMailer),
+            maintenance: Arc::new(Maintenance::new(false)),
+            logins: Arc::new(LoginThrottle::new(LockoutPolicy::default())),
+            trust_proxy: false,
+            jobs: Arc::new(JobMetrics::default()),
+            storage_metrics: Default::default(),
+            bus: Default::default(),
+            public: Arc::new(PublicLimiter::new(PublicRateLimit::default())),
+            retention: RetentionPolicy::default(),
+            health_checks: Default::default(),
+        }
+    }
+
+    /// Forwards the project of every event it sees, failing on audits
+    struct Probe(mpsc::UnboundedSender<String>);
+
+    #[async_trait]
+    impl Subscriber for Probe {
+        fn name(&self) -> &'static str {
+            "probe"
+        }
+
+        async fn handle(&self, _state: &AppState, event: &DomainEvent) -> anyhow::Result<()> {
+            self.0.send(event.project_id().to_string())?;
+            match event {
+                DomainEvent::Audit { .. } => anyhow::bail!("audits not wanted"),
+                DomainEvent::FlagChanged { .. } => Ok(()),
+            }
+        }
+    }
+
+    fn changed(project_id: &str) -> DomainEvent {
+        DomainEvent::FlagChanged {
+            project_id: project_id.to_string(),
+            keys: vec!["dark-mode".to_string()],
+            deleted: false,
+            at: Utc::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscribers_see_events_in_order_and_are_counted() {
+        let state = state();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        EventBus::start(&state, vec![Arc::new(Probe(tx))]);
+
+        state.bus.publish(changed("p1"));
+        audit(&state, "p2", "project.imported", "alice", "imported".into());
+        state.bus.publish(changed("p3"));
+
+        for expected in ["p1", "p2", "p3"] {
+            assert_eq!(rx.recv().await.unwrap(), expected);
+        }
+        // p2's failure was counted before p3 was handled
+        let metrics = state.bus.render();
+        assert!(metrics.contains(
+            "flaglite_event_subscriber_events_total{subscriber=\"probe\",outcome=\"failed\"} 1\n"
+        ));
+    }
+
+    #[test]
+    fn test_events_keep_their_wire_format() {
+        let event = serde_json::to_value(changed("p1")).unwrap();
+        assert_eq!(event["type"], "flag_changed");
+        assert_eq!(event["keys"][0], "dark-mode");
+    }
+}
@@ -0,0 +1,161 @@
+//! Small in-process TTL cache
+
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A map whose entries expire `ttl` after insertion, holding at most
+/// `capacity` of them. Expired entries are swept out as new ones come in,
+/// and once the cache is full the oldest entry makes room for the next.
+pub struct TtlCache<K, V> {
+    ttl: Duration,
+    capacity: usize,
+    entries: Mutex<Entries<K, V>>,
+}
+
+struct Entries<K, V> {
+    map: HashMap<K, (Instant, V)>,
+    /// Keys in insertion order, oldest first. A record is stale once its key
+    /// has been removed or inserted again since.
+    order: VecDeque<(Instant, K)>,
+}
+
+impl<K: Eq + Hash, V> Entries<K, V> {
+    /// Drop the oldest record, and its entry unless the record is stale
+    fn pop_oldest(&mut self) -> Option<Instant> {
+        let (inserted, key) = self.order.pop_front()?;
+        if self.map.get(&key).is_some_and(|(at, _)| *at == inserted) {
+            self.map.remove(&key);
+        }
+        Some(inserted)
+    }
+
+    /// Forget records whose entry is gone or was replaced
+    fn compact(&mut self) {
+        let map = &self.map;
+        self.order
+            .retain(|(inserted, key)| map.get(key).is_some_and(|(at, _)| at == inserted));
+    }
+}
+
+impl<K: Eq + Hash + Clone, V: Clone> TtlCache<K, V> {
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            ttl,
+            capacity: capacity.max(1),
+            entries: Mutex::new(Entries {
+                map: HashMap::new(),
+                order: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Get a live entry, dropping it if it has expired
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let (inserted, value) = entries.map.get(key)?;
+        if inserted.elapsed() < self.ttl {
+            return Some(value.clone());
+        }
+        entries.map.remove(key);
+        None
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+
+        // Records are in insertion order, so the expired ones are at the front
+        while entries
+            .order
+            .front()
+            .is_some_and(|(inserted, _)| now.duration_since(*inserted) >= self.ttl)
+        {
+            entries.pop_oldest();
+        }
+        while entries.map.len() >= self.capacity && !entries.map.contains_key(&key) {
+            if entries.pop_oldest().is_none() {
+                break;
+            }
+        }
+
+        entries.map.insert(key.clone(), (now, value));
+        entries.order.push_back((now, key));
+        if entries.order.len() > 2 * self.capacity {
+            entries.compact();
+        }
+    }
+
+    pub fn remove(&self, key: &K) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.map.remove(key);
+    }
+
+    /// Keep only the entries for which `keep` returns true
+    pub fn retain(&self, mut keep: impl FnMut(&K, &V) -> bool) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.map.retain(|key, (_, value)| keep(key, value));
+    }
+
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.map.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entries_expire() {
+        let cache = TtlCache::new(Duration::from_millis(20), 10);
+        cache.insert("a", 1);
+        assert_eq!(cache.get(&"a"), Some(1));
+
+        std::thread::sleep(Duration::from_millis(30));
+        assert_eq!(cache.get(&"a"), None);
+    }
+
+    #[test]
+    fn test_retain_invalidates_matching_entries() {
+        let cache = TtlCache::new(Duration::from_secs(60), 10);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+
+        cache.retain(|_, v| *v != 1);
+        assert_eq!(cache.get(&"a"), None);
+        assert_eq!(cache.get(&"b"), Some(2));
+    }
+
+    #[test]
+    fn test_full_cache_evicts_oldest_entry() {
+        let cache = TtlCache::new(Duration::from_secs(60), 2);
+        cache.insert("a", 1);
+        cache.insert("b", 2);
+        // Replacing an entry doesn't take up more room
+        cache.insert("a", 3);
+        assert_eq!(cache.len(), 2);
+
+        cache.insert("c", 4);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&"b"), None);
+        assert_eq!(cache.get(&"a"), Some(3));
+        assert_eq!(cache.get(&"c"), Some(4));
+    }
+
+    #[test]
+    fn test_insert_sweeps_expired_entries() {
+        let cache = TtlCache::new(Duration::from_millis(20), 100);
+        for i in 0..50 {
+            cache.insert(i, i);
+        }
+
+        std::thread::sleep(Duration::from_millis(30));
+        cache.insert(50, 50);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.get(&50), Some(50));
+    }
+}
@@ -0,0 +1,86 @@
+//! Optimistic concurrency for flag values
+//!
+//! Writers say which version they last saw, via `If-Match` or an
+//! `expected_version` field, and get 409 if the value has moved on since.
+
+use axum::http::{header::IF_MATCH, HeaderMap};
+
+use crate::error::{AppError, Result};
+
+/// The version a writer expects, from the body/query or the `If-Match` header
+///
+/// `If-Match` takes an entity tag (`"3"`, `W/"3"`) or a bare version; `*`
+/// matches anything. When both are given they must agree.
+pub fn expected_version(headers: &HeaderMap, explicit: Option<i64>) -> Result<Option<i64>> {
+    let Some(value) = headers.get(IF_MATCH) else {
+        return Ok(explicit);
+    };
+
+    let raw = value
+        .to_str()
+        .map_err(|_| AppError::BadRequest("Invalid If-Match header".to_string()))?
+        .trim();
+    if raw == "*" {
+        return Ok(explicit);
+    }
+
+    let tag = raw.strip_prefix("W/").unwrap_or(raw).trim_matches('"');
+    let version = tag.parse::<i64>().map_err(|_| {
+        AppError::BadRequest(format!("If-Match must be a flag value version, got {raw}"))
+    })?;
+
+    match explicit {
+        Some(v) if v != version => Err(AppError::BadRequest(
+            "If-Match and expected_version disagree".to_string(),
+        )),
+        _ => Ok(Some(version)),
+    }
+}
+
+/// 409 unless the stored version (0 if the value was never written) is the expected one
+pub fn check_version(key: &str, current: i64, expected: Option<i64>) -> Result<()> {
+    match expected {
+        Some(expected) if expected != current => Err(AppError::Conflict(format!(
+            "Flag '{key}' is at version {current}, expected {expected}; reload and try again"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn if_match(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_MATCH, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_if_match_formats() {
+        assert_eq!(
+            expected_version(&HeaderMap::new(), Some(2)).unwrap(),
+            Some(2)
+        );
+        assert_eq!(expected_version(&if_match("\"3\""), None).unwrap(), Some(3));
+        assert_eq!(
+            expected_version(&if_match("W/\"4\""), None).unwrap(),
+            Some(4)
+        );
+        assert_eq!(expected_version(&if_match("5"), Some(5)).unwrap(), Some(5));
+        assert_eq!(expected_version(&if_match("*"), None).unwrap(), None);
+        assert!(expected_version(&if_match("abc"), None).is_err());
+        assert!(expected_version(&if_match("\"3\""), Some(4)).is_err());
+    }
+
+    #[test]
+    fn test_check_version() {
+        assert!(check_version("f", 3, None).is_ok());
+        assert!(check_version("f", 3, Some(3)).is_ok());
+        assert!(matches!(
+            check_version("f", 3, Some(2)),
+            Err(AppError::Conflict(_))
+        ));
+    }
+}
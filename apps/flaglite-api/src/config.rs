@@ -1,8 +1,77 @@
 use anyhow::{Context, Result};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::jwt::JwtKeys;
+use crate::lockout::LockoutPolicy;
+use crate::mailer::SmtpConfig;
+use crate::public::PublicRateLimit;
+use crate::retention::{RetentionPolicy, DEFAULT_DELETED_ENVIRONMENT_DAYS};
+
+/// Database connections per server when DATABASE_MAX_CONNECTIONS isn't set
+pub const DEFAULT_DB_MAX_CONNECTIONS: u32 = 10;
+
+/// Storage operations slower than this are logged when SLOW_QUERY_MS isn't set
+pub const DEFAULT_SLOW_QUERY: Duration = Duration::from_millis(250);
+
+/// Who may create an account
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignupMode {
+    /// Anyone can sign up
+    #[default]
+    Open,
+    /// Signup requires an unused invite code
+    Invite,
+    /// Signup is turned off
+    Disabled,
+}
+
+impl FromStr for SignupMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "open" => Ok(SignupMode::Open),
+            "invite" => Ok(SignupMode::Invite),
+            "disabled" => Ok(SignupMode::Disabled),
+            _ => Err(format!(
+                "Unknown signup mode: {s}. Use 'open', 'invite', or 'disabled'."
+            )),
+        }
+    }
+}
 
 pub struct Config {
     pub database_url: String,
-    pub jwt_secret: String,
+    /// Size of the database connection pool
+    pub db_max_connections: u32,
+    /// From JWT_KEYS_FILE, else the comma-separated secrets in JWT_SECRET
+    pub jwt_keys: JwtKeys,
+    /// How long evaluation reads are cached in process; None disables caching
+    pub cache_ttl: Option<Duration>,
+    /// Storage operations taking at least this long are logged; None logs none
+    pub slow_query: Option<Duration>,
+    pub signup_mode: SignupMode,
+    /// Where evaluation events are published (see events::sink_from_spec)
+    pub evaluation_sink: Option<String>,
+    /// Bearer token for /v1/admin; the admin API is off without one
+    pub admin_token: Option<String>,
+    /// Outgoing mail server; emails are only logged without one
+    pub smtp: Option<SmtpConfig>,
+    /// Start in read-only maintenance mode
+    pub read_only: bool,
+    /// When failed logins lock a username or IP out
+    pub login_lockout: LockoutPolicy,
+    /// Trust X-Forwarded-For for the client IP, when behind a reverse proxy
+    pub trust_proxy: bool,
+    /// How often public environments can be evaluated without a key
+    pub public_rate_limit: PublicRateLimit,
+    /// How long flag history and evaluation counts are kept
+    pub retention: RetentionPolicy,
+    /// The only hosts guards' health checks may be polled at; any public
+    /// address when empty
+    pub guard_allowed_hosts: Vec<String>,
 }
 
 impl Config {
@@ -10,12 +79,209 @@ impl Config {
         let database_url = std::env::var("DATABASE_URL")
             .unwrap_or_else(|_| "sqlite:flaglite.db?mode=rwc".to_string());
 
-        let jwt_secret =
-            std::env::var("JWT_SECRET").context("JWT_SECRET environment variable is required")?;
+        let db_max_connections = match std::env::var("DATABASE_MAX_CONNECTIONS") {
+            Ok(n) => match n.parse() {
+                Ok(n) if n > 0 => n,
+                _ => anyhow::bail!("DATABASE_MAX_CONNECTIONS must be at least 1, got '{n}'"),
+            },
+            Err(_) => DEFAULT_DB_MAX_CONNECTIONS,
+        };
+
+        let jwt_keys = match std::env::var("JWT_KEYS_FILE")
+            .ok()
+            .filter(|s| !s.is_empty())
+        {
+            Some(path) => JwtKeys::from_jwks_file(Path::new(&path))?,
+            None => {
+                let secrets = std::env::var("JWT_SECRET")
+                    .context("JWT_SECRET (or JWT_KEYS_FILE) environment variable is required")?;
+                JwtKeys::from_secrets(&secrets).context("Invalid JWT_SECRET")?
+            }
+        };
+
+        let cache_ttl = match std::env::var("CACHE_TTL") {
+            Ok(secs) => {
+                let secs: u64 = secs
+                    .parse()
+                    .context("CACHE_TTL must be a whole number of seconds")?;
+                (secs > 0).then(|| Duration::from_secs(secs))
+            }
+            Err(_) => None,
+        };
+
+        let slow_query = match std::env::var("SLOW_QUERY_MS") {
+            Ok(ms) => {
+                let ms: u64 = ms
+                    .parse()
+                    .context("SLOW_QUERY_MS must be a whole number of milliseconds")?;
+                (ms > 0).then(|| Duration::from_millis(ms))
+            }
+            Err(_) => Some(DEFAULT_SLOW_QUERY),
+        };
+
+        let signup_mode = match std::env::var("SIGNUP_MODE") {
+            Ok(mode) => mode
+                .parse()
+                .map_err(anyhow::Error::msg)
+                .context("Invalid SIGNUP_MODE")?,
+            Err(_) => SignupMode::default(),
+        };
+
+        let evaluation_sink = std::env::var("EVALUATION_SINK")
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        let admin_token = std::env::var("ADMIN_TOKEN").ok().filter(|s| !s.is_empty());
+
+        let smtp = smtp_from_env()?;
+
+        let read_only = bool_from_env("READ_ONLY")?;
+        let login_lockout = lockout_from_env()?;
+        let trust_proxy = bool_from_env("TRUST_PROXY")?;
+        let public_rate_limit = public_rate_limit_from_env()?;
+        let retention = RetentionPolicy {
+            history_days: days("AUDIT_RETENTION_DAYS")?,
+            evaluation_days: days("EVALUATION_RETENTION_DAYS")?,
+            deleted_environment_days: days("DELETED_ENVIRONMENT_RETENTION_DAYS")?
+                .unwrap_or(DEFAULT_DELETED_ENVIRONMENT_DAYS),
+        };
+        let guard_allowed_hosts = std::env::var("GUARD_ALLOWED_HOSTS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|host| !host.is_empty())
+            .map(str::to_lowercase)
+            .collect();
 
         Ok(Config {
             database_url,
-            jwt_secret,
+            db_max_connections,
+            jwt_keys,
+            cache_ttl,
+            slow_query,
+            signup_mode,
+            evaluation_sink,
+            admin_token,
+            smtp,
+            read_only,
+            login_lockout,
+            trust_proxy,
+            public_rate_limit,
+            retention,
+            guard_allowed_hosts,
         })
     }
+
+    /// Zero-setup config for `serve --demo`: in-memory storage and, unless
+    /// JWT_SECRET is set, a random secret that only lives as long as the process
+    pub fn demo() -> Result<Self> {
+        let jwt_keys = match std::env::var("JWT_SECRET") {
+            Ok(secrets) => JwtKeys::from_secrets(&secrets).context("Invalid JWT_SECRET")?,
+            Err(_) => JwtKeys::random(),
+        };
+
+        Ok(Config {
+            database_url: "memory://".to_string(),
+            db_max_connections: DEFAULT_DB_MAX_CONNECTIONS,
+            jwt_keys,
+            cache_ttl: None,
+            slow_query: Some(DEFAULT_SLOW_QUERY),
+            signup_mode: SignupMode::Open,
+            evaluation_sink: None,
+            admin_token: None,
+            smtp: None,
+            read_only: false,
+            login_lockout: LockoutPolicy::default(),
+            trust_proxy: false,
+            public_rate_limit: PublicRateLimit::default(),
+            retention: RetentionPolicy::default(),
+            guard_allowed_hosts: Vec::new(),
+        })
+    }
+}
+
+fn bool_from_env(name: &str) -> Result<bool> {
+    match std::env::var(name) {
+        Ok(value) => match value.to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(true),
+            "" | "0" | "false" | "no" | "off" => Ok(false),
+            _ => anyhow::bail!("{name} must be true or false, got '{value}'"),
+        },
+        Err(_) => Ok(false),
+    }
+}
+
+fn number(name: &str, default: u32) -> Result<u32> {
+    match std::env::var(name) {
+        Ok(n) => n
+            .parse()
+            .with_context(|| format!("{name} must be a whole number, got '{n}'")),
+        Err(_) => Ok(default),
+    }
+}
+
+/// A retention period in days; None (keep forever) when unset or empty
+fn days(name: &str) -> Result<Option<u32>> {
+    match std::env::var(name) {
+        Ok(n) if !n.is_empty() => match n.parse() {
+            Ok(n) if n > 0 => Ok(Some(n)),
+            _ => anyhow::bail!("{name} must be a number of days, at least 1, got '{n}'"),
+        },
+        _ => Ok(None),
+    }
+}
+
+/// LOGIN_MAX_FAILURES, LOGIN_MAX_FAILURES_PER_IP, LOGIN_LOCKOUT_SECS and
+/// LOGIN_LOCKOUT_MAX_SECS, each falling back to its default
+fn lockout_from_env() -> Result<LockoutPolicy> {
+    let default = LockoutPolicy::default();
+    let lockout = number("LOGIN_LOCKOUT_SECS", default.lockout.num_seconds() as u32)?;
+    let max_lockout = number(
+        "LOGIN_LOCKOUT_MAX_SECS",
+        default.max_lockout.num_seconds() as u32,
+    )?;
+    if lockout == 0 || max_lockout < lockout {
+        anyhow::bail!(
+            "LOGIN_LOCKOUT_SECS must be at least 1 and no more than LOGIN_LOCKOUT_MAX_SECS"
+        );
+    }
+
+    Ok(LockoutPolicy {
+        max_failures: number("LOGIN_MAX_FAILURES", default.max_failures)?,
+        max_failures_per_ip: number("LOGIN_MAX_FAILURES_PER_IP", default.max_failures_per_ip)?,
+        lockout: chrono::Duration::seconds(lockout.into()),
+        max_lockout: chrono::Duration::seconds(max_lockout.into()),
+    })
+}
+
+/// PUBLIC_REQUESTS_PER_MINUTE and PUBLIC_REQUESTS_PER_MINUTE_PER_IP, each
+/// falling back to its default
+fn public_rate_limit_from_env() -> Result<PublicRateLimit> {
+    let default = PublicRateLimit::default();
+    Ok(PublicRateLimit {
+        per_client: number("PUBLIC_REQUESTS_PER_MINUTE", default.per_client)?,
+        per_ip: number("PUBLIC_REQUESTS_PER_MINUTE_PER_IP", default.per_ip)?,
+    })
+}
+
+/// SMTP_HOST enables SMTP; SMTP_FROM is then required
+fn smtp_from_env() -> Result<Option<SmtpConfig>> {
+    let var = |name| std::env::var(name).ok().filter(|s: &String| !s.is_empty());
+
+    let Some(host) = var("SMTP_HOST") else {
+        return Ok(None);
+    };
+    let port = match var("SMTP_PORT") {
+        Some(port) => port.parse().context("SMTP_PORT must be a port number")?,
+        None => 587,
+    };
+    let from = var("SMTP_FROM").context("SMTP_FROM is required when SMTP_HOST is set")?;
+
+    Ok(Some(SmtpConfig {
+        host,
+        port,
+        username: var("SMTP_USERNAME"),
+        password: var("SMTP_PASSWORD"),
+        from,
+    }))
 }
@@ -1,11 +1,14 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
 use serde_json::json;
 use thiserror::Error;
 
+use crate::models::ApiKeyScope;
+use crate::validation::FieldError;
+
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Invalid credentials")]
@@ -17,34 +20,116 @@ pub enum AppError {
     #[error("Not found: {0}")]
     NotFound(String),
 
+    /// With keys in the project close to the one asked for
+    #[error("Flag '{key}' not found")]
+    FlagNotFound {
+        key: String,
+        suggestions: Vec<String>,
+    },
+
     #[error("Unauthorized")]
     Unauthorized,
 
     #[error("Invalid API key")]
     InvalidApiKey,
 
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
+
+    /// A user API key was used for a request its scope doesn't cover
+    #[error("This API key's scope is {scope}; the request needs {required}")]
+    InsufficientScope {
+        scope: ApiKeyScope,
+        required: ApiKeyScope,
+    },
+
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    #[error("{}", summarize(.0))]
+    Validation(Vec<FieldError>),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    #[error("Flag '{0}' already exists")]
+    DuplicateFlag(String),
+
+    #[error("Environment name '{0}' is already in use")]
+    EnvironmentInUse(String),
+
+    #[error("Environment '{0}' is protected; confirm the change to apply it")]
+    ProtectedEnvironment(String),
+
+    #[error("Environment '{0}' is frozen; unfreeze it to change flags")]
+    EnvironmentFrozen(String),
+
+    #[error("Changes to environment '{0}' need a reason in this project")]
+    ReasonRequired(String),
+
+    #[error(
+        "Changes to environment '{0}' need approval; propose them with `flaglite flags propose`"
+    )]
+    ApprovalRequired(String),
+
     #[error("Database error: {0}")]
     Database(#[from] sqlx::Error),
 
     #[error("JWT error: {0}")]
     Jwt(#[from] jsonwebtoken::errors::Error),
 
+    #[error("Request quota exceeded; retry in {retry_after}s")]
+    QuotaExceeded { retry_after: u64 },
+
+    #[error("Too many failed login attempts; try again in {retry_after}s")]
+    LoginLocked { retry_after: u64 },
+
+    /// The username was changed too recently to change again
+    #[error("Username changed recently; it can be changed again in {retry_after}s")]
+    RenameCooldown { retry_after: u64 },
+
+    #[error("Service unavailable: {0}")]
+    Unavailable(String),
+
+    /// The server is in read-only maintenance mode
+    #[error("{0}")]
+    ReadOnly(String),
+
     #[error("Internal error: {0}")]
     Internal(String),
 }
 
+impl AppError {
+    /// The flag value was written by someone else since it was read
+    pub fn flag_value_conflict(key: &str) -> Self {
+        AppError::Conflict(format!(
+            "Flag '{key}' was changed by another request; reload and try again"
+        ))
+    }
+}
+
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, error_message) = match &self {
             AppError::InvalidCredentials => (StatusCode::UNAUTHORIZED, self.to_string()),
             AppError::UserAlreadyExists => (StatusCode::CONFLICT, self.to_string()),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg.clone()),
+            AppError::FlagNotFound { .. } => (StatusCode::NOT_FOUND, self.to_string()),
             AppError::Unauthorized => (StatusCode::UNAUTHORIZED, self.to_string()),
             AppError::InvalidApiKey => (StatusCode::UNAUTHORIZED, self.to_string()),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg.clone()),
+            AppError::InsufficientScope { .. } => (StatusCode::FORBIDDEN, self.to_string()),
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg.clone()),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg.clone()),
+            AppError::DuplicateFlag(_) => (StatusCode::CONFLICT, self.to_string()),
+            AppError::EnvironmentInUse(_) => (StatusCode::CONFLICT, self.to_string()),
+            AppError::Validation(_) => (StatusCode::UNPROCESSABLE_ENTITY, self.to_string()),
+            AppError::ProtectedEnvironment(_) => {
+                (StatusCode::PRECONDITION_REQUIRED, self.to_string())
+            }
+            AppError::EnvironmentFrozen(_) => (StatusCode::LOCKED, self.to_string()),
+            AppError::ReasonRequired(_) => (StatusCode::PRECONDITION_REQUIRED, self.to_string()),
+            AppError::ApprovalRequired(_) => (StatusCode::FORBIDDEN, self.to_string()),
             AppError::Database(e) => {
                 tracing::error!("Database error: {:?}", e);
                 (
@@ -53,6 +138,11 @@ impl IntoResponse for AppError {
                 )
             }
             AppError::Jwt(_) => (StatusCode::UNAUTHORIZED, "Invalid token".to_string()),
+            AppError::QuotaExceeded { .. } => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            AppError::LoginLocked { .. } => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            AppError::RenameCooldown { .. } => (StatusCode::TOO_MANY_REQUESTS, self.to_string()),
+            AppError::Unavailable(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg.clone()),
+            AppError::ReadOnly(msg) => (StatusCode::SERVICE_UNAVAILABLE, msg.clone()),
             AppError::Internal(msg) => {
                 tracing::error!("Internal error: {}", msg);
                 (
@@ -62,12 +152,86 @@ impl IntoResponse for AppError {
             }
         };
 
-        let body = Json(json!({
-            "error": error_message
-        }));
+        let body = match &self {
+            AppError::Validation(fields) => json!({
+                "error": error_message,
+                "code": "validation_error",
+                "details": fields,
+            }),
+            AppError::EnvironmentFrozen(environment) => json!({
+                "error": error_message,
+                "code": "environment_frozen",
+                "details": {"environment": environment},
+            }),
+            AppError::ReasonRequired(environment) => json!({
+                "error": error_message,
+                "code": "reason_required",
+                "details": {"environment": environment},
+            }),
+            AppError::ApprovalRequired(environment) => json!({
+                "error": error_message,
+                "code": "approval_required",
+                "details": {"environment": environment},
+            }),
+            AppError::DuplicateFlag(key) => json!({
+                "error": error_message,
+                "code": "duplicate_flag",
+                "details": {"key": key},
+            }),
+            AppError::EnvironmentInUse(environment) => json!({
+                "error": error_message,
+                "code": "environment_in_use",
+                "details": {"environment": environment},
+            }),
+            AppError::InsufficientScope { scope, required } => json!({
+                "error": error_message,
+                "code": "insufficient_scope",
+                "details": {"scope": scope, "required": required},
+            }),
+            AppError::ReadOnly(_) => json!({
+                "error": error_message,
+                "code": "read_only",
+            }),
+            AppError::LoginLocked { retry_after } => json!({
+                "error": error_message,
+                "code": "login_locked",
+                "details": {"retry_after": retry_after},
+            }),
+            AppError::RenameCooldown { retry_after } => json!({
+                "error": error_message,
+                "code": "rename_cooldown",
+                "details": {"retry_after": retry_after},
+            }),
+            AppError::FlagNotFound { key, suggestions } => json!({
+                "error": error_message,
+                "code": "flag_not_found",
+                "details": {"key": key, "suggestions": suggestions},
+            }),
+            _ => json!({
+                "error": error_message
+            }),
+        };
 
-        (status, body).into_response()
+        let mut response = (status, Json(body)).into_response();
+        if let AppError::QuotaExceeded { retry_after }
+        | AppError::LoginLocked { retry_after }
+        | AppError::RenameCooldown { retry_after } = self
+        {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
     }
 }
 
+/// One sentence per invalid field, for clients that only read `error`
+fn summarize(fields: &[FieldError]) -> String {
+    fields
+        .iter()
+        .map(|f| f.message.as_str())
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
 pub type Result<T> = std::result::Result<T, AppError>;
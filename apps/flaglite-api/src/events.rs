@@ -0,0 +1,326 @@
+//! Evaluation event export
+//!
+//! With EVALUATION_SINK set, every flag evaluation is published as one event
+//! (flag, environment, hashed user, result, reason) for offline experiment
+//! analysis. Events pass through a bounded queue: if the sink falls behind,
+//! events are dropped rather than slowing evaluations down.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, Mutex};
+
+/// Events waiting for the sink before new ones are dropped
+const QUEUE_CAPACITY: usize = 10_000;
+/// Most events handed to the sink at once
+const MAX_BATCH: usize = 500;
+
+/// Why an evaluation returned what it did
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EvaluationReason {
+    /// The flag has no value in this environment, so its default applies
+    NoValue,
+    /// The flag is off in this environment
+    Disabled,
+    /// On for everyone (rollout 100%)
+    FullRollout,
+    /// On, but rolled out to nobody (rollout 0%)
+    ZeroRollout,
+    /// The user's bucket falls inside the rollout percentage
+    InRollout,
+    /// The user's bucket falls outside the rollout percentage
+    OutOfRollout,
+    /// Partial rollout without a user ID, decided at random
+    Random,
+    /// The flag is past its expiry date
+    Expired,
+    /// The user is on the flag's allowlist in this environment
+    Allowlisted,
+    /// The user is on the flag's denylist in this environment
+    Denylisted,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EvaluationEvent {
+    pub timestamp: DateTime<Utc>,
+    pub project_id: String,
+    pub environment_id: String,
+    pub flag_key: String,
+    /// SHA-256 of the user ID, so events can be joined per user without exposing it
+    pub user_hash: Option<String>,
+    pub enabled: bool,
+    pub reason: EvaluationReason,
+}
+
+pub fn hash_user_id(user_id: &str) -> String {
+    format!("{:x}", Sha256::digest(user_id.as_bytes()))
+}
+
+/// Destination for evaluation events
+#[async_trait]
+pub trait EvaluationSink: Send + Sync {
+    async fn publish(&self, events: &[EvaluationEvent]) -> Result<()>;
+}
+
+/// One JSON object per line on stdout
+pub struct StdoutSink;
+
+#[async_trait]
+impl EvaluationSink for StdoutSink {
+    async fn publish(&self, events: &[EvaluationEvent]) -> Result<()> {
+        let mut out = tokio::io::stdout();
+        out.write_all(&json_lines(events)?).await?;
+        out.flush().await?;
+        Ok(())
+    }
+}
+
+/// One JSON object per line, appended to a file
+pub struct FileSink {
+    file: Mutex<tokio::fs::File>,
+}
+
+impl FileSink {
+    pub async fn open(path: &Path) -> Result<Self> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+#[async_trait]
+impl EvaluationSink for FileSink {
+    async fn publish(&self, events: &[EvaluationEvent]) -> Result<()> {
+        let mut file = self.file.lock().await;
+        file.write_all(&json_lines(events)?).await?;
+        file.flush().await?;
+        Ok(())
+    }
+}
+
+/// Publishes each event as a JSON message to a Kafka topic
+#[cfg(feature = "kafka")]
+pub struct KafkaSink {
+    producer: rdkafka::producer::FutureProducer,
+    topic: String,
+}
+
+#[cfg(feature = "kafka")]
+impl KafkaSink {
+    pub fn new(brokers: &str, topic: &str) -> Result<Self> {
+        let producer = rdkafka::ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .create()
+            .context("Failed to create Kafka producer")?;
+        Ok(Self {
+            producer,
+            topic: topic.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "kafka")]
+#[async_trait]
+impl EvaluationSink for KafkaSink {
+    async fn publish(&self, events: &[EvaluationEvent]) -> Result<()> {
+        use rdkafka::producer::FutureRecord;
+        use std::time::Duration;
+
+        for event in events {
+            let payload = serde_json::to_vec(event)?;
+            // Keyed by flag so one flag's events stay ordered within a partition
+            let record = FutureRecord::to(&self.topic)
+                .key(&event.flag_key)
+                .payload(&payload);
+            self.producer
+                .send(record, Duration::from_secs(5))
+                .await
+                .map_err(|(e, _)| anyhow::anyhow!("Kafka publish failed: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Publishes each event as a JSON message to a NATS subject
+#[cfg(feature = "nats")]
+pub struct NatsSink {
+    client: async_nats::Client,
+    subject: String,
+}
+
+#[cfg(feature = "nats")]
+impl NatsSink {
+    pub async fn connect(server: &str, subject: &str) -> Result<Self> {
+        let client = async_nats::connect(server)
+            .await
+            .with_context(|| format!("Failed to connect to NATS at {server}"))?;
+        Ok(Self {
+            client,
+            subject: subject.to_string(),
+        })
+    }
+}
+
+#[cfg(feature = "nats")]
+#[async_trait]
+impl EvaluationSink for NatsSink {
+    async fn publish(&self, events: &[EvaluationEvent]) -> Result<()> {
+        for event in events {
+            self.client
+                .publish(self.subject.clone(), serde_json::to_vec(event)?.into())
+                .await?;
+        }
+        self.client.flush().await?;
+        Ok(())
+    }
+}
+
+fn json_lines(events: &[EvaluationEvent]) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    for event in events {
+        serde_json::to_writer(&mut buf, event)?;
+        buf.push(b'\n');
+    }
+    Ok(buf)
+}
+
+/// Build the sink described by EVALUATION_SINK:
+/// `stdout`, `file:<path>`, `kafka://<brokers>/<topic>` or `nats://<host:port>/<subject>`
+pub async fn sink_from_spec(spec: &str) -> Result<Box<dyn EvaluationSink>> {
+    if spec == "stdout" {
+        return Ok(Box::new(StdoutSink));
+    }
+    if let Some(path) = spec.strip_prefix("file:") {
+        return Ok(Box::new(FileSink::open(Path::new(path)).await?));
+    }
+    if let Some(rest) = spec.strip_prefix("kafka://") {
+        let (brokers, topic) = split_target(rest, "kafka://<brokers>/<topic>")?;
+        #[cfg(feature = "kafka")]
+        return Ok(Box::new(KafkaSink::new(brokers, topic)?));
+        #[cfg(not(feature = "kafka"))]
+        {
+            let _ = (brokers, topic);
+            anyhow::bail!("EVALUATION_SINK uses Kafka, but this build lacks the `kafka` feature");
+        }
+    }
+    if let Some(rest) = spec.strip_prefix("nats://") {
+        let (server, subject) = split_target(rest, "nats://<host:port>/<subject>")?;
+        #[cfg(feature = "nats")]
+        return Ok(Box::new(NatsSink::connect(server, subject).await?));
+        #[cfg(not(feature = "nats"))]
+        {
+            let _ = (server, subject);
+            anyhow::bail!("EVALUATION_SINK uses NATS, but this build lacks the `nats` feature");
+        }
+    }
+
+    anyhow::bail!(
+        "Unknown EVALUATION_SINK: {spec}. Use 'stdout', 'file:<path>', 'kafka://<brokers>/<topic>' or 'nats://<host:port>/<subject>'."
+    )
+}
+
+/// Split `<server>/<name>`, both required
+fn split_target<'a>(target: &'a str, usage: &str) -> Result<(&'a str, &'a str)> {
+    match target.split_once('/') {
+        Some((server, name)) if !server.is_empty() && !name.is_empty() => Ok((server, name)),
+        _ => anyhow::bail!("EVALUATION_SINK must look like {usage}"),
+    }
+}
+
+/// Queue in front of a sink; cheap to clone into request handlers
+#[derive(Clone)]
+pub struct EvaluationEvents {
+    tx: mpsc::Sender<EvaluationEvent>,
+}
+
+impl EvaluationEvents {
+    /// Start publishing queued events to `sink` in the background
+    pub fn spawn(sink: Box<dyn EvaluationSink>) -> Self {
+        let (tx, mut rx) = mpsc::channel(QUEUE_CAPACITY);
+
+        tokio::spawn(async move {
+            let mut batch = Vec::with_capacity(MAX_BATCH);
+            while let Some(event) = rx.recv().await {
+                batch.push(event);
+                while batch.len() < MAX_BATCH {
+                    match rx.try_recv() {
+                        Ok(event) => batch.push(event),
+                        Err(_) => break,
+                    }
+                }
+
+                // Export is best-effort, like usage counts
+                if let Err(e) = sink.publish(&batch).await {
+                    tracing::warn!("Failed to publish {} evaluation events: {e:#}", batch.len());
+                }
+                batch.clear();
+            }
+        });
+
+        Self { tx }
+    }
+
+    /// Queue an event without waiting; dropped if the queue is full
+    pub fn emit(&self, event: EvaluationEvent) {
+        if self.tx.try_send(event).is_err() {
+            tracing::debug!("Evaluation event queue full, dropping event");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(key: &str) -> EvaluationEvent {
+        EvaluationEvent {
+            timestamp: Utc::now(),
+            project_id: "p".to_string(),
+            environment_id: "e".to_string(),
+            flag_key: key.to_string(),
+            user_hash: Some(hash_user_id("user-1")),
+            enabled: true,
+            reason: EvaluationReason::InRollout,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_sink_writes_json_lines() {
+        let path =
+            std::env::temp_dir().join(format!("flaglite-events-{}.jsonl", uuid::Uuid::new_v4()));
+        let sink = sink_from_spec(&format!("file:{}", path.display()))
+            .await
+            .unwrap();
+
+        sink.publish(&[event("a"), event("b")]).await.unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = content
+            .lines()
+            .map(|l| serde_json::from_str(l).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1]["flag_key"], "b");
+        assert_eq!(lines[0]["reason"], "in_rollout");
+        assert_ne!(lines[0]["user_hash"], "user-1");
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    async fn test_sink_spec_errors() {
+        assert!(sink_from_spec("syslog").await.is_err());
+        assert!(sink_from_spec("kafka://broker:9092").await.is_err());
+    }
+}
@@ -0,0 +1,33 @@
+//! Expired flag reporting
+//!
+//! Expired flags already evaluate to off; this job periodically logs the ones
+//! still defined so their owners remember to delete them and the code behind them.
+
+use anyhow::Context;
+use chrono::Utc;
+use std::time::Duration;
+
+use crate::models::AppState;
+
+/// How often expired flags are reported
+pub const REPORT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// The `report-expired-flags` job: log every expired flag still defined
+pub async fn report(state: AppState) -> anyhow::Result<()> {
+    let flags = state
+        .storage
+        .list_expired_flags(Utc::now())
+        .await
+        .context("Failed to list expired flags")?;
+
+    for flag in flags {
+        let expired_at = flag.expires_at.unwrap_or_default().to_rfc3339();
+        let owner = flag.owner.as_deref().unwrap_or("unowned");
+        tracing::warn!(
+            "Flag '{}' in project {} (owner: {owner}) expired at {expired_at} and evaluates to off; delete it and the code that checks it",
+            flag.key,
+            flag.project_id
+        );
+    }
+    Ok(())
+}
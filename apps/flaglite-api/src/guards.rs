@@ -0,0 +1,706 @@
+//! Anomaly guards
+//!
+//! A guard watches one change to a flag in one environment (a toggle, a set
+//! or a whole ramp) for a window of time, and puts the flag back if the change
+//! turns out to hurt. Health comes from two places: the `check-guards` job
+//! polls the guard's URL, where an answer other than 2xx, or a JSON body with
+//! `"healthy": false`, is unhealthy; and anyone holding the guard's token can
+//! push an unhealthy report. Either trips the guard: the flag is reverted to
+//! the state from before the change, a guarded ramp is aborted, and the
+//! rollback is recorded in the audit log. A trip reverts even in a frozen
+//! environment, since undoing a bad change is what the guard was set up for.
+//!
+//! A guard never reverts over a later change made by hand: once the flag has
+//! moved on, the guard is superseded. A guard that hears nothing wrong until
+//! its window closes has passed; a ramp guard's window starts when the ramp
+//! stops running. A health check that can't be reached is logged and tried
+//! again, not taken as unhealthy.
+//!
+//! Health checks are polled from inside the server's network, so only public
+//! addresses are, unless GUARD_ALLOWED_HOSTS names the hosts that may be
+//! polled instead. Redirects aren't followed and only the start of an answer
+//! is read.
+
+use anyhow::Context;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use futures_util::StreamExt;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::StatusCode;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::bus;
+use crate::error::Result;
+use crate::handlers::cli::record_changes;
+use crate::models::{
+    AppState, FlagValue, Guard, Ramp, GUARD_PASSED, GUARD_SUPERSEDED, GUARD_TRIPPED, RAMP_ABORTED,
+    RAMP_ACTIVE,
+};
+use crate::ramps;
+
+/// How often the `check-guards` job polls health checks
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// How long a health check has to answer
+const POLL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How many health checks are polled at once
+const MAX_CONCURRENT_POLLS: usize = 16;
+
+/// How much of a health check's answer is read; the rest is ignored
+const MAX_BODY_BYTES: usize = 64 * 1024;
+
+/// Longest reason kept in the flag's history and the audit log when a guard
+/// trips
+const MAX_MESSAGE_CHARS: usize = 500;
+
+/// Shortest and longest time a guard can watch for
+pub const MIN_WINDOW_SECS: i64 = 60;
+pub const MAX_WINDOW_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// Polls guards' health checks: any host at a public address, or with
+/// GUARD_ALLOWED_HOSTS only the hosts listed there, at any address
+#[derive(Clone)]
+pub struct HealthChecks {
+    http: reqwest::Client,
+    allowed_hosts: Arc<[String]>,
+}
+
+impl HealthChecks {
+    pub fn new(allowed_hosts: Vec<String>) -> Self {
+        let allowed_hosts: Arc<[String]> = allowed_hosts
+            .into_iter()
+            .map(|host| host.to_lowercase())
+            .collect();
+        let http = reqwest::Client::builder()
+            .timeout(POLL_TIMEOUT)
+            .redirect(reqwest::redirect::Policy::none())
+            .no_proxy()
+            .dns_resolver(Arc::new(PublicResolver {
+                allowed_hosts: allowed_hosts.clone(),
+            }))
+            .user_agent(concat!("flaglite-guard/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("HTTP client for health checks builds");
+        HealthChecks {
+            http,
+            allowed_hosts,
+        }
+    }
+
+    /// Why `url` can't be polled, as far as can be told without looking its
+    /// host up: a host that isn't allowed, or an address that isn't public
+    pub fn refuse(&self, url: &reqwest::Url) -> Option<String> {
+        let host = url.host_str()?;
+        if !self.allowed_hosts.is_empty() {
+            return (!allowed(&self.allowed_hosts, host))
+                .then(|| format!("{host} isn't in GUARD_ALLOWED_HOSTS"));
+        }
+        let ip: IpAddr = host.trim_matches(['[', ']']).parse().ok()?;
+        (!is_public(ip)).then(|| format!("{ip} isn't a public address"))
+    }
+
+    /// The status and the start of the body `url` answers with
+    async fn poll(&self, url: &str) -> std::result::Result<(StatusCode, String), String> {
+        let url = reqwest::Url::parse(url).map_err(|e| e.to_string())?;
+        if let Some(reason) = self.refuse(&url) {
+            return Err(reason);
+        }
+        let mut response = self.http.get(url).send().await.map_err(describe)?;
+        let status = response.status();
+        let mut body = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(describe)? {
+            let room = MAX_BODY_BYTES - body.len();
+            body.extend_from_slice(&chunk[..chunk.len().min(room)]);
+            if body.len() == MAX_BODY_BYTES {
+                break;
+            }
+        }
+        Ok((status, String::from_utf8_lossy(&body).into_owned()))
+    }
+}
+
+impl Default for HealthChecks {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+/// Resolves health check hosts, refusing any that resolves to an address
+/// that isn't public unless the host is allowed. Checking here, rather than
+/// before connecting, means a host can't resolve differently in between.
+struct PublicResolver {
+    allowed_hosts: Arc<[String]>,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let trusted = allowed(&self.allowed_hosts, name.as_str());
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((name.as_str(), 0)).await?.collect();
+            if !trusted {
+                if let Some(addr) = addrs.iter().find(|addr| !is_public(addr.ip())) {
+                    return Err(format!(
+                        "{} resolves to {}, which isn't a public address",
+                        name.as_str(),
+                        addr.ip()
+                    )
+                    .into());
+                }
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn allowed(allowed_hosts: &[String], host: &str) -> bool {
+    allowed_hosts.contains(&host.to_lowercase())
+}
+
+/// Whether `ip` is reachable on the public internet: not loopback, private,
+/// link-local (where cloud metadata services live), shared, unspecified,
+/// broadcast, documentation or multicast
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(a == 0
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_documentation()
+                || ip.is_multicast()
+                || (a == 100 && b & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    || first & 0xfe00 == 0xfc00
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// A request error with its causes, which say why a lookup was refused
+fn describe(e: reqwest::Error) -> String {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(&e);
+    while let Some(cause) = source {
+        message = format!("{message}: {cause}");
+        source = cause.source();
+    }
+    message
+}
+
+/// When a guard stops watching; None while its ramp is still running
+pub fn watching_until(guard: &Guard, ramp: Option<&Ramp>) -> Option<DateTime<Utc>> {
+    let start = match ramp {
+        Some(ramp) if ramps::is_running(ramp) => return None,
+        Some(ramp) => ramp.updated_at,
+        None => guard.created_at,
+    };
+    Some(start + ChronoDuration::seconds(guard.window_secs))
+}
+
+/// Whether the flag was changed by hand since the guarded change. An active
+/// ramp's own steps don't count; one that was changed under it pauses itself
+/// first.
+fn moved_on(guard: &Guard, ramp: Option<&Ramp>, current: Option<&FlagValue>) -> bool {
+    match ramp {
+        Some(ramp) => {
+            ramp.status != RAMP_ACTIVE
+                && ramps::current_percentage(current) != ramp.current_percentage
+        }
+        None => current.map_or(0, |fv| fv.version) != guard.version,
+    }
+}
+
+/// Why a health check's answer is unhealthy; None if it's healthy
+pub fn unhealthy(status: StatusCode, body: &str) -> Option<String> {
+    if !status.is_success() {
+        return Some(format!("health check answered {status}"));
+    }
+    let report: serde_json::Value = serde_json::from_str(body).ok()?;
+    if report.get("healthy") != Some(&serde_json::Value::Bool(false)) {
+        return None;
+    }
+    Some(
+        report
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or("health check reported unhealthy")
+            .to_string(),
+    )
+}
+
+/// The `check-guards` job: poll every watching guard. Guards wait while the
+/// server is read-only
+pub async fn check_job(state: AppState) -> anyhow::Result<()> {
+    if state.maintenance.is_read_only() {
+        return Ok(());
+    }
+    check(&state, Utc::now())
+        .await
+        .context("Failed to check guards")
+}
+
+/// End the guards whose window closed or whose flag moved on by `now`, and
+/// poll the rest, several at a time. A guard that can't be checked is logged
+/// and tried again on the next run.
+pub async fn check(state: &AppState, now: DateTime<Utc>) -> Result<()> {
+    let guards = state.storage.list_watching_guards().await?;
+    futures_util::stream::iter(guards)
+        .for_each_concurrent(MAX_CONCURRENT_POLLS, |guard| async move {
+            let id = guard.id.clone();
+            if let Err(e) = watch(state, guard, now).await {
+                tracing::warn!("Guard {id} couldn't be checked: {e}");
+            }
+        })
+        .await;
+    Ok(())
+}
+
+async fn guarded_ramp(state: &AppState, guard: &Guard) -> Result<Option<Ramp>> {
+    match &guard.ramp_id {
+        Some(id) => state.storage.get_ramp(id).await,
+        None => Ok(None),
+    }
+}
+
+async fn end(state: &AppState, mut guard: Guard, status: &str, now: DateTime<Utc>) -> Result<()> {
+    guard.status = status.to_string();
+    guard.ended_at = Some(now);
+    state.storage.end_guard(&guard).await?;
+    Ok(())
+}
+
+async fn watch(state: &AppState, guard: Guard, now: DateTime<Utc>) -> Result<()> {
+    let ramp = guarded_ramp(state, &guard).await?;
+    let current = state
+        .storage
+        .get_flag_value(&guard.flag_id, &guard.environment_id)
+        .await?;
+    if moved_on(&guard, ramp.as_ref(), current.as_ref()) {
+        return end(state, guard, GUARD_SUPERSEDED, now).await;
+    }
+    if watching_until(&guard, ramp.as_ref()).is_some_and(|until| now >= until) {
+        return end(state, guard, GUARD_PASSED, now).await;
+    }
+
+    let Some(url) = guard.url.clone() else {
+        return Ok(());
+    };
+    let (status, body) = match state.health_checks.poll(&url).await {
+        Ok(answer) => answer,
+        Err(e) => {
+            tracing::warn!("Guard {} couldn't poll {url}: {e}", guard.id);
+            return Ok(());
+        }
+    };
+    if let Some(message) = unhealthy(status, &body) {
+        trip(state, guard, &message, now).await?;
+    }
+    Ok(())
+}
+
+/// Revert the guarded change and abort a guarded ramp, recording why in the
+/// flag's history and the audit log. Returns false, reverting nothing, if the
+/// flag moved on or changed while reverting; a guard that moved on is ended
+/// as superseded.
+pub async fn trip(
+    state: &AppState,
+    mut guard: Guard,
+    message: &str,
+    now: DateTime<Utc>,
+) -> Result<bool> {
+    let (Some(flag), Some(environment)) = (
+        state.storage.get_flag_by_id(&guard.flag_id).await?,
+        state
+            .storage
+            .get_environment_by_id(&guard.environment_id)
+            .await?,
+    ) else {
+        return Ok(false);
+    };
+    let ramp = guarded_ramp(state, &guard).await?;
+    let current = state
+        .storage
+        .get_flag_value(&flag.id, &environment.id)
+        .await?;
+    if moved_on(&guard, ramp.as_ref(), current.as_ref()) {
+        end(state, guard, GUARD_SUPERSEDED, now).await?;
+        return Ok(false);
+    }
+    let Some(current) = current else {
+        return Ok(false);
+    };
+    let message: String = message.chars().take(MAX_MESSAGE_CHARS).collect();
+
+    // Stop the ramp first, so it can't step again over the revert
+    if let Some(mut ramp) = ramp.filter(ramps::is_running) {
+        let from = std::mem::replace(&mut ramp.status, RAMP_ABORTED.to_string());
+        ramp.next_step_at = None;
+        ramp.updated_at = now;
+        state.storage.update_ramp(&ramp, &from).await?;
+    }
+
+    let reverted = FlagValue {
+        enabled: guard.revert_enabled,
+        rollout_percentage: guard.revert_rollout_percentage,
+        value: guard.revert_value.clone(),
+        updated_at: now,
+        version: current.version + 1,
+        reason: Some(format!("Guard {} tripped: {message}", guard.id)),
+        ..current
+    };
+    if !state.storage.save_flag_values(&[reverted]).await? {
+        return Ok(false);
+    }
+    record_changes(
+        state,
+        &flag.project_id,
+        std::slice::from_ref(&flag.key),
+        false,
+    )
+    .await?;
+
+    guard.status = GUARD_TRIPPED.to_string();
+    guard.message = Some(message.clone());
+    guard.ended_at = Some(now);
+    state.storage.end_guard(&guard).await?;
+
+    let actor = state
+        .storage
+        .get_user_by_id(&guard.created_by)
+        .await?
+        .map_or_else(|| guard.created_by.clone(), |u| u.username);
+    let reverted_to = if guard.revert_enabled {
+        format!("on at {}%", guard.revert_rollout_percentage)
+    } else {
+        "off".to_string()
+    };
+    let ramp = guard
+        .ramp_id
+        .as_ref()
+        .map(|id| format!(" and ramp {id} aborted"))
+        .unwrap_or_default();
+    bus::audit(
+        state,
+        &guard.project_id,
+        "guard.tripped",
+        &actor,
+        format!(
+            "Guard {} in project {} tripped: {message}; '{}' in {} reverted to {reverted_to}{ramp}",
+            guard.id, guard.project_id, flag.key, environment.name
+        ),
+    );
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SignupMode;
+    use crate::jobs::JobMetrics;
+    use crate::jwt::JwtKeys;
+    use crate::lockout::{LockoutPolicy, LoginThrottle};
+    use crate::mailer::LogMailer;
+    use crate::maintenance::Maintenance;
+    use crate::models::{Environment, Flag, Project, User, GUARD_WATCHING};
+    use crate::public::{PublicLimiter, PublicRateLimit};
+    use crate::quota::QuotaLimiter;
+    use crate::retention::RetentionPolicy;
+    use crate::storage::memory::MemoryStorage;
+    use crate::usage::UsageRecorder;
+    use reqwest::StatusCode;
+    use std::sync::Arc;
+
+    fn state() -> AppState {
+        AppState {
+            storage: Arc::new(MemoryStorage::new()),
+            jwt_keys: Arc::new(JwtKeys::random()),
+            usage: Arc::new(UsageRecorder::new()),
+            signup_mode: SignupMode::Open,
+            events: None,
+            quotas: Arc::new(QuotaLimiter::new()),
+            admin_token: None,
+            mailer: Arc::new(LogMailer),
+            maintenance: Arc::new(Maintenance::new(false)),
+            logins: Arc::new(LoginThrottle::new(LockoutPolicy::default())),
+            trust_proxy: false,
+            jobs: Arc::new(JobMetrics::default()),
+            storage_metrics: Default::default(),
+            bus: Default::default(),
+            public: Arc::new(PublicLimiter::new(PublicRateLimit::default())),
+            retention: RetentionPolicy::default(),
+            health_checks: Default::default(),
+        }
+    }
+
+    /// A flag turned on at version 2 in production, guarded for ten minutes
+    /// with no health check to poll
+    async fn guarded(state: &AppState, now: DateTime<Utc>) -> Guard {
+        let storage = &state.storage;
+        storage
+            .create_user(&User {
+                id: "u1".to_string(),
+                username: "alice".to_string(),
+                password_hash: "hash".to_string(),
+                email: None,
+                email_verified_at: None,
+                session_epoch: 0,
+                created_at: now,
+                updated_at: now,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_project(&Project {
+                id: "p1".to_string(),
+                user_id: "u1".to_string(),
+                org_id: None,
+                name: "App".to_string(),
+                slug: "app".to_string(),
+                api_key: "ffl_proj_test".to_string(),
+                created_at: now,
+                require_change_reason: false,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_environment(&Environment {
+                id: "e1".to_string(),
+                project_id: "p1".to_string(),
+                name: "production".to_string(),
+                api_key: "ffl_env_production".to_string(),
+                protected: false,
+                frozen: false,
+                sort_order: 0,
+                color: None,
+                emoji: None,
+                cache_max_age: None,
+                cache_stale_while_revalidate: None,
+                public_client_id: None,
+                require_approval: false,
+                created_at: now,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_flag(&Flag {
+                id: "f1".to_string(),
+                project_id: "p1".to_string(),
+                key: "checkout".to_string(),
+                name: "checkout".to_string(),
+                description: None,
+                created_at: now,
+                expires_at: None,
+                owner: None,
+                repository: None,
+                code_path: None,
+                flag_type: "boolean".to_string(),
+                json_schema: None,
+                default_enabled: false,
+                default_value: None,
+                public: false,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_flag_value(&FlagValue {
+                id: "v1".to_string(),
+                flag_id: "f1".to_string(),
+                environment_id: "e1".to_string(),
+                enabled: true,
+                rollout_percentage: 100,
+                value: None,
+                updated_at: now,
+                version: 2,
+                reason: None,
+            })
+            .await
+            .unwrap();
+
+        let guard = Guard {
+            id: "g1".to_string(),
+            project_id: "p1".to_string(),
+            flag_id: "f1".to_string(),
+            environment_id: "e1".to_string(),
+            ramp_id: None,
+            url: None,
+            token_hash: "hash".to_string(),
+            version: 2,
+            revert_enabled: false,
+            revert_rollout_percentage: 100,
+            revert_value: None,
+            window_secs: 600,
+            status: GUARD_WATCHING.to_string(),
+            message: None,
+            created_by: "u1".to_string(),
+            created_at: now,
+            ended_at: None,
+        };
+        storage.create_guard(&guard).await.unwrap();
+        guard
+    }
+
+    async fn flag_value(state: &AppState) -> FlagValue {
+        state
+            .storage
+            .get_flag_value("f1", "e1")
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    async fn status(state: &AppState) -> String {
+        state.storage.get_guard("g1").await.unwrap().unwrap().status
+    }
+
+    #[test]
+    fn test_unhealthy() {
+        assert_eq!(unhealthy(StatusCode::OK, "OK"), None);
+        assert_eq!(unhealthy(StatusCode::OK, r#"{"healthy": true}"#), None);
+        assert_eq!(
+            unhealthy(StatusCode::SERVICE_UNAVAILABLE, "").as_deref(),
+            Some("health check answered 503 Service Unavailable")
+        );
+        assert_eq!(
+            unhealthy(
+                StatusCode::OK,
+                r#"{"healthy": false, "message": "error rate 4%"}"#
+            )
+            .as_deref(),
+            Some("error rate 4%")
+        );
+    }
+
+    #[test]
+    fn test_only_public_addresses_are_polled() {
+        for ip in ["93.184.216.34", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.0.0.5",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+
+        let checks = HealthChecks::default();
+        let url = |url: &str| reqwest::Url::parse(url).unwrap();
+        assert_eq!(
+            checks.refuse(&url("https://status.example.com/health")),
+            None
+        );
+        assert!(checks
+            .refuse(&url("http://169.254.169.254/latest/meta-data"))
+            .is_some());
+        assert!(checks.refuse(&url("http://[::1]:3000/health")).is_some());
+
+        let checks = HealthChecks::new(vec!["Health.Internal".to_string()]);
+        assert_eq!(checks.refuse(&url("http://health.internal/ok")), None);
+        assert!(checks
+            .refuse(&url("https://status.example.com/health"))
+            .is_some());
+    }
+
+    #[tokio::test]
+    async fn test_poll_refuses_private_hosts() {
+        let error = HealthChecks::default()
+            .poll("http://localhost:9/health")
+            .await
+            .unwrap_err();
+        assert!(error.contains("isn't a public address"), "{error}");
+    }
+
+    #[tokio::test]
+    async fn test_poll_reads_start_of_answer_without_redirects() {
+        use axum::{response::Redirect, routing::get, Router};
+
+        let app = Router::new()
+            .route("/big", get(|| async { "x".repeat(MAX_BODY_BYTES * 2) }))
+            .route("/moved", get(|| async { Redirect::temporary("/big") }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let checks = HealthChecks::new(vec!["127.0.0.1".to_string()]);
+        let (status, body) = checks.poll(&format!("http://{addr}/big")).await.unwrap();
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.len(), MAX_BODY_BYTES);
+
+        let (status, _) = checks.poll(&format!("http://{addr}/moved")).await.unwrap();
+        assert_eq!(status, StatusCode::TEMPORARY_REDIRECT);
+    }
+
+    #[tokio::test]
+    async fn test_trip_reverts_change() {
+        let state = state();
+        let now = Utc::now();
+        let guard = guarded(&state, now).await;
+
+        assert!(trip(&state, guard, "error rate 4%", now).await.unwrap());
+        let fv = flag_value(&state).await;
+        assert!(!fv.enabled);
+        assert_eq!(fv.version, 3);
+        assert_eq!(
+            fv.reason.as_deref(),
+            Some("Guard g1 tripped: error rate 4%")
+        );
+        let guard = state.storage.get_guard("g1").await.unwrap().unwrap();
+        assert_eq!(guard.status, GUARD_TRIPPED);
+        assert_eq!(guard.message.as_deref(), Some("error rate 4%"));
+    }
+
+    #[tokio::test]
+    async fn test_guard_never_reverts_change_by_hand() {
+        let state = state();
+        let now = Utc::now();
+        let guard = guarded(&state, now).await;
+
+        let mut fv = flag_value(&state).await;
+        fv.rollout_percentage = 50;
+        fv.version += 1;
+        assert!(state.storage.update_flag_value(&fv).await.unwrap());
+
+        assert!(!trip(&state, guard, "error rate 4%", now).await.unwrap());
+        assert_eq!(flag_value(&state).await.rollout_percentage, 50);
+        assert_eq!(status(&state).await, GUARD_SUPERSEDED);
+    }
+
+    #[tokio::test]
+    async fn test_guard_passes_after_window() {
+        let state = state();
+        let now = Utc::now();
+        guarded(&state, now).await;
+
+        check(&state, now + ChronoDuration::seconds(599))
+            .await
+            .unwrap();
+        assert_eq!(status(&state).await, GUARD_WATCHING);
+
+        check(&state, now + ChronoDuration::seconds(600))
+            .await
+            .unwrap();
+        assert_eq!(status(&state).await, GUARD_PASSED);
+        assert!(flag_value(&state).await.enabled);
+    }
+}
@@ -0,0 +1,173 @@
+//! Instance admin handlers
+//! For the operator of a hosted instance; authenticated with ADMIN_TOKEN.
+
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::AuthAdmin;
+use crate::error::{AppError, Result};
+use crate::jobs::JobStats;
+use crate::models::{AppState, ProjectQuota, StorageStats};
+use crate::validation::Validator;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaintenanceState {
+    pub read_only: bool,
+    /// Error message for rejected writes; a default is used when absent
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QuotaResponse {
+    pub project_id: String,
+    pub requests_per_minute: Option<i64>,
+    pub per_key_requests_per_minute: Option<i64>,
+    /// None until a quota is first set
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Omitted or null limits are removed
+#[derive(Debug, Deserialize)]
+pub struct SetQuotaRequest {
+    pub requests_per_minute: Option<i64>,
+    pub per_key_requests_per_minute: Option<i64>,
+}
+
+async fn require_project(state: &AppState, project_id: &str) -> Result<()> {
+    state
+        .storage
+        .get_project_by_id(project_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    Ok(())
+}
+
+/// GET /v1/admin/stats - Row counts and database size
+pub async fn stats(State(state): State<AppState>, _admin: AuthAdmin) -> Result<Json<StorageStats>> {
+    Ok(Json(state.storage.stats().await?))
+}
+
+/// GET /v1/admin/jobs - Run counts of this instance's background jobs
+pub async fn jobs(State(state): State<AppState>, _admin: AuthAdmin) -> Json<Vec<JobStats>> {
+    Json(state.jobs.snapshot())
+}
+
+/// GET /v1/admin/maintenance - Whether the server is read-only
+pub async fn get_maintenance(
+    State(state): State<AppState>,
+    _admin: AuthAdmin,
+) -> Json<MaintenanceState> {
+    Json(MaintenanceState {
+        read_only: state.maintenance.is_read_only(),
+        message: state.maintenance.custom_message(),
+    })
+}
+
+/// PUT /v1/admin/maintenance - Turn read-only maintenance mode on or off
+pub async fn set_maintenance(
+    State(state): State<AppState>,
+    _admin: AuthAdmin,
+    Json(req): Json<MaintenanceState>,
+) -> Json<MaintenanceState> {
+    let message = req.message.filter(|m| !m.trim().is_empty());
+    state.maintenance.set(req.read_only, message.clone());
+    Json(MaintenanceState {
+        read_only: req.read_only,
+        message,
+    })
+}
+
+/// GET /v1/admin/backup - Download a consistent snapshot of the database
+///
+/// The snapshot is written to a temporary file and read back, which suits the
+/// small SQLite deployments this is for.
+pub async fn backup(State(state): State<AppState>, _admin: AuthAdmin) -> Result<Response> {
+    let path = std::env::temp_dir().join(format!("flaglite-backup-{}.db", uuid::Uuid::new_v4()));
+    let snapshot = match state.storage.backup(&path).await {
+        Ok(()) => tokio::fs::read(&path)
+            .await
+            .map_err(|e| AppError::Internal(format!("Failed to read backup: {e}"))),
+        Err(e) => Err(e),
+    };
+    // Also cleans up after a backup that failed partway
+    let _ = tokio::fs::remove_file(&path).await;
+    let snapshot = snapshot?;
+
+    let filename = format!("flaglite-{}.db", Utc::now().format("%Y%m%d-%H%M%S"));
+    tracing::info!("Database backup downloaded ({} bytes)", snapshot.len());
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.sqlite3".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{filename}\""),
+            ),
+        ],
+        snapshot,
+    )
+        .into_response())
+}
+
+/// GET /v1/admin/projects/:project_id/quota - A project's request quota
+pub async fn get_quota(
+    State(state): State<AppState>,
+    _admin: AuthAdmin,
+    Path(project_id): Path<String>,
+) -> Result<Json<QuotaResponse>> {
+    require_project(&state, &project_id).await?;
+
+    let quota = state.storage.get_project_quota(&project_id).await?;
+    Ok(Json(QuotaResponse {
+        project_id,
+        requests_per_minute: quota.as_ref().and_then(|q| q.requests_per_minute),
+        per_key_requests_per_minute: quota.as_ref().and_then(|q| q.per_key_requests_per_minute),
+        updated_at: quota.map(|q| q.updated_at),
+    }))
+}
+
+/// PUT /v1/admin/projects/:project_id/quota - Set or lift a project's request quota
+pub async fn set_quota(
+    State(state): State<AppState>,
+    _admin: AuthAdmin,
+    Path(project_id): Path<String>,
+    Json(req): Json<SetQuotaRequest>,
+) -> Result<Json<QuotaResponse>> {
+    Validator::new()
+        .positive("requests_per_minute", "Quota", req.requests_per_minute)
+        .positive(
+            "per_key_requests_per_minute",
+            "Per-key quota",
+            req.per_key_requests_per_minute,
+        )
+        .finish()?;
+    require_project(&state, &project_id).await?;
+
+    let quota = ProjectQuota {
+        project_id,
+        requests_per_minute: req.requests_per_minute,
+        per_key_requests_per_minute: req.per_key_requests_per_minute,
+        updated_at: Utc::now(),
+    };
+    state.storage.set_project_quota(&quota).await?;
+    state.quotas.invalidate(&quota.project_id);
+
+    tracing::info!(
+        "Quota for project {} set to {:?}/min ({:?}/min per key)",
+        quota.project_id,
+        quota.requests_per_minute,
+        quota.per_key_requests_per_minute
+    );
+
+    Ok(Json(QuotaResponse {
+        project_id: quota.project_id,
+        requests_per_minute: quota.requests_per_minute,
+        per_key_requests_per_minute: quota.per_key_requests_per_minute,
+        updated_at: Some(quota.updated_at),
+    }))
+}
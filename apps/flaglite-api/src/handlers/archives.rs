@@ -0,0 +1,530 @@
+//! Project export and import handlers
+//! See [`crate::archive`] for what an archive holds.
+
+use axum::{
+    body::Bytes,
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+use crate::archive::{
+    ArchivedEnvironment, ArchivedFlag, ArchivedListedUser, ArchivedProject, ArchivedTemplate,
+    ArchivedValue, Manifest, ProjectArchive, FORMAT_VERSION,
+};
+use crate::auth::AuthUser;
+use crate::bus;
+use crate::error::{AppError, Result};
+use crate::handlers::cli::{new_project_slug, CliProject};
+use crate::handlers::user_lists::{MAX_LISTED_USERS, MAX_USER_ID_LENGTH};
+use crate::models::{
+    generate_env_api_key, generate_project_api_key, generate_public_client_id, AppState,
+    Environment, Flag, FlagTemplate, FlagValue, ListedUser, Project, LIST_ALLOW, LIST_DENY,
+};
+use crate::slug;
+use crate::templates::Template;
+use crate::validation::{
+    Validator, MAX_CACHE_SECONDS, MAX_EMOJI_LENGTH, MAX_NAME_LENGTH, MAX_REFERENCE_LENGTH,
+};
+
+const ARCHIVE_CONTENT_TYPE: &str = "application/gzip";
+const FLAG_TYPES: [&str; 4] = ["boolean", "string", "number", "json"];
+
+#[derive(Debug, Deserialize)]
+pub struct ImportProjectQuery {
+    /// Name of the new project; the archived project's name by default
+    pub name: Option<String>,
+    pub org_id: Option<String>,
+}
+
+/// GET /projects/:project_id/export - The project as a `.tar.gz` archive
+pub async fn export_project(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+) -> Result<Response> {
+    let project = auth.project(&state, &project_id).await?;
+
+    let environments = state
+        .storage
+        .list_environments_by_project(&project.id)
+        .await?;
+    let flags = state.storage.list_flags_by_project(&project.id).await?;
+    let flag_ids: Vec<String> = flags.iter().map(|f| f.id.clone()).collect();
+    let values = state
+        .storage
+        .list_flag_values_by_flag_ids(&flag_ids)
+        .await?;
+    let mut listed = Vec::new();
+    for env in &environments {
+        listed.extend(
+            state
+                .storage
+                .list_listed_users_by_environment(&env.id)
+                .await?,
+        );
+    }
+    let templates = state.storage.list_flag_templates(&project.id).await?;
+
+    let flag_keys: HashMap<&str, &str> = flags
+        .iter()
+        .map(|f| (f.id.as_str(), f.key.as_str()))
+        .collect();
+    let env_names: HashMap<&str, &str> = environments
+        .iter()
+        .map(|e| (e.id.as_str(), e.name.as_str()))
+        .collect();
+    let names = |flag_id: &str, environment_id: &str| {
+        Some((
+            flag_keys.get(flag_id)?.to_string(),
+            env_names.get(environment_id)?.to_string(),
+        ))
+    };
+
+    let archive = ProjectArchive {
+        manifest: Manifest {
+            format: FORMAT_VERSION,
+            flaglite_version: env!("CARGO_PKG_VERSION").to_string(),
+            exported_at: Utc::now(),
+            project_id: project.id.clone(),
+        },
+        project: ArchivedProject {
+            name: project.name.clone(),
+            slug: project.slug.clone(),
+            require_change_reason: project.require_change_reason,
+        },
+        environments: environments
+            .iter()
+            .map(|e| ArchivedEnvironment {
+                name: e.name.clone(),
+                protected: e.protected,
+                sort_order: e.sort_order,
+                color: e.color.clone(),
+                emoji: e.emoji.clone(),
+                cache_max_age: e.cache_max_age,
+                cache_stale_while_revalidate: e.cache_stale_while_revalidate,
+                public: e.public_client_id.is_some(),
+                require_approval: e.require_approval,
+            })
+            .collect(),
+        values: values
+            .iter()
+            .filter_map(|fv| {
+                let (flag, environment) = names(&fv.flag_id, &fv.environment_id)?;
+                Some(ArchivedValue {
+                    flag,
+                    environment,
+                    enabled: fv.enabled,
+                    rollout_percentage: fv.rollout_percentage,
+                    value: fv.parsed_value(),
+                    updated_at: fv.updated_at,
+                })
+            })
+            .collect(),
+        user_lists: listed
+            .into_iter()
+            .filter_map(|u| {
+                let (flag, environment) = names(&u.flag_id, &u.environment_id)?;
+                Some(ArchivedListedUser {
+                    flag,
+                    environment,
+                    user_id: u.user_id,
+                    list: u.list,
+                })
+            })
+            .collect(),
+        flags: flags
+            .iter()
+            .map(|f| ArchivedFlag {
+                key: f.key.clone(),
+                name: f.name.clone(),
+                description: f.description.clone(),
+                created_at: f.created_at,
+                expires_at: f.expires_at,
+                owner: f.owner.clone(),
+                repository: f.repository.clone(),
+                code_path: f.code_path.clone(),
+                flag_type: f.flag_type.clone(),
+                json_schema: f.schema(),
+                default_enabled: f.default_enabled,
+                default_value: f.parsed_default_value(),
+                public: f.public,
+            })
+            .collect(),
+        templates: templates
+            .into_iter()
+            .map(Template::from)
+            .map(|t| ArchivedTemplate {
+                name: t.name,
+                description: t.description,
+                default_enabled: t.default.enabled,
+                default_rollout: t.default.rollout,
+                environments: t.environments,
+            })
+            .collect(),
+    };
+    let body = archive
+        .to_tar_gz()
+        .map_err(|e| AppError::Internal(format!("Failed to write archive: {e}")))?;
+
+    let disposition = format!("attachment; filename=\"{}.tar.gz\"", project.slug);
+    Ok((
+        [
+            (header::CONTENT_TYPE, ARCHIVE_CONTENT_TYPE.to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Every check an archive must pass before anything is created, so a bad
+/// archive leaves no half-imported project behind
+fn validate(archive: &ProjectArchive, name: &str) -> Result<()> {
+    let mut validator = Validator::new();
+    validator.name("name", "Project name", name, MAX_NAME_LENGTH);
+
+    let mut env_names = HashSet::new();
+    for (i, env) in archive.environments.iter().enumerate() {
+        let field = |name: &str| format!("environments[{i}].{name}");
+        validator
+            .environment_name(&field("name"), &env.name)
+            .max_length(
+                &field("emoji"),
+                "Emoji",
+                env.emoji.as_deref().unwrap_or(""),
+                MAX_EMOJI_LENGTH,
+            )
+            .seconds(
+                &field("cache_max_age"),
+                "Cache max age",
+                env.cache_max_age,
+                MAX_CACHE_SECONDS,
+            )
+            .seconds(
+                &field("cache_stale_while_revalidate"),
+                "Stale-while-revalidate",
+                env.cache_stale_while_revalidate,
+                MAX_CACHE_SECONDS,
+            );
+        if let Some(color) = &env.color {
+            validator.color(&field("color"), color);
+        }
+        if !env_names.insert(env.name.as_str()) {
+            validator.fail(
+                &field("name"),
+                "unique",
+                format!("Environment '{}' is archived twice", env.name),
+            );
+        }
+    }
+
+    let mut flags = HashMap::new();
+    for (i, flag) in archive.flags.iter().enumerate() {
+        let field = |name: &str| format!("flags[{i}].{name}");
+        validator
+            .flag_key(&field("key"), &flag.key)
+            .max_length(&field("name"), "Flag name", &flag.name, MAX_NAME_LENGTH)
+            .max_length(
+                &field("owner"),
+                "Owner",
+                flag.owner.as_deref().unwrap_or(""),
+                MAX_NAME_LENGTH,
+            )
+            .max_length(
+                &field("repository"),
+                "Repository",
+                flag.repository.as_deref().unwrap_or(""),
+                MAX_REFERENCE_LENGTH,
+            )
+            .max_length(
+                &field("code_path"),
+                "Code path",
+                flag.code_path.as_deref().unwrap_or(""),
+                MAX_REFERENCE_LENGTH,
+            );
+        if !FLAG_TYPES.contains(&flag.flag_type.as_str()) {
+            validator.fail(
+                &field("flag_type"),
+                "one_of",
+                format!("Flag type must be one of {}", FLAG_TYPES.join(", ")),
+            );
+        }
+        if let Some(schema) = &flag.json_schema {
+            if flag.flag_type == "json" {
+                validator.json_schema(&field("json_schema"), schema);
+            } else {
+                validator.fail(
+                    &field("json_schema"),
+                    "schema",
+                    "A JSON Schema can only be attached to json flags",
+                );
+            }
+        }
+        if let (Some(schema), Some(value)) = (&flag.json_schema, &flag.default_value) {
+            validator.matches_schema(&field("default_value"), schema, value);
+        }
+        if flags.insert(flag.key.as_str(), flag).is_some() {
+            validator.fail(
+                &field("key"),
+                "unique",
+                format!("Flag '{}' is archived twice", flag.key),
+            );
+        }
+    }
+
+    // Values and list entries must name an archived flag and environment
+    let mut check_refs = |field: &str, flag: &str, environment: &str| {
+        if !flags.contains_key(flag) {
+            validator.fail(field, "reference", format!("Unknown flag '{flag}'"));
+        }
+        if !env_names.contains(environment) {
+            validator.fail(
+                field,
+                "reference",
+                format!("Unknown environment '{environment}'"),
+            );
+        }
+    };
+    for (i, value) in archive.values.iter().enumerate() {
+        check_refs(&format!("values[{i}]"), &value.flag, &value.environment);
+    }
+    let mut list_sizes: HashMap<(&str, &str, &str), usize> = HashMap::new();
+    for (i, listed) in archive.user_lists.iter().enumerate() {
+        check_refs(
+            &format!("user_lists[{i}]"),
+            &listed.flag,
+            &listed.environment,
+        );
+        *list_sizes
+            .entry((&listed.flag, &listed.environment, &listed.list))
+            .or_default() += 1;
+    }
+
+    let mut seen = HashSet::new();
+    for (i, value) in archive.values.iter().enumerate() {
+        let field = format!("values[{i}]");
+        validator.rollout(
+            &format!("{field}.rollout_percentage"),
+            value.rollout_percentage,
+        );
+        let schema = flags
+            .get(value.flag.as_str())
+            .and_then(|f| f.json_schema.as_ref());
+        if let (Some(schema), Some(json)) = (schema, &value.value) {
+            validator.matches_schema(&format!("{field}.value"), schema, json);
+        }
+        if !seen.insert((value.flag.as_str(), value.environment.as_str())) {
+            validator.fail(
+                &field,
+                "unique",
+                format!(
+                    "Flag '{}' has two values in '{}'",
+                    value.flag, value.environment
+                ),
+            );
+        }
+    }
+
+    for (i, listed) in archive.user_lists.iter().enumerate() {
+        let field = format!("user_lists[{i}]");
+        if listed.list != LIST_ALLOW && listed.list != LIST_DENY {
+            validator.fail(
+                &format!("{field}.list"),
+                "one_of",
+                format!("List must be '{LIST_ALLOW}' or '{LIST_DENY}'"),
+            );
+        }
+        if listed.user_id.trim().is_empty() || listed.user_id.len() > MAX_USER_ID_LENGTH {
+            validator.fail(
+                &format!("{field}.user_id"),
+                "max_length",
+                format!("User IDs must be 1 to {MAX_USER_ID_LENGTH} characters"),
+            );
+        }
+    }
+    if let Some(((flag, environment, _), _)) = list_sizes
+        .iter()
+        .find(|(_, size)| **size > MAX_LISTED_USERS)
+    {
+        validator.fail(
+            "user_lists",
+            "max_length",
+            format!("Flag '{flag}' lists more than {MAX_LISTED_USERS} users in '{environment}'"),
+        );
+    }
+
+    for (i, template) in archive.templates.iter().enumerate() {
+        let field = |name: &str| format!("templates[{i}].{name}");
+        validator
+            .name(
+                &field("name"),
+                "Template name",
+                &template.name,
+                MAX_NAME_LENGTH,
+            )
+            .rollout(&field("default_rollout"), template.default_rollout);
+        if slug::slugify(&template.name) != template.name {
+            validator.fail(
+                &field("name"),
+                "charset",
+                "Template name can only contain lowercase letters, numbers, and hyphens",
+            );
+        }
+        for (env, env_state) in &template.environments {
+            validator.rollout(
+                &field(&format!("environments.{env}.rollout")),
+                env_state.rollout,
+            );
+        }
+    }
+
+    validator.finish()
+}
+
+/// POST /projects/import - Create a project from an archive made by
+/// `GET /projects/:project_id/export`
+///
+/// The new project gets new ids and keys. Flag values start a new history at
+/// version 1, and frozen environments aren't frozen in the copy.
+pub async fn import_project(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Query(query): Query<ImportProjectQuery>,
+    body: Bytes,
+) -> Result<Json<CliProject>> {
+    let archive = ProjectArchive::from_tar_gz(&body).map_err(AppError::BadRequest)?;
+    let name = query
+        .name
+        .unwrap_or_else(|| archive.project.name.clone())
+        .trim()
+        .to_string();
+    validate(&archive, &name)?;
+    let org_id = auth.target_org(&state, query.org_id.as_deref()).await?;
+
+    let now = Utc::now();
+    let project = Project {
+        id: Uuid::new_v4().to_string(),
+        user_id: auth.user.id.clone(),
+        org_id: Some(org_id),
+        slug: new_project_slug(&state, &auth.user.id, &name).await?,
+        name,
+        api_key: generate_project_api_key(),
+        created_at: now,
+        require_change_reason: archive.project.require_change_reason,
+    };
+    state.storage.create_project(&project).await?;
+
+    let mut env_ids = HashMap::new();
+    for archived in &archive.environments {
+        let env = Environment {
+            id: Uuid::new_v4().to_string(),
+            project_id: project.id.clone(),
+            name: archived.name.clone(),
+            api_key: generate_env_api_key(),
+            protected: archived.protected,
+            frozen: false,
+            sort_order: archived.sort_order,
+            color: archived.color.clone(),
+            emoji: archived.emoji.clone(),
+            cache_max_age: archived.cache_max_age,
+            cache_stale_while_revalidate: archived.cache_stale_while_revalidate,
+            public_client_id: archived.public.then(generate_public_client_id),
+            require_approval: archived.require_approval,
+            created_at: now,
+        };
+        state.storage.create_environment(&env).await?;
+        env_ids.insert(archived.name.as_str(), env.id);
+    }
+
+    let mut flag_ids = HashMap::new();
+    for archived in &archive.flags {
+        let flag = Flag {
+            id: Uuid::new_v4().to_string(),
+            project_id: project.id.clone(),
+            key: archived.key.clone(),
+            name: archived.name.clone(),
+            description: archived.description.clone(),
+            created_at: archived.created_at,
+            expires_at: archived.expires_at,
+            owner: archived.owner.clone(),
+            repository: archived.repository.clone(),
+            code_path: archived.code_path.clone(),
+            flag_type: archived.flag_type.clone(),
+            json_schema: archived.json_schema.as_ref().map(|s| s.to_string()),
+            default_enabled: archived.default_enabled,
+            default_value: archived.default_value.as_ref().map(|v| v.to_string()),
+            public: archived.public,
+        };
+        state.storage.create_flag(&flag).await?;
+        flag_ids.insert(archived.key.as_str(), flag.id);
+    }
+
+    let values: Vec<FlagValue> = archive
+        .values
+        .iter()
+        .map(|archived| FlagValue {
+            id: Uuid::new_v4().to_string(),
+            flag_id: flag_ids[archived.flag.as_str()].clone(),
+            environment_id: env_ids[archived.environment.as_str()].clone(),
+            enabled: archived.enabled,
+            rollout_percentage: archived.rollout_percentage,
+            value: archived.value.as_ref().map(|v| v.to_string()),
+            updated_at: archived.updated_at,
+            version: 1,
+            reason: None,
+        })
+        .collect();
+    state.storage.upsert_flag_values(&values).await?;
+
+    let listed: Vec<ListedUser> = archive
+        .user_lists
+        .iter()
+        .map(|archived| ListedUser {
+            flag_id: flag_ids[archived.flag.as_str()].clone(),
+            environment_id: env_ids[archived.environment.as_str()].clone(),
+            user_id: archived.user_id.clone(),
+            list: archived.list.clone(),
+            created_at: now,
+        })
+        .collect();
+    state.storage.add_listed_users(&listed).await?;
+
+    for archived in &archive.templates {
+        state
+            .storage
+            .set_flag_template(&FlagTemplate {
+                project_id: project.id.clone(),
+                name: archived.name.clone(),
+                description: archived.description.clone(),
+                default_enabled: archived.default_enabled,
+                default_rollout: archived.default_rollout,
+                environments: serde_json::to_string(&archived.environments)
+                    .map_err(|e| AppError::Internal(e.to_string()))?,
+                updated_at: now,
+            })
+            .await?;
+    }
+
+    bus::audit(
+        &state,
+        &project.id,
+        "project.imported",
+        &auth.user.username,
+        format!(
+            "Project {}: imported by {} from an archive of project {} ({} environment(s), {} flag(s))",
+            project.id,
+            auth.user.username,
+            archive.manifest.project_id,
+            archive.environments.len(),
+            archive.flags.len()
+        ),
+    );
+
+    Ok(Json(project.into()))
+}
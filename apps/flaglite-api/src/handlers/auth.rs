@@ -1,18 +1,40 @@
-use axum::{extract::State, Json};
-use chrono::Utc;
+use axum::{
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use std::net::SocketAddr;
 use uuid::Uuid;
 
-use crate::auth::{create_jwt, hash_api_key, hash_password, verify_password, AuthUser};
+use crate::auth::{create_jwt, hash_api_key, hash_password, verify_password, AuthAdmin, AuthUser};
+use crate::bus;
+use crate::config::SignupMode;
 use crate::error::{AppError, Result};
+use crate::lockout;
+use crate::mailer::Email;
 use crate::models::{
-    generate_env_api_key, generate_project_api_key, generate_user_api_key, ApiKey,
-    ApiKeyCreatedResponse, AppState, AuthResponse, Environment, LoginRequest, Project,
-    SignupRequest, SignupResponse, UpdateUserRequest, User, UserResponse,
+    generate_account_token, generate_env_api_key, generate_invite_code, generate_project_api_key,
+    generate_user_api_key, personal_org_id, AccountToken, ApiKey, ApiKeyCreatedResponse,
+    ApiKeyResponse, ApiKeyScope, AppState, AuthResponse, CreateApiKeyRequest, Environment, Invite,
+    InviteResponse, LoginRequest, OrgMember, Organization, PasswordResetRequest, Project,
+    ResetPasswordRequest, SignupRequest, SignupResponse, UpdatePreferencesRequest,
+    UpdateUserRequest, User, UserPreferences, UserResponse, UsernameChange, VerifyEmailRequest,
+    ROLE_OWNER, TOKEN_EMAIL_VERIFICATION, TOKEN_PASSWORD_RESET,
 };
+use crate::slug;
 use crate::username::{generate_username, generate_username_with_suffix};
+use crate::validation::{Validator, MAX_NAME_LENGTH};
 
 const DEFAULT_ENVIRONMENTS: [&str; 3] = ["development", "staging", "production"];
 const MAX_USERNAME_RETRIES: u32 = 10;
+pub const INVITE_VALID_DAYS: i64 = 7;
+pub const RESET_TOKEN_VALID_HOURS: i64 = 1;
+pub const VERIFICATION_TOKEN_VALID_HOURS: i64 = 24;
+/// How long after changing their username a user has to wait to change it again
+pub const USERNAME_CHANGE_COOLDOWN_DAYS: i64 = 30;
+/// Output formats the CLI accepts for the `format` preference
+const PREFERENCE_FORMATS: [&str; 5] = ["pretty", "json", "json-v2", "csv", "tsv"];
 
 /// POST /v1/auth/signup
 /// Creates a new user account with optional username (auto-generated if not provided)
@@ -21,38 +43,19 @@ pub async fn signup(
     State(state): State<AppState>,
     Json(req): Json<SignupRequest>,
 ) -> Result<Json<SignupResponse>> {
-    // Validate password
-    if req.password.len() < 8 {
-        return Err(AppError::BadRequest(
-            "Password must be at least 8 characters".to_string(),
-        ));
+    let provided_username = req.username.map(|u| u.trim().to_lowercase());
+
+    let mut validator = Validator::new();
+    validator.password("password", &req.password);
+    if let Some(username) = &provided_username {
+        validator.username("username", username);
     }
+    validator.finish()?;
 
     // Generate or validate username
-    let username = if let Some(provided_username) = req.username {
-        // Validate provided username
-        let username = provided_username.trim().to_lowercase();
-        if username.len() < 3 {
-            return Err(AppError::BadRequest(
-                "Username must be at least 3 characters".to_string(),
-            ));
-        }
-        if username.len() > 32 {
-            return Err(AppError::BadRequest(
-                "Username must be at most 32 characters".to_string(),
-            ));
-        }
-        if !username
-            .chars()
-            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
-        {
-            return Err(AppError::BadRequest(
-                "Username can only contain letters, numbers, hyphens, and underscores".to_string(),
-            ));
-        }
-
-        // Check if username exists
-        if state.storage.username_exists(&username).await? {
+    let username = if let Some(username) = provided_username {
+        // Check if username exists, or belonged to someone before
+        if username_holder(&state, &username).await?.is_some() {
             return Err(AppError::UserAlreadyExists);
         }
 
@@ -62,7 +65,7 @@ pub async fn signup(
         let mut username = generate_username();
         let mut retries = 0;
 
-        while state.storage.username_exists(&username).await? {
+        while username_holder(&state, &username).await?.is_some() {
             if retries >= MAX_USERNAME_RETRIES {
                 // Fall back to username with suffix
                 username = generate_username_with_suffix();
@@ -81,16 +84,40 @@ pub async fn signup(
         username
     };
 
-    // Create user
     let user_id = Uuid::new_v4().to_string();
-    let password_hash = hash_password(&req.password)?;
     let now = Utc::now();
 
+    // Enforce SIGNUP_MODE; redeeming before the user exists keeps a code single-use under races
+    match state.signup_mode {
+        SignupMode::Open => {}
+        SignupMode::Disabled => {
+            return Err(AppError::Forbidden("Signup is disabled".to_string()));
+        }
+        SignupMode::Invite => {
+            let code = req.invite_code.as_deref().map(str::trim).unwrap_or("");
+            if code.is_empty() {
+                return Err(AppError::Forbidden(
+                    "An invite code is required to sign up".to_string(),
+                ));
+            }
+            if !state.storage.redeem_invite(code, &user_id, now).await? {
+                return Err(AppError::Forbidden(
+                    "Invalid or expired invite code".to_string(),
+                ));
+            }
+        }
+    }
+
+    // Create user
+    let password_hash = hash_password(&req.password)?;
+
     let user = User {
         id: user_id.clone(),
         username: username.clone(),
         password_hash,
         email: None,
+        email_verified_at: None,
+        session_epoch: 0,
         created_at: now,
         updated_at: now,
     };
@@ -111,10 +138,30 @@ pub async fn signup(
         name: Some("Default API Key".to_string()),
         created_at: now,
         revoked_at: None,
+        org_id: None,
+        scope: ApiKeyScope::Admin.to_string(),
     };
 
     state.storage.create_api_key(&api_key).await?;
 
+    // Every user owns a personal org; their first project lives there
+    let org = Organization {
+        id: personal_org_id(&user_id),
+        name: username.clone(),
+        personal: true,
+        created_at: now,
+    };
+    state.storage.create_organization(&org).await?;
+    state
+        .storage
+        .add_org_member(&OrgMember {
+            org_id: org.id.clone(),
+            user_id: user_id.clone(),
+            role: ROLE_OWNER.to_string(),
+            created_at: now,
+        })
+        .await?;
+
     // Create first project
     let project_name = req.project_name.unwrap_or_else(|| "default".to_string());
     let project_id = Uuid::new_v4().to_string();
@@ -123,16 +170,19 @@ pub async fn signup(
     let project = Project {
         id: project_id.clone(),
         user_id: user_id.clone(),
+        org_id: Some(org.id),
+        slug: slug::slugify(&project_name),
         name: project_name,
         api_key: project_api_key,
         created_at: now,
+        require_change_reason: false,
     };
 
     state.storage.create_project(&project).await?;
 
     // Create 3 default environments
     let mut environments = Vec::new();
-    for env_name in DEFAULT_ENVIRONMENTS {
+    for (sort_order, env_name) in (0..).zip(DEFAULT_ENVIRONMENTS) {
         let env_id = Uuid::new_v4().to_string();
         let env_api_key = generate_env_api_key();
 
@@ -141,6 +191,15 @@ pub async fn signup(
             project_id: project_id.clone(),
             name: env_name.to_string(),
             api_key: env_api_key,
+            protected: env_name == "production",
+            frozen: false,
+            sort_order,
+            color: None,
+            emoji: None,
+            cache_max_age: None,
+            cache_stale_while_revalidate: None,
+            public_client_id: None,
+            require_approval: false,
             created_at: now,
         };
 
@@ -149,7 +208,7 @@ pub async fn signup(
     }
 
     // Create JWT
-    let token = create_jwt(&user, &state.jwt_secret)?;
+    let token = create_jwt(&user, &state.jwt_keys)?;
 
     Ok(Json(SignupResponse {
         user: user.into(),
@@ -158,6 +217,7 @@ pub async fn signup(
             key: api_key_raw, // Full key - only shown once!
             key_prefix: api_key_prefix,
             name: Some("Default API Key".to_string()),
+            scope: ApiKeyScope::Admin,
             created_at: now,
         },
         token,
@@ -169,59 +229,584 @@ pub async fn signup(
 /// POST /v1/auth/login
 /// Authenticates a user with username and password
 /// Returns user info and JWT token
+/// Repeated failures lock the username or client IP out with 429
 pub async fn login(
     State(state): State<AppState>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(req): Json<LoginRequest>,
 ) -> Result<Json<AuthResponse>> {
-    // Find user by username
-    let user = state
-        .storage
-        .get_user_by_username(&req.username.to_lowercase())
-        .await?
-        .ok_or(AppError::InvalidCredentials)?;
+    let username = req.username.to_lowercase();
+    let ip = lockout::client_ip(peer.map(|c| c.0), &headers, state.trust_proxy);
+    let now = Utc::now();
+    let attempt = state.logins.begin(&username, ip, now)?;
 
-    // Verify password
-    if !verify_password(&req.password, &user.password_hash)? {
-        return Err(AppError::InvalidCredentials);
-    }
+    // Unknown usernames count as failures too, so they can't be probed faster
+    let user = state.storage.get_user_by_username(&username).await?;
+    let verified = match &user {
+        Some(user) => verify_password(&req.password, &user.password_hash)?,
+        None => false,
+    };
+    let Some(user) = user.filter(|_| verified) else {
+        let lockouts = attempt.fail(now);
+        for lockout in &lockouts {
+            lockout::audit(lockout);
+        }
+        return Err(match lockouts.iter().map(|l| l.retry_after).max() {
+            Some(retry_after) => AppError::LoginLocked { retry_after },
+            None => AppError::InvalidCredentials,
+        });
+    };
+    attempt.succeed();
 
     // Create JWT
-    let token = create_jwt(&user, &state.jwt_secret)?;
+    let token = create_jwt(&user, &state.jwt_keys)?;
 
     Ok(Json(AuthResponse {
         token,
         user: user.into(),
-        project: None,
-        environments: None,
     }))
 }
 
 /// GET /v1/auth/me
 /// Returns the authenticated user's info
 /// Requires JWT or API key
-pub async fn me(AuthUser(user): AuthUser) -> Result<Json<UserResponse>> {
+pub async fn me(AuthUser { user, .. }: AuthUser) -> Result<Json<UserResponse>> {
     Ok(Json(user.into()))
 }
 
 /// PATCH /v1/auth/me
-/// Updates the authenticated user's info (email)
+/// Updates the authenticated user's info (email, username)
 /// Requires JWT or API key
 pub async fn update_me(
     State(state): State<AppState>,
-    AuthUser(mut user): AuthUser,
+    AuthUser { mut user, .. }: AuthUser,
     Json(req): Json<UpdateUserRequest>,
 ) -> Result<Json<UserResponse>> {
+    let now = Utc::now();
+
+    if let Some(username) = req.username {
+        let username = username.trim().to_lowercase();
+        if username != user.username {
+            rename(&state, &mut user, username, now).await?;
+        }
+    }
+
     // Update email if provided
     if let Some(email) = req.email {
         let email = email.trim().to_lowercase();
-        if !email.is_empty() && !email.contains('@') {
-            return Err(AppError::BadRequest("Invalid email format".to_string()));
+        Validator::new().email("email", &email).finish()?;
+        let email = if email.is_empty() { None } else { Some(email) };
+        // A new address has to be verified again
+        if email != user.email {
+            user.email_verified_at = None;
         }
-        user.email = if email.is_empty() { None } else { Some(email) };
+        user.email = email;
     }
 
-    user.updated_at = Utc::now();
+    user.updated_at = now;
     state.storage.update_user(&user).await?;
 
     Ok(Json(user.into()))
 }
+
+/// GET /v1/auth/me/username-changes
+/// Returns the authenticated user's username changes, newest first
+pub async fn list_username_changes(
+    State(state): State<AppState>,
+    AuthUser { user, .. }: AuthUser,
+) -> Result<Json<Vec<UsernameChange>>> {
+    Ok(Json(state.storage.list_username_changes(&user.id).await?))
+}
+
+/// Give the user a new username. The old one stays reserved for them, and
+/// each of their projects gets an audit event linking the two names
+async fn rename(
+    state: &AppState,
+    user: &mut User,
+    username: String,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    Validator::new().username("username", &username).finish()?;
+
+    if let Some(last) = state.storage.list_username_changes(&user.id).await?.first() {
+        let allowed_at = last.changed_at + Duration::days(USERNAME_CHANGE_COOLDOWN_DAYS);
+        if now < allowed_at {
+            return Err(AppError::RenameCooldown {
+                retry_after: (allowed_at - now).num_seconds().max(1) as u64,
+            });
+        }
+    }
+
+    // Taking back one of your own old names is fine
+    if username_holder(state, &username)
+        .await?
+        .is_some_and(|holder| holder != user.id)
+    {
+        return Err(AppError::Conflict(format!(
+            "Username '{username}' is taken"
+        )));
+    }
+
+    let change = UsernameChange {
+        user_id: user.id.clone(),
+        old_username: user.username.clone(),
+        new_username: username,
+        changed_at: now,
+    };
+    state.storage.rename_user(&change).await?;
+    user.username = change.new_username.clone();
+    user.updated_at = now;
+
+    for project in state.storage.list_projects_by_user(&user.id).await? {
+        bus::audit(
+            state,
+            &project.id,
+            "user.renamed",
+            &change.new_username,
+            format!(
+                "User {} renamed from {} to {}",
+                user.id, change.old_username, change.new_username
+            ),
+        );
+    }
+    Ok(())
+}
+
+/// Who has `username`, or had it last: a user's old usernames stay reserved
+/// for them, so nobody else can be mistaken for them in older audit entries
+async fn username_holder(state: &AppState, username: &str) -> Result<Option<String>> {
+    if let Some(user) = state.storage.get_user_by_username(username).await? {
+        return Ok(Some(user.id));
+    }
+    Ok(state
+        .storage
+        .get_username_change(username)
+        .await?
+        .map(|change| change.user_id))
+}
+
+/// Look a user up by their username, or by one they've since changed
+pub async fn find_user_by_username(state: &AppState, username: &str) -> Result<Option<User>> {
+    if let Some(user) = state.storage.get_user_by_username(username).await? {
+        return Ok(Some(user));
+    }
+    match state.storage.get_username_change(username).await? {
+        Some(change) => state.storage.get_user_by_id(&change.user_id).await,
+        None => Ok(None),
+    }
+}
+
+/// GET /v1/auth/preferences
+/// Returns the user's saved CLI defaults; a default project they can no
+/// longer see is left out
+pub async fn get_preferences(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<UserPreferences>> {
+    let mut preferences = state
+        .storage
+        .get_user_preferences(&auth.user.id)
+        .await?
+        .unwrap_or_default();
+
+    if let Some(project_id) = &preferences.project_id {
+        match auth.project(&state, project_id).await {
+            Ok(_) => {}
+            Err(AppError::NotFound(_)) => preferences.project_id = None,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(Json(preferences))
+}
+
+/// PATCH /v1/auth/preferences
+/// Updates the given preferences; an empty string clears one
+/// Requires JWT or API key
+pub async fn update_preferences(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(req): Json<UpdatePreferencesRequest>,
+) -> Result<Json<UserPreferences>> {
+    let clear_empty = |value: String| {
+        let value = value.trim().to_string();
+        (!value.is_empty()).then_some(value)
+    };
+    let environment = req.environment.map(clear_empty);
+    let display_name = req.display_name.map(clear_empty);
+    let format = req.format.map(|f| clear_empty(f).map(|f| f.to_lowercase()));
+
+    let mut validator = Validator::new();
+    if let Some(Some(environment)) = &environment {
+        validator.environment_name("environment", environment);
+    }
+    if let Some(Some(display_name)) = &display_name {
+        validator.max_length(
+            "display_name",
+            "Display name",
+            display_name,
+            MAX_NAME_LENGTH,
+        );
+    }
+    if let Some(Some(format)) = &format {
+        if !PREFERENCE_FORMATS.contains(&format.as_str()) {
+            validator.fail(
+                "format",
+                "format",
+                format!("Format must be one of {}", PREFERENCE_FORMATS.join(", ")),
+            );
+        }
+    }
+    validator.finish()?;
+
+    let mut preferences = state
+        .storage
+        .get_user_preferences(&auth.user.id)
+        .await?
+        .unwrap_or_default();
+    preferences.user_id = auth.user.id.clone();
+
+    // Stored by ID, so renaming the project's slug doesn't lose it
+    if let Some(project_id) = req.project_id.map(clear_empty) {
+        preferences.project_id = match project_id {
+            Some(project_id) => Some(auth.project(&state, &project_id).await?.id),
+            None => None,
+        };
+    }
+    if let Some(environment) = environment {
+        preferences.environment = environment;
+    }
+    if let Some(display_name) = display_name {
+        preferences.display_name = display_name;
+    }
+    if let Some(format) = format {
+        preferences.format = format;
+    }
+
+    preferences.updated_at = Some(Utc::now());
+    state.storage.set_user_preferences(&preferences).await?;
+
+    Ok(Json(preferences))
+}
+
+/// GET /v1/auth/api-keys
+/// Lists the user's API keys, newest first; an org-scoped key sees only its org's keys
+pub async fn list_api_keys(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<Vec<ApiKeyResponse>>> {
+    let keys = state.storage.list_api_keys_by_user(&auth.user.id).await?;
+    Ok(Json(
+        keys.into_iter()
+            .filter(|k| auth.org_scope.is_none() || k.org_id == auth.org_scope)
+            .map(ApiKeyResponse::from)
+            .collect(),
+    ))
+}
+
+/// POST /v1/auth/api-keys
+/// Creates a user API key, optionally with a narrower scope
+/// An org-scoped key can only create keys for its own org
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(req): Json<CreateApiKeyRequest>,
+) -> Result<Json<ApiKeyCreatedResponse>> {
+    let org_id = auth.org_scope.clone();
+    Ok(Json(
+        issue_api_key(&state, &auth, req.name, req.scope.as_deref(), org_id).await?,
+    ))
+}
+
+/// Store a new user API key for the caller; the key itself is only returned here
+pub async fn issue_api_key(
+    state: &AppState,
+    auth: &AuthUser,
+    name: Option<String>,
+    scope: Option<&str>,
+    org_id: Option<String>,
+) -> Result<ApiKeyCreatedResponse> {
+    let scope = match scope {
+        Some(scope) => scope.parse::<ApiKeyScope>().map_err(AppError::BadRequest)?,
+        None => ApiKeyScope::Admin,
+    };
+    // Keys can't hand out more than they have
+    if scope > auth.scope {
+        return Err(AppError::InsufficientScope {
+            scope: auth.scope,
+            required: scope,
+        });
+    }
+
+    let now = Utc::now();
+    let key = generate_user_api_key();
+    let api_key = ApiKey {
+        id: Uuid::new_v4().to_string(),
+        user_id: auth.user.id.clone(),
+        key_hash: hash_api_key(&key),
+        key_prefix: key.chars().take(12).collect(),
+        name,
+        created_at: now,
+        revoked_at: None,
+        org_id,
+        scope: scope.to_string(),
+    };
+    state.storage.create_api_key(&api_key).await?;
+
+    Ok(ApiKeyCreatedResponse {
+        id: api_key.id,
+        key, // Full key - only shown once
+        key_prefix: api_key.key_prefix,
+        name: api_key.name,
+        scope,
+        created_at: now,
+    })
+}
+
+/// POST /v1/auth/password-reset
+/// Emails a reset token to the user's verified address
+/// Succeeds whether or not the user exists, so accounts can't be probed
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Json(req): Json<PasswordResetRequest>,
+) -> Result<()> {
+    let username = req.username.trim().to_lowercase();
+    let Some(user) = state.storage.get_user_by_username(&username).await? else {
+        return Ok(());
+    };
+    let Some(email) = user
+        .email
+        .clone()
+        .filter(|_| user.email_verified_at.is_some())
+    else {
+        tracing::info!("Password reset requested for {username}, who has no verified email");
+        return Ok(());
+    };
+
+    let token = issue_token(
+        &state,
+        &user,
+        TOKEN_PASSWORD_RESET,
+        &email,
+        Duration::hours(RESET_TOKEN_VALID_HOURS),
+    )
+    .await?;
+
+    let email = Email {
+        to: email,
+        subject: "Reset your FlagLite password".to_string(),
+        body: format!(
+            "Someone asked to reset the password for {username}. To choose a new one, run:\n\n    \
+             flaglite account reset --token {token}\n\nThe token expires in {RESET_TOKEN_VALID_HOURS} hour. \
+             If you didn't ask for this, ignore this email."
+        ),
+    };
+    if let Err(e) = state.mailer.send(&email).await {
+        tracing::error!("Failed to send password reset email to {username}: {e:#}");
+    }
+
+    Ok(())
+}
+
+/// POST /v1/auth/password-reset/confirm
+/// Sets a new password with a token from a reset email
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Json(req): Json<ResetPasswordRequest>,
+) -> Result<()> {
+    // Checked first so a rejected password doesn't use up the token
+    Validator::new()
+        .password("password", &req.password)
+        .finish()?;
+
+    let token = redeem_token(&state, &req.token, TOKEN_PASSWORD_RESET).await?;
+    let user = state
+        .storage
+        .get_user_by_id(&token.user_id)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+    // Whoever had the old password may still hold a session made with it
+    state
+        .storage
+        .set_password(&user.id, &hash_password(&req.password)?, true)
+        .await?;
+
+    if req.revoke_api_keys {
+        for key in state.storage.list_api_keys_by_user(&user.id).await? {
+            if key.revoked_at.is_none() {
+                state.storage.revoke_api_key(&key.id).await?;
+            }
+        }
+    }
+
+    tracing::info!("Password reset for {}", user.username);
+
+    Ok(())
+}
+
+/// POST /v1/auth/me/verify-email
+/// Emails a verification token to the authenticated user's address
+pub async fn send_verification_email(
+    State(state): State<AppState>,
+    AuthUser { user, .. }: AuthUser,
+) -> Result<()> {
+    let email = user
+        .email
+        .clone()
+        .ok_or_else(|| AppError::BadRequest("Set an email address first".to_string()))?;
+    if user.email_verified_at.is_some() {
+        return Err(AppError::BadRequest(
+            "Email is already verified".to_string(),
+        ));
+    }
+
+    let token = issue_token(
+        &state,
+        &user,
+        TOKEN_EMAIL_VERIFICATION,
+        &email,
+        Duration::hours(VERIFICATION_TOKEN_VALID_HOURS),
+    )
+    .await?;
+
+    let email = Email {
+        to: email,
+        subject: "Verify your FlagLite email".to_string(),
+        body: format!(
+            "To confirm this address for {}, run:\n\n    flaglite account verify-email --token {token}\n\n\
+             The token expires in {VERIFICATION_TOKEN_VALID_HOURS} hours.",
+            user.username
+        ),
+    };
+    state
+        .mailer
+        .send(&email)
+        .await
+        .map_err(|e| AppError::Internal(format!("Failed to send verification email: {e:#}")))?;
+
+    Ok(())
+}
+
+/// POST /v1/auth/verify-email
+/// Confirms the address a verification token was sent to
+pub async fn verify_email(
+    State(state): State<AppState>,
+    Json(req): Json<VerifyEmailRequest>,
+) -> Result<Json<UserResponse>> {
+    let token = redeem_token(&state, &req.token, TOKEN_EMAIL_VERIFICATION).await?;
+    let mut user = state
+        .storage
+        .get_user_by_id(&token.user_id)
+        .await?
+        .ok_or_else(invalid_token)?;
+
+    if user.email.as_deref() != Some(token.email.as_str()) {
+        return Err(AppError::BadRequest(
+            "The email address changed after this token was sent".to_string(),
+        ));
+    }
+
+    let now = Utc::now();
+    user.email_verified_at = Some(now);
+    user.updated_at = now;
+    state.storage.update_user(&user).await?;
+
+    Ok(Json(user.into()))
+}
+
+/// Store a new single-use token for `user`, returning it in the clear
+async fn issue_token(
+    state: &AppState,
+    user: &User,
+    purpose: &str,
+    email: &str,
+    valid_for: Duration,
+) -> Result<String> {
+    let token = generate_account_token();
+    let now = Utc::now();
+    state
+        .storage
+        .create_account_token(&AccountToken {
+            token_hash: hash_api_key(&token),
+            user_id: user.id.clone(),
+            purpose: purpose.to_string(),
+            email: email.to_string(),
+            created_at: now,
+            expires_at: now + valid_for,
+            used_at: None,
+        })
+        .await?;
+    Ok(token)
+}
+
+async fn redeem_token(state: &AppState, token: &str, purpose: &str) -> Result<AccountToken> {
+    state
+        .storage
+        .redeem_account_token(&hash_api_key(token.trim()), purpose, Utc::now())
+        .await?
+        .ok_or_else(invalid_token)
+}
+
+fn invalid_token() -> AppError {
+    AppError::BadRequest("Invalid or expired token".to_string())
+}
+
+/// Whether the caller owns a team (non-personal) organization its key reaches
+async fn owns_team_org(state: &AppState, auth: &AuthUser) -> Result<bool> {
+    for org in state
+        .storage
+        .list_organizations_by_user(&auth.user.id)
+        .await?
+    {
+        if org.personal
+            || auth
+                .org_scope
+                .as_ref()
+                .is_some_and(|scope| *scope != org.id)
+        {
+            continue;
+        }
+        if auth.org_member(state, &org.id).await?.role == ROLE_OWNER {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// POST /v1/invites
+/// Creates a single-use invite code for SIGNUP_MODE=invite. Only the
+/// instance operator (ADMIN_TOKEN) and owners of a team organization can.
+pub async fn create_invite(
+    State(state): State<AppState>,
+    admin: Option<AuthAdmin>,
+    auth: std::result::Result<AuthUser, AppError>,
+) -> Result<Json<InviteResponse>> {
+    let created_by = match admin {
+        Some(AuthAdmin) => None,
+        None => {
+            let auth = auth?;
+            if !owns_team_org(&state, &auth).await? {
+                return Err(AppError::Forbidden(
+                    "Only organization owners can create invites".to_string(),
+                ));
+            }
+            Some(auth.user.id)
+        }
+    };
+
+    let now = Utc::now();
+    let invite = Invite {
+        code: generate_invite_code(),
+        created_by,
+        created_at: now,
+        expires_at: now + Duration::days(INVITE_VALID_DAYS),
+        used_by: None,
+        used_at: None,
+    };
+
+    state.storage.create_invite(&invite).await?;
+
+    Ok(Json(invite.into()))
+}
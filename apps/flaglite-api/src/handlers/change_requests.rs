@@ -0,0 +1,620 @@
+//! Change request handlers
+//! A change request proposes turning a flag on or off, or changing its
+//! rollout, in one environment. Nothing changes until a project admin (an
+//! owner of the project's org) other than the proposer approves it.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::bus;
+use crate::error::{AppError, Result};
+use crate::handlers::cli::{change_reason, find_flag, record_changes};
+use crate::mailer::Email;
+use crate::models::{
+    AppState, ChangeRequest, Environment, Flag, FlagValue, Project, User, CHANGE_APPROVED,
+    CHANGE_PENDING, CHANGE_REJECTED, ROLE_OWNER,
+};
+use crate::validation::{Validator, MAX_COMMENT_LENGTH};
+
+#[derive(Debug, Deserialize)]
+pub struct CreateChangeRequest {
+    pub flag_key: String,
+    pub environment: String,
+    pub enabled: Option<bool>,
+    pub rollout_percentage: Option<i32>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListChangeRequestsQuery {
+    /// `pending`, `approved` or `rejected`; all when unset
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReviewChangeRequest {
+    pub comment: Option<String>,
+}
+
+/// A change request, with usernames in place of user ids
+#[derive(Debug, Serialize)]
+pub struct ChangeRequestResponse {
+    pub id: String,
+    pub project_id: String,
+    pub flag_key: String,
+    pub environment: String,
+    pub enabled: Option<bool>,
+    pub rollout_percentage: Option<i32>,
+    pub base_version: i64,
+    pub comment: Option<String>,
+    pub status: String,
+    pub proposed_by: String,
+    pub reviewed_by: Option<String>,
+    pub review_comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+/// Resolves user ids to usernames, looking each one up once
+#[derive(Default)]
+pub struct Usernames(HashMap<String, String>);
+
+impl Usernames {
+    pub async fn get(&mut self, state: &AppState, user_id: &str) -> Result<String> {
+        if let Some(username) = self.0.get(user_id) {
+            return Ok(username.clone());
+        }
+        let username = state
+            .storage
+            .get_user_by_id(user_id)
+            .await?
+            .map_or_else(|| user_id.to_string(), |u| u.username);
+        self.0.insert(user_id.to_string(), username.clone());
+        Ok(username)
+    }
+
+    async fn response(
+        &mut self,
+        state: &AppState,
+        change: ChangeRequest,
+    ) -> Result<ChangeRequestResponse> {
+        let proposed_by = self.get(state, &change.proposed_by).await?;
+        let reviewed_by = match &change.reviewed_by {
+            Some(id) => Some(self.get(state, id).await?),
+            None => None,
+        };
+        Ok(ChangeRequestResponse {
+            id: change.id,
+            project_id: change.project_id,
+            flag_key: change.flag_key,
+            environment: change.environment,
+            enabled: change.enabled,
+            rollout_percentage: change.rollout_percentage,
+            base_version: change.base_version,
+            comment: change.comment,
+            status: change.status,
+            proposed_by,
+            reviewed_by,
+            review_comment: change.review_comment,
+            created_at: change.created_at,
+            reviewed_at: change.reviewed_at,
+        })
+    }
+}
+
+/// Users who can approve changes to the project: its org's owners, or its
+/// creator for a project without an org, and owners it was shared with
+pub async fn project_admins(state: &AppState, project: &Project) -> Result<Vec<String>> {
+    let mut admins = match &project.org_id {
+        Some(org_id) => state
+            .storage
+            .list_org_members(org_id)
+            .await?
+            .into_iter()
+            .filter(|m| m.role == ROLE_OWNER)
+            .map(|m| m.user_id)
+            .collect(),
+        None => vec![project.user_id.clone()],
+    };
+    for member in state.storage.list_project_members(&project.id).await? {
+        if member.role == ROLE_OWNER && !admins.contains(&member.user_id) {
+            admins.push(member.user_id);
+        }
+    }
+    Ok(admins)
+}
+
+/// The change request, if the caller can reach its project
+async fn find_change_request(
+    state: &AppState,
+    auth: &AuthUser,
+    id: &str,
+) -> Result<(ChangeRequest, Project)> {
+    let not_found = || AppError::NotFound("Change request not found".to_string());
+    let change = state
+        .storage
+        .get_change_request(id)
+        .await?
+        .ok_or_else(not_found)?;
+    let project = auth
+        .project(state, &change.project_id)
+        .await
+        .map_err(|_| not_found())?;
+    Ok((change, project))
+}
+
+fn describe(change: &ChangeRequest) -> String {
+    let mut parts = Vec::new();
+    if let Some(enabled) = change.enabled {
+        parts.push(if enabled { "enable" } else { "disable" }.to_string());
+    }
+    if let Some(percentage) = change.rollout_percentage {
+        parts.push(format!("roll out to {percentage}%"));
+    }
+    format!(
+        "{} {} in {}",
+        parts.join(" and "),
+        change.flag_key,
+        change.environment
+    )
+}
+
+/// Email the project's admins, other than the proposer, about a new request
+async fn notify_admins(
+    state: &AppState,
+    project: &Project,
+    change: &ChangeRequest,
+    proposer: &User,
+) -> Result<()> {
+    for user_id in project_admins(state, project).await? {
+        if user_id == proposer.id {
+            continue;
+        }
+        let Some(user) = state.storage.get_user_by_id(&user_id).await? else {
+            continue;
+        };
+        let Some(to) = user.email.filter(|_| user.email_verified_at.is_some()) else {
+            continue;
+        };
+
+        let comment = change
+            .comment
+            .as_ref()
+            .map(|c| format!("\n\n    {c}"))
+            .unwrap_or_default();
+        let email = Email {
+            to,
+            subject: format!("Approval requested: {}", describe(change)),
+            body: format!(
+                "{} proposes to {} in project {}.{comment}\n\nTo review it, run:\n\n    \
+                 flaglite change-requests approve {id}\n    \
+                 flaglite change-requests reject {id}",
+                proposer.username,
+                describe(change),
+                project.name,
+                id = change.id
+            ),
+        };
+        if let Err(e) = state.mailer.send(&email).await {
+            tracing::error!(
+                "Failed to send change request email to {}: {e:#}",
+                user.username
+            );
+        }
+    }
+    Ok(())
+}
+
+/// POST /projects/:project_id/change-requests - Propose a flag change for approval
+pub async fn create_change_request(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+    Json(req): Json<CreateChangeRequest>,
+) -> Result<Json<ChangeRequestResponse>> {
+    let project = auth.project(&state, &project_id).await?;
+
+    let mut validator = Validator::new();
+    if req.enabled.is_none() && req.rollout_percentage.is_none() {
+        validator.fail(
+            "enabled",
+            "required",
+            "Set enabled, rollout_percentage, or both",
+        );
+    }
+    if let Some(percentage) = req.rollout_percentage {
+        validator.rollout("rollout_percentage", percentage);
+    }
+    let comment = req
+        .comment
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty());
+    if let Some(comment) = &comment {
+        validator.max_length("comment", "Comment", comment, MAX_COMMENT_LENGTH);
+    }
+    validator.finish()?;
+
+    let flag = find_flag(&state, &project.id, &req.flag_key).await?;
+    let environment = state
+        .storage
+        .get_environment_by_name(&project.id, &req.environment)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Environment '{}' not found", req.environment))
+        })?;
+    // The comment becomes the change's reason once approved
+    let comment = change_reason(&project, &environment, comment)?;
+    let base_version = state
+        .storage
+        .get_flag_value(&flag.id, &environment.id)
+        .await?
+        .map_or(0, |fv| fv.version);
+
+    let change = ChangeRequest {
+        id: Uuid::new_v4().to_string(),
+        project_id: project.id.clone(),
+        flag_key: flag.key,
+        environment: environment.name,
+        enabled: req.enabled,
+        rollout_percentage: req.rollout_percentage,
+        base_version,
+        comment,
+        status: CHANGE_PENDING.to_string(),
+        proposed_by: auth.user.id.clone(),
+        reviewed_by: None,
+        review_comment: None,
+        created_at: Utc::now(),
+        reviewed_at: None,
+    };
+    state.storage.create_change_request(&change).await?;
+    bus::audit(
+        &state,
+        &project.id,
+        "change_request.created",
+        &auth.user.username,
+        format!(
+            "Change request {} in project {}: {} proposed by {}",
+            change.id,
+            project.id,
+            describe(&change),
+            auth.user.username
+        ),
+    );
+
+    notify_admins(&state, &project, &change, &auth.user).await?;
+
+    Ok(Json(Usernames::default().response(&state, change).await?))
+}
+
+/// GET /projects/:project_id/change-requests - A project's change requests, newest first
+pub async fn list_change_requests(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+    Query(query): Query<ListChangeRequestsQuery>,
+) -> Result<Json<Vec<ChangeRequestResponse>>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    if let Some(status) = &query.status {
+        if ![CHANGE_PENDING, CHANGE_APPROVED, CHANGE_REJECTED].contains(&status.as_str()) {
+            let mut validator = Validator::new();
+            validator.fail(
+                "status",
+                "one_of",
+                "Status must be pending, approved or rejected",
+            );
+            validator.finish()?;
+        }
+    }
+
+    let changes = state
+        .storage
+        .list_change_requests(&project_id, query.status.as_deref())
+        .await?;
+    let mut usernames = Usernames::default();
+    let mut responses = Vec::with_capacity(changes.len());
+    for change in changes {
+        responses.push(usernames.response(&state, change).await?);
+    }
+    Ok(Json(responses))
+}
+
+/// GET /change-requests/:id - One change request
+pub async fn get_change_request(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<ChangeRequestResponse>> {
+    let (change, _) = find_change_request(&state, &auth, &id).await?;
+    Ok(Json(Usernames::default().response(&state, change).await?))
+}
+
+/// Mark a pending change request reviewed, or fail if someone got there first
+async fn review(
+    state: &AppState,
+    auth: &AuthUser,
+    mut change: ChangeRequest,
+    status: &str,
+    comment: Option<String>,
+) -> Result<ChangeRequest> {
+    change.status = status.to_string();
+    change.reviewed_by = Some(auth.user.id.clone());
+    change.review_comment = comment;
+    change.reviewed_at = Some(Utc::now());
+    if !state.storage.review_change_request(&change).await? {
+        return Err(AppError::Conflict(
+            "Change request was already reviewed".to_string(),
+        ));
+    }
+    Ok(change)
+}
+
+fn review_comment(req: Option<Json<ReviewChangeRequest>>) -> Result<Option<String>> {
+    let comment = req
+        .and_then(|Json(r)| r.comment)
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty());
+    if let Some(comment) = &comment {
+        Validator::new()
+            .max_length("comment", "Comment", comment, MAX_COMMENT_LENGTH)
+            .finish()?;
+    }
+    Ok(comment)
+}
+
+/// Write an approved change's flag value over `existing`
+async fn apply(
+    state: &AppState,
+    change: &ChangeRequest,
+    flag: &Flag,
+    environment_id: &str,
+    existing: Option<FlagValue>,
+) -> Result<()> {
+    let now = Utc::now();
+    match existing {
+        Some(fv) => {
+            let updated = FlagValue {
+                enabled: change.enabled.unwrap_or(fv.enabled),
+                rollout_percentage: change.rollout_percentage.unwrap_or(fv.rollout_percentage),
+                updated_at: now,
+                version: fv.version + 1,
+                reason: change.comment.clone(),
+                ..fv
+            };
+            if !state.storage.update_flag_value(&updated).await? {
+                return Err(AppError::flag_value_conflict(&change.flag_key));
+            }
+        }
+        None => {
+            let flag_value = FlagValue {
+                id: Uuid::new_v4().to_string(),
+                flag_id: flag.id.clone(),
+                environment_id: environment_id.to_string(),
+                enabled: change.enabled.unwrap_or(flag.default_enabled),
+                rollout_percentage: change.rollout_percentage.unwrap_or(100),
+                value: None,
+                updated_at: now,
+                version: 1,
+                reason: change.comment.clone(),
+            };
+            state.storage.create_flag_value(&flag_value).await?;
+        }
+    }
+    Ok(())
+}
+
+/// POST /change-requests/:id/approve - Approve a change request and apply it
+///
+/// Only a project admin other than the proposer can approve. The flag must
+/// not have changed since the request was made; if it has, the request stays
+/// pending and should be rejected and proposed again.
+pub async fn approve_change_request(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+    req: Option<Json<ReviewChangeRequest>>,
+) -> Result<Json<ChangeRequestResponse>> {
+    let comment = review_comment(req)?;
+    let (change, project) = find_change_request(&state, &auth, &id).await?;
+
+    if !project_admins(&state, &project)
+        .await?
+        .contains(&auth.user.id)
+    {
+        return Err(AppError::Forbidden(
+            "Only project admins can approve change requests".to_string(),
+        ));
+    }
+    if change.proposed_by == auth.user.id {
+        return Err(AppError::Forbidden(
+            "You can't approve your own change request".to_string(),
+        ));
+    }
+    if change.status != CHANGE_PENDING {
+        return Err(AppError::Conflict(format!(
+            "Change request is already {}",
+            change.status
+        )));
+    }
+
+    let flag = find_flag(&state, &project.id, &change.flag_key).await?;
+    let environment = state
+        .storage
+        .get_environment_by_name(&project.id, &change.environment)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Environment '{}' not found", change.environment))
+        })?;
+    // Approval stands in for the confirmation a protected environment needs,
+    // but a freeze still blocks it
+    if environment.frozen {
+        return Err(AppError::EnvironmentFrozen(change.environment));
+    }
+
+    let existing = state
+        .storage
+        .get_flag_value(&flag.id, &environment.id)
+        .await?;
+    let current_version = existing.as_ref().map_or(0, |fv| fv.version);
+    if current_version != change.base_version {
+        return Err(AppError::Conflict(format!(
+            "Flag '{}' has changed since this request was made (version {} is now {current_version}); \
+             reject it and propose the change again",
+            change.flag_key, change.base_version
+        )));
+    }
+
+    // Claim the request before applying it, so a concurrent reject or second
+    // approval can't race the write; give it back if the write fails
+    let change = review(&state, &auth, change, CHANGE_APPROVED, comment).await?;
+    if let Err(e) = apply(&state, &change, &flag, &environment.id, existing).await {
+        state.storage.reopen_change_request(&change.id).await?;
+        return Err(e);
+    }
+    record_changes(&state, &project.id, std::slice::from_ref(&flag.key), false).await?;
+
+    bus::audit(
+        &state,
+        &project.id,
+        "change_request.approved",
+        &auth.user.username,
+        format!(
+            "Change request {} in project {} approved by {}: {} applied",
+            change.id,
+            project.id,
+            auth.user.username,
+            describe(&change)
+        ),
+    );
+
+    Ok(Json(Usernames::default().response(&state, change).await?))
+}
+
+/// POST /change-requests/:id/reject - Reject a change request, leaving the flag as it is
+///
+/// Project admins can reject any request; proposers can withdraw their own.
+pub async fn reject_change_request(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+    req: Option<Json<ReviewChangeRequest>>,
+) -> Result<Json<ChangeRequestResponse>> {
+    let comment = review_comment(req)?;
+    let (change, project) = find_change_request(&state, &auth, &id).await?;
+
+    if change.proposed_by != auth.user.id
+        && !project_admins(&state, &project)
+            .await?
+            .contains(&auth.user.id)
+    {
+        return Err(AppError::Forbidden(
+            "Only project admins or the proposer can reject a change request".to_string(),
+        ));
+    }
+    if change.status != CHANGE_PENDING {
+        return Err(AppError::Conflict(format!(
+            "Change request is already {}",
+            change.status
+        )));
+    }
+
+    let change = review(&state, &auth, change, CHANGE_REJECTED, comment).await?;
+    bus::audit(
+        &state,
+        &project.id,
+        "change_request.rejected",
+        &auth.user.username,
+        format!(
+            "Change request {} in project {} rejected by {}",
+            change.id, project.id, auth.user.username
+        ),
+    );
+
+    Ok(Json(Usernames::default().response(&state, change).await?))
+}
+
+/// Whether an environment's flag changes have to go through change requests
+#[derive(Debug, Serialize)]
+pub struct EnvironmentApproval {
+    pub environment: String,
+    pub required: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetEnvironmentApproval {
+    pub required: bool,
+}
+
+async fn find_environment(state: &AppState, project: &Project, name: &str) -> Result<Environment> {
+    state
+        .storage
+        .get_environment_by_name(&project.id, name)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Environment '{name}' not found")))
+}
+
+/// GET /projects/:project_id/environments/:name/approval - Whether changes need approval
+pub async fn get_environment_approval(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, name)): Path<(String, String)>,
+) -> Result<Json<EnvironmentApproval>> {
+    let project = auth.project(&state, &project_id).await?;
+    let environment = find_environment(&state, &project, &name).await?;
+
+    Ok(Json(EnvironmentApproval {
+        environment: environment.name,
+        required: environment.require_approval,
+    }))
+}
+
+/// PUT /projects/:project_id/environments/:name/approval - Require approval for changes, or stop
+pub async fn set_environment_approval(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, name)): Path<(String, String)>,
+    Json(req): Json<SetEnvironmentApproval>,
+) -> Result<Json<EnvironmentApproval>> {
+    let project = auth.project(&state, &project_id).await?;
+    if !project_admins(&state, &project)
+        .await?
+        .contains(&auth.user.id)
+    {
+        return Err(AppError::Forbidden(
+            "Only project admins can change whether an environment needs approval".to_string(),
+        ));
+    }
+    let mut environment = find_environment(&state, &project, &name).await?;
+
+    if environment.require_approval != req.required {
+        environment.require_approval = req.required;
+        state.storage.update_environment(&environment).await?;
+        let action = if req.required {
+            "approval_required"
+        } else {
+            "approval_lifted"
+        };
+        bus::audit(
+            &state,
+            &project.id,
+            &format!("environment.{action}"),
+            &auth.user.username,
+            format!(
+                "Environment {} in project {}: {action} by {}",
+                environment.name, project.id, auth.user.username
+            ),
+        );
+    }
+
+    Ok(Json(EnvironmentApproval {
+        environment: environment.name,
+        required: environment.require_approval,
+    }))
+}
@@ -2,17 +2,36 @@
 //! These handlers provide responses in the format expected by the CLI client
 
 use axum::{
+    body::Body,
     extract::{Path, Query, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Response},
     Json,
 };
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
+use futures_util::stream;
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
 use crate::auth::AuthUser;
+use crate::bus::{self, DomainEvent};
+use crate::concurrency;
 use crate::error::{AppError, Result};
+use crate::events::EvaluationReason;
+use crate::handlers::change_requests::project_admins;
+use crate::handlers::flags::{bucket, evaluate, served_value};
 use crate::models::{
-    generate_env_api_key, generate_project_api_key, AppState, Environment, Flag, FlagValue, Project,
+    generate_env_api_key, generate_project_api_key, generate_public_client_id, AppState,
+    Environment, EvaluationCount, Flag, FlagValue, Project, RolloutValue,
+};
+use crate::signing;
+use crate::slug;
+use crate::suggest;
+use crate::usage;
+use crate::validation::{
+    Validator, MAX_CACHE_SECONDS, MAX_COMMENT_LENGTH, MAX_EMOJI_LENGTH, MAX_NAME_LENGTH,
+    MAX_REFERENCE_LENGTH,
 };
 
 const DEFAULT_ENVIRONMENTS: [&str; 3] = ["development", "staging", "production"];
@@ -26,18 +45,21 @@ pub struct CliProject {
     pub name: String,
     pub description: Option<String>,
     pub slug: String,
+    pub org_id: Option<String>,
+    pub require_change_reason: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 impl From<Project> for CliProject {
     fn from(p: Project) -> Self {
-        let slug = p.name.to_lowercase().replace(' ', "-");
         CliProject {
             id: Uuid::parse_str(&p.id).unwrap_or_else(|_| Uuid::nil()),
+            org_id: p.org_id,
             name: p.name,
             description: None,
-            slug,
+            slug: p.slug,
+            require_change_reason: p.require_change_reason,
             created_at: p.created_at,
             updated_at: p.created_at, // API doesn't track updated_at
         }
@@ -53,11 +75,22 @@ pub struct CliEnvironment {
     pub project_id: Uuid,
     pub api_key: String,
     pub is_production: bool,
+    pub protected: bool,
+    pub frozen: bool,
+    /// Lists show environments by ascending sort order
+    pub sort_order: i32,
+    pub color: Option<String>,
+    pub emoji: Option<String>,
+    /// Seconds SDK responses may be cached; None for each endpoint's default
+    pub cache_max_age: Option<i32>,
+    pub cache_stale_while_revalidate: Option<i32>,
+    /// Set while public flags can be evaluated without a key
+    pub public_client_id: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
-impl CliEnvironment {
-    fn from_env(e: Environment) -> Self {
+impl From<Environment> for CliEnvironment {
+    fn from(e: Environment) -> Self {
         CliEnvironment {
             id: Uuid::parse_str(&e.id).unwrap_or_else(|_| Uuid::nil()),
             name: e.name.clone(),
@@ -65,6 +98,14 @@ impl CliEnvironment {
             project_id: Uuid::parse_str(&e.project_id).unwrap_or_else(|_| Uuid::nil()),
             api_key: e.api_key,
             is_production: e.name == "production",
+            protected: e.protected,
+            frozen: e.frozen,
+            sort_order: e.sort_order,
+            color: e.color,
+            emoji: e.emoji,
+            cache_max_age: e.cache_max_age,
+            cache_stale_while_revalidate: e.cache_stale_while_revalidate,
+            public_client_id: e.public_client_id,
             created_at: e.created_at,
         }
     }
@@ -81,6 +122,27 @@ pub enum CliFlagType {
     Json,
 }
 
+impl CliFlagType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CliFlagType::Boolean => "boolean",
+            CliFlagType::String => "string",
+            CliFlagType::Number => "number",
+            CliFlagType::Json => "json",
+        }
+    }
+
+    /// Flags stored before types were persisted are booleans
+    fn from_stored(s: &str) -> Self {
+        match s {
+            "string" => CliFlagType::String,
+            "number" => CliFlagType::Number,
+            "json" => CliFlagType::Json,
+            _ => CliFlagType::Boolean,
+        }
+    }
+}
+
 /// Flag response matching CLI expectations
 #[derive(Debug, Serialize)]
 pub struct CliFlag {
@@ -92,19 +154,43 @@ pub struct CliFlag {
     pub project_id: Uuid,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Past `expires_at`: evaluates to off until deleted
+    pub expired: bool,
+    pub owner: Option<String>,
+    pub repository: Option<String>,
+    pub code_path: Option<String>,
+    /// Values of a json flag must match this schema
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<serde_json::Value>,
+    /// State in environments where the flag has no value
+    pub default_enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<serde_json::Value>,
+    /// Evaluable through public environments
+    pub public: bool,
 }
 
 impl CliFlag {
     fn from_flag(f: Flag) -> Self {
         CliFlag {
+            expired: f.is_expired(Utc::now()),
+            expires_at: f.expires_at,
             id: Uuid::parse_str(&f.id).unwrap_or_else(|_| Uuid::nil()),
+            flag_type: CliFlagType::from_stored(&f.flag_type),
+            json_schema: f.schema(),
+            default_value: f.parsed_default_value(),
             key: f.key,
             name: f.name,
             description: f.description,
-            flag_type: CliFlagType::Boolean,
+            default_enabled: f.default_enabled,
             project_id: Uuid::parse_str(&f.project_id).unwrap_or_else(|_| Uuid::nil()),
             created_at: f.created_at,
             updated_at: f.created_at,
+            owner: f.owner,
+            repository: f.repository,
+            code_path: f.code_path,
+            public: f.public,
         }
     }
 }
@@ -114,6 +200,28 @@ impl CliFlag {
 pub struct FlagEnvironmentValue {
     pub enabled: bool,
     pub rollout: i32,
+    /// Flag value version for `expected_version`; 0 until the value is first written
+    pub version: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    /// Why the current version was written (API version 2)
+    pub reason: Option<String>,
+    /// When the current version was written (API version 2)
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl FlagEnvironmentValue {
+    /// An environment without a value shows the flag's default
+    fn from_value(flag: &Flag, value: Option<&FlagValue>) -> Self {
+        FlagEnvironmentValue {
+            value: value.and_then(FlagValue::parsed_value),
+            enabled: value.map_or(flag.default_enabled, |fv| fv.enabled),
+            rollout: value.map(|fv| fv.rollout_percentage).unwrap_or(100),
+            version: value.map(|fv| fv.version).unwrap_or(0),
+            reason: value.and_then(|fv| fv.reason.clone()),
+            updated_at: value.map(|fv| fv.updated_at),
+        }
+    }
 }
 
 /// Flag with state matching CLI expectations
@@ -124,7 +232,80 @@ pub struct CliFlagWithState {
     pub enabled: bool,
     pub value: Option<serde_json::Value>,
     /// Environment-specific flag states (for dashboard)
-    pub environments: std::collections::HashMap<String, FlagEnvironmentValue>,
+    pub environments: HashMap<String, FlagEnvironmentValue>,
+    /// Most recent SDK evaluation in any environment
+    pub last_evaluated_at: Option<DateTime<Utc>>,
+}
+
+impl CliFlagWithState {
+    /// `current` is the flag's value in the requested environment; without
+    /// one the flag's default applies, as in evaluation
+    fn new(
+        flag: Flag,
+        current: Option<&FlagValue>,
+        environments: HashMap<String, FlagEnvironmentValue>,
+        last_evaluated_at: Option<DateTime<Utc>>,
+    ) -> Self {
+        CliFlagWithState {
+            enabled: current.map_or(flag.default_enabled, |fv| fv.enabled),
+            value: current.and_then(FlagValue::parsed_value),
+            flag: CliFlag::from_flag(flag),
+            environments,
+            last_evaluated_at,
+        }
+    }
+}
+
+/// Evaluation counts for one day
+#[derive(Debug, Serialize)]
+pub struct DailyCount {
+    pub day: NaiveDate,
+    pub count: i64,
+}
+
+/// Evaluation usage of a flag in one environment
+#[derive(Debug, Serialize)]
+pub struct EnvironmentStats {
+    pub total: i64,
+    pub last_evaluated_at: Option<DateTime<Utc>>,
+    /// Most recent day first
+    pub daily: Vec<DailyCount>,
+}
+
+/// Evaluation usage of a flag
+#[derive(Debug, Serialize)]
+pub struct FlagStats {
+    pub key: String,
+    pub total: i64,
+    pub last_evaluated_at: Option<DateTime<Utc>>,
+    pub environments: HashMap<String, EnvironmentStats>,
+}
+
+/// Flag usage of a project in one environment
+#[derive(Debug, Serialize)]
+pub struct ProjectEnvironmentStats {
+    pub name: String,
+    /// Flags on here
+    pub enabled: i64,
+    /// `enabled` as a share of the project's flags, 0-100
+    pub enabled_percentage: f64,
+    /// Flags on for only part of the users
+    pub partial_rollout: i64,
+    pub last_changed_at: Option<DateTime<Utc>>,
+    /// When the environment's SDK key was issued
+    pub api_key_created_at: DateTime<Utc>,
+}
+
+/// Flag usage summary of a project
+#[derive(Debug, Serialize)]
+pub struct ProjectStats {
+    pub project_id: String,
+    pub slug: String,
+    pub flags: i64,
+    pub last_changed_at: Option<DateTime<Utc>>,
+    /// When the project key (ffl_proj_*) was issued
+    pub api_key_created_at: DateTime<Utc>,
+    pub environments: Vec<ProjectEnvironmentStats>,
 }
 
 /// Request to create a project
@@ -133,6 +314,15 @@ pub struct CliFlagWithState {
 pub struct CreateProjectRequest {
     pub name: String,
     pub description: Option<String>,
+    /// Defaults to the API key's org, or the caller's personal org
+    pub org_id: Option<String>,
+}
+
+/// Request to update a project's settings
+#[derive(Debug, Deserialize)]
+pub struct UpdateProjectRequest {
+    /// Make changes to protected environments give a reason
+    pub require_change_reason: Option<bool>,
 }
 
 /// Request to create a flag
@@ -146,6 +336,82 @@ pub struct CreateFlagRequest {
     pub flag_type: CliFlagType,
     #[serde(default)]
     pub enabled: bool,
+    /// Temporary flags turn off by themselves at this time
+    pub expires_at: Option<DateTime<Utc>>,
+    pub owner: Option<String>,
+    pub repository: Option<String>,
+    pub code_path: Option<String>,
+    /// Only for json flags; values set later are validated against it
+    pub json_schema: Option<serde_json::Value>,
+    /// State in environments the flag has no value in, e.g. ones created later
+    #[serde(default)]
+    pub default_enabled: bool,
+    /// Served when on and the environment sets no value
+    pub default_value: Option<serde_json::Value>,
+}
+
+/// Request to change a flag's ownership metadata or defaults; an empty string
+/// clears a metadata field
+#[derive(Debug, Deserialize)]
+pub struct UpdateFlagRequest {
+    pub owner: Option<String>,
+    pub repository: Option<String>,
+    pub code_path: Option<String>,
+    pub default_enabled: Option<bool>,
+    pub default_value: Option<serde_json::Value>,
+    /// Remove the default value
+    #[serde(default)]
+    pub clear_default_value: bool,
+    /// Let public environments serve the flag without a key
+    pub public: Option<bool>,
+}
+
+/// Request to clone a project
+#[derive(Debug, Deserialize)]
+pub struct CloneProjectRequest {
+    pub name: String,
+    /// Copy per-environment flag values (enabled, rollout) instead of resetting them
+    #[serde(default)]
+    pub include_values: bool,
+}
+
+/// Request to update an environment's settings; an empty string clears the
+/// color or emoji
+#[derive(Debug, Deserialize)]
+pub struct UpdateEnvironmentRequest {
+    pub protected: Option<bool>,
+    /// `#rrggbb`
+    pub color: Option<String>,
+    pub emoji: Option<String>,
+    /// Seconds CDNs and proxies may cache SDK responses; 0 turns caching off
+    pub cache_max_age: Option<i32>,
+    /// Seconds past that a cache may serve a response while it refetches
+    pub cache_stale_while_revalidate: Option<i32>,
+    /// Serve public flags without a key, under a public client ID
+    pub public: Option<bool>,
+}
+
+/// Request to add an environment to a project
+#[derive(Debug, Deserialize)]
+pub struct CreateEnvironmentRequest {
+    pub name: String,
+    /// Environment whose flag values the new one starts with; without it
+    /// flags have no value there and evaluate to their defaults
+    pub copy_from: Option<String>,
+}
+
+/// Request to set the order environments are listed in
+#[derive(Debug, Deserialize)]
+pub struct ReorderEnvironmentsRequest {
+    /// Environment names, first to last; ones left out follow in their
+    /// current order
+    pub order: Vec<String>,
+}
+
+/// Query params for listing projects
+#[derive(Debug, Deserialize)]
+pub struct ListProjectsQuery {
+    pub org_id: Option<String>,
 }
 
 /// Query params for flag operations
@@ -154,35 +420,457 @@ pub struct FlagQuery {
     pub environment: Option<String>,
 }
 
+/// Query params for a page of flag history
+#[derive(Debug, Deserialize)]
+pub struct FlagHistoryQuery {
+    pub environment: Option<String>,
+    /// Versions per page; [`HISTORY_PAGE_SIZE`] when absent
+    pub limit: Option<i64>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+}
+
+/// Query params for listing flags
+#[derive(Debug, Deserialize)]
+pub struct ListFlagsQuery {
+    pub environment: Option<String>,
+    /// Only flags with this owner (case-insensitive)
+    pub owner: Option<String>,
+    /// Only flags read by this repository
+    pub repository: Option<String>,
+}
+
+/// Query params for flag toggles
+#[derive(Debug, Deserialize)]
+pub struct ToggleQuery {
+    pub environment: Option<String>,
+    /// Reject the toggle with 409 unless the flag value is at this version
+    pub expected_version: Option<i64>,
+    /// Acknowledge that the target environment is protected
+    #[serde(default)]
+    pub confirm: bool,
+    /// Why the flag is being toggled, kept in its history
+    pub reason: Option<String>,
+}
+
+/// Body for setting a flag's state explicitly
+#[derive(Debug, Deserialize)]
+pub struct SetFlagStateRequest {
+    pub enabled: bool,
+    pub environment: String,
+    /// Reject the change with 409 unless the flag value is still at this version
+    pub expected_version: Option<i64>,
+    /// Acknowledge that the target environment is protected
+    #[serde(default)]
+    pub confirm: bool,
+    /// New value served while enabled (json flags are checked against their
+    /// schema); the current value is kept when absent
+    pub value: Option<serde_json::Value>,
+    /// Why the change is made, kept in the flag's history
+    pub reason: Option<String>,
+}
+
+/// Request to compute experiment assignments for a batch of users
+#[derive(Debug, Deserialize)]
+pub struct AssignmentsRequest {
+    pub environment: String,
+    pub user_ids: Vec<String>,
+}
+
+/// What one user gets from a flag
+#[derive(Debug, Serialize)]
+pub struct Assignment {
+    pub user_id: String,
+    /// 0-99; users below the rollout percentage are in
+    pub bucket: i32,
+    pub enabled: bool,
+    pub reason: EvaluationReason,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+}
+
+/// Assignments in one environment, in request order
+#[derive(Debug, Serialize)]
+pub struct AssignmentsResponse {
+    pub key: String,
+    pub environment: String,
+    pub rollout_percentage: i32,
+    pub assignments: Vec<Assignment>,
+}
+
+/// Most user IDs in one assignments request
+pub const MAX_ASSIGNMENT_USERS: usize = 10_000;
+
+/// Request to change several flags in one environment at once
+#[derive(Debug, Deserialize)]
+pub struct BatchUpdateFlagsRequest {
+    pub environment: String,
+    pub keys: Vec<String>,
+    pub enabled: Option<bool>,
+    pub rollout_percentage: Option<i32>,
+    /// Required to change flags in a protected environment
+    #[serde(default)]
+    pub confirm: bool,
+    /// Why the flags are changed, kept in each flag's history
+    pub reason: Option<String>,
+}
+
+/// One flag's state after a batch update
+#[derive(Debug, Serialize)]
+pub struct BatchUpdatedFlag {
+    pub key: String,
+    /// False if the flag was already in the requested state
+    pub changed: bool,
+    pub enabled: bool,
+    pub rollout: i32,
+    pub version: i64,
+}
+
+/// Result of a batch update, in request order
+#[derive(Debug, Serialize)]
+pub struct BatchUpdateFlagsResponse {
+    pub environment: String,
+    pub flags: Vec<BatchUpdatedFlag>,
+}
+
+/// Request to copy one environment's flag values into another, possibly in
+/// another project
+#[derive(Debug, Deserialize)]
+pub struct CopyEnvironmentRequest {
+    pub from_project: String,
+    pub from_environment: String,
+    pub to_project: String,
+    pub to_environment: String,
+    /// Required to change flags in a protected environment
+    #[serde(default)]
+    pub confirm: bool,
+    /// Why the flags are changed, kept in each flag's history
+    pub reason: Option<String>,
+    /// Report what would change without changing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A flag both projects have that wasn't copied
+#[derive(Debug, Serialize)]
+pub struct SkippedFlag {
+    pub key: String,
+    pub reason: String,
+}
+
+/// Result of copying an environment: the flags copied, by key, and the ones
+/// that couldn't be
+#[derive(Debug, Serialize)]
+pub struct CopyEnvironmentResponse {
+    pub from_project: String,
+    pub from_environment: String,
+    pub to_project: String,
+    pub to_environment: String,
+    pub dry_run: bool,
+    pub flags: Vec<BatchUpdatedFlag>,
+    /// Source flags the target project has no flag for
+    pub missing_in_target: Vec<String>,
+    /// Target flags the source project has no flag for; left unchanged
+    pub missing_in_source: Vec<String>,
+    /// Flags of another type, or whose value the target's schema rejects
+    pub skipped: Vec<SkippedFlag>,
+}
+
+/// Most flags in one batch update
+pub const MAX_BATCH_FLAGS: usize = 1_000;
+
+/// Request to create the flags an application defines in code
+#[derive(Debug, Deserialize)]
+pub struct RegisterFlagsRequest {
+    pub flags: Vec<CreateFlagRequest>,
+}
+
+/// Which of the registered flags were created, in request order
+#[derive(Debug, Serialize)]
+pub struct RegisterFlagsResponse {
+    pub created: Vec<String>,
+    /// Already in the project, and left as they were
+    pub existing: Vec<String>,
+}
+
+/// One recorded version of a flag's value in an environment
+#[derive(Debug, Serialize)]
+pub struct FlagVersion {
+    pub version: i64,
+    pub enabled: bool,
+    pub rollout_percentage: i32,
+    pub value: Option<serde_json::Value>,
+    pub changed_at: DateTime<Utc>,
+    pub reason: Option<String>,
+}
+
+impl From<&FlagValue> for FlagVersion {
+    fn from(fv: &FlagValue) -> Self {
+        Self {
+            version: fv.version,
+            enabled: fv.enabled,
+            rollout_percentage: fv.rollout_percentage,
+            value: fv.parsed_value(),
+            changed_at: fv.updated_at,
+            reason: fv.reason.clone(),
+        }
+    }
+}
+
+/// A page of a flag's versions in one environment, newest first
+#[derive(Debug, Serialize)]
+pub struct FlagHistoryResponse {
+    pub key: String,
+    pub environment: String,
+    pub versions: Vec<FlagVersion>,
+    /// Pass as `cursor` for the next, older page; None on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// One line of an NDJSON history export
+#[derive(Debug, Serialize)]
+struct FlagHistoryLine<'a> {
+    key: &'a str,
+    environment: &'a str,
+    #[serde(flatten)]
+    version: FlagVersion,
+}
+
+/// Versions per page of flag history when the request doesn't say
+pub const HISTORY_PAGE_SIZE: i64 = 100;
+
+/// Most versions in one page of flag history
+pub const MAX_HISTORY_PAGE_SIZE: i64 = 1_000;
+
+/// Versions read from storage at a time while streaming an export
+const HISTORY_EXPORT_BATCH: i64 = 500;
+
+/// Media type that asks for a streamed export instead of pages
+const NDJSON: &str = "application/x-ndjson";
+
+/// Request to restore a flag's value to an earlier version
+#[derive(Debug, Deserialize)]
+pub struct RollbackFlagRequest {
+    pub environment: String,
+    /// Version to restore; the one before the current version when absent
+    pub to_version: Option<i64>,
+    /// Reject the rollback with 409 unless the flag value is still at this version
+    pub expected_version: Option<i64>,
+    /// Acknowledge that the target environment is protected
+    #[serde(default)]
+    pub confirm: bool,
+    /// Why the flag is rolled back, kept in its history
+    pub reason: Option<String>,
+}
+
+/// A flag's value after a rollback
+#[derive(Debug, Serialize)]
+pub struct RollbackFlagResponse {
+    pub key: String,
+    pub environment: String,
+    /// The version whose state was restored
+    pub restored_version: i64,
+    /// False if the flag already had that state
+    pub changed: bool,
+    #[serde(flatten)]
+    pub current: FlagVersion,
+}
+
+// ============ Helpers ============
+
+/// Stored and not-yet-flushed evaluation counts for the given flags
+async fn evaluation_counts(state: &AppState, flag_ids: &[String]) -> Result<Vec<EvaluationCount>> {
+    let stored = state.storage.list_evaluation_counts(flag_ids).await?;
+    Ok(usage::merge(
+        stored.into_iter().chain(state.usage.pending(flag_ids)),
+    ))
+}
+
+/// Most recent evaluation of each flag, across all environments
+async fn last_evaluated(
+    state: &AppState,
+    flag_ids: &[String],
+) -> Result<HashMap<String, DateTime<Utc>>> {
+    let mut last: HashMap<String, DateTime<Utc>> = HashMap::new();
+    for c in evaluation_counts(state, flag_ids).await? {
+        let entry = last.entry(c.flag_id).or_insert(c.last_evaluated_at);
+        *entry = (*entry).max(c.last_evaluated_at);
+    }
+    Ok(last)
+}
+
+/// State of a flag in each environment, keyed by environment name
+pub async fn environment_values(
+    state: &AppState,
+    flag: &Flag,
+    environments: &[Environment],
+) -> Result<HashMap<String, FlagEnvironmentValue>> {
+    let mut values = HashMap::new();
+    for env in environments {
+        let flag_value = state.storage.get_flag_value(&flag.id, &env.id).await?;
+        values.insert(
+            env.name.clone(),
+            FlagEnvironmentValue::from_value(flag, flag_value.as_ref()),
+        );
+    }
+    Ok(values)
+}
+
+/// Response for a flag after a state change, with every environment's state
+async fn flag_with_state(
+    state: &AppState,
+    project_id: &str,
+    flag: Flag,
+    current: &FlagValue,
+) -> Result<CliFlagWithState> {
+    let environments = state
+        .storage
+        .list_environments_by_project(project_id)
+        .await?;
+    let env_values = environment_values(state, &flag, &environments).await?;
+
+    let last_evaluated_at = last_evaluated(state, std::slice::from_ref(&flag.id))
+        .await?
+        .remove(&flag.id);
+
+    Ok(CliFlagWithState::new(
+        flag,
+        Some(current),
+        env_values,
+        last_evaluated_at,
+    ))
+}
+
 // ============ Handlers ============
 
-/// GET /projects - List all projects for authenticated user
+/// Slug for a new project of `user_id`'s, numbered if the name's is taken
+pub async fn new_project_slug(state: &AppState, user_id: &str, name: &str) -> Result<String> {
+    let taken: HashSet<String> = state
+        .storage
+        .list_projects_by_user(user_id)
+        .await?
+        .into_iter()
+        .map(|p| p.slug)
+        .collect();
+    Ok(slug::unique_slug(name, &taken))
+}
+
+/// GET /projects - List projects in the caller's orgs, optionally just one org
 pub async fn list_projects(
     State(state): State<AppState>,
-    AuthUser(user): AuthUser,
+    auth: AuthUser,
+    Query(query): Query<ListProjectsQuery>,
 ) -> Result<Json<Vec<CliProject>>> {
-    let projects = state.storage.list_projects_by_user(&user.id).await?;
+    let projects = auth.projects(&state, query.org_id.as_deref()).await?;
     let responses: Vec<CliProject> = projects.into_iter().map(|p| p.into()).collect();
     Ok(Json(responses))
 }
 
+/// GET /projects/:project_id - Get a project by id or slug
+pub async fn get_project(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+) -> Result<Json<CliProject>> {
+    let project = auth.project(&state, &project_id).await?;
+    Ok(Json(project.into()))
+}
+
+/// GET /projects/:project_id/stats - Flag counts per environment
+pub async fn project_stats(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+) -> Result<Json<ProjectStats>> {
+    let project = auth.project(&state, &project_id).await?;
+    let summary = state
+        .storage
+        .project_summary(&project.id, Utc::now())
+        .await?;
+
+    // One decimal place
+    let percentage = |count: i64| match summary.flags {
+        0 => 0.0,
+        flags => (count * 1000 / flags) as f64 / 10.0,
+    };
+    let environments = summary
+        .environments
+        .into_iter()
+        .map(|env| ProjectEnvironmentStats {
+            enabled_percentage: percentage(env.enabled),
+            name: env.name,
+            enabled: env.enabled,
+            partial_rollout: env.partial_rollout,
+            last_changed_at: env.last_changed_at,
+            api_key_created_at: env.created_at,
+        })
+        .collect();
+
+    Ok(Json(ProjectStats {
+        project_id: project.id,
+        slug: project.slug,
+        flags: summary.flags,
+        last_changed_at: summary.last_changed_at,
+        api_key_created_at: project.created_at,
+        environments,
+    }))
+}
+
+/// PATCH /projects/:project_id - Update project settings
+pub async fn update_project(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+    Json(req): Json<UpdateProjectRequest>,
+) -> Result<Json<CliProject>> {
+    let mut project = auth.project(&state, &project_id).await?;
+
+    let Some(require) = req
+        .require_change_reason
+        .filter(|require| *require != project.require_change_reason)
+    else {
+        return Ok(Json(project.into()));
+    };
+    // The policy constrains members, so they can't be the ones to lift it
+    if !require
+        && !project_admins(&state, &project)
+            .await?
+            .contains(&auth.user.id)
+    {
+        return Err(AppError::Forbidden(
+            "Only project owners can stop requiring change reasons".to_string(),
+        ));
+    }
+
+    project.require_change_reason = require;
+    state.storage.update_project(&project).await?;
+    let action = if require {
+        "change_reason_required"
+    } else {
+        "change_reason_optional"
+    };
+    bus::audit(
+        &state,
+        &project.id,
+        &format!("project.{action}"),
+        &auth.user.username,
+        format!("Project {}: {action} by {}", project.id, auth.user.username),
+    );
+
+    Ok(Json(project.into()))
+}
+
 /// POST /projects - Create a new project
 pub async fn create_project(
     State(state): State<AppState>,
-    AuthUser(user): AuthUser,
+    auth: AuthUser,
     Json(req): Json<CreateProjectRequest>,
 ) -> Result<Json<CliProject>> {
+    Validator::new()
+        .name("name", "Project name", &req.name, MAX_NAME_LENGTH)
+        .finish()?;
     let name = req.name.trim();
-    if name.is_empty() {
-        return Err(AppError::BadRequest(
-            "Project name cannot be empty".to_string(),
-        ));
-    }
-    if name.len() > 255 {
-        return Err(AppError::BadRequest(
-            "Project name must be at most 255 characters".to_string(),
-        ));
-    }
+    let org_id = auth.target_org(&state, req.org_id.as_deref()).await?;
 
     let now = Utc::now();
     let project_id = Uuid::new_v4().to_string();
@@ -190,16 +878,19 @@ pub async fn create_project(
 
     let project = Project {
         id: project_id.clone(),
-        user_id: user.id.clone(),
+        user_id: auth.user.id.clone(),
+        org_id: Some(org_id),
         name: name.to_string(),
+        slug: new_project_slug(&state, &auth.user.id, name).await?,
         api_key: project_api_key,
         created_at: now,
+        require_change_reason: false,
     };
 
     state.storage.create_project(&project).await?;
 
     // Create 3 default environments
-    for env_name in DEFAULT_ENVIRONMENTS {
+    for (sort_order, env_name) in (0..).zip(DEFAULT_ENVIRONMENTS) {
         let env_id = Uuid::new_v4().to_string();
         let env_api_key = generate_env_api_key();
 
@@ -208,6 +899,15 @@ pub async fn create_project(
             project_id: project_id.clone(),
             name: env_name.to_string(),
             api_key: env_api_key,
+            protected: env_name == "production",
+            frozen: false,
+            sort_order,
+            color: None,
+            emoji: None,
+            cache_max_age: None,
+            cache_stale_while_revalidate: None,
+            public_client_id: None,
+            require_approval: false,
             created_at: now,
         };
 
@@ -217,53 +917,533 @@ pub async fn create_project(
     Ok(Json(project.into()))
 }
 
-/// GET /projects/:project_id/environments - List environments for a project
-pub async fn list_environments(
+/// POST /projects/:project_id/clone - Duplicate a project's environments and flags
+pub async fn clone_project(
     State(state): State<AppState>,
-    AuthUser(user): AuthUser,
+    auth: AuthUser,
     Path(project_id): Path<String>,
-) -> Result<Json<Vec<CliEnvironment>>> {
-    // Verify project belongs to user
-    let project = state
+    Json(req): Json<CloneProjectRequest>,
+) -> Result<Json<CliProject>> {
+    let source = auth.project(&state, &project_id).await?;
+
+    Validator::new()
+        .name("name", "Project name", &req.name, MAX_NAME_LENGTH)
+        .finish()?;
+    let name = req.name.trim();
+
+    let now = Utc::now();
+    let new_project_id = Uuid::new_v4().to_string();
+
+    let project = Project {
+        id: new_project_id.clone(),
+        user_id: auth.user.id.clone(),
+        org_id: source.org_id.clone(),
+        name: name.to_string(),
+        slug: new_project_slug(&state, &auth.user.id, name).await?,
+        api_key: generate_project_api_key(),
+        created_at: now,
+        require_change_reason: source.require_change_reason,
+    };
+
+    // Recreate environments, remembering which new environment replaces which old one
+    let source_envs = state
         .storage
-        .get_project_by_id(&project_id)
-        .await?
-        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+        .list_environments_by_project(&source.id)
+        .await?;
+    let mut environments = Vec::with_capacity(source_envs.len());
+    let mut env_id_map = HashMap::new();
+    for source_env in &source_envs {
+        let env = Environment {
+            id: Uuid::new_v4().to_string(),
+            project_id: new_project_id.clone(),
+            name: source_env.name.clone(),
+            api_key: generate_env_api_key(),
+            protected: source_env.protected,
+            // A freeze covers one environment's changes, not its copies
+            frozen: false,
+            sort_order: source_env.sort_order,
+            color: source_env.color.clone(),
+            emoji: source_env.emoji.clone(),
+            cache_max_age: source_env.cache_max_age,
+            cache_stale_while_revalidate: source_env.cache_stale_while_revalidate,
+            // A copy is public too, under a client ID of its own
+            public_client_id: source_env
+                .public_client_id
+                .as_ref()
+                .map(|_| generate_public_client_id()),
+            require_approval: source_env.require_approval,
+            created_at: now,
+        };
+        env_id_map.insert(source_env.id.clone(), env.id.clone());
+        environments.push(env);
+    }
+
+    // Copy flag definitions
+    let source_flags = state.storage.list_flags_by_project(&source.id).await?;
+    let source_flag_ids: Vec<String> = source_flags.iter().map(|f| f.id.clone()).collect();
+    let source_values = if req.include_values {
+        state
+            .storage
+            .list_flag_values_by_flag_ids(&source_flag_ids)
+            .await?
+    } else {
+        vec![]
+    };
+
+    let mut flags = Vec::with_capacity(source_flags.len());
+    let mut flag_values = Vec::new();
+    for source_flag in source_flags {
+        let flag = Flag {
+            id: Uuid::new_v4().to_string(),
+            project_id: new_project_id.clone(),
+            key: source_flag.key,
+            name: source_flag.name,
+            description: source_flag.description,
+            created_at: now,
+            expires_at: source_flag.expires_at,
+            owner: source_flag.owner,
+            repository: source_flag.repository,
+            code_path: source_flag.code_path,
+            flag_type: source_flag.flag_type,
+            json_schema: source_flag.json_schema,
+            default_enabled: source_flag.default_enabled,
+            default_value: source_flag.default_value,
+            public: source_flag.public,
+        };
 
-    if project.user_id != user.id {
-        return Err(AppError::NotFound("Project not found".to_string()));
+        flag_values.extend(env_id_map.iter().map(|(source_env_id, env_id)| {
+            let copied = source_values
+                .iter()
+                .find(|fv| fv.flag_id == source_flag.id && &fv.environment_id == source_env_id);
+            FlagValue {
+                id: Uuid::new_v4().to_string(),
+                flag_id: flag.id.clone(),
+                environment_id: env_id.clone(),
+                enabled: copied.map(|fv| fv.enabled).unwrap_or(false),
+                rollout_percentage: copied.map(|fv| fv.rollout_percentage).unwrap_or(100),
+                value: copied.and_then(|fv| fv.value.clone()),
+                updated_at: now,
+                version: 1,
+                reason: None,
+            }
+        }));
+        flags.push(flag);
     }
 
-    let environments = state
+    // In one go, so a failed write doesn't leave half a project behind
+    state
         .storage
-        .list_environments_by_project(&project_id)
+        .create_project_with(&project, &environments, &flags, &flag_values)
         .await?;
-    let responses: Vec<CliEnvironment> = environments
-        .into_iter()
-        .map(CliEnvironment::from_env)
-        .collect();
-    Ok(Json(responses))
-}
 
-/// GET /projects/:project_id/flags - List flags for a project
-pub async fn list_flags(
+    Ok(Json(project.into()))
+}
+
+/// GET /projects/:project_id/environments - List environments for a project
+pub async fn list_environments(
     State(state): State<AppState>,
-    AuthUser(user): AuthUser,
+    auth: AuthUser,
     Path(project_id): Path<String>,
-    Query(query): Query<FlagQuery>,
-) -> Result<Json<Vec<CliFlagWithState>>> {
-    // Verify project belongs to user
-    let project = state
+) -> Result<Json<Vec<CliEnvironment>>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    let environments = state
+        .storage
+        .list_environments_by_project(&project_id)
+        .await?;
+    let responses: Vec<CliEnvironment> =
+        environments.into_iter().map(CliEnvironment::from).collect();
+    Ok(Json(responses))
+}
+
+/// POST /projects/:project_id/environments - Add an environment, optionally
+/// seeded with another environment's flag values
+pub async fn create_environment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+    Json(req): Json<CreateEnvironmentRequest>,
+) -> Result<Json<CliEnvironment>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    let name = req.name.trim();
+    Validator::new().environment_name("name", name).finish()?;
+
+    let environments = state
         .storage
-        .get_project_by_id(&project_id)
+        .list_environments_by_project(&project_id)
+        .await?;
+    if environments.iter().any(|e| e.name == name) {
+        return Err(AppError::EnvironmentInUse(name.to_string()));
+    }
+    let source = match req.copy_from.as_deref() {
+        Some(source) => Some(
+            environments
+                .iter()
+                .find(|e| e.name == source)
+                .ok_or_else(|| AppError::NotFound(format!("Environment '{source}' not found")))?,
+        ),
+        None => None,
+    };
+
+    let now = Utc::now();
+    let environment = Environment {
+        id: Uuid::new_v4().to_string(),
+        project_id: project_id.clone(),
+        name: name.to_string(),
+        api_key: generate_env_api_key(),
+        protected: false,
+        frozen: false,
+        // Listed after the existing environments
+        sort_order: environments
+            .iter()
+            .map(|e| e.sort_order + 1)
+            .max()
+            .unwrap_or(0),
+        color: None,
+        emoji: None,
+        cache_max_age: None,
+        cache_stale_while_revalidate: None,
+        public_client_id: None,
+        require_approval: false,
+        created_at: now,
+    };
+    state.storage.create_environment(&environment).await?;
+
+    if let Some(source) = source {
+        let flags = state.storage.list_flags_by_project(&project_id).await?;
+        let flag_ids: Vec<String> = flags.iter().map(|f| f.id.clone()).collect();
+        let values = state
+            .storage
+            .list_flag_values_by_flag_ids(&flag_ids)
+            .await?;
+
+        let flag_values: Vec<FlagValue> = values
+            .iter()
+            .filter(|fv| fv.environment_id == source.id)
+            .map(|source_value| FlagValue {
+                id: Uuid::new_v4().to_string(),
+                flag_id: source_value.flag_id.clone(),
+                environment_id: environment.id.clone(),
+                enabled: source_value.enabled,
+                rollout_percentage: source_value.rollout_percentage,
+                value: source_value.value.clone(),
+                updated_at: now,
+                version: 1,
+                reason: None,
+            })
+            .collect();
+        state.storage.upsert_flag_values(&flag_values).await?;
+    }
+
+    let copied = source
+        .map(|s| format!(" with the flag values of {}", s.name))
+        .unwrap_or_default();
+    bus::audit(
+        &state,
+        &project_id,
+        "environment.created",
+        &auth.user.username,
+        format!(
+            "Environment {name} created in project {project_id}{copied} by {}",
+            auth.user.username
+        ),
+    );
+
+    Ok(Json(environment.into()))
+}
+
+/// PATCH /projects/:project_id/environments/:name - Update environment settings
+pub async fn update_environment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, name)): Path<(String, String)>,
+    Json(req): Json<UpdateEnvironmentRequest>,
+) -> Result<Json<CliEnvironment>> {
+    let project = auth.project(&state, &project_id).await?;
+    let project_id = project.id.clone();
+
+    let mut environment = state
+        .storage
+        .get_environment_by_name(&project_id, &name)
         .await?
-        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+        .ok_or_else(|| AppError::NotFound(format!("Environment '{name}' not found")))?;
+
+    Validator::new()
+        .color("color", req.color.as_deref().unwrap_or("").trim())
+        .max_length(
+            "emoji",
+            "Emoji",
+            req.emoji.as_deref().unwrap_or("").trim(),
+            MAX_EMOJI_LENGTH,
+        )
+        .seconds(
+            "cache_max_age",
+            "Cache max age",
+            req.cache_max_age,
+            MAX_CACHE_SECONDS,
+        )
+        .seconds(
+            "cache_stale_while_revalidate",
+            "Stale-while-revalidate",
+            req.cache_stale_while_revalidate,
+            MAX_CACHE_SECONDS,
+        )
+        .finish()?;
+
+    // Otherwise a member could lift the protection and then delete it
+    let unprotecting = environment.protected && req.protected == Some(false);
+    // A public client ID can't be kept secret, so publishing is the owners' call
+    let publishing = environment.public_client_id.is_none() && req.public == Some(true);
+    if (unprotecting || publishing)
+        && !project_admins(&state, &project)
+            .await?
+            .contains(&auth.user.id)
+    {
+        return Err(AppError::Forbidden(if unprotecting {
+            "Only project owners can unprotect an environment".to_string()
+        } else {
+            "Only project owners can publish an environment".to_string()
+        }));
+    }
+    let was_public = environment.public_client_id.is_some();
+
+    if let Some(protected) = req.protected {
+        environment.protected = protected;
+    }
+    if let Some(color) = req.color {
+        environment.color = non_empty(Some(color)).map(|c| c.to_lowercase());
+    }
+    if let Some(emoji) = req.emoji {
+        environment.emoji = non_empty(Some(emoji));
+    }
+    if let Some(max_age) = req.cache_max_age {
+        environment.cache_max_age = Some(max_age);
+    }
+    if let Some(stale) = req.cache_stale_while_revalidate {
+        environment.cache_stale_while_revalidate = Some(stale);
+    }
+    // The client ID stays the same while the environment stays public, so
+    // pages that embed it keep working
+    match req.public {
+        Some(true) if environment.public_client_id.is_none() => {
+            environment.public_client_id = Some(generate_public_client_id());
+        }
+        Some(false) => environment.public_client_id = None,
+        _ => {}
+    }
 
-    if project.user_id != user.id {
-        return Err(AppError::NotFound("Project not found".to_string()));
+    state.storage.update_environment(&environment).await?;
+    if was_public != environment.public_client_id.is_some() {
+        let action = if was_public {
+            "unpublished"
+        } else {
+            "published"
+        };
+        bus::audit(
+            &state,
+            &project_id,
+            &format!("environment.{action}"),
+            &auth.user.username,
+            format!(
+                "Environment {} in project {project_id} {action} by {}",
+                environment.name, auth.user.username
+            ),
+        );
     }
 
-    let flags = state.storage.list_flags_by_project(&project_id).await?;
+    Ok(Json(environment.into()))
+}
+
+/// PUT /projects/:project_id/environments - Set the order environments are listed in
+pub async fn reorder_environments(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+    Json(req): Json<ReorderEnvironmentsRequest>,
+) -> Result<Json<Vec<CliEnvironment>>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    let mut environments = state
+        .storage
+        .list_environments_by_project(&project_id)
+        .await?;
+
+    let mut validator = Validator::new();
+    for (i, name) in req.order.iter().enumerate() {
+        if !environments.iter().any(|e| &e.name == name) {
+            validator.fail(
+                &format!("order/{i}"),
+                "format",
+                format!("Environment '{name}' not found"),
+            );
+        } else if req.order[..i].contains(name) {
+            validator.fail(
+                &format!("order/{i}"),
+                "format",
+                format!("Environment '{name}' is listed twice"),
+            );
+        }
+    }
+    validator.finish()?;
+
+    // Listed environments first, the rest after them in their current order
+    let position = |e: &Environment| {
+        req.order
+            .iter()
+            .position(|name| name == &e.name)
+            .unwrap_or(req.order.len())
+    };
+    environments.sort_by_key(|e| position(e));
+
+    for (sort_order, environment) in (0..).zip(environments.iter_mut()) {
+        if environment.sort_order != sort_order {
+            environment.sort_order = sort_order;
+            state.storage.update_environment(environment).await?;
+        }
+    }
+
+    Ok(Json(
+        environments.into_iter().map(CliEnvironment::from).collect(),
+    ))
+}
+
+/// POST /projects/:project_id/environments/:name/freeze - Lock the environment's flag values
+pub async fn freeze_environment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, name)): Path<(String, String)>,
+) -> Result<Json<CliEnvironment>> {
+    set_frozen(&state, &auth, &project_id, &name, true).await
+}
+
+/// POST /projects/:project_id/environments/:name/unfreeze - Allow flag changes again
+pub async fn unfreeze_environment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, name)): Path<(String, String)>,
+) -> Result<Json<CliEnvironment>> {
+    set_frozen(&state, &auth, &project_id, &name, false).await
+}
+
+async fn set_frozen(
+    state: &AppState,
+    auth: &AuthUser,
+    project_id: &str,
+    name: &str,
+    frozen: bool,
+) -> Result<Json<CliEnvironment>> {
+    let project = auth.project(state, project_id).await?;
+    let project_id = project.id.as_str();
+
+    let mut environment = state
+        .storage
+        .get_environment_by_name(project_id, name)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Environment '{name}' not found")))?;
+
+    // Anyone can start a freeze, but only owners can lift someone else's
+    if environment.frozen
+        && !frozen
+        && !project_admins(state, &project)
+            .await?
+            .contains(&auth.user.id)
+    {
+        return Err(AppError::Forbidden(
+            "Only project owners can unfreeze an environment".to_string(),
+        ));
+    }
+
+    if environment.frozen != frozen {
+        environment.frozen = frozen;
+        state.storage.update_environment(&environment).await?;
+        let action = if frozen { "frozen" } else { "unfrozen" };
+        bus::audit(
+            state,
+            project_id,
+            &format!("environment.{action}"),
+            &auth.user.username,
+            format!(
+                "Environment {name} in project {project_id} {action} by {}",
+                auth.user.username
+            ),
+        );
+    }
+
+    Ok(Json(environment.into()))
+}
+
+/// POST /projects/:project_id/environments/:name/signing-key/rotate - Replace the
+/// key that signs the environment's SDK snapshots
+///
+/// Returns the keys SDKs should now accept: the new one, plus retired ones
+/// still in their grace period.
+pub async fn rotate_signing_key(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, name)): Path<(String, String)>,
+) -> Result<Json<signing::Jwks>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    let environment = state
+        .storage
+        .get_environment_by_name(&project_id, &name)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Environment '{name}' not found")))?;
+
+    let now = Utc::now();
+    state
+        .storage
+        .retire_signing_keys(&environment.id, now)
+        .await?;
+    let key = signing::generate(&environment.id, now);
+    state.storage.create_signing_key(&key).await?;
+    bus::audit(
+        &state,
+        &project_id,
+        "signing_key.rotated",
+        &auth.user.username,
+        format!(
+            "Signing key for environment {name} in project {project_id} rotated to {} by {}",
+            key.id, auth.user.username
+        ),
+    );
+
+    let keys = state.storage.list_signing_keys(&environment.id).await?;
+    Ok(Json(signing::published(&keys, now)))
+}
+
+/// GET /projects/:project_id/flags - List flags for a project
+pub async fn list_flags(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+    Query(query): Query<ListFlagsQuery>,
+) -> Result<Json<Vec<CliFlagWithState>>> {
+    let project = auth.project(&state, &project_id).await?;
+    let project_id = project.id.clone();
+
+    let flags: Vec<Flag> = state
+        .storage
+        .list_flags_by_project(&project_id)
+        .await?
+        .into_iter()
+        .filter(|f| {
+            query.owner.as_deref().is_none_or(|owner| {
+                f.owner
+                    .as_deref()
+                    .is_some_and(|o| o.eq_ignore_ascii_case(owner))
+            })
+        })
+        .filter(|f| {
+            query
+                .repository
+                .as_deref()
+                .is_none_or(|repo| f.repository.as_deref() == Some(repo))
+        })
+        .collect();
+    let flag_ids: Vec<String> = flags.iter().map(|f| f.id.clone()).collect();
+    let last_evaluated = last_evaluated(&state, &flag_ids).await?;
 
     // Get all environments for the project
     let environments = state
@@ -280,36 +1460,19 @@ pub async fn list_flags(
 
     let mut responses = Vec::new();
     for flag in flags {
-        // Build environments map with all environment states
-        let mut env_values = std::collections::HashMap::new();
-        for env in &environments {
-            let flag_value = state.storage.get_flag_value(&flag.id, &env.id).await?;
-            env_values.insert(
-                env.name.clone(),
-                FlagEnvironmentValue {
-                    enabled: flag_value.as_ref().map(|fv| fv.enabled).unwrap_or(false),
-                    rollout: flag_value.as_ref().map(|fv| fv.rollout_percentage).unwrap_or(100),
-                },
-            );
-        }
+        let env_values = environment_values(&state, &flag, &environments).await?;
 
-        let enabled = if let Some(ref env) = current_environment {
-            state
-                .storage
-                .get_flag_value(&flag.id, &env.id)
-                .await?
-                .map(|fv| fv.enabled)
-                .unwrap_or(false)
-        } else {
-            false
+        let current = match current_environment {
+            Some(ref env) => state.storage.get_flag_value(&flag.id, &env.id).await?,
+            None => None,
         };
-
-        responses.push(CliFlagWithState {
-            flag: CliFlag::from_flag(flag),
-            enabled,
-            value: None,
-            environments: env_values,
-        });
+        let last_evaluated_at = last_evaluated.get(&flag.id).copied();
+        responses.push(CliFlagWithState::new(
+            flag,
+            current.as_ref(),
+            env_values,
+            last_evaluated_at,
+        ));
     }
 
     Ok(Json(responses))
@@ -318,34 +1481,119 @@ pub async fn list_flags(
 /// POST /projects/:project_id/flags - Create a new flag
 pub async fn create_flag(
     State(state): State<AppState>,
-    AuthUser(user): AuthUser,
+    auth: AuthUser,
     Path(project_id): Path<String>,
     Json(req): Json<CreateFlagRequest>,
 ) -> Result<Json<CliFlag>> {
-    // Verify project belongs to user
-    let project = state
-        .storage
-        .get_project_by_id(&project_id)
-        .await?
-        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    let project_id = auth.project(&state, &project_id).await?.id;
+    let enabled = req.enabled;
+    let flag = insert_flag(&state, project_id, req, |_| (enabled, 100)).await?;
+    Ok(Json(flag))
+}
 
-    if project.user_id != user.id {
-        return Err(AppError::NotFound("Project not found".to_string()));
+/// PUT /projects/:project_id/flags - Create the listed flags that don't exist yet
+///
+/// Lets an application register the flags it defines in code on startup:
+/// flags already in the project are left alone, so sending the same list
+/// again changes nothing.
+pub async fn register_flags(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+    Json(req): Json<RegisterFlagsRequest>,
+) -> Result<Json<RegisterFlagsResponse>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    let mut validator = Validator::new();
+    if req.flags.is_empty() {
+        validator.fail("flags", "required", "At least one flag is required");
+    } else if req.flags.len() > MAX_BATCH_FLAGS {
+        validator.fail(
+            "flags",
+            "max_length",
+            format!("At most {MAX_BATCH_FLAGS} flags per request"),
+        );
     }
+    validator.finish()?;
 
-    // Validate key
-    if req.key.is_empty() || req.key.len() > 255 {
-        return Err(AppError::BadRequest("Invalid flag key".to_string()));
+    let mut created = Vec::new();
+    let mut existing = Vec::new();
+    for flag in req.flags {
+        if existing.contains(&flag.key) || created.contains(&flag.key) {
+            continue;
+        }
+        if state
+            .storage
+            .get_flag_by_key(&project_id, &flag.key)
+            .await?
+            .is_some()
+        {
+            existing.push(flag.key);
+            continue;
+        }
+        let enabled = flag.enabled;
+        let flag = insert_flag(&state, project_id.clone(), flag, |_| (enabled, 100)).await?;
+        created.push(flag.key);
     }
-    if !req
-        .key
-        .chars()
-        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
-    {
-        return Err(AppError::BadRequest(
-            "Flag key can only contain alphanumeric characters, hyphens, and underscores"
-                .to_string(),
-        ));
+
+    Ok(Json(RegisterFlagsResponse { created, existing }))
+}
+
+/// Validate and store a new flag, with a value in each of the project's
+/// environments in the `(enabled, rollout)` state `initial` gives for it
+pub async fn insert_flag(
+    state: &AppState,
+    project_id: String,
+    req: CreateFlagRequest,
+    initial: impl Fn(&Environment) -> (bool, i32),
+) -> Result<CliFlag> {
+    let mut validator = Validator::new();
+    validator
+        .flag_key("key", &req.key)
+        .max_length("name", "Flag name", &req.name, MAX_NAME_LENGTH)
+        .future("expires_at", "Expiry", req.expires_at)
+        .max_length(
+            "owner",
+            "Owner",
+            req.owner.as_deref().unwrap_or(""),
+            MAX_NAME_LENGTH,
+        )
+        .max_length(
+            "repository",
+            "Repository",
+            req.repository.as_deref().unwrap_or(""),
+            MAX_REFERENCE_LENGTH,
+        )
+        .max_length(
+            "code_path",
+            "Code path",
+            req.code_path.as_deref().unwrap_or(""),
+            MAX_REFERENCE_LENGTH,
+        );
+    if let Some(schema) = &req.json_schema {
+        if matches!(req.flag_type, CliFlagType::Json) {
+            validator.json_schema("json_schema", schema);
+        } else {
+            validator.fail(
+                "json_schema",
+                "schema",
+                "A JSON Schema can only be attached to json flags",
+            );
+        }
+    }
+    if let (Some(schema), Some(value)) = (&req.json_schema, &req.default_value) {
+        validator.matches_schema("default_value", schema, value);
+    }
+    validator.finish()?;
+
+    let environments = state
+        .storage
+        .list_environments_by_project(&project_id)
+        .await?;
+
+    // A new flag that starts off changes nothing, so it's allowed during a freeze
+    if let Some(env) = environments.iter().find(|e| e.frozen && initial(e).0) {
+        return Err(AppError::EnvironmentFrozen(env.name.clone()));
     }
 
     // Check for duplicate
@@ -355,10 +1603,7 @@ pub async fn create_flag(
         .await?
         .is_some()
     {
-        return Err(AppError::BadRequest(format!(
-            "Flag '{}' already exists",
-            req.key
-        )));
+        return Err(AppError::DuplicateFlag(req.key.clone()));
     }
 
     let now = Utc::now();
@@ -371,56 +1616,232 @@ pub async fn create_flag(
         name: req.name.clone(),
         description: req.description.clone(),
         created_at: now,
+        expires_at: req.expires_at,
+        owner: non_empty(req.owner),
+        repository: non_empty(req.repository),
+        code_path: non_empty(req.code_path),
+        flag_type: req.flag_type.as_str().to_string(),
+        json_schema: req.json_schema.as_ref().map(|s| s.to_string()),
+        default_enabled: req.default_enabled,
+        default_value: req.default_value.as_ref().map(|v| v.to_string()),
+        public: false,
     };
 
     state.storage.create_flag(&flag).await?;
 
     // Create flag values for all environments
-    let environments = state
-        .storage
-        .list_environments_by_project(&project_id)
-        .await?;
+    let flag_values: Vec<FlagValue> = environments
+        .iter()
+        .map(|env| {
+            let (enabled, rollout_percentage) = initial(env);
+            FlagValue {
+                id: Uuid::new_v4().to_string(),
+                flag_id: flag_id.clone(),
+                environment_id: env.id.clone(),
+                enabled,
+                rollout_percentage,
+                value: None,
+                updated_at: now,
+                version: 1,
+                reason: None,
+            }
+        })
+        .collect();
+    state.storage.upsert_flag_values(&flag_values).await?;
+    record_changes(state, &project_id, std::slice::from_ref(&flag.key), false).await?;
 
-    for env in &environments {
-        let fv_id = Uuid::new_v4().to_string();
-        let flag_value = FlagValue {
-            id: fv_id,
-            flag_id: flag_id.clone(),
-            environment_id: env.id.clone(),
-            enabled: req.enabled,
-            rollout_percentage: 100,
-            updated_at: now,
-        };
+    Ok(CliFlag::from_flag(flag))
+}
+
+/// PATCH /projects/:project_id/flags/:key - Set a flag's owner, code references and defaults
+pub async fn update_flag(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+    Json(req): Json<UpdateFlagRequest>,
+) -> Result<Json<CliFlag>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    Validator::new()
+        .max_length(
+            "owner",
+            "Owner",
+            req.owner.as_deref().unwrap_or(""),
+            MAX_NAME_LENGTH,
+        )
+        .max_length(
+            "repository",
+            "Repository",
+            req.repository.as_deref().unwrap_or(""),
+            MAX_REFERENCE_LENGTH,
+        )
+        .max_length(
+            "code_path",
+            "Code path",
+            req.code_path.as_deref().unwrap_or(""),
+            MAX_REFERENCE_LENGTH,
+        )
+        .finish()?;
+
+    let mut flag = find_flag(&state, &project_id, &key).await?;
+
+    if req.default_enabled.is_some() || req.default_value.is_some() || req.clear_default_value {
+        let mut validator = Validator::new();
+        if req.default_value.is_some() && req.clear_default_value {
+            validator.fail(
+                "default_value",
+                "conflict",
+                "Set a default value or clear it, not both",
+            );
+        }
+        if let (Some(schema), Some(value)) = (flag.schema(), &req.default_value) {
+            validator.matches_schema("default_value", &schema, value);
+        }
+        validator.finish()?;
 
-        state.storage.create_flag_value(&flag_value).await?;
+        // Defaults decide evaluation wherever the flag has no value
+        let environments = state
+            .storage
+            .list_environments_by_project(&project_id)
+            .await?;
+        if let Some(env) = environments.iter().find(|e| e.frozen) {
+            return Err(AppError::EnvironmentFrozen(env.name.clone()));
+        }
+
+        if let Some(enabled) = req.default_enabled {
+            flag.default_enabled = enabled;
+        }
+        if let Some(value) = &req.default_value {
+            flag.default_value = Some(value.to_string());
+        }
+        if req.clear_default_value {
+            flag.default_value = None;
+        }
+        state.storage.update_flag_defaults(&flag).await?;
+        record_changes(&state, &project_id, std::slice::from_ref(&flag.key), false).await?;
+    }
+
+    // Metadata doesn't change evaluation, so this is allowed during a freeze
+    if let Some(owner) = req.owner {
+        flag.owner = non_empty(Some(owner));
+    }
+    if let Some(repository) = req.repository {
+        flag.repository = non_empty(Some(repository));
+    }
+    if let Some(code_path) = req.code_path {
+        flag.code_path = non_empty(Some(code_path));
+    }
+
+    state.storage.update_flag_ownership(&flag).await?;
+
+    if let Some(public) = req.public.filter(|p| *p != flag.public) {
+        flag.public = public;
+        state.storage.update_flag_visibility(&flag).await?;
+        bus::audit(
+            &state,
+            &project_id,
+            "flag.visibility_changed",
+            &auth.user.username,
+            format!(
+                "Flag {} in project {project_id} made {} by {}",
+                flag.key,
+                if public { "public" } else { "private" },
+                auth.user.username
+            ),
+        );
     }
 
     Ok(Json(CliFlag::from_flag(flag)))
 }
 
+/// Give the flags the project's next revision, so SDKs polling
+/// `GET /v1/changes` fetch them again, and tell live subscribers
+pub async fn record_changes(
+    state: &AppState,
+    project_id: &str,
+    keys: &[String],
+    deleted: bool,
+) -> Result<()> {
+    if !keys.is_empty() {
+        state
+            .storage
+            .record_flag_changes(project_id, keys, deleted)
+            .await?;
+        state.bus.publish(DomainEvent::FlagChanged {
+            project_id: project_id.to_string(),
+            keys: keys.to_vec(),
+            deleted,
+            at: Utc::now(),
+        });
+    }
+    Ok(())
+}
+
+/// The flag with `key`, or a 404 suggesting similar keys in the project
+pub async fn find_flag(state: &AppState, project_id: &str, key: &str) -> Result<Flag> {
+    if let Some(flag) = state.storage.get_flag_by_key(project_id, key).await? {
+        return Ok(flag);
+    }
+
+    let flags = state.storage.list_flags_by_project(project_id).await?;
+    Err(AppError::FlagNotFound {
+        key: key.to_string(),
+        suggestions: suggest::similar_keys(key, flags.iter().map(|f| f.key.as_str())),
+    })
+}
+
+/// Trimmed, with blank treated as unset
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+/// The reason given for changing flags in `environment`; projects can
+/// require one for their protected environments
+pub fn change_reason(
+    project: &Project,
+    environment: &Environment,
+    reason: Option<String>,
+) -> Result<Option<String>> {
+    let reason = non_empty(reason);
+    if let Some(reason) = &reason {
+        Validator::new()
+            .max_length("reason", "Reason", reason, MAX_COMMENT_LENGTH)
+            .finish()?;
+    } else if project.require_change_reason && environment.protected {
+        return Err(AppError::ReasonRequired(environment.name.clone()));
+    }
+    Ok(reason)
+}
+
+/// Fails if flag changes in `environment` must go through change requests
+pub fn direct_change(environment: &Environment) -> Result<()> {
+    if environment.require_approval {
+        return Err(AppError::ApprovalRequired(environment.name.clone()));
+    }
+    Ok(())
+}
+
+/// `reason` as the end of an audit message
+pub fn because(reason: &Option<String>) -> String {
+    reason
+        .as_deref()
+        .map(|reason| format!(": {reason}"))
+        .unwrap_or_default()
+}
+
 /// GET /projects/:project_id/flags/:key - Get a specific flag
 pub async fn get_flag(
     State(state): State<AppState>,
-    AuthUser(user): AuthUser,
+    auth: AuthUser,
     Path((project_id, key)): Path<(String, String)>,
     Query(query): Query<FlagQuery>,
 ) -> Result<Json<CliFlagWithState>> {
-    // Verify project belongs to user
-    let project = state
-        .storage
-        .get_project_by_id(&project_id)
-        .await?
-        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    let project = auth.project(&state, &project_id).await?;
+    let project_id = project.id.clone();
 
-    if project.user_id != user.id {
-        return Err(AppError::NotFound("Project not found".to_string()));
-    }
-
-    let flag = state
-        .storage
-        .get_flag_by_key(&project_id, &key)
-        .await?
-        .ok_or_else(|| AppError::NotFound(format!("Flag '{key}' not found")))?;
+    let flag = find_flag(&state, &project_id, &key).await?;
 
     // Get all environments for the project
     let environments = state
@@ -428,18 +1849,7 @@ pub async fn get_flag(
         .list_environments_by_project(&project_id)
         .await?;
 
-    // Build environments map with all environment states
-    let mut env_values = std::collections::HashMap::new();
-    for env in &environments {
-        let flag_value = state.storage.get_flag_value(&flag.id, &env.id).await?;
-        env_values.insert(
-            env.name.clone(),
-            FlagEnvironmentValue {
-                enabled: flag_value.as_ref().map(|fv| fv.enabled).unwrap_or(false),
-                rollout: flag_value.as_ref().map(|fv| fv.rollout_percentage).unwrap_or(100),
-            },
-        );
-    }
+    let env_values = environment_values(&state, &flag, &environments).await?;
 
     // Get environment for state lookup
     let env_name = query.environment.as_deref().unwrap_or("development");
@@ -448,48 +1858,81 @@ pub async fn get_flag(
         .get_environment_by_name(&project_id, env_name)
         .await?;
 
-    let enabled = if let Some(ref env) = environment {
-        state
-            .storage
-            .get_flag_value(&flag.id, &env.id)
-            .await?
-            .map(|fv| fv.enabled)
-            .unwrap_or(false)
-    } else {
-        false
+    let current = match environment {
+        Some(ref env) => state.storage.get_flag_value(&flag.id, &env.id).await?,
+        None => None,
     };
+    let last_evaluated_at = last_evaluated(&state, std::slice::from_ref(&flag.id))
+        .await?
+        .remove(&flag.id);
+
+    Ok(Json(CliFlagWithState::new(
+        flag,
+        current.as_ref(),
+        env_values,
+        last_evaluated_at,
+    )))
+}
+
+/// GET /projects/:project_id/flags/:key/stats - Evaluation counts per environment and day
+pub async fn flag_stats(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+) -> Result<Json<FlagStats>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    let flag = find_flag(&state, &project_id, &key).await?;
+
+    let counts = evaluation_counts(&state, std::slice::from_ref(&flag.id)).await?;
+    let environments = state
+        .storage
+        .list_environments_by_project(&project_id)
+        .await?;
+
+    let mut env_stats = HashMap::new();
+    for env in environments {
+        let env_counts: Vec<&EvaluationCount> = counts
+            .iter()
+            .filter(|c| c.environment_id == env.id)
+            .collect();
+
+        env_stats.insert(
+            env.name,
+            EnvironmentStats {
+                total: env_counts.iter().map(|c| c.count).sum(),
+                last_evaluated_at: env_counts.iter().map(|c| c.last_evaluated_at).max(),
+                daily: env_counts
+                    .iter()
+                    .map(|c| DailyCount {
+                        day: c.day,
+                        count: c.count,
+                    })
+                    .collect(),
+            },
+        );
+    }
 
-    Ok(Json(CliFlagWithState {
-        flag: CliFlag::from_flag(flag),
-        enabled,
-        value: None,
-        environments: env_values,
+    Ok(Json(FlagStats {
+        key: flag.key,
+        total: counts.iter().map(|c| c.count).sum(),
+        last_evaluated_at: counts.iter().map(|c| c.last_evaluated_at).max(),
+        environments: env_stats,
     }))
 }
 
 /// POST /projects/:project_id/flags/:key/toggle - Toggle a flag
 pub async fn toggle_flag(
     State(state): State<AppState>,
-    AuthUser(user): AuthUser,
+    auth: AuthUser,
     Path((project_id, key)): Path<(String, String)>,
-    Query(query): Query<FlagQuery>,
+    Query(query): Query<ToggleQuery>,
+    headers: HeaderMap,
 ) -> Result<Json<CliFlagWithState>> {
-    // Verify project belongs to user
-    let project = state
-        .storage
-        .get_project_by_id(&project_id)
-        .await?
-        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    let project = auth.project(&state, &project_id).await?;
+    let project_id = project.id.clone();
 
-    if project.user_id != user.id {
-        return Err(AppError::NotFound("Project not found".to_string()));
-    }
-
-    let flag = state
-        .storage
-        .get_flag_by_key(&project_id, &key)
-        .await?
-        .ok_or_else(|| AppError::NotFound(format!("Flag '{key}' not found")))?;
+    let flag = find_flag(&state, &project_id, &key).await?;
 
     let env_name = query
         .environment
@@ -501,6 +1944,15 @@ pub async fn toggle_flag(
         .await?
         .ok_or_else(|| AppError::NotFound(format!("Environment '{env_name}' not found")))?;
 
+    if environment.frozen {
+        return Err(AppError::EnvironmentFrozen(env_name));
+    }
+    if environment.protected && !query.confirm {
+        return Err(AppError::ProtectedEnvironment(env_name));
+    }
+    direct_change(&environment)?;
+    let reason = change_reason(&project, &environment, query.reason)?;
+
     let now = Utc::now();
 
     // Toggle the flag
@@ -509,7 +1961,14 @@ pub async fn toggle_flag(
         .get_flag_value(&flag.id, &environment.id)
         .await?;
 
-    let new_enabled = match existing {
+    let expected = concurrency::expected_version(&headers, query.expected_version)?;
+    concurrency::check_version(
+        &key,
+        existing.as_ref().map(|fv| fv.version).unwrap_or(0),
+        expected,
+    )?;
+
+    let current = match existing {
         Some(fv) => {
             let toggled = !fv.enabled;
             let updated_fv = FlagValue {
@@ -518,10 +1977,15 @@ pub async fn toggle_flag(
                 environment_id: environment.id,
                 enabled: toggled,
                 rollout_percentage: fv.rollout_percentage,
+                value: fv.value,
                 updated_at: now,
+                version: fv.version + 1,
+                reason,
             };
-            state.storage.update_flag_value(&updated_fv).await?;
-            toggled
+            if !state.storage.update_flag_value(&updated_fv).await? {
+                return Err(AppError::flag_value_conflict(&key));
+            }
+            updated_fv
         }
         None => {
             let fv_id = Uuid::new_v4().to_string();
@@ -529,65 +1993,827 @@ pub async fn toggle_flag(
                 id: fv_id,
                 flag_id: flag.id.clone(),
                 environment_id: environment.id,
-                enabled: true,
+                enabled: !flag.default_enabled,
                 rollout_percentage: 100,
+                value: None,
                 updated_at: now,
+                version: 1,
+                reason,
             };
             state.storage.create_flag_value(&flag_value).await?;
-            true
+            flag_value
         }
     };
+    record_changes(&state, &project_id, std::slice::from_ref(&flag.key), false).await?;
 
-    // Get all environments and build environments map
-    let environments = state
+    Ok(Json(
+        flag_with_state(&state, &project_id, flag, &current).await?,
+    ))
+}
+
+/// PUT /projects/:project_id/flags/:key/state - Set a flag on or off in one environment
+///
+/// Unlike toggle, repeating the request is harmless: a flag already in the
+/// requested state is left untouched.
+pub async fn set_flag_state(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(req): Json<SetFlagStateRequest>,
+) -> Result<Json<CliFlagWithState>> {
+    let project = auth.project(&state, &project_id).await?;
+    let project_id = project.id.clone();
+
+    let flag = find_flag(&state, &project_id, &key).await?;
+
+    if let (Some(schema), Some(value)) = (flag.schema(), &req.value) {
+        Validator::new()
+            .matches_schema("value", &schema, value)
+            .finish()?;
+    }
+
+    let environment = state
         .storage
-        .list_environments_by_project(&project_id)
+        .get_environment_by_name(&project_id, &req.environment)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Environment '{}' not found", req.environment))
+        })?;
+
+    if environment.frozen {
+        return Err(AppError::EnvironmentFrozen(req.environment));
+    }
+    if environment.protected && !req.confirm {
+        return Err(AppError::ProtectedEnvironment(req.environment));
+    }
+    direct_change(&environment)?;
+    let reason = change_reason(&project, &environment, req.reason)?;
+
+    let existing = state
+        .storage
+        .get_flag_value(&flag.id, &environment.id)
         .await?;
-    let mut env_values = std::collections::HashMap::new();
-    for env in &environments {
-        let flag_value = state.storage.get_flag_value(&flag.id, &env.id).await?;
-        env_values.insert(
-            env.name.clone(),
-            FlagEnvironmentValue {
-                enabled: flag_value.as_ref().map(|fv| fv.enabled).unwrap_or(false),
-                rollout: flag_value.as_ref().map(|fv| fv.rollout_percentage).unwrap_or(100),
+
+    // A missing value is version 0, so `expected_version: 0` means "never set"
+    let expected = concurrency::expected_version(&headers, req.expected_version)?;
+    let current_version = existing.as_ref().map(|fv| fv.version).unwrap_or(0);
+    concurrency::check_version(&key, current_version, expected)?;
+
+    let now = Utc::now();
+    let value = req.value.as_ref().map(|v| v.to_string());
+    let current = match existing {
+        Some(fv) if fv.enabled == req.enabled && (value.is_none() || value == fv.value) => fv,
+        Some(fv) => {
+            let updated_fv = FlagValue {
+                enabled: req.enabled,
+                value: value.or(fv.value.clone()),
+                updated_at: now,
+                version: fv.version + 1,
+                reason,
+                ..fv
+            };
+            if !state.storage.update_flag_value(&updated_fv).await? {
+                return Err(AppError::flag_value_conflict(&key));
+            }
+            record_changes(&state, &project_id, std::slice::from_ref(&flag.key), false).await?;
+            updated_fv
+        }
+        None => {
+            let flag_value = FlagValue {
+                id: Uuid::new_v4().to_string(),
+                flag_id: flag.id.clone(),
+                environment_id: environment.id,
+                enabled: req.enabled,
+                rollout_percentage: 100,
+                value,
+                updated_at: now,
+                version: 1,
+                reason,
+            };
+            state.storage.create_flag_value(&flag_value).await?;
+            record_changes(&state, &project_id, std::slice::from_ref(&flag.key), false).await?;
+            flag_value
+        }
+    };
+
+    Ok(Json(
+        flag_with_state(&state, &project_id, flag, &current).await?,
+    ))
+}
+
+/// POST /projects/:project_id/flags/:key/assignments - What each user gets from a flag
+///
+/// Uses the same bucketing as SDK evaluation, so the result matches what those
+/// users are served. Nothing is recorded as an evaluation.
+pub async fn flag_assignments(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+    Json(req): Json<AssignmentsRequest>,
+) -> Result<Json<AssignmentsResponse>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    let mut validator = Validator::new();
+    if req.user_ids.is_empty() {
+        validator.fail("user_ids", "required", "At least one user ID is required");
+    } else if req.user_ids.len() > MAX_ASSIGNMENT_USERS {
+        validator.fail(
+            "user_ids",
+            "max_length",
+            format!("At most {MAX_ASSIGNMENT_USERS} user IDs per request"),
+        );
+    }
+    if req.user_ids.iter().any(|id| id.trim().is_empty()) {
+        validator.fail("user_ids", "required", "User IDs cannot be empty");
+    }
+    validator.finish()?;
+
+    let flag = find_flag(&state, &project_id, &key).await?;
+    let environment = state
+        .storage
+        .get_environment_by_name(&project_id, &req.environment)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Environment '{}' not found", req.environment))
+        })?;
+    let users = state
+        .storage
+        .list_listed_users(&flag.id, &environment.id)
+        .await?;
+    let value = state
+        .storage
+        .get_flag_value(&flag.id, &environment.id)
+        .await?
+        .map(|fv| RolloutValue::from(&fv).with_listed_users(users));
+
+    let assignments = req
+        .user_ids
+        .into_iter()
+        .map(|user_id| {
+            let (enabled, reason) = evaluate(
+                &flag.key,
+                flag.expires_at,
+                value.as_ref(),
+                flag.default_enabled,
+                Some(&user_id),
+            );
+            Assignment {
+                bucket: bucket(&flag.key, &user_id),
+                value: served_value(value.as_ref(), flag.parsed_default_value(), enabled),
+                user_id,
+                enabled,
+                reason,
+            }
+        })
+        .collect();
+
+    Ok(Json(AssignmentsResponse {
+        key: flag.key,
+        environment: environment.name,
+        rollout_percentage: value.map(|v| v.rollout_percentage).unwrap_or(0),
+        assignments,
+    }))
+}
+
+/// PATCH /projects/:project_id/flags - Turn many flags on or off, or set their
+/// rollout, in one environment
+///
+/// All changes are written in one transaction: an unknown key or a concurrent
+/// change to any of the flags leaves every flag as it was.
+pub async fn batch_update_flags(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+    Json(req): Json<BatchUpdateFlagsRequest>,
+) -> Result<Json<BatchUpdateFlagsResponse>> {
+    let project = auth.project(&state, &project_id).await?;
+    let project_id = project.id.clone();
+
+    let mut validator = Validator::new();
+    if req.keys.is_empty() {
+        validator.fail("keys", "required", "At least one flag key is required");
+    } else if req.keys.len() > MAX_BATCH_FLAGS {
+        validator.fail(
+            "keys",
+            "max_length",
+            format!("At most {MAX_BATCH_FLAGS} flags per request"),
+        );
+    }
+    if req.enabled.is_none() && req.rollout_percentage.is_none() {
+        validator.fail(
+            "enabled",
+            "required",
+            "Set enabled, rollout_percentage, or both",
+        );
+    }
+    if let Some(percentage) = req.rollout_percentage {
+        validator.rollout("rollout_percentage", percentage);
+    }
+    validator.finish()?;
+
+    let environment = state
+        .storage
+        .get_environment_by_name(&project_id, &req.environment)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Environment '{}' not found", req.environment))
+        })?;
+
+    if environment.frozen {
+        return Err(AppError::EnvironmentFrozen(req.environment));
+    }
+    if environment.protected && !req.confirm {
+        return Err(AppError::ProtectedEnvironment(req.environment));
+    }
+    direct_change(&environment)?;
+    let reason = change_reason(&project, &environment, req.reason)?;
+
+    let flags = state.storage.list_flags_by_project(&project_id).await?;
+    let mut keys = req.keys;
+    let mut seen = std::collections::HashSet::new();
+    keys.retain(|key| seen.insert(key.clone()));
+    let mut selected = Vec::with_capacity(keys.len());
+    for key in &keys {
+        match flags.iter().find(|f| &f.key == key) {
+            Some(flag) => selected.push(flag),
+            None => {
+                return Err(AppError::FlagNotFound {
+                    key: key.clone(),
+                    suggestions: suggest::similar_keys(key, flags.iter().map(|f| f.key.as_str())),
+                })
+            }
+        }
+    }
+
+    let flag_ids: Vec<String> = selected.iter().map(|f| f.id.clone()).collect();
+    let mut existing: HashMap<String, FlagValue> = state
+        .storage
+        .list_flag_values_by_flag_ids(&flag_ids)
+        .await?
+        .into_iter()
+        .filter(|fv| fv.environment_id == environment.id)
+        .map(|fv| (fv.flag_id.clone(), fv))
+        .collect();
+
+    let now = Utc::now();
+    let mut writes = Vec::new();
+    let mut results = Vec::with_capacity(selected.len());
+    for flag in selected {
+        let current = existing.remove(&flag.id);
+        let before = current.as_ref().map_or((flag.default_enabled, 100), |fv| {
+            (fv.enabled, fv.rollout_percentage)
+        });
+        let after = (
+            req.enabled.unwrap_or(before.0),
+            req.rollout_percentage.unwrap_or(before.1),
+        );
+
+        let changed = current.is_none() || after != before;
+        let flag_value = match current {
+            Some(fv) if !changed => fv,
+            Some(fv) => FlagValue {
+                enabled: after.0,
+                rollout_percentage: after.1,
+                updated_at: now,
+                version: fv.version + 1,
+                reason: reason.clone(),
+                ..fv
+            },
+            None => FlagValue {
+                id: Uuid::new_v4().to_string(),
+                flag_id: flag.id.clone(),
+                environment_id: environment.id.clone(),
+                enabled: after.0,
+                rollout_percentage: after.1,
+                value: None,
+                updated_at: now,
+                version: 1,
+                reason: reason.clone(),
+            },
+        };
+
+        results.push(BatchUpdatedFlag {
+            key: flag.key.clone(),
+            changed,
+            enabled: flag_value.enabled,
+            rollout: flag_value.rollout_percentage,
+            version: flag_value.version,
+        });
+        if changed {
+            writes.push(flag_value);
+        }
+    }
+
+    if !state.storage.save_flag_values(&writes).await? {
+        return Err(AppError::Conflict(
+            "Some of the flags were changed by another request; nothing was applied. Reload and try again".to_string(),
+        ));
+    }
+    let changed_keys: Vec<String> = results
+        .iter()
+        .filter(|r| r.changed)
+        .map(|r| r.key.clone())
+        .collect();
+    record_changes(&state, &project_id, &changed_keys, false).await?;
+
+    bus::audit(
+        &state,
+        &project_id,
+        "flags.batch_updated",
+        &auth.user.username,
+        format!(
+            "{} flags changed in environment {} of project {project_id} by {}{}",
+            writes.len(),
+            environment.name,
+            auth.user.username,
+            because(&reason)
+        ),
+    );
+
+    Ok(Json(BatchUpdateFlagsResponse {
+        environment: environment.name,
+        flags: results,
+    }))
+}
+
+/// POST /environments/copy - Copy an environment's flag values into another
+/// environment, usually of another project, for every flag key both have
+pub async fn copy_environment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(req): Json<CopyEnvironmentRequest>,
+) -> Result<Json<CopyEnvironmentResponse>> {
+    let from_project = auth.project(&state, &req.from_project).await?;
+    let to_project = auth.project(&state, &req.to_project).await?;
+
+    let source = find_environment(&state, &from_project.id, &req.from_environment).await?;
+    let target = find_environment(&state, &to_project.id, &req.to_environment).await?;
+    if source.id == target.id {
+        let mut validator = Validator::new();
+        validator.fail(
+            "to_environment",
+            "invalid",
+            "The source and target are the same environment",
+        );
+        validator.finish()?;
+    }
+
+    if !req.dry_run {
+        if target.frozen {
+            return Err(AppError::EnvironmentFrozen(target.name));
+        }
+        if target.protected && !req.confirm {
+            return Err(AppError::ProtectedEnvironment(target.name));
+        }
+        direct_change(&target)?;
+    }
+    // A dry run changes nothing, so it needs no reason
+    let reason = if req.dry_run {
+        non_empty(req.reason)
+    } else {
+        change_reason(&to_project, &target, req.reason)?
+    };
+
+    let source_flags = state
+        .storage
+        .list_flags_by_project(&from_project.id)
+        .await?;
+    let target_flags = state.storage.list_flags_by_project(&to_project.id).await?;
+    let source_values: HashMap<String, FlagValue> = state
+        .storage
+        .list_flag_values_by_flag_ids(
+            &source_flags
+                .iter()
+                .map(|f| f.id.clone())
+                .collect::<Vec<_>>(),
+        )
+        .await?
+        .into_iter()
+        .filter(|fv| fv.environment_id == source.id)
+        .map(|fv| (fv.flag_id.clone(), fv))
+        .collect();
+    let mut target_values: HashMap<String, FlagValue> = state
+        .storage
+        .list_flag_values_by_flag_ids(
+            &target_flags
+                .iter()
+                .map(|f| f.id.clone())
+                .collect::<Vec<_>>(),
+        )
+        .await?
+        .into_iter()
+        .filter(|fv| fv.environment_id == target.id)
+        .map(|fv| (fv.flag_id.clone(), fv))
+        .collect();
+
+    let source_by_key: HashMap<&str, &Flag> =
+        source_flags.iter().map(|f| (f.key.as_str(), f)).collect();
+    let target_keys: HashSet<&str> = target_flags.iter().map(|f| f.key.as_str()).collect();
+
+    let now = Utc::now();
+    let mut writes = Vec::new();
+    let mut results = Vec::new();
+    let mut missing_in_source = Vec::new();
+    let mut skipped = Vec::new();
+    for flag in &target_flags {
+        let Some(from) = source_by_key.get(flag.key.as_str()) else {
+            missing_in_source.push(flag.key.clone());
+            continue;
+        };
+        if from.flag_type != flag.flag_type {
+            skipped.push(SkippedFlag {
+                key: flag.key.clone(),
+                reason: format!(
+                    "{} in the source, {} in the target",
+                    from.flag_type, flag.flag_type
+                ),
+            });
+            continue;
+        }
+
+        // What the source serves, defaults included, so the target serves
+        // the same whatever its own defaults are
+        let after = match source_values.get(&from.id) {
+            Some(fv) => (fv.enabled, fv.rollout_percentage, fv.value.clone()),
+            None => (from.default_enabled, 100, from.default_value.clone()),
+        };
+        let value = after
+            .2
+            .as_deref()
+            .and_then(|v| serde_json::from_str::<serde_json::Value>(v).ok());
+        if let (Some(schema), Some(value)) = (flag.schema(), &value) {
+            if Validator::new()
+                .matches_schema("value", &schema, value)
+                .finish()
+                .is_err()
+            {
+                skipped.push(SkippedFlag {
+                    key: flag.key.clone(),
+                    reason: "the value doesn't match the target's schema".to_string(),
+                });
+                continue;
+            }
+        }
+
+        let current = target_values.remove(&flag.id);
+        let changed = current.as_ref().is_none_or(|fv| {
+            (fv.enabled, fv.rollout_percentage, &fv.value) != (after.0, after.1, &after.2)
+        });
+        let flag_value = match current {
+            Some(fv) if !changed => fv,
+            Some(fv) => FlagValue {
+                enabled: after.0,
+                rollout_percentage: after.1,
+                value: after.2,
+                updated_at: now,
+                version: fv.version + 1,
+                reason: reason.clone(),
+                ..fv
             },
+            None => FlagValue {
+                id: Uuid::new_v4().to_string(),
+                flag_id: flag.id.clone(),
+                environment_id: target.id.clone(),
+                enabled: after.0,
+                rollout_percentage: after.1,
+                value: after.2,
+                updated_at: now,
+                version: 1,
+                reason: reason.clone(),
+            },
+        };
+
+        results.push(BatchUpdatedFlag {
+            key: flag.key.clone(),
+            changed,
+            enabled: flag_value.enabled,
+            rollout: flag_value.rollout_percentage,
+            version: flag_value.version,
+        });
+        if changed {
+            writes.push(flag_value);
+        }
+    }
+    let missing_in_target = source_flags
+        .iter()
+        .filter(|f| !target_keys.contains(f.key.as_str()))
+        .map(|f| f.key.clone())
+        .collect();
+
+    if !req.dry_run {
+        if !state.storage.save_flag_values(&writes).await? {
+            return Err(AppError::Conflict(
+                "Some of the flags were changed by another request; nothing was applied. Reload and try again".to_string(),
+            ));
+        }
+        let changed_keys: Vec<String> = results
+            .iter()
+            .filter(|r| r.changed)
+            .map(|r| r.key.clone())
+            .collect();
+        record_changes(&state, &to_project.id, &changed_keys, false).await?;
+
+        bus::audit(
+            &state,
+            &to_project.id,
+            "environment.copied",
+            &auth.user.username,
+            format!(
+                "{} flags copied from environment {} of project {} to environment {} of project {} by {}{}",
+                writes.len(),
+                source.name,
+                from_project.slug,
+                target.name,
+                to_project.slug,
+                auth.user.username,
+                because(&reason)
+            ),
         );
     }
 
-    Ok(Json(CliFlagWithState {
-        flag: CliFlag::from_flag(flag),
-        enabled: new_enabled,
-        value: None,
-        environments: env_values,
+    Ok(Json(CopyEnvironmentResponse {
+        from_project: from_project.slug,
+        from_environment: source.name,
+        to_project: to_project.slug,
+        to_environment: target.name,
+        dry_run: req.dry_run,
+        flags: results,
+        missing_in_target,
+        missing_in_source,
+        skipped,
+    }))
+}
+
+/// The project's environment named `name`
+async fn find_environment(state: &AppState, project_id: &str, name: &str) -> Result<Environment> {
+    state
+        .storage
+        .get_environment_by_name(project_id, name)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Environment '{name}' not found")))
+}
+
+/// The flag's current value in a named environment, and the environment
+async fn environment_value(
+    state: &AppState,
+    project_id: &str,
+    flag: &Flag,
+    environment: &str,
+) -> Result<(Environment, Option<FlagValue>)> {
+    let environment = state
+        .storage
+        .get_environment_by_name(project_id, environment)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Environment '{environment}' not found")))?;
+    let value = state
+        .storage
+        .get_flag_value(&flag.id, &environment.id)
+        .await?;
+    Ok((environment, value))
+}
+
+/// GET /projects/:project_id/flags/:key/history - A flag's versions in one
+/// environment, a page at a time
+///
+/// Pages seek past the last version of the previous one rather than skipping
+/// rows, so they stay fast however long the history grows. With `Accept:
+/// application/x-ndjson` every version is streamed instead, one JSON object
+/// per line, reading a batch from storage at a time.
+pub async fn flag_history(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+    Query(query): Query<FlagHistoryQuery>,
+    headers: HeaderMap,
+) -> Result<Response> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    let flag = find_flag(&state, &project_id, &key).await?;
+    let env_name = query.environment.as_deref().unwrap_or("development");
+    let (environment, current) = environment_value(&state, &project_id, &flag, env_name).await?;
+    let flag_value_id = current.map(|fv| fv.id);
+
+    let wants_ndjson = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(NDJSON));
+    if wants_ndjson {
+        let body = export_history(state, flag.key, environment.name, flag_value_id);
+        return Ok(([(header::CONTENT_TYPE, NDJSON)], body).into_response());
+    }
+
+    let limit = query.limit.unwrap_or(HISTORY_PAGE_SIZE);
+    if !(1..=MAX_HISTORY_PAGE_SIZE).contains(&limit) {
+        return Err(AppError::BadRequest(format!(
+            "limit must be between 1 and {MAX_HISTORY_PAGE_SIZE}"
+        )));
+    }
+    let before = query
+        .cursor
+        .as_deref()
+        .map(|cursor| {
+            cursor
+                .parse::<i64>()
+                .map_err(|_| AppError::BadRequest(format!("Invalid cursor '{cursor}'")))
+        })
+        .transpose()?;
+
+    // One more than asked for tells whether there is a next page
+    let mut history = match flag_value_id {
+        Some(id) => {
+            state
+                .storage
+                .list_flag_value_history_page(&id, before, limit + 1)
+                .await?
+        }
+        None => vec![],
+    };
+    let next_cursor = if history.len() as i64 > limit {
+        history.truncate(limit as usize);
+        history.last().map(|fv| fv.version.to_string())
+    } else {
+        None
+    };
+
+    Ok(Json(FlagHistoryResponse {
+        key: flag.key,
+        environment: environment.name,
+        versions: history.iter().map(FlagVersion::from).collect(),
+        next_cursor,
+    })
+    .into_response())
+}
+
+/// Every version as NDJSON, newest first, fetched from storage one batch per
+/// chunk sent so memory use doesn't grow with the history
+fn export_history(
+    state: AppState,
+    key: String,
+    environment: String,
+    flag_value_id: Option<String>,
+) -> Body {
+    // (before, finished): the next batch's cursor, and whether the last
+    // batch came back short
+    let stream = stream::try_unfold((None, false), move |(before, finished)| {
+        let state = state.clone();
+        let key = key.clone();
+        let environment = environment.clone();
+        let flag_value_id = flag_value_id.clone();
+        async move {
+            let Some(id) = flag_value_id.filter(|_| !finished) else {
+                return Ok::<_, AppError>(None);
+            };
+            let batch = state
+                .storage
+                .list_flag_value_history_page(&id, before, HISTORY_EXPORT_BATCH)
+                .await?;
+
+            let mut chunk = Vec::new();
+            for fv in &batch {
+                let line = FlagHistoryLine {
+                    key: &key,
+                    environment: &environment,
+                    version: FlagVersion::from(fv),
+                };
+                serde_json::to_writer(&mut chunk, &line)
+                    .map_err(|e| AppError::Internal(e.to_string()))?;
+                chunk.push(b'\n');
+            }
+            let finished = (batch.len() as i64) < HISTORY_EXPORT_BATCH;
+            let before = batch.last().map(|fv| fv.version);
+            Ok(Some((chunk, (before, finished))))
+        }
+    });
+    Body::from_stream(stream)
+}
+
+/// POST /projects/:project_id/flags/:key/rollback - Restore an earlier version
+/// of a flag's value
+///
+/// The restored state is written as a new version, with the same version check
+/// as any other write, so a change made in between is never overwritten.
+pub async fn rollback_flag(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(req): Json<RollbackFlagRequest>,
+) -> Result<Json<RollbackFlagResponse>> {
+    let project = auth.project(&state, &project_id).await?;
+    let project_id = project.id.clone();
+
+    let flag = find_flag(&state, &project_id, &key).await?;
+    let (environment, current) =
+        environment_value(&state, &project_id, &flag, &req.environment).await?;
+
+    if environment.frozen {
+        return Err(AppError::EnvironmentFrozen(req.environment));
+    }
+    if environment.protected && !req.confirm {
+        return Err(AppError::ProtectedEnvironment(req.environment));
+    }
+    direct_change(&environment)?;
+    let reason = change_reason(&project, &environment, req.reason)?;
+
+    let expected = concurrency::expected_version(&headers, req.expected_version)?;
+    let current_version = current.as_ref().map(|fv| fv.version).unwrap_or(0);
+    concurrency::check_version(&key, current_version, expected)?;
+
+    let Some(current) = current else {
+        return Err(AppError::BadRequest(format!(
+            "Flag '{key}' has never been set in environment '{}'; there is nothing to roll back",
+            environment.name
+        )));
+    };
+
+    let history = state.storage.list_flag_value_history(&current.id).await?;
+    let target = match req.to_version {
+        Some(version) => history
+            .iter()
+            .find(|fv| fv.version == version)
+            .ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "Flag '{key}' has no version {version} in environment '{}'",
+                    environment.name
+                ))
+            })?,
+        None => history
+            .iter()
+            .find(|fv| fv.version < current.version)
+            .ok_or_else(|| {
+                AppError::BadRequest(format!(
+                    "Flag '{key}' has no earlier version in environment '{}'",
+                    environment.name
+                ))
+            })?,
+    };
+
+    let changed = (target.enabled, target.rollout_percentage, &target.value)
+        != (current.enabled, current.rollout_percentage, &current.value);
+    let restored_version = target.version;
+    let flag_value = if changed {
+        let updated = FlagValue {
+            enabled: target.enabled,
+            rollout_percentage: target.rollout_percentage,
+            value: target.value.clone(),
+            updated_at: Utc::now(),
+            version: current.version + 1,
+            reason: reason.clone(),
+            ..current
+        };
+        if !state.storage.update_flag_value(&updated).await? {
+            return Err(AppError::flag_value_conflict(&key));
+        }
+        record_changes(&state, &project_id, std::slice::from_ref(&flag.key), false).await?;
+        bus::audit(
+            &state,
+            &project_id,
+            "flag.rolled_back",
+            &auth.user.username,
+            format!(
+                "Flag {key} rolled back to version {restored_version} in environment {} of project {project_id} by {}{}",
+                environment.name,
+                auth.user.username,
+                because(&reason)
+            ),
+        );
+        updated
+    } else {
+        current
+    };
+
+    Ok(Json(RollbackFlagResponse {
+        key: flag.key,
+        environment: environment.name,
+        restored_version,
+        changed,
+        current: FlagVersion::from(&flag_value),
     }))
 }
 
 /// DELETE /projects/:project_id/flags/:key - Delete a flag
 pub async fn delete_flag(
     State(state): State<AppState>,
-    AuthUser(user): AuthUser,
+    auth: AuthUser,
     Path((project_id, key)): Path<(String, String)>,
 ) -> Result<()> {
-    // Verify project belongs to user
-    let project = state
-        .storage
-        .get_project_by_id(&project_id)
-        .await?
-        .ok_or_else(|| AppError::NotFound("Project not found".to_string()))?;
+    let project_id = auth.project(&state, &project_id).await?.id;
 
-    if project.user_id != user.id {
-        return Err(AppError::NotFound("Project not found".to_string()));
-    }
+    let flag = find_flag(&state, &project_id, &key).await?;
 
-    let flag = state
+    // Deleting removes the flag's value in every environment
+    let environments = state
         .storage
-        .get_flag_by_key(&project_id, &key)
-        .await?
-        .ok_or_else(|| AppError::NotFound(format!("Flag '{key}' not found")))?;
+        .list_environments_by_project(&project_id)
+        .await?;
+    if let Some(env) = environments.into_iter().find(|e| e.frozen) {
+        return Err(AppError::EnvironmentFrozen(env.name));
+    }
 
     // Delete flag (cascade should handle flag_values)
     state.storage.delete_flag(&flag.id).await?;
+    record_changes(&state, &project_id, &[flag.key], true).await?;
 
     Ok(())
 }
@@ -0,0 +1,263 @@
+//! Environment deletion and restore
+//!
+//! Deleting an environment archives it with its flag values and its
+//! allowlists and denylists, and a restore brings it back as it was, keys
+//! included, until the archive is purged DELETED_ENVIRONMENT_RETENTION_DAYS
+//! later. History, evaluation counts, signing keys, ramps and guards are
+//! deleted with the environment. Protected environments can only be deleted
+//! by a project owner, and only with `force`.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+use crate::auth::AuthUser;
+use crate::bus;
+use crate::error::{AppError, Result};
+use crate::handlers::change_requests::project_admins;
+use crate::handlers::cli::CliEnvironment;
+use crate::models::{AppState, DeletedEnvironment, Environment, FlagValue, ListedUser};
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteEnvironmentQuery {
+    /// Required, with a project owner's credentials, to delete a protected
+    /// environment
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// A deleted environment that can still be restored
+#[derive(Debug, Serialize)]
+pub struct DeletedEnvironmentResponse {
+    pub id: String,
+    pub name: String,
+    /// Flags that had a value in the environment
+    pub flag_values: usize,
+    pub listed_users: usize,
+    pub deleted_by: String,
+    pub deleted_at: DateTime<Utc>,
+    /// When the archive is purged and the environment can no longer be restored
+    pub purge_after: DateTime<Utc>,
+}
+
+/// What an archive holds, read back from its JSON columns
+struct Archived {
+    environment: Environment,
+    flag_values: Vec<FlagValue>,
+    listed_users: Vec<ListedUser>,
+}
+
+impl Archived {
+    fn read(deleted: &DeletedEnvironment) -> Result<Self> {
+        let invalid = |e: serde_json::Error| {
+            AppError::Internal(format!(
+                "Archive of environment {} is unreadable: {e}",
+                deleted.id
+            ))
+        };
+        Ok(Archived {
+            environment: serde_json::from_str(&deleted.environment).map_err(invalid)?,
+            flag_values: serde_json::from_str(&deleted.flag_values).map_err(invalid)?,
+            listed_users: serde_json::from_str(&deleted.listed_users).map_err(invalid)?,
+        })
+    }
+}
+
+impl TryFrom<DeletedEnvironment> for DeletedEnvironmentResponse {
+    type Error = AppError;
+
+    fn try_from(deleted: DeletedEnvironment) -> Result<Self> {
+        let archived = Archived::read(&deleted)?;
+        Ok(DeletedEnvironmentResponse {
+            id: deleted.id,
+            name: deleted.name,
+            flag_values: archived.flag_values.len(),
+            listed_users: archived.listed_users.len(),
+            deleted_by: deleted.deleted_by,
+            deleted_at: deleted.deleted_at,
+            purge_after: deleted.purge_after,
+        })
+    }
+}
+
+/// DELETE /projects/:project_id/environments/:name - Delete an environment,
+/// keeping it restorable for the retention period
+pub async fn delete_environment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, name)): Path<(String, String)>,
+    Query(query): Query<DeleteEnvironmentQuery>,
+) -> Result<Json<DeletedEnvironmentResponse>> {
+    let project = auth.project(&state, &project_id).await?;
+    let project_id = project.id.clone();
+
+    let environments = state
+        .storage
+        .list_environments_by_project(&project_id)
+        .await?;
+    let environment = environments
+        .iter()
+        .find(|e| e.name == name)
+        .ok_or_else(|| AppError::NotFound(format!("Environment '{name}' not found")))?;
+
+    if environments.len() == 1 {
+        return Err(AppError::BadRequest(format!(
+            "Environment '{name}' is the project's only environment; create another before deleting it"
+        )));
+    }
+    if environment.frozen {
+        return Err(AppError::EnvironmentFrozen(name));
+    }
+    if environment.protected {
+        if !query.force {
+            return Err(AppError::ProtectedEnvironment(name));
+        }
+        if !project_admins(&state, &project)
+            .await?
+            .contains(&auth.user.id)
+        {
+            return Err(AppError::Forbidden(
+                "Only project owners can delete protected environments".to_string(),
+            ));
+        }
+    }
+
+    let flag_ids: Vec<String> = state
+        .storage
+        .list_flags_by_project(&project_id)
+        .await?
+        .into_iter()
+        .map(|f| f.id)
+        .collect();
+    let flag_values: Vec<FlagValue> = state
+        .storage
+        .list_flag_values_by_flag_ids(&flag_ids)
+        .await?
+        .into_iter()
+        .filter(|fv| fv.environment_id == environment.id)
+        .collect();
+    let listed_users = state
+        .storage
+        .list_listed_users_by_environment(&environment.id)
+        .await?;
+
+    let now = Utc::now();
+    let to_json = |e: serde_json::Error| AppError::Internal(e.to_string());
+    let deleted = DeletedEnvironment {
+        id: environment.id.clone(),
+        project_id: project_id.clone(),
+        name: name.clone(),
+        environment: serde_json::to_string(environment).map_err(to_json)?,
+        flag_values: serde_json::to_string(&flag_values).map_err(to_json)?,
+        listed_users: serde_json::to_string(&listed_users).map_err(to_json)?,
+        deleted_by: auth.user.username.clone(),
+        deleted_at: now,
+        purge_after: now + Duration::days(state.retention.deleted_environment_days.into()),
+    };
+    state
+        .storage
+        .delete_environment(environment, &deleted)
+        .await?;
+
+    bus::audit(
+        &state,
+        &project_id,
+        "environment.deleted",
+        &auth.user.username,
+        format!(
+            "Environment {name} deleted from project {project_id} by {}; restorable until {}",
+            auth.user.username,
+            deleted.purge_after.to_rfc3339()
+        ),
+    );
+
+    Ok(Json(deleted.try_into()?))
+}
+
+/// GET /projects/:project_id/deleted-environments - Deleted environments that
+/// can still be restored, most recently deleted first
+pub async fn list_deleted_environments(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+) -> Result<Json<Vec<DeletedEnvironmentResponse>>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    let deleted = state
+        .storage
+        .list_deleted_environments(&project_id)
+        .await?
+        .into_iter()
+        .map(DeletedEnvironmentResponse::try_from)
+        .collect::<Result<_>>()?;
+    Ok(Json(deleted))
+}
+
+/// POST /projects/:project_id/environments/:name/restore - Bring back the
+/// most recently deleted environment with this name
+pub async fn restore_environment(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, name)): Path<(String, String)>,
+) -> Result<Json<CliEnvironment>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    let deleted = state
+        .storage
+        .list_deleted_environments(&project_id)
+        .await?
+        .into_iter()
+        .find(|d| d.name == name)
+        .ok_or_else(|| AppError::NotFound(format!("No deleted environment '{name}' to restore")))?;
+    let Archived {
+        environment,
+        flag_values,
+        listed_users,
+    } = Archived::read(&deleted)?;
+
+    // Flags deleted since then stay deleted
+    let flags: HashSet<String> = state
+        .storage
+        .list_flags_by_project(&project_id)
+        .await?
+        .into_iter()
+        .map(|f| f.id)
+        .collect();
+    let flag_values: Vec<FlagValue> = flag_values
+        .into_iter()
+        .filter(|fv| flags.contains(&fv.flag_id))
+        .collect();
+    let listed_users: Vec<ListedUser> = listed_users
+        .into_iter()
+        .filter(|u| flags.contains(&u.flag_id))
+        .collect();
+
+    if !state
+        .storage
+        .restore_environment(&environment, &flag_values, &listed_users)
+        .await?
+    {
+        // Restored or purged by another request in the meantime
+        return Err(AppError::NotFound(format!(
+            "No deleted environment '{name}' to restore"
+        )));
+    }
+
+    bus::audit(
+        &state,
+        &project_id,
+        "environment.restored",
+        &auth.user.username,
+        format!(
+            "Environment {name} restored in project {project_id} with {} flag value(s) by {}",
+            flag_values.len(),
+            auth.user.username
+        ),
+    );
+
+    Ok(Json(environment.into()))
+}
@@ -1,26 +1,173 @@
+//! SDK flag evaluation; flag management lives in the CLI handlers
+
 use axum::{
     extract::{Path, Query, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue},
+    response::IntoResponse,
     Json,
 };
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
 use std::collections::HashMap;
 use std::io::Cursor;
-use uuid::Uuid;
 
-use crate::auth::{AuthProject, FlexAuth};
+use crate::auth::FlexAuth;
 use crate::error::{AppError, Result};
+use crate::events::{hash_user_id, EvaluationEvent, EvaluationReason};
 use crate::models::{
-    AppState, CreateFlagRequest, EvaluateFlagQuery, Flag, FlagEnvironmentValue,
-    FlagEvaluationResponse, FlagResponse, FlagToggleResponse, FlagValue, ToggleFlagQuery,
-    UpdateFlagValueRequest,
+    AppState, Environment, EvaluateFlagQuery, Flag, FlagChangesQuery, FlagChangesResponse,
+    FlagConfig, FlagConfigResponse, FlagEvaluationResponse, ListedUser, RolloutValue,
+    SignedFlagConfigResponse, SigningKey,
 };
+use crate::signing;
 
-/// Deterministic percentage rollout using murmur3 hash
-fn is_enabled_for_user(flag_key: &str, user_id: &str, rollout_percentage: i32) -> bool {
+/// The user's rollout bucket (0-99) for a flag, from a murmur3 hash of both;
+/// the same on every server and relay
+pub fn bucket(flag_key: &str, user_id: &str) -> i32 {
     let input = format!("{flag_key}:{user_id}");
     let hash = murmur3::murmur3_32(&mut Cursor::new(input.as_bytes()), 0).unwrap_or(0);
-    let bucket = (hash % 100) as i32;
-    bucket < rollout_percentage
+    (hash % 100) as i32
+}
+
+/// The value served with an evaluation: the environment's, else the flag's
+/// default, and nothing when the flag is off
+pub fn served_value(
+    value: Option<&RolloutValue>,
+    default_value: Option<serde_json::Value>,
+    enabled: bool,
+) -> Option<serde_json::Value> {
+    value
+        .and_then(|v| v.value.clone())
+        .or(default_value)
+        .filter(|_| enabled)
+}
+
+/// Deterministic percentage rollout: users in buckets below the percentage are in
+fn is_enabled_for_user(flag_key: &str, user_id: &str, rollout_percentage: i32) -> bool {
+    bucket(flag_key, user_id) < rollout_percentage
+}
+
+/// Evaluate one flag from its state in an environment; `value` is None when
+/// the flag has no value there, and `default_enabled` applies instead. While
+/// the flag is on, its denylist and then its allowlist decide for the users
+/// on them, before the rollout percentage. Shared with the relay so both
+/// answer alike.
+pub fn evaluate(
+    key: &str,
+    expires_at: Option<DateTime<Utc>>,
+    value: Option<&RolloutValue>,
+    default_enabled: bool,
+    user_id: Option<&str>,
+) -> (bool, EvaluationReason) {
+    if expires_at.is_some_and(|at| at <= Utc::now()) {
+        return (false, EvaluationReason::Expired);
+    }
+
+    let Some(value) = value else {
+        return (default_enabled, EvaluationReason::NoValue);
+    };
+
+    let listed = |list: &[String]| {
+        user_id.is_some_and(|user_id| list.binary_search_by(|id| id.as_str().cmp(user_id)).is_ok())
+    };
+
+    if !value.enabled {
+        (false, EvaluationReason::Disabled)
+    } else if listed(&value.denylist) {
+        (false, EvaluationReason::Denylisted)
+    } else if listed(&value.allowlist) {
+        (true, EvaluationReason::Allowlisted)
+    } else if value.rollout_percentage >= 100 {
+        (true, EvaluationReason::FullRollout)
+    } else if value.rollout_percentage <= 0 {
+        (false, EvaluationReason::ZeroRollout)
+    } else {
+        // Percentage rollout
+        match user_id {
+            Some(user_id) => {
+                if is_enabled_for_user(key, user_id, value.rollout_percentage) {
+                    (true, EvaluationReason::InRollout)
+                } else {
+                    (false, EvaluationReason::OutOfRollout)
+                }
+            }
+            None => {
+                // No user ID = random evaluation
+                let random = rand::random::<u32>() % 100;
+                (
+                    (random as i32) < value.rollout_percentage,
+                    EvaluationReason::Random,
+                )
+            }
+        }
+    }
+}
+
+/// Project and environment an SDK key evaluates against.
+/// Environment keys use their environment; project keys default to production.
+async fn sdk_environment(state: &AppState, auth: &FlexAuth) -> Result<(String, Environment)> {
+    match auth {
+        FlexAuth::Environment(env, project) => Ok((project.id.clone(), (**env).clone())),
+        FlexAuth::Project(project) => {
+            let env = state
+                .storage
+                .get_environment_by_name(&project.id, "production")
+                .await?
+                .ok_or_else(|| {
+                    AppError::NotFound("Production environment not found".to_string())
+                })?;
+            Ok((project.id.clone(), env))
+        }
+    }
+}
+
+/// How long past its max age a cache may serve a response while the server is down
+const STALE_IF_ERROR_SECONDS: i32 = 3600;
+
+/// `Cache-Control` for an SDK response from `environment`: its own cache
+/// settings if it has them, else the endpoint's `default` (None sends none)
+pub fn cache_control(
+    environment: &Environment,
+    default: Option<&'static str>,
+) -> Option<HeaderValue> {
+    let Some(max_age) = environment.cache_max_age else {
+        return default.map(HeaderValue::from_static);
+    };
+    if max_age == 0 {
+        return Some(HeaderValue::from_static("no-store"));
+    }
+
+    let mut value = format!("public, max-age={max_age}");
+    if let Some(stale) = environment.cache_stale_while_revalidate.filter(|s| *s > 0) {
+        value.push_str(&format!(", stale-while-revalidate={stale}"));
+    }
+    value.push_str(&format!(", stale-if-error={STALE_IF_ERROR_SECONDS}"));
+    HeaderValue::from_str(&value).ok()
+}
+
+/// Like [`cache_control`], but a coin flip for a partial rollout without a
+/// user ID isn't worth reusing
+pub fn evaluation_cache_control(
+    environment: &Environment,
+    default: Option<&'static str>,
+    reason: EvaluationReason,
+) -> Option<HeaderValue> {
+    let value = cache_control(environment, default)?;
+    match reason {
+        EvaluationReason::Random => Some(HeaderValue::from_static("no-store")),
+        _ => Some(value),
+    }
+}
+
+/// Response headers carrying `cache_control`; responses differ by SDK key,
+/// so shared caches must key on it
+pub fn cache_headers(cache_control: Option<HeaderValue>) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(value) = cache_control {
+        headers.insert(header::CACHE_CONTROL, value);
+        headers.insert(header::VARY, HeaderValue::from_static("authorization"));
+    }
+    headers
 }
 
 /// Evaluate a flag (SDK endpoint - uses environment API key)
@@ -29,11 +176,8 @@ pub async fn evaluate_flag(
     Path(key): Path<String>,
     Query(query): Query<EvaluateFlagQuery>,
     auth: FlexAuth,
-) -> Result<Json<FlagEvaluationResponse>> {
-    let (project_id, environment_id) = match &auth {
-        FlexAuth::Environment(env, project) => (project.id.clone(), Some(env.id.clone())),
-        FlexAuth::Project(project) => (project.id.clone(), None),
-    };
+) -> Result<impl IntoResponse> {
+    let (project_id, environment) = sdk_environment(&state, &auth).await?;
 
     // Get the flag
     let flag = state
@@ -42,367 +186,544 @@ pub async fn evaluate_flag(
         .await?
         .ok_or_else(|| AppError::NotFound(format!("Flag '{key}' not found")))?;
 
-    // If using env API key, get that specific environment's value
-    // If using project key, default to production
-    let env_id = match environment_id {
-        Some(id) => id,
-        None => {
-            // Default to production environment
-            let env = state
+    let (evaluation, reason) =
+        evaluate_in(&state, &environment, flag, query.user_id.as_deref()).await?;
+    Ok((
+        cache_headers(evaluation_cache_control(&environment, None, reason)),
+        Json(evaluation),
+    ))
+}
+
+/// A flag's value in an environment with its allowlist and denylist, which
+/// are only looked up when there's a user they could name
+pub async fn with_lists(
+    state: &AppState,
+    value: Option<RolloutValue>,
+    flag_id: &str,
+    environment_id: &str,
+    user_id: Option<&str>,
+) -> Result<Option<RolloutValue>> {
+    match value {
+        Some(value) if user_id.is_some() => {
+            let users = state
                 .storage
-                .get_environment_by_name(&project_id, "production")
-                .await?
-                .ok_or_else(|| {
-                    AppError::NotFound("Production environment not found".to_string())
-                })?;
-            env.id
+                .list_listed_users(flag_id, environment_id)
+                .await?;
+            Ok(Some(value.with_listed_users(users)))
         }
-    };
+        value => Ok(value),
+    }
+}
+
+/// Evaluate `flag` in `environment`, counting its usage and emitting the
+/// evaluation event
+pub async fn evaluate_in(
+    state: &AppState,
+    environment: &Environment,
+    flag: Flag,
+    user_id: Option<&str>,
+) -> Result<(FlagEvaluationResponse, EvaluationReason)> {
+    state.usage.record(&flag.id, &environment.id);
 
     // Get flag value for this environment
-    let flag_value = state.storage.get_flag_value(&flag.id, &env_id).await?;
-
-    let enabled = match flag_value {
-        Some(fv) => {
-            if !fv.enabled {
-                false
-            } else if fv.rollout_percentage >= 100 {
-                true
-            } else if fv.rollout_percentage <= 0 {
-                false
-            } else {
-                // Percentage rollout
-                match &query.user_id {
-                    Some(user_id) => is_enabled_for_user(&key, user_id, fv.rollout_percentage),
-                    None => {
-                        // No user ID = random evaluation
-                        let random = rand::random::<u32>() % 100;
-                        (random as i32) < fv.rollout_percentage
-                    }
-                }
-            }
-        }
-        None => false, // No flag value = disabled
-    };
+    let flag_value = state
+        .storage
+        .get_flag_value(&flag.id, &environment.id)
+        .await?;
+    let value = with_lists(
+        state,
+        flag_value.as_ref().map(RolloutValue::from),
+        &flag.id,
+        &environment.id,
+        user_id,
+    )
+    .await?;
+
+    let (enabled, reason) = evaluate(
+        &flag.key,
+        flag.expires_at,
+        value.as_ref(),
+        flag.default_enabled,
+        user_id,
+    );
+
+    if let Some(events) = &state.events {
+        events.emit(EvaluationEvent {
+            timestamp: Utc::now(),
+            project_id: environment.project_id.clone(),
+            environment_id: environment.id.clone(),
+            flag_key: flag.key.clone(),
+            user_hash: user_id.map(hash_user_id),
+            enabled,
+            reason,
+        });
+    }
 
-    Ok(Json(FlagEvaluationResponse { key, enabled }))
+    let value = served_value(value.as_ref(), flag.parsed_default_value(), enabled);
+    Ok((
+        FlagEvaluationResponse {
+            key: flag.key,
+            enabled,
+            value,
+        },
+        reason,
+    ))
 }
 
-/// List all flags for a project
-// Kept for future use
-#[allow(dead_code)]
-pub async fn list_flags(
-    State(state): State<AppState>,
-    AuthProject(project): AuthProject,
-) -> Result<Json<Vec<FlagResponse>>> {
-    // Get all flags for the project
-    let flags = state.storage.list_flags_by_project(&project.id).await?;
+/// How long shell scripts and proxies may reuse an answer from
+/// `GET /v1/flags/:key/enabled` in environments without cache settings, and
+/// how long past that while the server is down
+const ENABLED_CACHE_CONTROL: &str =
+    "public, max-age=10, stale-while-revalidate=30, stale-if-error=3600";
 
-    // Get all environments
-    let environments = state
-        .storage
-        .list_environments_by_project(&project.id)
-        .await?;
+/// Seconds the server would like SDKs to wait between polls of `/v1/flags` and
+/// `/v1/changes`
+const POLL_INTERVAL_HEADER: HeaderName = HeaderName::from_static("x-poll-interval");
 
-    let env_map: HashMap<String, String> = environments
-        .iter()
-        .map(|e| (e.id.clone(), e.name.clone()))
-        .collect();
+/// Poll interval for environments whose cache settings don't ask for longer
+const POLL_INTERVAL_SECONDS: i32 = 30;
 
-    // Get all flag values
-    let flag_ids: Vec<String> = flags.iter().map(|f| f.id.clone()).collect();
-    let flag_values = state
+/// Nothing changes while the server is read-only, so polls can be rare
+const MAINTENANCE_POLL_INTERVAL_SECONDS: i32 = 300;
+
+/// Carries the answer for HEAD requests, which have no body
+const FLAG_ENABLED_HEADER: HeaderName = HeaderName::from_static("x-flag-enabled");
+
+/// Whether a flag is on, as `true` or `false` in plain text, for callers that
+/// can't afford JSON: shell scripts, nginx njs/lua, health checks
+pub async fn flag_enabled(
+    State(state): State<AppState>,
+    Path(key): Path<String>,
+    Query(query): Query<EvaluateFlagQuery>,
+    auth: FlexAuth,
+) -> Result<impl IntoResponse> {
+    let (project_id, environment) = sdk_environment(&state, &auth).await?;
+    let env_id = environment.id.clone();
+
+    let switch = state
         .storage
-        .list_flag_values_by_flag_ids(&flag_ids)
-        .await?;
+        .get_flag_switch(&project_id, &env_id, &key)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Flag '{key}' not found")))?;
 
-    // Group flag values by flag_id
-    let mut flag_value_map: HashMap<String, Vec<FlagValue>> = HashMap::new();
-    for fv in flag_values {
-        flag_value_map
-            .entry(fv.flag_id.clone())
-            .or_default()
-            .push(fv);
+    state.usage.record(&switch.flag_id, &env_id);
+
+    let value = with_lists(
+        &state,
+        switch.rollout_value(),
+        &switch.flag_id,
+        &env_id,
+        query.user_id.as_deref(),
+    )
+    .await?;
+    let (enabled, reason) = evaluate(
+        &key,
+        switch.expires_at,
+        value.as_ref(),
+        switch.default_enabled,
+        query.user_id.as_deref(),
+    );
+
+    if let Some(events) = &state.events {
+        events.emit(EvaluationEvent {
+            timestamp: Utc::now(),
+            project_id,
+            environment_id: env_id,
+            flag_key: key,
+            user_hash: query.user_id.as_deref().map(hash_user_id),
+            enabled,
+            reason,
+        });
     }
 
-    // Build response
-    let mut responses = Vec::new();
-    for flag in flags {
-        let mut env_values: HashMap<String, FlagEnvironmentValue> = HashMap::new();
-
-        // Initialize all environments with defaults
-        for env in &environments {
-            env_values.insert(
-                env.name.clone(),
-                FlagEnvironmentValue {
-                    enabled: false,
-                    rollout: 100,
-                },
-            );
-        }
+    let answer = if enabled { "true" } else { "false" };
 
-        // Override with actual values
-        if let Some(values) = flag_value_map.get(&flag.id) {
-            for fv in values {
-                if let Some(env_name) = env_map.get(&fv.environment_id) {
-                    env_values.insert(
-                        env_name.clone(),
-                        FlagEnvironmentValue {
-                            enabled: fv.enabled,
-                            rollout: fv.rollout_percentage,
-                        },
-                    );
-                }
-            }
-        }
+    let mut headers = cache_headers(evaluation_cache_control(
+        &environment,
+        Some(ENABLED_CACHE_CONTROL),
+        reason,
+    ));
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+    headers.insert(FLAG_ENABLED_HEADER, HeaderValue::from_static(answer));
 
-        responses.push(FlagResponse {
-            key: flag.key,
-            name: flag.name,
-            description: flag.description,
-            environments: env_values,
-        });
+    Ok((headers, answer))
+}
+
+/// Seconds SDKs should wait between polls of `environment`: no fewer than its
+/// responses may be cached for, and more while the server is read-only
+fn poll_interval(environment: &Environment, read_only: bool) -> i32 {
+    if read_only {
+        return MAINTENANCE_POLL_INTERVAL_SECONDS;
     }
+    environment
+        .cache_max_age
+        .unwrap_or_default()
+        .max(POLL_INTERVAL_SECONDS)
+}
 
-    Ok(Json(responses))
+/// Every flag's state in the key's environment, for relays and SDKs that
+/// evaluate locally
+pub async fn flag_config(
+    State(state): State<AppState>,
+    auth: FlexAuth,
+) -> Result<impl IntoResponse> {
+    let (project_id, environment) = sdk_environment(&state, &auth).await?;
+    let mut headers = cache_headers(cache_control(&environment, None));
+    headers.insert(
+        POLL_INTERVAL_HEADER,
+        poll_interval(&environment, state.maintenance.is_read_only()).into(),
+    );
+    Ok((
+        headers,
+        Json(snapshot(&state, project_id, environment.id).await?),
+    ))
 }
 
-/// Create a new flag
-// Kept for future use
-#[allow(dead_code)]
-pub async fn create_flag(
+/// Flags changed since the client's last poll, so SDKs holding many flags can
+/// poll cheaply. Returns every flag when `since` is 0 or ahead of the project,
+/// e.g. after the database was restored.
+pub async fn flag_changes(
     State(state): State<AppState>,
-    AuthProject(project): AuthProject,
-    Json(req): Json<CreateFlagRequest>,
-) -> Result<Json<FlagResponse>> {
-    // Validate key format
-    if req.key.is_empty() || req.key.len() > 255 {
-        return Err(AppError::BadRequest("Invalid flag key".to_string()));
-    }
-    if !req
-        .key
-        .chars()
-        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
-    {
+    auth: FlexAuth,
+    Query(query): Query<FlagChangesQuery>,
+) -> Result<impl IntoResponse> {
+    if query.since < 0 {
         return Err(AppError::BadRequest(
-            "Flag key can only contain alphanumeric characters, hyphens, and underscores"
-                .to_string(),
+            "since must be a revision from a previous response, or 0".to_string(),
         ));
     }
+    let (project_id, environment) = sdk_environment(&state, &auth).await?;
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        POLL_INTERVAL_HEADER,
+        poll_interval(&environment, state.maintenance.is_read_only()).into(),
+    );
+    let environment_id = environment.id;
+
+    // Read before the flags, so a change racing this request is sent again
+    // on the next poll rather than missed
+    let revision = state.storage.get_flag_revision(&project_id).await?;
+    let flags = state.storage.list_flags_by_project(&project_id).await?;
+
+    let full = query.since == 0 || query.since > revision;
+    let (flags, deleted) = if full {
+        (flags, vec![])
+    } else {
+        let changes = state
+            .storage
+            .list_flag_changes(&project_id, query.since)
+            .await?;
+        let flags = flags
+            .into_iter()
+            .filter(|f| changes.iter().any(|c| c.flag_key == f.key && !c.deleted))
+            .collect();
+        let deleted = changes
+            .into_iter()
+            .filter(|c| c.deleted)
+            .map(|c| c.flag_key)
+            .collect();
+        (flags, deleted)
+    };
 
-    // Check for duplicate
-    let existing = state.storage.get_flag_by_key(&project.id, &req.key).await?;
+    Ok((
+        headers,
+        Json(FlagChangesResponse {
+            flags: configs(&state, &environment_id, flags).await?,
+            project_id,
+            environment_id,
+            revision,
+            full,
+            deleted,
+        }),
+    ))
+}
 
-    if existing.is_some() {
-        return Err(AppError::BadRequest(format!(
-            "Flag '{}' already exists",
-            req.key
-        )));
+/// The same snapshot signed with the environment's key, for browser and mobile
+/// SDKs that must detect tampering
+pub async fn signed_flag_config(
+    State(state): State<AppState>,
+    auth: FlexAuth,
+) -> Result<impl IntoResponse> {
+    let (project_id, environment) = sdk_environment(&state, &auth).await?;
+    let headers = cache_headers(cache_control(&environment, None));
+    let environment_id = environment.id;
+    let key = active_signing_key(&state, &environment_id).await?;
+
+    #[derive(Serialize)]
+    struct Claims {
+        iat: i64,
+        #[serde(flatten)]
+        config: FlagConfigResponse,
     }
-
-    let now = Utc::now();
-    let flag_id = Uuid::new_v4().to_string();
-
-    // Create the flag
-    let flag = Flag {
-        id: flag_id.clone(),
-        project_id: project.id.clone(),
-        key: req.key.clone(),
-        name: req.name.clone(),
-        description: req.description.clone(),
-        created_at: now,
+    let claims = Claims {
+        iat: Utc::now().timestamp(),
+        config: snapshot(&state, project_id, environment_id).await?,
     };
 
-    state.storage.create_flag(&flag).await?;
-
-    // Get all environments and create default flag values
-    let environments = state
-        .storage
-        .list_environments_by_project(&project.id)
-        .await?;
-
-    let mut env_values: HashMap<String, FlagEnvironmentValue> = HashMap::new();
-
-    for env in &environments {
-        let fv_id = Uuid::new_v4().to_string();
-        let flag_value = FlagValue {
-            id: fv_id,
-            flag_id: flag_id.clone(),
-            environment_id: env.id.clone(),
-            enabled: false,
-            rollout_percentage: 100,
-            updated_at: now,
-        };
+    Ok((
+        headers,
+        Json(SignedFlagConfigResponse {
+            jws: signing::sign(&key, &claims)?,
+            kid: key.id,
+        }),
+    ))
+}
 
-        state.storage.create_flag_value(&flag_value).await?;
+/// Public keys for verifying the key's environment's signed snapshots
+pub async fn signing_keys(
+    State(state): State<AppState>,
+    auth: FlexAuth,
+) -> Result<Json<signing::Jwks>> {
+    let (_, environment) = sdk_environment(&state, &auth).await?;
+    let environment_id = environment.id;
+    // Created here too, so SDKs can fetch keys before the first signed snapshot
+    active_signing_key(&state, &environment_id).await?;
+    let keys = state.storage.list_signing_keys(&environment_id).await?;
+    Ok(Json(signing::published(&keys, Utc::now())))
+}
 
-        env_values.insert(
-            env.name.clone(),
-            FlagEnvironmentValue {
-                enabled: false,
-                rollout: 100,
-            },
-        );
+/// The environment's current signing key, created on first use
+async fn active_signing_key(state: &AppState, environment_id: &str) -> Result<SigningKey> {
+    let keys = state.storage.list_signing_keys(environment_id).await?;
+    if let Some(key) = signing::active(&keys) {
+        return Ok(key.clone());
     }
+    let key = signing::generate(environment_id, Utc::now());
+    state.storage.create_signing_key(&key).await?;
+    Ok(key)
+}
 
-    Ok(Json(FlagResponse {
-        key: req.key,
-        name: req.name,
-        description: req.description,
-        environments: env_values,
-    }))
+async fn snapshot(
+    state: &AppState,
+    project_id: String,
+    environment_id: String,
+) -> Result<FlagConfigResponse> {
+    let flags = state.storage.list_flags_by_project(&project_id).await?;
+    Ok(FlagConfigResponse {
+        flags: configs(state, &environment_id, flags).await?,
+        project_id,
+        environment_id,
+    })
 }
 
-/// Update flag value for a specific environment
-// Kept for future use
-#[allow(dead_code)]
-pub async fn update_flag_value(
-    State(state): State<AppState>,
-    AuthProject(project): AuthProject,
-    Path((key, env_name)): Path<(String, String)>,
-    Json(req): Json<UpdateFlagValueRequest>,
-) -> Result<Json<FlagEnvironmentValue>> {
-    // Get the flag
-    let flag = state
+/// The flags as served to SDKs, with their values in the environment
+async fn configs(
+    state: &AppState,
+    environment_id: &str,
+    flags: Vec<Flag>,
+) -> Result<Vec<FlagConfig>> {
+    let flag_ids: Vec<String> = flags.iter().map(|f| f.id.clone()).collect();
+    let mut listed: HashMap<String, Vec<ListedUser>> = HashMap::new();
+    for user in state
         .storage
-        .get_flag_by_key(&project.id, &key)
+        .list_listed_users_by_environment(environment_id)
         .await?
-        .ok_or_else(|| AppError::NotFound(format!("Flag '{key}' not found")))?;
-
-    // Get the environment
-    let environment = state
+    {
+        listed.entry(user.flag_id.clone()).or_default().push(user);
+    }
+    let mut values: HashMap<String, RolloutValue> = state
         .storage
-        .get_environment_by_name(&project.id, &env_name)
+        .list_flag_values_by_flag_ids(&flag_ids)
         .await?
-        .ok_or_else(|| AppError::NotFound(format!("Environment '{env_name}' not found")))?;
-
-    // Get or create flag value
-    let existing = state
-        .storage
-        .get_flag_value(&flag.id, &environment.id)
-        .await?;
+        .into_iter()
+        .filter(|fv| fv.environment_id == environment_id)
+        .map(|fv| {
+            let users = listed.remove(&fv.flag_id).unwrap_or_default();
+            (
+                fv.flag_id.clone(),
+                RolloutValue::from(&fv).with_listed_users(users),
+            )
+        })
+        .collect();
 
-    let now = Utc::now();
+    Ok(flags
+        .into_iter()
+        .map(|flag| FlagConfig {
+            value: values.remove(&flag.id),
+            default_value: flag.parsed_default_value(),
+            default_enabled: flag.default_enabled,
+            key: flag.key,
+            expires_at: flag.expires_at,
+        })
+        .collect())
+}
 
-    let (enabled, rollout) = match existing {
-        Some(fv) => {
-            let new_enabled = req.enabled.unwrap_or(fv.enabled);
-            let new_rollout = req.rollout_percentage.unwrap_or(fv.rollout_percentage);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{LIST_ALLOW, LIST_DENY};
+
+    fn value(enabled: bool, rollout_percentage: i32) -> RolloutValue {
+        RolloutValue {
+            enabled,
+            rollout_percentage,
+            value: None,
+            allowlist: Vec::new(),
+            denylist: Vec::new(),
+        }
+    }
 
-            // Validate rollout percentage
-            if !(0..=100).contains(&new_rollout) {
-                return Err(AppError::BadRequest(
-                    "Rollout percentage must be between 0 and 100".to_string(),
-                ));
-            }
+    #[test]
+    fn test_evaluate_reasons() {
+        let user = Some("user-1");
+        assert_eq!(
+            evaluate("f", None, None, false, user),
+            (false, EvaluationReason::NoValue)
+        );
+        assert_eq!(
+            evaluate("f", None, None, true, user),
+            (true, EvaluationReason::NoValue)
+        );
+        assert_eq!(
+            evaluate("f", None, Some(&value(false, 100)), false, user),
+            (false, EvaluationReason::Disabled)
+        );
+        assert_eq!(
+            evaluate("f", None, Some(&value(true, 100)), false, user),
+            (true, EvaluationReason::FullRollout)
+        );
+        assert_eq!(
+            evaluate("f", None, Some(&value(true, 0)), false, user),
+            (false, EvaluationReason::ZeroRollout)
+        );
 
-            let updated_fv = FlagValue {
-                id: fv.id,
-                flag_id: flag.id,
-                environment_id: environment.id,
-                enabled: new_enabled,
-                rollout_percentage: new_rollout,
-                updated_at: now,
-            };
+        let (enabled, reason) = evaluate("f", None, Some(&value(true, 50)), false, user);
+        assert_eq!(enabled, is_enabled_for_user("f", "user-1", 50));
+        assert!(matches!(
+            reason,
+            EvaluationReason::InRollout | EvaluationReason::OutOfRollout
+        ));
+        assert_eq!(
+            evaluate("f", None, Some(&value(true, 50)), false, None).1,
+            EvaluationReason::Random
+        );
 
-            state.storage.update_flag_value(&updated_fv).await?;
+        let past = Some(Utc::now() - chrono::Duration::minutes(1));
+        assert_eq!(
+            evaluate("f", past, Some(&value(true, 100)), false, user),
+            (false, EvaluationReason::Expired)
+        );
+        let future = Some(Utc::now() + chrono::Duration::days(1));
+        assert_eq!(
+            evaluate("f", future, Some(&value(true, 100)), false, user).1,
+            EvaluationReason::FullRollout
+        );
+    }
 
-            (new_enabled, new_rollout)
-        }
-        None => {
-            let enabled = req.enabled.unwrap_or(false);
-            let rollout = req.rollout_percentage.unwrap_or(100);
-
-            if !(0..=100).contains(&rollout) {
-                return Err(AppError::BadRequest(
-                    "Rollout percentage must be between 0 and 100".to_string(),
-                ));
-            }
+    #[test]
+    fn test_lists_decide_before_rollout() {
+        let listed = |list: &str, user_id: &str| ListedUser {
+            flag_id: "f1".to_string(),
+            environment_id: "e1".to_string(),
+            user_id: user_id.to_string(),
+            list: list.to_string(),
+            created_at: Utc::now(),
+        };
+        let lists = |enabled, percentage| {
+            value(enabled, percentage).with_listed_users([
+                listed(LIST_ALLOW, "zoe"),
+                listed(LIST_ALLOW, "beta-1"),
+                listed(LIST_DENY, "mallory"),
+            ])
+        };
 
-            let fv_id = Uuid::new_v4().to_string();
-            let flag_value = FlagValue {
-                id: fv_id,
-                flag_id: flag.id,
-                environment_id: environment.id,
-                enabled,
-                rollout_percentage: rollout,
-                updated_at: now,
-            };
+        assert_eq!(lists(true, 0).allowlist, ["beta-1", "zoe"]);
+        assert_eq!(
+            evaluate("f", None, Some(&lists(true, 0)), false, Some("zoe")),
+            (true, EvaluationReason::Allowlisted)
+        );
+        assert_eq!(
+            evaluate("f", None, Some(&lists(true, 100)), false, Some("mallory")),
+            (false, EvaluationReason::Denylisted)
+        );
+        assert_eq!(
+            evaluate("f", None, Some(&lists(true, 0)), false, Some("someone")),
+            (false, EvaluationReason::ZeroRollout)
+        );
+        // Turning the flag off still turns it off for everyone
+        assert_eq!(
+            evaluate("f", None, Some(&lists(false, 0)), false, Some("zoe")),
+            (false, EvaluationReason::Disabled)
+        );
+    }
 
-            state.storage.create_flag_value(&flag_value).await?;
+    #[test]
+    fn test_served_value_falls_back_to_default() {
+        let default = Some(serde_json::json!("blue"));
+        let mut on = value(true, 100);
+        assert_eq!(served_value(None, default.clone(), true), default);
+        assert_eq!(served_value(Some(&on), default.clone(), true), default);
+        on.value = Some(serde_json::json!("green"));
+        assert_eq!(
+            served_value(Some(&on), default.clone(), true),
+            Some(serde_json::json!("green"))
+        );
+        assert_eq!(served_value(Some(&on), default, false), None);
+    }
 
-            (enabled, rollout)
+    fn environment(cache_max_age: Option<i32>, stale: Option<i32>) -> Environment {
+        Environment {
+            id: "e1".to_string(),
+            project_id: "p1".to_string(),
+            name: "production".to_string(),
+            api_key: String::new(),
+            protected: false,
+            frozen: false,
+            sort_order: 0,
+            color: None,
+            emoji: None,
+            cache_max_age,
+            cache_stale_while_revalidate: stale,
+            public_client_id: None,
+            require_approval: false,
+            created_at: Utc::now(),
         }
-    };
-
-    Ok(Json(FlagEnvironmentValue { enabled, rollout }))
-}
-
-/// Toggle a flag in a specific environment
-// Kept for future use
-#[allow(dead_code)]
-pub async fn toggle_flag(
-    State(state): State<AppState>,
-    AuthProject(project): AuthProject,
-    Path(key): Path<String>,
-    Query(query): Query<ToggleFlagQuery>,
-) -> Result<Json<FlagToggleResponse>> {
-    // Get the flag
-    let flag = state
-        .storage
-        .get_flag_by_key(&project.id, &key)
-        .await?
-        .ok_or_else(|| AppError::NotFound(format!("Flag '{key}' not found")))?;
-
-    // Get the environment
-    let env_name = &query.environment;
-    let environment = state
-        .storage
-        .get_environment_by_name(&project.id, env_name)
-        .await?
-        .ok_or_else(|| AppError::NotFound(format!("Environment '{env_name}' not found")))?;
+    }
 
-    let now = Utc::now();
+    #[test]
+    fn test_cache_control_from_environment_settings() {
+        let header = |env: &Environment, default, reason| {
+            evaluation_cache_control(env, default, reason).map(|v| v.to_str().unwrap().to_string())
+        };
+        let unset = environment(None, None);
+        assert_eq!(header(&unset, None, EvaluationReason::FullRollout), None);
+        assert_eq!(
+            header(
+                &unset,
+                Some(ENABLED_CACHE_CONTROL),
+                EvaluationReason::FullRollout
+            )
+            .as_deref(),
+            Some(ENABLED_CACHE_CONTROL)
+        );
 
-    // Get current value and toggle
-    let existing = state
-        .storage
-        .get_flag_value(&flag.id, &environment.id)
-        .await?;
+        let cached = environment(Some(60), Some(300));
+        assert_eq!(
+            header(&cached, None, EvaluationReason::InRollout).as_deref(),
+            Some("public, max-age=60, stale-while-revalidate=300, stale-if-error=3600")
+        );
+        assert_eq!(
+            header(&cached, None, EvaluationReason::Random).as_deref(),
+            Some("no-store")
+        );
 
-    let new_enabled = match existing {
-        Some(fv) => {
-            let toggled = !fv.enabled;
-            let updated_fv = FlagValue {
-                id: fv.id,
-                flag_id: flag.id,
-                environment_id: environment.id,
-                enabled: toggled,
-                rollout_percentage: fv.rollout_percentage,
-                updated_at: now,
-            };
-            state.storage.update_flag_value(&updated_fv).await?;
-            toggled
-        }
-        None => {
-            // No value exists, create with enabled = true (toggle from default false)
-            let fv_id = Uuid::new_v4().to_string();
-            let flag_value = FlagValue {
-                id: fv_id,
-                flag_id: flag.id,
-                environment_id: environment.id,
-                enabled: true,
-                rollout_percentage: 100,
-                updated_at: now,
-            };
-            state.storage.create_flag_value(&flag_value).await?;
-            true
-        }
-    };
+        let off = environment(Some(0), Some(300));
+        assert_eq!(
+            header(
+                &off,
+                Some(ENABLED_CACHE_CONTROL),
+                EvaluationReason::FullRollout
+            )
+            .as_deref(),
+            Some("no-store")
+        );
+    }
 
-    Ok(Json(FlagToggleResponse {
-        key,
-        environment: query.environment,
-        enabled: new_enabled,
-    }))
+    #[test]
+    fn test_poll_interval_follows_cache_and_maintenance() {
+        assert_eq!(poll_interval(&environment(None, None), false), 30);
+        assert_eq!(poll_interval(&environment(Some(0), None), false), 30);
+        assert_eq!(poll_interval(&environment(Some(120), None), false), 120);
+        assert_eq!(poll_interval(&environment(Some(120), None), true), 300);
+    }
 }
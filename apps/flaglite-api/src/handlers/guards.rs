@@ -0,0 +1,417 @@
+//! Guard handlers
+//! A guard reverts a flag change if a health check or a pushed report says
+//! it's unhealthy; see [`crate::guards`] for how guards are checked.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::{hash_api_key, AuthUser};
+use crate::bus;
+use crate::error::{AppError, Result};
+use crate::guards::{self, HealthChecks, MAX_WINDOW_SECS, MIN_WINDOW_SECS};
+use crate::handlers::cli::find_flag;
+use crate::handlers::ramps::Responses;
+use crate::models::{
+    generate_guard_token, AppState, Environment, Flag, FlagValue, Guard, Project, GUARD_CANCELLED,
+    GUARD_PASSED, GUARD_SUPERSEDED, GUARD_TRIPPED, GUARD_WATCHING,
+};
+use crate::validation::Validator;
+
+/// How to watch a change: the health check to poll, if any, and for how long
+#[derive(Debug, Deserialize)]
+pub struct GuardSpec {
+    /// Polled while the guard watches; unhealthy reports can be pushed either way
+    pub url: Option<String>,
+    /// Seconds the guard watches for, from the change or the end of its ramp
+    pub window_secs: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateGuardRequest {
+    pub environment: String,
+    /// Version of the flag's value the change produced; the current one when
+    /// unset
+    pub version: Option<i64>,
+    #[serde(flatten)]
+    pub spec: GuardSpec,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListGuardsQuery {
+    /// `watching`, `passed`, `tripped`, `superseded` or `cancelled`; all when unset
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GuardReport {
+    pub token: String,
+    /// Healthy reports are accepted and change nothing
+    #[serde(default)]
+    pub healthy: bool,
+    pub message: Option<String>,
+}
+
+/// A guard, with the flag key, environment name and creator's username in
+/// place of ids
+#[derive(Debug, Serialize)]
+pub struct GuardResponse {
+    pub id: String,
+    pub project_id: String,
+    pub flag_key: String,
+    pub environment: String,
+    pub ramp_id: Option<String>,
+    pub url: Option<String>,
+    pub version: i64,
+    pub revert_enabled: bool,
+    pub revert_rollout_percentage: i32,
+    pub revert_value: Option<serde_json::Value>,
+    pub window_secs: i64,
+    pub status: String,
+    pub message: Option<String>,
+    /// When the guard stops watching; None while its ramp is still running
+    pub watching_until: Option<DateTime<Utc>>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Token to push unhealthy reports with; only returned when the guard is
+    /// created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+impl Responses {
+    pub async fn guard(&mut self, state: &AppState, guard: Guard) -> Result<GuardResponse> {
+        let (flag_key, environment, created_by) = self
+            .names(
+                state,
+                &guard.flag_id,
+                &guard.environment_id,
+                &guard.created_by,
+            )
+            .await?;
+        let ramp = match &guard.ramp_id {
+            Some(id) => state.storage.get_ramp(id).await?,
+            None => None,
+        };
+        let revert_value = guard
+            .revert_value
+            .as_deref()
+            .and_then(|v| serde_json::from_str(v).ok());
+        Ok(GuardResponse {
+            watching_until: (guard.status == GUARD_WATCHING)
+                .then(|| guards::watching_until(&guard, ramp.as_ref()))
+                .flatten(),
+            flag_key,
+            environment,
+            id: guard.id,
+            project_id: guard.project_id,
+            ramp_id: guard.ramp_id,
+            url: guard.url,
+            version: guard.version,
+            revert_enabled: guard.revert_enabled,
+            revert_rollout_percentage: guard.revert_rollout_percentage,
+            revert_value,
+            window_secs: guard.window_secs,
+            status: guard.status,
+            message: guard.message,
+            created_by,
+            created_at: guard.created_at,
+            ended_at: guard.ended_at,
+            token: None,
+        })
+    }
+}
+
+async fn response(state: &AppState, guard: Guard) -> Result<Json<GuardResponse>> {
+    let mut responses = Responses::for_project(state, &guard.project_id).await?;
+    Ok(Json(responses.guard(state, guard).await?))
+}
+
+/// Check a guard spec, adding what's wrong to `validator`
+pub fn validate(validator: &mut Validator, spec: &GuardSpec, checks: &HealthChecks) {
+    if let Some(url) = &spec.url {
+        match reqwest::Url::parse(url) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => {
+                if let Some(reason) = checks.refuse(&url) {
+                    validator.fail(
+                        "url",
+                        "host",
+                        format!("Guard URL can't be polled: {reason}"),
+                    );
+                }
+            }
+            _ => validator.fail("url", "url", "Guard URL must be an http or https URL"),
+        }
+    }
+    if !(MIN_WINDOW_SECS..=MAX_WINDOW_SECS).contains(&spec.window_secs) {
+        validator.fail(
+            "window_secs",
+            "range",
+            format!("Window must be between {MIN_WINDOW_SECS} seconds and 7 days"),
+        );
+    }
+}
+
+/// The state `existing` is reverted to when a guard trips: the flag's
+/// defaults if it had no value
+pub fn revert_to(flag: &Flag, existing: Option<&FlagValue>) -> (bool, i32, Option<String>) {
+    match existing {
+        Some(fv) => (fv.enabled, fv.rollout_percentage, fv.value.clone()),
+        None => (flag.default_enabled, 100, None),
+    }
+}
+
+/// Start watching a change to the flag, returning the guard's response with
+/// its token
+#[allow(clippy::too_many_arguments)]
+pub async fn watch(
+    state: &AppState,
+    auth: &AuthUser,
+    flag: &Flag,
+    environment: &Environment,
+    ramp_id: Option<String>,
+    version: i64,
+    revert: (bool, i32, Option<String>),
+    spec: GuardSpec,
+) -> Result<GuardResponse> {
+    let token = generate_guard_token();
+    let (revert_enabled, revert_rollout_percentage, revert_value) = revert;
+    let guard = Guard {
+        id: Uuid::new_v4().to_string(),
+        project_id: flag.project_id.clone(),
+        flag_id: flag.id.clone(),
+        environment_id: environment.id.clone(),
+        ramp_id,
+        url: spec.url,
+        token_hash: hash_api_key(&token),
+        version,
+        revert_enabled,
+        revert_rollout_percentage,
+        revert_value,
+        window_secs: spec.window_secs,
+        status: GUARD_WATCHING.to_string(),
+        message: None,
+        created_by: auth.user.id.clone(),
+        created_at: Utc::now(),
+        ended_at: None,
+    };
+    state.storage.create_guard(&guard).await?;
+    let watched = match &guard.ramp_id {
+        Some(id) => format!("ramp {id}"),
+        None => format!("version {version}"),
+    };
+    bus::audit(
+        state,
+        &guard.project_id,
+        "guard.created",
+        &auth.user.username,
+        format!(
+            "Guard {} in project {}: {} guards {watched} of '{}' in {} for {}s",
+            guard.id,
+            guard.project_id,
+            auth.user.username,
+            flag.key,
+            environment.name,
+            guard.window_secs
+        ),
+    );
+
+    let mut responses = Responses::for_project(state, &guard.project_id).await?;
+    let mut response = responses.guard(state, guard).await?;
+    response.token = Some(token);
+    Ok(response)
+}
+
+/// POST /projects/:project_id/flags/:key/guards - Revert a flag's latest
+/// change if it turns out unhealthy
+///
+/// The flag is put back to the version before the guarded one.
+pub async fn create_guard(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+    Json(req): Json<CreateGuardRequest>,
+) -> Result<Json<GuardResponse>> {
+    let project = auth.project(&state, &project_id).await?;
+
+    let mut validator = Validator::new();
+    validate(&mut validator, &req.spec, &state.health_checks);
+    validator.finish()?;
+
+    let flag = find_flag(&state, &project.id, &key).await?;
+    let environment = state
+        .storage
+        .get_environment_by_name(&project.id, &req.environment)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Environment '{}' not found", req.environment))
+        })?;
+    let Some(current) = state
+        .storage
+        .get_flag_value(&flag.id, &environment.id)
+        .await?
+    else {
+        return Err(AppError::BadRequest(format!(
+            "Flag '{key}' has never been set in environment '{}'; there is no change to guard",
+            environment.name
+        )));
+    };
+    if let Some(version) = req.version.filter(|v| *v != current.version) {
+        return Err(AppError::Conflict(format!(
+            "Flag '{key}' is at version {} in environment '{}', not {version}; only the latest change can be guarded",
+            current.version, environment.name
+        )));
+    }
+
+    let history = state.storage.list_flag_value_history(&current.id).await?;
+    let previous = history.iter().find(|fv| fv.version < current.version);
+    if previous.is_none() && current.version > 1 {
+        return Err(AppError::BadRequest(format!(
+            "Flag '{key}' has no earlier version in environment '{}' to revert to",
+            environment.name
+        )));
+    }
+
+    let guard = watch(
+        &state,
+        &auth,
+        &flag,
+        &environment,
+        None,
+        current.version,
+        revert_to(&flag, previous),
+        req.spec,
+    )
+    .await?;
+    Ok(Json(guard))
+}
+
+/// GET /projects/:project_id/guards - A project's guards, newest first
+pub async fn list_guards(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+    Query(query): Query<ListGuardsQuery>,
+) -> Result<Json<Vec<GuardResponse>>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    if let Some(status) = &query.status {
+        let statuses = [
+            GUARD_WATCHING,
+            GUARD_PASSED,
+            GUARD_TRIPPED,
+            GUARD_SUPERSEDED,
+            GUARD_CANCELLED,
+        ];
+        if !statuses.contains(&status.as_str()) {
+            let mut validator = Validator::new();
+            validator.fail(
+                "status",
+                "one_of",
+                "Status must be watching, passed, tripped, superseded or cancelled",
+            );
+            validator.finish()?;
+        }
+    }
+
+    let guards = state.storage.list_guards(&project_id).await?;
+    let mut responses = Responses::for_project(&state, &project_id).await?;
+    let mut result = Vec::with_capacity(guards.len());
+    for guard in guards {
+        if query.status.as_ref().is_none_or(|s| *s == guard.status) {
+            result.push(responses.guard(&state, guard).await?);
+        }
+    }
+    Ok(Json(result))
+}
+
+/// The guard, if the caller can reach its project
+async fn find_guard(state: &AppState, auth: &AuthUser, id: &str) -> Result<(Guard, Project)> {
+    let not_found = || AppError::NotFound("Guard not found".to_string());
+    let guard = state.storage.get_guard(id).await?.ok_or_else(not_found)?;
+    let project = auth
+        .project(state, &guard.project_id)
+        .await
+        .map_err(|_| not_found())?;
+    Ok((guard, project))
+}
+
+/// GET /guards/:id - One guard and what it has seen
+pub async fn get_guard(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<GuardResponse>> {
+    let (guard, _) = find_guard(&state, &auth, &id).await?;
+    response(&state, guard).await
+}
+
+/// POST /guards/:id/cancel - Stop watching, leaving the flag as it is
+pub async fn cancel_guard(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<GuardResponse>> {
+    let (mut guard, project) = find_guard(&state, &auth, &id).await?;
+    if guard.status != GUARD_WATCHING {
+        return Err(AppError::Conflict(format!("Guard is {}", guard.status)));
+    }
+
+    guard.status = GUARD_CANCELLED.to_string();
+    guard.ended_at = Some(Utc::now());
+    if !state.storage.end_guard(&guard).await? {
+        return Err(AppError::Conflict(
+            "Guard ended while it was being cancelled; reload to see how".to_string(),
+        ));
+    }
+    bus::audit(
+        &state,
+        &project.id,
+        "guard.cancelled",
+        &auth.user.username,
+        format!(
+            "Guard {} in project {} cancelled by {}",
+            guard.id, project.id, auth.user.username
+        ),
+    );
+    response(&state, guard).await
+}
+
+/// POST /guards/report - Tell a guard whether its change is healthy
+///
+/// Authenticated by the guard's token alone, so monitoring and error
+/// trackers can push to it. An unhealthy report trips the guard.
+pub async fn report(
+    State(state): State<AppState>,
+    Json(req): Json<GuardReport>,
+) -> Result<Json<GuardResponse>> {
+    let guard = state
+        .storage
+        .get_guard_by_token_hash(&hash_api_key(req.token.trim()))
+        .await?
+        .ok_or_else(|| AppError::NotFound("Guard not found".to_string()))?;
+    if guard.status != GUARD_WATCHING {
+        return Err(AppError::Conflict(format!("Guard is {}", guard.status)));
+    }
+
+    let id = guard.id.clone();
+    if !req.healthy {
+        let message = req
+            .message
+            .filter(|m| !m.trim().is_empty())
+            .unwrap_or_else(|| "unhealthy report received".to_string());
+        guards::trip(&state, guard, &message, Utc::now()).await?;
+    }
+
+    let guard = state
+        .storage
+        .get_guard(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Guard not found".to_string()))?;
+    response(&state, guard).await
+}
@@ -0,0 +1,26 @@
+//! Readiness probe and metrics for load balancers, Kubernetes and Prometheus
+
+use axum::extract::State;
+use axum::http::header;
+use axum::response::IntoResponse;
+
+use crate::error::{AppError, Result};
+use crate::models::AppState;
+
+/// GET /ready - OK while the database answers, 503 otherwise
+pub async fn ready(State(state): State<AppState>) -> Result<&'static str> {
+    state.storage.health_check().await.map_err(|e| {
+        tracing::warn!("Readiness check failed: {e}");
+        AppError::Unavailable("Database is unavailable".to_string())
+    })?;
+    Ok("OK")
+}
+
+/// GET /metrics - Storage operation timings and event subscriber counts in
+/// the Prometheus text format
+pub async fn metrics(State(state): State<AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.storage_metrics.render() + &state.bus.render(),
+    )
+}
@@ -0,0 +1,397 @@
+//! Project invite link and member handlers
+//! A link shares one project with any signed-in user who redeems it, without
+//! adding them to the project's organization. Project owners can see who
+//! joined this way and take their access back.
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::{hash_api_key, AuthUser};
+use crate::bus;
+use crate::error::{AppError, Result};
+use crate::handlers::change_requests::{project_admins, Usernames};
+use crate::handlers::cli::CliProject;
+use crate::models::{
+    generate_invite_link_token, AppState, InviteLink, Project, ProjectMember, ROLE_MEMBER,
+    ROLE_OWNER,
+};
+use crate::validation::Validator;
+
+/// How long a link works for when the request doesn't say
+pub const DEFAULT_LINK_VALID_SECS: i64 = 7 * 24 * 60 * 60;
+pub const MIN_LINK_VALID_SECS: i64 = 60 * 60;
+pub const MAX_LINK_VALID_SECS: i64 = 30 * 24 * 60 * 60;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateInviteLinkRequest {
+    /// Role redeemers get: `member` (default) or `owner`
+    pub role: Option<String>,
+    pub expires_in_secs: Option<i64>,
+}
+
+/// An invite link, with its creator's username in place of their id
+#[derive(Debug, Serialize)]
+pub struct InviteLinkResponse {
+    pub id: String,
+    pub project_id: String,
+    pub role: String,
+    /// `active`, `expired` or `revoked`
+    pub status: &'static str,
+    pub uses: i64,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Redeemed at `POST /v1/join/:token`; only returned when the link is
+    /// created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// Someone a project was shared with by invite link
+#[derive(Debug, Serialize)]
+pub struct ProjectMemberResponse {
+    pub user_id: String,
+    pub username: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JoinResponse {
+    pub project: CliProject,
+    /// The caller's role from the link; None when they could already reach
+    /// the project through its organization
+    pub role: Option<String>,
+    /// False when the link changed nothing
+    pub joined: bool,
+}
+
+fn status(link: &InviteLink, now: DateTime<Utc>) -> &'static str {
+    if link.revoked_at.is_some() {
+        "revoked"
+    } else if link.expires_at <= now {
+        "expired"
+    } else {
+        "active"
+    }
+}
+
+async fn response(
+    state: &AppState,
+    usernames: &mut Usernames,
+    link: InviteLink,
+) -> Result<InviteLinkResponse> {
+    Ok(InviteLinkResponse {
+        status: status(&link, Utc::now()),
+        created_by: usernames.get(state, &link.created_by).await?,
+        id: link.id,
+        project_id: link.project_id,
+        role: link.role,
+        uses: link.uses,
+        created_at: link.created_at,
+        expires_at: link.expires_at,
+        revoked_at: link.revoked_at,
+        token: None,
+    })
+}
+
+async fn member_response(
+    state: &AppState,
+    usernames: &mut Usernames,
+    member: ProjectMember,
+) -> Result<ProjectMemberResponse> {
+    Ok(ProjectMemberResponse {
+        username: usernames.get(state, &member.user_id).await?,
+        user_id: member.user_id,
+        role: member.role,
+        created_at: member.created_at,
+    })
+}
+
+/// The project, if the caller may manage its invite links and members
+async fn require_admin(
+    state: &AppState,
+    auth: &AuthUser,
+    project_id: &str,
+    what: &str,
+) -> Result<Project> {
+    let project = auth.project(state, project_id).await?;
+    if !project_admins(state, &project)
+        .await?
+        .contains(&auth.user.id)
+    {
+        return Err(AppError::Forbidden(format!(
+            "Only project owners can manage {what}"
+        )));
+    }
+    Ok(project)
+}
+
+/// POST /projects/:project_id/invite-links - Create a link that shares the
+/// project; the token is only returned here
+pub async fn create_invite_link(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+    Json(req): Json<CreateInviteLinkRequest>,
+) -> Result<Json<InviteLinkResponse>> {
+    let project = require_admin(&state, &auth, &project_id, "invite links").await?;
+
+    let role = req.role.unwrap_or_else(|| ROLE_MEMBER.to_string());
+    let expires_in = req.expires_in_secs.unwrap_or(DEFAULT_LINK_VALID_SECS);
+    let mut validator = Validator::new();
+    if role != ROLE_OWNER && role != ROLE_MEMBER {
+        validator.fail(
+            "role",
+            "one_of",
+            format!("Role must be '{ROLE_OWNER}' or '{ROLE_MEMBER}'"),
+        );
+    }
+    if !(MIN_LINK_VALID_SECS..=MAX_LINK_VALID_SECS).contains(&expires_in) {
+        validator.fail(
+            "expires_in_secs",
+            "range",
+            "Links must expire between 1 hour and 30 days from now",
+        );
+    }
+    validator.finish()?;
+
+    let now = Utc::now();
+    let token = generate_invite_link_token();
+    let link = InviteLink {
+        id: Uuid::new_v4().to_string(),
+        project_id: project.id.clone(),
+        token_hash: hash_api_key(&token),
+        role,
+        uses: 0,
+        created_by: auth.user.id.clone(),
+        created_at: now,
+        expires_at: now + Duration::seconds(expires_in),
+        revoked_at: None,
+    };
+    state.storage.create_invite_link(&link).await?;
+    bus::audit(
+        &state,
+        &project.id,
+        "invite_link.created",
+        &auth.user.username,
+        format!(
+            "Invite link {} in project {}: {} shared the project as {} until {}",
+            link.id,
+            project.id,
+            auth.user.username,
+            link.role,
+            link.expires_at.format("%Y-%m-%d %H:%M:%S UTC")
+        ),
+    );
+
+    let mut response = response(&state, &mut Usernames::default(), link).await?;
+    response.token = Some(token);
+    Ok(Json(response))
+}
+
+/// GET /projects/:project_id/invite-links - A project's invite links, newest first
+pub async fn list_invite_links(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+) -> Result<Json<Vec<InviteLinkResponse>>> {
+    let project = require_admin(&state, &auth, &project_id, "invite links").await?;
+
+    let mut usernames = Usernames::default();
+    let mut links = Vec::new();
+    for link in state.storage.list_invite_links(&project.id).await? {
+        links.push(response(&state, &mut usernames, link).await?);
+    }
+    Ok(Json(links))
+}
+
+/// DELETE /projects/:project_id/invite-links/:id - Stop a link working
+///
+/// Users who already joined with it keep their access.
+pub async fn revoke_invite_link(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, id)): Path<(String, String)>,
+) -> Result<Json<InviteLinkResponse>> {
+    let project = require_admin(&state, &auth, &project_id, "invite links").await?;
+    let not_found = || AppError::NotFound("Invite link not found".to_string());
+    let link = state
+        .storage
+        .get_invite_link(&id)
+        .await?
+        .filter(|l| l.project_id == project.id)
+        .ok_or_else(not_found)?;
+
+    if state
+        .storage
+        .revoke_invite_link(&link.id, Utc::now())
+        .await?
+    {
+        bus::audit(
+            &state,
+            &project.id,
+            "invite_link.revoked",
+            &auth.user.username,
+            format!(
+                "Invite link {} in project {}: revoked by {} after {} use(s)",
+                link.id, project.id, auth.user.username, link.uses
+            ),
+        );
+    }
+
+    let link = state
+        .storage
+        .get_invite_link(&link.id)
+        .await?
+        .ok_or_else(not_found)?;
+    Ok(Json(
+        response(&state, &mut Usernames::default(), link).await?,
+    ))
+}
+
+/// GET /projects/:project_id/members - Users the project was shared with by
+/// invite link, longest-standing first
+pub async fn list_members(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+) -> Result<Json<Vec<ProjectMemberResponse>>> {
+    let project = require_admin(&state, &auth, &project_id, "project members").await?;
+
+    let mut usernames = Usernames::default();
+    let mut members = Vec::new();
+    for member in state.storage.list_project_members(&project.id).await? {
+        members.push(member_response(&state, &mut usernames, member).await?);
+    }
+    Ok(Json(members))
+}
+
+/// DELETE /projects/:project_id/members/:user_id - Take back the access an
+/// invite link gave
+///
+/// Users who can also reach the project through its organization keep that
+/// access.
+pub async fn remove_member(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, user_id)): Path<(String, String)>,
+) -> Result<Json<ProjectMemberResponse>> {
+    let project = require_admin(&state, &auth, &project_id, "project members").await?;
+    let not_found = || AppError::NotFound("Project member not found".to_string());
+    let member = state
+        .storage
+        .get_project_member(&project.id, &user_id)
+        .await?
+        .ok_or_else(not_found)?;
+
+    // Removed by another request in the meantime
+    if !state
+        .storage
+        .remove_project_member(&project.id, &member.user_id)
+        .await?
+    {
+        return Err(not_found());
+    }
+
+    let member = member_response(&state, &mut Usernames::default(), member).await?;
+    bus::audit(
+        &state,
+        &project.id,
+        "project.member_removed",
+        &auth.user.username,
+        format!(
+            "{} ({}) removed from project {} by {}",
+            member.username, member.role, project.id, auth.user.username
+        ),
+    );
+    Ok(Json(member))
+}
+
+/// POST /join/:token - Get access to the project an invite link shares
+///
+/// A link with a higher role than the caller already has raises it;
+/// otherwise nothing changes and the link isn't counted as used.
+pub async fn join(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(token): Path<String>,
+) -> Result<Json<JoinResponse>> {
+    if auth.org_scope.is_some() {
+        return Err(AppError::Forbidden(
+            "An API key limited to an organization can't join projects".to_string(),
+        ));
+    }
+
+    let link = state
+        .storage
+        .get_invite_link_by_token_hash(&hash_api_key(&token))
+        .await?
+        .ok_or_else(|| AppError::NotFound("Invite link not found".to_string()))?;
+    let now = Utc::now();
+    if status(&link, now) != "active" {
+        return Err(AppError::BadRequest(format!(
+            "This invite link is {}",
+            status(&link, now)
+        )));
+    }
+    let project = state
+        .storage
+        .get_project_by_id(&link.project_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Invite link not found".to_string()))?;
+
+    let existing = state
+        .storage
+        .get_project_member(&project.id, &auth.user.id)
+        .await?;
+    let unchanged = match &existing {
+        Some(member) => member.role == ROLE_OWNER || link.role == ROLE_MEMBER,
+        None => auth.project(&state, &project.id).await.is_ok(),
+    };
+    if unchanged {
+        return Ok(Json(JoinResponse {
+            project: project.into(),
+            role: existing.map(|m| m.role),
+            joined: false,
+        }));
+    }
+
+    // Checked again here, so a link revoked meanwhile isn't used
+    if !state.storage.use_invite_link(&link.id, now).await? {
+        return Err(AppError::BadRequest(
+            "This invite link has expired or been revoked".to_string(),
+        ));
+    }
+    state
+        .storage
+        .set_project_member(&ProjectMember {
+            project_id: project.id.clone(),
+            user_id: auth.user.id.clone(),
+            role: link.role.clone(),
+            created_at: existing.map_or(now, |m| m.created_at),
+        })
+        .await?;
+    bus::audit(
+        &state,
+        &project.id,
+        "project.joined",
+        &auth.user.username,
+        format!(
+            "Invite link {} in project {}: {} joined as {}",
+            link.id, project.id, auth.user.username, link.role
+        ),
+    );
+
+    Ok(Json(JoinResponse {
+        project: project.into(),
+        role: Some(link.role),
+        joined: true,
+    }))
+}
@@ -1,7 +1,15 @@
 use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
+use serde_json::{json, Value};
+use std::fmt::Write as _;
+use std::sync::OnceLock;
 
-const LLMS_TXT: &str = r#"# FlagLite
+use crate::models::ApiKeyScope;
+use crate::routes::{self, Auth, SCHEMAS};
+use crate::versioning::{ApiVersion, VERSION_HEADER};
+
+/// Hand-written prose before the generated endpoint reference
+const INTRO: &str = r#"# FlagLite
 
 > Feature flags for teams who ship fast — without the enterprise tax.
 
@@ -32,30 +40,10 @@ FlagLite is an open-source feature flag service. Self-host or use our hosted ver
 
 3. Evaluate in your app (see SDK examples below)
 
-## API Endpoints
-
-Base URL: `https://api.flaglite.dev/v1` (or your self-hosted instance)
-
-### Authentication
-- `POST /v1/auth/signup` — Create account, returns JWT + API key
-- `POST /v1/auth/login` — Get JWT token
-- `GET /v1/auth/me` — Get current user
-
-### Projects
-- `GET /v1/projects` — List all projects
-- `POST /v1/projects` — Create project `{"name": "string"}`
-
-### Environments
-- `GET /v1/projects/{project_id}/environments` — List environments (dev/staging/prod)
-
-### Flags
-- `GET /v1/projects/{project_id}/flags?environment={env}` — List all flags
-- `POST /v1/projects/{project_id}/flags` — Create flag `{"key": "string", "name": "string", "enabled": bool}`
-- `GET /v1/projects/{project_id}/flags/{key}?environment={env}` — Get flag with state
-- `DELETE /v1/projects/{project_id}/flags/{key}` — Delete flag
-- `POST /v1/projects/{project_id}/flags/{key}/toggle?environment={env}` — Toggle flag on/off
+"#;
 
-## SDKs
+/// Hand-written prose after the generated endpoint reference
+const OUTRO: &str = r#"## SDKs
 
 ### JavaScript/TypeScript
 ```bash
@@ -127,12 +115,173 @@ if (await client.evaluate('payments-enabled', { userId })) {
 - API: https://api.flaglite.dev
 "#;
 
+const BASE_URL: &str = "https://api.flaglite.dev/v1";
+
+/// A request/response shape as JSON, or the schema name it refers to
+fn shape(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()))
+}
+
+fn render_txt() -> String {
+    let mut out = String::from(INTRO);
+
+    out.push_str("## API Endpoints\n\n");
+    let _ = writeln!(
+        out,
+        "Base URL: `{BASE_URL}` (or your self-hosted instance)\n\nUnless noted, endpoints take {}.",
+        Auth::User.description()
+    );
+    let _ = writeln!(
+        out,
+        "\nShapes below are API version 1. Ask for version {latest} with a `/v{latest}` path prefix or `Accept: application/vnd.flaglite.v{latest}+json`; endpoints whose responses differ list the changes. Responses name their version in `{VERSION_HEADER}`.",
+        latest = ApiVersion::LATEST.number()
+    );
+
+    for section in routes::sections() {
+        let _ = write!(out, "\n### {}\n", section.title);
+        for route in section.routes {
+            let e = route.endpoint;
+            let _ = writeln!(
+                out,
+                "- `{} {}` — {}",
+                e.method.as_str(),
+                e.display_path(),
+                e.summary
+            );
+            if e.auth != Auth::User {
+                let _ = writeln!(out, "  - Auth: {}", e.auth.description());
+            } else if e.scope == ApiKeyScope::Admin {
+                let _ = writeln!(out, "  - API keys need the `admin` scope");
+            }
+            for param in e.query {
+                let _ = writeln!(out, "  - Query `{}`: {}", param.name, param.description);
+            }
+            if let Some(request) = e.request {
+                let _ = writeln!(out, "  - Body: `{request}`");
+            }
+            if let Some(response) = e.response {
+                let _ = writeln!(out, "  - Returns: `{response}`");
+            }
+            if let Some(notes) = e.notes {
+                let _ = writeln!(out, "  - {notes}");
+            }
+            for change in e.changes {
+                let _ = writeln!(
+                    out,
+                    "  - Version {}: {}",
+                    change.version.number(),
+                    change.description
+                );
+            }
+        }
+    }
+
+    out.push_str("\n### Schemas\n");
+    for (name, schema) in SCHEMAS {
+        let _ = writeln!(out, "- `{name}`: `{schema}`");
+    }
+
+    out.push('\n');
+    out.push_str(OUTRO);
+    out
+}
+
+fn render_json() -> String {
+    let mut endpoints = Vec::new();
+    for section in routes::sections() {
+        for route in section.routes {
+            let e = route.endpoint;
+            endpoints.push(json!({
+                "method": e.method.as_str(),
+                "path": e.display_path(),
+                "section": section.title,
+                "auth": e.auth.as_str(),
+                "summary": e.summary,
+                "query": e.query.iter().map(|p| json!({
+                    "name": p.name,
+                    "description": p.description,
+                })).collect::<Vec<_>>(),
+                "request": e.request.map(shape),
+                "response": e.response.map(shape),
+                "notes": e.notes,
+                "writes": e.writes,
+                // Only user credentials carry a scope
+                "scope": matches!(e.auth, Auth::User | Auth::Sdk).then_some(e.scope),
+                "changes": e.changes.iter().map(|c| json!({
+                    "version": c.version.number(),
+                    "description": c.description,
+                })).collect::<Vec<_>>(),
+            }));
+        }
+    }
+
+    let schemas: serde_json::Map<String, Value> = SCHEMAS
+        .iter()
+        .map(|(name, schema)| (name.to_string(), shape(schema)))
+        .collect();
+
+    let doc = json!({
+        "name": "FlagLite",
+        "base_url": BASE_URL,
+        "auth": {
+            "none": Auth::None.description(),
+            "user": Auth::User.description(),
+            "sdk": Auth::Sdk.description(),
+            "admin": Auth::Admin.description(),
+            "public": Auth::Public.description(),
+        },
+        "api_version": {
+            "default": ApiVersion::V1.number(),
+            "latest": ApiVersion::LATEST.number(),
+            "header": VERSION_HEADER,
+        },
+        "endpoints": endpoints,
+        "schemas": schemas,
+    });
+    serde_json::to_string_pretty(&doc).unwrap_or_default()
+}
+
 /// Handler for /llms.txt - provides LLM-friendly documentation
 pub async fn llms_txt() -> Response {
+    static LLMS_TXT: OnceLock<String> = OnceLock::new();
     (
         StatusCode::OK,
         [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
-        LLMS_TXT,
+        LLMS_TXT.get_or_init(render_txt).as_str(),
     )
         .into_response()
 }
+
+/// Handler for /llms.json - the same endpoint reference for tool integrations
+pub async fn llms_json() -> Response {
+    static LLMS_JSON: OnceLock<String> = OnceLock::new();
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/json")],
+        LLMS_JSON.get_or_init(render_json).as_str(),
+    )
+        .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_route_is_documented() {
+        let txt = render_txt();
+        let doc: Value = serde_json::from_str(&render_json()).unwrap();
+        let endpoints = doc["endpoints"].as_array().unwrap();
+
+        for section in routes::sections() {
+            for route in section.routes {
+                let e = route.endpoint;
+                let line = format!("`{} {}`", e.method.as_str(), e.display_path());
+                assert!(txt.contains(&line), "llms.txt is missing {line}");
+                assert!(endpoints
+                    .iter()
+                    .any(|j| j["method"] == e.method.as_str() && j["path"] == e.display_path()));
+            }
+        }
+    }
+}
@@ -1,5 +1,18 @@
+pub mod admin;
+pub mod archives;
 pub mod auth;
+pub mod change_requests;
 pub mod cli;
+pub mod deleted_environments;
 pub mod flags;
+pub mod guards;
+pub mod health;
+pub mod invite_links;
 pub mod llms;
-pub mod projects;
+pub mod orgs;
+pub mod public;
+pub mod ramps;
+pub mod search;
+pub mod templates;
+pub mod user_lists;
+pub mod ws;
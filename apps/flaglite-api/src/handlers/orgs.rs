@@ -0,0 +1,324 @@
+//! Organization handlers
+//! Orgs own projects; every member of an org can manage its projects.
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::error::{AppError, Result};
+use crate::handlers::auth::{find_user_by_username, issue_api_key};
+use crate::models::{
+    ApiKeyCreatedResponse, AppState, OrgMember, Organization, ROLE_MEMBER, ROLE_OWNER,
+};
+use crate::validation::{Validator, MAX_NAME_LENGTH};
+
+/// An org as seen by one of its members
+#[derive(Debug, Serialize)]
+pub struct OrgResponse {
+    pub id: String,
+    pub name: String,
+    pub personal: bool,
+    /// The caller's role in the org
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrgMemberResponse {
+    pub user_id: String,
+    pub username: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrgRequest {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AddOrgMemberRequest {
+    pub username: String,
+    /// `owner` or `member` (default)
+    pub role: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateOrgMemberRequest {
+    /// `owner` or `member`
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateOrgApiKeyRequest {
+    pub name: Option<String>,
+    /// `read`, `write` or `admin` (default)
+    pub scope: Option<String>,
+}
+
+/// The caller's membership, rejecting anyone who isn't an owner
+async fn require_owner(auth: &AuthUser, state: &AppState, org_id: &str) -> Result<OrgMember> {
+    let member = auth.org_member(state, org_id).await?;
+    if member.role != ROLE_OWNER {
+        return Err(AppError::Forbidden(
+            "Only organization owners can do that".to_string(),
+        ));
+    }
+    Ok(member)
+}
+
+fn validate_role(role: &str) -> Result<()> {
+    if role != ROLE_OWNER && role != ROLE_MEMBER {
+        return Err(AppError::BadRequest(format!(
+            "Role must be '{ROLE_OWNER}' or '{ROLE_MEMBER}'"
+        )));
+    }
+    Ok(())
+}
+
+/// The member with this user id, and their username
+async fn find_member(state: &AppState, org_id: &str, user_id: &str) -> Result<(OrgMember, String)> {
+    let member = state
+        .storage
+        .get_org_member(org_id, user_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Member not found".to_string()))?;
+    let username = state
+        .storage
+        .get_user_by_id(user_id)
+        .await?
+        .map_or_else(|| user_id.to_string(), |u| u.username);
+    Ok((member, username))
+}
+
+fn last_owner() -> AppError {
+    AppError::Conflict(
+        "An organization needs at least one owner; make someone else an owner first".to_string(),
+    )
+}
+
+async fn get_org(state: &AppState, org_id: &str) -> Result<Organization> {
+    state
+        .storage
+        .get_organization(org_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Organization not found".to_string()))
+}
+
+/// GET /v1/orgs - Orgs the caller belongs to
+pub async fn list_orgs(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> Result<Json<Vec<OrgResponse>>> {
+    let orgs = state
+        .storage
+        .list_organizations_by_user(&auth.user.id)
+        .await?;
+
+    let mut responses = Vec::new();
+    for org in orgs {
+        // An org-scoped key only sees its own org
+        let Ok(member) = auth.org_member(&state, &org.id).await else {
+            continue;
+        };
+        responses.push(OrgResponse {
+            id: org.id,
+            name: org.name,
+            personal: org.personal,
+            role: member.role,
+            created_at: org.created_at,
+        });
+    }
+
+    Ok(Json(responses))
+}
+
+/// POST /v1/orgs - Create an org owned by the caller
+pub async fn create_org(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(req): Json<CreateOrgRequest>,
+) -> Result<Json<OrgResponse>> {
+    if auth.org_scope.is_some() {
+        return Err(AppError::Forbidden(
+            "Organization API keys can't create organizations".to_string(),
+        ));
+    }
+
+    Validator::new()
+        .name("name", "Organization name", &req.name, MAX_NAME_LENGTH)
+        .finish()?;
+
+    let now = Utc::now();
+    let org = Organization {
+        id: Uuid::new_v4().to_string(),
+        name: req.name.trim().to_string(),
+        personal: false,
+        created_at: now,
+    };
+    state.storage.create_organization(&org).await?;
+
+    state
+        .storage
+        .add_org_member(&OrgMember {
+            org_id: org.id.clone(),
+            user_id: auth.user.id.clone(),
+            role: ROLE_OWNER.to_string(),
+            created_at: now,
+        })
+        .await?;
+
+    Ok(Json(OrgResponse {
+        id: org.id,
+        name: org.name,
+        personal: false,
+        role: ROLE_OWNER.to_string(),
+        created_at: now,
+    }))
+}
+
+/// GET /v1/orgs/:org_id/members
+pub async fn list_members(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(org_id): Path<String>,
+) -> Result<Json<Vec<OrgMemberResponse>>> {
+    auth.org_member(&state, &org_id).await?;
+
+    let members = state.storage.list_org_members(&org_id).await?;
+    let mut responses = Vec::new();
+    for member in members {
+        let Some(user) = state.storage.get_user_by_id(&member.user_id).await? else {
+            continue;
+        };
+        responses.push(OrgMemberResponse {
+            user_id: member.user_id,
+            username: user.username,
+            role: member.role,
+            created_at: member.created_at,
+        });
+    }
+
+    Ok(Json(responses))
+}
+
+/// POST /v1/orgs/:org_id/members - Add an existing user to the org (owners only)
+pub async fn add_member(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(org_id): Path<String>,
+    Json(req): Json<AddOrgMemberRequest>,
+) -> Result<Json<OrgMemberResponse>> {
+    require_owner(&auth, &state, &org_id).await?;
+
+    let org = get_org(&state, &org_id).await?;
+    if org.personal {
+        return Err(AppError::BadRequest(
+            "Personal organizations can't have other members".to_string(),
+        ));
+    }
+
+    let role = req.role.as_deref().unwrap_or(ROLE_MEMBER);
+    validate_role(role)?;
+
+    let username = req.username.trim().to_lowercase();
+    let user = find_user_by_username(&state, &username)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("User '{username}' not found")))?;
+
+    if state
+        .storage
+        .get_org_member(&org_id, &user.id)
+        .await?
+        .is_some()
+    {
+        return Err(AppError::Conflict(format!(
+            "'{username}' is already a member of this organization"
+        )));
+    }
+
+    let member = OrgMember {
+        org_id,
+        user_id: user.id,
+        role: role.to_string(),
+        created_at: Utc::now(),
+    };
+    state.storage.add_org_member(&member).await?;
+
+    Ok(Json(OrgMemberResponse {
+        user_id: member.user_id,
+        username: user.username,
+        role: member.role,
+        created_at: member.created_at,
+    }))
+}
+
+/// PATCH /v1/orgs/:org_id/members/:user_id - Change a member's role (owners only)
+pub async fn update_member(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((org_id, user_id)): Path<(String, String)>,
+    Json(req): Json<UpdateOrgMemberRequest>,
+) -> Result<Json<OrgMemberResponse>> {
+    require_owner(&auth, &state, &org_id).await?;
+    validate_role(&req.role)?;
+
+    let (member, username) = find_member(&state, &org_id, &user_id).await?;
+    if !state
+        .storage
+        .set_org_member_role(&org_id, &user_id, &req.role)
+        .await?
+    {
+        return Err(last_owner());
+    }
+
+    Ok(Json(OrgMemberResponse {
+        user_id: member.user_id,
+        username,
+        role: req.role,
+        created_at: member.created_at,
+    }))
+}
+
+/// DELETE /v1/orgs/:org_id/members/:user_id - Remove a member (owners only)
+///
+/// They lose the org's projects, and API keys they limited to the org stop
+/// working.
+pub async fn remove_member(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((org_id, user_id)): Path<(String, String)>,
+) -> Result<Json<OrgMemberResponse>> {
+    require_owner(&auth, &state, &org_id).await?;
+
+    let (member, username) = find_member(&state, &org_id, &user_id).await?;
+    if !state.storage.remove_org_member(&org_id, &user_id).await? {
+        return Err(last_owner());
+    }
+
+    Ok(Json(OrgMemberResponse {
+        user_id: member.user_id,
+        username,
+        role: member.role,
+        created_at: member.created_at,
+    }))
+}
+
+/// POST /v1/orgs/:org_id/api-keys - Create a user API key limited to this org (owners only)
+pub async fn create_api_key(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(org_id): Path<String>,
+    Json(req): Json<CreateOrgApiKeyRequest>,
+) -> Result<Json<ApiKeyCreatedResponse>> {
+    require_owner(&auth, &state, &org_id).await?;
+
+    Ok(Json(
+        issue_api_key(&state, &auth, req.name, req.scope.as_deref(), Some(org_id)).await?,
+    ))
+}
@@ -0,0 +1,98 @@
+//! Keyless evaluation through public environments
+//! Pages that can't keep a secret, such as a marketing site, evaluate the
+//! flags a project made public with the environment's public client ID.
+//! Other flags are answered as if they didn't exist.
+
+use axum::{
+    extract::{ConnectInfo, Path, Query, State},
+    http::{HeaderMap, HeaderValue},
+    response::IntoResponse,
+    Json,
+};
+use chrono::Utc;
+use std::net::SocketAddr;
+
+use crate::error::{AppError, Result};
+use crate::events::EvaluationReason;
+use crate::handlers::flags::{cache_control, cache_headers, evaluate_in, evaluation_cache_control};
+use crate::lockout;
+use crate::models::{AppState, Environment, EvaluateFlagQuery};
+
+/// The public environment with `client_id`, once the request is within the
+/// public rate limits
+async fn public_environment(
+    state: &AppState,
+    client_id: &str,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: &HeaderMap,
+) -> Result<Environment> {
+    let ip = lockout::client_ip(peer.map(|c| c.0), headers, state.trust_proxy);
+    state.public.check(client_id, ip, Utc::now())?;
+
+    state
+        .storage
+        .get_environment_by_public_client_id(client_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Unknown public client ID".to_string()))
+}
+
+/// GET /v1/public/:client_id/flags/:key - Evaluate a public flag
+pub async fn evaluate_flag(
+    State(state): State<AppState>,
+    Path((client_id, key)): Path<(String, String)>,
+    Query(query): Query<EvaluateFlagQuery>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse> {
+    let environment = public_environment(&state, &client_id, peer, &headers).await?;
+
+    // No suggestions: they'd name private flags
+    let flag = state
+        .storage
+        .get_flag_by_key(&environment.project_id, &key)
+        .await?
+        .filter(|f| f.public)
+        .ok_or_else(|| AppError::NotFound(format!("Flag '{key}' not found")))?;
+
+    let (evaluation, reason) =
+        evaluate_in(&state, &environment, flag, query.user_id.as_deref()).await?;
+    Ok((
+        cache_headers(evaluation_cache_control(&environment, None, reason)),
+        Json(evaluation),
+    ))
+}
+
+/// GET /v1/public/:client_id/flags - Evaluate every public flag
+pub async fn evaluate_flags(
+    State(state): State<AppState>,
+    Path(client_id): Path<String>,
+    Query(query): Query<EvaluateFlagQuery>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse> {
+    let environment = public_environment(&state, &client_id, peer, &headers).await?;
+
+    let flags = state
+        .storage
+        .list_flags_by_project(&environment.project_id)
+        .await?;
+
+    let mut evaluations = Vec::new();
+    let mut random = false;
+    for flag in flags.into_iter().filter(|f| f.public) {
+        let (evaluation, reason) =
+            evaluate_in(&state, &environment, flag, query.user_id.as_deref()).await?;
+        random |= matches!(reason, EvaluationReason::Random);
+        evaluations.push(evaluation);
+    }
+
+    // One coin flip makes the whole answer not worth reusing
+    let cache = cache_control(&environment, None).map(|value| {
+        if random {
+            HeaderValue::from_static("no-store")
+        } else {
+            value
+        }
+    });
+    Ok((cache_headers(cache), Json(evaluations)))
+}
@@ -0,0 +1,452 @@
+//! Ramp handlers
+//! A ramp raises a flag's rollout in one environment step by step; see
+//! [`crate::ramps`] for how the steps are taken.
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::auth::AuthUser;
+use crate::bus;
+use crate::error::{AppError, Result};
+use crate::handlers::change_requests::Usernames;
+use crate::handlers::cli::{because, change_reason, direct_change, find_flag};
+use crate::handlers::guards::{self, GuardResponse, GuardSpec};
+use crate::models::{
+    AppState, Project, Ramp, RAMP_ABORTED, RAMP_ACTIVE, RAMP_COMPLETED, RAMP_PAUSED,
+};
+use crate::ramps::{self, MAX_INTERVAL_SECS, MIN_INTERVAL_SECS};
+use crate::validation::Validator;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRampRequest {
+    pub environment: String,
+    /// Rollout the ramp ends at
+    pub target_percentage: i32,
+    /// Percentage points added each step
+    pub step_percentage: i32,
+    /// Seconds between steps
+    pub interval_secs: i64,
+    /// Acknowledge that the target environment is protected
+    #[serde(default)]
+    pub confirm: bool,
+    /// Why the flag is ramped, kept in its history with each step
+    pub reason: Option<String>,
+    /// Revert to the rollout before the ramp, and abort it, if this guard
+    /// hears the ramp is unhealthy
+    pub guard: Option<GuardSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListRampsQuery {
+    /// `active`, `paused`, `completed` or `aborted`; all when unset
+    pub status: Option<String>,
+}
+
+/// A ramp, with the flag key, environment name and creator's username in
+/// place of ids
+#[derive(Debug, Serialize)]
+pub struct RampResponse {
+    pub id: String,
+    pub project_id: String,
+    pub flag_key: String,
+    pub environment: String,
+    pub target_percentage: i32,
+    pub step_percentage: i32,
+    pub interval_secs: i64,
+    pub current_percentage: i32,
+    pub status: String,
+    pub next_step_at: Option<DateTime<Utc>>,
+    pub reason: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// The ramp's guard; only returned when the ramp is created
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guard: Option<GuardResponse>,
+}
+
+/// Builds responses for a project's ramps and guards, looking each name up once
+pub struct Responses {
+    usernames: Usernames,
+    flag_keys: HashMap<String, String>,
+    environments: HashMap<String, String>,
+}
+
+impl Responses {
+    pub async fn for_project(state: &AppState, project_id: &str) -> Result<Self> {
+        let flag_keys = state
+            .storage
+            .list_flags_by_project(project_id)
+            .await?
+            .into_iter()
+            .map(|f| (f.id, f.key))
+            .collect();
+        let environments = state
+            .storage
+            .list_environments_by_project(project_id)
+            .await?
+            .into_iter()
+            .map(|e| (e.id, e.name))
+            .collect();
+        Ok(Self {
+            usernames: Usernames::default(),
+            flag_keys,
+            environments,
+        })
+    }
+
+    /// Flag key, environment name and username for the ids
+    pub async fn names(
+        &mut self,
+        state: &AppState,
+        flag_id: &String,
+        environment_id: &String,
+        user_id: &str,
+    ) -> Result<(String, String, String)> {
+        let name = |names: &HashMap<String, String>, id: &String| {
+            names.get(id).cloned().unwrap_or_else(|| id.clone())
+        };
+        Ok((
+            name(&self.flag_keys, flag_id),
+            name(&self.environments, environment_id),
+            self.usernames.get(state, user_id).await?,
+        ))
+    }
+
+    async fn response(&mut self, state: &AppState, ramp: Ramp) -> Result<RampResponse> {
+        let (flag_key, environment, created_by) = self
+            .names(state, &ramp.flag_id, &ramp.environment_id, &ramp.created_by)
+            .await?;
+        Ok(RampResponse {
+            flag_key,
+            environment,
+            id: ramp.id,
+            project_id: ramp.project_id,
+            target_percentage: ramp.target_percentage,
+            step_percentage: ramp.step_percentage,
+            interval_secs: ramp.interval_secs,
+            current_percentage: ramp.current_percentage,
+            status: ramp.status,
+            next_step_at: ramp.next_step_at,
+            reason: ramp.reason,
+            created_by,
+            created_at: ramp.created_at,
+            updated_at: ramp.updated_at,
+            guard: None,
+        })
+    }
+}
+
+async fn response(state: &AppState, ramp: Ramp) -> Result<Json<RampResponse>> {
+    let mut responses = Responses::for_project(state, &ramp.project_id).await?;
+    Ok(Json(responses.response(state, ramp).await?))
+}
+
+/// The ramp, if the caller can reach its project
+async fn find_ramp(state: &AppState, auth: &AuthUser, id: &str) -> Result<(Ramp, Project)> {
+    let not_found = || AppError::NotFound("Ramp not found".to_string());
+    let ramp = state.storage.get_ramp(id).await?.ok_or_else(not_found)?;
+    let project = auth
+        .project(state, &ramp.project_id)
+        .await
+        .map_err(|_| not_found())?;
+    Ok((ramp, project))
+}
+
+/// POST /projects/:project_id/flags/:key/ramps - Raise a flag's rollout step by step
+///
+/// Turns the flag on and takes the first step right away, starting from the
+/// rollout it has now (0% if it's off).
+pub async fn create_ramp(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+    Json(req): Json<CreateRampRequest>,
+) -> Result<Json<RampResponse>> {
+    let project = auth.project(&state, &project_id).await?;
+
+    let mut validator = Validator::new();
+    validator.rollout("target_percentage", req.target_percentage);
+    if !(1..=100).contains(&req.step_percentage) {
+        validator.fail(
+            "step_percentage",
+            "range",
+            "Step must be between 1 and 100 percentage points",
+        );
+    }
+    if !(MIN_INTERVAL_SECS..=MAX_INTERVAL_SECS).contains(&req.interval_secs) {
+        validator.fail(
+            "interval_secs",
+            "range",
+            format!("Interval must be between {MIN_INTERVAL_SECS} seconds and 7 days"),
+        );
+    }
+    if let Some(guard) = &req.guard {
+        guards::validate(&mut validator, guard, &state.health_checks);
+    }
+    validator.finish()?;
+
+    let flag = find_flag(&state, &project.id, &key).await?;
+    let environment = state
+        .storage
+        .get_environment_by_name(&project.id, &req.environment)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Environment '{}' not found", req.environment))
+        })?;
+    if environment.frozen {
+        return Err(AppError::EnvironmentFrozen(req.environment));
+    }
+    if environment.protected && !req.confirm {
+        return Err(AppError::ProtectedEnvironment(req.environment));
+    }
+    direct_change(&environment)?;
+    let reason = change_reason(&project, &environment, req.reason)?;
+
+    if let Some(running) = state
+        .storage
+        .list_ramps(&project.id)
+        .await?
+        .into_iter()
+        .find(|r| {
+            r.flag_id == flag.id && r.environment_id == environment.id && ramps::is_running(r)
+        })
+    {
+        return Err(AppError::Conflict(format!(
+            "Flag '{}' is already being ramped in {} (ramp {}); abort that ramp first",
+            flag.key, environment.name, running.id
+        )));
+    }
+
+    let existing = state
+        .storage
+        .get_flag_value(&flag.id, &environment.id)
+        .await?;
+    let start = ramps::current_percentage(existing.as_ref());
+    if start >= req.target_percentage {
+        let mut validator = Validator::new();
+        validator.fail(
+            "target_percentage",
+            "range",
+            format!(
+                "'{}' is already rolled out to {start}% in {}",
+                flag.key, environment.name
+            ),
+        );
+        validator.finish()?;
+    }
+
+    let revert = guards::revert_to(&flag, existing.as_ref());
+    let version = existing.as_ref().map_or(0, |fv| fv.version);
+    let now = Utc::now();
+    let percentage = ramps::next_percentage(start, req.step_percentage, req.target_percentage);
+    let mut ramp = Ramp {
+        id: Uuid::new_v4().to_string(),
+        project_id: project.id.clone(),
+        flag_id: flag.id.clone(),
+        environment_id: environment.id.clone(),
+        target_percentage: req.target_percentage,
+        step_percentage: req.step_percentage,
+        interval_secs: req.interval_secs,
+        current_percentage: percentage,
+        status: RAMP_ACTIVE.to_string(),
+        next_step_at: None,
+        reason: reason.clone(),
+        created_by: auth.user.id.clone(),
+        created_at: now,
+        updated_at: now,
+    };
+    ramp.next_step_at = ramps::next_step_at(&ramp, percentage, now);
+    if ramp.next_step_at.is_none() {
+        ramp.status = RAMP_COMPLETED.to_string();
+    }
+
+    if !ramps::set_rollout(
+        &state,
+        &flag,
+        &environment,
+        existing,
+        percentage,
+        reason.clone(),
+        now,
+    )
+    .await?
+    {
+        return Err(AppError::flag_value_conflict(&flag.key));
+    }
+    state.storage.create_ramp(&ramp).await?;
+    bus::audit(
+        &state,
+        &project.id,
+        "ramp.created",
+        &auth.user.username,
+        format!(
+            "Ramp {} in project {}: {} ramps '{}' in {} from {start}% to {}% by {} points every {}s, now at {percentage}%{}",
+            ramp.id,
+            project.id,
+            auth.user.username,
+            flag.key,
+            environment.name,
+            ramp.target_percentage,
+            ramp.step_percentage,
+            ramp.interval_secs,
+            because(&reason)
+        ),
+    );
+
+    let ramp_id = ramp.id.clone();
+    let mut responses = Responses::for_project(&state, &project.id).await?;
+    let mut response = responses.response(&state, ramp).await?;
+    if let Some(spec) = req.guard {
+        let guard = guards::watch(
+            &state,
+            &auth,
+            &flag,
+            &environment,
+            Some(ramp_id),
+            version,
+            revert,
+            spec,
+        )
+        .await?;
+        response.guard = Some(guard);
+    }
+    Ok(Json(response))
+}
+
+/// GET /projects/:project_id/ramps - A project's ramps, newest first
+pub async fn list_ramps(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+    Query(query): Query<ListRampsQuery>,
+) -> Result<Json<Vec<RampResponse>>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    if let Some(status) = &query.status {
+        if ![RAMP_ACTIVE, RAMP_PAUSED, RAMP_COMPLETED, RAMP_ABORTED].contains(&status.as_str()) {
+            let mut validator = Validator::new();
+            validator.fail(
+                "status",
+                "one_of",
+                "Status must be active, paused, completed or aborted",
+            );
+            validator.finish()?;
+        }
+    }
+
+    let ramps = state.storage.list_ramps(&project_id).await?;
+    let mut responses = Responses::for_project(&state, &project_id).await?;
+    let mut result = Vec::with_capacity(ramps.len());
+    for ramp in ramps {
+        if query.status.as_ref().is_none_or(|s| *s == ramp.status) {
+            result.push(responses.response(&state, ramp).await?);
+        }
+    }
+    Ok(Json(result))
+}
+
+/// GET /ramps/:id - One ramp and how far it has got
+pub async fn get_ramp(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<RampResponse>> {
+    let (ramp, _) = find_ramp(&state, &auth, &id).await?;
+    response(&state, ramp).await
+}
+
+/// Move a ramp from `from` to `to`, or fail if it isn't in `from`; `action`
+/// names the move in the audit log
+async fn transition(
+    state: &AppState,
+    auth: &AuthUser,
+    id: &str,
+    from: &[&str],
+    to: &str,
+    action: &str,
+) -> Result<Ramp> {
+    let (mut ramp, project) = find_ramp(state, auth, id).await?;
+    let status = ramp.status.clone();
+    if !from.contains(&status.as_str()) {
+        return Err(AppError::Conflict(format!("Ramp is {status}")));
+    }
+
+    let now = Utc::now();
+    ramp.status = to.to_string();
+    ramp.next_step_at = None;
+    ramp.updated_at = now;
+    if to == RAMP_ACTIVE {
+        // Carry on from wherever the flag is now, a full interval from now
+        let existing = state
+            .storage
+            .get_flag_value(&ramp.flag_id, &ramp.environment_id)
+            .await?;
+        ramp.current_percentage = ramps::current_percentage(existing.as_ref());
+        ramp.next_step_at = ramps::next_step_at(&ramp, ramp.current_percentage, now);
+        if ramp.next_step_at.is_none() {
+            ramp.status = RAMP_COMPLETED.to_string();
+        }
+    }
+    if !state.storage.update_ramp(&ramp, &status).await? {
+        return Err(AppError::Conflict(
+            "Ramp was changed by another request; reload and try again".to_string(),
+        ));
+    }
+
+    bus::audit(
+        state,
+        &project.id,
+        &format!("ramp.{action}"),
+        &auth.user.username,
+        format!(
+            "Ramp {} in project {} {action} by {} at {}%",
+            ramp.id, project.id, auth.user.username, ramp.current_percentage
+        ),
+    );
+    Ok(ramp)
+}
+
+/// POST /ramps/:id/pause - Stop taking steps, leaving the rollout as it is
+pub async fn pause_ramp(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<RampResponse>> {
+    let ramp = transition(&state, &auth, &id, &[RAMP_ACTIVE], RAMP_PAUSED, "paused").await?;
+    response(&state, ramp).await
+}
+
+/// POST /ramps/:id/resume - Carry on a paused ramp from the flag's current rollout
+pub async fn resume_ramp(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<RampResponse>> {
+    let ramp = transition(&state, &auth, &id, &[RAMP_PAUSED], RAMP_ACTIVE, "resumed").await?;
+    response(&state, ramp).await
+}
+
+/// POST /ramps/:id/abort - End a ramp for good, leaving the rollout where it got to
+pub async fn abort_ramp(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<RampResponse>> {
+    let ramp = transition(
+        &state,
+        &auth,
+        &id,
+        &[RAMP_ACTIVE, RAMP_PAUSED],
+        RAMP_ABORTED,
+        "aborted",
+    )
+    .await?;
+    response(&state, ramp).await
+}
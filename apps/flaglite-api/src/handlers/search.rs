@@ -0,0 +1,99 @@
+//! Flag search across projects
+//! Finds flags by key or name in every project the caller can access, for
+//! people who remember a flag but not which service it belongs to.
+
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::auth::AuthUser;
+use crate::error::{AppError, Result};
+use crate::handlers::cli::{environment_values, FlagEnvironmentValue};
+use crate::models::AppState;
+
+/// Most flags one search returns
+const MAX_RESULTS: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    /// Part of a flag key or name, matched case-insensitively
+    pub q: String,
+    /// Only this organization's projects
+    pub org_id: Option<String>,
+}
+
+/// A flag found by search, with its project and state in every environment
+#[derive(Debug, Serialize)]
+pub struct FlagSearchResult {
+    pub project_id: String,
+    pub project_slug: String,
+    pub project_name: String,
+    pub key: String,
+    pub name: String,
+    pub environments: HashMap<String, FlagEnvironmentValue>,
+}
+
+/// GET /v1/search - Find flags by key or name across projects
+pub async fn search_flags(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Query(query): Query<SearchQuery>,
+) -> Result<Json<Vec<FlagSearchResult>>> {
+    let needle = query.q.trim().to_lowercase();
+    if needle.is_empty() {
+        return Err(AppError::BadRequest(
+            "q must name part of a flag key or name".to_string(),
+        ));
+    }
+
+    let mut matches = Vec::new();
+    for project in auth.projects(&state, query.org_id.as_deref()).await? {
+        let flags = state.storage.list_flags_by_project(&project.id).await?;
+        matches.extend(
+            flags
+                .into_iter()
+                .filter(|f| {
+                    f.key.to_lowercase().contains(&needle)
+                        || f.name.to_lowercase().contains(&needle)
+                })
+                .map(|f| (project.clone(), f)),
+        );
+    }
+
+    // Exact keys first, then keys starting with the query, then the rest
+    matches.sort_by_cached_key(|(project, flag)| {
+        let key = flag.key.to_lowercase();
+        (
+            key != needle,
+            !key.starts_with(&needle),
+            flag.key.clone(),
+            project.slug.clone(),
+        )
+    });
+    matches.truncate(MAX_RESULTS);
+
+    let mut environments = HashMap::new();
+    let mut results = Vec::new();
+    for (project, flag) in matches {
+        if !environments.contains_key(&project.id) {
+            let list = state
+                .storage
+                .list_environments_by_project(&project.id)
+                .await?;
+            environments.insert(project.id.clone(), list);
+        }
+        results.push(FlagSearchResult {
+            environments: environment_values(&state, &flag, &environments[&project.id]).await?,
+            project_id: project.id,
+            project_slug: project.slug,
+            project_name: project.name,
+            key: flag.key,
+            name: flag.name,
+        });
+    }
+
+    Ok(Json(results))
+}
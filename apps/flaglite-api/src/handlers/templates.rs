@@ -0,0 +1,170 @@
+//! Flag template handlers
+//! Templates set the state new flags start in per environment (see templates.rs).
+
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::auth::AuthUser;
+use crate::error::{AppError, Result};
+use crate::handlers::cli::{insert_flag, CliFlag, CreateFlagRequest};
+use crate::models::{AppState, FlagTemplate};
+use crate::slug;
+use crate::templates::{self, Template, TemplateState};
+use crate::validation::{Validator, MAX_NAME_LENGTH, MAX_REFERENCE_LENGTH};
+
+#[derive(Debug, Serialize)]
+pub struct TemplateResponse {
+    pub name: String,
+    pub description: Option<String>,
+    /// State in environments `environments` doesn't name
+    pub default: TemplateState,
+    pub environments: BTreeMap<String, TemplateState>,
+    pub builtin: bool,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<Template> for TemplateResponse {
+    fn from(t: Template) -> Self {
+        TemplateResponse {
+            name: t.name,
+            description: t.description,
+            default: t.default,
+            environments: t.environments,
+            builtin: t.builtin,
+            updated_at: t.updated_at,
+        }
+    }
+}
+
+/// Omitted states are off at 100%
+#[derive(Debug, Deserialize)]
+pub struct SetTemplateRequest {
+    pub description: Option<String>,
+    #[serde(default)]
+    pub default: TemplateState,
+    #[serde(default)]
+    pub environments: BTreeMap<String, TemplateState>,
+}
+
+/// The project's template called `name`, stored or built in
+async fn find_template(state: &AppState, project_id: &str, name: &str) -> Result<Template> {
+    if let Some(stored) = state.storage.get_flag_template(project_id, name).await? {
+        return Ok(stored.into());
+    }
+    templates::builtin()
+        .into_iter()
+        .find(|t| t.name == name)
+        .ok_or_else(|| AppError::NotFound(format!("Template '{name}' not found")))
+}
+
+/// GET /projects/:project_id/templates - Built-in and stored templates
+pub async fn list_templates(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(project_id): Path<String>,
+) -> Result<Json<Vec<TemplateResponse>>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+    let stored = state.storage.list_flag_templates(&project_id).await?;
+    Ok(Json(
+        templates::merge(stored)
+            .into_iter()
+            .map(TemplateResponse::from)
+            .collect(),
+    ))
+}
+
+/// PUT /projects/:project_id/templates/:name - Create or replace a template
+pub async fn set_template(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, name)): Path<(String, String)>,
+    Json(req): Json<SetTemplateRequest>,
+) -> Result<Json<TemplateResponse>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    let mut validator = Validator::new();
+    validator
+        .name("name", "Template name", &name, MAX_NAME_LENGTH)
+        .max_length(
+            "description",
+            "Description",
+            req.description.as_deref().unwrap_or(""),
+            MAX_REFERENCE_LENGTH,
+        )
+        .rollout("default.rollout", req.default.rollout);
+    if slug::slugify(&name) != name {
+        validator.fail(
+            "name",
+            "charset",
+            "Template name can only contain lowercase letters, numbers, and hyphens",
+        );
+    }
+    for (env, env_state) in &req.environments {
+        validator.rollout(&format!("environments.{env}.rollout"), env_state.rollout);
+    }
+    validator.finish()?;
+
+    let template = FlagTemplate {
+        project_id,
+        name,
+        description: req.description.filter(|d| !d.trim().is_empty()),
+        default_enabled: req.default.enabled,
+        default_rollout: req.default.rollout,
+        environments: serde_json::to_string(&req.environments)
+            .map_err(|e| AppError::Internal(e.to_string()))?,
+        updated_at: Utc::now(),
+    };
+    state.storage.set_flag_template(&template).await?;
+
+    Ok(Json(Template::from(template).into()))
+}
+
+/// DELETE /projects/:project_id/templates/:name - Delete a stored template
+pub async fn delete_template(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, name)): Path<(String, String)>,
+) -> Result<()> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+
+    if state
+        .storage
+        .delete_flag_template(&project_id, &name)
+        .await?
+    {
+        return Ok(());
+    }
+    if templates::builtin().iter().any(|t| t.name == name) {
+        return Err(AppError::BadRequest(format!(
+            "'{name}' is a built-in template; replace it with PUT to change it"
+        )));
+    }
+    Err(AppError::NotFound(format!("Template '{name}' not found")))
+}
+
+/// POST /projects/:project_id/templates/:name/flags - Create a flag in the
+/// state the template gives each environment
+pub async fn create_flag_from_template(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, name)): Path<(String, String)>,
+    Json(mut req): Json<CreateFlagRequest>,
+) -> Result<Json<CliFlag>> {
+    let project_id = auth.project(&state, &project_id).await?.id;
+    let template = find_template(&state, &project_id, &name).await?;
+
+    // Environments created later start in the template's default state too
+    req.default_enabled = template.default.enabled;
+
+    let flag = insert_flag(&state, project_id, req, |env| {
+        let s = template.state_for(env);
+        (s.enabled, s.rollout)
+    })
+    .await?;
+    Ok(Json(flag))
+}
@@ -0,0 +1,300 @@
+//! Flag allowlist and denylist handlers
+//! While a flag is on in an environment, users on its allowlist there get it
+//! whatever their rollout bucket and users on its denylist never do; see
+//! [`crate::handlers::flags::evaluate`].
+
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+use crate::auth::AuthUser;
+use crate::bus;
+use crate::error::{AppError, Result};
+use crate::handlers::cli::{
+    because, change_reason, direct_change, find_flag, record_changes, FlagQuery,
+};
+use crate::models::{AppState, Environment, ListedUser, LIST_ALLOW, LIST_DENY};
+use crate::validation::Validator;
+
+/// Most users on one list of a flag in one environment
+pub const MAX_LISTED_USERS: usize = 10_000;
+pub const MAX_USER_ID_LENGTH: usize = 256;
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateUserListRequest {
+    pub environment: String,
+    /// Users to put on the list; users on the other list move to this one
+    #[serde(default)]
+    pub add: Vec<String>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+    /// Empty the list before adding
+    #[serde(default)]
+    pub clear: bool,
+    /// Acknowledge that the environment is protected
+    #[serde(default)]
+    pub confirm: bool,
+    pub reason: Option<String>,
+}
+
+/// A flag's allowlist or denylist in one environment
+#[derive(Debug, Serialize)]
+pub struct UserListResponse {
+    pub key: String,
+    pub environment: String,
+    /// `allowlist` or `denylist`
+    pub list: &'static str,
+    /// Sorted
+    pub user_ids: Vec<String>,
+    /// Users the change put on the list; only returned by updates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub added: Option<usize>,
+    /// Users the change took off the list; only returned by updates
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub removed: Option<usize>,
+}
+
+fn list_name(list: &str) -> &'static str {
+    if list == LIST_ALLOW {
+        "allowlist"
+    } else {
+        "denylist"
+    }
+}
+
+async fn environment(state: &AppState, project_id: &str, name: &str) -> Result<Environment> {
+    state
+        .storage
+        .get_environment_by_name(project_id, name)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Environment '{name}' not found")))
+}
+
+/// The users on `list` for the flag in the environment, sorted
+async fn user_ids(
+    state: &AppState,
+    flag_id: &str,
+    environment_id: &str,
+    list: &str,
+) -> Result<Vec<String>> {
+    let mut user_ids: Vec<String> = state
+        .storage
+        .list_listed_users(flag_id, environment_id)
+        .await?
+        .into_iter()
+        .filter(|u| u.list == list)
+        .map(|u| u.user_id)
+        .collect();
+    user_ids.sort();
+    Ok(user_ids)
+}
+
+async fn get_list(
+    state: AppState,
+    auth: AuthUser,
+    project_id: String,
+    key: String,
+    query: FlagQuery,
+    list: &'static str,
+) -> Result<Json<UserListResponse>> {
+    let project = auth.project(&state, &project_id).await?;
+    let flag = find_flag(&state, &project.id, &key).await?;
+    let env_name = query.environment.as_deref().unwrap_or("development");
+    let environment = environment(&state, &project.id, env_name).await?;
+
+    Ok(Json(UserListResponse {
+        user_ids: user_ids(&state, &flag.id, &environment.id, list).await?,
+        key: flag.key,
+        environment: environment.name,
+        list: list_name(list),
+        added: None,
+        removed: None,
+    }))
+}
+
+async fn update_list(
+    state: AppState,
+    auth: AuthUser,
+    project_id: String,
+    key: String,
+    req: UpdateUserListRequest,
+    list: &'static str,
+) -> Result<Json<UserListResponse>> {
+    let project = auth.project(&state, &project_id).await?;
+
+    let trimmed = |ids: Vec<String>| -> BTreeSet<String> {
+        ids.into_iter().map(|id| id.trim().to_string()).collect()
+    };
+    let add = trimmed(req.add);
+    let remove = trimmed(req.remove);
+    let mut validator = Validator::new();
+    if add.is_empty() && remove.is_empty() && !req.clear {
+        validator.fail(
+            "add",
+            "required",
+            "Give users to add or remove, or clear the list",
+        );
+    }
+    for (field, ids) in [("add", &add), ("remove", &remove)] {
+        if ids.iter().any(|id| id.is_empty()) {
+            validator.fail(field, "required", "User IDs cannot be empty");
+        }
+        if ids.iter().any(|id| id.len() > MAX_USER_ID_LENGTH) {
+            validator.fail(
+                field,
+                "max_length",
+                format!("User IDs must be at most {MAX_USER_ID_LENGTH} characters"),
+            );
+        }
+    }
+    if let Some(id) = add.intersection(&remove).next() {
+        validator.fail(
+            "remove",
+            "conflict",
+            format!("'{id}' can't be both added and removed"),
+        );
+    }
+    validator.finish()?;
+
+    let flag = find_flag(&state, &project.id, &key).await?;
+    let environment = environment(&state, &project.id, &req.environment).await?;
+    if environment.frozen {
+        return Err(AppError::EnvironmentFrozen(req.environment));
+    }
+    if environment.protected && !req.confirm {
+        return Err(AppError::ProtectedEnvironment(req.environment));
+    }
+    direct_change(&environment)?;
+    let reason = change_reason(&project, &environment, req.reason)?;
+
+    let current: BTreeSet<String> = user_ids(&state, &flag.id, &environment.id, list)
+        .await?
+        .into_iter()
+        .collect();
+    let kept = if req.clear {
+        0
+    } else {
+        current.iter().filter(|id| !remove.contains(*id)).count()
+    };
+    let new: Vec<&String> = add
+        .iter()
+        .filter(|id| req.clear || !current.contains(*id))
+        .collect();
+    if kept + new.len() > MAX_LISTED_USERS {
+        let mut validator = Validator::new();
+        validator.fail(
+            "add",
+            "max_length",
+            format!(
+                "A {} can hold at most {MAX_LISTED_USERS} users",
+                list_name(list)
+            ),
+        );
+        validator.finish()?;
+    }
+
+    let removed = if req.clear {
+        state
+            .storage
+            .clear_listed_users(&flag.id, &environment.id, list)
+            .await?
+    } else {
+        let remove: Vec<String> = remove.into_iter().collect();
+        state
+            .storage
+            .remove_listed_users(&flag.id, &environment.id, list, &remove)
+            .await?
+    } as usize;
+    let now = Utc::now();
+    let listed: Vec<ListedUser> = new
+        .iter()
+        .map(|id| ListedUser {
+            flag_id: flag.id.clone(),
+            environment_id: environment.id.clone(),
+            user_id: id.to_string(),
+            list: list.to_string(),
+            created_at: now,
+        })
+        .collect();
+    state.storage.add_listed_users(&listed).await?;
+
+    // Re-adding users a clear just removed doesn't change the list for them
+    let (added, removed) = if req.clear {
+        let readded = new.iter().filter(|id| current.contains(**id)).count();
+        (new.len() - readded, removed - readded)
+    } else {
+        (new.len(), removed)
+    };
+    if added > 0 || removed > 0 {
+        record_changes(&state, &project.id, std::slice::from_ref(&flag.key), false).await?;
+        bus::audit(
+            &state,
+            &project.id,
+            &format!("flag.{}_changed", list_name(list)),
+            &auth.user.username,
+            format!(
+                "Flag {} in {}: {} added {added} and removed {removed} user(s) on the {}{}",
+                flag.key,
+                environment.name,
+                auth.user.username,
+                list_name(list),
+                because(&reason)
+            ),
+        );
+    }
+
+    Ok(Json(UserListResponse {
+        user_ids: user_ids(&state, &flag.id, &environment.id, list).await?,
+        key: flag.key,
+        environment: environment.name,
+        list: list_name(list),
+        added: Some(added),
+        removed: Some(removed),
+    }))
+}
+
+/// GET /projects/:project_id/flags/:key/allowlist - Users who always get the
+/// flag while it's on in the environment
+pub async fn get_allowlist(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+    Query(query): Query<FlagQuery>,
+) -> Result<Json<UserListResponse>> {
+    get_list(state, auth, project_id, key, query, LIST_ALLOW).await
+}
+
+/// PATCH /projects/:project_id/flags/:key/allowlist - Add and remove users
+pub async fn update_allowlist(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+    Json(req): Json<UpdateUserListRequest>,
+) -> Result<Json<UserListResponse>> {
+    update_list(state, auth, project_id, key, req, LIST_ALLOW).await
+}
+
+/// GET /projects/:project_id/flags/:key/denylist - Users who never get the
+/// flag in the environment
+pub async fn get_denylist(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+    Query(query): Query<FlagQuery>,
+) -> Result<Json<UserListResponse>> {
+    get_list(state, auth, project_id, key, query, LIST_DENY).await
+}
+
+/// PATCH /projects/:project_id/flags/:key/denylist - Add and remove users
+pub async fn update_denylist(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path((project_id, key)): Path<(String, String)>,
+    Json(req): Json<UpdateUserListRequest>,
+) -> Result<Json<UserListResponse>> {
+    update_list(state, auth, project_id, key, req, LIST_DENY).await
+}
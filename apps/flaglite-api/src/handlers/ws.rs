@@ -0,0 +1,188 @@
+//! WebSocket API
+//! A connection subscribes to projects and receives their flag changes and
+//! audit events as JSON text messages, for dashboards that update live.
+
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    http::{header::AUTHORIZATION, HeaderMap},
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{Instant, MissedTickBehavior};
+
+use crate::auth::AuthUser;
+use crate::error::{AppError, Result};
+use crate::models::AppState;
+
+/// How often the server pings an idle connection
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A connection that sends nothing, not even a pong, for this long is closed
+const IDLE_TIMEOUT: Duration = Duration::from_secs(90);
+
+#[derive(Debug, Deserialize)]
+pub struct ConnectQuery {
+    /// JWT or user API key, for clients (browsers) that can't set headers on
+    /// the handshake
+    pub token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// Project ID or slug
+    Subscribe {
+        project_id: String,
+    },
+    Unsubscribe {
+        project_id: String,
+    },
+    Ping,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage {
+    Subscribed {
+        project_id: String,
+    },
+    Unsubscribed {
+        project_id: String,
+    },
+    Pong,
+    /// The connection fell behind and `missed` events were dropped; refetch
+    /// the subscribed projects' state
+    Lagged {
+        missed: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// GET /v1/ws - Upgrade to a WebSocket for live project events
+pub async fn connect(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(query): Query<ConnectQuery>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response> {
+    let header = headers
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    let token = header
+        .or(query.token.as_deref())
+        .ok_or(AppError::Unauthorized)?;
+    let auth = AuthUser::from_token(&state, token).await?;
+
+    Ok(upgrade.on_upgrade(move |socket| session(socket, state, auth)))
+}
+
+async fn session(mut socket: WebSocket, state: AppState, auth: AuthUser) {
+    let mut events = state.bus.subscribe();
+    let mut projects: HashSet<String> = HashSet::new();
+
+    let mut keepalive = tokio::time::interval_at(Instant::now() + PING_INTERVAL, PING_INTERVAL);
+    keepalive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut last_seen = Instant::now();
+
+    loop {
+        let reply = tokio::select! {
+            message = socket.recv() => {
+                last_seen = Instant::now();
+                match message {
+                    Some(Ok(Message::Text(text))) => {
+                        Some(handle(&state, &auth, &mut projects, &text).await)
+                    }
+                    Some(Ok(Message::Binary(_))) => Some(ServerMessage::Error {
+                        message: "Messages must be JSON text".to_string(),
+                    }),
+                    // Pings are answered by the WebSocket layer itself
+                    Some(Ok(Message::Ping(_) | Message::Pong(_))) => None,
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                }
+            }
+            event = events.recv() => match event {
+                Ok(event) if projects.contains(event.project_id()) => {
+                    if send(&mut socket, &event).await.is_err() {
+                        break;
+                    }
+                    None
+                }
+                Ok(_) => None,
+                Err(RecvError::Lagged(missed)) if !projects.is_empty() => {
+                    Some(ServerMessage::Lagged { missed })
+                }
+                Err(RecvError::Lagged(_)) => None,
+                Err(RecvError::Closed) => break,
+            },
+            _ = keepalive.tick() => {
+                if last_seen.elapsed() > IDLE_TIMEOUT {
+                    break;
+                }
+                if socket.send(Message::Ping(Vec::new())).await.is_err() {
+                    break;
+                }
+                None
+            }
+        };
+
+        if let Some(reply) = reply {
+            if send(&mut socket, &reply).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+async fn handle(
+    state: &AppState,
+    auth: &AuthUser,
+    projects: &mut HashSet<String>,
+    text: &str,
+) -> ServerMessage {
+    let message = match serde_json::from_str::<ClientMessage>(text) {
+        Ok(message) => message,
+        Err(e) => {
+            return ServerMessage::Error {
+                message: format!("Invalid message: {e}"),
+            }
+        }
+    };
+
+    match message {
+        ClientMessage::Subscribe { project_id } => match auth.project(state, &project_id).await {
+            Ok(project) => {
+                projects.insert(project.id.clone());
+                ServerMessage::Subscribed {
+                    project_id: project.id,
+                }
+            }
+            Err(e) => ServerMessage::Error {
+                message: e.to_string(),
+            },
+        },
+        ClientMessage::Unsubscribe { project_id } => {
+            // Accept the slug too, as subscribe does
+            let project_id = match auth.project(state, &project_id).await {
+                Ok(project) => project.id,
+                Err(_) => project_id,
+            };
+            projects.remove(&project_id);
+            ServerMessage::Unsubscribed { project_id }
+        }
+        ClientMessage::Ping => ServerMessage::Pong,
+    }
+}
+
+async fn send<T: Serialize>(socket: &mut WebSocket, message: &T) -> std::result::Result<(), ()> {
+    let text = serde_json::to_string(message).map_err(|_| ())?;
+    socket.send(Message::Text(text)).await.map_err(|_| ())
+}
@@ -0,0 +1,353 @@
+//! Background jobs
+//!
+//! Periodic work is declared once in [`registry`] and run by a [`JobRunner`]:
+//! each job on its own interval, retried with jittered exponential backoff
+//! when it fails. On shutdown the runner stops jobs between runs, waits for
+//! the ones in progress, and runs those marked `on_shutdown` one last time.
+//! Per-job counts are kept in memory and served on `GET /v1/admin/jobs`.
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
+
+use crate::expiry;
+use crate::guards;
+use crate::models::AppState;
+use crate::ramps;
+use crate::retention;
+use crate::usage;
+
+/// First retry waits about this long; each further one doubles it
+const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest shutdown waits for jobs to finish before giving up on them
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+type JobFuture = Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>>;
+type JobFn = Arc<dyn Fn(AppState) -> JobFuture + Send + Sync>;
+
+/// A task run every `interval`
+pub struct Job {
+    name: &'static str,
+    interval: Duration,
+    retries: u32,
+    retry_delay: Duration,
+    on_shutdown: bool,
+    run: JobFn,
+}
+
+fn job<F, Fut>(name: &'static str, interval: Duration, run: F) -> Job
+where
+    F: Fn(AppState) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    Job {
+        name,
+        interval,
+        retries: 0,
+        retry_delay: DEFAULT_RETRY_DELAY,
+        on_shutdown: false,
+        run: Arc::new(move |state| Box::pin(run(state))),
+    }
+}
+
+impl Job {
+    /// Retry a failed run up to `retries` times before waiting for the next interval
+    fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    #[cfg(test)]
+    fn retry_delay(mut self, delay: Duration) -> Self {
+        self.retry_delay = delay;
+        self
+    }
+
+    /// Run once more when the server shuts down, e.g. to flush buffered data
+    fn on_shutdown(mut self) -> Self {
+        self.on_shutdown = true;
+        self
+    }
+}
+
+/// Every periodic job the server runs
+pub fn registry() -> Vec<Job> {
+    vec![
+        job("flush-usage", usage::FLUSH_INTERVAL, usage::flush_job).on_shutdown(),
+        job(
+            "report-expired-flags",
+            expiry::REPORT_INTERVAL,
+            expiry::report,
+        )
+        .retries(3),
+        job("prune", retention::PRUNE_INTERVAL, retention::prune_job).retries(3),
+        job("advance-ramps", ramps::ADVANCE_INTERVAL, ramps::advance_job),
+        job("check-guards", guards::CHECK_INTERVAL, guards::check_job),
+    ]
+}
+
+/// One job's counts since the process started
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct JobStats {
+    pub name: String,
+    pub interval_secs: u64,
+    pub running: bool,
+    pub runs: u64,
+    pub failures: u64,
+    /// Runs that were retries of a failed one
+    pub retries: u64,
+    pub last_run_at: Option<DateTime<Utc>>,
+    pub last_duration_ms: Option<u64>,
+    pub last_success_at: Option<DateTime<Utc>>,
+    pub last_error: Option<String>,
+}
+
+/// Counts for every registered job, by name
+#[derive(Default)]
+pub struct JobMetrics {
+    jobs: Mutex<BTreeMap<&'static str, JobStats>>,
+}
+
+impl JobMetrics {
+    pub fn snapshot(&self) -> Vec<JobStats> {
+        self.lock().values().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<&'static str, JobStats>> {
+        self.jobs.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn register(&self, job: &Job) {
+        self.lock().insert(
+            job.name,
+            JobStats {
+                name: job.name.to_string(),
+                interval_secs: job.interval.as_secs(),
+                ..JobStats::default()
+            },
+        );
+    }
+
+    fn update(&self, name: &'static str, f: impl FnOnce(&mut JobStats)) {
+        if let Some(stats) = self.lock().get_mut(name) {
+            f(stats);
+        }
+    }
+}
+
+/// Runs the registered jobs until shut down
+pub struct JobRunner {
+    shutdown: watch::Sender<bool>,
+    tasks: JoinSet<()>,
+}
+
+impl JobRunner {
+    /// Start every job, recording its runs in the state's job metrics
+    pub fn start(state: AppState, jobs: Vec<Job>) -> Self {
+        let (shutdown, stopped) = watch::channel(false);
+        let mut tasks = JoinSet::new();
+        for job in jobs {
+            state.jobs.register(&job);
+            tasks.spawn(run_job(job, state.clone(), stopped.clone()));
+        }
+        Self { shutdown, tasks }
+    }
+
+    /// Stop the jobs and wait for them, running the `on_shutdown` ones a last time
+    pub async fn shutdown(mut self) {
+        let _ = self.shutdown.send(true);
+        let finished = async { while self.tasks.join_next().await.is_some() {} };
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, finished)
+            .await
+            .is_err()
+        {
+            tracing::warn!(
+                "Background jobs still running after {}s; stopping without them",
+                SHUTDOWN_TIMEOUT.as_secs()
+            );
+        }
+    }
+}
+
+async fn run_job(job: Job, state: AppState, mut stopped: watch::Receiver<bool>) {
+    let mut ticker = tokio::time::interval(job.interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = stopped.wait_for(|s| *s) => break,
+        }
+        run_with_retries(&job, &state, &mut stopped).await;
+    }
+
+    if job.on_shutdown {
+        if let Err(e) = run_once(&job, &state).await {
+            tracing::warn!("Job {} failed on shutdown: {e:#}", job.name);
+        }
+    }
+}
+
+async fn run_with_retries(job: &Job, state: &AppState, stopped: &mut watch::Receiver<bool>) {
+    for attempt in 0..=job.retries {
+        if attempt > 0 {
+            let delay = backoff(
+                job.retry_delay,
+                attempt,
+                rand::thread_rng().gen_range(0.5..1.5),
+            );
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = stopped.wait_for(|s| *s) => return,
+            }
+            state.jobs.update(job.name, |s| s.retries += 1);
+        }
+
+        match run_once(job, state).await {
+            Ok(()) => return,
+            Err(e) if attempt < job.retries => {
+                tracing::warn!(
+                    "Job {} failed (attempt {} of {}), retrying: {e:#}",
+                    job.name,
+                    attempt + 1,
+                    job.retries + 1
+                );
+            }
+            Err(e) => tracing::warn!("Job {} failed: {e:#}", job.name),
+        }
+    }
+}
+
+async fn run_once(job: &Job, state: &AppState) -> anyhow::Result<()> {
+    let started_at = Utc::now();
+    let started = Instant::now();
+    state.jobs.update(job.name, |s| {
+        s.running = true;
+        s.last_run_at = Some(started_at);
+    });
+
+    let result = (job.run)(state.clone()).await;
+
+    let duration = started.elapsed().as_millis() as u64;
+    state.jobs.update(job.name, |s| {
+        s.running = false;
+        s.runs += 1;
+        s.last_duration_ms = Some(duration);
+        match &result {
+            Ok(()) => {
+                s.last_success_at = Some(Utc::now());
+                s.last_error = None;
+            }
+            Err(e) => {
+                s.failures += 1;
+                s.last_error = Some(format!("{e:#}"));
+            }
+        }
+    });
+    result
+}
+
+/// Delay before retry `attempt` (1-based): the base doubled for each earlier
+/// retry, scaled by `jitter` so failing jobs don't retry in lockstep
+fn backoff(base: Duration, attempt: u32, jitter: f64) -> Duration {
+    let factor = 1u32
+        .checked_shl(attempt.saturating_sub(1))
+        .unwrap_or(u32::MAX);
+    base.saturating_mul(factor).mul_f64(jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SignupMode;
+    use crate::jwt::JwtKeys;
+    use crate::lockout::{LockoutPolicy, LoginThrottle};
+    use crate::mailer::LogMailer;
+    use crate::maintenance::Maintenance;
+    use crate::public::{PublicLimiter, PublicRateLimit};
+    use crate::quota::QuotaLimiter;
+    use crate::retention::RetentionPolicy;
+    use crate::storage::memory::MemoryStorage;
+    use crate::usage::UsageRecorder;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn state() -> AppState {
+        AppState {
+            storage: Arc::new(MemoryStorage::new()),
+            jwt_keys: Arc::new(JwtKeys::random()),
+            usage: Arc::new(UsageRecorder::new()),
+            signup_mode: SignupMode::Open,
+            events: None,
+            quotas: Arc::new(QuotaLimiter::new()),
+            admin_token: None,
+            mailer: Arc::new(LogMailer),
+            maintenance: Arc::new(Maintenance::new(false)),
+            logins: Arc::new(LoginThrottle::new(LockoutPolicy::default())),
+            trust_proxy: false,
+            jobs: Arc::new(JobMetrics::default()),
+            storage_metrics: Default::default(),
+            bus: Default::default(),
+            public: Arc::new(PublicLimiter::new(PublicRateLimit::default())),
+            retention: RetentionPolicy::default(),
+            health_checks: Default::default(),
+        }
+    }
+
+    #[test]
+    fn test_backoff_doubles_with_jitter() {
+        let base = Duration::from_secs(1);
+        assert_eq!(backoff(base, 1, 1.0), Duration::from_secs(1));
+        assert_eq!(backoff(base, 3, 1.0), Duration::from_secs(4));
+        assert_eq!(backoff(base, 3, 0.5), Duration::from_secs(2));
+        assert_eq!(backoff(base, 2, 1.5), Duration::from_secs(3));
+        assert!(backoff(base, 64, 1.0) >= Duration::from_secs(u32::MAX as u64));
+    }
+
+    #[tokio::test]
+    async fn test_retries_then_runs_on_shutdown() {
+        let state = state();
+        let calls = Arc::new(AtomicU32::new(0));
+
+        let counted = calls.clone();
+        let flaky = job("flaky", Duration::from_secs(3600), move |_| {
+            let call = counted.fetch_add(1, Ordering::SeqCst);
+            async move {
+                match call {
+                    0 => anyhow::bail!("first run fails"),
+                    _ => Ok(()),
+                }
+            }
+        })
+        .retries(2)
+        .retry_delay(Duration::from_millis(1))
+        .on_shutdown();
+
+        let runner = JobRunner::start(state.clone(), vec![flaky]);
+        for _ in 0..100 {
+            if calls.load(Ordering::SeqCst) >= 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        runner.shutdown().await;
+
+        // The first tick's failure, one retry, then the run on shutdown
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        let stats = &state.jobs.snapshot()[0];
+        assert_eq!(stats.name, "flaky");
+        assert_eq!(stats.runs, 3);
+        assert_eq!(stats.failures, 1);
+        assert_eq!(stats.retries, 1);
+        assert!(stats.last_error.is_none());
+        assert!(!stats.running);
+    }
+}
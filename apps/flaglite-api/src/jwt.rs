@@ -0,0 +1,241 @@
+//! Secrets for signing and verifying session tokens
+//!
+//! Tokens are HS256 JWTs carrying the `kid` of the secret that signed them.
+//! Several secrets can be configured: the first signs new tokens and all of
+//! them verify, so a secret can be rotated by putting the new one first and
+//! dropping the old one once its tokens have expired. Tokens from before key
+//! ids were added are tried against every secret.
+
+use anyhow::{bail, Context};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{decode, decode_header, encode, DecodingKey, EncodingKey, Header, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+use crate::error::{AppError, Result};
+use crate::models::Claims;
+
+/// Bytes in a secret generated for `serve --demo`
+const RANDOM_SECRET_LEN: usize = 32;
+
+struct JwtKey {
+    kid: String,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl JwtKey {
+    fn new(kid: String, secret: &[u8]) -> Self {
+        Self {
+            kid,
+            encoding: EncodingKey::from_secret(secret),
+            decoding: DecodingKey::from_secret(secret),
+        }
+    }
+}
+
+/// The configured JWT secrets, the signing one first
+pub struct JwtKeys {
+    keys: Vec<JwtKey>,
+}
+
+impl JwtKeys {
+    /// Comma-separated secrets, as in JWT_SECRET; each one's id is derived
+    /// from its hash
+    pub fn from_secrets(secrets: &str) -> anyhow::Result<Self> {
+        let keys = secrets
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|secret| JwtKey::new(derived_kid(secret.as_bytes()), secret.as_bytes()))
+            .collect();
+        Self::new(keys)
+    }
+
+    /// A JWK set of `oct` keys, each with a `kid`; the first one signs
+    pub fn from_jwks(json: &str) -> anyhow::Result<Self> {
+        let jwks: Jwks = serde_json::from_str(json).context("Invalid JWK set")?;
+        let keys = jwks
+            .keys
+            .into_iter()
+            .map(|jwk| {
+                if jwk.kty != "oct" {
+                    bail!(
+                        "Key '{}' has kty '{}'; only 'oct' keys are supported",
+                        jwk.kid,
+                        jwk.kty
+                    );
+                }
+                let secret = URL_SAFE_NO_PAD
+                    .decode(jwk.k.trim_end_matches('='))
+                    .with_context(|| format!("Key '{}' is not valid base64url", jwk.kid))?;
+                Ok(JwtKey::new(jwk.kid, &secret))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Self::new(keys)
+    }
+
+    pub fn from_jwks_file(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_jwks(&json).with_context(|| format!("Failed to load {}", path.display()))
+    }
+
+    fn new(keys: Vec<JwtKey>) -> anyhow::Result<Self> {
+        if keys.is_empty() {
+            bail!("At least one JWT secret is required");
+        }
+        for (i, key) in keys.iter().enumerate() {
+            if keys[..i].iter().any(|k| k.kid == key.kid) {
+                bail!("JWT key id '{}' is used twice", key.kid);
+            }
+        }
+        Ok(Self { keys })
+    }
+
+    /// One random secret that only lives as long as the process
+    pub fn random() -> Self {
+        let secret: [u8; RANDOM_SECRET_LEN] = rand::random();
+        Self {
+            keys: vec![JwtKey::new(derived_kid(&secret), &secret)],
+        }
+    }
+
+    /// Number of secrets that verify tokens
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    /// Sign `claims` with the first secret
+    pub fn sign(&self, claims: &Claims) -> Result<String> {
+        let key = &self.keys[0];
+        let header = Header {
+            kid: Some(key.kid.clone()),
+            ..Header::default()
+        };
+        Ok(encode(&header, claims, &key.encoding)?)
+    }
+
+    /// Verify a token with the secret its `kid` names, or with each secret
+    /// in turn for tokens without one
+    pub fn verify(&self, token: &str) -> Result<Claims> {
+        let header = decode_header(token)?;
+        let validation = Validation::default();
+
+        if let Some(kid) = header.kid {
+            let key = self
+                .keys
+                .iter()
+                .find(|k| k.kid == kid)
+                .ok_or(AppError::Unauthorized)?;
+            return Ok(decode::<Claims>(token, &key.decoding, &validation)?.claims);
+        }
+
+        let mut last_error = None;
+        for key in &self.keys {
+            match decode::<Claims>(token, &key.decoding, &validation) {
+                Ok(data) => return Ok(data.claims),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.map_or(AppError::Unauthorized, AppError::from))
+    }
+}
+
+#[derive(Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Deserialize)]
+struct Jwk {
+    kty: String,
+    kid: String,
+    k: String,
+}
+
+/// A stable id for a secret that doesn't reveal it
+fn derived_kid(secret: &[u8]) -> String {
+    let hash = format!("{:x}", Sha256::digest(secret));
+    hash[..16].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "old-secret-0123456789abcdef0123456789";
+    const NEW: &str = "new-secret-0123456789abcdef0123456789";
+
+    fn claims() -> Claims {
+        let now = chrono::Utc::now().timestamp();
+        Claims {
+            sub: "user-1".to_string(),
+            username: "alice".to_string(),
+            iat: now,
+            exp: now + 3600,
+            epoch: 0,
+        }
+    }
+
+    #[test]
+    fn test_rotation_keeps_old_tokens_valid() {
+        let before = JwtKeys::from_secrets(OLD).unwrap();
+        let token = before.sign(&claims()).unwrap();
+
+        let rotated = JwtKeys::from_secrets(&format!("{NEW}, {OLD}")).unwrap();
+        assert_eq!(rotated.verify(&token).unwrap().sub, "user-1");
+
+        // New tokens are signed with the first secret
+        let token = rotated.sign(&claims()).unwrap();
+        let kid = decode_header(&token).unwrap().kid.unwrap();
+        assert_eq!(kid, derived_kid(NEW.as_bytes()));
+        assert!(before.verify(&token).is_err());
+
+        // Dropping the old secret invalidates its tokens
+        let token = before.sign(&claims()).unwrap();
+        let after = JwtKeys::from_secrets(NEW).unwrap();
+        assert!(matches!(after.verify(&token), Err(AppError::Unauthorized)));
+    }
+
+    #[test]
+    fn test_tokens_without_kid_try_every_secret() {
+        let token = encode(
+            &Header::default(),
+            &claims(),
+            &EncodingKey::from_secret(OLD.as_bytes()),
+        )
+        .unwrap();
+
+        let keys = JwtKeys::from_secrets(&format!("{NEW},{OLD}")).unwrap();
+        assert_eq!(keys.verify(&token).unwrap().username, "alice");
+        assert!(JwtKeys::from_secrets(NEW).unwrap().verify(&token).is_err());
+    }
+
+    #[test]
+    fn test_jwks() {
+        let k = URL_SAFE_NO_PAD.encode(NEW);
+        let keys = JwtKeys::from_jwks(&format!(
+            r#"{{"keys": [{{"kty": "oct", "kid": "2026-10", "k": "{k}"}}]}}"#
+        ))
+        .unwrap();
+        let token = keys.sign(&claims()).unwrap();
+        assert_eq!(
+            decode_header(&token).unwrap().kid.as_deref(),
+            Some("2026-10")
+        );
+        assert!(keys.verify(&token).is_ok());
+
+        assert!(JwtKeys::from_jwks(r#"{"keys": []}"#).is_err());
+        assert!(
+            JwtKeys::from_jwks(r#"{"keys": [{"kty": "RSA", "kid": "a", "k": "c2VjcmV0"}]}"#)
+                .is_err()
+        );
+        assert!(JwtKeys::from_jwks(&format!(
+            r#"{{"keys": [{{"kty": "oct", "kid": "a", "k": "{k}"}}, {{"kty": "oct", "kid": "a", "k": "{k}"}}]}}"#
+        ))
+        .is_err());
+    }
+}
@@ -0,0 +1,480 @@
+//! Brute-force protection for password login
+//!
+//! Failed logins are counted per username and per client IP. Once either
+//! reaches its threshold, further attempts are refused with 429 for a lockout
+//! that doubles with every failure after that, up to a maximum. Counts are
+//! forgotten after a successful login (per username) or once no failure has
+//! been seen for the maximum lockout. Like quotas, they're kept in process
+//! memory, so each API instance tracks them independently.
+//!
+//! An attempt is reserved before the password is checked, so parallel
+//! requests can't all get past the check before the first failure counts.
+
+use axum::http::HeaderMap;
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+
+use crate::error::{AppError, Result};
+
+/// Thresholds for locking out failed logins
+#[derive(Debug, Clone, Copy)]
+pub struct LockoutPolicy {
+    /// Failures for one username before it's locked; 0 turns lockout off
+    pub max_failures: u32,
+    /// Failures from one IP, across usernames, before it's locked
+    pub max_failures_per_ip: u32,
+    /// Lockout after the failure that reaches a threshold
+    pub lockout: Duration,
+    /// Longest lockout, however many failures follow
+    pub max_lockout: Duration,
+}
+
+impl Default for LockoutPolicy {
+    fn default() -> Self {
+        Self {
+            max_failures: 5,
+            max_failures_per_ip: 20,
+            lockout: Duration::seconds(30),
+            max_lockout: Duration::minutes(15),
+        }
+    }
+}
+
+/// Most usernames and IPs tracked at once; past it, the least recently
+/// failed one that isn't locked out is forgotten
+const MAX_SUBJECTS: usize = 100_000;
+
+#[derive(Debug, Clone, Copy)]
+struct Failures {
+    count: u32,
+    /// Attempts reserved but not yet known to have failed or succeeded
+    pending: u32,
+    last: DateTime<Utc>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+impl Failures {
+    fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            count: 0,
+            pending: 0,
+            last: now,
+            locked_until: None,
+        }
+    }
+}
+
+/// A username or IP that was just locked out
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lockout {
+    /// `user:<username>` or `ip:<address>`
+    pub subject: String,
+    pub failures: u32,
+    pub retry_after: u64,
+}
+
+pub struct LoginThrottle {
+    policy: LockoutPolicy,
+    capacity: usize,
+    /// Keyed by `user:<username>` and `ip:<address>`
+    failures: Mutex<HashMap<String, Failures>>,
+}
+
+impl LoginThrottle {
+    pub fn new(policy: LockoutPolicy) -> Self {
+        Self::with_capacity(policy, MAX_SUBJECTS)
+    }
+
+    fn with_capacity(policy: LockoutPolicy, capacity: usize) -> Self {
+        Self {
+            policy,
+            capacity: capacity.max(1),
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.policy.max_failures > 0
+    }
+
+    fn threshold(&self, per_ip: bool) -> u32 {
+        if per_ip {
+            self.policy.max_failures_per_ip
+        } else {
+            self.policy.max_failures
+        }
+    }
+
+    /// Reserve a login attempt, or refuse it while the username or IP is
+    /// locked out. Attempts already in flight count as failures until they
+    /// resolve, so no more run at once than could fail before a lockout.
+    pub fn begin(
+        &self,
+        username: &str,
+        ip: Option<IpAddr>,
+        now: DateTime<Utc>,
+    ) -> Result<LoginAttempt<'_>> {
+        let mut attempt = LoginAttempt {
+            throttle: self,
+            subjects: subjects(username, ip),
+            username: username.to_string(),
+            resolved: true,
+        };
+        if !self.enabled() {
+            return Ok(attempt);
+        }
+
+        let mut failures = self.failures.lock().unwrap_or_else(|e| e.into_inner());
+        let mut retry_after = None;
+        for (subject, per_ip) in &attempt.subjects {
+            let Some(f) = failures.get(subject) else {
+                continue;
+            };
+            if let Some(until) = f.locked_until.filter(|until| *until > now) {
+                retry_after = retry_after.max(Some(seconds_until(until, now)));
+            } else if f.pending > 0 && f.count + f.pending >= self.threshold(*per_ip) {
+                // The attempts in flight could start a lockout; wait for them
+                retry_after = retry_after.max(Some(1));
+            }
+        }
+        if let Some(retry_after) = retry_after {
+            return Err(AppError::LoginLocked { retry_after });
+        }
+
+        self.sweep(&mut failures, now);
+        for (subject, _) in &attempt.subjects {
+            if !failures.contains_key(subject) {
+                self.make_room(&mut failures);
+            }
+            failures
+                .entry(subject.clone())
+                .or_insert_with(|| Failures::new(now))
+                .pending += 1;
+        }
+        attempt.resolved = false;
+        Ok(attempt)
+    }
+
+    /// Forget counts that have been quiet for the maximum lockout
+    fn sweep(&self, failures: &mut HashMap<String, Failures>, now: DateTime<Utc>) {
+        let stale = now - self.policy.max_lockout;
+        failures.retain(|_, f| {
+            f.pending > 0 || f.last > stale || f.locked_until.is_some_and(|u| u > now)
+        });
+    }
+
+    /// Forget the least recently failed subject that isn't locked out or in
+    /// flight, if the map is full
+    fn make_room(&self, failures: &mut HashMap<String, Failures>) {
+        if failures.len() < self.capacity {
+            return;
+        }
+        let oldest = failures
+            .iter()
+            .filter(|(_, f)| f.pending == 0 && f.locked_until.is_none())
+            .min_by_key(|(_, f)| f.last)
+            .map(|(subject, _)| subject.clone());
+        if let Some(subject) = oldest {
+            failures.remove(&subject);
+        }
+    }
+
+    /// The base lockout, doubled for each failure past the threshold
+    fn lockout_for(&self, past_threshold: u32) -> Duration {
+        let factor = 1i32.checked_shl(past_threshold).unwrap_or(i32::MAX);
+        self.policy
+            .lockout
+            .checked_mul(factor)
+            .map_or(self.policy.max_lockout, |d| d.min(self.policy.max_lockout))
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.failures
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+}
+
+/// A reserved login attempt; dropping it unresolved (say, on a database
+/// error) releases the reservation without counting a failure
+pub struct LoginAttempt<'a> {
+    throttle: &'a LoginThrottle,
+    subjects: Vec<(String, bool)>,
+    username: String,
+    resolved: bool,
+}
+
+impl LoginAttempt<'_> {
+    /// Count the attempt as failed, returning the lockouts it started
+    pub fn fail(mut self, now: DateTime<Utc>) -> Vec<Lockout> {
+        if self.resolved {
+            return Vec::new();
+        }
+        self.resolved = true;
+
+        let throttle = self.throttle;
+        let mut failures = throttle.failures.lock().unwrap_or_else(|e| e.into_inner());
+        let mut lockouts = Vec::new();
+        for (subject, per_ip) in &self.subjects {
+            let entry = failures
+                .entry(subject.clone())
+                .or_insert_with(|| Failures::new(now));
+            entry.pending = entry.pending.saturating_sub(1);
+            entry.count += 1;
+            entry.last = now;
+
+            let threshold = throttle.threshold(*per_ip);
+            if entry.count >= threshold {
+                let until = now + throttle.lockout_for(entry.count - threshold);
+                entry.locked_until = Some(until);
+                lockouts.push(Lockout {
+                    subject: subject.clone(),
+                    failures: entry.count,
+                    retry_after: seconds_until(until, now),
+                });
+            }
+        }
+        lockouts
+    }
+
+    /// Forget the username's failures after it logs in
+    pub fn succeed(mut self) {
+        self.release();
+        let mut failures = self
+            .throttle
+            .failures
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        failures.remove(&format!("user:{}", self.username));
+    }
+
+    fn release(&mut self) {
+        if self.resolved {
+            return;
+        }
+        self.resolved = true;
+        let mut failures = self
+            .throttle
+            .failures
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        for (subject, _) in &self.subjects {
+            if let Some(entry) = failures.get_mut(subject) {
+                entry.pending = entry.pending.saturating_sub(1);
+            }
+        }
+    }
+}
+
+impl Drop for LoginAttempt<'_> {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+/// The counters an attempt touches, and whether each is the per-IP one
+fn subjects(username: &str, ip: Option<IpAddr>) -> Vec<(String, bool)> {
+    let mut subjects = vec![(format!("user:{username}"), false)];
+    if let Some(ip) = ip {
+        subjects.push((format!("ip:{ip}"), true));
+    }
+    subjects
+}
+
+fn seconds_until(until: DateTime<Utc>, now: DateTime<Utc>) -> u64 {
+    // Rounded up, so a client retrying on time isn't refused again
+    let millis = (until - now).num_milliseconds().max(0) as u64;
+    millis.div_ceil(1000).max(1)
+}
+
+/// The client's address: the last X-Forwarded-For entry, the one the
+/// trusted proxy appended, when the server sits behind one; otherwise the
+/// peer of the connection. Earlier entries come from the client and can be
+/// anything.
+pub fn client_ip(
+    peer: Option<SocketAddr>,
+    headers: &HeaderMap,
+    trust_proxy: bool,
+) -> Option<IpAddr> {
+    if trust_proxy {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.rsplit(',').next())
+            .and_then(|ip| ip.trim().parse().ok());
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+    peer.map(|addr| addr.ip())
+}
+
+/// Write a lockout to the audit log
+pub fn audit(lockout: &Lockout) {
+    tracing::warn!(
+        target: "audit",
+        event = "login_lockout",
+        subject = %lockout.subject,
+        failures = lockout.failures,
+        retry_after = lockout.retry_after,
+        "Locked out {} for {}s after {} failed logins",
+        lockout.subject,
+        lockout.retry_after,
+        lockout.failures
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle() -> LoginThrottle {
+        LoginThrottle::new(LockoutPolicy {
+            max_failures: 3,
+            max_failures_per_ip: 5,
+            lockout: Duration::seconds(10),
+            max_lockout: Duration::seconds(60),
+        })
+    }
+
+    /// One attempt that fails
+    fn fail(
+        throttle: &LoginThrottle,
+        name: &str,
+        ip: Option<IpAddr>,
+        now: DateTime<Utc>,
+    ) -> Vec<Lockout> {
+        throttle.begin(name, ip, now).unwrap().fail(now)
+    }
+
+    #[test]
+    fn test_locks_username_with_backoff() {
+        let throttle = throttle();
+        let now = Utc::now();
+
+        assert!(fail(&throttle, "alice", None, now).is_empty());
+        assert!(fail(&throttle, "alice", None, now).is_empty());
+        let lockouts = fail(&throttle, "alice", None, now);
+        assert_eq!(lockouts.len(), 1);
+        assert_eq!(lockouts[0].subject, "user:alice");
+        assert_eq!(lockouts[0].retry_after, 10);
+
+        assert!(matches!(
+            throttle.begin("alice", None, now),
+            Err(AppError::LoginLocked { retry_after: 10 })
+        ));
+        throttle.begin("bob", None, now).unwrap();
+
+        // Each failure after the lockout ends doubles the next one, up to the maximum
+        let mut later = now + Duration::seconds(10);
+        for expected in [20, 40, 60] {
+            let lockouts = fail(&throttle, "alice", None, later);
+            assert_eq!(lockouts[0].retry_after, expected);
+            later += Duration::seconds(expected as i64);
+        }
+
+        throttle.begin("alice", None, later).unwrap().succeed();
+        throttle.begin("alice", None, later).unwrap();
+    }
+
+    #[test]
+    fn test_locks_ip_across_usernames() {
+        let throttle = throttle();
+        let now = Utc::now();
+        let ip: IpAddr = "203.0.113.7".parse().unwrap();
+
+        for name in ["a", "b", "c", "d"] {
+            assert!(fail(&throttle, name, Some(ip), now).is_empty());
+        }
+        let lockouts = fail(&throttle, "e", Some(ip), now);
+        assert_eq!(lockouts[0].subject, "ip:203.0.113.7");
+
+        assert!(throttle.begin("f", Some(ip), now).is_err());
+        throttle.begin("f", None, now).unwrap();
+    }
+
+    #[test]
+    fn test_attempts_in_flight_count_toward_the_threshold() {
+        let throttle = throttle();
+        let now = Utc::now();
+
+        // Only as many at once as could fail before the lockout
+        let first = throttle.begin("alice", None, now).unwrap();
+        let second = throttle.begin("alice", None, now).unwrap();
+        let third = throttle.begin("alice", None, now).unwrap();
+        assert!(matches!(
+            throttle.begin("alice", None, now),
+            Err(AppError::LoginLocked { retry_after: 1 })
+        ));
+
+        // An attempt that ends without an answer gives its place back
+        drop(third);
+        let third = throttle.begin("alice", None, now).unwrap();
+        assert!(first.fail(now).is_empty());
+        assert!(second.fail(now).is_empty());
+        assert_eq!(third.fail(now).len(), 1);
+        assert!(throttle.begin("alice", None, now).is_err());
+    }
+
+    #[test]
+    fn test_failures_expire_and_zero_disables() {
+        let throttle = throttle();
+        let now = Utc::now();
+        fail(&throttle, "alice", None, now);
+        fail(&throttle, "alice", None, now);
+
+        // Forgotten once quiet for the maximum lockout
+        let later = now + Duration::seconds(61);
+        assert!(fail(&throttle, "alice", None, later).is_empty());
+
+        let off = LoginThrottle::new(LockoutPolicy {
+            max_failures: 0,
+            ..LockoutPolicy::default()
+        });
+        for _ in 0..10 {
+            assert!(fail(&off, "alice", None, now).is_empty());
+        }
+        off.begin("alice", None, now).unwrap();
+        assert_eq!(off.len(), 0);
+    }
+
+    #[test]
+    fn test_tracks_a_bounded_number_of_subjects() {
+        let throttle = LoginThrottle::with_capacity(throttle().policy, 10);
+        let now = Utc::now();
+        for _ in 0..3 {
+            fail(&throttle, "alice", None, now);
+        }
+
+        for i in 0..100u8 {
+            let ip = IpAddr::from([203, 0, 113, i]);
+            let at = now + Duration::milliseconds(i.into());
+            fail(&throttle, &format!("user{i}"), Some(ip), at);
+        }
+        assert!(throttle.len() <= 10);
+        // A locked-out username isn't what makes room
+        assert!(throttle.begin("alice", None, now).is_err());
+    }
+
+    #[test]
+    fn test_client_ip_trusts_forwarded_for_only_behind_proxy() {
+        let peer: SocketAddr = "10.0.0.1:5000".parse().unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "198.51.100.2".parse().unwrap());
+
+        assert_eq!(
+            client_ip(Some(peer), &headers, false),
+            Some("10.0.0.1".parse().unwrap())
+        );
+        // The proxy appends the address it saw; what precedes it is the client's say
+        headers.insert("x-forwarded-for", "1.2.3.4, 198.51.100.2".parse().unwrap());
+        assert_eq!(
+            client_ip(Some(peer), &headers, true),
+            Some("198.51.100.2".parse().unwrap())
+        );
+    }
+}
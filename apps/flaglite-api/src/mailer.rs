@@ -0,0 +1,105 @@
+//! Outgoing email for password resets and email verification
+//!
+//! With SMTP_HOST set (and the `smtp` feature), mail goes through that
+//! server. Otherwise messages are written to the log, which is enough to
+//! copy a token out of during development.
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// SMTP settings from the environment
+#[derive(Debug, Clone)]
+#[cfg_attr(not(feature = "smtp"), allow(dead_code))]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Sender address, e.g. `FlagLite <noreply@example.com>`
+    pub from: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<()>;
+}
+
+/// Logs each message instead of sending it
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, email: &Email) -> Result<()> {
+        tracing::info!(
+            "Email to {} (SMTP_HOST not set, not sent): {}\n{}",
+            email.to,
+            email.subject,
+            email.body
+        );
+        Ok(())
+    }
+}
+
+#[cfg(feature = "smtp")]
+pub struct SmtpMailer {
+    transport: lettre::AsyncSmtpTransport<lettre::Tokio1Executor>,
+    from: lettre::message::Mailbox,
+}
+
+#[cfg(feature = "smtp")]
+impl SmtpMailer {
+    pub fn new(config: &SmtpConfig) -> Result<Self> {
+        use anyhow::Context;
+        use lettre::transport::smtp::authentication::Credentials;
+
+        let mut transport =
+            lettre::AsyncSmtpTransport::<lettre::Tokio1Executor>::starttls_relay(&config.host)
+                .context("Invalid SMTP_HOST")?
+                .port(config.port);
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            transport = transport.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Self {
+            transport: transport.build(),
+            from: config.from.parse().context("Invalid SMTP_FROM")?,
+        })
+    }
+}
+
+#[cfg(feature = "smtp")]
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, email: &Email) -> Result<()> {
+        use lettre::AsyncTransport;
+
+        let message = lettre::Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse()?)
+            .subject(&email.subject)
+            .body(email.body.clone())?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+pub fn from_config(smtp: Option<&SmtpConfig>) -> Result<Box<dyn Mailer>> {
+    let Some(config) = smtp else {
+        return Ok(Box::new(LogMailer));
+    };
+
+    #[cfg(feature = "smtp")]
+    return Ok(Box::new(SmtpMailer::new(config)?));
+    #[cfg(not(feature = "smtp"))]
+    {
+        let _ = config;
+        anyhow::bail!("SMTP_HOST is set, but this build lacks the `smtp` feature");
+    }
+}
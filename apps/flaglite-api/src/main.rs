@@ -1,20 +1,45 @@
+mod archive;
 mod auth;
+mod bus;
+mod cache;
+mod concurrency;
 mod config;
 mod error;
+mod events;
+mod expiry;
+mod guards;
 mod handlers;
+mod jobs;
+mod jwt;
+mod lockout;
+mod mailer;
+mod maintenance;
+mod metrics;
 mod models;
+mod public;
+mod quota;
+mod ramps;
+mod relay;
+mod retention;
+mod routes;
+mod signing;
+mod slug;
 mod storage;
+mod suggest;
+mod telemetry;
+mod templates;
+mod usage;
 mod username;
+mod validation;
+mod versioning;
 
-use axum::{
-    routing::{delete, get, post},
-    Router,
-};
+use axum::{middleware, Extension, Router};
 use clap::{Parser, Subcommand};
 use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 #[derive(Parser)]
 #[command(name = "flaglite")]
@@ -35,9 +60,80 @@ enum Commands {
         /// Host to bind to
         #[arg(long, default_value = "0.0.0.0")]
         host: String,
+
+        /// Run with in-memory storage and no required configuration
+        #[arg(long)]
+        demo: bool,
+
+        /// Serve the SDK endpoints (flag evaluation) on this port instead;
+        /// --port then serves everything else, e.g. on an internal interface
+        #[arg(long, env = "SDK_PORT", conflicts_with = "sdk_only")]
+        sdk_port: Option<u16>,
+
+        /// Host to bind --sdk-port to
+        #[arg(long, env = "SDK_HOST", default_value = "0.0.0.0")]
+        sdk_host: String,
+
+        /// Serve only the SDK endpoints, for public evaluation-only instances
+        #[arg(long)]
+        sdk_only: bool,
+    },
+    /// Serve evaluations from an in-memory copy of an upstream server's flags
+    Relay {
+        /// Upstream FlagLite API URL
+        #[arg(long, env = "FLAGLITE_UPSTREAM")]
+        upstream: String,
+
+        /// Environment or project API key to pull flags with; clients must send the same key
+        #[arg(long, env = "FLAGLITE_RELAY_API_KEY")]
+        api_key: String,
+
+        /// Seconds between refreshes from upstream
+        #[arg(long, default_value = "30")]
+        interval: u64,
+
+        /// Port to listen on
+        #[arg(short, long, default_value = "3000")]
+        port: u16,
+
+        /// Host to bind to
+        #[arg(long, default_value = "0.0.0.0")]
+        host: String,
     },
     /// Run database migrations
     Migrate,
+    /// Create an invite code for SIGNUP_MODE=invite
+    Invite {
+        /// Days until the code expires
+        #[arg(long, default_value_t = handlers::auth::INVITE_VALID_DAYS)]
+        days: i64,
+    },
+    /// Delete flag history, evaluation counts and deleted environments past
+    /// their retention now, instead of waiting for the daily job
+    Prune {
+        /// Keep this many days of flag versions [default: AUDIT_RETENTION_DAYS]
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        history_days: Option<u32>,
+        /// Keep this many days of evaluation counts [default: EVALUATION_RETENTION_DAYS]
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        evaluation_days: Option<u32>,
+        /// Compact the database afterwards so its file shrinks; writes wait
+        /// until it finishes
+        #[arg(long)]
+        vacuum: bool,
+    },
+    /// Copy the SQLite database to a file while the server keeps running
+    Backup {
+        /// File to write; must not exist
+        #[arg(long, short)]
+        output: PathBuf,
+    },
+    /// Replace the SQLite database with a backup (stop the server first)
+    Restore {
+        /// Backup written by `backup` or downloaded from /v1/admin/backup
+        #[arg(long, short)]
+        input: PathBuf,
+    },
 }
 
 #[tokio::main]
@@ -45,99 +141,346 @@ async fn main() -> anyhow::Result<()> {
     // Load .env file if present
     dotenvy::dotenv().ok();
 
-    // Initialize logging
-    tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "flaglite=debug,tower_http=debug".into()),
-        )
-        .with(tracing_subscriber::fmt::layer())
-        .init();
+    // Initialize logging and trace export; spans are flushed when this drops
+    let _telemetry = telemetry::init()?;
 
     let cli = Cli::parse();
-    let config = config::Config::from_env()?;
 
     match cli.command {
-        Commands::Serve { port, host } => {
-            let storage = storage::create_storage(&config.database_url).await?;
+        Commands::Serve {
+            port,
+            host,
+            demo,
+            sdk_port,
+            sdk_host,
+            sdk_only,
+        } => {
+            let config = if demo {
+                config::Config::demo()?
+            } else {
+                config::Config::from_env()?
+            };
+            let storage =
+                storage::create_storage(&config.database_url, config.db_max_connections).await?;
 
             // Run migrations on startup
             storage.run_migrations().await?;
 
+            // Timed below the cache, so the timings are the database's
+            let storage_metrics = Arc::new(metrics::StorageMetrics::default());
+            let storage: Arc<dyn storage::Storage> = Arc::new(storage::InstrumentedStorage::new(
+                storage,
+                storage_metrics.clone(),
+                config.slow_query,
+            ));
+
+            let storage: Arc<dyn storage::Storage> = match config.cache_ttl {
+                Some(ttl) => {
+                    tracing::info!("Caching evaluation reads for {}s", ttl.as_secs());
+                    Arc::new(storage::CachedStorage::new(storage, ttl))
+                }
+                None => storage,
+            };
+
+            let maintenance = Arc::new(maintenance::Maintenance::new(config.read_only));
+            if config.read_only {
+                tracing::warn!("READ_ONLY is set; starting in read-only maintenance mode");
+            }
+
+            let usage = Arc::new(usage::UsageRecorder::new());
+
+            let events = match &config.evaluation_sink {
+                Some(spec) => {
+                    let sink = events::sink_from_spec(spec).await?;
+                    tracing::info!("Publishing evaluation events to {spec}");
+                    Some(events::EvaluationEvents::spawn(sink))
+                }
+                None => None,
+            };
+
+            let mailer = mailer::from_config(config.smtp.as_ref())?;
+            match &config.smtp {
+                Some(smtp) => tracing::info!("Sending email through {}:{}", smtp.host, smtp.port),
+                None => tracing::info!("SMTP_HOST not set; emails are written to the log"),
+            }
+
+            if config.jwt_keys.len() > 1 {
+                tracing::info!(
+                    "Verifying sessions with {} JWT secrets; the first signs new ones",
+                    config.jwt_keys.len()
+                );
+            }
+
             let app_state = models::AppState {
                 storage,
-                jwt_secret: config.jwt_secret,
+                jwt_keys: Arc::new(config.jwt_keys),
+                usage,
+                signup_mode: config.signup_mode,
+                events,
+                quotas: Arc::new(quota::QuotaLimiter::new()),
+                admin_token: config.admin_token,
+                mailer: Arc::from(mailer),
+                maintenance,
+                logins: Arc::new(lockout::LoginThrottle::new(config.login_lockout)),
+                trust_proxy: config.trust_proxy,
+                jobs: Arc::new(jobs::JobMetrics::default()),
+                storage_metrics,
+                bus: bus::EventBus::default(),
+                public: Arc::new(public::PublicLimiter::new(config.public_rate_limit)),
+                retention: config.retention,
+                health_checks: guards::HealthChecks::new(config.guard_allowed_hosts),
+            };
+            bus::EventBus::start(&app_state, bus::subscribers());
+            let jobs = jobs::JobRunner::start(app_state.clone(), jobs::registry());
+
+            let (stop, stopped) = tokio::sync::watch::channel(false);
+            tokio::spawn(async move {
+                shutdown_signal().await;
+                tracing::info!("Shutting down");
+                let _ = stop.send(true);
+            });
+
+            let api = if sdk_only {
+                routes::Api::Sdk
+            } else if sdk_port.is_some() {
+                routes::Api::Management
+            } else {
+                routes::Api::All
             };
 
-            let app = create_router(app_state);
+            let addr: SocketAddr = format!("{host}:{port}").parse()?;
+            let listener = tokio::net::TcpListener::bind(addr).await?;
+            match api {
+                routes::Api::Sdk => tracing::info!("🚀 FlagLite SDK API listening on {addr}"),
+                _ => tracing::info!("🚀 FlagLite API listening on {addr}"),
+            }
+            let served = serve(
+                listener,
+                create_router(app_state.clone(), api),
+                stopped.clone(),
+            );
+
+            match sdk_port {
+                Some(sdk_port) => {
+                    let sdk_addr: SocketAddr = format!("{sdk_host}:{sdk_port}").parse()?;
+                    let sdk_listener = tokio::net::TcpListener::bind(sdk_addr).await?;
+                    tracing::info!("🚀 FlagLite SDK API listening on {sdk_addr}");
+                    let sdk = serve(
+                        sdk_listener,
+                        create_router(app_state, routes::Api::Sdk),
+                        stopped,
+                    );
+                    tokio::try_join!(served, sdk)?;
+                }
+                None => served.await?,
+            }
+
+            // Runs the final usage flush, so counts from the last seconds aren't lost
+            jobs.shutdown().await;
+        }
+        Commands::Relay {
+            upstream,
+            api_key,
+            interval,
+            port,
+            host,
+        } => {
+            if interval == 0 {
+                anyhow::bail!("--interval must be at least 1 second");
+            }
 
             let addr: SocketAddr = format!("{host}:{port}").parse()?;
-            tracing::info!("🚀 FlagLite API listening on {addr}");
+            tracing::info!("🛰️  FlagLite relay for {upstream} listening on {addr}");
 
             let listener = tokio::net::TcpListener::bind(addr).await?;
-            axum::serve(listener, app).await?;
+            relay::run(
+                upstream,
+                api_key,
+                std::time::Duration::from_secs(interval),
+                listener,
+            )
+            .await?;
         }
         Commands::Migrate => {
-            let storage = storage::create_storage(&config.database_url).await?;
+            let config = config::Config::from_env()?;
+            let storage =
+                storage::create_storage(&config.database_url, config.db_max_connections).await?;
             storage.run_migrations().await?;
             tracing::info!("✅ Migrations completed successfully");
         }
+        Commands::Invite { days } => {
+            let config = config::Config::from_env()?;
+            let storage =
+                storage::create_storage(&config.database_url, config.db_max_connections).await?;
+            storage.run_migrations().await?;
+
+            let now = chrono::Utc::now();
+            let invite = models::Invite {
+                code: models::generate_invite_code(),
+                created_by: None,
+                created_at: now,
+                expires_at: now + chrono::Duration::days(days),
+                used_by: None,
+                used_at: None,
+            };
+            storage.create_invite(&invite).await?;
+
+            // Printed bare so scripts can capture it
+            println!("{}", invite.code);
+        }
+        Commands::Prune {
+            history_days,
+            evaluation_days,
+            vacuum,
+        } => {
+            let config = config::Config::from_env()?;
+            let policy = retention::RetentionPolicy {
+                history_days: history_days.or(config.retention.history_days),
+                evaluation_days: evaluation_days.or(config.retention.evaluation_days),
+                ..config.retention
+            };
+            if policy.keeps_everything() && !vacuum {
+                anyhow::bail!(
+                    "Nothing to prune: set AUDIT_RETENTION_DAYS or EVALUATION_RETENTION_DAYS, or pass --history-days or --evaluation-days"
+                );
+            }
+
+            let storage =
+                storage::create_storage(&config.database_url, config.db_max_connections).await?;
+            storage.run_migrations().await?;
+
+            let pruned = retention::prune(storage.as_ref(), policy, chrono::Utc::now()).await?;
+            tracing::info!(
+                "✅ Pruned {} flag version(s), {} evaluation count(s) and {} deleted environment(s)",
+                pruned.flag_versions,
+                pruned.evaluation_counts,
+                pruned.deleted_environments
+            );
+            if vacuum {
+                storage.vacuum().await?;
+                tracing::info!("✅ Compacted the database");
+            }
+        }
+        Commands::Backup { output } => {
+            if output.exists() {
+                anyhow::bail!("{} already exists", output.display());
+            }
+
+            let config = config::Config::from_env()?;
+            let storage =
+                storage::create_storage(&config.database_url, config.db_max_connections).await?;
+            storage.backup(&output).await?;
+            tracing::info!("✅ Backed up the database to {}", output.display());
+        }
+        Commands::Restore { input } => {
+            let config = config::Config::from_env()?;
+            if !config.database_url.starts_with("sqlite:") {
+                anyhow::bail!("restore only supports SQLite databases");
+            }
+
+            let previous = storage::sqlite::restore(&config.database_url, &input).await?;
+            tracing::info!("✅ Restored the database from {}", input.display());
+            if let Some(previous) = previous {
+                tracing::info!("The replaced database was kept at {}", previous.display());
+            }
+        }
     }
 
     Ok(())
 }
 
-fn create_router(state: models::AppState) -> Router {
+/// Serve HTTP/1.1 and h2c; small evaluation responses go out without waiting
+/// on Nagle's algorithm. Handlers can see the peer address (for login lockout).
+/// Once `stopped` turns true, stops accepting and finishes in-flight requests
+async fn serve(
+    listener: tokio::net::TcpListener,
+    app: Router,
+    mut stopped: tokio::sync::watch::Receiver<bool>,
+) -> std::io::Result<()> {
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .tcp_nodelay(true)
+    .with_graceful_shutdown(async move {
+        let _ = stopped.wait_for(|s| *s).await;
+    })
+    .await
+}
+
+/// Ctrl-C, or SIGTERM from a process manager
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("Failed to listen for Ctrl-C: {e}");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+fn create_router(state: models::AppState, api: routes::Api) -> Router {
     let cors = CorsLayer::new()
         .allow_origin(Any)
         .allow_methods(Any)
         .allow_headers(Any);
 
-    Router::new()
-        // Health check
-        .route("/health", get(|| async { "OK" }))
-        // LLMs.txt for AI assistants
-        .route("/llms.txt", get(handlers::llms::llms_txt))
-        // Auth routes
-        .route("/v1/auth/signup", post(handlers::auth::signup))
-        .route("/v1/auth/login", post(handlers::auth::login))
-        .route(
-            "/v1/auth/me",
-            get(handlers::auth::me).patch(handlers::auth::update_me),
-        )
-        // Project routes (v1)
-        .route("/v1/projects", get(handlers::cli::list_projects))
-        .route("/v1/projects", post(handlers::cli::create_project))
-        .route(
-            "/v1/projects/:project_id/environments",
-            get(handlers::cli::list_environments),
-        )
-        .route(
-            "/v1/projects/:project_id/flags",
-            get(handlers::cli::list_flags),
-        )
-        .route(
-            "/v1/projects/:project_id/flags",
-            post(handlers::cli::create_flag),
-        )
-        .route(
-            "/v1/projects/:project_id/flags/:key",
-            get(handlers::cli::get_flag),
-        )
-        .route(
-            "/v1/projects/:project_id/flags/:key",
-            delete(handlers::cli::delete_flag),
-        )
-        .route(
-            "/v1/projects/:project_id/flags/:key/toggle",
-            post(handlers::cli::toggle_flag),
-        )
-        // SDK evaluation endpoint (uses env API keys)
-        .route(
-            "/v1/flags/:key/evaluate",
-            get(handlers::flags::evaluate_flag),
-        )
-        .layer(TraceLayer::new_for_http())
+    let mut router = Router::new();
+    for route in routes::sections()
+        .into_iter()
+        .flat_map(|s| s.routes)
+        .filter(|route| api.serves(&route.endpoint))
+    {
+        // The auth extractors check user API keys against the route's scope
+        let handler = route.handler.layer(Extension(route.endpoint.scope));
+        let handler = if route.endpoint.writes {
+            handler.layer(middleware::from_fn_with_state(
+                state.clone(),
+                maintenance::enforce,
+            ))
+        } else {
+            handler
+        };
+        let handler = if route.endpoint.changes.is_empty() {
+            handler
+        } else {
+            handler.layer(middleware::from_fn_with_state(
+                route.endpoint.changes,
+                versioning::downgrade,
+            ))
+        };
+        if let Some(path) = versioning::v2_path(route.endpoint.path) {
+            router = router.route(&path, handler.clone());
+        }
+        router = router.route(route.endpoint.path, handler);
+    }
+
+    router
+        .layer(middleware::from_fn(versioning::negotiate))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            quota::enforce,
+        ))
+        .layer(TraceLayer::new_for_http().make_span_with(telemetry::make_span))
         .layer(cors)
         .with_state(state)
 }
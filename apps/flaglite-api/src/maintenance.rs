@@ -0,0 +1,89 @@
+//! Read-only maintenance mode
+//!
+//! While it's on, every endpoint that writes answers 503 and evaluation
+//! counts are held in memory, so the database can be backed up, migrated or
+//! vacuumed without breaking SDK reads. Turned on with READ_ONLY at startup
+//! or through `PUT /v1/admin/maintenance`.
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+use crate::error::AppError;
+use crate::models::AppState;
+
+const DEFAULT_MESSAGE: &str =
+    "FlagLite is in read-only maintenance mode; changes are disabled, flag reads and evaluation keep working";
+
+pub struct Maintenance {
+    read_only: AtomicBool,
+    /// Shown instead of the default message, e.g. when the maintenance ends
+    message: RwLock<Option<String>>,
+}
+
+impl Maintenance {
+    pub fn new(read_only: bool) -> Self {
+        Self {
+            read_only: AtomicBool::new(read_only),
+            message: RwLock::new(None),
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, read_only: bool, message: Option<String>) {
+        *self.message.write().unwrap_or_else(|e| e.into_inner()) = message;
+        self.read_only.store(read_only, Ordering::Relaxed);
+        if read_only {
+            tracing::warn!("Read-only maintenance mode on");
+        } else {
+            tracing::info!("Read-only maintenance mode off");
+        }
+    }
+
+    /// The custom message, if one was set
+    pub fn custom_message(&self) -> Option<String> {
+        self.message
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    pub fn message(&self) -> String {
+        self.custom_message()
+            .unwrap_or_else(|| DEFAULT_MESSAGE.to_string())
+    }
+}
+
+/// Middleware for routes that write: 503 while in maintenance mode
+pub async fn enforce(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if state.maintenance.is_read_only() {
+        return AppError::ReadOnly(state.maintenance.message()).into_response();
+    }
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_defaults_until_set() {
+        let maintenance = Maintenance::new(true);
+        assert!(maintenance.is_read_only());
+        assert_eq!(maintenance.message(), DEFAULT_MESSAGE);
+
+        maintenance.set(true, Some("Back at 14:00 UTC".to_string()));
+        assert_eq!(maintenance.message(), "Back at 14:00 UTC");
+
+        maintenance.set(false, None);
+        assert!(!maintenance.is_read_only());
+        assert_eq!(maintenance.message(), DEFAULT_MESSAGE);
+    }
+}
@@ -0,0 +1,128 @@
+//! Prometheus metrics
+//!
+//! `GET /metrics` serves how long each storage operation takes, as a
+//! histogram per operation, in the Prometheus text format. The timings are
+//! taken around the database backend, below the evaluation cache, so they
+//! show what the database costs rather than what cache hits save.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds of the duration buckets, in seconds
+const BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+/// Counts of one operation's durations
+#[derive(Debug, Clone, Default)]
+struct Histogram {
+    /// Observations at or under each bound in [`BUCKETS`], not cumulative
+    buckets: [u64; BUCKETS.len()],
+    count: u64,
+    sum: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if let Some(i) = BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[i] += 1;
+        }
+        self.count += 1;
+        self.sum += seconds;
+    }
+}
+
+/// Durations of storage operations, by operation
+#[derive(Default)]
+pub struct StorageMetrics {
+    operations: Mutex<BTreeMap<&'static str, Histogram>>,
+}
+
+impl StorageMetrics {
+    pub fn record(&self, operation: &'static str, elapsed: Duration) {
+        self.operations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(operation)
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    /// The histograms in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let operations = self
+            .operations
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+
+        let name = "flaglite_storage_operation_duration_seconds";
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP {name} Time taken by storage operations");
+        let _ = writeln!(out, "# TYPE {name} histogram");
+        for (operation, histogram) in &operations {
+            let mut cumulative = 0;
+            for (bound, count) in BUCKETS.iter().zip(histogram.buckets) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "{name}_bucket{{operation=\"{operation}\",le=\"{bound}\"}} {cumulative}"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "{name}_bucket{{operation=\"{operation}\",le=\"+Inf\"}} {}",
+                histogram.count
+            );
+            let _ = writeln!(
+                out,
+                "{name}_sum{{operation=\"{operation}\"}} {}",
+                histogram.sum
+            );
+            let _ = writeln!(
+                out,
+                "{name}_count{{operation=\"{operation}\"}} {}",
+                histogram.count
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_cumulative_buckets() {
+        let metrics = StorageMetrics::default();
+        metrics.record("list_flags_by_project", Duration::from_millis(3));
+        metrics.record("list_flags_by_project", Duration::from_millis(600));
+        metrics.record("list_flags_by_project", Duration::from_secs(9));
+
+        let text = metrics.render();
+        let line = |suffix: &str| format!("flaglite_storage_operation_duration_seconds_{suffix}");
+        assert!(text.contains(&format!(
+            "{} 0\n",
+            line("bucket{operation=\"list_flags_by_project\",le=\"0.0025\"}")
+        )));
+        assert!(text.contains(&format!(
+            "{} 1\n",
+            line("bucket{operation=\"list_flags_by_project\",le=\"0.005\"}")
+        )));
+        assert!(text.contains(&format!(
+            "{} 2\n",
+            line("bucket{operation=\"list_flags_by_project\",le=\"1\"}")
+        )));
+        assert!(text.contains(&format!(
+            "{} 3\n",
+            line("bucket{operation=\"list_flags_by_project\",le=\"+Inf\"}")
+        )));
+        assert!(text.contains(&format!(
+            "{} 3\n",
+            line("count{operation=\"list_flags_by_project\"}")
+        )));
+    }
+}
@@ -1,16 +1,57 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use std::collections::HashMap;
 use std::sync::Arc;
-use uuid::Uuid;
 
+use crate::bus::EventBus;
+use crate::config::SignupMode;
+use crate::events::EvaluationEvents;
+use crate::guards::HealthChecks;
+use crate::handlers::cli::{CliEnvironment, CliProject};
+use crate::jobs::JobMetrics;
+use crate::jwt::JwtKeys;
+use crate::lockout::LoginThrottle;
+use crate::mailer::Mailer;
+use crate::maintenance::Maintenance;
+use crate::metrics::StorageMetrics;
+use crate::public::PublicLimiter;
+use crate::quota::QuotaLimiter;
+use crate::retention::RetentionPolicy;
 use crate::storage::Storage;
+use crate::usage::UsageRecorder;
 
 #[derive(Clone)]
 pub struct AppState {
     pub storage: Arc<dyn Storage>,
-    pub jwt_secret: String,
+    /// Secrets session tokens are signed and verified with
+    pub jwt_keys: Arc<JwtKeys>,
+    pub usage: Arc<UsageRecorder>,
+    pub signup_mode: SignupMode,
+    /// Evaluation event export, when EVALUATION_SINK is set
+    pub events: Option<EvaluationEvents>,
+    pub quotas: Arc<QuotaLimiter>,
+    /// ADMIN_TOKEN, when the admin API is enabled
+    pub admin_token: Option<String>,
+    /// Sends password reset and verification emails
+    pub mailer: Arc<dyn Mailer>,
+    /// Read-only maintenance mode, checked by every route that writes
+    pub maintenance: Arc<Maintenance>,
+    /// Failed login counts for brute-force lockout
+    pub logins: Arc<LoginThrottle>,
+    /// Take the client IP from X-Forwarded-For (TRUST_PROXY)
+    pub trust_proxy: bool,
+    /// Run counts of the background jobs
+    pub jobs: Arc<JobMetrics>,
+    /// Durations of storage operations, served at /metrics
+    pub storage_metrics: Arc<StorageMetrics>,
+    /// Flag changes and audit events for WebSocket subscribers
+    pub bus: EventBus,
+    /// Request counts for keyless evaluation of public environments
+    pub public: Arc<PublicLimiter>,
+    /// How long flag history and evaluation counts are kept
+    pub retention: RetentionPolicy,
+    /// Polls guards' health checks, limited to GUARD_ALLOWED_HOSTS if set
+    pub health_checks: HealthChecks,
 }
 
 // ============ User ============
@@ -21,6 +62,12 @@ pub struct User {
     pub username: String,
     pub password_hash: String,
     pub email: Option<String>,
+    /// When `email` was confirmed by redeeming a verification token
+    pub email_verified_at: Option<DateTime<Utc>>,
+    /// Sessions (JWTs) are only valid for the epoch they were issued in;
+    /// bumping it, as a password reset does, signs all of them out
+    #[serde(default)]
+    pub session_epoch: i64,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -30,6 +77,7 @@ pub struct UserResponse {
     pub id: String,
     pub username: String,
     pub email: Option<String>,
+    pub email_verified: bool,
     pub created_at: DateTime<Utc>,
 }
 
@@ -39,13 +87,86 @@ impl From<User> for UserResponse {
             id: user.id,
             username: user.username,
             email: user.email,
+            email_verified: user.email_verified_at.is_some(),
             created_at: user.created_at,
         }
     }
 }
 
+/// A user swapping one username for another. The old name stays reserved
+/// for them and still finds them, so audit entries made under it can be
+/// attributed
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct UsernameChange {
+    #[serde(skip)]
+    pub user_id: String,
+    pub old_username: String,
+    pub new_username: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Defaults the CLI applies when the user logs in, so every machine they
+/// use starts out the same
+#[derive(Debug, Clone, Default, Serialize, Deserialize, FromRow)]
+pub struct UserPreferences {
+    #[serde(skip)]
+    pub user_id: String,
+    /// Default project, stored by ID
+    pub project_id: Option<String>,
+    pub environment: Option<String>,
+    pub display_name: Option<String>,
+    /// CLI output format: pretty, json, csv or tsv
+    pub format: Option<String>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
 // ============ API Key ============
 
+/// What a user API key may do; each scope includes the ones before it.
+/// Sessions (JWTs) can do everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyScope {
+    /// Requests that don't change anything
+    Read,
+    /// Changes to projects, environments, flags and templates
+    Write,
+    /// Also API keys, invites, organizations and account settings
+    #[default]
+    Admin,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ApiKeyScope::Read => "read",
+            ApiKeyScope::Write => "write",
+            ApiKeyScope::Admin => "admin",
+        }
+    }
+}
+
+impl std::fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ApiKeyScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "read" => Ok(ApiKeyScope::Read),
+            "write" => Ok(ApiKeyScope::Write),
+            "admin" => Ok(ApiKeyScope::Admin),
+            _ => Err(format!(
+                "Unknown scope: {s}. Use 'read', 'write', or 'admin'."
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct ApiKey {
     pub id: String,
@@ -55,24 +176,40 @@ pub struct ApiKey {
     pub name: Option<String>,
     pub created_at: DateTime<Utc>,
     pub revoked_at: Option<DateTime<Utc>>,
+    pub org_id: Option<String>, // Set for org-scoped keys, which only reach that org's projects
+    /// read, write or admin (see ApiKeyScope); keys from before scopes are admin
+    pub scope: String,
+}
+
+impl ApiKey {
+    /// An unknown stored scope grants the least
+    pub fn scope(&self) -> ApiKeyScope {
+        self.scope.parse().unwrap_or(ApiKeyScope::Read)
+    }
 }
 
+/// An API key in listings; the key itself is never shown again
 #[derive(Debug, Serialize)]
-#[allow(dead_code)] // Kept for future use in API responses
 pub struct ApiKeyResponse {
     pub id: String,
     pub key_prefix: String,
     pub name: Option<String>,
+    pub scope: ApiKeyScope,
+    pub org_id: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
 }
 
 impl From<ApiKey> for ApiKeyResponse {
     fn from(key: ApiKey) -> Self {
         ApiKeyResponse {
+            scope: key.scope(),
             id: key.id,
             key_prefix: key.key_prefix,
             name: key.name,
+            org_id: key.org_id,
             created_at: key.created_at,
+            revoked_at: key.revoked_at,
         }
     }
 }
@@ -84,7 +221,108 @@ pub struct ApiKeyCreatedResponse {
     pub key: String, // Full key - only shown once
     pub key_prefix: String,
     pub name: Option<String>,
+    pub scope: ApiKeyScope,
+    pub created_at: DateTime<Utc>,
+}
+
+// ============ Invite ============
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Invite {
+    pub code: String,               // inv_*
+    pub created_by: Option<String>, // None when created with `flaglite-api invite`
     pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_by: Option<String>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InviteResponse {
+    pub code: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl From<Invite> for InviteResponse {
+    fn from(invite: Invite) -> Self {
+        InviteResponse {
+            code: invite.code,
+            expires_at: invite.expires_at,
+        }
+    }
+}
+
+// ============ Account tokens ============
+
+pub const TOKEN_PASSWORD_RESET: &str = "password_reset";
+pub const TOKEN_EMAIL_VERIFICATION: &str = "email_verification";
+
+/// Single-use token emailed to a user; only its hash is stored
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct AccountToken {
+    pub token_hash: String,
+    pub user_id: String,
+    /// TOKEN_PASSWORD_RESET or TOKEN_EMAIL_VERIFICATION
+    pub purpose: String,
+    /// Address the token was sent to; verification only confirms this one
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+// ============ Organization ============
+
+pub const ROLE_OWNER: &str = "owner";
+pub const ROLE_MEMBER: &str = "member";
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Organization {
+    pub id: String,
+    pub name: String,
+    pub personal: bool, // Created with the user; can't gain members
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct OrgMember {
+    pub org_id: String,
+    pub user_id: String,
+    pub role: String, // owner | member
+    pub created_at: DateTime<Utc>,
+}
+
+/// Access to a single project, outside its organization, given by redeeming
+/// an invite link
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ProjectMember {
+    pub project_id: String,
+    pub user_id: String,
+    pub role: String, // owner | member
+    pub created_at: DateTime<Utc>,
+}
+
+/// A reusable link that shares a project with whoever redeems it before it
+/// expires or is revoked; only its token's hash is stored
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct InviteLink {
+    pub id: String,
+    pub project_id: String,
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    /// Role redeemers get in the project: owner or member
+    pub role: String,
+    /// How many users joined with it
+    pub uses: i64,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Every user gets a personal org; its id is derived so migrations can backfill it in SQL
+pub fn personal_org_id(user_id: &str) -> String {
+    format!("personal_{user_id}")
 }
 
 // ============ Project ============
@@ -92,73 +330,246 @@ pub struct ApiKeyCreatedResponse {
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
 pub struct Project {
     pub id: String,
-    pub user_id: String,
+    pub user_id: String,        // Creator
+    pub org_id: Option<String>, // None only for rows not yet backfilled
     pub name: String,
+    pub slug: String,    // Unique among the creator's projects
     pub api_key: String, // ffl_proj_*
     pub created_at: DateTime<Utc>,
+    /// Changes to protected environments must say why
+    pub require_change_reason: bool,
 }
 
-#[derive(Debug, Serialize)]
-pub struct ProjectResponse {
-    pub id: Uuid,
+/// Evaluation traffic limits for a project, set by the instance admin
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ProjectQuota {
+    pub project_id: String,
+    /// Across all of the project's SDK keys; None is unlimited
+    pub requests_per_minute: Option<i64>,
+    /// For any single SDK key; None is unlimited
+    pub per_key_requests_per_minute: Option<i64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Starting state of new flags per environment, stored per project and applied
+/// by `POST /v1/projects/:project_id/templates/:name/flags`
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FlagTemplate {
+    pub project_id: String,
+    /// Unique within the project, e.g. `kill-switch`
     pub name: String,
     pub description: Option<String>,
-    pub slug: String,
-    pub created_at: DateTime<Utc>,
+    /// State in environments `environments` doesn't name
+    pub default_enabled: bool,
+    pub default_rollout: i32,
+    /// JSON object of environment name to `{"enabled": bool, "rollout": int}`
+    pub environments: String,
     pub updated_at: DateTime<Utc>,
 }
 
-impl From<Project> for ProjectResponse {
-    fn from(p: Project) -> Self {
-        let slug = p.name.to_lowercase().replace(' ', "-");
-        ProjectResponse {
-            id: Uuid::parse_str(&p.id).unwrap_or_else(|_| Uuid::nil()),
-            name: p.name,
-            description: None,
-            slug,
-            created_at: p.created_at,
-            updated_at: p.created_at, // API doesn't track updated_at separately
-        }
-    }
+/// The last change to one flag key, for SDKs polling `GET /v1/changes`.
+/// Each change to a project's flags takes the project's next revision.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct FlagChange {
+    pub project_id: String,
+    pub flag_key: String,
+    pub revision: i64,
+    /// Kept after the flag is deleted, so pollers learn of it
+    pub deleted: bool,
+    pub changed_at: DateTime<Utc>,
 }
 
-// ============ Environment ============
+pub const CHANGE_PENDING: &str = "pending";
+pub const CHANGE_APPROVED: &str = "approved";
+pub const CHANGE_REJECTED: &str = "rejected";
 
+/// A proposed change to a flag in one environment, applied only once a
+/// project admin approves it
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
-pub struct Environment {
+pub struct ChangeRequest {
     pub id: String,
     pub project_id: String,
-    pub name: String,    // development, staging, production
-    pub api_key: String, // ffl_env_*
+    pub flag_key: String,
+    pub environment: String,
+    /// None leaves the flag's on/off state as it is
+    pub enabled: Option<bool>,
+    /// None leaves the rollout as it is
+    pub rollout_percentage: Option<i32>,
+    /// Version of the flag value when proposed; approval fails once it moves on
+    pub base_version: i64,
+    pub comment: Option<String>,
+    pub status: String, // pending | approved | rejected
+    pub proposed_by: String,
+    pub reviewed_by: Option<String>,
+    pub review_comment: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub reviewed_at: Option<DateTime<Utc>>,
 }
 
-#[derive(Debug, Serialize)]
-pub struct EnvironmentResponse {
+pub const RAMP_ACTIVE: &str = "active";
+pub const RAMP_PAUSED: &str = "paused";
+pub const RAMP_COMPLETED: &str = "completed";
+pub const RAMP_ABORTED: &str = "aborted";
+
+/// A plan to raise a flag's rollout in one environment by `step_percentage`
+/// every `interval_secs` until it reaches `target_percentage`, carried out by
+/// the `advance-ramps` job
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Ramp {
+    pub id: String,
+    pub project_id: String,
+    pub flag_id: String,
+    pub environment_id: String,
+    pub target_percentage: i32,
+    pub step_percentage: i32,
+    pub interval_secs: i64,
+    /// Rollout the ramp last set
+    pub current_percentage: i32,
+    pub status: String, // active | paused | completed | aborted
+    /// When an active ramp takes its next step
+    pub next_step_at: Option<DateTime<Utc>>,
+    /// Recorded as the reason for each step
+    pub reason: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub const GUARD_WATCHING: &str = "watching";
+pub const GUARD_PASSED: &str = "passed";
+pub const GUARD_TRIPPED: &str = "tripped";
+pub const GUARD_SUPERSEDED: &str = "superseded";
+pub const GUARD_CANCELLED: &str = "cancelled";
+
+/// A watch on one change to a flag in one environment, carried out by the
+/// `check-guards` job: if the health check at `url`, or a report pushed with
+/// the guard's token, says unhealthy within the window, the flag is put back
+/// to the revert state
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Guard {
     pub id: String,
+    pub project_id: String,
+    pub flag_id: String,
+    pub environment_id: String,
+    /// The ramp guarded; the window starts once it stops running
+    pub ramp_id: Option<String>,
+    /// Health check polled while the guard watches
+    pub url: Option<String>,
+    /// SHA256 of the token unhealthy reports are pushed with
+    #[serde(skip_serializing)]
+    pub token_hash: String,
+    /// Flag value version the change produced; ignored for ramp guards
+    pub version: i64,
+    /// State the flag is put back to when the guard trips
+    pub revert_enabled: bool,
+    pub revert_rollout_percentage: i32,
+    pub revert_value: Option<String>,
+    pub window_secs: i64,
+    pub status: String, // watching | passed | tripped | superseded | cancelled
+    /// Why the guard tripped
+    pub message: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+/// Size of the stored data, for the instance admin
+#[derive(Debug, Clone, Serialize)]
+pub struct StorageStats {
+    /// `sqlite`, `postgres` or `memory`
+    pub backend: &'static str,
+    pub users: i64,
+    pub projects: i64,
+    pub environments: i64,
+    pub flags: i64,
+    /// On-disk size of the database; None for in-memory storage
+    pub database_bytes: Option<i64>,
+}
+
+/// Flag counts of a project, aggregated by the database
+#[derive(Debug, Clone)]
+pub struct ProjectSummary {
+    pub flags: i64,
+    /// Latest flag change, including deletions; None if flags never changed
+    pub last_changed_at: Option<DateTime<Utc>>,
+    /// In the environments' sort order
+    pub environments: Vec<EnvironmentSummary>,
+}
+
+/// Flag counts of one environment, as flags evaluate there (flags without a
+/// value count by their default, expired flags as off)
+#[derive(Debug, Clone, FromRow)]
+pub struct EnvironmentSummary {
     pub name: String,
-    pub slug: String,
+    /// SDK keys aren't rotated, so the environment's key is as old as it is
+    pub created_at: DateTime<Utc>,
+    pub enabled: i64,
+    /// On for only part of the users (rollout below 100%)
+    pub partial_rollout: i64,
+    /// Latest write to a flag value in the environment
+    pub last_changed_at: Option<DateTime<Utc>>,
+}
+
+/// Ed25519 key that signs an environment's flag snapshots for client-side SDKs
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SigningKey {
+    /// Published as the JWS `kid`
+    pub id: String,
+    pub environment_id: String,
+    /// Base64url seed; never leaves the server
+    pub private_key: String,
+    /// Base64url public key, as in the JWK `x` member
+    pub public_key: String,
+    pub created_at: DateTime<Utc>,
+    /// Set when the key is rotated out; it stays published for a grace period
+    pub retired_at: Option<DateTime<Utc>>,
+}
+
+// ============ Environment ============
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Environment {
+    pub id: String,
     pub project_id: String,
-    pub api_key: String,
+    pub name: String,          // development, staging, production
+    pub api_key: String,       // ffl_env_*
+    pub protected: bool,       // changes require explicit confirmation
+    pub frozen: bool,          // flag values can't change until unfrozen
+    pub sort_order: i32,       // position in lists, lowest first
+    pub color: Option<String>, // #rrggbb
+    pub emoji: Option<String>,
+    /// Seconds SDK responses may be cached; None for each endpoint's default
+    pub cache_max_age: Option<i32>,
+    /// Seconds past `cache_max_age` a cache may serve while it refetches
+    pub cache_stale_while_revalidate: Option<i32>,
+    /// `ffl_pub_*`; set while the environment is public, and its public
+    /// flags can be evaluated without a key
+    pub public_client_id: Option<String>,
+    /// Flag changes go through change requests; only owners change this
     #[serde(default)]
-    pub is_production: bool,
+    pub require_approval: bool,
     pub created_at: DateTime<Utc>,
 }
 
-impl From<Environment> for EnvironmentResponse {
-    fn from(e: Environment) -> Self {
-        let slug = e.name.to_lowercase().replace(' ', "-");
-        let is_production = e.name.to_lowercase() == "production";
-        EnvironmentResponse {
-            id: e.id,
-            name: e.name,
-            slug,
-            project_id: e.project_id,
-            api_key: e.api_key,
-            is_production,
-            created_at: e.created_at,
-        }
-    }
+/// An environment kept after deletion so it can be restored, until
+/// `purge_after`. Only what a restore brings back is kept: the environment,
+/// its current flag values and its allowlists and denylists.
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct DeletedEnvironment {
+    /// The environment's ID, which a restore reuses
+    pub id: String,
+    pub project_id: String,
+    pub name: String,
+    /// The `Environment` as JSON text; restored with the same keys
+    pub environment: String,
+    /// Its `FlagValue`s, as a JSON array
+    pub flag_values: String,
+    /// Its `ListedUser`s, as a JSON array
+    pub listed_users: String,
+    /// Username of whoever deleted it
+    pub deleted_by: String,
+    pub deleted_at: DateTime<Utc>,
+    pub purge_after: DateTime<Utc>,
 }
 
 // ============ Flag ============
@@ -171,6 +582,41 @@ pub struct Flag {
     pub name: String,
     pub description: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// After this the flag evaluates to off everywhere, until it is deleted
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Person or team to ask about the flag (e.g. during stale-flag cleanup)
+    pub owner: Option<String>,
+    pub repository: Option<String>,
+    /// Where in `repository` the flag is read
+    pub code_path: Option<String>,
+    /// boolean, string, number or json
+    pub flag_type: String,
+    /// JSON Schema (as JSON text) that values of a json flag must match
+    pub json_schema: Option<String>,
+    /// Whether the flag is on in environments where it has no value yet
+    pub default_enabled: bool,
+    /// Served when on and the environment sets no value, as JSON text
+    pub default_value: Option<String>,
+    /// Evaluable through public environments without a key; off by default
+    pub public: bool,
+}
+
+impl Flag {
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|at| at <= now)
+    }
+
+    pub fn schema(&self) -> Option<serde_json::Value> {
+        self.json_schema
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+    }
+
+    pub fn parsed_default_value(&self) -> Option<serde_json::Value> {
+        self.default_value
+            .as_deref()
+            .and_then(|s| serde_json::from_str(s).ok())
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
@@ -180,40 +626,181 @@ pub struct FlagValue {
     pub environment_id: String,
     pub enabled: bool,
     pub rollout_percentage: i32,
+    /// Value served when enabled, as JSON text; None for plain on/off flags
+    pub value: Option<String>,
     pub updated_at: DateTime<Utc>,
+    /// Starts at 1 and increases by one on every write
+    pub version: i64,
+    /// Why this version was written, when the writer said
+    pub reason: Option<String>,
 }
 
-// Kept for future use
-#[allow(dead_code)]
-#[derive(Debug, Serialize)]
-pub struct FlagEnvironmentValue {
-    pub enabled: bool,
-    pub rollout: i32,
+impl FlagValue {
+    pub fn parsed_value(&self) -> Option<serde_json::Value> {
+        self.value
+            .as_deref()
+            .and_then(|v| serde_json::from_str(v).ok())
+    }
 }
 
-// Kept for future use
-#[allow(dead_code)]
-#[derive(Debug, Serialize)]
-pub struct FlagResponse {
-    pub key: String,
-    pub name: String,
-    pub description: Option<String>,
-    pub environments: HashMap<String, FlagEnvironmentValue>,
+/// What deciding whether a flag is on in one environment needs, read in a
+/// single query; `enabled` and `rollout_percentage` are None when the flag
+/// has no value there
+#[derive(Debug, Clone, FromRow)]
+pub struct FlagSwitch {
+    pub flag_id: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub default_enabled: bool,
+    pub enabled: Option<bool>,
+    pub rollout_percentage: Option<i32>,
+}
+
+impl FlagSwitch {
+    pub fn new(flag: &Flag, value: Option<&FlagValue>) -> Self {
+        Self {
+            flag_id: flag.id.clone(),
+            expires_at: flag.expires_at,
+            default_enabled: flag.default_enabled,
+            enabled: value.map(|v| v.enabled),
+            rollout_percentage: value.map(|v| v.rollout_percentage),
+        }
+    }
+
+    /// The flag's value in the environment, without the served value
+    pub fn rollout_value(&self) -> Option<RolloutValue> {
+        Some(RolloutValue {
+            enabled: self.enabled?,
+            rollout_percentage: self.rollout_percentage?,
+            value: None,
+            allowlist: Vec::new(),
+            denylist: Vec::new(),
+        })
+    }
+}
+
+pub const LIST_ALLOW: &str = "allow";
+pub const LIST_DENY: &str = "deny";
+
+/// A user on a flag's allowlist or denylist in one environment; a user is on
+/// at most one of the two
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ListedUser {
+    pub flag_id: String,
+    pub environment_id: String,
+    pub user_id: String,
+    pub list: String, // allow | deny
+    pub created_at: DateTime<Utc>,
+}
+
+// ============ Usage ============
+
+/// Evaluations of one flag in one environment on one (UTC) day
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct EvaluationCount {
+    pub flag_id: String,
+    pub environment_id: String,
+    pub day: NaiveDate,
+    pub count: i64,
+    pub last_evaluated_at: DateTime<Utc>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct FlagEvaluationResponse {
     pub key: String,
     pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
 }
 
-// Kept for future use
-#[allow(dead_code)]
-#[derive(Debug, Serialize)]
-pub struct FlagToggleResponse {
-    pub key: String,
-    pub environment: String,
+/// The parts of a flag value an evaluation needs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolloutValue {
     pub enabled: bool,
+    pub rollout_percentage: i32,
+    /// Served to users the flag is on for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    /// Users the flag is on for while enabled, whatever their bucket; sorted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowlist: Vec<String>,
+    /// Users the flag is off for; sorted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denylist: Vec<String>,
+}
+
+impl RolloutValue {
+    /// Add the users on the flag's lists in the environment
+    pub fn with_listed_users(mut self, users: impl IntoIterator<Item = ListedUser>) -> Self {
+        for user in users {
+            match user.list.as_str() {
+                LIST_ALLOW => self.allowlist.push(user.user_id),
+                _ => self.denylist.push(user.user_id),
+            }
+        }
+        // Sorted here rather than by the database, whose collation may differ
+        self.allowlist.sort();
+        self.denylist.sort();
+        self
+    }
+}
+
+impl From<&FlagValue> for RolloutValue {
+    fn from(fv: &FlagValue) -> Self {
+        RolloutValue {
+            enabled: fv.enabled,
+            rollout_percentage: fv.rollout_percentage,
+            value: fv.parsed_value(),
+            allowlist: Vec::new(),
+            denylist: Vec::new(),
+        }
+    }
+}
+
+/// A flag as served to relays; `value` is None if it has none in the environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagConfig {
+    pub key: String,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    pub value: Option<RolloutValue>,
+    /// Applies when `value` is None
+    #[serde(default)]
+    pub default_enabled: bool,
+    /// Served when the flag is on and its value sets none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<serde_json::Value>,
+}
+
+/// Everything needed to evaluate one environment's flags without the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagConfigResponse {
+    pub project_id: String,
+    pub environment_id: String,
+    pub flags: Vec<FlagConfig>,
+}
+
+/// Flags changed in the key's environment since the client's revision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagChangesResponse {
+    pub project_id: String,
+    pub environment_id: String,
+    /// Pass as `since` on the next poll
+    pub revision: i64,
+    /// True when `flags` is every flag rather than only the changed ones, and
+    /// the client should drop flags it holds that aren't listed
+    pub full: bool,
+    pub flags: Vec<FlagConfig>,
+    /// Keys of flags deleted since `since`
+    pub deleted: Vec<String>,
+}
+
+/// An environment's flag snapshot as a compact JWS, for SDKs on untrusted devices
+#[derive(Debug, Serialize)]
+pub struct SignedFlagConfigResponse {
+    /// Signed `FlagConfigResponse` plus `iat`; verify it against the signing keys
+    pub jws: String,
+    /// Key it was signed with
+    pub kid: String,
 }
 
 // ============ API Requests ============
@@ -223,6 +810,7 @@ pub struct SignupRequest {
     pub username: Option<String>, // Optional - auto-generated if not provided
     pub password: String,
     pub project_name: Option<String>,
+    pub invite_code: Option<String>, // Required when SIGNUP_MODE=invite
 }
 
 #[derive(Debug, Serialize)]
@@ -230,16 +818,14 @@ pub struct SignupResponse {
     pub user: UserResponse,
     pub api_key: ApiKeyCreatedResponse,
     pub token: String,
-    pub project: Option<ProjectResponse>,
-    pub environments: Option<Vec<EnvironmentResponse>>,
+    pub project: Option<CliProject>,
+    pub environments: Option<Vec<CliEnvironment>>,
 }
 
 #[derive(Debug, Serialize)]
 pub struct AuthResponse {
     pub token: String,
     pub user: UserResponse,
-    pub project: Option<ProjectResponse>,
-    pub environments: Option<Vec<EnvironmentResponse>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -251,27 +837,42 @@ pub struct LoginRequest {
 #[derive(Debug, Deserialize)]
 pub struct UpdateUserRequest {
     pub email: Option<String>,
+    pub username: Option<String>,
 }
 
-#[allow(dead_code)] // Kept for future SDK use
+/// Omitted fields are left alone; an empty string clears one
 #[derive(Debug, Deserialize)]
-pub struct CreateFlagRequest {
-    pub key: String,
-    pub name: String,
-    pub description: Option<String>,
+pub struct UpdatePreferencesRequest {
+    pub project_id: Option<String>,
+    pub environment: Option<String>,
+    pub display_name: Option<String>,
+    pub format: Option<String>,
 }
 
-#[allow(dead_code)] // Kept for future SDK use
 #[derive(Debug, Deserialize)]
-pub struct UpdateFlagValueRequest {
-    pub enabled: Option<bool>,
-    pub rollout_percentage: Option<i32>,
+pub struct CreateApiKeyRequest {
+    pub name: Option<String>,
+    /// `read`, `write` or `admin` (default)
+    pub scope: Option<String>,
 }
 
-#[allow(dead_code)] // Kept for future SDK use
 #[derive(Debug, Deserialize)]
-pub struct ToggleFlagQuery {
-    pub environment: String,
+pub struct PasswordResetRequest {
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+    /// Also revoke the user's API keys
+    #[serde(default)]
+    pub revoke_api_keys: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
 }
 
 #[allow(dead_code)] // Kept for future SDK use
@@ -280,6 +881,13 @@ pub struct EvaluateFlagQuery {
     pub user_id: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct FlagChangesQuery {
+    /// `revision` from the previous poll; 0 or absent for everything
+    #[serde(default)]
+    pub since: i64,
+}
+
 // ============ JWT Claims ============
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -288,6 +896,9 @@ pub struct Claims {
     pub username: String,
     pub exp: i64,
     pub iat: i64,
+    /// The user's `session_epoch` when the token was issued
+    #[serde(default)]
+    pub epoch: i64,
 }
 
 // ============ API Key Types ============
@@ -319,6 +930,29 @@ pub fn generate_env_api_key() -> String {
     format!("ffl_env_{}", generate_random_alphanumeric(32))
 }
 
+/// Public client ID, which identifies an environment but grants nothing
+pub fn generate_public_client_id() -> String {
+    format!("ffl_pub_{}", generate_random_alphanumeric(24))
+}
+
+pub fn generate_invite_code() -> String {
+    format!("inv_{}", generate_random_alphanumeric(24))
+}
+
+pub fn generate_account_token() -> String {
+    format!("tok_{}", generate_random_alphanumeric(32))
+}
+
+/// Token a guard's unhealthy reports are pushed with
+pub fn generate_guard_token() -> String {
+    format!("ffl_guard_{}", generate_random_alphanumeric(32))
+}
+
+/// Token of a project invite link
+pub fn generate_invite_link_token() -> String {
+    format!("ffl_join_{}", generate_random_alphanumeric(32))
+}
+
 /// Check if key is a user API key (flg_ prefix)
 pub fn is_user_api_key(key: &str) -> bool {
     key.starts_with("flg_")
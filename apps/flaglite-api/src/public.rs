@@ -0,0 +1,127 @@
+//! Rate limits for anonymous evaluation through public environments
+//!
+//! Public evaluation needs no key, so it's limited by what a caller can't
+//! hide: requests are counted per public client ID and per client IP, in
+//! one-minute windows. Like quotas, counts are kept in process memory, so
+//! each API instance enforces its limits independently.
+
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+
+use crate::error::{AppError, Result};
+
+/// Requests per minute allowed through public environments; 0 is unlimited
+#[derive(Debug, Clone, Copy)]
+pub struct PublicRateLimit {
+    /// Across every caller of one public client ID
+    pub per_client: u32,
+    /// From one IP, across client IDs
+    pub per_ip: u32,
+}
+
+impl Default for PublicRateLimit {
+    fn default() -> Self {
+        Self {
+            per_client: 600,
+            per_ip: 60,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    /// Minute the counts belong to (seconds since the epoch / 60)
+    minute: i64,
+    /// Requests this minute, keyed by `client:<id>` and `ip:<address>`
+    counts: HashMap<String, u32>,
+}
+
+pub struct PublicLimiter {
+    limit: PublicRateLimit,
+    counters: Mutex<Counters>,
+}
+
+impl PublicLimiter {
+    pub fn new(limit: PublicRateLimit) -> Self {
+        Self {
+            limit,
+            counters: Mutex::new(Counters::default()),
+        }
+    }
+
+    /// Count a request for `client_id`, failing once a limit is used up
+    pub fn check(&self, client_id: &str, ip: Option<IpAddr>, now: DateTime<Utc>) -> Result<()> {
+        let minute = now.timestamp().div_euclid(60);
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        if counters.minute != minute {
+            counters.minute = minute;
+            counters.counts.clear();
+        }
+
+        let mut buckets = vec![(format!("client:{client_id}"), self.limit.per_client)];
+        if let Some(ip) = ip {
+            buckets.push((format!("ip:{ip}"), self.limit.per_ip));
+        }
+        buckets.retain(|(_, limit)| *limit > 0);
+
+        // Check every limit before counting, so a rejected request uses none of them
+        for (bucket, limit) in &buckets {
+            if counters.counts.get(bucket).copied().unwrap_or(0) >= *limit {
+                return Err(AppError::QuotaExceeded {
+                    retry_after: (60 - now.timestamp().rem_euclid(60)) as u64,
+                });
+            }
+        }
+        for (bucket, _) in buckets {
+            *counters.counts.entry(bucket).or_default() += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_limits_per_client_and_per_ip() {
+        let limiter = PublicLimiter::new(PublicRateLimit {
+            per_client: 3,
+            per_ip: 2,
+        });
+        let now = Utc.with_ymd_and_hms(2026, 1, 1, 12, 0, 15).unwrap();
+        let one: IpAddr = "203.0.113.1".parse().unwrap();
+        let two: IpAddr = "203.0.113.2".parse().unwrap();
+
+        limiter.check("ffl_pub_a", Some(one), now).unwrap();
+        limiter.check("ffl_pub_a", Some(one), now).unwrap();
+        assert!(matches!(
+            limiter.check("ffl_pub_b", Some(one), now),
+            Err(AppError::QuotaExceeded { retry_after: 45 })
+        ));
+
+        // The rejected request didn't count against ffl_pub_b
+        limiter.check("ffl_pub_a", Some(two), now).unwrap();
+        assert!(limiter.check("ffl_pub_a", Some(two), now).is_err());
+        limiter.check("ffl_pub_b", Some(two), now).unwrap();
+
+        // A new minute starts over
+        let later = now + chrono::Duration::minutes(1);
+        limiter.check("ffl_pub_a", Some(one), later).unwrap();
+    }
+
+    #[test]
+    fn test_zero_is_unlimited() {
+        let limiter = PublicLimiter::new(PublicRateLimit {
+            per_client: 0,
+            per_ip: 0,
+        });
+        let now = Utc::now();
+        for _ in 0..100 {
+            limiter.check("ffl_pub_a", None, now).unwrap();
+        }
+    }
+}
@@ -0,0 +1,280 @@
+//! Per-minute request quotas for SDK keys
+//!
+//! The instance admin sets a project's quota through the admin API. Requests
+//! made with one of the project's environment or project keys count against
+//! the project limit and, when set, a per-key limit. Counts are kept in
+//! process memory in one-minute windows, so each API instance enforces its
+//! limits independently.
+
+use axum::{
+    extract::{Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::auth::hash_api_key;
+use crate::cache::TtlCache;
+use crate::error::{AppError, Result};
+use crate::models::{AppState, ProjectQuota};
+use crate::storage::Storage;
+
+/// How long a key's project and quota are reused before being looked up again
+const PLAN_TTL: Duration = Duration::from_secs(30);
+
+/// Keys whose plan is remembered at once
+const MAX_PLANS: usize = 10_000;
+
+/// The limits that apply to one SDK key
+#[derive(Clone)]
+struct Plan {
+    project_id: String,
+    quota: ProjectQuota,
+}
+
+#[derive(Default)]
+struct Counters {
+    /// Minute the counts belong to (seconds since the epoch / 60)
+    minute: i64,
+    /// Requests this minute, keyed by `project:<id>` and `key:<hash>`
+    counts: HashMap<String, i64>,
+}
+
+pub struct QuotaLimiter {
+    /// Keyed by SDK key hash; None for keys without a quota (or unknown keys)
+    plans: TtlCache<String, Option<Plan>>,
+    counters: Mutex<Counters>,
+}
+
+impl Default for QuotaLimiter {
+    fn default() -> Self {
+        Self {
+            plans: TtlCache::new(PLAN_TTL, MAX_PLANS),
+            counters: Mutex::new(Counters::default()),
+        }
+    }
+}
+
+impl QuotaLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a request made with `token`, failing once a limit is used up
+    pub async fn check(
+        &self,
+        storage: &dyn Storage,
+        token: &str,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        let key_hash = hash_api_key(token);
+        let plan = match self.plans.get(&key_hash) {
+            Some(plan) => plan,
+            None => {
+                let plan = load_plan(storage, token).await?;
+                self.plans.insert(key_hash.clone(), plan.clone());
+                plan
+            }
+        };
+
+        match plan {
+            Some(plan) => self.count(&plan, &key_hash, now),
+            None => Ok(()),
+        }
+    }
+
+    /// Forget cached quotas for a project, so a change applies immediately
+    pub fn invalidate(&self, project_id: &str) {
+        self.plans
+            .retain(|_, plan| plan.as_ref().is_none_or(|p| p.project_id != project_id));
+    }
+
+    fn count(&self, plan: &Plan, key_hash: &str, now: DateTime<Utc>) -> Result<()> {
+        let minute = now.timestamp().div_euclid(60);
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        if counters.minute != minute {
+            counters.minute = minute;
+            counters.counts.clear();
+        }
+
+        let buckets = [
+            (
+                format!("project:{}", plan.project_id),
+                plan.quota.requests_per_minute,
+            ),
+            (
+                format!("key:{key_hash}"),
+                plan.quota.per_key_requests_per_minute,
+            ),
+        ];
+
+        // Check every limit before counting, so a rejected request uses none of them
+        for (bucket, limit) in &buckets {
+            if let Some(limit) = limit {
+                if counters.counts.get(bucket).copied().unwrap_or(0) >= *limit {
+                    return Err(AppError::QuotaExceeded {
+                        retry_after: (60 - now.timestamp().rem_euclid(60)) as u64,
+                    });
+                }
+            }
+        }
+        for (bucket, limit) in buckets {
+            if limit.is_some() {
+                *counters.counts.entry(bucket).or_default() += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The project behind an SDK key and its quota, if it has one
+async fn load_plan(storage: &dyn Storage, token: &str) -> Result<Option<Plan>> {
+    let project_id = if token.starts_with("ffl_env_") {
+        storage
+            .get_environment_by_api_key(token)
+            .await?
+            .map(|env| env.project_id)
+    } else {
+        storage
+            .get_project_by_api_key(token)
+            .await?
+            .map(|project| project.id)
+    };
+
+    // Unknown keys are left for the handler to reject
+    let Some(project_id) = project_id else {
+        return Ok(None);
+    };
+
+    Ok(storage
+        .get_project_quota(&project_id)
+        .await?
+        .filter(|q| q.requests_per_minute.is_some() || q.per_key_requests_per_minute.is_some())
+        .map(|quota| Plan { project_id, quota }))
+}
+
+/// Middleware: reject SDK key requests over their project's quota with 429
+pub async fn enforce(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .filter(|t| t.starts_with("ffl_env_") || t.starts_with("ffl_proj_"));
+
+    if let Some(token) = token {
+        if let Err(e) = state
+            .quotas
+            .check(state.storage.as_ref(), token, Utc::now())
+            .await
+        {
+            return e.into_response();
+        }
+    }
+
+    next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Environment, Project};
+    use crate::storage::MemoryStorage;
+
+    async fn storage_with_quota(project: Option<i64>, per_key: Option<i64>) -> MemoryStorage {
+        let storage = MemoryStorage::new();
+        let now = Utc::now();
+        storage
+            .create_project(&Project {
+                id: "p1".to_string(),
+                user_id: "u1".to_string(),
+                org_id: None,
+                name: "Project".to_string(),
+                slug: "project".to_string(),
+                api_key: "ffl_proj_one".to_string(),
+                created_at: now,
+                require_change_reason: false,
+            })
+            .await
+            .unwrap();
+        for (id, key) in [("e1", "ffl_env_one"), ("e2", "ffl_env_two")] {
+            storage
+                .create_environment(&Environment {
+                    id: id.to_string(),
+                    project_id: "p1".to_string(),
+                    name: id.to_string(),
+                    api_key: key.to_string(),
+                    protected: false,
+                    frozen: false,
+                    sort_order: 0,
+                    color: None,
+                    emoji: None,
+                    cache_max_age: None,
+                    cache_stale_while_revalidate: None,
+                    public_client_id: None,
+                    require_approval: false,
+                    created_at: now,
+                })
+                .await
+                .unwrap();
+        }
+        storage
+            .set_project_quota(&ProjectQuota {
+                project_id: "p1".to_string(),
+                requests_per_minute: project,
+                per_key_requests_per_minute: per_key,
+                updated_at: now,
+            })
+            .await
+            .unwrap();
+        storage
+    }
+
+    #[tokio::test]
+    async fn test_project_limit_spans_keys() {
+        let storage = storage_with_quota(Some(3), None).await;
+        let limiter = QuotaLimiter::new();
+        let now = Utc::now();
+
+        for token in ["ffl_env_one", "ffl_env_two", "ffl_proj_one"] {
+            limiter.check(&storage, token, now).await.unwrap();
+        }
+        let err = limiter.check(&storage, "ffl_env_one", now).await;
+        assert!(matches!(err, Err(AppError::QuotaExceeded { .. })));
+
+        // A new minute starts a new window
+        let later = now + chrono::Duration::seconds(60);
+        limiter.check(&storage, "ffl_env_one", later).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_per_key_limit_and_invalidate() {
+        let storage = storage_with_quota(None, Some(1)).await;
+        let limiter = QuotaLimiter::new();
+        let now = Utc::now();
+
+        limiter.check(&storage, "ffl_env_one", now).await.unwrap();
+        assert!(limiter.check(&storage, "ffl_env_one", now).await.is_err());
+        limiter.check(&storage, "ffl_env_two", now).await.unwrap();
+
+        // Lifting the quota applies without waiting for the cache
+        storage
+            .set_project_quota(&ProjectQuota {
+                project_id: "p1".to_string(),
+                requests_per_minute: None,
+                per_key_requests_per_minute: None,
+                updated_at: now,
+            })
+            .await
+            .unwrap();
+        limiter.invalidate("p1");
+        limiter.check(&storage, "ffl_env_one", now).await.unwrap();
+
+        // Unknown keys pass through to the handler
+        limiter.check(&storage, "ffl_env_nope", now).await.unwrap();
+    }
+}
@@ -0,0 +1,438 @@
+//! Rollout ramps
+//!
+//! A ramp raises a flag's rollout in one environment a step at a time. The
+//! first step is taken when the ramp is created; the `advance-ramps` job takes
+//! each later one once its interval has passed, until the target is reached.
+//! Pausing stops the steps and resuming waits a full interval before the next.
+//! Aborting ends the ramp, leaving the rollout where it got to. A frozen
+//! environment, or the server in read-only mode, holds ramps where they are.
+//!
+//! A ramp never overrides a change made by hand: if the flag's rollout isn't
+//! what the ramp last set when its next step is due, the ramp pauses itself.
+//! Resuming it carries on from the rollout the flag has then.
+
+use anyhow::Context;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::bus;
+use crate::error::Result;
+use crate::handlers::cli::{because, record_changes};
+use crate::models::{
+    AppState, Environment, Flag, FlagValue, Ramp, RAMP_ACTIVE, RAMP_COMPLETED, RAMP_PAUSED,
+};
+
+/// How often the `advance-ramps` job looks for due steps
+pub const ADVANCE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Shortest and longest time a ramp can wait between steps
+pub const MIN_INTERVAL_SECS: i64 = 60;
+pub const MAX_INTERVAL_SECS: i64 = 7 * 24 * 60 * 60;
+
+/// Rollout after one step of `step` from `current`, stopping at `target`
+pub fn next_percentage(current: i32, step: i32, target: i32) -> i32 {
+    current.saturating_add(step).min(target)
+}
+
+/// The rollout a flag value gives; a disabled flag reaches nobody
+pub fn current_percentage(value: Option<&FlagValue>) -> i32 {
+    value
+        .filter(|fv| fv.enabled)
+        .map_or(0, |fv| fv.rollout_percentage)
+}
+
+/// Whether a ramp is still active or paused, rather than over
+pub fn is_running(ramp: &Ramp) -> bool {
+    ramp.status == RAMP_ACTIVE || ramp.status == RAMP_PAUSED
+}
+
+/// When a ramp at `percentage` takes its next step; None once it's done
+pub fn next_step_at(ramp: &Ramp, percentage: i32, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let interval = ramp.interval_secs.min(MAX_INTERVAL_SECS);
+    (percentage < ramp.target_percentage).then(|| now + ChronoDuration::seconds(interval))
+}
+
+/// Turn the flag on at `percentage` in the environment. Returns false,
+/// writing nothing, if the value changed since `existing` was read.
+pub async fn set_rollout(
+    state: &AppState,
+    flag: &Flag,
+    environment: &Environment,
+    existing: Option<FlagValue>,
+    percentage: i32,
+    reason: Option<String>,
+    now: DateTime<Utc>,
+) -> Result<bool> {
+    let value = match existing {
+        Some(fv) => FlagValue {
+            enabled: true,
+            rollout_percentage: percentage,
+            updated_at: now,
+            version: fv.version + 1,
+            reason,
+            ..fv
+        },
+        None => FlagValue {
+            id: Uuid::new_v4().to_string(),
+            flag_id: flag.id.clone(),
+            environment_id: environment.id.clone(),
+            enabled: true,
+            rollout_percentage: percentage,
+            value: None,
+            updated_at: now,
+            version: 1,
+            reason,
+        },
+    };
+    if !state.storage.save_flag_values(&[value]).await? {
+        return Ok(false);
+    }
+    record_changes(
+        state,
+        &flag.project_id,
+        std::slice::from_ref(&flag.key),
+        false,
+    )
+    .await?;
+    Ok(true)
+}
+
+/// The `advance-ramps` job: take every step that's due. Ramps wait while
+/// the server is read-only
+pub async fn advance_job(state: AppState) -> anyhow::Result<()> {
+    if state.maintenance.is_read_only() {
+        return Ok(());
+    }
+    advance(&state, Utc::now())
+        .await
+        .context("Failed to advance ramps")
+}
+
+/// Take the next step of each ramp due by `now`. A ramp that can't step is
+/// logged and tried again on the next run.
+pub async fn advance(state: &AppState, now: DateTime<Utc>) -> Result<()> {
+    for ramp in state.storage.list_due_ramps(now).await? {
+        let id = ramp.id.clone();
+        if let Err(e) = step(state, ramp, now).await {
+            tracing::warn!("Ramp {id} couldn't take its next step: {e}");
+        }
+    }
+    Ok(())
+}
+
+async fn step(state: &AppState, mut ramp: Ramp, now: DateTime<Utc>) -> Result<()> {
+    let (Some(flag), Some(environment)) = (
+        state.storage.get_flag_by_id(&ramp.flag_id).await?,
+        state
+            .storage
+            .get_environment_by_id(&ramp.environment_id)
+            .await?,
+    ) else {
+        return Ok(());
+    };
+    // A frozen environment holds the ramp until it's unfrozen
+    if environment.frozen {
+        return Ok(());
+    }
+    let actor = state
+        .storage
+        .get_user_by_id(&ramp.created_by)
+        .await?
+        .map_or_else(|| ramp.created_by.clone(), |u| u.username);
+
+    let existing = state
+        .storage
+        .get_flag_value(&flag.id, &environment.id)
+        .await?;
+    let current = current_percentage(existing.as_ref());
+    if current != ramp.current_percentage {
+        ramp.status = RAMP_PAUSED.to_string();
+        ramp.next_step_at = None;
+        ramp.updated_at = now;
+        if state.storage.update_ramp(&ramp, RAMP_ACTIVE).await? {
+            bus::audit(
+                state,
+                &ramp.project_id,
+                "ramp.paused",
+                &actor,
+                format!(
+                    "Ramp {} in project {} paused: '{}' in {} was changed to {current}% by hand",
+                    ramp.id, ramp.project_id, flag.key, environment.name
+                ),
+            );
+        }
+        return Ok(());
+    }
+
+    let percentage = next_percentage(current, ramp.step_percentage, ramp.target_percentage);
+    if !set_rollout(
+        state,
+        &flag,
+        &environment,
+        existing,
+        percentage,
+        ramp.reason.clone(),
+        now,
+    )
+    .await?
+    {
+        // Changed under us; the next run sees the change and pauses
+        return Ok(());
+    }
+
+    ramp.current_percentage = percentage;
+    ramp.next_step_at = next_step_at(&ramp, percentage, now);
+    if ramp.next_step_at.is_none() {
+        ramp.status = RAMP_COMPLETED.to_string();
+    }
+    ramp.updated_at = now;
+    // Paused or aborted while stepping: the step stands, and resuming
+    // carries on from it
+    if !state.storage.update_ramp(&ramp, RAMP_ACTIVE).await? {
+        return Ok(());
+    }
+    let action = if ramp.next_step_at.is_none() {
+        "ramp.completed"
+    } else {
+        "ramp.stepped"
+    };
+    bus::audit(
+        state,
+        &ramp.project_id,
+        action,
+        &actor,
+        format!(
+            "Ramp {} in project {}: '{}' in {} rolled out to {percentage}% of {}%{}",
+            ramp.id,
+            ramp.project_id,
+            flag.key,
+            environment.name,
+            ramp.target_percentage,
+            because(&ramp.reason)
+        ),
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SignupMode;
+    use crate::jobs::JobMetrics;
+    use crate::jwt::JwtKeys;
+    use crate::lockout::{LockoutPolicy, LoginThrottle};
+    use crate::mailer::LogMailer;
+    use crate::maintenance::Maintenance;
+    use crate::models::{Project, User};
+    use crate::public::{PublicLimiter, PublicRateLimit};
+    use crate::quota::QuotaLimiter;
+    use crate::retention::RetentionPolicy;
+    use crate::storage::memory::MemoryStorage;
+    use crate::usage::UsageRecorder;
+    use std::sync::Arc;
+
+    fn state() -> AppState {
+        AppState {
+            storage: Arc::new(MemoryStorage::new()),
+            jwt_keys: Arc::new(JwtKeys::random()),
+            usage: Arc::new(UsageRecorder::new()),
+            signup_mode: SignupMode::Open,
+            events: None,
+            quotas: Arc::new(QuotaLimiter::new()),
+            admin_token: None,
+            mailer: Arc::new(LogMailer),
+            maintenance: Arc::new(Maintenance::new(false)),
+            logins: Arc::new(LoginThrottle::new(LockoutPolicy::default())),
+            trust_proxy: false,
+            jobs: Arc::new(JobMetrics::default()),
+            storage_metrics: Default::default(),
+            bus: Default::default(),
+            public: Arc::new(PublicLimiter::new(PublicRateLimit::default())),
+            retention: RetentionPolicy::default(),
+            health_checks: Default::default(),
+        }
+    }
+
+    /// A project with a flag on at 10% in production, ramping to 30% by 15
+    async fn ramping(state: &AppState, now: DateTime<Utc>) -> Ramp {
+        let storage = &state.storage;
+        storage
+            .create_user(&User {
+                id: "u1".to_string(),
+                username: "alice".to_string(),
+                password_hash: "hash".to_string(),
+                email: None,
+                email_verified_at: None,
+                session_epoch: 0,
+                created_at: now,
+                updated_at: now,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_project(&Project {
+                id: "p1".to_string(),
+                user_id: "u1".to_string(),
+                org_id: None,
+                name: "App".to_string(),
+                slug: "app".to_string(),
+                api_key: "ffl_proj_test".to_string(),
+                created_at: now,
+                require_change_reason: false,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_environment(&Environment {
+                id: "e1".to_string(),
+                project_id: "p1".to_string(),
+                name: "production".to_string(),
+                api_key: "ffl_env_production".to_string(),
+                protected: false,
+                frozen: false,
+                sort_order: 0,
+                color: None,
+                emoji: None,
+                cache_max_age: None,
+                cache_stale_while_revalidate: None,
+                public_client_id: None,
+                require_approval: false,
+                created_at: now,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_flag(&Flag {
+                id: "f1".to_string(),
+                project_id: "p1".to_string(),
+                key: "checkout".to_string(),
+                name: "checkout".to_string(),
+                description: None,
+                created_at: now,
+                expires_at: None,
+                owner: None,
+                repository: None,
+                code_path: None,
+                flag_type: "boolean".to_string(),
+                json_schema: None,
+                default_enabled: false,
+                default_value: None,
+                public: false,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_flag_value(&FlagValue {
+                id: "v1".to_string(),
+                flag_id: "f1".to_string(),
+                environment_id: "e1".to_string(),
+                enabled: true,
+                rollout_percentage: 10,
+                value: None,
+                updated_at: now,
+                version: 1,
+                reason: None,
+            })
+            .await
+            .unwrap();
+
+        let ramp = Ramp {
+            id: "r1".to_string(),
+            project_id: "p1".to_string(),
+            flag_id: "f1".to_string(),
+            environment_id: "e1".to_string(),
+            target_percentage: 30,
+            step_percentage: 15,
+            interval_secs: 600,
+            current_percentage: 10,
+            status: RAMP_ACTIVE.to_string(),
+            next_step_at: Some(now),
+            reason: Some("gradual launch".to_string()),
+            created_by: "u1".to_string(),
+            created_at: now,
+            updated_at: now,
+        };
+        storage.create_ramp(&ramp).await.unwrap();
+        ramp
+    }
+
+    async fn rollout(state: &AppState) -> (i32, i64) {
+        let fv = state
+            .storage
+            .get_flag_value("f1", "e1")
+            .await
+            .unwrap()
+            .unwrap();
+        (fv.rollout_percentage, fv.version)
+    }
+
+    #[tokio::test]
+    async fn test_ramp_steps_until_target() {
+        let state = state();
+        let now = Utc::now();
+        ramping(&state, now).await;
+
+        // Not due yet
+        advance(&state, now - ChronoDuration::seconds(1))
+            .await
+            .unwrap();
+        assert_eq!(rollout(&state).await, (10, 1));
+
+        advance(&state, now).await.unwrap();
+        assert_eq!(rollout(&state).await, (25, 2));
+        let ramp = state.storage.get_ramp("r1").await.unwrap().unwrap();
+        assert_eq!(ramp.status, RAMP_ACTIVE);
+        assert_eq!(ramp.current_percentage, 25);
+        assert_eq!(ramp.next_step_at, Some(now + ChronoDuration::seconds(600)));
+
+        // The last step stops at the target
+        advance(&state, now + ChronoDuration::seconds(600))
+            .await
+            .unwrap();
+        assert_eq!(rollout(&state).await, (30, 3));
+        let ramp = state.storage.get_ramp("r1").await.unwrap().unwrap();
+        assert_eq!(ramp.status, RAMP_COMPLETED);
+        assert_eq!(ramp.next_step_at, None);
+        let history = state.storage.list_flag_value_history("v1").await.unwrap();
+        assert_eq!(history[0].reason.as_deref(), Some("gradual launch"));
+    }
+
+    #[tokio::test]
+    async fn test_next_step_at_caps_interval() {
+        let state = state();
+        let now = Utc::now();
+        let mut ramp = ramping(&state, now).await;
+
+        // Ramps stored before the interval was bounded
+        ramp.interval_secs = i64::MAX;
+        assert_eq!(
+            next_step_at(&ramp, 10, now),
+            Some(now + ChronoDuration::seconds(MAX_INTERVAL_SECS))
+        );
+        assert_eq!(next_step_at(&ramp, 30, now), None);
+    }
+
+    #[tokio::test]
+    async fn test_ramp_pauses_after_change_by_hand() {
+        let state = state();
+        let now = Utc::now();
+        ramping(&state, now).await;
+
+        let mut fv = state
+            .storage
+            .get_flag_value("f1", "e1")
+            .await
+            .unwrap()
+            .unwrap();
+        fv.rollout_percentage = 0;
+        fv.version += 1;
+        assert!(state.storage.update_flag_value(&fv).await.unwrap());
+
+        advance(&state, now).await.unwrap();
+        assert_eq!(rollout(&state).await, (0, 2));
+        let ramp = state.storage.get_ramp("r1").await.unwrap().unwrap();
+        assert_eq!(ramp.status, RAMP_PAUSED);
+        assert_eq!(ramp.next_step_at, None);
+    }
+}
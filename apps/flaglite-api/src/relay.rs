@@ -0,0 +1,200 @@
+//! Read-only relay for edge regions
+//!
+//! `flaglite-api relay` pulls one environment's flags from an upstream server
+//! on an interval and answers evaluations from memory. When upstream is
+//! unreachable it keeps serving the last config it fetched.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header::AUTHORIZATION, HeaderMap},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tower_http::cors::{Any, CorsLayer};
+use tower_http::trace::TraceLayer;
+
+use crate::error::{AppError, Result};
+use crate::handlers::flags::{evaluate, served_value};
+use crate::models::{EvaluateFlagQuery, FlagConfig, FlagConfigResponse, FlagEvaluationResponse};
+
+/// Upstream requests give up after this long
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Last config fetched from upstream
+struct Snapshot {
+    response: FlagConfigResponse,
+    flags: HashMap<String, FlagConfig>,
+    fetched_at: DateTime<Utc>,
+}
+
+impl Snapshot {
+    fn new(response: FlagConfigResponse) -> Self {
+        let flags = response
+            .flags
+            .iter()
+            .map(|f| (f.key.clone(), f.clone()))
+            .collect();
+        Self {
+            response,
+            flags,
+            fetched_at: Utc::now(),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct RelayState {
+    /// The upstream key; clients must present the same one
+    api_key: Arc<str>,
+    snapshot: Arc<RwLock<Option<Snapshot>>>,
+}
+
+impl RelayState {
+    fn authorize(&self, headers: &HeaderMap) -> Result<()> {
+        let token = headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .ok_or(AppError::Unauthorized)?;
+
+        if token != &*self.api_key {
+            return Err(AppError::InvalidApiKey);
+        }
+        Ok(())
+    }
+}
+
+/// Run the relay until the process exits
+pub async fn run(
+    upstream: String,
+    api_key: String,
+    interval: Duration,
+    listener: tokio::net::TcpListener,
+) -> anyhow::Result<()> {
+    let state = RelayState {
+        api_key: api_key.into(),
+        snapshot: Arc::new(RwLock::new(None)),
+    };
+
+    let http = reqwest::Client::builder()
+        .timeout(UPSTREAM_TIMEOUT)
+        .user_agent(concat!("flaglite-relay/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+    let url = format!("{}/v1/flags", upstream.trim_end_matches('/'));
+    tokio::spawn(poll(http, url, state.clone(), interval));
+
+    let cors = CorsLayer::new()
+        .allow_origin(Any)
+        .allow_methods(Any)
+        .allow_headers(Any);
+
+    let app = Router::new()
+        .route("/health", get(health))
+        .route("/v1/flags", get(flag_config))
+        .route("/v1/flags/:key/evaluate", get(evaluate_flag))
+        .layer(TraceLayer::new_for_http().make_span_with(crate::telemetry::make_span))
+        .layer(cors)
+        .with_state(state);
+
+    axum::serve(listener, app).tcp_nodelay(true).await?;
+    Ok(())
+}
+
+/// Refresh the snapshot forever; failures keep the previous one
+async fn poll(http: reqwest::Client, url: String, state: RelayState, interval: Duration) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        ticker.tick().await;
+
+        match fetch(&http, &url, &state.api_key).await {
+            Ok(response) => {
+                tracing::debug!("Fetched {} flags from upstream", response.flags.len());
+                *state.snapshot.write().unwrap() = Some(Snapshot::new(response));
+            }
+            Err(e) => {
+                let age = state
+                    .snapshot
+                    .read()
+                    .unwrap()
+                    .as_ref()
+                    .map(|s| format!("serving config from {}", s.fetched_at.to_rfc3339()))
+                    .unwrap_or_else(|| "no config yet".to_string());
+                tracing::warn!("Failed to refresh from upstream ({age}): {e:#}");
+            }
+        }
+    }
+}
+
+async fn fetch(
+    http: &reqwest::Client,
+    url: &str,
+    api_key: &str,
+) -> anyhow::Result<FlagConfigResponse> {
+    let response = http
+        .get(url)
+        .bearer_auth(api_key)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(response.json().await?)
+}
+
+fn not_ready() -> AppError {
+    AppError::Unavailable("Relay has not fetched flags from upstream yet".to_string())
+}
+
+async fn health(State(state): State<RelayState>) -> Result<&'static str> {
+    // Not ready until there is something to serve
+    match *state.snapshot.read().unwrap() {
+        Some(_) => Ok("OK"),
+        None => Err(not_ready()),
+    }
+}
+
+async fn flag_config(
+    State(state): State<RelayState>,
+    headers: HeaderMap,
+) -> Result<Json<FlagConfigResponse>> {
+    state.authorize(&headers)?;
+
+    let snapshot = state.snapshot.read().unwrap();
+    let snapshot = snapshot.as_ref().ok_or_else(not_ready)?;
+    Ok(Json(snapshot.response.clone()))
+}
+
+async fn evaluate_flag(
+    State(state): State<RelayState>,
+    Path(key): Path<String>,
+    Query(query): Query<EvaluateFlagQuery>,
+    headers: HeaderMap,
+) -> Result<Json<FlagEvaluationResponse>> {
+    state.authorize(&headers)?;
+
+    let snapshot = state.snapshot.read().unwrap();
+    let snapshot = snapshot.as_ref().ok_or_else(not_ready)?;
+    let flag = snapshot
+        .flags
+        .get(&key)
+        .ok_or_else(|| AppError::NotFound(format!("Flag '{key}' not found")))?;
+
+    let (enabled, _) = evaluate(
+        &key,
+        flag.expires_at,
+        flag.value.as_ref(),
+        flag.default_enabled,
+        query.user_id.as_deref(),
+    );
+
+    let value = served_value(flag.value.as_ref(), flag.default_value.clone(), enabled);
+    Ok(Json(FlagEvaluationResponse {
+        key,
+        enabled,
+        value,
+    }))
+}
@@ -0,0 +1,212 @@
+//! Data retention
+//!
+//! Flag history grows with every change and evaluation counts with every day
+//! of traffic. With AUDIT_RETENTION_DAYS or EVALUATION_RETENTION_DAYS set, the
+//! `prune` job deletes older rows once a day, and `flaglite-api prune` does the
+//! same on demand. Each flag's current version is always kept, so pruning
+//! never changes what a flag serves. Deleting a flag removes its history and
+//! counts right away, so deleted flags leave nothing behind to prune.
+//!
+//! Deleted environments are archived so they can be restored, and the job
+//! purges their archives DELETED_ENVIRONMENT_RETENTION_DAYS (30 by default)
+//! after deletion.
+
+use anyhow::Context;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::time::Duration;
+
+use crate::error::Result;
+use crate::models::AppState;
+use crate::storage::Storage;
+
+/// How often the `prune` job runs
+pub const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Days a deleted environment can be restored for, unless configured
+pub const DEFAULT_DELETED_ENVIRONMENT_DAYS: u32 = 30;
+
+/// How long old data is kept; None keeps it forever
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Days flag versions are kept (AUDIT_RETENTION_DAYS)
+    pub history_days: Option<u32>,
+    /// Days of evaluation counts kept (EVALUATION_RETENTION_DAYS). Config
+    /// rejects 0; here it's taken as 1, so today's counts are never pruned
+    pub evaluation_days: Option<u32>,
+    /// Days a deleted environment's archive is kept
+    /// (DELETED_ENVIRONMENT_RETENTION_DAYS)
+    pub deleted_environment_days: u32,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            history_days: None,
+            evaluation_days: None,
+            deleted_environment_days: DEFAULT_DELETED_ENVIRONMENT_DAYS,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Flag history and evaluation counts are never pruned
+    pub fn keeps_everything(&self) -> bool {
+        self.history_days.is_none() && self.evaluation_days.is_none()
+    }
+}
+
+/// Rows deleted by one prune
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Pruned {
+    pub flag_versions: u64,
+    pub evaluation_counts: u64,
+    pub deleted_environments: u64,
+}
+
+/// Delete what `policy` no longer keeps as of `now`
+pub async fn prune(
+    storage: &dyn Storage,
+    policy: RetentionPolicy,
+    now: DateTime<Utc>,
+) -> Result<Pruned> {
+    let mut pruned = Pruned::default();
+    if let Some(days) = policy.history_days {
+        let before = now - ChronoDuration::days(days.into());
+        pruned.flag_versions = storage.prune_flag_value_history(before).await?;
+    }
+    if let Some(days) = policy.evaluation_days {
+        // Whole days: today counts as one, so 1 keeps only today's counts
+        let before = (now - ChronoDuration::days(i64::from(days.max(1)) - 1)).date_naive();
+        pruned.evaluation_counts = storage.prune_evaluation_counts(before).await?;
+    }
+    // Archives carry their own purge time, set when the environment was deleted
+    pruned.deleted_environments = storage.purge_deleted_environments(now).await?;
+    Ok(pruned)
+}
+
+/// The `prune` job: apply the configured retention. Nothing is pruned while
+/// the server is read-only
+pub async fn prune_job(state: AppState) -> anyhow::Result<()> {
+    if state.maintenance.is_read_only() {
+        return Ok(());
+    }
+    let pruned = prune(state.storage.as_ref(), state.retention, Utc::now())
+        .await
+        .context("Failed to prune old data")?;
+    if pruned != Pruned::default() {
+        tracing::info!(
+            "Pruned {} flag version(s), {} evaluation count(s) and {} deleted environment(s) past retention",
+            pruned.flag_versions,
+            pruned.evaluation_counts,
+            pruned.deleted_environments
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{EvaluationCount, Flag, FlagValue};
+    use crate::storage::memory::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_prune_keeps_current_versions_and_recent_days() {
+        let storage = MemoryStorage::new();
+        let now = Utc::now();
+        let days_ago = |days| now - ChronoDuration::days(days);
+
+        storage
+            .create_flag(&Flag {
+                id: "f1".to_string(),
+                project_id: "p1".to_string(),
+                key: "dark-mode".to_string(),
+                name: "Dark mode".to_string(),
+                description: None,
+                created_at: days_ago(100),
+                expires_at: None,
+                owner: None,
+                repository: None,
+                code_path: None,
+                flag_type: "boolean".to_string(),
+                json_schema: None,
+                default_enabled: false,
+                default_value: None,
+                public: false,
+            })
+            .await
+            .unwrap();
+
+        // Versions 1 and 2 are old; 3 is old too, but current
+        let mut value = FlagValue {
+            id: "v1".to_string(),
+            flag_id: "f1".to_string(),
+            environment_id: "e1".to_string(),
+            enabled: true,
+            rollout_percentage: 100,
+            value: None,
+            updated_at: days_ago(60),
+            version: 1,
+            reason: None,
+        };
+        storage.create_flag_value(&value).await.unwrap();
+        for version in 2..=3 {
+            value.version = version;
+            value.updated_at = days_ago(50 - version);
+            assert!(storage.update_flag_value(&value).await.unwrap());
+        }
+
+        let counts: Vec<_> = [0, 1, 2, 40]
+            .into_iter()
+            .map(|days| EvaluationCount {
+                flag_id: "f1".to_string(),
+                environment_id: "e1".to_string(),
+                day: days_ago(days).date_naive(),
+                count: 1,
+                last_evaluated_at: days_ago(days),
+            })
+            .collect();
+        storage.record_evaluations(&counts).await.unwrap();
+
+        let policy = RetentionPolicy {
+            history_days: Some(30),
+            evaluation_days: Some(2),
+            ..RetentionPolicy::default()
+        };
+        let pruned = prune(&storage, policy, now).await.unwrap();
+        assert_eq!(
+            pruned,
+            Pruned {
+                flag_versions: 2,
+                evaluation_counts: 2,
+                deleted_environments: 0,
+            }
+        );
+
+        let history = storage.list_flag_value_history("v1").await.unwrap();
+        assert_eq!(history.iter().map(|h| h.version).collect::<Vec<_>>(), [3]);
+        let kept = storage
+            .list_evaluation_counts(&["f1".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(kept.len(), 2);
+
+        // Nothing configured, nothing deleted
+        let pruned = prune(&storage, RetentionPolicy::default(), now).await;
+        assert_eq!(pruned.unwrap(), Pruned::default());
+
+        // 0 days keeps today's counts, like 1
+        let policy = RetentionPolicy {
+            evaluation_days: Some(0),
+            ..RetentionPolicy::default()
+        };
+        let pruned = prune(&storage, policy, now).await.unwrap();
+        assert_eq!(pruned.evaluation_counts, 1);
+        let kept = storage
+            .list_evaluation_counts(&["f1".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].day, now.date_naive());
+    }
+}
@@ -0,0 +1,1118 @@
+//! Route table
+//!
+//! Every endpoint is declared once, with its documentation next to its handler.
+//! The router is built from this table and so are /llms.txt and /llms.json,
+//! which therefore can't list a route that doesn't exist or miss one that does.
+
+use axum::handler::Handler;
+use axum::routing::{on, MethodFilter, MethodRouter};
+
+use crate::handlers;
+use crate::models::{ApiKeyScope, AppState};
+use crate::versioning::{Change, ENVIRONMENT_REASONS};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Post,
+    Put,
+    Patch,
+    Delete,
+}
+
+impl Method {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Patch => "PATCH",
+            Method::Delete => "DELETE",
+        }
+    }
+
+    fn filter(self) -> MethodFilter {
+        match self {
+            Method::Get => MethodFilter::GET,
+            Method::Post => MethodFilter::POST,
+            Method::Put => MethodFilter::PUT,
+            Method::Patch => MethodFilter::PATCH,
+            Method::Delete => MethodFilter::DELETE,
+        }
+    }
+}
+
+/// Credentials an endpoint accepts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Auth {
+    None,
+    /// JWT or user API key (`flg_`)
+    User,
+    /// Environment key (`ffl_env_`), project key (`ffl_proj_`), user API key or JWT
+    Sdk,
+    /// The server's ADMIN_TOKEN
+    Admin,
+    /// None; a public environment's client ID (`ffl_pub_`) in the path
+    Public,
+}
+
+impl Auth {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Auth::None => "none",
+            Auth::User => "user",
+            Auth::Sdk => "sdk",
+            Auth::Admin => "admin",
+            Auth::Public => "public",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Auth::None => "No authentication",
+            Auth::User => "`Authorization: Bearer <JWT or flg_ API key>`",
+            Auth::Sdk => {
+                "`Authorization: Bearer <ffl_env_ or ffl_proj_ key>` (user credentials also work)"
+            }
+            Auth::Admin => "`Authorization: Bearer <ADMIN_TOKEN>` (set on the server)",
+            Auth::Public => {
+                "No authentication; the environment's public client ID (`ffl_pub_`) goes in the path"
+            }
+        }
+    }
+}
+
+/// Which endpoints a listener serves (see `serve --sdk-port`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Api {
+    /// Every endpoint on one listener
+    All,
+    /// Everything but the SDK endpoints, for an internal interface
+    Management,
+    /// Only the endpoints SDKs and relays call, plus the probes and metrics
+    Sdk,
+}
+
+impl Api {
+    pub fn serves(self, endpoint: &Endpoint) -> bool {
+        let sdk = matches!(endpoint.auth, Auth::Sdk | Auth::Public);
+        match self {
+            Api::All => true,
+            Api::Management => !sdk,
+            Api::Sdk => sdk || matches!(endpoint.path, "/health" | "/ready" | "/metrics"),
+        }
+    }
+}
+
+/// Query string parameter
+#[derive(Debug, Clone, Copy)]
+pub struct Param {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// Documentation for one method on one path
+#[derive(Debug, Clone)]
+pub struct Endpoint {
+    pub method: Method,
+    /// Axum path syntax (`/v1/flags/:key`)
+    pub path: &'static str,
+    pub auth: Auth,
+    pub summary: &'static str,
+    pub query: &'static [Param],
+    /// JSON body shape, with field types as values
+    pub request: Option<&'static str>,
+    /// JSON response shape, or the name of one of [`SCHEMAS`] (`Project`, `Project[]`)
+    pub response: Option<&'static str>,
+    pub notes: Option<&'static str>,
+    /// Changes stored data, so it's unavailable in read-only maintenance mode
+    pub writes: bool,
+    /// Narrowest user API key scope that may call it
+    pub scope: ApiKeyScope,
+    /// Response changes since API version 1; `response` documents version 1
+    pub changes: &'static [Change],
+}
+
+impl Endpoint {
+    /// Path in the `{param}` form used by documentation
+    pub fn display_path(&self) -> String {
+        self.path
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(param) => format!("{{{param}}}"),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+pub struct Route {
+    pub endpoint: Endpoint,
+    pub handler: MethodRouter<AppState>,
+}
+
+/// Routes under one documentation heading
+pub struct Section {
+    pub title: &'static str,
+    pub routes: Vec<Route>,
+}
+
+fn route<H, T>(method: Method, path: &'static str, handler: H) -> Route
+where
+    H: Handler<T, AppState>,
+    T: 'static,
+{
+    Route {
+        endpoint: Endpoint {
+            method,
+            path,
+            auth: Auth::User,
+            summary: "",
+            query: &[],
+            request: None,
+            response: None,
+            notes: None,
+            writes: method != Method::Get,
+            scope: if method == Method::Get {
+                ApiKeyScope::Read
+            } else {
+                ApiKeyScope::Write
+            },
+            changes: &[],
+        },
+        handler: on(method.filter(), handler),
+    }
+}
+
+impl Route {
+    fn auth(mut self, auth: Auth) -> Self {
+        self.endpoint.auth = auth;
+        self
+    }
+
+    fn summary(mut self, summary: &'static str) -> Self {
+        self.endpoint.summary = summary;
+        self
+    }
+
+    fn query(mut self, query: &'static [Param]) -> Self {
+        self.endpoint.query = query;
+        self
+    }
+
+    fn request(mut self, request: &'static str) -> Self {
+        self.endpoint.request = Some(request);
+        self
+    }
+
+    fn response(mut self, response: &'static str) -> Self {
+        self.endpoint.response = Some(response);
+        self
+    }
+
+    fn notes(mut self, notes: &'static str) -> Self {
+        self.endpoint.notes = Some(notes);
+        self
+    }
+
+    /// The response changed in later API versions; older clients get it as
+    /// `response` documents
+    fn changes(mut self, changes: &'static [Change]) -> Self {
+        self.endpoint.changes = changes;
+        self
+    }
+
+    /// Doesn't change stored data despite its method; stays available in
+    /// read-only maintenance mode
+    fn read_only(mut self) -> Self {
+        self.endpoint.writes = false;
+        self.endpoint.scope = ApiKeyScope::Read;
+        self
+    }
+
+    /// Manages accounts, orgs or keys rather than project data; needs an
+    /// admin-scoped key
+    fn admin(mut self) -> Self {
+        self.endpoint.scope = ApiKeyScope::Admin;
+        self
+    }
+}
+
+const ENVIRONMENT_PARAM: Param = Param {
+    name: "environment",
+    description: "Environment name (default: development)",
+};
+
+const USER_LIST_REQUEST: &str = r#"{"environment": "string", "add": "string[]?", "remove": "string[]?", "clear": "bool?", "confirm": "bool?", "reason": "string?"}"#;
+
+const USER_LIST_NOTES: &str = "While the flag is on, users on its denylist never get it and users on its allowlist always do, before the rollout percentage applies; a user is on at most one of the two, so adding them to one list takes them off the other. `clear` empties the list before `add` is applied. At most 10000 users per list, and user IDs of up to 256 characters. Frozen (423) and unconfirmed protected environments are rejected as for flag state changes";
+
+/// Response shapes shared by several endpoints
+pub const SCHEMAS: &[(&str, &str)] = &[
+    (
+        "User",
+        r#"{"id": "string", "username": "string", "email": "string?", "email_verified": "bool", "created_at": "datetime"}"#,
+    ),
+    (
+        "UsernameChange",
+        r#"{"old_username": "string", "new_username": "string", "changed_at": "datetime"}"#,
+    ),
+    (
+        "UserPreferences",
+        r#"{"project_id": "string?", "environment": "string?", "display_name": "string?", "format": "string?", "updated_at": "datetime?"}"#,
+    ),
+    (
+        "ApiKey",
+        r#"{"id": "string", "key_prefix": "string", "name": "string?", "scope": "read|write|admin", "org_id": "string?", "created_at": "datetime", "revoked_at": "datetime?"}"#,
+    ),
+    (
+        "ApiKeyCreated",
+        r#"{"id": "string", "key": "string", "key_prefix": "string", "name": "string?", "scope": "read|write|admin", "created_at": "datetime"}"#,
+    ),
+    (
+        "Organization",
+        r#"{"id": "string", "name": "string", "personal": "bool", "role": "owner|member", "created_at": "datetime"}"#,
+    ),
+    (
+        "OrgMember",
+        r#"{"user_id": "string", "username": "string", "role": "owner|member", "created_at": "datetime"}"#,
+    ),
+    (
+        "Project",
+        r#"{"id": "uuid", "name": "string", "description": "string?", "slug": "string", "org_id": "string?", "require_change_reason": "bool", "created_at": "datetime", "updated_at": "datetime"}"#,
+    ),
+    (
+        "InviteLink",
+        r#"{"id": "string", "project_id": "string", "role": "member|owner", "status": "active|expired|revoked", "uses": "int", "created_by": "string", "created_at": "datetime", "expires_at": "datetime", "revoked_at": "datetime?", "token": "string?"}"#,
+    ),
+    (
+        "ProjectMember",
+        r#"{"user_id": "string", "username": "string", "role": "member|owner", "created_at": "datetime"}"#,
+    ),
+    (
+        "Environment",
+        r#"{"id": "uuid", "name": "string", "slug": "string", "project_id": "uuid", "api_key": "string", "is_production": "bool", "protected": "bool", "frozen": "bool", "sort_order": "int", "color": "string?", "emoji": "string?", "cache_max_age": "int?", "cache_stale_while_revalidate": "int?", "public_client_id": "string?", "created_at": "datetime"}"#,
+    ),
+    (
+        "Flag",
+        r#"{"id": "uuid", "key": "string", "name": "string", "description": "string?", "flag_type": "boolean|string|number|json", "project_id": "uuid", "created_at": "datetime", "updated_at": "datetime", "expires_at": "datetime?", "expired": "bool", "owner": "string?", "repository": "string?", "code_path": "string?", "json_schema": "object?", "default_enabled": "bool", "default_value": "json?", "public": "bool"}"#,
+    ),
+    (
+        "FlagWithState",
+        r#"{"id": "uuid", "key": "string", "name": "string", "description": "string?", "flag_type": "boolean|string|number|json", "project_id": "uuid", "created_at": "datetime", "updated_at": "datetime", "expires_at": "datetime?", "expired": "bool", "owner": "string?", "repository": "string?", "code_path": "string?", "json_schema": "object?", "default_enabled": "bool", "default_value": "json?", "public": "bool", "enabled": "bool", "value": "json?", "environments": {"<env>": {"enabled": "bool", "rollout": "int", "version": "int", "value": "json?"}}, "last_evaluated_at": "datetime?"}"#,
+    ),
+    (
+        "ChangeRequest",
+        r#"{"id": "string", "project_id": "string", "flag_key": "string", "environment": "string", "enabled": "bool?", "rollout_percentage": "int?", "base_version": "int", "comment": "string?", "status": "pending|approved|rejected", "proposed_by": "string", "reviewed_by": "string?", "review_comment": "string?", "created_at": "datetime", "reviewed_at": "datetime?"}"#,
+    ),
+    (
+        "Ramp",
+        r#"{"id": "string", "project_id": "string", "flag_key": "string", "environment": "string", "target_percentage": "int", "step_percentage": "int", "interval_secs": "int", "current_percentage": "int", "status": "active|paused|completed|aborted", "next_step_at": "datetime?", "reason": "string?", "created_by": "string", "created_at": "datetime", "updated_at": "datetime"}"#,
+    ),
+    (
+        "Guard",
+        r#"{"id": "string", "project_id": "string", "flag_key": "string", "environment": "string", "ramp_id": "string?", "url": "string?", "version": "int", "revert_enabled": "bool", "revert_rollout_percentage": "int", "revert_value": "json?", "window_secs": "int", "status": "watching|passed|tripped|superseded|cancelled", "message": "string?", "watching_until": "datetime?", "created_by": "string", "created_at": "datetime", "ended_at": "datetime?", "token": "string? (only when created)"}"#,
+    ),
+    (
+        "UserList",
+        r#"{"key": "string", "environment": "string", "list": "allowlist|denylist", "user_ids": "string[]", "added": "int? (only from updates)", "removed": "int? (only from updates)"}"#,
+    ),
+    (
+        "ProjectQuota",
+        r#"{"project_id": "uuid", "requests_per_minute": "int?", "per_key_requests_per_minute": "int?", "updated_at": "datetime?"}"#,
+    ),
+];
+
+/// All API routes, grouped as they appear in the docs
+pub fn sections() -> Vec<Section> {
+    use handlers::{
+        admin, archives, auth, change_requests, cli, deleted_environments, flags, guards, health,
+        invite_links, llms, orgs, public, ramps, search, templates, user_lists, ws,
+    };
+    use Method::*;
+
+    vec![
+        Section {
+            title: "Service",
+            routes: vec![
+                route(Get, "/health", || async { "OK" })
+                    .auth(Auth::None)
+                    .summary("Health check")
+                    .response(r#""OK""#),
+                route(Get, "/ready", health::ready)
+                    .auth(Auth::None)
+                    .summary("Readiness check: whether the database answers")
+                    .response(r#""OK""#)
+                    .notes("503 while the database can't be reached, so load balancers stop routing to the instance; `/health` only checks the process is up"),
+                route(Get, "/metrics", health::metrics)
+                    .auth(Auth::None)
+                    .summary("Prometheus metrics: how long each storage operation takes")
+                    .notes("`flaglite_storage_operation_duration_seconds`, a histogram labelled by `operation`, timed at the database below the evaluation cache. Operations slower than SLOW_QUERY_MS are also logged"),
+                route(Get, "/llms.txt", llms::llms_txt)
+                    .auth(Auth::None)
+                    .summary("This document"),
+                route(Get, "/llms.json", llms::llms_json)
+                    .auth(Auth::None)
+                    .summary("Machine-readable route list for tool integrations"),
+            ],
+        },
+        Section {
+            title: "Authentication",
+            routes: vec![
+                route(Post, "/v1/auth/signup", auth::signup)
+                    .auth(Auth::None)
+                    .summary("Create account, returns JWT + API key")
+                    .request(r#"{"username": "string?", "password": "string", "project_name": "string?", "invite_code": "string?"}"#)
+                    .response(r#"{"user": "User", "api_key": "ApiKeyCreated", "token": "string", "project": "Project", "environments": "Environment[]"}"#)
+                    .notes("`invite_code` is required when the server is invite-only"),
+                route(Post, "/v1/auth/login", auth::login)
+                    .read_only()
+                    .auth(Auth::None)
+                    .summary("Get JWT token")
+                    .request(r#"{"username": "string", "password": "string"}"#)
+                    .response(r#"{"token": "string", "user": "User"}"#)
+                    .notes("Repeated failures lock the username or client IP out: 429 with `code: login_locked`, `Retry-After` and `details.retry_after` in seconds"),
+                route(Get, "/v1/auth/me", auth::me)
+                    .summary("Get current user")
+                    .response("User"),
+                route(Patch, "/v1/auth/me", auth::update_me)
+                    .admin()
+                    .summary("Update current user")
+                    .request(r#"{"email": "string?", "username": "string?"}"#)
+                    .response("User")
+                    .notes("A username can be changed once every 30 days: 429 with `code: rename_cooldown`, `Retry-After` and `details.retry_after` in seconds otherwise. Old usernames stay reserved for their user, who can take one back, and still find them when adding org members. Each of the user's projects gets a `user.renamed` audit event; tokens issued before the change keep working"),
+                route(Get, "/v1/auth/me/username-changes", auth::list_username_changes)
+                    .summary("List your username changes, newest first")
+                    .response("UsernameChange[]"),
+                route(Get, "/v1/auth/preferences", auth::get_preferences)
+                    .summary("Get your saved CLI defaults")
+                    .response("UserPreferences"),
+                route(Patch, "/v1/auth/preferences", auth::update_preferences)
+                    .summary("Update your saved CLI defaults")
+                    .request(r#"{"project_id": "string?", "environment": "string?", "display_name": "string?", "format": "pretty|json|json-v2|csv|tsv?"}"#)
+                    .response("UserPreferences")
+                    .notes("Omitted fields are left alone and an empty string clears one. `project_id` takes an ID or slug and is stored as the ID. The CLI applies these on login"),
+                route(Post, "/v1/auth/me/verify-email", auth::send_verification_email)
+                    .admin()
+                    .summary("Email a verification token to the current user's address")
+                    .notes("Tokens expire after 24 hours. Without SMTP_HOST the email is only logged"),
+                route(Post, "/v1/auth/verify-email", auth::verify_email)
+                    .auth(Auth::None)
+                    .summary("Confirm an email address with a verification token")
+                    .request(r#"{"token": "string"}"#)
+                    .response("User")
+                    .notes("Fails if the address changed after the token was sent"),
+                route(Post, "/v1/auth/password-reset", auth::request_password_reset)
+                    .auth(Auth::None)
+                    .summary("Email a password reset token")
+                    .request(r#"{"username": "string"}"#)
+                    .notes("Only sent to a verified email address. Always succeeds, so it can't be used to probe for accounts; tokens expire after 1 hour"),
+                route(Post, "/v1/auth/password-reset/confirm", auth::reset_password)
+                    .auth(Auth::None)
+                    .summary("Set a new password with a reset token")
+                    .request(r#"{"token": "string", "password": "string", "revoke_api_keys": "bool?"}"#)
+                    .notes("Each token works once. Every session (JWT) issued before the reset stops working; `revoke_api_keys: true` revokes the user's API keys as well"),
+                route(Get, "/v1/auth/api-keys", auth::list_api_keys)
+                    .summary("List your API keys, newest first; revoked keys included")
+                    .response("ApiKey[]")
+                    .notes("An org-limited key only sees that org's keys"),
+                route(Post, "/v1/auth/api-keys", auth::create_api_key)
+                    .admin()
+                    .summary("Create an API key, optionally with a narrower scope")
+                    .request(r#"{"name": "string?", "scope": "read|write|admin?"}"#)
+                    .response("ApiKeyCreated")
+                    .notes("Scopes: `read` only makes requests that change nothing; `write` also changes projects, environments, flags, templates and change requests; `admin` (default) can do everything, including managing keys, invites, orgs and account settings. Requests beyond a key's scope return 403 with `code: insufficient_scope`. A key created with an org-limited key is limited to the same org"),
+                route(Post, "/v1/invites", auth::create_invite)
+                    .admin()
+                    .summary("Create a single-use invite code")
+                    .response(r#"{"code": "string", "expires_at": "datetime"}"#)
+                    .notes("Only owners of a team (non-personal) organization can create invites (403), or the operator, sending `ADMIN_TOKEN` as the bearer token"),
+            ],
+        },
+        Section {
+            title: "Organizations",
+            routes: vec![
+                route(Get, "/v1/orgs", orgs::list_orgs)
+                    .summary("List your organizations (every user has a personal one)")
+                    .response("Organization[]"),
+                route(Post, "/v1/orgs", orgs::create_org)
+                    .admin()
+                    .summary("Create organization")
+                    .request(r#"{"name": "string"}"#)
+                    .response("Organization"),
+                route(Get, "/v1/orgs/:org_id/members", orgs::list_members)
+                    .summary("List members")
+                    .response("OrgMember[]"),
+                route(Post, "/v1/orgs/:org_id/members", orgs::add_member)
+                    .admin()
+                    .summary("Add member (owners only)")
+                    .request(r#"{"username": "string", "role": "member|owner"}"#)
+                    .response("OrgMember"),
+                route(Patch, "/v1/orgs/:org_id/members/:user_id", orgs::update_member)
+                    .admin()
+                    .summary("Change a member's role (owners only)")
+                    .request(r#"{"role": "member|owner"}"#)
+                    .response("OrgMember")
+                    .notes("Demoting the last owner is a 409"),
+                route(Delete, "/v1/orgs/:org_id/members/:user_id", orgs::remove_member)
+                    .admin()
+                    .summary("Remove a member (owners only)")
+                    .response("OrgMember")
+                    .notes("They lose access to the org's projects, and API keys they limited to the org stop working. Removing the last owner is a 409"),
+                route(Post, "/v1/orgs/:org_id/api-keys", orgs::create_api_key)
+                    .admin()
+                    .summary("Create an API key limited to this organization (owners only)")
+                    .request(r#"{"name": "string?", "scope": "read|write|admin?"}"#)
+                    .response("ApiKeyCreated")
+                    .notes("`scope` defaults to `admin`; a key can't create a key with a broader scope than its own"),
+            ],
+        },
+        Section {
+            title: "Projects",
+            routes: vec![
+                route(Get, "/v1/projects", cli::list_projects)
+                    .summary("List projects in your organizations")
+                    .query(&[Param {
+                        name: "org_id",
+                        description: "Only this organization's projects",
+                    }])
+                    .response("Project[]"),
+                route(Post, "/v1/projects", cli::create_project)
+                    .summary("Create project with development, staging and production environments")
+                    .request(r#"{"name": "string", "description": "string?", "org_id": "string?"}"#)
+                    .response("Project")
+                    .notes("`org_id` defaults to your personal organization"),
+                route(Get, "/v1/projects/:project_id", cli::get_project)
+                    .summary("Get a project by id or slug")
+                    .response("Project")
+                    .notes("Every `/v1/projects/:project_id` route accepts the slug in place of the id"),
+                route(Patch, "/v1/projects/:project_id", cli::update_project)
+                    .admin()
+                    .summary("Update project settings")
+                    .request(r#"{"require_change_reason": "bool?"}"#)
+                    .response("Project")
+                    .notes("With `require_change_reason`, toggling, setting, batch-updating or rolling back flags in a protected environment without a `reason` is a 428 with `code: reason_required`; change requests for one need a `comment`, which becomes the reason when approved. Only project owners can turn it off (403)"),
+                route(Get, "/v1/projects/:project_id/stats", cli::project_stats)
+                    .summary("Flag counts per environment: enabled, at partial rollout, last changed")
+                    .response(r#"{"project_id": "string", "slug": "string", "flags": "int", "last_changed_at": "datetime?", "api_key_created_at": "datetime", "environments": [{"name": "string", "enabled": "int", "enabled_percentage": "number", "partial_rollout": "int", "last_changed_at": "datetime?", "api_key_created_at": "datetime"}]}"#)
+                    .notes("Flags without a value count by their default; expired flags count as off. SDK keys aren't rotated, so a key is as old as its project or environment"),
+                route(Post, "/v1/projects/:project_id/clone", cli::clone_project)
+                    .summary("Copy a project's environments and flags into a new project")
+                    .request(r#"{"name": "string", "include_values": "bool?"}"#)
+                    .response("Project"),
+                route(Get, "/v1/projects/:project_id/export", archives::export_project)
+                    .summary("Download the project as a .tar.gz archive")
+                    .notes("The archive is a directory named after the slug with `manifest.json`, `project.json`, `environments.json`, `flags.json`, `values.json` (each flag's current state per environment), `user_lists.json` and `templates.json`. Records refer to each other by flag key and environment name. Keys, history, members and change requests aren't included"),
+                route(Post, "/v1/import", archives::import_project)
+                    .summary("Create a project from an archive made by the export endpoint")
+                    .query(&[
+                        Param {
+                            name: "name",
+                            description: "Name of the new project (default: the archived project's)",
+                        },
+                        Param {
+                            name: "org_id",
+                            description: "Organization to create it in (default: your personal organization)",
+                        },
+                    ])
+                    .response("Project")
+                    .notes("The body is the archive itself (`Content-Type: application/gzip`), at most 2 MB. The whole archive is validated first, so a 422 creates nothing. The project gets new ids and keys; values start over at version 1 and no environment is frozen. Archives from a newer server are a 400"),
+            ],
+        },
+        Section {
+            title: "Project sharing",
+            routes: vec![
+                route(Post, "/v1/projects/:project_id/invite-links", invite_links::create_invite_link)
+                    .admin()
+                    .summary("Create a link that shares the project with whoever redeems it (project owners only)")
+                    .request(r#"{"role": "member|owner?", "expires_in_secs": "int?"}"#)
+                    .response("InviteLink")
+                    .notes("`role` defaults to `member`; `expires_in_secs` defaults to 7 days and ranges from 1 hour to 30 days. The `token` is only returned here. Project owners are the owners of its organization, the creator of a project without one, and users who joined with an `owner` link"),
+                route(Get, "/v1/projects/:project_id/invite-links", invite_links::list_invite_links)
+                    .summary("List the project's invite links, newest first (project owners only)")
+                    .response("InviteLink[]"),
+                route(Delete, "/v1/projects/:project_id/invite-links/:id", invite_links::revoke_invite_link)
+                    .admin()
+                    .summary("Revoke an invite link (project owners only)")
+                    .response("InviteLink")
+                    .notes("Users who already joined keep their access; remove them with `DELETE /v1/projects/:project_id/members/:user_id`"),
+                route(Get, "/v1/projects/:project_id/members", invite_links::list_members)
+                    .summary("List the users the project was shared with by invite link (project owners only)")
+                    .response("ProjectMember[]"),
+                route(Delete, "/v1/projects/:project_id/members/:user_id", invite_links::remove_member)
+                    .admin()
+                    .summary("Take back the access an invite link gave a user (project owners only)")
+                    .response("ProjectMember")
+                    .notes("Members of the project's organization keep the access they have through it"),
+                route(Post, "/v1/join/:token", invite_links::join)
+                    .admin()
+                    .summary("Join the project an invite link shares")
+                    .response(r#"{"project": "Project", "role": "member|owner?", "joined": "bool"}"#)
+                    .notes("Any signed-in user can redeem a link until it expires or is revoked; they get the project, not its organization. `joined` is false, and the link isn't counted, when the caller already had the project with at least the link's role. Not available to org-limited API keys"),
+            ],
+        },
+        Section {
+            title: "Environments",
+            routes: vec![
+                route(Get, "/v1/projects/:project_id/environments", cli::list_environments)
+                    .summary("List environments (dev/staging/prod) by sort_order")
+                    .response("Environment[]"),
+                route(Post, "/v1/projects/:project_id/environments", cli::create_environment)
+                    .summary("Add an environment, optionally starting from another environment's flag values")
+                    .request(r#"{"name": "string", "copy_from": "string?"}"#)
+                    .response("Environment")
+                    .notes("Names are lowercase letters, digits and hyphens. With `copy_from`, every flag starts with that environment's enabled state, rollout and value; without it flags evaluate to their defaults until set. A name already in use is a 409"),
+                route(Put, "/v1/projects/:project_id/environments", cli::reorder_environments)
+                    .summary("Set the order environments are listed in")
+                    .request(r#"{"order": "string[]"}"#)
+                    .response("Environment[]")
+                    .notes("Names not in `order` keep their relative order after the listed ones. An unknown or repeated name is a 422"),
+                route(Patch, "/v1/projects/:project_id/environments/:name", cli::update_environment)
+                    .summary("Update environment")
+                    .request(r#"{"protected": "bool?", "color": "string?", "emoji": "string?", "cache_max_age": "int?", "cache_stale_while_revalidate": "int?", "public": "bool?"}"#)
+                    .response("Environment")
+                    .notes("`color` is `#rrggbb`; an empty `color` or `emoji` clears it. `cache_max_age` and `cache_stale_while_revalidate` (seconds, up to a day) set the `Cache-Control` of the environment's SDK responses; a `cache_max_age` of 0 makes them `no-store`. `public: true` gives the environment a `public_client_id` for keyless evaluation of public flags; `false` removes it, and turning it back on issues a new one. Only project owners may set `protected` to false or make a private environment public (403)"),
+                route(Post, "/v1/projects/:project_id/environments/:name/freeze", cli::freeze_environment)
+                    .summary("Freeze an environment: flag changes that affect it fail with 423 until unfrozen")
+                    .response("Environment")
+                    .notes("Blocks toggling and setting flags in the environment, creating flags enabled, and deleting flags"),
+                route(Post, "/v1/projects/:project_id/environments/:name/unfreeze", cli::unfreeze_environment)
+                    .summary("Unfreeze an environment")
+                    .response("Environment")
+                    .notes("Only project owners can unfreeze (403)"),
+                route(Get, "/v1/projects/:project_id/environments/:name/approval", change_requests::get_environment_approval)
+                    .summary("Whether flag changes in an environment need approval")
+                    .response(r#"{"environment": "string", "required": "bool"}"#),
+                route(Put, "/v1/projects/:project_id/environments/:name/approval", change_requests::set_environment_approval)
+                    .summary("Require approval for flag changes in an environment, or stop requiring it")
+                    .request(r#"{"required": "bool"}"#)
+                    .response(r#"{"environment": "string", "required": "bool"}"#)
+                    .notes("Only project admins (the org's owners) can change this (403). While it's required, toggling, setting, batch-updating, copying into or rolling back flags in the environment, changing its user lists and starting ramps are a 403 with `code: approval_required`; propose the change as a change request instead"),
+                route(Delete, "/v1/projects/:project_id/environments/:name", deleted_environments::delete_environment)
+                    .summary("Delete an environment, keeping it restorable for DELETED_ENVIRONMENT_RETENTION_DAYS (default 30)")
+                    .query(&[Param {
+                        name: "force",
+                        description: "Required (true) to delete a protected environment such as production, otherwise 428; only project owners may, otherwise 403",
+                    }])
+                    .response(r#"{"id": "string", "name": "string", "flag_values": "int", "listed_users": "int", "deleted_by": "string", "deleted_at": "datetime", "purge_after": "datetime"}"#)
+                    .notes("The environment's flag values and allowlists and denylists are archived with it; its history, evaluation counts, signing keys, ramps and guards are deleted. A frozen environment (423) or the project's only environment (400) can't be deleted"),
+                route(Get, "/v1/projects/:project_id/deleted-environments", deleted_environments::list_deleted_environments)
+                    .summary("List deleted environments that can still be restored, most recent first")
+                    .response(r#"[{"id": "string", "name": "string", "flag_values": "int", "listed_users": "int", "deleted_by": "string", "deleted_at": "datetime", "purge_after": "datetime"}]"#),
+                route(Post, "/v1/projects/:project_id/environments/:name/restore", deleted_environments::restore_environment)
+                    .summary("Restore the most recently deleted environment with this name")
+                    .response("Environment")
+                    .notes("The environment comes back with its API key, public client ID and settings, and the flag values and user lists it had, except for flags deleted since. 409 if an environment of the same name was created meanwhile; 404 once the archive is purged"),
+                route(Post, "/v1/projects/:project_id/environments/:name/signing-key/rotate", cli::rotate_signing_key)
+                    .summary("Replace the key that signs the environment's SDK snapshots")
+                    .response(r#"{"keys": [{"kty": "OKP", "crv": "Ed25519", "alg": "EdDSA", "use": "sig", "kid": "string", "x": "string"}]}"#)
+                    .notes("The old key stays published for 7 days so snapshots cached on devices still verify"),
+                route(Post, "/v1/environments/copy", cli::copy_environment)
+                    .summary("Copy an environment's flag values into an environment of another project")
+                    .request(r#"{"from_project": "string", "from_environment": "string", "to_project": "string", "to_environment": "string", "confirm": "bool?", "reason": "string?", "dry_run": "bool?"}"#)
+                    .response(r#"{"from_project": "string", "from_environment": "string", "to_project": "string", "to_environment": "string", "dry_run": "bool", "flags": [{"key": "string", "changed": "bool", "enabled": "bool", "rollout": "int", "version": "int"}], "missing_in_target": "string[]", "missing_in_source": "string[]", "skipped": [{"key": "string", "reason": "string"}]}"#)
+                    .notes("Projects are IDs or slugs, and may be the same project. Each target flag with a source flag of the same key and type gets the source's enabled state, rollout and value (its defaults where it has no value); other target flags are unchanged and listed in `missing_in_source`, type and schema mismatches in `skipped`. Applied in one transaction like a batch update: a frozen (423) or unconfirmed protected target, or a concurrent change (409), leaves every flag unchanged. `dry_run` reports the same without changing anything"),
+            ],
+        },
+        Section {
+            title: "Flags",
+            routes: vec![
+                route(Get, "/v1/projects/:project_id/flags", cli::list_flags)
+                    .summary("List all flags")
+                    .query(&[
+                        ENVIRONMENT_PARAM,
+                        Param {
+                            name: "owner",
+                            description: "Only flags with this owner (case-insensitive)",
+                        },
+                        Param {
+                            name: "repository",
+                            description: "Only flags read by this repository",
+                        },
+                    ])
+                    .response("FlagWithState[]")
+                    .changes(&[ENVIRONMENT_REASONS]),
+                route(Post, "/v1/projects/:project_id/flags", cli::create_flag)
+                    .summary("Create flag")
+                    .request(r#"{"key": "string", "name": "string", "description": "string?", "flag_type": "string?", "enabled": "bool?", "expires_at": "datetime?", "owner": "string?", "repository": "string?", "code_path": "string?", "json_schema": "object?", "default_enabled": "bool?", "default_value": "json?"}"#)
+                    .response("Flag")
+                    .notes("After `expires_at` the flag evaluates to off in every environment until it is deleted. `json_schema` is only accepted with `flag_type: json`; an invalid schema is a 422. `default_enabled` and `default_value` apply in environments where the flag has no value, such as ones created later"),
+                route(Patch, "/v1/projects/:project_id/flags", cli::batch_update_flags)
+                    .summary("Turn many flags on or off, or set their rollout, in one environment")
+                    .request(r#"{"environment": "string", "keys": "string[]", "enabled": "bool?", "rollout_percentage": "int?", "confirm": "bool?", "reason": "string?"}"#)
+                    .response(r#"{"environment": "string", "flags": [{"key": "string", "changed": "bool", "enabled": "bool", "rollout": "int", "version": "int"}]}"#)
+                    .notes("Applied in one transaction: an unknown key (404), a frozen (423) or unconfirmed protected environment, or a concurrent change (409) leaves every flag unchanged. At most 1000 keys"),
+                route(Put, "/v1/projects/:project_id/flags", cli::register_flags)
+                    .summary("Create the listed flags that don't exist yet")
+                    .request(r#"{"flags": [{"key": "string", "name": "string", "flag_type": "string?", "enabled": "bool?", "default_enabled": "bool?", "default_value": "json?"}]}"#)
+                    .response(r#"{"created": "string[]", "existing": "string[]"}"#)
+                    .notes("For flags defined in code: each entry takes the fields of Create flag, and flags the project already has are reported in `existing` and left unchanged, so the call is safe to repeat on every start. Flags are created in order, so an invalid entry (422) stops at that flag. At most 1000 flags"),
+                route(Get, "/v1/projects/:project_id/flags/:key", cli::get_flag)
+                    .summary("Get flag with state")
+                    .query(&[ENVIRONMENT_PARAM])
+                    .response("FlagWithState")
+                    .changes(&[ENVIRONMENT_REASONS]),
+                route(Patch, "/v1/projects/:project_id/flags/:key", cli::update_flag)
+                    .summary("Set a flag's owner, code references and defaults")
+                    .request(r#"{"owner": "string?", "repository": "string?", "code_path": "string?", "default_enabled": "bool?", "default_value": "json?", "clear_default_value": "bool?", "public": "bool?"}"#)
+                    .response("Flag")
+                    .notes("Omitted fields are unchanged; an empty string clears an ownership field. Changing the defaults is rejected with 423 while any environment is frozen. `public: true` lets public environments serve the flag without a key"),
+                route(Delete, "/v1/projects/:project_id/flags/:key", cli::delete_flag)
+                    .summary("Delete flag"),
+                route(Get, "/v1/projects/:project_id/flags/:key/stats", cli::flag_stats)
+                    .summary("Evaluation counts per environment and day")
+                    .response(r#"{"key": "string", "total": "int", "last_evaluated_at": "datetime?", "environments": {"<env>": {"total": "int", "daily": [{"day": "date", "count": "int"}]}}}"#),
+                route(Post, "/v1/projects/:project_id/flags/:key/assignments", cli::flag_assignments)
+                    .read_only()
+                    .summary("Each user's bucket and result for a flag, for experiment analysis")
+                    .request(r#"{"environment": "string", "user_ids": ["string"]}"#)
+                    .response(r#"{"key": "string", "environment": "string", "rollout_percentage": "int", "assignments": [{"user_id": "string", "bucket": "int", "enabled": "bool", "reason": "string", "value": "json?"}]}"#)
+                    .notes("Same murmur3 bucketing as `/v1/flags/:key/evaluate`, so results match what SDKs serve; at most 10000 users per request and nothing is counted as an evaluation"),
+                route(Get, "/v1/projects/:project_id/flags/:key/allowlist", user_lists::get_allowlist)
+                    .summary("Users who always get the flag while it's on")
+                    .query(&[ENVIRONMENT_PARAM])
+                    .response("UserList"),
+                route(Patch, "/v1/projects/:project_id/flags/:key/allowlist", user_lists::update_allowlist)
+                    .summary("Add users to or remove them from the allowlist")
+                    .request(USER_LIST_REQUEST)
+                    .response("UserList")
+                    .notes(USER_LIST_NOTES),
+                route(Get, "/v1/projects/:project_id/flags/:key/denylist", user_lists::get_denylist)
+                    .summary("Users who never get the flag")
+                    .query(&[ENVIRONMENT_PARAM])
+                    .response("UserList"),
+                route(Patch, "/v1/projects/:project_id/flags/:key/denylist", user_lists::update_denylist)
+                    .summary("Add users to or remove them from the denylist")
+                    .request(USER_LIST_REQUEST)
+                    .response("UserList")
+                    .notes(USER_LIST_NOTES),
+                route(Put, "/v1/projects/:project_id/flags/:key/state", cli::set_flag_state)
+                    .summary("Set flag state")
+                    .request(r#"{"enabled": "bool", "environment": "string", "expected_version": "int?", "confirm": "bool?", "value": "json?", "reason": "string?"}"#)
+                    .response("FlagWithState")
+                    .changes(&[ENVIRONMENT_REASONS])
+                    .notes("409 if `expected_version` is given and the flag changed since. A `value` that doesn't match the flag's JSON Schema is a 422 with one `details` entry per violation, its field naming the path (`value/limits/max`)"),
+                route(Get, "/v1/projects/:project_id/flags/:key/history", cli::flag_history)
+                    .summary("Every version of a flag's value in one environment, newest first")
+                    .query(&[
+                        ENVIRONMENT_PARAM,
+                        Param {
+                            name: "limit",
+                            description: "Versions per page, 1 to 1000 (default 100)",
+                        },
+                        Param {
+                            name: "cursor",
+                            description: "`next_cursor` from the previous page",
+                        },
+                    ])
+                    .response(r#"{"key": "string", "environment": "string", "versions": [{"version": "int", "enabled": "bool", "rollout_percentage": "int", "value": "json?", "changed_at": "datetime", "reason": "string?"}], "next_cursor": "string?"}"#)
+                    .notes("`next_cursor` is null on the last page. With `Accept: application/x-ndjson`, every version is streamed instead, one JSON object per line (a version plus its `key` and `environment`), ignoring `limit` and `cursor`"),
+                route(Post, "/v1/projects/:project_id/flags/:key/rollback", cli::rollback_flag)
+                    .summary("Restore a flag's value in one environment to an earlier version")
+                    .request(r#"{"environment": "string", "to_version": "int?", "expected_version": "int?", "confirm": "bool?", "reason": "string?"}"#)
+                    .response(r#"{"key": "string", "environment": "string", "restored_version": "int", "changed": "bool", "version": "int", "enabled": "bool", "rollout_percentage": "int", "value": "json?", "changed_at": "datetime", "reason": "string?"}"#)
+                    .notes("Without `to_version`, restores the version before the current one. The restored state is written as a new version; 409 if the flag changed concurrently, 423 if the environment is frozen, 428 if it is protected and `confirm` isn't set"),
+                route(Post, "/v1/projects/:project_id/flags/:key/toggle", cli::toggle_flag)
+                    .summary("Toggle flag on/off")
+                    .query(&[
+                        ENVIRONMENT_PARAM,
+                        Param {
+                            name: "confirm",
+                            description: "Required (true) for protected environments such as production, otherwise 428",
+                        },
+                        Param {
+                            name: "reason",
+                            description: "Why the flag is toggled, kept in its history; required in protected environments of projects with `require_change_reason`",
+                        },
+                        Param {
+                            name: "expected_version",
+                            description: "Fail with 409 instead of overwriting a concurrent change (or send `If-Match: \"<version>\"`)",
+                        },
+                    ])
+                    .response("FlagWithState")
+                    .changes(&[ENVIRONMENT_REASONS]),
+                route(Get, "/v1/search", search::search_flags)
+                    .summary("Find flags by part of their key or name across all your projects")
+                    .query(&[
+                        Param {
+                            name: "q",
+                            description: "Text to find in flag keys and names (case-insensitive)",
+                        },
+                        Param {
+                            name: "org_id",
+                            description: "Only this organization's projects",
+                        },
+                    ])
+                    .response(r#"[{"project_id": "string", "project_slug": "string", "project_name": "string", "key": "string", "name": "string", "environments": {"<env>": {"enabled": "bool", "rollout": "int", "version": "int", "value": "json?"}}}]"#)
+                    .changes(&[ENVIRONMENT_REASONS])
+                    .notes("Exact key matches first, then keys starting with `q`; at most 50 flags"),
+            ],
+        },
+        Section {
+            title: "Templates",
+            routes: vec![
+                route(Get, "/v1/projects/:project_id/templates", templates::list_templates)
+                    .summary("Built-in and stored flag templates")
+                    .response(r#"[{"name": "string", "description": "string?", "default": {"enabled": "bool", "rollout": "int"}, "environments": {"<env>": {"enabled": "bool", "rollout": "int"}}, "builtin": "bool", "updated_at": "datetime?"}]"#)
+                    .notes("Every project has `kill-switch` (off everywhere) and `experiment` (on in development, 0% in production, off elsewhere); a stored template of the same name replaces one"),
+                route(Put, "/v1/projects/:project_id/templates/:name", templates::set_template)
+                    .summary("Create or replace a flag template")
+                    .request(r#"{"description": "string?", "default": {"enabled": "bool", "rollout": "int?"}, "environments": {"<env>": {"enabled": "bool", "rollout": "int?"}}}"#)
+                    .response(r#"{"name": "string", "description": "string?", "default": {"enabled": "bool", "rollout": "int"}, "environments": {"<env>": {"enabled": "bool", "rollout": "int"}}, "builtin": "bool", "updated_at": "datetime?"}"#)
+                    .notes("Names are lowercase letters, numbers and hyphens. Environments are matched by name; `default` applies to the rest. Omitted states are off, and omitted rollouts 100"),
+                route(Delete, "/v1/projects/:project_id/templates/:name", templates::delete_template)
+                    .summary("Delete a stored flag template")
+                    .notes("Deleting a stored template that replaced a built-in brings the built-in back; built-ins themselves can't be deleted"),
+                route(Post, "/v1/projects/:project_id/templates/:name/flags", templates::create_flag_from_template)
+                    .summary("Create a flag in the state a template gives each environment")
+                    .request(r#"{"key": "string", "name": "string", "description": "string?", "flag_type": "string?", "expires_at": "datetime?", "owner": "string?", "repository": "string?", "code_path": "string?", "json_schema": "object?", "default_value": "json?"}"#)
+                    .response("Flag")
+                    .notes("Same as creating a flag, except `enabled` is ignored and `default_enabled` comes from the template's `default`"),
+            ],
+        },
+        Section {
+            title: "Change requests",
+            routes: vec![
+                route(Post, "/v1/projects/:project_id/change-requests", change_requests::create_change_request)
+                    .summary("Propose turning a flag on or off, or changing its rollout, in one environment")
+                    .request(r#"{"flag_key": "string", "environment": "string", "enabled": "bool?", "rollout_percentage": "int?", "comment": "string?"}"#)
+                    .response("ChangeRequest")
+                    .notes("Nothing changes until the request is approved. Project admins (the org's owners) other than the proposer are emailed at their verified address"),
+                route(Get, "/v1/projects/:project_id/change-requests", change_requests::list_change_requests)
+                    .summary("A project's change requests, newest first")
+                    .query(&[Param {
+                        name: "status",
+                        description: "Only `pending`, `approved` or `rejected` requests",
+                    }])
+                    .response("ChangeRequest[]"),
+                route(Get, "/v1/change-requests/:id", change_requests::get_change_request)
+                    .summary("Get a change request")
+                    .response("ChangeRequest"),
+                route(Post, "/v1/change-requests/:id/approve", change_requests::approve_change_request)
+                    .summary("Approve a change request and apply it")
+                    .request(r#"{"comment": "string?"}"#)
+                    .response("ChangeRequest")
+                    .notes("Only a project admin other than the proposer can approve (403). Approval counts as confirming a protected environment and is how changes reach one that requires approval; a frozen one is still a 423. 409 if the request was already reviewed, or if the flag changed since it was proposed"),
+                route(Post, "/v1/change-requests/:id/reject", change_requests::reject_change_request)
+                    .summary("Reject a change request")
+                    .request(r#"{"comment": "string?"}"#)
+                    .response("ChangeRequest")
+                    .notes("Project admins can reject any request, and proposers can withdraw their own"),
+            ],
+        },
+        Section {
+            title: "Ramps",
+            routes: vec![
+                route(Post, "/v1/projects/:project_id/flags/:key/ramps", ramps::create_ramp)
+                    .summary("Raise a flag's rollout in one environment step by step")
+                    .request(r#"{"environment": "string", "target_percentage": "int", "step_percentage": "int", "interval_secs": "int", "confirm": "bool?", "reason": "string?", "guard": {"url": "string?", "window_secs": "int"}}"#)
+                    .response("Ramp")
+                    .notes("Turns the flag on and takes the first step right away, from its current rollout (0% if off); the server takes each later step once `interval_secs` (60 seconds to 7 days) has passed. The reason is recorded with every step. 409 if the flag is already being ramped in the environment; 422 if it's already at the target. Protected environments need `confirm`, and a frozen environment holds a ramp until it's unfrozen. A ramp whose flag is changed by hand pauses itself. With `guard`, the response's `guard` is a Guard that reverts the flag to its rollout before the ramp, and aborts the ramp, if it trips while the ramp runs or within `window_secs` after"),
+                route(Get, "/v1/projects/:project_id/ramps", ramps::list_ramps)
+                    .summary("A project's ramps, newest first")
+                    .query(&[Param {
+                        name: "status",
+                        description: "Only `active`, `paused`, `completed` or `aborted` ramps",
+                    }])
+                    .response("Ramp[]"),
+                route(Get, "/v1/ramps/:id", ramps::get_ramp)
+                    .summary("Get a ramp and how far it has got")
+                    .response("Ramp"),
+                route(Post, "/v1/ramps/:id/pause", ramps::pause_ramp)
+                    .summary("Stop an active ramp's steps, leaving the rollout as it is")
+                    .response("Ramp")
+                    .notes("409 unless the ramp is active"),
+                route(Post, "/v1/ramps/:id/resume", ramps::resume_ramp)
+                    .summary("Carry on a paused ramp")
+                    .response("Ramp")
+                    .notes("Continues from the flag's rollout now, with the next step a full interval away. 409 unless the ramp is paused"),
+                route(Post, "/v1/ramps/:id/abort", ramps::abort_ramp)
+                    .summary("End an active or paused ramp for good")
+                    .response("Ramp")
+                    .notes("The rollout stays where the ramp got to"),
+            ],
+        },
+        Section {
+            title: "Guards",
+            routes: vec![
+                route(Post, "/v1/projects/:project_id/flags/:key/guards", guards::create_guard)
+                    .summary("Revert a flag's latest change in one environment if it turns out unhealthy")
+                    .request(r#"{"environment": "string", "version": "int?", "url": "string?", "window_secs": "int"}"#)
+                    .response("Guard")
+                    .notes("For `window_secs` (60 to 604800) the server polls `url` every 15 seconds: an answer other than 2xx, or a JSON body with `\"healthy\": false` (and an optional `message`), trips the guard, as does an unhealthy report pushed with the returned `token`. Tripping puts the flag back to the version before `version` (default: the current one) and records the rollback in the audit log. 409 if `version` isn't the current one. A guard whose flag is changed again is superseded and never reverts; an unreachable `url` doesn't count as unhealthy"),
+                route(Get, "/v1/projects/:project_id/guards", guards::list_guards)
+                    .summary("A project's guards, newest first")
+                    .query(&[Param {
+                        name: "status",
+                        description: "Only `watching`, `passed`, `tripped`, `superseded` or `cancelled` guards",
+                    }])
+                    .response("Guard[]"),
+                route(Get, "/v1/guards/:id", guards::get_guard)
+                    .summary("Get a guard and what it has seen")
+                    .response("Guard"),
+                route(Post, "/v1/guards/:id/cancel", guards::cancel_guard)
+                    .summary("Stop a guard watching, leaving the flag as it is")
+                    .response("Guard")
+                    .notes("409 unless the guard is watching"),
+                route(Post, "/v1/guards/report", guards::report)
+                    .auth(Auth::None)
+                    .summary("Tell a guard whether its change is healthy")
+                    .request(r#"{"token": "string", "healthy": "bool?", "message": "string?"}"#)
+                    .response("Guard")
+                    .notes("Authenticated by the guard's `ffl_guard_` token, so error trackers and alerting can push to it. A report that isn't `healthy` trips the guard. 404 for an unknown token; 409 unless the guard is watching"),
+            ],
+        },
+        Section {
+            title: "Live events",
+            routes: vec![
+                route(Get, "/v1/ws", ws::connect)
+                    .summary("WebSocket of flag changes and audit events in the projects a connection subscribes to")
+                    .query(&[Param {
+                        name: "token",
+                        description: "JWT or `flg_` key, for clients that can't set the Authorization header on the handshake",
+                    }])
+                    .request(r#"{"type": "subscribe|unsubscribe|ping", "project_id": "string?"}"#)
+                    .response(r#"{"type": "subscribed|unsubscribed|pong|lagged|error|flag_changed|audit", "project_id": "string?", "keys": "string[]?", "deleted": "bool?", "action": "string?", "actor": "string?", "message": "string?", "missed": "int?", "at": "datetime?"}"#)
+                    .notes("JSON text messages in both directions. `flag_changed` and `audit` events only come from the API instance the socket is connected to. The server pings every 30s and closes connections silent for 90s; `lagged` means events were dropped and the client should refetch"),
+            ],
+        },
+        Section {
+            title: "Evaluation",
+            routes: vec![
+                route(Get, "/v1/flags/:key/evaluate", flags::evaluate_flag)
+                    .auth(Auth::Sdk)
+                    .summary("Evaluate a flag")
+                    .query(&[Param {
+                        name: "user_id",
+                        description: "Stable user ID for percentage rollouts",
+                    }])
+                    .response(r#"{"key": "string", "enabled": "bool", "value": "json?"}"#)
+                    .notes("Project keys evaluate against production. Cacheable as the environment's `cache_max_age` allows; uncached when it has none"),
+                route(Get, "/v1/flags/:key/enabled", flags::flag_enabled)
+                    .auth(Auth::Sdk)
+                    .summary("Whether a flag is on, as plain text, for shell scripts and proxies")
+                    .query(&[Param {
+                        name: "user_id",
+                        description: "Stable user ID for percentage rollouts",
+                    }])
+                    .response(r#""bool""#)
+                    .notes("`text/plain`: the bare `true` or `false`. Also answers HEAD, with the result in the `X-Flag-Enabled` header. Cacheable for 10s (or the environment's `cache_max_age`), and for up to an hour while the server errors; partial rollouts without `user_id` are `no-store`. Unknown flags are a JSON 404 like everywhere else"),
+                route(Get, "/v1/flags", flags::flag_config)
+                    .auth(Auth::Sdk)
+                    .summary("Every flag's state in the key's environment, for local evaluation")
+                    .response(r#"{"project_id": "uuid", "environment_id": "uuid", "flags": [{"key": "string", "expires_at": "datetime?", "value": {"enabled": "bool", "rollout_percentage": "int", "value": "json?"}, "default_enabled": "bool", "default_value": "json?"}]}"#)
+                    .notes("`value` is null for flags with no value in the environment; `default_enabled` and `default_value` apply instead. Used by `flaglite-api relay`. Cacheable as the environment's `cache_max_age` allows. `X-Poll-Interval` is how many seconds pollers should wait before the next request"),
+                route(Get, "/v1/changes", flags::flag_changes)
+                    .auth(Auth::Sdk)
+                    .summary("Only the flags changed since a previous poll, for SDKs with many flags")
+                    .query(&[Param {
+                        name: "since",
+                        description: "`revision` from the previous response; 0 for every flag",
+                    }])
+                    .response(r#"{"project_id": "uuid", "environment_id": "uuid", "revision": "int", "full": "bool", "flags": [{"key": "string", "expires_at": "datetime?", "value": {"enabled": "bool", "rollout_percentage": "int", "value": "json?"}, "default_enabled": "bool", "default_value": "json?"}], "deleted": ["string"]}"#)
+                    .notes("Revisions increase with every flag change in the project. `full` is true when `flags` holds every flag (since 0, or a revision the server doesn't know) and flags missing from it should be dropped. `X-Poll-Interval` is how many seconds pollers should wait before the next request: at least 30, the environment's `cache_max_age` if longer, and 300 during maintenance"),
+                route(Get, "/v1/flags/signed", flags::signed_flag_config)
+                    .auth(Auth::Sdk)
+                    .summary("The same snapshot as a JWS signed with the environment's key, for browser and mobile SDKs")
+                    .response(r#"{"jws": "string", "kid": "string"}"#)
+                    .notes("Ed25519 (`alg: EdDSA`). The payload is the `/v1/flags` response plus `iat`; verify it with the key from `/v1/flags/signing-keys` whose `kid` matches the JWS header"),
+                route(Get, "/v1/flags/signing-keys", flags::signing_keys)
+                    .auth(Auth::Sdk)
+                    .summary("Public keys (JWK set) that verify the environment's signed snapshots")
+                    .response(r#"{"keys": [{"kty": "OKP", "crv": "Ed25519", "alg": "EdDSA", "use": "sig", "kid": "string", "x": "string"}]}"#),
+            ],
+        },
+        Section {
+            title: "Public evaluation",
+            routes: vec![
+                route(Get, "/v1/public/:client_id/flags", public::evaluate_flags)
+                    .auth(Auth::Public)
+                    .summary("Evaluate every public flag, for pages that can't keep a key secret")
+                    .query(&[Param {
+                        name: "user_id",
+                        description: "Stable user ID for percentage rollouts",
+                    }])
+                    .response(r#"[{"key": "string", "enabled": "bool", "value": "json?"}]"#)
+                    .notes("Only flags marked `public` are evaluated; the environment must be public. Limited per client ID and per client IP (PUBLIC_REQUESTS_PER_MINUTE, PUBLIC_REQUESTS_PER_MINUTE_PER_IP), with 429 and `Retry-After` over a limit. Cacheable as the environment's `cache_max_age` allows"),
+                route(Get, "/v1/public/:client_id/flags/:key", public::evaluate_flag)
+                    .auth(Auth::Public)
+                    .summary("Evaluate one public flag")
+                    .query(&[Param {
+                        name: "user_id",
+                        description: "Stable user ID for percentage rollouts",
+                    }])
+                    .response(r#"{"key": "string", "enabled": "bool", "value": "json?"}"#)
+                    .notes("Flags that aren't public are a 404, as if they didn't exist. Rate limited and cacheable like the list"),
+            ],
+        },
+        Section {
+            title: "Admin",
+            routes: vec![
+                route(Get, "/v1/admin/stats", admin::stats)
+                    .auth(Auth::Admin)
+                    .summary("Row counts and database size")
+                    .response(r#"{"backend": "sqlite|postgres|memory", "users": "int", "projects": "int", "environments": "int", "flags": "int", "database_bytes": "int?"}"#)
+                    .notes("`database_bytes` is the SQLite file size (excluding an unmerged WAL) or `pg_database_size`; null for in-memory storage"),
+                route(Get, "/v1/admin/backup", admin::backup)
+                    .auth(Auth::Admin)
+                    .summary("Download a consistent snapshot of the SQLite database")
+                    .notes("Returns the database file (`application/vnd.sqlite3`), taken without pausing the server; restore it with `flaglite-api restore --input`. 400 with PostgreSQL, which has pg_dump"),
+                route(Get, "/v1/admin/projects/:project_id/quota", admin::get_quota)
+                    .auth(Auth::Admin)
+                    .summary("A project's request quota")
+                    .response("ProjectQuota"),
+                route(Get, "/v1/admin/jobs", admin::jobs)
+                    .auth(Auth::Admin)
+                    .summary("Run counts of the background jobs")
+                    .response(r#"[{"name": "string", "interval_secs": "int", "running": "bool", "runs": "int", "failures": "int", "retries": "int", "last_run_at": "datetime?", "last_duration_ms": "int?", "last_success_at": "datetime?", "last_error": "string?"}]"#)
+                    .notes("Counted since this instance started, so each instance reports its own"),
+                route(Get, "/v1/admin/maintenance", admin::get_maintenance)
+                    .auth(Auth::Admin)
+                    .summary("Whether the server is in read-only maintenance mode")
+                    .response(r#"{"read_only": "bool", "message": "string?"}"#),
+                route(Put, "/v1/admin/maintenance", admin::set_maintenance)
+                    .auth(Auth::Admin)
+                    .summary("Turn read-only maintenance mode on or off")
+                    .request(r#"{"read_only": "bool", "message": "string?"}"#)
+                    .response(r#"{"read_only": "bool", "message": "string?"}"#)
+                    .read_only()
+                    .notes("While on, every endpoint that writes returns 503 with `code: read_only` and `message` (or a default) as the error; reads, login and flag evaluation keep working. Not persisted: a restart goes back to READ_ONLY"),
+                route(Put, "/v1/admin/projects/:project_id/quota", admin::set_quota)
+                    .auth(Auth::Admin)
+                    .summary("Set a project's request quota")
+                    .request(r#"{"requests_per_minute": "int?", "per_key_requests_per_minute": "int?"}"#)
+                    .response("ProjectQuota")
+                    .notes("Null or omitted removes a limit. Requests with the project's `ffl_env_`/`ffl_proj_` keys over a limit get 429 with `Retry-After`"),
+            ],
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_routes_are_unique_and_documented() {
+        let mut seen = HashSet::new();
+        for section in sections() {
+            for route in section.routes {
+                let e = route.endpoint;
+                assert!(
+                    seen.insert((e.method, e.path)),
+                    "{} {} declared twice",
+                    e.method.as_str(),
+                    e.path
+                );
+                assert!(!e.summary.is_empty(), "{} has no summary", e.path);
+
+                if let Some(request) = e.request {
+                    assert!(
+                        serde_json::from_str::<serde_json::Value>(request).is_ok(),
+                        "{} request shape is not JSON",
+                        e.path
+                    );
+                }
+                if let Some(response) = e.response {
+                    let name = response.trim_end_matches("[]");
+                    assert!(
+                        serde_json::from_str::<serde_json::Value>(response).is_ok()
+                            || SCHEMAS.iter().any(|(n, _)| *n == name),
+                        "{} response is neither JSON nor a known schema",
+                        e.path
+                    );
+                }
+            }
+        }
+
+        for (name, schema) in SCHEMAS {
+            assert!(
+                serde_json::from_str::<serde_json::Value>(schema).is_ok(),
+                "schema {name} is not JSON"
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_path() {
+        let route = route(
+            Method::Get,
+            "/v1/projects/:project_id/flags/:key",
+            || async { "OK" },
+        );
+        assert_eq!(
+            route.endpoint.display_path(),
+            "/v1/projects/{project_id}/flags/{key}"
+        );
+    }
+
+    #[test]
+    fn test_scopes() {
+        let scope = |method: Method, path: &str| {
+            sections()
+                .into_iter()
+                .flat_map(|s| s.routes)
+                .find(|r| r.endpoint.method == method && r.endpoint.path == path)
+                .map(|r| r.endpoint.scope)
+                .unwrap()
+        };
+
+        assert_eq!(scope(Method::Get, "/v1/projects"), ApiKeyScope::Read);
+        assert_eq!(
+            scope(
+                Method::Post,
+                "/v1/projects/:project_id/flags/:key/assignments"
+            ),
+            ApiKeyScope::Read
+        );
+        assert_eq!(scope(Method::Post, "/v1/projects"), ApiKeyScope::Write);
+        assert_eq!(
+            scope(Method::Delete, "/v1/projects/:project_id/flags/:key"),
+            ApiKeyScope::Write
+        );
+        assert_eq!(scope(Method::Post, "/v1/auth/api-keys"), ApiKeyScope::Admin);
+        assert_eq!(
+            scope(Method::Post, "/v1/orgs/:org_id/api-keys"),
+            ApiKeyScope::Admin
+        );
+
+        assert!(ApiKeyScope::Read < ApiKeyScope::Write && ApiKeyScope::Write < ApiKeyScope::Admin);
+        assert_eq!("WRITE".parse::<ApiKeyScope>(), Ok(ApiKeyScope::Write));
+        assert!("owner".parse::<ApiKeyScope>().is_err());
+    }
+
+    #[test]
+    fn test_sdk_and_management_apis_split_the_routes() {
+        let endpoints: Vec<Endpoint> = sections()
+            .into_iter()
+            .flat_map(|s| s.routes)
+            .map(|r| r.endpoint)
+            .collect();
+        let served =
+            |api: Api, path: &str| endpoints.iter().any(|e| e.path == path && api.serves(e));
+
+        assert!(served(Api::Sdk, "/v1/flags/:key/evaluate"));
+        assert!(served(Api::Sdk, "/v1/public/:client_id/flags/:key"));
+        assert!(served(Api::Sdk, "/health"));
+        assert!(served(Api::Sdk, "/ready"));
+        assert!(served(Api::Sdk, "/metrics"));
+        assert!(!served(Api::Sdk, "/v1/auth/signup"));
+        assert!(!served(Api::Sdk, "/v1/admin/maintenance"));
+
+        assert!(!served(Api::Management, "/v1/flags/:key/evaluate"));
+        assert!(!served(Api::Management, "/v1/public/:client_id/flags"));
+        assert!(served(Api::Management, "/health"));
+        assert!(served(Api::Management, "/v1/projects"));
+
+        for e in &endpoints {
+            assert!(Api::All.serves(e));
+            assert!(
+                Api::Sdk.serves(e) || Api::Management.serves(e),
+                "{} is on neither listener",
+                e.path
+            );
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Signed flag snapshots for SDKs on devices we don't control
+//!
+//! Browser and mobile SDKs can't keep a secret, so each environment gets its
+//! own Ed25519 key pair. Snapshots are signed as compact JWS (`alg: EdDSA`)
+//! and SDKs verify them with the public keys published as a JWK set. A
+//! rotated key stays published for [`RETIRED_KEY_GRACE_DAYS`] so payloads cached
+//! on devices still verify.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use ed25519_dalek::Signer;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::error::{AppError, Result};
+use crate::models::SigningKey;
+
+pub const ALGORITHM: &str = "EdDSA";
+
+/// Days a rotated-out key is still published
+pub const RETIRED_KEY_GRACE_DAYS: i64 = 7;
+
+/// Public half of a signing key, as an RFC 8037 JWK
+#[derive(Debug, Serialize)]
+pub struct Jwk {
+    pub kty: &'static str,
+    pub crv: &'static str,
+    pub alg: &'static str,
+    #[serde(rename = "use")]
+    pub use_: &'static str,
+    pub kid: String,
+    pub x: String,
+}
+
+impl From<&SigningKey> for Jwk {
+    fn from(key: &SigningKey) -> Self {
+        Jwk {
+            kty: "OKP",
+            crv: "Ed25519",
+            alg: ALGORITHM,
+            use_: "sig",
+            kid: key.id.clone(),
+            x: key.public_key.clone(),
+        }
+    }
+}
+
+/// Keys SDKs accept signatures from
+#[derive(Debug, Serialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+#[derive(Serialize)]
+struct Header<'a> {
+    alg: &'static str,
+    typ: &'static str,
+    kid: &'a str,
+}
+
+/// A fresh key pair for the environment
+pub fn generate(environment_id: &str, now: DateTime<Utc>) -> SigningKey {
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&rand::random::<[u8; 32]>());
+    SigningKey {
+        id: format!("sk_{}", Uuid::new_v4().simple()),
+        environment_id: environment_id.to_string(),
+        private_key: URL_SAFE_NO_PAD.encode(signing_key.to_bytes()),
+        public_key: URL_SAFE_NO_PAD.encode(signing_key.verifying_key().to_bytes()),
+        created_at: now,
+        retired_at: None,
+    }
+}
+
+/// The key new payloads are signed with: the newest one not retired
+pub fn active(keys: &[SigningKey]) -> Option<&SigningKey> {
+    keys.iter()
+        .filter(|k| k.retired_at.is_none())
+        .max_by_key(|k| k.created_at)
+}
+
+/// Keys SDKs should accept: the active ones and those retired within the grace period
+pub fn published(keys: &[SigningKey], now: DateTime<Utc>) -> Jwks {
+    let grace = Duration::days(RETIRED_KEY_GRACE_DAYS);
+    Jwks {
+        keys: keys
+            .iter()
+            .filter(|k| k.retired_at.is_none_or(|at| at + grace > now))
+            .map(Jwk::from)
+            .collect(),
+    }
+}
+
+/// `payload` as a compact JWS signed with `key`
+pub fn sign(key: &SigningKey, payload: &impl Serialize) -> Result<String> {
+    let seed: [u8; 32] = URL_SAFE_NO_PAD
+        .decode(&key.private_key)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| AppError::Internal(format!("Signing key {} is corrupt", key.id)))?;
+    let signing_key = ed25519_dalek::SigningKey::from_bytes(&seed);
+
+    let header = Header {
+        alg: ALGORITHM,
+        typ: "JWT",
+        kid: &key.id,
+    };
+    let signing_input = format!("{}.{}", segment(&header)?, segment(payload)?);
+    let signature = signing_key.sign(signing_input.as_bytes());
+
+    Ok(format!(
+        "{signing_input}.{}",
+        URL_SAFE_NO_PAD.encode(signature.to_bytes())
+    ))
+}
+
+/// Base64url of the value's JSON
+fn segment(value: &impl Serialize) -> Result<String> {
+    let json = serde_json::to_vec(value)
+        .map_err(|e| AppError::Internal(format!("Failed to encode payload: {e}")))?;
+    Ok(URL_SAFE_NO_PAD.encode(json))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+
+    #[test]
+    fn test_sign_verifies_with_published_key() {
+        let now = Utc::now();
+        let key = generate("env-1", now);
+        let jws = sign(&key, &serde_json::json!({ "flags": [] })).unwrap();
+
+        let parts: Vec<&str> = jws.split('.').collect();
+        assert_eq!(parts.len(), 3);
+        let header: serde_json::Value =
+            serde_json::from_slice(&URL_SAFE_NO_PAD.decode(parts[0]).unwrap()).unwrap();
+        assert_eq!(header["alg"], "EdDSA");
+        assert_eq!(header["kid"], key.id);
+
+        let jwks = published(std::slice::from_ref(&key), now);
+        let x: [u8; 32] = URL_SAFE_NO_PAD
+            .decode(&jwks.keys[0].x)
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let signature: [u8; 64] = URL_SAFE_NO_PAD
+            .decode(parts[2])
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let verifying_key = VerifyingKey::from_bytes(&x).unwrap();
+        let signing_input = format!("{}.{}", parts[0], parts[1]);
+        assert!(verifying_key
+            .verify(signing_input.as_bytes(), &Signature::from_bytes(&signature))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_rotated_keys_stay_published_for_grace_period() {
+        let now = Utc::now();
+        let mut old = generate("env-1", now - Duration::days(30));
+        old.retired_at = Some(now - Duration::days(1));
+        let mut expired = generate("env-1", now - Duration::days(60));
+        expired.retired_at = Some(now - Duration::days(8));
+        let current = generate("env-1", now);
+        let keys = vec![current.clone(), old.clone(), expired];
+
+        assert_eq!(active(&keys).unwrap().id, current.id);
+        let kids: Vec<String> = published(&keys, now)
+            .keys
+            .into_iter()
+            .map(|k| k.kid)
+            .collect();
+        assert_eq!(kids, vec![current.id, old.id]);
+    }
+}
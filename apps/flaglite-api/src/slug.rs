@@ -0,0 +1,62 @@
+//! Project slugs - URL-safe names that address a project in place of its id
+//! Examples: "Checkout Service" -> checkout-service, then checkout-service-2
+
+use std::collections::HashSet;
+
+/// Slug for a project without any other characters to go on
+const FALLBACK: &str = "project";
+
+/// Lowercase `name`, collapsing every run of other characters into one dash
+pub fn slugify(name: &str) -> String {
+    let mut slug = String::with_capacity(name.len());
+    for c in name.chars().flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        FALLBACK.to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// The slug for `name` that isn't in `taken`, numbering repeats from 2
+pub fn unique_slug(name: &str, taken: &HashSet<String>) -> String {
+    let base = slugify(name);
+    if !taken.contains(&base) {
+        return base;
+    }
+
+    (2..)
+        .map(|n| format!("{base}-{n}"))
+        .find(|slug| !taken.contains(slug))
+        .unwrap_or(base)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Checkout Service"), "checkout-service");
+        assert_eq!(slugify("  API / v2 (beta)  "), "api-v2-beta");
+        assert_eq!(slugify("default"), "default");
+        assert_eq!(slugify("🚀"), "project");
+    }
+
+    #[test]
+    fn test_unique_slug_numbers_repeats() {
+        let mut taken = HashSet::new();
+        assert_eq!(unique_slug("Web App", &taken), "web-app");
+
+        taken.insert("web-app".to_string());
+        taken.insert("web-app-2".to_string());
+        assert_eq!(unique_slug("web app", &taken), "web-app-3");
+    }
+}
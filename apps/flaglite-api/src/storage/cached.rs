@@ -0,0 +1,1039 @@
+//! Read-through cache in front of another storage backend
+//!
+//! Only the lookups on the evaluation path and the ones every authenticated
+//! request makes (API key, user, project) are cached. Mutations made through
+//! this instance invalidate the affected entries immediately, so a revoked key
+//! stops working at once here; changes made by other replicas become visible
+//! once entries expire (CACHE_TTL).
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::Storage;
+use crate::cache::TtlCache;
+use crate::error::Result;
+use crate::models::{
+    AccountToken, ApiKey, ChangeRequest, DeletedEnvironment, Environment, EvaluationCount, Flag,
+    FlagChange, FlagSwitch, FlagTemplate, FlagValue, Guard, Invite, InviteLink, ListedUser,
+    OrgMember, Organization, Project, ProjectMember, ProjectQuota, ProjectSummary, Ramp,
+    SigningKey, StorageStats, User, UserPreferences, UsernameChange,
+};
+
+/// Entries each lookup keeps, so a flood of distinct keys can't grow the
+/// cache without bound
+const MAX_ENTRIES: usize = 10_000;
+
+/// How long an unknown API key is remembered as unknown, at most: long enough
+/// to absorb a client retrying a bad key, short enough that a key created on
+/// another replica works almost at once
+const MISS_TTL: Duration = Duration::from_secs(1);
+
+pub struct CachedStorage {
+    inner: Arc<dyn Storage>,
+    /// (project_id, key) -> flag
+    flags: TtlCache<(String, String), Option<Flag>>,
+    /// (flag_id, environment_id) -> value
+    flag_values: TtlCache<(String, String), Option<FlagValue>>,
+    /// (flag_id, environment_id) -> users on its allowlist and denylist
+    listed_users: TtlCache<(String, String), Vec<ListedUser>>,
+    /// api_key -> environment
+    environments_by_key: TtlCache<String, Environment>,
+    /// (project_id, name) -> environment
+    environments_by_name: TtlCache<(String, String), Option<Environment>>,
+    /// key_hash -> user API key
+    api_keys: TtlCache<String, ApiKey>,
+    /// key_hashes found to match no key, kept apart so made-up keys can't
+    /// crowd real ones out of `api_keys`
+    api_key_misses: TtlCache<String, ()>,
+    /// user_id -> user
+    users: TtlCache<String, Option<User>>,
+    /// project_id -> project
+    projects: TtlCache<String, Option<Project>>,
+    /// api_key -> project
+    projects_by_key: TtlCache<String, Project>,
+    /// user_id -> the project a user key or JWT falls back to
+    first_projects: TtlCache<String, Option<Project>>,
+}
+
+impl CachedStorage {
+    pub fn new(inner: Arc<dyn Storage>, ttl: Duration) -> Self {
+        Self {
+            inner,
+            flags: TtlCache::new(ttl, MAX_ENTRIES),
+            flag_values: TtlCache::new(ttl, MAX_ENTRIES),
+            listed_users: TtlCache::new(ttl, MAX_ENTRIES),
+            environments_by_key: TtlCache::new(ttl, MAX_ENTRIES),
+            environments_by_name: TtlCache::new(ttl, MAX_ENTRIES),
+            api_keys: TtlCache::new(ttl, MAX_ENTRIES),
+            api_key_misses: TtlCache::new(ttl.min(MISS_TTL), MAX_ENTRIES),
+            users: TtlCache::new(ttl, MAX_ENTRIES),
+            projects: TtlCache::new(ttl, MAX_ENTRIES),
+            projects_by_key: TtlCache::new(ttl, MAX_ENTRIES),
+            first_projects: TtlCache::new(ttl, MAX_ENTRIES),
+        }
+    }
+
+    /// Drop every cached copy of a project that was created or changed
+    fn invalidate_project(&self, project: &Project) {
+        let stale = |cached: &Option<Project>| cached.as_ref().is_some_and(|p| p.id == project.id);
+        self.projects.remove(&project.id);
+        self.projects_by_key.remove(&project.api_key);
+        self.projects_by_key
+            .retain(|_, cached| cached.id != project.id);
+        self.first_projects.remove(&project.user_id);
+        self.first_projects.retain(|_, cached| !stale(cached));
+    }
+
+    /// Drop an environment that was deleted or restored, and everything
+    /// cached for it
+    fn forget_environment(&self, env: &Environment) {
+        self.environments_by_key
+            .retain(|_, cached| cached.id != env.id);
+        self.environments_by_name
+            .remove(&(env.project_id.clone(), env.name.clone()));
+        self.flag_values
+            .retain(|(_, environment_id), _| environment_id != &env.id);
+        self.listed_users
+            .retain(|(_, environment_id), _| environment_id != &env.id);
+    }
+}
+
+#[async_trait]
+impl Storage for CachedStorage {
+    // ============ Users ============
+
+    async fn create_user(&self, user: &User) -> Result<()> {
+        self.inner.create_user(user).await?;
+        self.users.remove(&user.id);
+        Ok(())
+    }
+
+    async fn set_password(
+        &self,
+        user_id: &str,
+        password_hash: &str,
+        bump_epoch: bool,
+    ) -> Result<()> {
+        self.inner
+            .set_password(user_id, password_hash, bump_epoch)
+            .await?;
+        self.users.remove(&user_id.to_string());
+        Ok(())
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>> {
+        self.inner.get_user_by_username(username).await
+    }
+
+    async fn get_user_by_id(&self, id: &str) -> Result<Option<User>> {
+        let cache_key = id.to_string();
+        if let Some(user) = self.users.get(&cache_key) {
+            return Ok(user);
+        }
+
+        let user = self.inner.get_user_by_id(id).await?;
+        self.users.insert(cache_key, user.clone());
+        Ok(user)
+    }
+
+    async fn update_user(&self, user: &User) -> Result<()> {
+        self.inner.update_user(user).await?;
+        self.users.remove(&user.id);
+        Ok(())
+    }
+
+    async fn username_exists(&self, username: &str) -> Result<bool> {
+        self.inner.username_exists(username).await
+    }
+
+    async fn rename_user(&self, change: &UsernameChange) -> Result<()> {
+        self.inner.rename_user(change).await?;
+        self.users.remove(&change.user_id);
+        Ok(())
+    }
+
+    async fn list_username_changes(&self, user_id: &str) -> Result<Vec<UsernameChange>> {
+        self.inner.list_username_changes(user_id).await
+    }
+
+    async fn get_username_change(&self, old_username: &str) -> Result<Option<UsernameChange>> {
+        self.inner.get_username_change(old_username).await
+    }
+
+    async fn get_user_preferences(&self, user_id: &str) -> Result<Option<UserPreferences>> {
+        self.inner.get_user_preferences(user_id).await
+    }
+
+    async fn set_user_preferences(&self, preferences: &UserPreferences) -> Result<()> {
+        self.inner.set_user_preferences(preferences).await
+    }
+
+    // ============ API Keys ============
+
+    async fn create_api_key(&self, api_key: &ApiKey) -> Result<()> {
+        self.inner.create_api_key(api_key).await?;
+        self.api_keys.remove(&api_key.key_hash);
+        self.api_key_misses.remove(&api_key.key_hash);
+        Ok(())
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        let cache_key = key_hash.to_string();
+        if let Some(api_key) = self.api_keys.get(&cache_key) {
+            return Ok(Some(api_key));
+        }
+        if self.api_key_misses.get(&cache_key).is_some() {
+            return Ok(None);
+        }
+
+        let api_key = self.inner.get_api_key_by_hash(key_hash).await?;
+        match &api_key {
+            Some(api_key) => self.api_keys.insert(cache_key, api_key.clone()),
+            None => self.api_key_misses.insert(cache_key, ()),
+        }
+        Ok(api_key)
+    }
+
+    async fn list_api_keys_by_user(&self, user_id: &str) -> Result<Vec<ApiKey>> {
+        self.inner.list_api_keys_by_user(user_id).await
+    }
+
+    async fn revoke_api_key(&self, id: &str) -> Result<()> {
+        self.inner.revoke_api_key(id).await?;
+        self.api_keys.retain(|_, cached| cached.id != id);
+        Ok(())
+    }
+
+    // ============ Invites ============
+
+    async fn create_invite(&self, invite: &Invite) -> Result<()> {
+        self.inner.create_invite(invite).await
+    }
+
+    async fn redeem_invite(&self, code: &str, user_id: &str, now: DateTime<Utc>) -> Result<bool> {
+        self.inner.redeem_invite(code, user_id, now).await
+    }
+
+    // ============ Account tokens ============
+
+    async fn create_account_token(&self, token: &AccountToken) -> Result<()> {
+        self.inner.create_account_token(token).await
+    }
+
+    async fn redeem_account_token(
+        &self,
+        token_hash: &str,
+        purpose: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AccountToken>> {
+        self.inner
+            .redeem_account_token(token_hash, purpose, now)
+            .await
+    }
+
+    // ============ Organizations ============
+
+    async fn create_organization(&self, org: &Organization) -> Result<()> {
+        self.inner.create_organization(org).await
+    }
+
+    async fn get_organization(&self, id: &str) -> Result<Option<Organization>> {
+        self.inner.get_organization(id).await
+    }
+
+    async fn list_organizations_by_user(&self, user_id: &str) -> Result<Vec<Organization>> {
+        self.inner.list_organizations_by_user(user_id).await
+    }
+
+    async fn add_org_member(&self, member: &OrgMember) -> Result<()> {
+        self.inner.add_org_member(member).await
+    }
+
+    async fn get_org_member(&self, org_id: &str, user_id: &str) -> Result<Option<OrgMember>> {
+        self.inner.get_org_member(org_id, user_id).await
+    }
+
+    async fn list_org_members(&self, org_id: &str) -> Result<Vec<OrgMember>> {
+        self.inner.list_org_members(org_id).await
+    }
+
+    async fn set_org_member_role(&self, org_id: &str, user_id: &str, role: &str) -> Result<bool> {
+        self.inner.set_org_member_role(org_id, user_id, role).await
+    }
+
+    async fn remove_org_member(&self, org_id: &str, user_id: &str) -> Result<bool> {
+        let removed = self.inner.remove_org_member(org_id, user_id).await?;
+        // The project their keys fall back to may be one they just lost
+        self.first_projects.remove(&user_id.to_string());
+        Ok(removed)
+    }
+
+    // ============ Projects ============
+
+    async fn create_project(&self, project: &Project) -> Result<()> {
+        self.inner.create_project(project).await?;
+        self.invalidate_project(project);
+        Ok(())
+    }
+
+    async fn create_project_with(
+        &self,
+        project: &Project,
+        environments: &[Environment],
+        flags: &[Flag],
+        flag_values: &[FlagValue],
+    ) -> Result<()> {
+        self.inner
+            .create_project_with(project, environments, flags, flag_values)
+            .await?;
+        self.invalidate_project(project);
+        for env in environments {
+            self.environments_by_key.remove(&env.api_key);
+            self.environments_by_name
+                .remove(&(env.project_id.clone(), env.name.clone()));
+        }
+        for flag in flags {
+            self.flags
+                .remove(&(flag.project_id.clone(), flag.key.clone()));
+        }
+        for fv in flag_values {
+            self.flag_values
+                .remove(&(fv.flag_id.clone(), fv.environment_id.clone()));
+        }
+        Ok(())
+    }
+
+    async fn get_project_by_id(&self, id: &str) -> Result<Option<Project>> {
+        let cache_key = id.to_string();
+        if let Some(project) = self.projects.get(&cache_key) {
+            return Ok(project);
+        }
+
+        let project = self.inner.get_project_by_id(id).await?;
+        self.projects.insert(cache_key, project.clone());
+        Ok(project)
+    }
+
+    async fn get_project_by_api_key(&self, api_key: &str) -> Result<Option<Project>> {
+        let cache_key = api_key.to_string();
+        if let Some(project) = self.projects_by_key.get(&cache_key) {
+            return Ok(Some(project));
+        }
+
+        let project = self.inner.get_project_by_api_key(api_key).await?;
+        if let Some(project) = &project {
+            self.projects_by_key.insert(cache_key, project.clone());
+        }
+        Ok(project)
+    }
+
+    async fn list_projects_by_user(&self, user_id: &str) -> Result<Vec<Project>> {
+        self.inner.list_projects_by_user(user_id).await
+    }
+
+    async fn get_first_project_by_user(&self, user_id: &str) -> Result<Option<Project>> {
+        let cache_key = user_id.to_string();
+        if let Some(project) = self.first_projects.get(&cache_key) {
+            return Ok(project);
+        }
+
+        let project = self.inner.get_first_project_by_user(user_id).await?;
+        self.first_projects.insert(cache_key, project.clone());
+        Ok(project)
+    }
+
+    async fn list_projects_by_org(&self, org_id: &str) -> Result<Vec<Project>> {
+        self.inner.list_projects_by_org(org_id).await
+    }
+
+    async fn update_project(&self, project: &Project) -> Result<()> {
+        self.inner.update_project(project).await?;
+        self.invalidate_project(project);
+        Ok(())
+    }
+
+    async fn project_summary(
+        &self,
+        project_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<ProjectSummary> {
+        self.inner.project_summary(project_id, now).await
+    }
+
+    // ============ Environments ============
+
+    async fn create_environment(&self, env: &Environment) -> Result<()> {
+        self.inner.create_environment(env).await?;
+        self.environments_by_key.remove(&env.api_key);
+        self.environments_by_name
+            .remove(&(env.project_id.clone(), env.name.clone()));
+        Ok(())
+    }
+
+    async fn get_environment_by_id(&self, id: &str) -> Result<Option<Environment>> {
+        self.inner.get_environment_by_id(id).await
+    }
+
+    async fn get_environment_by_api_key(&self, api_key: &str) -> Result<Option<Environment>> {
+        let cache_key = api_key.to_string();
+        if let Some(env) = self.environments_by_key.get(&cache_key) {
+            return Ok(Some(env));
+        }
+
+        let env = self.inner.get_environment_by_api_key(api_key).await?;
+        if let Some(env) = &env {
+            self.environments_by_key.insert(cache_key, env.clone());
+        }
+        Ok(env)
+    }
+
+    async fn get_environment_by_public_client_id(
+        &self,
+        client_id: &str,
+    ) -> Result<Option<Environment>> {
+        self.inner
+            .get_environment_by_public_client_id(client_id)
+            .await
+    }
+
+    async fn get_environment_by_name(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<Option<Environment>> {
+        let cache_key = (project_id.to_string(), name.to_string());
+        if let Some(env) = self.environments_by_name.get(&cache_key) {
+            return Ok(env);
+        }
+
+        let env = self.inner.get_environment_by_name(project_id, name).await?;
+        self.environments_by_name.insert(cache_key, env.clone());
+        Ok(env)
+    }
+
+    async fn update_environment(&self, env: &Environment) -> Result<()> {
+        self.inner.update_environment(env).await?;
+        // Under its old key too, if the key was just rotated
+        self.environments_by_key
+            .retain(|_, cached| cached.id != env.id);
+        self.environments_by_name
+            .remove(&(env.project_id.clone(), env.name.clone()));
+        Ok(())
+    }
+
+    async fn list_environments_by_project(&self, project_id: &str) -> Result<Vec<Environment>> {
+        self.inner.list_environments_by_project(project_id).await
+    }
+
+    // ============ Deleted environments ============
+
+    async fn delete_environment(
+        &self,
+        env: &Environment,
+        deleted: &DeletedEnvironment,
+    ) -> Result<()> {
+        self.inner.delete_environment(env, deleted).await?;
+        self.forget_environment(env);
+        Ok(())
+    }
+
+    async fn list_deleted_environments(&self, project_id: &str) -> Result<Vec<DeletedEnvironment>> {
+        self.inner.list_deleted_environments(project_id).await
+    }
+
+    async fn restore_environment(
+        &self,
+        env: &Environment,
+        values: &[FlagValue],
+        users: &[ListedUser],
+    ) -> Result<bool> {
+        let restored = self.inner.restore_environment(env, values, users).await?;
+        self.forget_environment(env);
+        Ok(restored)
+    }
+
+    async fn purge_deleted_environments(&self, now: DateTime<Utc>) -> Result<u64> {
+        self.inner.purge_deleted_environments(now).await
+    }
+
+    // ============ Flags ============
+
+    async fn create_flag(&self, flag: &Flag) -> Result<()> {
+        self.inner.create_flag(flag).await?;
+        self.flags
+            .remove(&(flag.project_id.clone(), flag.key.clone()));
+        Ok(())
+    }
+
+    async fn update_flag_ownership(&self, flag: &Flag) -> Result<()> {
+        self.inner.update_flag_ownership(flag).await?;
+        self.flags
+            .remove(&(flag.project_id.clone(), flag.key.clone()));
+        Ok(())
+    }
+
+    async fn update_flag_defaults(&self, flag: &Flag) -> Result<()> {
+        self.inner.update_flag_defaults(flag).await?;
+        self.flags
+            .remove(&(flag.project_id.clone(), flag.key.clone()));
+        Ok(())
+    }
+
+    async fn update_flag_visibility(&self, flag: &Flag) -> Result<()> {
+        self.inner.update_flag_visibility(flag).await?;
+        self.flags
+            .remove(&(flag.project_id.clone(), flag.key.clone()));
+        Ok(())
+    }
+
+    async fn get_flag_by_id(&self, id: &str) -> Result<Option<Flag>> {
+        self.inner.get_flag_by_id(id).await
+    }
+
+    async fn get_flag_by_key(&self, project_id: &str, key: &str) -> Result<Option<Flag>> {
+        let cache_key = (project_id.to_string(), key.to_string());
+        if let Some(flag) = self.flags.get(&cache_key) {
+            return Ok(flag);
+        }
+
+        let flag = self.inner.get_flag_by_key(project_id, key).await?;
+        self.flags.insert(cache_key, flag.clone());
+        Ok(flag)
+    }
+
+    async fn list_flags_by_project(&self, project_id: &str) -> Result<Vec<Flag>> {
+        self.inner.list_flags_by_project(project_id).await
+    }
+
+    async fn list_expired_flags(&self, now: DateTime<Utc>) -> Result<Vec<Flag>> {
+        self.inner.list_expired_flags(now).await
+    }
+
+    // ============ Flag Values ============
+
+    async fn create_flag_value(&self, flag_value: &FlagValue) -> Result<()> {
+        self.inner.create_flag_value(flag_value).await?;
+        self.flag_values.remove(&(
+            flag_value.flag_id.clone(),
+            flag_value.environment_id.clone(),
+        ));
+        Ok(())
+    }
+
+    async fn get_flag_value(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+    ) -> Result<Option<FlagValue>> {
+        let cache_key = (flag_id.to_string(), environment_id.to_string());
+        if let Some(fv) = self.flag_values.get(&cache_key) {
+            return Ok(fv);
+        }
+
+        let fv = self.inner.get_flag_value(flag_id, environment_id).await?;
+        self.flag_values.insert(cache_key, fv.clone());
+        Ok(fv)
+    }
+
+    async fn get_flag_switch(
+        &self,
+        project_id: &str,
+        environment_id: &str,
+        key: &str,
+    ) -> Result<Option<FlagSwitch>> {
+        // Served from the flag and value caches the other evaluations fill
+        let Some(flag) = self.get_flag_by_key(project_id, key).await? else {
+            return Ok(None);
+        };
+        let value = self.get_flag_value(&flag.id, environment_id).await?;
+        Ok(Some(FlagSwitch::new(&flag, value.as_ref())))
+    }
+
+    async fn update_flag_value(&self, flag_value: &FlagValue) -> Result<bool> {
+        let updated = self.inner.update_flag_value(flag_value).await;
+        // Drop the entry even on a version mismatch so a retry reads the current row
+        self.flag_values.remove(&(
+            flag_value.flag_id.clone(),
+            flag_value.environment_id.clone(),
+        ));
+        updated
+    }
+
+    async fn save_flag_values(&self, flag_values: &[FlagValue]) -> Result<bool> {
+        let saved = self.inner.save_flag_values(flag_values).await;
+        for flag_value in flag_values {
+            self.flag_values.remove(&(
+                flag_value.flag_id.clone(),
+                flag_value.environment_id.clone(),
+            ));
+        }
+        saved
+    }
+
+    async fn upsert_flag_values(&self, flag_values: &[FlagValue]) -> Result<()> {
+        let written = self.inner.upsert_flag_values(flag_values).await;
+        for fv in flag_values {
+            self.flag_values
+                .remove(&(fv.flag_id.clone(), fv.environment_id.clone()));
+        }
+        written
+    }
+
+    async fn list_flag_value_history(&self, flag_value_id: &str) -> Result<Vec<FlagValue>> {
+        self.inner.list_flag_value_history(flag_value_id).await
+    }
+
+    async fn list_flag_value_history_page(
+        &self,
+        flag_value_id: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<FlagValue>> {
+        self.inner
+            .list_flag_value_history_page(flag_value_id, before, limit)
+            .await
+    }
+
+    async fn list_flag_values_by_flag_ids(&self, flag_ids: &[String]) -> Result<Vec<FlagValue>> {
+        self.inner.list_flag_values_by_flag_ids(flag_ids).await
+    }
+
+    async fn delete_flag(&self, flag_id: &str) -> Result<()> {
+        self.inner.delete_flag(flag_id).await?;
+        self.flags
+            .retain(|_, flag| !matches!(flag, Some(f) if f.id == flag_id));
+        self.flag_values
+            .retain(|(cached_flag_id, _), _| cached_flag_id != flag_id);
+        self.listed_users
+            .retain(|(cached_flag_id, _), _| cached_flag_id != flag_id);
+        Ok(())
+    }
+
+    // ============ Allowlists and denylists ============
+
+    async fn add_listed_users(&self, users: &[ListedUser]) -> Result<()> {
+        self.inner.add_listed_users(users).await?;
+        for user in users {
+            self.listed_users
+                .remove(&(user.flag_id.clone(), user.environment_id.clone()));
+        }
+        Ok(())
+    }
+
+    async fn remove_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+        user_ids: &[String],
+    ) -> Result<u64> {
+        let removed = self
+            .inner
+            .remove_listed_users(flag_id, environment_id, list, user_ids)
+            .await?;
+        self.listed_users
+            .remove(&(flag_id.to_string(), environment_id.to_string()));
+        Ok(removed)
+    }
+
+    async fn clear_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+    ) -> Result<u64> {
+        let removed = self
+            .inner
+            .clear_listed_users(flag_id, environment_id, list)
+            .await?;
+        self.listed_users
+            .remove(&(flag_id.to_string(), environment_id.to_string()));
+        Ok(removed)
+    }
+
+    async fn list_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>> {
+        let cache_key = (flag_id.to_string(), environment_id.to_string());
+        if let Some(users) = self.listed_users.get(&cache_key) {
+            return Ok(users);
+        }
+
+        let users = self
+            .inner
+            .list_listed_users(flag_id, environment_id)
+            .await?;
+        self.listed_users.insert(cache_key, users.clone());
+        Ok(users)
+    }
+
+    async fn list_listed_users_by_environment(
+        &self,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>> {
+        self.inner
+            .list_listed_users_by_environment(environment_id)
+            .await
+    }
+
+    // ============ Usage ============
+
+    async fn record_evaluations(&self, counts: &[EvaluationCount]) -> Result<()> {
+        self.inner.record_evaluations(counts).await
+    }
+
+    async fn list_evaluation_counts(&self, flag_ids: &[String]) -> Result<Vec<EvaluationCount>> {
+        self.inner.list_evaluation_counts(flag_ids).await
+    }
+
+    // ============ Quotas ============
+
+    async fn get_project_quota(&self, project_id: &str) -> Result<Option<ProjectQuota>> {
+        self.inner.get_project_quota(project_id).await
+    }
+
+    async fn set_project_quota(&self, quota: &ProjectQuota) -> Result<()> {
+        self.inner.set_project_quota(quota).await
+    }
+
+    // ============ Flag Templates ============
+
+    async fn list_flag_templates(&self, project_id: &str) -> Result<Vec<FlagTemplate>> {
+        self.inner.list_flag_templates(project_id).await
+    }
+
+    async fn get_flag_template(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<Option<FlagTemplate>> {
+        self.inner.get_flag_template(project_id, name).await
+    }
+
+    async fn set_flag_template(&self, template: &FlagTemplate) -> Result<()> {
+        self.inner.set_flag_template(template).await
+    }
+
+    async fn delete_flag_template(&self, project_id: &str, name: &str) -> Result<bool> {
+        self.inner.delete_flag_template(project_id, name).await
+    }
+
+    // ============ Flag Changes ============
+
+    async fn record_flag_changes(
+        &self,
+        project_id: &str,
+        keys: &[String],
+        deleted: bool,
+    ) -> Result<i64> {
+        self.inner
+            .record_flag_changes(project_id, keys, deleted)
+            .await
+    }
+
+    async fn get_flag_revision(&self, project_id: &str) -> Result<i64> {
+        self.inner.get_flag_revision(project_id).await
+    }
+
+    async fn list_flag_changes(&self, project_id: &str, since: i64) -> Result<Vec<FlagChange>> {
+        self.inner.list_flag_changes(project_id, since).await
+    }
+
+    // ============ Signing Keys ============
+
+    async fn create_signing_key(&self, key: &SigningKey) -> Result<()> {
+        self.inner.create_signing_key(key).await
+    }
+
+    async fn list_signing_keys(&self, environment_id: &str) -> Result<Vec<SigningKey>> {
+        self.inner.list_signing_keys(environment_id).await
+    }
+
+    async fn retire_signing_keys(&self, environment_id: &str, at: DateTime<Utc>) -> Result<()> {
+        self.inner.retire_signing_keys(environment_id, at).await
+    }
+
+    // ============ Change Requests ============
+
+    async fn create_change_request(&self, change: &ChangeRequest) -> Result<()> {
+        self.inner.create_change_request(change).await
+    }
+
+    async fn get_change_request(&self, id: &str) -> Result<Option<ChangeRequest>> {
+        self.inner.get_change_request(id).await
+    }
+
+    async fn list_change_requests(
+        &self,
+        project_id: &str,
+        status: Option<&str>,
+    ) -> Result<Vec<ChangeRequest>> {
+        self.inner.list_change_requests(project_id, status).await
+    }
+
+    async fn review_change_request(&self, change: &ChangeRequest) -> Result<bool> {
+        self.inner.review_change_request(change).await
+    }
+
+    async fn reopen_change_request(&self, id: &str) -> Result<()> {
+        self.inner.reopen_change_request(id).await
+    }
+
+    // ============ Ramps ============
+
+    async fn create_ramp(&self, ramp: &Ramp) -> Result<()> {
+        self.inner.create_ramp(ramp).await
+    }
+
+    async fn get_ramp(&self, id: &str) -> Result<Option<Ramp>> {
+        self.inner.get_ramp(id).await
+    }
+
+    async fn list_ramps(&self, project_id: &str) -> Result<Vec<Ramp>> {
+        self.inner.list_ramps(project_id).await
+    }
+
+    async fn list_due_ramps(&self, now: DateTime<Utc>) -> Result<Vec<Ramp>> {
+        self.inner.list_due_ramps(now).await
+    }
+
+    async fn update_ramp(&self, ramp: &Ramp, from_status: &str) -> Result<bool> {
+        self.inner.update_ramp(ramp, from_status).await
+    }
+
+    async fn create_guard(&self, guard: &Guard) -> Result<()> {
+        self.inner.create_guard(guard).await
+    }
+
+    async fn get_guard(&self, id: &str) -> Result<Option<Guard>> {
+        self.inner.get_guard(id).await
+    }
+
+    async fn get_guard_by_token_hash(&self, token_hash: &str) -> Result<Option<Guard>> {
+        self.inner.get_guard_by_token_hash(token_hash).await
+    }
+
+    async fn list_guards(&self, project_id: &str) -> Result<Vec<Guard>> {
+        self.inner.list_guards(project_id).await
+    }
+
+    async fn list_watching_guards(&self) -> Result<Vec<Guard>> {
+        self.inner.list_watching_guards().await
+    }
+
+    async fn end_guard(&self, guard: &Guard) -> Result<bool> {
+        self.inner.end_guard(guard).await
+    }
+
+    // ============ Project sharing ============
+
+    async fn set_project_member(&self, member: &ProjectMember) -> Result<()> {
+        self.inner.set_project_member(member).await?;
+        self.first_projects.remove(&member.user_id);
+        Ok(())
+    }
+
+    async fn get_project_member(
+        &self,
+        project_id: &str,
+        user_id: &str,
+    ) -> Result<Option<ProjectMember>> {
+        self.inner.get_project_member(project_id, user_id).await
+    }
+
+    async fn list_project_members(&self, project_id: &str) -> Result<Vec<ProjectMember>> {
+        self.inner.list_project_members(project_id).await
+    }
+
+    async fn remove_project_member(&self, project_id: &str, user_id: &str) -> Result<bool> {
+        let removed = self
+            .inner
+            .remove_project_member(project_id, user_id)
+            .await?;
+        self.first_projects.remove(&user_id.to_string());
+        Ok(removed)
+    }
+
+    async fn list_projects_shared_with(&self, user_id: &str) -> Result<Vec<Project>> {
+        self.inner.list_projects_shared_with(user_id).await
+    }
+
+    async fn create_invite_link(&self, link: &InviteLink) -> Result<()> {
+        self.inner.create_invite_link(link).await
+    }
+
+    async fn get_invite_link(&self, id: &str) -> Result<Option<InviteLink>> {
+        self.inner.get_invite_link(id).await
+    }
+
+    async fn get_invite_link_by_token_hash(&self, token_hash: &str) -> Result<Option<InviteLink>> {
+        self.inner.get_invite_link_by_token_hash(token_hash).await
+    }
+
+    async fn list_invite_links(&self, project_id: &str) -> Result<Vec<InviteLink>> {
+        self.inner.list_invite_links(project_id).await
+    }
+
+    async fn use_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool> {
+        self.inner.use_invite_link(id, now).await
+    }
+
+    async fn revoke_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool> {
+        self.inner.revoke_invite_link(id, now).await
+    }
+
+    // ============ Retention ============
+
+    async fn prune_flag_value_history(&self, before: DateTime<Utc>) -> Result<u64> {
+        self.inner.prune_flag_value_history(before).await
+    }
+
+    async fn prune_evaluation_counts(&self, before: NaiveDate) -> Result<u64> {
+        self.inner.prune_evaluation_counts(before).await
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        self.inner.vacuum().await
+    }
+
+    // ============ Migrations ============
+
+    async fn backup(&self, path: &Path) -> Result<()> {
+        self.inner.backup(path).await
+    }
+
+    async fn run_migrations(&self) -> Result<()> {
+        self.inner.run_migrations().await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.inner.health_check().await
+    }
+
+    async fn stats(&self) -> Result<StorageStats> {
+        self.inner.stats().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_revoking_through_the_cache_takes_effect_at_once() {
+        let inner = Arc::new(MemoryStorage::new());
+        let storage = CachedStorage::new(inner.clone(), Duration::from_secs(60));
+        let key = |id: &str| ApiKey {
+            id: id.to_string(),
+            user_id: "u1".to_string(),
+            key_hash: format!("hash-{id}"),
+            key_prefix: "flg_abcd".to_string(),
+            name: None,
+            created_at: Utc::now(),
+            revoked_at: None,
+            org_id: None,
+            scope: "admin".to_string(),
+        };
+        storage.create_api_key(&key("k1")).await.unwrap();
+        storage.create_api_key(&key("k2")).await.unwrap();
+        assert!(storage
+            .get_api_key_by_hash("hash-k1")
+            .await
+            .unwrap()
+            .is_some());
+        assert!(storage
+            .get_api_key_by_hash("hash-k2")
+            .await
+            .unwrap()
+            .is_some());
+
+        // Served from the cache: a change made elsewhere isn't seen until expiry
+        inner.revoke_api_key("k2").await.unwrap();
+        assert!(storage
+            .get_api_key_by_hash("hash-k2")
+            .await
+            .unwrap()
+            .is_some());
+
+        storage.revoke_api_key("k1").await.unwrap();
+        assert!(storage
+            .get_api_key_by_hash("hash-k1")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_membership_changes_forget_the_fallback_project() {
+        let inner = Arc::new(MemoryStorage::new());
+        let storage = CachedStorage::new(inner.clone(), Duration::from_secs(60));
+        storage.get_first_project_by_user("u1").await.unwrap();
+        assert_eq!(storage.first_projects.len(), 1);
+
+        storage
+            .set_project_member(&ProjectMember {
+                project_id: "p1".to_string(),
+                user_id: "u1".to_string(),
+                role: "member".to_string(),
+                created_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        assert_eq!(storage.first_projects.len(), 0);
+
+        storage.get_first_project_by_user("u1").await.unwrap();
+        storage.remove_org_member("o1", "u1").await.unwrap();
+        assert_eq!(storage.first_projects.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_keys_are_remembered_briefly() {
+        let inner = Arc::new(MemoryStorage::new());
+        let storage = CachedStorage::new(inner.clone(), Duration::from_millis(20));
+        let key = |id: &str| ApiKey {
+            id: id.to_string(),
+            user_id: "u1".to_string(),
+            key_hash: format!("hash-{id}"),
+            key_prefix: "flg_abcd".to_string(),
+            name: None,
+            created_at: Utc::now(),
+            revoked_at: None,
+            org_id: None,
+            scope: "admin".to_string(),
+        };
+        for id in ["k1", "k2"] {
+            let hash = format!("hash-{id}");
+            assert!(storage.get_api_key_by_hash(&hash).await.unwrap().is_none());
+        }
+        // Misses don't take room from real keys
+        assert_eq!(storage.api_keys.len(), 0);
+
+        // Created through the cache, found at once
+        storage.create_api_key(&key("k1")).await.unwrap();
+        assert!(storage
+            .get_api_key_by_hash("hash-k1")
+            .await
+            .unwrap()
+            .is_some());
+
+        // Created behind the cache's back, found once the miss expires
+        inner.create_api_key(&key("k2")).await.unwrap();
+        assert!(storage
+            .get_api_key_by_hash("hash-k2")
+            .await
+            .unwrap()
+            .is_none());
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(storage
+            .get_api_key_by_hash("hash-k2")
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
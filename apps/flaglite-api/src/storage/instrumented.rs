@@ -0,0 +1,982 @@
+//! Timing of every storage operation
+//!
+//! Each call to the wrapped backend runs in a `storage` tracing span named
+//! after the operation, with the time it took recorded on the span and in
+//! [`StorageMetrics`]. Operations slower than SLOW_QUERY_MS are logged as
+//! warnings, to show which queries make a slow page slow.
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::Instrument;
+
+use super::Storage;
+use crate::error::Result;
+use crate::metrics::StorageMetrics;
+use crate::models::{
+    AccountToken, ApiKey, ChangeRequest, DeletedEnvironment, Environment, EvaluationCount, Flag,
+    FlagChange, FlagSwitch, FlagTemplate, FlagValue, Guard, Invite, InviteLink, ListedUser,
+    OrgMember, Organization, Project, ProjectMember, ProjectQuota, ProjectSummary, Ramp,
+    SigningKey, StorageStats, User, UserPreferences, UsernameChange,
+};
+
+pub struct InstrumentedStorage {
+    inner: Arc<dyn Storage>,
+    metrics: Arc<StorageMetrics>,
+    /// Operations taking at least this long are logged; None logs none
+    slow_query: Option<Duration>,
+}
+
+impl InstrumentedStorage {
+    pub fn new(
+        inner: Arc<dyn Storage>,
+        metrics: Arc<StorageMetrics>,
+        slow_query: Option<Duration>,
+    ) -> Self {
+        Self {
+            inner,
+            metrics,
+            slow_query,
+        }
+    }
+
+    async fn timed<T>(
+        &self,
+        operation: &'static str,
+        call: impl Future<Output = Result<T>>,
+    ) -> Result<T> {
+        let span = tracing::info_span!(
+            "storage",
+            operation,
+            elapsed_ms = tracing::field::Empty,
+            otel.name = %format!("storage {operation}"),
+        );
+        let started = Instant::now();
+        let result = call.instrument(span.clone()).await;
+        let elapsed = started.elapsed();
+
+        span.record("elapsed_ms", elapsed.as_millis() as u64);
+        self.metrics.record(operation, elapsed);
+        if self
+            .slow_query
+            .is_some_and(|threshold| elapsed >= threshold)
+        {
+            tracing::warn!(
+                parent: &span,
+                "Slow storage operation: {operation} took {}ms",
+                elapsed.as_millis()
+            );
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl Storage for InstrumentedStorage {
+    async fn create_user(&self, user: &User) -> Result<()> {
+        self.timed("create_user", self.inner.create_user(user))
+            .await
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>> {
+        self.timed(
+            "get_user_by_username",
+            self.inner.get_user_by_username(username),
+        )
+        .await
+    }
+
+    async fn get_user_by_id(&self, id: &str) -> Result<Option<User>> {
+        self.timed("get_user_by_id", self.inner.get_user_by_id(id))
+            .await
+    }
+
+    async fn update_user(&self, user: &User) -> Result<()> {
+        self.timed("update_user", self.inner.update_user(user))
+            .await
+    }
+
+    async fn set_password(
+        &self,
+        user_id: &str,
+        password_hash: &str,
+        bump_epoch: bool,
+    ) -> Result<()> {
+        self.timed(
+            "set_password",
+            self.inner.set_password(user_id, password_hash, bump_epoch),
+        )
+        .await
+    }
+
+    async fn username_exists(&self, username: &str) -> Result<bool> {
+        self.timed("username_exists", self.inner.username_exists(username))
+            .await
+    }
+
+    async fn rename_user(&self, change: &UsernameChange) -> Result<()> {
+        self.timed("rename_user", self.inner.rename_user(change))
+            .await
+    }
+
+    async fn list_username_changes(&self, user_id: &str) -> Result<Vec<UsernameChange>> {
+        self.timed(
+            "list_username_changes",
+            self.inner.list_username_changes(user_id),
+        )
+        .await
+    }
+
+    async fn get_username_change(&self, old_username: &str) -> Result<Option<UsernameChange>> {
+        self.timed(
+            "get_username_change",
+            self.inner.get_username_change(old_username),
+        )
+        .await
+    }
+
+    async fn get_user_preferences(&self, user_id: &str) -> Result<Option<UserPreferences>> {
+        self.timed(
+            "get_user_preferences",
+            self.inner.get_user_preferences(user_id),
+        )
+        .await
+    }
+
+    async fn set_user_preferences(&self, preferences: &UserPreferences) -> Result<()> {
+        self.timed(
+            "set_user_preferences",
+            self.inner.set_user_preferences(preferences),
+        )
+        .await
+    }
+
+    async fn create_api_key(&self, api_key: &ApiKey) -> Result<()> {
+        self.timed("create_api_key", self.inner.create_api_key(api_key))
+            .await
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        self.timed(
+            "get_api_key_by_hash",
+            self.inner.get_api_key_by_hash(key_hash),
+        )
+        .await
+    }
+
+    async fn list_api_keys_by_user(&self, user_id: &str) -> Result<Vec<ApiKey>> {
+        self.timed(
+            "list_api_keys_by_user",
+            self.inner.list_api_keys_by_user(user_id),
+        )
+        .await
+    }
+
+    async fn revoke_api_key(&self, id: &str) -> Result<()> {
+        self.timed("revoke_api_key", self.inner.revoke_api_key(id))
+            .await
+    }
+
+    async fn create_invite(&self, invite: &Invite) -> Result<()> {
+        self.timed("create_invite", self.inner.create_invite(invite))
+            .await
+    }
+
+    async fn redeem_invite(&self, code: &str, user_id: &str, now: DateTime<Utc>) -> Result<bool> {
+        self.timed(
+            "redeem_invite",
+            self.inner.redeem_invite(code, user_id, now),
+        )
+        .await
+    }
+
+    async fn create_account_token(&self, token: &AccountToken) -> Result<()> {
+        self.timed(
+            "create_account_token",
+            self.inner.create_account_token(token),
+        )
+        .await
+    }
+
+    async fn redeem_account_token(
+        &self,
+        token_hash: &str,
+        purpose: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AccountToken>> {
+        self.timed(
+            "redeem_account_token",
+            self.inner.redeem_account_token(token_hash, purpose, now),
+        )
+        .await
+    }
+
+    async fn create_organization(&self, org: &Organization) -> Result<()> {
+        self.timed("create_organization", self.inner.create_organization(org))
+            .await
+    }
+
+    async fn get_organization(&self, id: &str) -> Result<Option<Organization>> {
+        self.timed("get_organization", self.inner.get_organization(id))
+            .await
+    }
+
+    async fn list_organizations_by_user(&self, user_id: &str) -> Result<Vec<Organization>> {
+        self.timed(
+            "list_organizations_by_user",
+            self.inner.list_organizations_by_user(user_id),
+        )
+        .await
+    }
+
+    async fn add_org_member(&self, member: &OrgMember) -> Result<()> {
+        self.timed("add_org_member", self.inner.add_org_member(member))
+            .await
+    }
+
+    async fn get_org_member(&self, org_id: &str, user_id: &str) -> Result<Option<OrgMember>> {
+        self.timed("get_org_member", self.inner.get_org_member(org_id, user_id))
+            .await
+    }
+
+    async fn list_org_members(&self, org_id: &str) -> Result<Vec<OrgMember>> {
+        self.timed("list_org_members", self.inner.list_org_members(org_id))
+            .await
+    }
+
+    async fn set_org_member_role(&self, org_id: &str, user_id: &str, role: &str) -> Result<bool> {
+        self.timed(
+            "set_org_member_role",
+            self.inner.set_org_member_role(org_id, user_id, role),
+        )
+        .await
+    }
+
+    async fn remove_org_member(&self, org_id: &str, user_id: &str) -> Result<bool> {
+        self.timed(
+            "remove_org_member",
+            self.inner.remove_org_member(org_id, user_id),
+        )
+        .await
+    }
+
+    async fn create_project(&self, project: &Project) -> Result<()> {
+        self.timed("create_project", self.inner.create_project(project))
+            .await
+    }
+
+    async fn create_project_with(
+        &self,
+        project: &Project,
+        environments: &[Environment],
+        flags: &[Flag],
+        flag_values: &[FlagValue],
+    ) -> Result<()> {
+        self.timed(
+            "create_project_with",
+            self.inner
+                .create_project_with(project, environments, flags, flag_values),
+        )
+        .await
+    }
+
+    async fn get_project_by_id(&self, id: &str) -> Result<Option<Project>> {
+        self.timed("get_project_by_id", self.inner.get_project_by_id(id))
+            .await
+    }
+
+    async fn get_project_by_api_key(&self, api_key: &str) -> Result<Option<Project>> {
+        self.timed(
+            "get_project_by_api_key",
+            self.inner.get_project_by_api_key(api_key),
+        )
+        .await
+    }
+
+    async fn list_projects_by_user(&self, user_id: &str) -> Result<Vec<Project>> {
+        self.timed(
+            "list_projects_by_user",
+            self.inner.list_projects_by_user(user_id),
+        )
+        .await
+    }
+
+    async fn get_first_project_by_user(&self, user_id: &str) -> Result<Option<Project>> {
+        self.timed(
+            "get_first_project_by_user",
+            self.inner.get_first_project_by_user(user_id),
+        )
+        .await
+    }
+
+    async fn list_projects_by_org(&self, org_id: &str) -> Result<Vec<Project>> {
+        self.timed(
+            "list_projects_by_org",
+            self.inner.list_projects_by_org(org_id),
+        )
+        .await
+    }
+
+    async fn update_project(&self, project: &Project) -> Result<()> {
+        self.timed("update_project", self.inner.update_project(project))
+            .await
+    }
+
+    async fn project_summary(
+        &self,
+        project_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<ProjectSummary> {
+        self.timed(
+            "project_summary",
+            self.inner.project_summary(project_id, now),
+        )
+        .await
+    }
+
+    async fn create_environment(&self, env: &Environment) -> Result<()> {
+        self.timed("create_environment", self.inner.create_environment(env))
+            .await
+    }
+
+    async fn get_environment_by_id(&self, id: &str) -> Result<Option<Environment>> {
+        self.timed(
+            "get_environment_by_id",
+            self.inner.get_environment_by_id(id),
+        )
+        .await
+    }
+
+    async fn get_environment_by_api_key(&self, api_key: &str) -> Result<Option<Environment>> {
+        self.timed(
+            "get_environment_by_api_key",
+            self.inner.get_environment_by_api_key(api_key),
+        )
+        .await
+    }
+
+    async fn get_environment_by_public_client_id(
+        &self,
+        client_id: &str,
+    ) -> Result<Option<Environment>> {
+        self.timed(
+            "get_environment_by_public_client_id",
+            self.inner.get_environment_by_public_client_id(client_id),
+        )
+        .await
+    }
+
+    async fn get_environment_by_name(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<Option<Environment>> {
+        self.timed(
+            "get_environment_by_name",
+            self.inner.get_environment_by_name(project_id, name),
+        )
+        .await
+    }
+
+    async fn update_environment(&self, env: &Environment) -> Result<()> {
+        self.timed("update_environment", self.inner.update_environment(env))
+            .await
+    }
+
+    async fn list_environments_by_project(&self, project_id: &str) -> Result<Vec<Environment>> {
+        self.timed(
+            "list_environments_by_project",
+            self.inner.list_environments_by_project(project_id),
+        )
+        .await
+    }
+
+    async fn delete_environment(
+        &self,
+        env: &Environment,
+        deleted: &DeletedEnvironment,
+    ) -> Result<()> {
+        self.timed(
+            "delete_environment",
+            self.inner.delete_environment(env, deleted),
+        )
+        .await
+    }
+
+    async fn list_deleted_environments(&self, project_id: &str) -> Result<Vec<DeletedEnvironment>> {
+        self.timed(
+            "list_deleted_environments",
+            self.inner.list_deleted_environments(project_id),
+        )
+        .await
+    }
+
+    async fn restore_environment(
+        &self,
+        env: &Environment,
+        values: &[FlagValue],
+        users: &[ListedUser],
+    ) -> Result<bool> {
+        self.timed(
+            "restore_environment",
+            self.inner.restore_environment(env, values, users),
+        )
+        .await
+    }
+
+    async fn purge_deleted_environments(&self, now: DateTime<Utc>) -> Result<u64> {
+        self.timed(
+            "purge_deleted_environments",
+            self.inner.purge_deleted_environments(now),
+        )
+        .await
+    }
+
+    async fn create_flag(&self, flag: &Flag) -> Result<()> {
+        self.timed("create_flag", self.inner.create_flag(flag))
+            .await
+    }
+
+    async fn update_flag_ownership(&self, flag: &Flag) -> Result<()> {
+        self.timed(
+            "update_flag_ownership",
+            self.inner.update_flag_ownership(flag),
+        )
+        .await
+    }
+
+    async fn update_flag_defaults(&self, flag: &Flag) -> Result<()> {
+        self.timed(
+            "update_flag_defaults",
+            self.inner.update_flag_defaults(flag),
+        )
+        .await
+    }
+
+    async fn update_flag_visibility(&self, flag: &Flag) -> Result<()> {
+        self.timed(
+            "update_flag_visibility",
+            self.inner.update_flag_visibility(flag),
+        )
+        .await
+    }
+
+    async fn get_flag_by_id(&self, id: &str) -> Result<Option<Flag>> {
+        self.timed("get_flag_by_id", self.inner.get_flag_by_id(id))
+            .await
+    }
+
+    async fn get_flag_by_key(&self, project_id: &str, key: &str) -> Result<Option<Flag>> {
+        self.timed(
+            "get_flag_by_key",
+            self.inner.get_flag_by_key(project_id, key),
+        )
+        .await
+    }
+
+    async fn list_flags_by_project(&self, project_id: &str) -> Result<Vec<Flag>> {
+        self.timed(
+            "list_flags_by_project",
+            self.inner.list_flags_by_project(project_id),
+        )
+        .await
+    }
+
+    async fn list_expired_flags(&self, now: DateTime<Utc>) -> Result<Vec<Flag>> {
+        self.timed("list_expired_flags", self.inner.list_expired_flags(now))
+            .await
+    }
+
+    async fn create_flag_value(&self, flag_value: &FlagValue) -> Result<()> {
+        self.timed(
+            "create_flag_value",
+            self.inner.create_flag_value(flag_value),
+        )
+        .await
+    }
+
+    async fn get_flag_value(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+    ) -> Result<Option<FlagValue>> {
+        self.timed(
+            "get_flag_value",
+            self.inner.get_flag_value(flag_id, environment_id),
+        )
+        .await
+    }
+
+    async fn get_flag_switch(
+        &self,
+        project_id: &str,
+        environment_id: &str,
+        key: &str,
+    ) -> Result<Option<FlagSwitch>> {
+        self.timed(
+            "get_flag_switch",
+            self.inner.get_flag_switch(project_id, environment_id, key),
+        )
+        .await
+    }
+
+    async fn update_flag_value(&self, flag_value: &FlagValue) -> Result<bool> {
+        self.timed(
+            "update_flag_value",
+            self.inner.update_flag_value(flag_value),
+        )
+        .await
+    }
+
+    async fn save_flag_values(&self, flag_values: &[FlagValue]) -> Result<bool> {
+        self.timed("save_flag_values", self.inner.save_flag_values(flag_values))
+            .await
+    }
+
+    async fn upsert_flag_values(&self, flag_values: &[FlagValue]) -> Result<()> {
+        self.timed(
+            "upsert_flag_values",
+            self.inner.upsert_flag_values(flag_values),
+        )
+        .await
+    }
+
+    async fn list_flag_value_history(&self, flag_value_id: &str) -> Result<Vec<FlagValue>> {
+        self.timed(
+            "list_flag_value_history",
+            self.inner.list_flag_value_history(flag_value_id),
+        )
+        .await
+    }
+
+    async fn list_flag_value_history_page(
+        &self,
+        flag_value_id: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<FlagValue>> {
+        self.timed(
+            "list_flag_value_history_page",
+            self.inner
+                .list_flag_value_history_page(flag_value_id, before, limit),
+        )
+        .await
+    }
+
+    async fn list_flag_values_by_flag_ids(&self, flag_ids: &[String]) -> Result<Vec<FlagValue>> {
+        self.timed(
+            "list_flag_values_by_flag_ids",
+            self.inner.list_flag_values_by_flag_ids(flag_ids),
+        )
+        .await
+    }
+
+    async fn delete_flag(&self, flag_id: &str) -> Result<()> {
+        self.timed("delete_flag", self.inner.delete_flag(flag_id))
+            .await
+    }
+
+    async fn add_listed_users(&self, users: &[ListedUser]) -> Result<()> {
+        self.timed("add_listed_users", self.inner.add_listed_users(users))
+            .await
+    }
+
+    async fn remove_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+        user_ids: &[String],
+    ) -> Result<u64> {
+        self.timed(
+            "remove_listed_users",
+            self.inner
+                .remove_listed_users(flag_id, environment_id, list, user_ids),
+        )
+        .await
+    }
+
+    async fn clear_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+    ) -> Result<u64> {
+        self.timed(
+            "clear_listed_users",
+            self.inner.clear_listed_users(flag_id, environment_id, list),
+        )
+        .await
+    }
+
+    async fn list_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>> {
+        self.timed(
+            "list_listed_users",
+            self.inner.list_listed_users(flag_id, environment_id),
+        )
+        .await
+    }
+
+    async fn list_listed_users_by_environment(
+        &self,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>> {
+        self.timed(
+            "list_listed_users_by_environment",
+            self.inner.list_listed_users_by_environment(environment_id),
+        )
+        .await
+    }
+
+    async fn record_evaluations(&self, counts: &[EvaluationCount]) -> Result<()> {
+        self.timed("record_evaluations", self.inner.record_evaluations(counts))
+            .await
+    }
+
+    async fn list_evaluation_counts(&self, flag_ids: &[String]) -> Result<Vec<EvaluationCount>> {
+        self.timed(
+            "list_evaluation_counts",
+            self.inner.list_evaluation_counts(flag_ids),
+        )
+        .await
+    }
+
+    async fn get_project_quota(&self, project_id: &str) -> Result<Option<ProjectQuota>> {
+        self.timed(
+            "get_project_quota",
+            self.inner.get_project_quota(project_id),
+        )
+        .await
+    }
+
+    async fn set_project_quota(&self, quota: &ProjectQuota) -> Result<()> {
+        self.timed("set_project_quota", self.inner.set_project_quota(quota))
+            .await
+    }
+
+    async fn list_flag_templates(&self, project_id: &str) -> Result<Vec<FlagTemplate>> {
+        self.timed(
+            "list_flag_templates",
+            self.inner.list_flag_templates(project_id),
+        )
+        .await
+    }
+
+    async fn get_flag_template(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<Option<FlagTemplate>> {
+        self.timed(
+            "get_flag_template",
+            self.inner.get_flag_template(project_id, name),
+        )
+        .await
+    }
+
+    async fn set_flag_template(&self, template: &FlagTemplate) -> Result<()> {
+        self.timed("set_flag_template", self.inner.set_flag_template(template))
+            .await
+    }
+
+    async fn delete_flag_template(&self, project_id: &str, name: &str) -> Result<bool> {
+        self.timed(
+            "delete_flag_template",
+            self.inner.delete_flag_template(project_id, name),
+        )
+        .await
+    }
+
+    async fn record_flag_changes(
+        &self,
+        project_id: &str,
+        keys: &[String],
+        deleted: bool,
+    ) -> Result<i64> {
+        self.timed(
+            "record_flag_changes",
+            self.inner.record_flag_changes(project_id, keys, deleted),
+        )
+        .await
+    }
+
+    async fn get_flag_revision(&self, project_id: &str) -> Result<i64> {
+        self.timed(
+            "get_flag_revision",
+            self.inner.get_flag_revision(project_id),
+        )
+        .await
+    }
+
+    async fn list_flag_changes(&self, project_id: &str, since: i64) -> Result<Vec<FlagChange>> {
+        self.timed(
+            "list_flag_changes",
+            self.inner.list_flag_changes(project_id, since),
+        )
+        .await
+    }
+
+    async fn create_signing_key(&self, key: &SigningKey) -> Result<()> {
+        self.timed("create_signing_key", self.inner.create_signing_key(key))
+            .await
+    }
+
+    async fn list_signing_keys(&self, environment_id: &str) -> Result<Vec<SigningKey>> {
+        self.timed(
+            "list_signing_keys",
+            self.inner.list_signing_keys(environment_id),
+        )
+        .await
+    }
+
+    async fn retire_signing_keys(&self, environment_id: &str, at: DateTime<Utc>) -> Result<()> {
+        self.timed(
+            "retire_signing_keys",
+            self.inner.retire_signing_keys(environment_id, at),
+        )
+        .await
+    }
+
+    async fn create_change_request(&self, change: &ChangeRequest) -> Result<()> {
+        self.timed(
+            "create_change_request",
+            self.inner.create_change_request(change),
+        )
+        .await
+    }
+
+    async fn get_change_request(&self, id: &str) -> Result<Option<ChangeRequest>> {
+        self.timed("get_change_request", self.inner.get_change_request(id))
+            .await
+    }
+
+    async fn list_change_requests(
+        &self,
+        project_id: &str,
+        status: Option<&str>,
+    ) -> Result<Vec<ChangeRequest>> {
+        self.timed(
+            "list_change_requests",
+            self.inner.list_change_requests(project_id, status),
+        )
+        .await
+    }
+
+    async fn review_change_request(&self, change: &ChangeRequest) -> Result<bool> {
+        self.timed(
+            "review_change_request",
+            self.inner.review_change_request(change),
+        )
+        .await
+    }
+
+    async fn reopen_change_request(&self, id: &str) -> Result<()> {
+        self.timed(
+            "reopen_change_request",
+            self.inner.reopen_change_request(id),
+        )
+        .await
+    }
+
+    async fn create_ramp(&self, ramp: &Ramp) -> Result<()> {
+        self.timed("create_ramp", self.inner.create_ramp(ramp))
+            .await
+    }
+
+    async fn get_ramp(&self, id: &str) -> Result<Option<Ramp>> {
+        self.timed("get_ramp", self.inner.get_ramp(id)).await
+    }
+
+    async fn list_ramps(&self, project_id: &str) -> Result<Vec<Ramp>> {
+        self.timed("list_ramps", self.inner.list_ramps(project_id))
+            .await
+    }
+
+    async fn list_due_ramps(&self, now: DateTime<Utc>) -> Result<Vec<Ramp>> {
+        self.timed("list_due_ramps", self.inner.list_due_ramps(now))
+            .await
+    }
+
+    async fn update_ramp(&self, ramp: &Ramp, from_status: &str) -> Result<bool> {
+        self.timed("update_ramp", self.inner.update_ramp(ramp, from_status))
+            .await
+    }
+
+    async fn create_guard(&self, guard: &Guard) -> Result<()> {
+        self.timed("create_guard", self.inner.create_guard(guard))
+            .await
+    }
+
+    async fn get_guard(&self, id: &str) -> Result<Option<Guard>> {
+        self.timed("get_guard", self.inner.get_guard(id)).await
+    }
+
+    async fn get_guard_by_token_hash(&self, token_hash: &str) -> Result<Option<Guard>> {
+        self.timed(
+            "get_guard_by_token_hash",
+            self.inner.get_guard_by_token_hash(token_hash),
+        )
+        .await
+    }
+
+    async fn list_guards(&self, project_id: &str) -> Result<Vec<Guard>> {
+        self.timed("list_guards", self.inner.list_guards(project_id))
+            .await
+    }
+
+    async fn list_watching_guards(&self) -> Result<Vec<Guard>> {
+        self.timed("list_watching_guards", self.inner.list_watching_guards())
+            .await
+    }
+
+    async fn end_guard(&self, guard: &Guard) -> Result<bool> {
+        self.timed("end_guard", self.inner.end_guard(guard)).await
+    }
+
+    async fn set_project_member(&self, member: &ProjectMember) -> Result<()> {
+        self.timed("set_project_member", self.inner.set_project_member(member))
+            .await
+    }
+
+    async fn get_project_member(
+        &self,
+        project_id: &str,
+        user_id: &str,
+    ) -> Result<Option<ProjectMember>> {
+        self.timed(
+            "get_project_member",
+            self.inner.get_project_member(project_id, user_id),
+        )
+        .await
+    }
+
+    async fn list_project_members(&self, project_id: &str) -> Result<Vec<ProjectMember>> {
+        self.timed(
+            "list_project_members",
+            self.inner.list_project_members(project_id),
+        )
+        .await
+    }
+
+    async fn remove_project_member(&self, project_id: &str, user_id: &str) -> Result<bool> {
+        self.timed(
+            "remove_project_member",
+            self.inner.remove_project_member(project_id, user_id),
+        )
+        .await
+    }
+
+    async fn list_projects_shared_with(&self, user_id: &str) -> Result<Vec<Project>> {
+        self.timed(
+            "list_projects_shared_with",
+            self.inner.list_projects_shared_with(user_id),
+        )
+        .await
+    }
+
+    async fn create_invite_link(&self, link: &InviteLink) -> Result<()> {
+        self.timed("create_invite_link", self.inner.create_invite_link(link))
+            .await
+    }
+
+    async fn get_invite_link(&self, id: &str) -> Result<Option<InviteLink>> {
+        self.timed("get_invite_link", self.inner.get_invite_link(id))
+            .await
+    }
+
+    async fn get_invite_link_by_token_hash(&self, token_hash: &str) -> Result<Option<InviteLink>> {
+        self.timed(
+            "get_invite_link_by_token_hash",
+            self.inner.get_invite_link_by_token_hash(token_hash),
+        )
+        .await
+    }
+
+    async fn list_invite_links(&self, project_id: &str) -> Result<Vec<InviteLink>> {
+        self.timed(
+            "list_invite_links",
+            self.inner.list_invite_links(project_id),
+        )
+        .await
+    }
+
+    async fn use_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool> {
+        self.timed("use_invite_link", self.inner.use_invite_link(id, now))
+            .await
+    }
+
+    async fn revoke_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool> {
+        self.timed("revoke_invite_link", self.inner.revoke_invite_link(id, now))
+            .await
+    }
+
+    async fn prune_flag_value_history(&self, before: DateTime<Utc>) -> Result<u64> {
+        self.timed(
+            "prune_flag_value_history",
+            self.inner.prune_flag_value_history(before),
+        )
+        .await
+    }
+
+    async fn prune_evaluation_counts(&self, before: NaiveDate) -> Result<u64> {
+        self.timed(
+            "prune_evaluation_counts",
+            self.inner.prune_evaluation_counts(before),
+        )
+        .await
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        self.timed("vacuum", self.inner.vacuum()).await
+    }
+
+    async fn run_migrations(&self) -> Result<()> {
+        self.timed("run_migrations", self.inner.run_migrations())
+            .await
+    }
+
+    async fn backup(&self, path: &Path) -> Result<()> {
+        self.timed("backup", self.inner.backup(path)).await
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        self.timed("health_check", self.inner.health_check()).await
+    }
+
+    async fn stats(&self) -> Result<StorageStats> {
+        self.timed("stats", self.inner.stats()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::memory::MemoryStorage;
+
+    #[tokio::test]
+    async fn test_operations_are_timed_per_operation() {
+        let metrics = Arc::new(StorageMetrics::default());
+        let storage =
+            InstrumentedStorage::new(Arc::new(MemoryStorage::new()), metrics.clone(), None);
+
+        storage.get_user_by_id("u1").await.unwrap();
+        storage.get_user_by_id("u2").await.unwrap();
+        storage.list_flags_by_project("p1").await.unwrap();
+
+        let text = metrics.render();
+        assert!(text.contains(
+            "flaglite_storage_operation_duration_seconds_count{operation=\"get_user_by_id\"} 2\n"
+        ));
+        assert!(text.contains(
+            "flaglite_storage_operation_duration_seconds_count{operation=\"list_flags_by_project\"} 1\n"
+        ));
+        assert!(!text.contains("operation=\"create_user\""));
+    }
+}
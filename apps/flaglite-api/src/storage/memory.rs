@@ -0,0 +1,1642 @@
+//! In-memory storage backend
+//!
+//! Selected with `DATABASE_URL=memory://` or `flaglite-api serve --demo`.
+//! Nothing is persisted; intended for tests and zero-setup demos.
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use super::Storage;
+use crate::error::{AppError, Result};
+use crate::models::{
+    AccountToken, ApiKey, ChangeRequest, DeletedEnvironment, Environment, EnvironmentSummary,
+    EvaluationCount, Flag, FlagChange, FlagSwitch, FlagTemplate, FlagValue, Guard, Invite,
+    InviteLink, ListedUser, OrgMember, Organization, Project, ProjectMember, ProjectQuota,
+    ProjectSummary, Ramp, SigningKey, StorageStats, User, UserPreferences, UsernameChange,
+    CHANGE_APPROVED, CHANGE_PENDING, GUARD_WATCHING, RAMP_ACTIVE, ROLE_OWNER,
+};
+
+#[derive(Default)]
+struct MemoryData {
+    users: Vec<User>,
+    username_changes: Vec<UsernameChange>,
+    api_keys: Vec<ApiKey>,
+    invites: Vec<Invite>,
+    account_tokens: Vec<AccountToken>,
+    organizations: Vec<Organization>,
+    org_members: Vec<OrgMember>,
+    projects: Vec<Project>,
+    environments: Vec<Environment>,
+    deleted_environments: Vec<DeletedEnvironment>,
+    flags: Vec<Flag>,
+    flag_values: Vec<FlagValue>,
+    flag_value_history: Vec<FlagValue>,
+    evaluations: Vec<EvaluationCount>,
+    quotas: Vec<ProjectQuota>,
+    preferences: Vec<UserPreferences>,
+    signing_keys: Vec<SigningKey>,
+    flag_templates: Vec<FlagTemplate>,
+    /// Latest revision by project ID
+    flag_revisions: HashMap<String, i64>,
+    flag_changes: Vec<FlagChange>,
+    change_requests: Vec<ChangeRequest>,
+    ramps: Vec<Ramp>,
+    guards: Vec<Guard>,
+    listed_users: Vec<ListedUser>,
+    project_members: Vec<ProjectMember>,
+    invite_links: Vec<InviteLink>,
+}
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    data: RwLock<MemoryData>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A panic while holding the lock leaves plain data behind, so keep serving it
+    fn read(&self) -> RwLockReadGuard<'_, MemoryData> {
+        self.data.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, MemoryData> {
+        self.data.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl Storage for MemoryStorage {
+    // ============ Users ============
+
+    async fn create_user(&self, user: &User) -> Result<()> {
+        let mut data = self.write();
+        if data.users.iter().any(|u| u.username == user.username) {
+            return Err(AppError::UserAlreadyExists);
+        }
+        data.users.push(user.clone());
+        Ok(())
+    }
+
+    async fn get_user_by_username(&self, username: &str) -> Result<Option<User>> {
+        Ok(self
+            .read()
+            .users
+            .iter()
+            .find(|u| u.username == username)
+            .cloned())
+    }
+
+    async fn get_user_by_id(&self, id: &str) -> Result<Option<User>> {
+        Ok(self.read().users.iter().find(|u| u.id == id).cloned())
+    }
+
+    async fn update_user(&self, user: &User) -> Result<()> {
+        let mut data = self.write();
+        if let Some(existing) = data.users.iter_mut().find(|u| u.id == user.id) {
+            existing.email = user.email.clone();
+            existing.email_verified_at = user.email_verified_at;
+            existing.updated_at = user.updated_at;
+        }
+        Ok(())
+    }
+
+    async fn set_password(
+        &self,
+        user_id: &str,
+        password_hash: &str,
+        bump_epoch: bool,
+    ) -> Result<()> {
+        let mut data = self.write();
+        if let Some(existing) = data.users.iter_mut().find(|u| u.id == user_id) {
+            existing.password_hash = password_hash.to_string();
+            existing.session_epoch += i64::from(bump_epoch);
+            existing.updated_at = Utc::now();
+        }
+        Ok(())
+    }
+
+    async fn username_exists(&self, username: &str) -> Result<bool> {
+        Ok(self.read().users.iter().any(|u| u.username == username))
+    }
+
+    async fn rename_user(&self, change: &UsernameChange) -> Result<()> {
+        let mut data = self.write();
+        if data
+            .users
+            .iter()
+            .any(|u| u.username == change.new_username && u.id != change.user_id)
+        {
+            return Err(AppError::UserAlreadyExists);
+        }
+        if let Some(user) = data.users.iter_mut().find(|u| u.id == change.user_id) {
+            user.username = change.new_username.clone();
+            user.updated_at = change.changed_at;
+        }
+        data.username_changes.push(change.clone());
+        Ok(())
+    }
+
+    async fn list_username_changes(&self, user_id: &str) -> Result<Vec<UsernameChange>> {
+        let mut changes: Vec<_> = self
+            .read()
+            .username_changes
+            .iter()
+            .filter(|c| c.user_id == user_id)
+            .cloned()
+            .collect();
+        changes.sort_by_key(|c| std::cmp::Reverse(c.changed_at));
+        Ok(changes)
+    }
+
+    async fn get_username_change(&self, old_username: &str) -> Result<Option<UsernameChange>> {
+        Ok(self
+            .read()
+            .username_changes
+            .iter()
+            .filter(|c| c.old_username == old_username)
+            .max_by_key(|c| c.changed_at)
+            .cloned())
+    }
+
+    async fn get_user_preferences(&self, user_id: &str) -> Result<Option<UserPreferences>> {
+        Ok(self
+            .read()
+            .preferences
+            .iter()
+            .find(|p| p.user_id == user_id)
+            .cloned())
+    }
+
+    async fn set_user_preferences(&self, preferences: &UserPreferences) -> Result<()> {
+        let mut data = self.write();
+        data.preferences
+            .retain(|p| p.user_id != preferences.user_id);
+        data.preferences.push(preferences.clone());
+        Ok(())
+    }
+
+    // ============ API Keys ============
+
+    async fn create_api_key(&self, api_key: &ApiKey) -> Result<()> {
+        self.write().api_keys.push(api_key.clone());
+        Ok(())
+    }
+
+    async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>> {
+        Ok(self
+            .read()
+            .api_keys
+            .iter()
+            .find(|k| k.key_hash == key_hash && k.revoked_at.is_none())
+            .cloned())
+    }
+
+    async fn list_api_keys_by_user(&self, user_id: &str) -> Result<Vec<ApiKey>> {
+        let mut keys: Vec<ApiKey> = self
+            .read()
+            .api_keys
+            .iter()
+            .filter(|k| k.user_id == user_id)
+            .cloned()
+            .collect();
+        keys.sort_by_key(|k| std::cmp::Reverse(k.created_at));
+        Ok(keys)
+    }
+
+    async fn revoke_api_key(&self, id: &str) -> Result<()> {
+        let mut data = self.write();
+        if let Some(key) = data.api_keys.iter_mut().find(|k| k.id == id) {
+            key.revoked_at = Some(Utc::now());
+        }
+        Ok(())
+    }
+
+    // ============ Invites ============
+
+    async fn create_invite(&self, invite: &Invite) -> Result<()> {
+        self.write().invites.push(invite.clone());
+        Ok(())
+    }
+
+    async fn redeem_invite(&self, code: &str, user_id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let mut data = self.write();
+        match data
+            .invites
+            .iter_mut()
+            .find(|i| i.code == code && i.used_at.is_none() && i.expires_at > now)
+        {
+            Some(invite) => {
+                invite.used_by = Some(user_id.to_string());
+                invite.used_at = Some(now);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // ============ Account tokens ============
+
+    async fn create_account_token(&self, token: &AccountToken) -> Result<()> {
+        self.write().account_tokens.push(token.clone());
+        Ok(())
+    }
+
+    async fn redeem_account_token(
+        &self,
+        token_hash: &str,
+        purpose: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AccountToken>> {
+        let mut data = self.write();
+        Ok(data
+            .account_tokens
+            .iter_mut()
+            .find(|t| {
+                t.token_hash == token_hash
+                    && t.purpose == purpose
+                    && t.used_at.is_none()
+                    && t.expires_at > now
+            })
+            .map(|token| {
+                token.used_at = Some(now);
+                token.clone()
+            }))
+    }
+
+    // ============ Organizations ============
+
+    async fn create_organization(&self, org: &Organization) -> Result<()> {
+        self.write().organizations.push(org.clone());
+        Ok(())
+    }
+
+    async fn get_organization(&self, id: &str) -> Result<Option<Organization>> {
+        Ok(self
+            .read()
+            .organizations
+            .iter()
+            .find(|o| o.id == id)
+            .cloned())
+    }
+
+    async fn list_organizations_by_user(&self, user_id: &str) -> Result<Vec<Organization>> {
+        let data = self.read();
+        let mut orgs: Vec<Organization> = data
+            .organizations
+            .iter()
+            .filter(|o| {
+                data.org_members
+                    .iter()
+                    .any(|m| m.org_id == o.id && m.user_id == user_id)
+            })
+            .cloned()
+            .collect();
+        orgs.sort_by(|a, b| b.personal.cmp(&a.personal).then(a.name.cmp(&b.name)));
+        Ok(orgs)
+    }
+
+    async fn add_org_member(&self, member: &OrgMember) -> Result<()> {
+        let mut data = self.write();
+        if data
+            .org_members
+            .iter()
+            .any(|m| m.org_id == member.org_id && m.user_id == member.user_id)
+        {
+            return Err(AppError::BadRequest(
+                "User is already a member of this organization".to_string(),
+            ));
+        }
+        data.org_members.push(member.clone());
+        Ok(())
+    }
+
+    async fn get_org_member(&self, org_id: &str, user_id: &str) -> Result<Option<OrgMember>> {
+        Ok(self
+            .read()
+            .org_members
+            .iter()
+            .find(|m| m.org_id == org_id && m.user_id == user_id)
+            .cloned())
+    }
+
+    async fn list_org_members(&self, org_id: &str) -> Result<Vec<OrgMember>> {
+        let mut members: Vec<OrgMember> = self
+            .read()
+            .org_members
+            .iter()
+            .filter(|m| m.org_id == org_id)
+            .cloned()
+            .collect();
+        members.sort_by_key(|a| a.created_at);
+        Ok(members)
+    }
+
+    async fn set_org_member_role(&self, org_id: &str, user_id: &str, role: &str) -> Result<bool> {
+        let mut data = self.write();
+        let other_owner = data
+            .org_members
+            .iter()
+            .any(|m| m.org_id == org_id && m.user_id != user_id && m.role == ROLE_OWNER);
+        match data
+            .org_members
+            .iter_mut()
+            .find(|m| m.org_id == org_id && m.user_id == user_id)
+        {
+            Some(member) if role == ROLE_OWNER || member.role != ROLE_OWNER || other_owner => {
+                member.role = role.to_string();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn remove_org_member(&self, org_id: &str, user_id: &str) -> Result<bool> {
+        let mut data = self.write();
+        let other_owner = data
+            .org_members
+            .iter()
+            .any(|m| m.org_id == org_id && m.user_id != user_id && m.role == ROLE_OWNER);
+        let Some(i) = data
+            .org_members
+            .iter()
+            .position(|m| m.org_id == org_id && m.user_id == user_id)
+        else {
+            return Ok(false);
+        };
+        if data.org_members[i].role == ROLE_OWNER && !other_owner {
+            return Ok(false);
+        }
+        data.org_members.remove(i);
+        Ok(true)
+    }
+
+    // ============ Projects ============
+
+    async fn create_project(&self, project: &Project) -> Result<()> {
+        self.write().projects.push(project.clone());
+        Ok(())
+    }
+
+    async fn create_project_with(
+        &self,
+        project: &Project,
+        environments: &[Environment],
+        flags: &[Flag],
+        flag_values: &[FlagValue],
+    ) -> Result<()> {
+        // Everything is new, so there's nothing to collide with
+        let mut data = self.write();
+        data.projects.push(project.clone());
+        data.environments.extend_from_slice(environments);
+        data.flags.extend_from_slice(flags);
+        data.flag_values.extend_from_slice(flag_values);
+        data.flag_value_history.extend_from_slice(flag_values);
+        Ok(())
+    }
+
+    async fn get_project_by_id(&self, id: &str) -> Result<Option<Project>> {
+        Ok(self.read().projects.iter().find(|p| p.id == id).cloned())
+    }
+
+    async fn get_project_by_api_key(&self, api_key: &str) -> Result<Option<Project>> {
+        Ok(self
+            .read()
+            .projects
+            .iter()
+            .find(|p| p.api_key == api_key)
+            .cloned())
+    }
+
+    async fn list_projects_by_user(&self, user_id: &str) -> Result<Vec<Project>> {
+        let mut projects: Vec<Project> = self
+            .read()
+            .projects
+            .iter()
+            .filter(|p| p.user_id == user_id)
+            .cloned()
+            .collect();
+        projects.sort_by_key(|p| std::cmp::Reverse(p.created_at));
+        Ok(projects)
+    }
+
+    async fn get_first_project_by_user(&self, user_id: &str) -> Result<Option<Project>> {
+        Ok(self
+            .read()
+            .projects
+            .iter()
+            .find(|p| p.user_id == user_id)
+            .cloned())
+    }
+
+    async fn list_projects_by_org(&self, org_id: &str) -> Result<Vec<Project>> {
+        let mut projects: Vec<Project> = self
+            .read()
+            .projects
+            .iter()
+            .filter(|p| p.org_id.as_deref() == Some(org_id))
+            .cloned()
+            .collect();
+        projects.sort_by_key(|p| std::cmp::Reverse(p.created_at));
+        Ok(projects)
+    }
+
+    async fn update_project(&self, project: &Project) -> Result<()> {
+        if let Some(p) = self
+            .write()
+            .projects
+            .iter_mut()
+            .find(|p| p.id == project.id)
+        {
+            p.require_change_reason = project.require_change_reason;
+        }
+        Ok(())
+    }
+
+    async fn project_summary(
+        &self,
+        project_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<ProjectSummary> {
+        let data = self.read();
+        let flags: Vec<&Flag> = data
+            .flags
+            .iter()
+            .filter(|f| f.project_id == project_id)
+            .collect();
+        let mut envs: Vec<&Environment> = data
+            .environments
+            .iter()
+            .filter(|e| e.project_id == project_id)
+            .collect();
+        envs.sort_by_key(|e| e.sort_order);
+
+        let environments = envs
+            .into_iter()
+            .map(|env| {
+                let mut summary = EnvironmentSummary {
+                    name: env.name.clone(),
+                    created_at: env.created_at,
+                    enabled: 0,
+                    partial_rollout: 0,
+                    last_changed_at: None,
+                };
+                for flag in &flags {
+                    let value = data
+                        .flag_values
+                        .iter()
+                        .find(|v| v.flag_id == flag.id && v.environment_id == env.id);
+                    let enabled =
+                        value.map_or(flag.default_enabled, |v| v.enabled) && !flag.is_expired(now);
+                    summary.enabled += enabled as i64;
+                    summary.partial_rollout +=
+                        (enabled && value.is_some_and(|v| v.rollout_percentage < 100)) as i64;
+                    summary.last_changed_at =
+                        summary.last_changed_at.max(value.map(|v| v.updated_at));
+                }
+                summary
+            })
+            .collect();
+
+        Ok(ProjectSummary {
+            flags: flags.len() as i64,
+            last_changed_at: data
+                .flag_changes
+                .iter()
+                .filter(|c| c.project_id == project_id)
+                .map(|c| c.changed_at)
+                .max(),
+            environments,
+        })
+    }
+
+    // ============ Environments ============
+
+    async fn create_environment(&self, env: &Environment) -> Result<()> {
+        let mut data = self.write();
+        if data
+            .environments
+            .iter()
+            .any(|e| e.project_id == env.project_id && e.name == env.name)
+        {
+            return Err(AppError::EnvironmentInUse(env.name.clone()));
+        }
+        data.environments.push(env.clone());
+        Ok(())
+    }
+
+    async fn get_environment_by_id(&self, id: &str) -> Result<Option<Environment>> {
+        Ok(self
+            .read()
+            .environments
+            .iter()
+            .find(|e| e.id == id)
+            .cloned())
+    }
+
+    async fn get_environment_by_api_key(&self, api_key: &str) -> Result<Option<Environment>> {
+        Ok(self
+            .read()
+            .environments
+            .iter()
+            .find(|e| e.api_key == api_key)
+            .cloned())
+    }
+
+    async fn get_environment_by_public_client_id(
+        &self,
+        client_id: &str,
+    ) -> Result<Option<Environment>> {
+        Ok(self
+            .read()
+            .environments
+            .iter()
+            .find(|e| e.public_client_id.as_deref() == Some(client_id))
+            .cloned())
+    }
+
+    async fn get_environment_by_name(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<Option<Environment>> {
+        Ok(self
+            .read()
+            .environments
+            .iter()
+            .find(|e| e.project_id == project_id && e.name == name)
+            .cloned())
+    }
+
+    async fn update_environment(&self, env: &Environment) -> Result<()> {
+        let mut data = self.write();
+        if let Some(existing) = data.environments.iter_mut().find(|e| e.id == env.id) {
+            existing.protected = env.protected;
+            existing.frozen = env.frozen;
+            existing.sort_order = env.sort_order;
+            existing.color = env.color.clone();
+            existing.emoji = env.emoji.clone();
+            existing.cache_max_age = env.cache_max_age;
+            existing.cache_stale_while_revalidate = env.cache_stale_while_revalidate;
+            existing.public_client_id = env.public_client_id.clone();
+            existing.require_approval = env.require_approval;
+        }
+        Ok(())
+    }
+
+    async fn list_environments_by_project(&self, project_id: &str) -> Result<Vec<Environment>> {
+        let mut envs: Vec<Environment> = self
+            .read()
+            .environments
+            .iter()
+            .filter(|e| e.project_id == project_id)
+            .cloned()
+            .collect();
+        envs.sort_by_key(|e| e.sort_order);
+        Ok(envs)
+    }
+
+    // ============ Deleted environments ============
+
+    async fn delete_environment(
+        &self,
+        env: &Environment,
+        deleted: &DeletedEnvironment,
+    ) -> Result<()> {
+        let mut data = self.write();
+        data.flag_values.retain(|fv| fv.environment_id != env.id);
+        data.flag_value_history
+            .retain(|fv| fv.environment_id != env.id);
+        data.evaluations.retain(|c| c.environment_id != env.id);
+        data.listed_users.retain(|u| u.environment_id != env.id);
+        data.signing_keys.retain(|k| k.environment_id != env.id);
+        data.ramps.retain(|r| r.environment_id != env.id);
+        data.guards.retain(|g| g.environment_id != env.id);
+        data.environments.retain(|e| e.id != env.id);
+        data.deleted_environments.push(deleted.clone());
+        Ok(())
+    }
+
+    async fn list_deleted_environments(&self, project_id: &str) -> Result<Vec<DeletedEnvironment>> {
+        let mut deleted: Vec<DeletedEnvironment> = self
+            .read()
+            .deleted_environments
+            .iter()
+            .filter(|d| d.project_id == project_id)
+            .cloned()
+            .collect();
+        deleted.sort_by_key(|d| std::cmp::Reverse(d.deleted_at));
+        Ok(deleted)
+    }
+
+    async fn restore_environment(
+        &self,
+        env: &Environment,
+        values: &[FlagValue],
+        users: &[ListedUser],
+    ) -> Result<bool> {
+        let mut data = self.write();
+        if !data.deleted_environments.iter().any(|d| d.id == env.id) {
+            return Ok(false);
+        }
+        if data
+            .environments
+            .iter()
+            .any(|e| e.project_id == env.project_id && e.name == env.name)
+        {
+            return Err(AppError::EnvironmentInUse(env.name.clone()));
+        }
+        data.deleted_environments.retain(|d| d.id != env.id);
+        data.environments.push(env.clone());
+        data.flag_values.extend_from_slice(values);
+        data.flag_value_history.extend_from_slice(values);
+        data.listed_users.extend_from_slice(users);
+        Ok(true)
+    }
+
+    async fn purge_deleted_environments(&self, now: DateTime<Utc>) -> Result<u64> {
+        let mut data = self.write();
+        let before = data.deleted_environments.len();
+        data.deleted_environments.retain(|d| d.purge_after > now);
+        Ok((before - data.deleted_environments.len()) as u64)
+    }
+
+    // ============ Flags ============
+
+    async fn create_flag(&self, flag: &Flag) -> Result<()> {
+        let mut data = self.write();
+        if data
+            .flags
+            .iter()
+            .any(|f| f.project_id == flag.project_id && f.key == flag.key)
+        {
+            return Err(AppError::DuplicateFlag(flag.key.clone()));
+        }
+        data.flags.push(flag.clone());
+        Ok(())
+    }
+
+    async fn update_flag_ownership(&self, flag: &Flag) -> Result<()> {
+        let mut data = self.write();
+        if let Some(existing) = data.flags.iter_mut().find(|f| f.id == flag.id) {
+            existing.owner = flag.owner.clone();
+            existing.repository = flag.repository.clone();
+            existing.code_path = flag.code_path.clone();
+        }
+        Ok(())
+    }
+
+    async fn update_flag_defaults(&self, flag: &Flag) -> Result<()> {
+        let mut data = self.write();
+        if let Some(existing) = data.flags.iter_mut().find(|f| f.id == flag.id) {
+            existing.default_enabled = flag.default_enabled;
+            existing.default_value = flag.default_value.clone();
+        }
+        Ok(())
+    }
+
+    async fn update_flag_visibility(&self, flag: &Flag) -> Result<()> {
+        let mut data = self.write();
+        if let Some(existing) = data.flags.iter_mut().find(|f| f.id == flag.id) {
+            existing.public = flag.public;
+        }
+        Ok(())
+    }
+
+    async fn get_flag_by_id(&self, id: &str) -> Result<Option<Flag>> {
+        Ok(self.read().flags.iter().find(|f| f.id == id).cloned())
+    }
+
+    async fn get_flag_by_key(&self, project_id: &str, key: &str) -> Result<Option<Flag>> {
+        Ok(self
+            .read()
+            .flags
+            .iter()
+            .find(|f| f.project_id == project_id && f.key == key)
+            .cloned())
+    }
+
+    async fn list_flags_by_project(&self, project_id: &str) -> Result<Vec<Flag>> {
+        let mut flags: Vec<Flag> = self
+            .read()
+            .flags
+            .iter()
+            .filter(|f| f.project_id == project_id)
+            .cloned()
+            .collect();
+        flags.sort_by_key(|f| std::cmp::Reverse(f.created_at));
+        Ok(flags)
+    }
+
+    async fn list_expired_flags(&self, now: DateTime<Utc>) -> Result<Vec<Flag>> {
+        let mut flags: Vec<Flag> = self
+            .read()
+            .flags
+            .iter()
+            .filter(|f| f.is_expired(now))
+            .cloned()
+            .collect();
+        flags.sort_by_key(|f| f.expires_at);
+        Ok(flags)
+    }
+
+    // ============ Flag Values ============
+
+    async fn create_flag_value(&self, flag_value: &FlagValue) -> Result<()> {
+        let mut data = self.write();
+        data.flag_values.push(flag_value.clone());
+        data.flag_value_history.push(flag_value.clone());
+        Ok(())
+    }
+
+    async fn get_flag_value(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+    ) -> Result<Option<FlagValue>> {
+        Ok(self
+            .read()
+            .flag_values
+            .iter()
+            .find(|fv| fv.flag_id == flag_id && fv.environment_id == environment_id)
+            .cloned())
+    }
+
+    async fn get_flag_switch(
+        &self,
+        project_id: &str,
+        environment_id: &str,
+        key: &str,
+    ) -> Result<Option<FlagSwitch>> {
+        let data = self.read();
+        Ok(data
+            .flags
+            .iter()
+            .find(|f| f.project_id == project_id && f.key == key)
+            .map(|flag| {
+                let value = data
+                    .flag_values
+                    .iter()
+                    .find(|fv| fv.flag_id == flag.id && fv.environment_id == environment_id);
+                FlagSwitch::new(flag, value)
+            }))
+    }
+
+    async fn update_flag_value(&self, flag_value: &FlagValue) -> Result<bool> {
+        let mut guard = self.write();
+        let data = &mut *guard;
+        match data
+            .flag_values
+            .iter_mut()
+            .find(|fv| fv.id == flag_value.id && fv.version == flag_value.version - 1)
+        {
+            Some(existing) => {
+                *existing = flag_value.clone();
+                data.flag_value_history.push(flag_value.clone());
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn save_flag_values(&self, flag_values: &[FlagValue]) -> Result<bool> {
+        let mut data = self.write();
+        let current = |fv: &FlagValue| {
+            data.flag_values
+                .iter()
+                .position(|existing| existing.id == fv.id && existing.version == fv.version - 1)
+        };
+        // Check every update before applying any, so a lost race writes nothing
+        let mut positions = Vec::with_capacity(flag_values.len());
+        for fv in flag_values {
+            match (fv.version, current(fv)) {
+                (1, _) => positions.push(None),
+                (_, Some(i)) => positions.push(Some(i)),
+                (_, None) => return Ok(false),
+            }
+        }
+        for (fv, position) in flag_values.iter().zip(positions) {
+            match position {
+                Some(i) => data.flag_values[i] = fv.clone(),
+                None => data.flag_values.push(fv.clone()),
+            }
+            data.flag_value_history.push(fv.clone());
+        }
+        Ok(true)
+    }
+
+    async fn upsert_flag_values(&self, flag_values: &[FlagValue]) -> Result<()> {
+        let mut data = self.write();
+        for fv in flag_values {
+            let existing = data
+                .flag_values
+                .iter_mut()
+                .find(|e| e.flag_id == fv.flag_id && e.environment_id == fv.environment_id);
+            let written = match existing {
+                Some(existing) => {
+                    *existing = FlagValue {
+                        id: existing.id.clone(),
+                        version: existing.version + 1,
+                        ..fv.clone()
+                    };
+                    existing.clone()
+                }
+                None => {
+                    data.flag_values.push(fv.clone());
+                    fv.clone()
+                }
+            };
+            data.flag_value_history.push(written);
+        }
+        Ok(())
+    }
+
+    async fn list_flag_value_history(&self, flag_value_id: &str) -> Result<Vec<FlagValue>> {
+        let mut history: Vec<FlagValue> = self
+            .read()
+            .flag_value_history
+            .iter()
+            .filter(|fv| fv.id == flag_value_id)
+            .cloned()
+            .collect();
+        history.sort_by_key(|h| std::cmp::Reverse(h.version));
+        Ok(history)
+    }
+
+    async fn list_flag_value_history_page(
+        &self,
+        flag_value_id: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<FlagValue>> {
+        let mut history = self.list_flag_value_history(flag_value_id).await?;
+        history.retain(|fv| before.is_none_or(|before| fv.version < before));
+        history.truncate(usize::try_from(limit).unwrap_or_default());
+        Ok(history)
+    }
+
+    async fn list_flag_values_by_flag_ids(&self, flag_ids: &[String]) -> Result<Vec<FlagValue>> {
+        Ok(self
+            .read()
+            .flag_values
+            .iter()
+            .filter(|fv| flag_ids.contains(&fv.flag_id))
+            .cloned()
+            .collect())
+    }
+
+    async fn delete_flag(&self, flag_id: &str) -> Result<()> {
+        let mut data = self.write();
+        data.flag_values.retain(|fv| fv.flag_id != flag_id);
+        data.flag_value_history.retain(|fv| fv.flag_id != flag_id);
+        data.evaluations.retain(|c| c.flag_id != flag_id);
+        data.ramps.retain(|r| r.flag_id != flag_id);
+        data.guards.retain(|g| g.flag_id != flag_id);
+        data.listed_users.retain(|u| u.flag_id != flag_id);
+        data.flags.retain(|f| f.id != flag_id);
+        Ok(())
+    }
+
+    // ============ Allowlists and denylists ============
+
+    async fn add_listed_users(&self, users: &[ListedUser]) -> Result<()> {
+        let mut data = self.write();
+        for user in users {
+            match data.listed_users.iter_mut().find(|u| {
+                u.flag_id == user.flag_id
+                    && u.environment_id == user.environment_id
+                    && u.user_id == user.user_id
+            }) {
+                Some(existing) if existing.list == user.list => {}
+                Some(existing) => *existing = user.clone(),
+                None => data.listed_users.push(user.clone()),
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+        user_ids: &[String],
+    ) -> Result<u64> {
+        let mut data = self.write();
+        let before = data.listed_users.len();
+        data.listed_users.retain(|u| {
+            !(u.flag_id == flag_id
+                && u.environment_id == environment_id
+                && u.list == list
+                && user_ids.contains(&u.user_id))
+        });
+        Ok((before - data.listed_users.len()) as u64)
+    }
+
+    async fn clear_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+    ) -> Result<u64> {
+        let mut data = self.write();
+        let before = data.listed_users.len();
+        data.listed_users.retain(|u| {
+            !(u.flag_id == flag_id && u.environment_id == environment_id && u.list == list)
+        });
+        Ok((before - data.listed_users.len()) as u64)
+    }
+
+    async fn list_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>> {
+        Ok(self
+            .read()
+            .listed_users
+            .iter()
+            .filter(|u| u.flag_id == flag_id && u.environment_id == environment_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn list_listed_users_by_environment(
+        &self,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>> {
+        Ok(self
+            .read()
+            .listed_users
+            .iter()
+            .filter(|u| u.environment_id == environment_id)
+            .cloned()
+            .collect())
+    }
+
+    // ============ Usage ============
+
+    async fn record_evaluations(&self, counts: &[EvaluationCount]) -> Result<()> {
+        let mut data = self.write();
+        for c in counts {
+            if !data.flags.iter().any(|f| f.id == c.flag_id) {
+                continue;
+            }
+
+            let existing = data.evaluations.iter_mut().find(|e| {
+                e.flag_id == c.flag_id && e.environment_id == c.environment_id && e.day == c.day
+            });
+            match existing {
+                Some(e) => {
+                    e.count += c.count;
+                    e.last_evaluated_at = e.last_evaluated_at.max(c.last_evaluated_at);
+                }
+                None => data.evaluations.push(c.clone()),
+            }
+        }
+        Ok(())
+    }
+
+    async fn list_evaluation_counts(&self, flag_ids: &[String]) -> Result<Vec<EvaluationCount>> {
+        let mut counts: Vec<EvaluationCount> = self
+            .read()
+            .evaluations
+            .iter()
+            .filter(|c| flag_ids.contains(&c.flag_id))
+            .cloned()
+            .collect();
+        counts.sort_by_key(|c| std::cmp::Reverse(c.day));
+        Ok(counts)
+    }
+
+    // ============ Quotas ============
+
+    async fn get_project_quota(&self, project_id: &str) -> Result<Option<ProjectQuota>> {
+        Ok(self
+            .read()
+            .quotas
+            .iter()
+            .find(|q| q.project_id == project_id)
+            .cloned())
+    }
+
+    async fn set_project_quota(&self, quota: &ProjectQuota) -> Result<()> {
+        let mut data = self.write();
+        data.quotas.retain(|q| q.project_id != quota.project_id);
+        data.quotas.push(quota.clone());
+        Ok(())
+    }
+
+    // ============ Flag Templates ============
+
+    async fn list_flag_templates(&self, project_id: &str) -> Result<Vec<FlagTemplate>> {
+        let mut templates: Vec<FlagTemplate> = self
+            .read()
+            .flag_templates
+            .iter()
+            .filter(|t| t.project_id == project_id)
+            .cloned()
+            .collect();
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(templates)
+    }
+
+    async fn get_flag_template(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<Option<FlagTemplate>> {
+        Ok(self
+            .read()
+            .flag_templates
+            .iter()
+            .find(|t| t.project_id == project_id && t.name == name)
+            .cloned())
+    }
+
+    async fn set_flag_template(&self, template: &FlagTemplate) -> Result<()> {
+        let mut data = self.write();
+        data.flag_templates
+            .retain(|t| !(t.project_id == template.project_id && t.name == template.name));
+        data.flag_templates.push(template.clone());
+        Ok(())
+    }
+
+    async fn delete_flag_template(&self, project_id: &str, name: &str) -> Result<bool> {
+        let mut data = self.write();
+        let before = data.flag_templates.len();
+        data.flag_templates
+            .retain(|t| !(t.project_id == project_id && t.name == name));
+        Ok(data.flag_templates.len() < before)
+    }
+
+    // ============ Flag Changes ============
+
+    async fn record_flag_changes(
+        &self,
+        project_id: &str,
+        keys: &[String],
+        deleted: bool,
+    ) -> Result<i64> {
+        let mut data = self.write();
+        let revision = data
+            .flag_revisions
+            .entry(project_id.to_string())
+            .or_insert(0);
+        *revision += 1;
+        let revision = *revision;
+
+        let now = Utc::now();
+        data.flag_changes
+            .retain(|c| !(c.project_id == project_id && keys.contains(&c.flag_key)));
+        data.flag_changes.extend(keys.iter().map(|key| FlagChange {
+            project_id: project_id.to_string(),
+            flag_key: key.clone(),
+            revision,
+            deleted,
+            changed_at: now,
+        }));
+        Ok(revision)
+    }
+
+    async fn get_flag_revision(&self, project_id: &str) -> Result<i64> {
+        Ok(self
+            .read()
+            .flag_revisions
+            .get(project_id)
+            .copied()
+            .unwrap_or(0))
+    }
+
+    async fn list_flag_changes(&self, project_id: &str, since: i64) -> Result<Vec<FlagChange>> {
+        let mut changes: Vec<FlagChange> = self
+            .read()
+            .flag_changes
+            .iter()
+            .filter(|c| c.project_id == project_id && c.revision > since)
+            .cloned()
+            .collect();
+        changes.sort_by_key(|c| c.revision);
+        Ok(changes)
+    }
+
+    // ============ Signing Keys ============
+
+    async fn create_signing_key(&self, key: &SigningKey) -> Result<()> {
+        self.write().signing_keys.push(key.clone());
+        Ok(())
+    }
+
+    async fn list_signing_keys(&self, environment_id: &str) -> Result<Vec<SigningKey>> {
+        let mut keys: Vec<SigningKey> = self
+            .read()
+            .signing_keys
+            .iter()
+            .filter(|k| k.environment_id == environment_id)
+            .cloned()
+            .collect();
+        keys.sort_by_key(|k| std::cmp::Reverse(k.created_at));
+        Ok(keys)
+    }
+
+    async fn retire_signing_keys(&self, environment_id: &str, at: DateTime<Utc>) -> Result<()> {
+        for key in self.write().signing_keys.iter_mut() {
+            if key.environment_id == environment_id && key.retired_at.is_none() {
+                key.retired_at = Some(at);
+            }
+        }
+        Ok(())
+    }
+
+    // ============ Change Requests ============
+
+    async fn create_change_request(&self, change: &ChangeRequest) -> Result<()> {
+        self.write().change_requests.push(change.clone());
+        Ok(())
+    }
+
+    async fn get_change_request(&self, id: &str) -> Result<Option<ChangeRequest>> {
+        Ok(self
+            .read()
+            .change_requests
+            .iter()
+            .find(|c| c.id == id)
+            .cloned())
+    }
+
+    async fn list_change_requests(
+        &self,
+        project_id: &str,
+        status: Option<&str>,
+    ) -> Result<Vec<ChangeRequest>> {
+        let mut changes: Vec<ChangeRequest> = self
+            .read()
+            .change_requests
+            .iter()
+            .filter(|c| c.project_id == project_id && status.is_none_or(|s| c.status == s))
+            .cloned()
+            .collect();
+        changes.sort_by_key(|c| std::cmp::Reverse(c.created_at));
+        Ok(changes)
+    }
+
+    async fn review_change_request(&self, change: &ChangeRequest) -> Result<bool> {
+        let mut data = self.write();
+        let Some(existing) = data
+            .change_requests
+            .iter_mut()
+            .find(|c| c.id == change.id && c.status == CHANGE_PENDING)
+        else {
+            return Ok(false);
+        };
+        existing.status = change.status.clone();
+        existing.reviewed_by = change.reviewed_by.clone();
+        existing.review_comment = change.review_comment.clone();
+        existing.reviewed_at = change.reviewed_at;
+        Ok(true)
+    }
+
+    async fn reopen_change_request(&self, id: &str) -> Result<()> {
+        let mut data = self.write();
+        if let Some(existing) = data
+            .change_requests
+            .iter_mut()
+            .find(|c| c.id == id && c.status == CHANGE_APPROVED)
+        {
+            existing.status = CHANGE_PENDING.to_string();
+            existing.reviewed_by = None;
+            existing.review_comment = None;
+            existing.reviewed_at = None;
+        }
+        Ok(())
+    }
+
+    // ============ Ramps ============
+
+    async fn create_ramp(&self, ramp: &Ramp) -> Result<()> {
+        self.write().ramps.push(ramp.clone());
+        Ok(())
+    }
+
+    async fn get_ramp(&self, id: &str) -> Result<Option<Ramp>> {
+        Ok(self.read().ramps.iter().find(|r| r.id == id).cloned())
+    }
+
+    async fn list_ramps(&self, project_id: &str) -> Result<Vec<Ramp>> {
+        let mut ramps: Vec<Ramp> = self
+            .read()
+            .ramps
+            .iter()
+            .filter(|r| r.project_id == project_id)
+            .cloned()
+            .collect();
+        ramps.sort_by_key(|r| std::cmp::Reverse(r.created_at));
+        Ok(ramps)
+    }
+
+    async fn list_due_ramps(&self, now: DateTime<Utc>) -> Result<Vec<Ramp>> {
+        let mut ramps: Vec<Ramp> = self
+            .read()
+            .ramps
+            .iter()
+            .filter(|r| r.status == RAMP_ACTIVE && r.next_step_at.is_some_and(|at| at <= now))
+            .cloned()
+            .collect();
+        ramps.sort_by_key(|r| r.next_step_at);
+        Ok(ramps)
+    }
+
+    async fn update_ramp(&self, ramp: &Ramp, from_status: &str) -> Result<bool> {
+        let mut data = self.write();
+        match data
+            .ramps
+            .iter_mut()
+            .find(|r| r.id == ramp.id && r.status == from_status)
+        {
+            Some(existing) => {
+                *existing = ramp.clone();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // ============ Guards ============
+
+    async fn create_guard(&self, guard: &Guard) -> Result<()> {
+        self.write().guards.push(guard.clone());
+        Ok(())
+    }
+
+    async fn get_guard(&self, id: &str) -> Result<Option<Guard>> {
+        Ok(self.read().guards.iter().find(|g| g.id == id).cloned())
+    }
+
+    async fn get_guard_by_token_hash(&self, token_hash: &str) -> Result<Option<Guard>> {
+        Ok(self
+            .read()
+            .guards
+            .iter()
+            .find(|g| g.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn list_guards(&self, project_id: &str) -> Result<Vec<Guard>> {
+        let mut guards: Vec<Guard> = self
+            .read()
+            .guards
+            .iter()
+            .filter(|g| g.project_id == project_id)
+            .cloned()
+            .collect();
+        guards.sort_by_key(|g| std::cmp::Reverse(g.created_at));
+        Ok(guards)
+    }
+
+    async fn list_watching_guards(&self) -> Result<Vec<Guard>> {
+        Ok(self
+            .read()
+            .guards
+            .iter()
+            .filter(|g| g.status == GUARD_WATCHING)
+            .cloned()
+            .collect())
+    }
+
+    async fn end_guard(&self, guard: &Guard) -> Result<bool> {
+        let mut data = self.write();
+        match data
+            .guards
+            .iter_mut()
+            .find(|g| g.id == guard.id && g.status == GUARD_WATCHING)
+        {
+            Some(existing) => {
+                existing.status = guard.status.clone();
+                existing.message = guard.message.clone();
+                existing.ended_at = guard.ended_at;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // ============ Project sharing ============
+
+    async fn set_project_member(&self, member: &ProjectMember) -> Result<()> {
+        let mut data = self.write();
+        match data
+            .project_members
+            .iter_mut()
+            .find(|m| m.project_id == member.project_id && m.user_id == member.user_id)
+        {
+            Some(existing) => existing.role = member.role.clone(),
+            None => data.project_members.push(member.clone()),
+        }
+        Ok(())
+    }
+
+    async fn get_project_member(
+        &self,
+        project_id: &str,
+        user_id: &str,
+    ) -> Result<Option<ProjectMember>> {
+        Ok(self
+            .read()
+            .project_members
+            .iter()
+            .find(|m| m.project_id == project_id && m.user_id == user_id)
+            .cloned())
+    }
+
+    async fn list_project_members(&self, project_id: &str) -> Result<Vec<ProjectMember>> {
+        Ok(self
+            .read()
+            .project_members
+            .iter()
+            .filter(|m| m.project_id == project_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn remove_project_member(&self, project_id: &str, user_id: &str) -> Result<bool> {
+        let mut data = self.write();
+        let before = data.project_members.len();
+        data.project_members
+            .retain(|m| !(m.project_id == project_id && m.user_id == user_id));
+        Ok(data.project_members.len() < before)
+    }
+
+    async fn list_projects_shared_with(&self, user_id: &str) -> Result<Vec<Project>> {
+        let data = self.read();
+        let mut projects: Vec<Project> = data
+            .projects
+            .iter()
+            .filter(|p| {
+                data.project_members
+                    .iter()
+                    .any(|m| m.project_id == p.id && m.user_id == user_id)
+            })
+            .cloned()
+            .collect();
+        projects.sort_by_key(|p| std::cmp::Reverse(p.created_at));
+        Ok(projects)
+    }
+
+    async fn create_invite_link(&self, link: &InviteLink) -> Result<()> {
+        self.write().invite_links.push(link.clone());
+        Ok(())
+    }
+
+    async fn get_invite_link(&self, id: &str) -> Result<Option<InviteLink>> {
+        Ok(self
+            .read()
+            .invite_links
+            .iter()
+            .find(|l| l.id == id)
+            .cloned())
+    }
+
+    async fn get_invite_link_by_token_hash(&self, token_hash: &str) -> Result<Option<InviteLink>> {
+        Ok(self
+            .read()
+            .invite_links
+            .iter()
+            .find(|l| l.token_hash == token_hash)
+            .cloned())
+    }
+
+    async fn list_invite_links(&self, project_id: &str) -> Result<Vec<InviteLink>> {
+        let mut links: Vec<InviteLink> = self
+            .read()
+            .invite_links
+            .iter()
+            .filter(|l| l.project_id == project_id)
+            .cloned()
+            .collect();
+        links.sort_by_key(|l| std::cmp::Reverse(l.created_at));
+        Ok(links)
+    }
+
+    async fn use_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let mut data = self.write();
+        match data
+            .invite_links
+            .iter_mut()
+            .find(|l| l.id == id && l.revoked_at.is_none() && l.expires_at > now)
+        {
+            Some(link) => {
+                link.uses += 1;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn revoke_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let mut data = self.write();
+        match data
+            .invite_links
+            .iter_mut()
+            .find(|l| l.id == id && l.revoked_at.is_none())
+        {
+            Some(link) => {
+                link.revoked_at = Some(now);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    // ============ Retention ============
+
+    async fn prune_flag_value_history(&self, before: DateTime<Utc>) -> Result<u64> {
+        let mut data = self.write();
+        let MemoryData {
+            flag_values,
+            flag_value_history,
+            ..
+        } = &mut *data;
+        let count = flag_value_history.len();
+        flag_value_history.retain(|h| {
+            h.updated_at >= before
+                || flag_values
+                    .iter()
+                    .any(|fv| fv.id == h.id && fv.version == h.version)
+        });
+        Ok((count - flag_value_history.len()) as u64)
+    }
+
+    async fn prune_evaluation_counts(&self, before: NaiveDate) -> Result<u64> {
+        let mut data = self.write();
+        let count = data.evaluations.len();
+        data.evaluations.retain(|c| c.day >= before);
+        Ok((count - data.evaluations.len()) as u64)
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        Ok(())
+    }
+
+    // ============ Migrations ============
+
+    async fn run_migrations(&self) -> Result<()> {
+        tracing::info!("Using in-memory storage, no migrations to run");
+        Ok(())
+    }
+
+    // ============ Backups ============
+
+    async fn backup(&self, _path: &Path) -> Result<()> {
+        Err(AppError::BadRequest(
+            "In-memory storage has nothing to back up".to_string(),
+        ))
+    }
+
+    // ============ Health ============
+
+    async fn health_check(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<StorageStats> {
+        let data = self.read();
+        Ok(StorageStats {
+            backend: "memory",
+            users: data.users.len() as i64,
+            projects: data.projects.len() as i64,
+            environments: data.environments.len() as i64,
+            flags: data.flags.len() as i64,
+            database_bytes: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: &str, username: &str) -> User {
+        let now = Utc::now();
+        User {
+            id: id.to_string(),
+            username: username.to_string(),
+            password_hash: "hash".to_string(),
+            email: None,
+            email_verified_at: None,
+            session_epoch: 0,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_username_rejected() {
+        let storage = MemoryStorage::new();
+        storage.create_user(&user("u1", "alice")).await.unwrap();
+
+        let result = storage.create_user(&user("u2", "alice")).await;
+        assert!(matches!(result, Err(AppError::UserAlreadyExists)));
+        assert!(storage.username_exists("alice").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_delete_flag_removes_values() {
+        let storage = MemoryStorage::new();
+        let now = Utc::now();
+
+        let flag = Flag {
+            id: "f1".to_string(),
+            project_id: "p1".to_string(),
+            key: "dark-mode".to_string(),
+            name: "Dark Mode".to_string(),
+            description: None,
+            created_at: now,
+            expires_at: None,
+            owner: None,
+            repository: None,
+            code_path: None,
+            flag_type: "boolean".to_string(),
+            json_schema: None,
+            default_enabled: false,
+            default_value: None,
+            public: false,
+        };
+        storage.create_flag(&flag).await.unwrap();
+        storage
+            .create_flag_value(&FlagValue {
+                id: "fv1".to_string(),
+                flag_id: "f1".to_string(),
+                environment_id: "e1".to_string(),
+                enabled: true,
+                rollout_percentage: 100,
+                value: None,
+                updated_at: now,
+                version: 1,
+                reason: None,
+            })
+            .await
+            .unwrap();
+
+        storage.delete_flag("f1").await.unwrap();
+
+        assert!(storage
+            .get_flag_by_key("p1", "dark-mode")
+            .await
+            .unwrap()
+            .is_none());
+        assert!(storage.get_flag_value("f1", "e1").await.unwrap().is_none());
+        assert!(storage
+            .list_flag_value_history("fv1")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_flag_value_writes_record_history() {
+        let storage = MemoryStorage::new();
+        let v1 = FlagValue {
+            id: "fv1".to_string(),
+            flag_id: "f1".to_string(),
+            environment_id: "e1".to_string(),
+            enabled: true,
+            rollout_percentage: 100,
+            value: None,
+            updated_at: Utc::now(),
+            version: 1,
+            reason: None,
+        };
+        storage.create_flag_value(&v1).await.unwrap();
+
+        let v2 = FlagValue {
+            enabled: false,
+            version: 2,
+            ..v1.clone()
+        };
+        assert!(storage.update_flag_value(&v2).await.unwrap());
+        // A lost race records nothing
+        assert!(!storage.update_flag_value(&v2).await.unwrap());
+
+        let v3 = FlagValue {
+            rollout_percentage: 10,
+            version: 3,
+            ..v2.clone()
+        };
+        assert!(storage.save_flag_values(&[v3]).await.unwrap());
+
+        let history = storage.list_flag_value_history("fv1").await.unwrap();
+        let versions: Vec<_> = history
+            .iter()
+            .map(|fv| (fv.version, fv.enabled, fv.rollout_percentage))
+            .collect();
+        assert_eq!(
+            versions,
+            vec![(3, false, 10), (2, false, 100), (1, true, 100)]
+        );
+    }
+}
@@ -1,14 +1,34 @@
 // Storage abstraction module - v2
-use crate::error::Result;
-use crate::models::{ApiKey, Environment, Flag, FlagValue, Project, User};
+use crate::error::{AppError, Result};
+use crate::models::{
+    AccountToken, ApiKey, ChangeRequest, DeletedEnvironment, Environment, EvaluationCount, Flag,
+    FlagChange, FlagSwitch, FlagTemplate, FlagValue, Guard, Invite, InviteLink, ListedUser,
+    OrgMember, Organization, Project, ProjectMember, ProjectQuota, ProjectSummary, Ramp,
+    SigningKey, StorageStats, User, UserPreferences, UsernameChange,
+};
 use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::path::Path;
 
+pub mod cached;
+pub mod instrumented;
+pub mod memory;
 pub mod postgres;
 pub mod sqlite;
 
+pub use cached::CachedStorage;
+pub use instrumented::InstrumentedStorage;
+pub use memory::MemoryStorage;
 pub use postgres::PostgresStorage;
 pub use sqlite::SqliteStorage;
 
+/// Prepared statements kept per database connection. Above sqlx's default of
+/// 100 so the hot evaluation queries aren't evicted by the ~100 others.
+const STATEMENT_CACHE_CAPACITY: usize = 256;
+
+/// Rows per multi-row insert, well under both databases' bind parameter limits
+const UPSERT_BATCH_ROWS: usize = 500;
+
 /// Storage trait for FlagLite - abstracts database operations
 #[allow(dead_code)]
 #[async_trait]
@@ -17,8 +37,27 @@ pub trait Storage: Send + Sync {
     async fn create_user(&self, user: &User) -> Result<()>;
     async fn get_user_by_username(&self, username: &str) -> Result<Option<User>>;
     async fn get_user_by_id(&self, id: &str) -> Result<Option<User>>;
+    /// Save the user's email and its verification; the password and session
+    /// epoch only change through `set_password`
     async fn update_user(&self, user: &User) -> Result<()>;
+    /// Replace the user's password hash, and with `bump_epoch` end every
+    /// session signed in before
+    async fn set_password(
+        &self,
+        user_id: &str,
+        password_hash: &str,
+        bump_epoch: bool,
+    ) -> Result<()>;
     async fn username_exists(&self, username: &str) -> Result<bool>;
+    /// Give the user `change.new_username` and record the change, together
+    async fn rename_user(&self, change: &UsernameChange) -> Result<()>;
+    /// The user's username changes, newest first
+    async fn list_username_changes(&self, user_id: &str) -> Result<Vec<UsernameChange>>;
+    /// The latest change away from `old_username`, naming who held it
+    async fn get_username_change(&self, old_username: &str) -> Result<Option<UsernameChange>>;
+    async fn get_user_preferences(&self, user_id: &str) -> Result<Option<UserPreferences>>;
+    /// Insert or replace the user's preferences
+    async fn set_user_preferences(&self, preferences: &UserPreferences) -> Result<()>;
 
     // API Keys
     async fn create_api_key(&self, api_key: &ApiKey) -> Result<()>;
@@ -26,29 +65,113 @@ pub trait Storage: Send + Sync {
     async fn list_api_keys_by_user(&self, user_id: &str) -> Result<Vec<ApiKey>>;
     async fn revoke_api_key(&self, id: &str) -> Result<()>;
 
+    // Invites
+    async fn create_invite(&self, invite: &Invite) -> Result<()>;
+    /// Mark an unused, unexpired invite as used; returns false if it can't be used
+    async fn redeem_invite(&self, code: &str, user_id: &str, now: DateTime<Utc>) -> Result<bool>;
+
+    // Account tokens (password reset, email verification)
+    async fn create_account_token(&self, token: &AccountToken) -> Result<()>;
+    /// Mark an unused, unexpired token as used and return it; None if it can't be used
+    async fn redeem_account_token(
+        &self,
+        token_hash: &str,
+        purpose: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AccountToken>>;
+
+    // Organizations
+    async fn create_organization(&self, org: &Organization) -> Result<()>;
+    async fn get_organization(&self, id: &str) -> Result<Option<Organization>>;
+    async fn list_organizations_by_user(&self, user_id: &str) -> Result<Vec<Organization>>;
+    async fn add_org_member(&self, member: &OrgMember) -> Result<()>;
+    async fn get_org_member(&self, org_id: &str, user_id: &str) -> Result<Option<OrgMember>>;
+    async fn list_org_members(&self, org_id: &str) -> Result<Vec<OrgMember>>;
+    /// Change a member's role, unless that leaves the org without an owner;
+    /// returns false, writing nothing, if it would or they aren't a member
+    async fn set_org_member_role(&self, org_id: &str, user_id: &str, role: &str) -> Result<bool>;
+    /// Remove a member, unless they're the org's last owner; returns false,
+    /// writing nothing, if they are or they aren't a member
+    async fn remove_org_member(&self, org_id: &str, user_id: &str) -> Result<bool>;
+
     // Projects
     async fn create_project(&self, project: &Project) -> Result<()>;
+    /// Create a project together with its environments, flags and flag
+    /// values: all of them, or none if any write fails
+    async fn create_project_with(
+        &self,
+        project: &Project,
+        environments: &[Environment],
+        flags: &[Flag],
+        flag_values: &[FlagValue],
+    ) -> Result<()>;
     async fn get_project_by_id(&self, id: &str) -> Result<Option<Project>>;
     async fn get_project_by_api_key(&self, api_key: &str) -> Result<Option<Project>>;
     async fn list_projects_by_user(&self, user_id: &str) -> Result<Vec<Project>>;
     async fn get_first_project_by_user(&self, user_id: &str) -> Result<Option<Project>>;
+    async fn list_projects_by_org(&self, org_id: &str) -> Result<Vec<Project>>;
+    /// Save a project's settings (`require_change_reason`)
+    async fn update_project(&self, project: &Project) -> Result<()>;
+    /// Flag counts per environment for `GET /v1/projects/:project_id/stats`;
+    /// flags expired by `now` count as off
+    async fn project_summary(&self, project_id: &str, now: DateTime<Utc>)
+        -> Result<ProjectSummary>;
 
     // Environments
     async fn create_environment(&self, env: &Environment) -> Result<()>;
     async fn get_environment_by_id(&self, id: &str) -> Result<Option<Environment>>;
     async fn get_environment_by_api_key(&self, api_key: &str) -> Result<Option<Environment>>;
+    /// The public environment with this client ID
+    async fn get_environment_by_public_client_id(
+        &self,
+        client_id: &str,
+    ) -> Result<Option<Environment>>;
     async fn get_environment_by_name(
         &self,
         project_id: &str,
         name: &str,
     ) -> Result<Option<Environment>>;
+    async fn update_environment(&self, env: &Environment) -> Result<()>;
+    /// In display order (`sort_order`, then creation)
     async fn list_environments_by_project(&self, project_id: &str) -> Result<Vec<Environment>>;
 
+    // Deleted environments
+    /// Delete the environment with everything recorded for it (flag values
+    /// and their history, evaluation counts, user lists, signing keys, ramps
+    /// and guards), keeping `deleted` in the same transaction
+    async fn delete_environment(
+        &self,
+        env: &Environment,
+        deleted: &DeletedEnvironment,
+    ) -> Result<()>;
+    /// The project's deleted environments not yet purged, newest first
+    async fn list_deleted_environments(&self, project_id: &str) -> Result<Vec<DeletedEnvironment>>;
+    /// Recreate a deleted environment as `env`, with `values` (each recorded
+    /// in its history) and `users`, and forget it was deleted, in one
+    /// transaction. Returns false, writing nothing, when it was already
+    /// restored or purged.
+    async fn restore_environment(
+        &self,
+        env: &Environment,
+        values: &[FlagValue],
+        users: &[ListedUser],
+    ) -> Result<bool>;
+    /// Drop deleted environments kept until `now` or earlier; returns how many
+    async fn purge_deleted_environments(&self, now: DateTime<Utc>) -> Result<u64>;
+
     // Flags
     async fn create_flag(&self, flag: &Flag) -> Result<()>;
+    /// Overwrite the flag's owner, repository, and code path
+    async fn update_flag_ownership(&self, flag: &Flag) -> Result<()>;
+    /// Overwrite what the flag evaluates to in environments without a value
+    async fn update_flag_defaults(&self, flag: &Flag) -> Result<()>;
+    /// Overwrite whether the flag is evaluable through public environments
+    async fn update_flag_visibility(&self, flag: &Flag) -> Result<()>;
     async fn get_flag_by_id(&self, id: &str) -> Result<Option<Flag>>;
     async fn get_flag_by_key(&self, project_id: &str, key: &str) -> Result<Option<Flag>>;
     async fn list_flags_by_project(&self, project_id: &str) -> Result<Vec<Flag>>;
+    /// Flags in every project whose expiry is at or before `now`, soonest first
+    async fn list_expired_flags(&self, now: DateTime<Utc>) -> Result<Vec<Flag>>;
 
     // Flag Values
     async fn create_flag_value(&self, flag_value: &FlagValue) -> Result<()>;
@@ -57,23 +180,231 @@ pub trait Storage: Send + Sync {
         flag_id: &str,
         environment_id: &str,
     ) -> Result<Option<FlagValue>>;
-    async fn update_flag_value(&self, flag_value: &FlagValue) -> Result<()>;
+    /// The flag with `key` and its value in the environment, in one query
+    async fn get_flag_switch(
+        &self,
+        project_id: &str,
+        environment_id: &str,
+        key: &str,
+    ) -> Result<Option<FlagSwitch>>;
+    /// Overwrite the stored value if it is still at `flag_value.version - 1`.
+    /// Returns false, writing nothing, when another write got there first.
+    async fn update_flag_value(&self, flag_value: &FlagValue) -> Result<bool>;
+    /// Write several values atomically: version 1 is inserted, later versions
+    /// are updated as in `update_flag_value`. Returns false, writing nothing,
+    /// if any update lost a race.
+    async fn save_flag_values(&self, flag_values: &[FlagValue]) -> Result<bool>;
+    /// Write many values at once with multi-row inserts, all in one
+    /// transaction. A flag that already has a value in the environment keeps
+    /// its row, which takes the new state at the next version. Every version
+    /// written is recorded in the history. At most one value per flag and
+    /// environment.
+    async fn upsert_flag_values(&self, flag_values: &[FlagValue]) -> Result<()>;
+    /// Every version a flag value has been written at, newest first. Each
+    /// write above records one in the same transaction.
+    async fn list_flag_value_history(&self, flag_value_id: &str) -> Result<Vec<FlagValue>>;
+    /// Up to `limit` versions older than `before` (the newest when None),
+    /// newest first. Seeks on the (id, version) key, so deep pages cost the
+    /// same as the first.
+    async fn list_flag_value_history_page(
+        &self,
+        flag_value_id: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<FlagValue>>;
     async fn list_flag_values_by_flag_ids(&self, flag_ids: &[String]) -> Result<Vec<FlagValue>>;
     async fn delete_flag(&self, flag_id: &str) -> Result<()>;
 
+    // Allowlists and denylists
+    /// Put each user on their list for the flag in the environment, taking
+    /// them off the other list if they were on it. At most one entry per
+    /// flag, environment and user.
+    async fn add_listed_users(&self, users: &[ListedUser]) -> Result<()>;
+    /// Take the users off `list`; returns how many were on it
+    async fn remove_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+        user_ids: &[String],
+    ) -> Result<u64>;
+    /// Empty `list`; returns how many users were on it
+    async fn clear_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+    ) -> Result<u64>;
+    /// The users on either list for the flag in the environment
+    async fn list_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>>;
+    /// The users on every flag's lists in the environment
+    async fn list_listed_users_by_environment(
+        &self,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>>;
+
+    // Usage
+    /// Add the counts to any already stored for the same flag, environment, and day
+    async fn record_evaluations(&self, counts: &[EvaluationCount]) -> Result<()>;
+    async fn list_evaluation_counts(&self, flag_ids: &[String]) -> Result<Vec<EvaluationCount>>;
+
+    // Quotas
+    async fn get_project_quota(&self, project_id: &str) -> Result<Option<ProjectQuota>>;
+    /// Insert or replace the project's quota
+    async fn set_project_quota(&self, quota: &ProjectQuota) -> Result<()>;
+
+    // Flag templates
+    /// The project's stored templates, by name
+    async fn list_flag_templates(&self, project_id: &str) -> Result<Vec<FlagTemplate>>;
+    async fn get_flag_template(&self, project_id: &str, name: &str)
+        -> Result<Option<FlagTemplate>>;
+    /// Insert or replace the project's template of the same name
+    async fn set_flag_template(&self, template: &FlagTemplate) -> Result<()>;
+    /// Returns whether there was a template to delete
+    async fn delete_flag_template(&self, project_id: &str, name: &str) -> Result<bool>;
+
+    // Flag changes
+    /// Give the keys the project's next revision and return it
+    async fn record_flag_changes(
+        &self,
+        project_id: &str,
+        keys: &[String],
+        deleted: bool,
+    ) -> Result<i64>;
+    /// The project's latest revision; 0 before its first change
+    async fn get_flag_revision(&self, project_id: &str) -> Result<i64>;
+    /// Each key's last change, for keys changed after revision `since`
+    async fn list_flag_changes(&self, project_id: &str, since: i64) -> Result<Vec<FlagChange>>;
+
+    // Signing keys
+    async fn create_signing_key(&self, key: &SigningKey) -> Result<()>;
+    /// Every key of the environment, retired ones included, newest first
+    async fn list_signing_keys(&self, environment_id: &str) -> Result<Vec<SigningKey>>;
+    /// Mark the environment's active keys retired
+    async fn retire_signing_keys(&self, environment_id: &str, at: DateTime<Utc>) -> Result<()>;
+
+    // Change requests
+    async fn create_change_request(&self, change: &ChangeRequest) -> Result<()>;
+    async fn get_change_request(&self, id: &str) -> Result<Option<ChangeRequest>>;
+    /// The project's change requests, optionally only those with `status`, newest first
+    async fn list_change_requests(
+        &self,
+        project_id: &str,
+        status: Option<&str>,
+    ) -> Result<Vec<ChangeRequest>>;
+    /// Record the review of a pending change request; returns false, writing
+    /// nothing, if it was already reviewed
+    async fn review_change_request(&self, change: &ChangeRequest) -> Result<bool>;
+    /// Put an approved change request back to pending, when applying it failed
+    async fn reopen_change_request(&self, id: &str) -> Result<()>;
+
+    // Ramps
+    async fn create_ramp(&self, ramp: &Ramp) -> Result<()>;
+    async fn get_ramp(&self, id: &str) -> Result<Option<Ramp>>;
+    /// The project's ramps, newest first
+    async fn list_ramps(&self, project_id: &str) -> Result<Vec<Ramp>>;
+    /// Active ramps whose next step is due by `now`
+    async fn list_due_ramps(&self, now: DateTime<Utc>) -> Result<Vec<Ramp>>;
+    /// Save the ramp's progress and status if it's still `from_status`;
+    /// returns false, writing nothing, if it moved on
+    async fn update_ramp(&self, ramp: &Ramp, from_status: &str) -> Result<bool>;
+
+    // Guards
+    async fn create_guard(&self, guard: &Guard) -> Result<()>;
+    async fn get_guard(&self, id: &str) -> Result<Option<Guard>>;
+    async fn get_guard_by_token_hash(&self, token_hash: &str) -> Result<Option<Guard>>;
+    /// The project's guards, newest first
+    async fn list_guards(&self, project_id: &str) -> Result<Vec<Guard>>;
+    /// Guards still watching, in every project
+    async fn list_watching_guards(&self) -> Result<Vec<Guard>>;
+    /// Record how a watching guard ended; returns false, writing nothing, if
+    /// it had already ended
+    async fn end_guard(&self, guard: &Guard) -> Result<bool>;
+
+    // Project sharing
+    /// Insert the membership, or replace the role of an existing one
+    async fn set_project_member(&self, member: &ProjectMember) -> Result<()>;
+    async fn get_project_member(
+        &self,
+        project_id: &str,
+        user_id: &str,
+    ) -> Result<Option<ProjectMember>>;
+    async fn list_project_members(&self, project_id: &str) -> Result<Vec<ProjectMember>>;
+    /// Take back the access an invite link gave; returns false if the user
+    /// had none
+    async fn remove_project_member(&self, project_id: &str, user_id: &str) -> Result<bool>;
+    /// Projects shared with the user by invite link, newest first
+    async fn list_projects_shared_with(&self, user_id: &str) -> Result<Vec<Project>>;
+    async fn create_invite_link(&self, link: &InviteLink) -> Result<()>;
+    async fn get_invite_link(&self, id: &str) -> Result<Option<InviteLink>>;
+    async fn get_invite_link_by_token_hash(&self, token_hash: &str) -> Result<Option<InviteLink>>;
+    /// The project's invite links, newest first; revoked and expired ones included
+    async fn list_invite_links(&self, project_id: &str) -> Result<Vec<InviteLink>>;
+    /// Count a use of a link that's neither revoked nor expired at `now`;
+    /// returns false, writing nothing, otherwise
+    async fn use_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool>;
+    /// Revoke a link; returns false if it already was
+    async fn revoke_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool>;
+
+    // Retention
+    /// Delete flag versions written before `before`, except each value's
+    /// current version; returns how many were deleted
+    async fn prune_flag_value_history(&self, before: DateTime<Utc>) -> Result<u64>;
+    /// Delete evaluation counts of days before `before`; returns how many
+    async fn prune_evaluation_counts(&self, before: NaiveDate) -> Result<u64>;
+    /// Give the space of deleted rows back to the filesystem
+    async fn vacuum(&self) -> Result<()>;
+
     // Migrations
     async fn run_migrations(&self) -> Result<()>;
+
+    // Backups
+    /// Write a consistent copy of the database to `path`, which must not exist,
+    /// without blocking readers or writers. Only SQLite supports this.
+    async fn backup(&self, path: &Path) -> Result<()>;
+
+    // Health
+    /// Fail unless the database answers a trivial query
+    async fn health_check(&self) -> Result<()>;
+    /// Row counts and database size
+    async fn stats(&self) -> Result<StorageStats>;
 }
 
-/// Create storage based on DATABASE_URL
-pub async fn create_storage(database_url: &str) -> Result<std::sync::Arc<dyn Storage>> {
-    if database_url.starts_with("postgres") {
+/// Create storage based on DATABASE_URL, with up to `max_connections` pooled
+/// database connections
+pub async fn create_storage(
+    database_url: &str,
+    max_connections: u32,
+) -> Result<std::sync::Arc<dyn Storage>> {
+    if database_url.starts_with("memory:") {
+        tracing::warn!("Using in-memory storage - data will be lost on restart");
+        Ok(std::sync::Arc::new(MemoryStorage::new()))
+    } else if database_url.starts_with("postgres") {
         tracing::info!("Using PostgreSQL storage");
-        let storage = PostgresStorage::new(database_url).await?;
+        let storage = PostgresStorage::new(database_url, max_connections).await?;
         Ok(std::sync::Arc::new(storage))
     } else {
         tracing::info!("Using SQLite storage");
-        let storage = SqliteStorage::new(database_url).await?;
+        let storage = SqliteStorage::new(database_url, max_connections).await?;
         Ok(std::sync::Arc::new(storage))
     }
 }
+
+/// Turn a constraint violation from a write into the domain error it stands
+/// for: `duplicate` when a unique key is already taken, a bad request when a
+/// row it references doesn't exist. Anything else stays a database error.
+fn constraint_error(e: sqlx::Error, duplicate: impl FnOnce() -> AppError) -> AppError {
+    if let sqlx::Error::Database(db) = &e {
+        if db.is_unique_violation() {
+            return duplicate();
+        }
+        if db.is_foreign_key_violation() {
+            return AppError::BadRequest("Refers to a record that doesn't exist".to_string());
+        }
+    }
+    AppError::Database(e)
+}
@@ -1,25 +1,226 @@
 use async_trait::async_trait;
-use chrono::Utc;
-use sqlx::postgres::PgPoolOptions;
-use sqlx::PgPool;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{PgConnection, PgPool, Postgres, QueryBuilder};
+use std::collections::HashSet;
+use std::path::Path;
+use std::str::FromStr;
 
-use super::Storage;
-use crate::error::Result;
-use crate::models::{ApiKey, Environment, Flag, FlagValue, Project, User};
+use super::{constraint_error, Storage, STATEMENT_CACHE_CAPACITY, UPSERT_BATCH_ROWS};
+use crate::error::{AppError, Result};
+use crate::models::{
+    AccountToken, ApiKey, ChangeRequest, DeletedEnvironment, Environment, EvaluationCount, Flag,
+    FlagChange, FlagSwitch, FlagTemplate, FlagValue, Guard, Invite, InviteLink, ListedUser,
+    OrgMember, Organization, Project, ProjectMember, ProjectQuota, ProjectSummary, Ramp,
+    SigningKey, StorageStats, User, UserPreferences, UsernameChange,
+};
+use crate::slug;
 
 pub struct PostgresStorage {
     pool: PgPool,
 }
 
 impl PostgresStorage {
-    pub async fn new(database_url: &str) -> Result<Self> {
+    pub async fn new(database_url: &str, max_connections: u32) -> Result<Self> {
+        let options = PgConnectOptions::from_str(database_url)?
+            .statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+
         let pool = PgPoolOptions::new()
-            .max_connections(5)
-            .connect(database_url)
+            .max_connections(max_connections)
+            .connect_with(options)
             .await?;
 
         Ok(Self { pool })
     }
+
+    /// Add a column to an existing table, returning whether it was missing
+    async fn add_column_if_missing(
+        &self,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<bool> {
+        let exists: bool = sqlx::query_scalar(
+            "SELECT EXISTS (SELECT 1 FROM information_schema.columns WHERE table_name = $1 AND column_name = $2)",
+        )
+        .bind(table)
+        .bind(column)
+        .fetch_one(&self.pool)
+        .await?;
+
+        if exists {
+            return Ok(false);
+        }
+
+        sqlx::query(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))
+        .execute(&self.pool)
+        .await?;
+        Ok(true)
+    }
+
+    /// Give projects created before slugs were stored one derived from their
+    /// name, oldest first so the oldest project keeps the plain slug
+    async fn backfill_project_slugs(&self) -> Result<()> {
+        let unslugged: Vec<(String, String, String)> = sqlx::query_as(
+            "SELECT id, user_id, name FROM projects WHERE slug IS NULL ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        for (id, user_id, name) in unslugged {
+            let taken: HashSet<String> = sqlx::query_scalar(
+                "SELECT slug FROM projects WHERE user_id = $1 AND slug IS NOT NULL",
+            )
+            .bind(&user_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+
+            sqlx::query("UPDATE projects SET slug = $1 WHERE id = $2")
+                .bind(slug::unique_slug(&name, &taken))
+                .bind(&id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Insert a project, on `conn` so it can join a transaction
+    async fn insert_project(conn: &mut PgConnection, project: &Project) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO projects (id, user_id, org_id, name, slug, api_key, created_at, require_change_reason) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        )
+        .bind(&project.id)
+        .bind(&project.user_id)
+        .bind(&project.org_id)
+        .bind(&project.name)
+        .bind(&project.slug)
+        .bind(&project.api_key)
+        .bind(project.created_at)
+        .bind(project.require_change_reason)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Insert an environment, on `conn` so it can join a transaction
+    async fn insert_environment(conn: &mut PgConnection, env: &Environment) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO environments (id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
+        )
+        .bind(&env.id)
+        .bind(&env.project_id)
+        .bind(&env.name)
+        .bind(&env.api_key)
+        .bind(env.protected)
+        .bind(env.frozen)
+        .bind(env.sort_order)
+        .bind(&env.color)
+        .bind(&env.emoji)
+        .bind(env.cache_max_age)
+        .bind(env.cache_stale_while_revalidate)
+        .bind(&env.public_client_id)
+        .bind(env.require_approval)
+        .bind(env.created_at)
+        .execute(conn)
+        .await
+        .map_err(|e| constraint_error(e, || AppError::EnvironmentInUse(env.name.clone())))?;
+        Ok(())
+    }
+
+    /// Insert a flag, on `conn` so it can join a transaction
+    async fn insert_flag(conn: &mut PgConnection, flag: &Flag) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO flags (id, project_id, key, name, description, created_at, expires_at, owner, repository, code_path, flag_type, json_schema, default_enabled, default_value, public) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15)",
+        )
+        .bind(&flag.id)
+        .bind(&flag.project_id)
+        .bind(&flag.key)
+        .bind(&flag.name)
+        .bind(&flag.description)
+        .bind(flag.created_at)
+        .bind(flag.expires_at)
+        .bind(&flag.owner)
+        .bind(&flag.repository)
+        .bind(&flag.code_path)
+        .bind(&flag.flag_type)
+        .bind(&flag.json_schema)
+        .bind(flag.default_enabled)
+        .bind(&flag.default_value)
+        .bind(flag.public)
+        .execute(conn)
+        .await
+        .map_err(|e| constraint_error(e, || AppError::DuplicateFlag(flag.key.clone())))?;
+        Ok(())
+    }
+
+    /// Upsert `flag_values` and record their history, on `conn` so it can
+    /// join a transaction
+    async fn write_flag_values(conn: &mut PgConnection, flag_values: &[FlagValue]) -> Result<()> {
+        for batch in flag_values.chunks(UPSERT_BATCH_ROWS) {
+            let mut query = QueryBuilder::<Postgres>::new(
+                "INSERT INTO flag_values (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) ",
+            );
+            query.push_values(batch, |mut row, fv| {
+                row.push_bind(&fv.id)
+                    .push_bind(&fv.flag_id)
+                    .push_bind(&fv.environment_id)
+                    .push_bind(fv.enabled)
+                    .push_bind(fv.rollout_percentage)
+                    .push_bind(&fv.value)
+                    .push_bind(fv.updated_at)
+                    .push_bind(fv.version)
+                    .push_bind(&fv.reason);
+            });
+            // The stored row's id and version, so the history matches it
+            query.push(
+                " ON CONFLICT (flag_id, environment_id) DO UPDATE SET enabled = excluded.enabled, rollout_percentage = excluded.rollout_percentage, value = excluded.value, updated_at = excluded.updated_at, version = flag_values.version + 1, reason = excluded.reason RETURNING id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason",
+            );
+            let written: Vec<FlagValue> = query.build_query_as().fetch_all(&mut *conn).await?;
+
+            let mut history = QueryBuilder::<Postgres>::new(
+                "INSERT INTO flag_value_history (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) ",
+            );
+            history.push_values(&written, |mut row, fv| {
+                row.push_bind(&fv.id)
+                    .push_bind(&fv.flag_id)
+                    .push_bind(&fv.environment_id)
+                    .push_bind(fv.enabled)
+                    .push_bind(fv.rollout_percentage)
+                    .push_bind(&fv.value)
+                    .push_bind(fv.updated_at)
+                    .push_bind(fv.version)
+                    .push_bind(&fv.reason);
+            });
+            history.build().execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+
+    /// Record `flag_value` as written, in the same transaction as the write
+    async fn record_flag_value_history(
+        conn: &mut PgConnection,
+        flag_value: &FlagValue,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO flag_value_history (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(&flag_value.id)
+        .bind(&flag_value.flag_id)
+        .bind(&flag_value.environment_id)
+        .bind(flag_value.enabled)
+        .bind(flag_value.rollout_percentage)
+        .bind(&flag_value.value)
+        .bind(flag_value.updated_at)
+        .bind(flag_value.version)
+        .bind(&flag_value.reason)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -28,22 +229,25 @@ impl Storage for PostgresStorage {
 
     async fn create_user(&self, user: &User) -> Result<()> {
         sqlx::query(
-            "INSERT INTO users (id, username, password_hash, email, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6)",
+            "INSERT INTO users (id, username, password_hash, email, email_verified_at, session_epoch, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
         )
         .bind(&user.id)
         .bind(&user.username)
         .bind(&user.password_hash)
         .bind(&user.email)
+        .bind(user.email_verified_at)
+        .bind(user.session_epoch)
         .bind(user.created_at)
         .bind(user.updated_at)
         .execute(&self.pool)
-        .await?;
+        .await
+        .map_err(|e| constraint_error(e, || AppError::UserAlreadyExists))?;
         Ok(())
     }
 
     async fn get_user_by_username(&self, username: &str) -> Result<Option<User>> {
         let user = sqlx::query_as(
-            "SELECT id, username, password_hash, email, created_at, updated_at FROM users WHERE username = $1",
+            "SELECT id, username, password_hash, email, email_verified_at, session_epoch, created_at, updated_at FROM users WHERE username = $1",
         )
         .bind(username)
         .fetch_optional(&self.pool)
@@ -53,7 +257,7 @@ impl Storage for PostgresStorage {
 
     async fn get_user_by_id(&self, id: &str) -> Result<Option<User>> {
         let user = sqlx::query_as(
-            "SELECT id, username, password_hash, email, created_at, updated_at FROM users WHERE id = $1",
+            "SELECT id, username, password_hash, email, email_verified_at, session_epoch, created_at, updated_at FROM users WHERE id = $1",
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -62,12 +266,33 @@ impl Storage for PostgresStorage {
     }
 
     async fn update_user(&self, user: &User) -> Result<()> {
-        sqlx::query("UPDATE users SET email = $1, updated_at = $2 WHERE id = $3")
-            .bind(&user.email)
-            .bind(user.updated_at)
-            .bind(&user.id)
-            .execute(&self.pool)
-            .await?;
+        sqlx::query(
+            "UPDATE users SET email = $1, email_verified_at = $2, updated_at = $3 WHERE id = $4",
+        )
+        .bind(&user.email)
+        .bind(user.email_verified_at)
+        .bind(user.updated_at)
+        .bind(&user.id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn set_password(
+        &self,
+        user_id: &str,
+        password_hash: &str,
+        bump_epoch: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE users SET password_hash = $1, session_epoch = session_epoch + $2, updated_at = $3 WHERE id = $4",
+        )
+        .bind(password_hash)
+        .bind(i64::from(bump_epoch))
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
@@ -79,11 +304,87 @@ impl Storage for PostgresStorage {
         Ok(result.0 > 0)
     }
 
+    async fn rename_user(&self, change: &UsernameChange) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE users SET username = $1, updated_at = $2 WHERE id = $3")
+            .bind(&change.new_username)
+            .bind(change.changed_at)
+            .bind(&change.user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| constraint_error(e, || AppError::UserAlreadyExists))?;
+        sqlx::query(
+            "INSERT INTO username_changes (user_id, old_username, new_username, changed_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&change.user_id)
+        .bind(&change.old_username)
+        .bind(&change.new_username)
+        .bind(change.changed_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn list_username_changes(&self, user_id: &str) -> Result<Vec<UsernameChange>> {
+        let changes = sqlx::query_as(
+            "SELECT user_id, old_username, new_username, changed_at FROM username_changes WHERE user_id = $1 ORDER BY changed_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(changes)
+    }
+
+    async fn get_username_change(&self, old_username: &str) -> Result<Option<UsernameChange>> {
+        let change = sqlx::query_as(
+            "SELECT user_id, old_username, new_username, changed_at FROM username_changes WHERE old_username = $1 ORDER BY changed_at DESC LIMIT 1",
+        )
+        .bind(old_username)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(change)
+    }
+
+    async fn get_user_preferences(&self, user_id: &str) -> Result<Option<UserPreferences>> {
+        let preferences = sqlx::query_as(
+            "SELECT user_id, project_id, environment, display_name, format, updated_at FROM user_preferences WHERE user_id = $1",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(preferences)
+    }
+
+    async fn set_user_preferences(&self, preferences: &UserPreferences) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_preferences (user_id, project_id, environment, display_name, format, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (user_id) DO UPDATE SET
+            project_id = excluded.project_id,
+            environment = excluded.environment,
+            display_name = excluded.display_name,
+            format = excluded.format,
+            updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&preferences.user_id)
+        .bind(&preferences.project_id)
+        .bind(&preferences.environment)
+        .bind(&preferences.display_name)
+        .bind(&preferences.format)
+        .bind(preferences.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
     // ============ API Keys ============
 
     async fn create_api_key(&self, api_key: &ApiKey) -> Result<()> {
         sqlx::query(
-            "INSERT INTO api_keys (id, user_id, key_hash, key_prefix, name, created_at, revoked_at) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+            "INSERT INTO api_keys (id, user_id, key_hash, key_prefix, name, created_at, revoked_at, org_id, scope) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
         )
         .bind(&api_key.id)
         .bind(&api_key.user_id)
@@ -92,6 +393,8 @@ impl Storage for PostgresStorage {
         .bind(&api_key.name)
         .bind(api_key.created_at)
         .bind(api_key.revoked_at)
+        .bind(&api_key.org_id)
+        .bind(&api_key.scope)
         .execute(&self.pool)
         .await?;
         Ok(())
@@ -99,7 +402,7 @@ impl Storage for PostgresStorage {
 
     async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>> {
         let api_key = sqlx::query_as(
-            "SELECT id, user_id, key_hash, key_prefix, name, created_at, revoked_at FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
+            "SELECT id, user_id, key_hash, key_prefix, name, created_at, revoked_at, org_id, scope FROM api_keys WHERE key_hash = $1 AND revoked_at IS NULL",
         )
         .bind(key_hash)
         .fetch_optional(&self.pool)
@@ -109,7 +412,7 @@ impl Storage for PostgresStorage {
 
     async fn list_api_keys_by_user(&self, user_id: &str) -> Result<Vec<ApiKey>> {
         let keys = sqlx::query_as(
-            "SELECT id, user_id, key_hash, key_prefix, name, created_at, revoked_at FROM api_keys WHERE user_id = $1 ORDER BY created_at DESC",
+            "SELECT id, user_id, key_hash, key_prefix, name, created_at, revoked_at, org_id, scope FROM api_keys WHERE user_id = $1 ORDER BY created_at DESC",
         )
         .bind(user_id)
         .fetch_all(&self.pool)
@@ -126,25 +429,219 @@ impl Storage for PostgresStorage {
         Ok(())
     }
 
-    // ============ Projects ============
+    // ============ Invites ============
 
-    async fn create_project(&self, project: &Project) -> Result<()> {
+    async fn create_invite(&self, invite: &Invite) -> Result<()> {
+        sqlx::query("INSERT INTO invites (code, created_by, created_at, expires_at) VALUES ($1, $2, $3, $4)")
+            .bind(&invite.code)
+            .bind(&invite.created_by)
+            .bind(invite.created_at)
+            .bind(invite.expires_at)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn redeem_invite(&self, code: &str, user_id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE invites SET used_by = $1, used_at = $2 WHERE code = $3 AND used_at IS NULL AND expires_at > $2",
+        )
+            .bind(user_id)
+            .bind(now)
+            .bind(code)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    // ============ Account tokens ============
+
+    async fn create_account_token(&self, token: &AccountToken) -> Result<()> {
         sqlx::query(
-            "INSERT INTO projects (id, user_id, name, api_key, created_at) VALUES ($1, $2, $3, $4, $5)",
+            "INSERT INTO account_tokens (token_hash, user_id, purpose, email, created_at, expires_at) VALUES ($1, $2, $3, $4, $5, $6)",
         )
-        .bind(&project.id)
-        .bind(&project.user_id)
-        .bind(&project.name)
-        .bind(&project.api_key)
-        .bind(project.created_at)
+        .bind(&token.token_hash)
+        .bind(&token.user_id)
+        .bind(&token.purpose)
+        .bind(&token.email)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn redeem_account_token(
+        &self,
+        token_hash: &str,
+        purpose: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AccountToken>> {
+        let result = sqlx::query(
+            "UPDATE account_tokens SET used_at = $1 WHERE token_hash = $2 AND purpose = $3 AND used_at IS NULL AND expires_at > $4",
+        )
+        .bind(now)
+        .bind(token_hash)
+        .bind(purpose)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() != 1 {
+            return Ok(None);
+        }
+
+        let token = sqlx::query_as(
+            "SELECT token_hash, user_id, purpose, email, created_at, expires_at, used_at FROM account_tokens WHERE token_hash = $1",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(token)
+    }
+
+    // ============ Organizations ============
+
+    async fn create_organization(&self, org: &Organization) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO organizations (id, name, personal, created_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&org.id)
+        .bind(&org.name)
+        .bind(org.personal)
+        .bind(org.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_organization(&self, id: &str) -> Result<Option<Organization>> {
+        let org = sqlx::query_as(
+            "SELECT id, name, personal, created_at FROM organizations WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(org)
+    }
+
+    async fn list_organizations_by_user(&self, user_id: &str) -> Result<Vec<Organization>> {
+        let orgs = sqlx::query_as(
+            r#"
+            SELECT o.id, o.name, o.personal, o.created_at
+            FROM organizations o
+            JOIN org_members m ON m.org_id = o.id
+            WHERE m.user_id = $1
+            ORDER BY o.personal DESC, o.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(orgs)
+    }
+
+    async fn add_org_member(&self, member: &OrgMember) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO org_members (org_id, user_id, role, created_at) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(&member.org_id)
+        .bind(&member.user_id)
+        .bind(&member.role)
+        .bind(member.created_at)
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
+    async fn get_org_member(&self, org_id: &str, user_id: &str) -> Result<Option<OrgMember>> {
+        let member = sqlx::query_as(
+            "SELECT org_id, user_id, role, created_at FROM org_members WHERE org_id = $1 AND user_id = $2",
+        )
+        .bind(org_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(member)
+    }
+
+    async fn list_org_members(&self, org_id: &str) -> Result<Vec<OrgMember>> {
+        let members = sqlx::query_as(
+            "SELECT org_id, user_id, role, created_at FROM org_members WHERE org_id = $1 ORDER BY created_at",
+        )
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(members)
+    }
+
+    async fn set_org_member_role(&self, org_id: &str, user_id: &str, role: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        // Taken before reading the owners, so two owners demoting or
+        // removing each other at once can't both succeed
+        sqlx::query("SELECT id FROM organizations WHERE id = $1 FOR UPDATE")
+            .bind(org_id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query(
+            "UPDATE org_members SET role = $1 WHERE org_id = $2 AND user_id = $3 AND ($1 = 'owner' OR role <> 'owner' OR EXISTS (SELECT 1 FROM org_members o WHERE o.org_id = org_members.org_id AND o.user_id <> org_members.user_id AND o.role = 'owner'))",
+        )
+        .bind(role)
+        .bind(org_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn remove_org_member(&self, org_id: &str, user_id: &str) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        // Taken before reading the owners, so two owners demoting or
+        // removing each other at once can't both succeed
+        sqlx::query("SELECT id FROM organizations WHERE id = $1 FOR UPDATE")
+            .bind(org_id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query(
+            "DELETE FROM org_members WHERE org_id = $1 AND user_id = $2 AND (role <> 'owner' OR EXISTS (SELECT 1 FROM org_members o WHERE o.org_id = org_members.org_id AND o.user_id <> org_members.user_id AND o.role = 'owner'))",
+        )
+        .bind(org_id)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ============ Projects ============
+
+    async fn create_project(&self, project: &Project) -> Result<()> {
+        Self::insert_project(&mut *self.pool.acquire().await?, project).await
+    }
+
+    async fn create_project_with(
+        &self,
+        project: &Project,
+        environments: &[Environment],
+        flags: &[Flag],
+        flag_values: &[FlagValue],
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        Self::insert_project(&mut tx, project).await?;
+        for env in environments {
+            Self::insert_environment(&mut tx, env).await?;
+        }
+        for flag in flags {
+            Self::insert_flag(&mut tx, flag).await?;
+        }
+        Self::write_flag_values(&mut tx, flag_values).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
     async fn get_project_by_id(&self, id: &str) -> Result<Option<Project>> {
         let project = sqlx::query_as(
-            "SELECT id, user_id, name, api_key, created_at FROM projects WHERE id = $1",
+            "SELECT id, user_id, org_id, name, slug, api_key, created_at, require_change_reason FROM projects WHERE id = $1",
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -154,7 +651,7 @@ impl Storage for PostgresStorage {
 
     async fn get_project_by_api_key(&self, api_key: &str) -> Result<Option<Project>> {
         let project = sqlx::query_as(
-            "SELECT id, user_id, name, api_key, created_at FROM projects WHERE api_key = $1",
+            "SELECT id, user_id, org_id, name, slug, api_key, created_at, require_change_reason FROM projects WHERE api_key = $1",
         )
         .bind(api_key)
         .fetch_optional(&self.pool)
@@ -164,7 +661,7 @@ impl Storage for PostgresStorage {
 
     async fn list_projects_by_user(&self, user_id: &str) -> Result<Vec<Project>> {
         let projects = sqlx::query_as(
-            "SELECT id, user_id, name, api_key, created_at FROM projects WHERE user_id = $1 ORDER BY created_at DESC",
+            "SELECT id, user_id, org_id, name, slug, api_key, created_at, require_change_reason FROM projects WHERE user_id = $1 ORDER BY created_at DESC",
         )
         .bind(user_id)
         .fetch_all(&self.pool)
@@ -174,7 +671,7 @@ impl Storage for PostgresStorage {
 
     async fn get_first_project_by_user(&self, user_id: &str) -> Result<Option<Project>> {
         let project = sqlx::query_as(
-            "SELECT id, user_id, name, api_key, created_at FROM projects WHERE user_id = $1 LIMIT 1",
+            "SELECT id, user_id, org_id, name, slug, api_key, created_at, require_change_reason FROM projects WHERE user_id = $1 LIMIT 1",
         )
         .bind(user_id)
         .fetch_optional(&self.pool)
@@ -182,25 +679,73 @@ impl Storage for PostgresStorage {
         Ok(project)
     }
 
-    // ============ Environments ============
-
-    async fn create_environment(&self, env: &Environment) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO environments (id, project_id, name, api_key, created_at) VALUES ($1, $2, $3, $4, $5)",
+    async fn list_projects_by_org(&self, org_id: &str) -> Result<Vec<Project>> {
+        let projects = sqlx::query_as(
+            "SELECT id, user_id, org_id, name, slug, api_key, created_at, require_change_reason FROM projects WHERE org_id = $1 ORDER BY created_at DESC",
         )
-        .bind(&env.id)
-        .bind(&env.project_id)
-        .bind(&env.name)
-        .bind(&env.api_key)
-        .bind(env.created_at)
-        .execute(&self.pool)
+        .bind(org_id)
+        .fetch_all(&self.pool)
         .await?;
+        Ok(projects)
+    }
+
+    async fn update_project(&self, project: &Project) -> Result<()> {
+        sqlx::query("UPDATE projects SET require_change_reason = $1 WHERE id = $2")
+            .bind(project.require_change_reason)
+            .bind(&project.id)
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 
+    async fn project_summary(
+        &self,
+        project_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<ProjectSummary> {
+        let (flags, last_changed_at): (i64, Option<DateTime<Utc>>) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM flags WHERE project_id = $1),
+                    (SELECT MAX(changed_at) FROM flag_changes WHERE project_id = $1)",
+        )
+        .bind(project_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        // Flags without a value in an environment join as NULLs and count by
+        // their default
+        let environments = sqlx::query_as(
+            "SELECT e.name, e.created_at,
+                    COUNT(CASE WHEN COALESCE(fv.enabled, f.default_enabled) AND (f.expires_at IS NULL OR f.expires_at > $2) THEN 1 END) AS enabled,
+                    COUNT(CASE WHEN COALESCE(fv.enabled, f.default_enabled) AND (f.expires_at IS NULL OR f.expires_at > $2) AND fv.rollout_percentage < 100 THEN 1 END) AS partial_rollout,
+                    MAX(fv.updated_at) AS last_changed_at
+             FROM environments e
+             LEFT JOIN flags f ON f.project_id = e.project_id
+             LEFT JOIN flag_values fv ON fv.flag_id = f.id AND fv.environment_id = e.id
+             WHERE e.project_id = $1
+             GROUP BY e.id, e.name, e.created_at, e.sort_order
+             ORDER BY e.sort_order, e.created_at",
+        )
+        .bind(project_id)
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(ProjectSummary {
+            flags,
+            last_changed_at,
+            environments,
+        })
+    }
+
+    // ============ Environments ============
+
+    async fn create_environment(&self, env: &Environment) -> Result<()> {
+        Self::insert_environment(&mut *self.pool.acquire().await?, env).await
+    }
+
     async fn get_environment_by_id(&self, id: &str) -> Result<Option<Environment>> {
         let env = sqlx::query_as(
-            "SELECT id, project_id, name, api_key, created_at FROM environments WHERE id = $1",
+            "SELECT id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at FROM environments WHERE id = $1",
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -210,7 +755,7 @@ impl Storage for PostgresStorage {
 
     async fn get_environment_by_api_key(&self, api_key: &str) -> Result<Option<Environment>> {
         let env = sqlx::query_as(
-            "SELECT id, project_id, name, api_key, created_at FROM environments WHERE api_key = $1",
+            "SELECT id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at FROM environments WHERE api_key = $1",
         )
         .bind(api_key)
         .fetch_optional(&self.pool)
@@ -218,24 +763,56 @@ impl Storage for PostgresStorage {
         Ok(env)
     }
 
-    async fn get_environment_by_name(
+    async fn get_environment_by_public_client_id(
         &self,
-        project_id: &str,
-        name: &str,
+        client_id: &str,
     ) -> Result<Option<Environment>> {
         let env = sqlx::query_as(
-            "SELECT id, project_id, name, api_key, created_at FROM environments WHERE project_id = $1 AND name = $2",
+            "SELECT id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at FROM environments WHERE public_client_id = $1",
         )
-        .bind(project_id)
-        .bind(name)
+        .bind(client_id)
         .fetch_optional(&self.pool)
         .await?;
         Ok(env)
     }
 
-    async fn list_environments_by_project(&self, project_id: &str) -> Result<Vec<Environment>> {
-        let envs = sqlx::query_as(
-            "SELECT id, project_id, name, api_key, created_at FROM environments WHERE project_id = $1",
+    async fn get_environment_by_name(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<Option<Environment>> {
+        let env = sqlx::query_as(
+            "SELECT id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at FROM environments WHERE project_id = $1 AND name = $2",
+        )
+        .bind(project_id)
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(env)
+    }
+
+    async fn update_environment(&self, env: &Environment) -> Result<()> {
+        sqlx::query(
+            "UPDATE environments SET protected = $1, frozen = $2, sort_order = $3, color = $4, emoji = $5, cache_max_age = $6, cache_stale_while_revalidate = $7, public_client_id = $8, require_approval = $9 WHERE id = $10",
+        )
+            .bind(env.protected)
+            .bind(env.frozen)
+            .bind(env.sort_order)
+            .bind(&env.color)
+            .bind(&env.emoji)
+            .bind(env.cache_max_age)
+            .bind(env.cache_stale_while_revalidate)
+            .bind(&env.public_client_id)
+            .bind(env.require_approval)
+            .bind(&env.id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_environments_by_project(&self, project_id: &str) -> Result<Vec<Environment>> {
+        let envs = sqlx::query_as(
+            "SELECT id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at FROM environments WHERE project_id = $1 ORDER BY sort_order, created_at",
         )
         .bind(project_id)
         .fetch_all(&self.pool)
@@ -243,26 +820,175 @@ impl Storage for PostgresStorage {
         Ok(envs)
     }
 
-    // ============ Flags ============
+    // ============ Deleted environments ============
 
-    async fn create_flag(&self, flag: &Flag) -> Result<()> {
+    async fn delete_environment(
+        &self,
+        env: &Environment,
+        deleted: &DeletedEnvironment,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
-            "INSERT INTO flags (id, project_id, key, name, description, created_at) VALUES ($1, $2, $3, $4, $5, $6)",
+            "INSERT INTO deleted_environments (id, project_id, name, environment, flag_values, listed_users, deleted_by, deleted_at, purge_after) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
         )
-        .bind(&flag.id)
-        .bind(&flag.project_id)
-        .bind(&flag.key)
-        .bind(&flag.name)
-        .bind(&flag.description)
-        .bind(flag.created_at)
-        .execute(&self.pool)
+        .bind(&deleted.id)
+        .bind(&deleted.project_id)
+        .bind(&deleted.name)
+        .bind(&deleted.environment)
+        .bind(&deleted.flag_values)
+        .bind(&deleted.listed_users)
+        .bind(&deleted.deleted_by)
+        .bind(deleted.deleted_at)
+        .bind(deleted.purge_after)
+        .execute(&mut *tx)
+        .await?;
+        // Delete child rows first (foreign key)
+        for table in [
+            "flag_values",
+            "flag_value_history",
+            "flag_evaluations",
+            "flag_user_lists",
+            "signing_keys",
+            "ramps",
+            "guards",
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE environment_id = $1"))
+                .bind(&env.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM environments WHERE id = $1")
+            .bind(&env.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn list_deleted_environments(&self, project_id: &str) -> Result<Vec<DeletedEnvironment>> {
+        let deleted = sqlx::query_as(
+            "SELECT id, project_id, name, environment, flag_values, listed_users, deleted_by, deleted_at, purge_after FROM deleted_environments WHERE project_id = $1 ORDER BY deleted_at DESC",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
         .await?;
+        Ok(deleted)
+    }
+
+    async fn restore_environment(
+        &self,
+        env: &Environment,
+        values: &[FlagValue],
+        users: &[ListedUser],
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let archived = sqlx::query("DELETE FROM deleted_environments WHERE id = $1")
+            .bind(&env.id)
+            .execute(&mut *tx)
+            .await?;
+        if archived.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query(
+            "INSERT INTO environments (id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
+        )
+        .bind(&env.id)
+        .bind(&env.project_id)
+        .bind(&env.name)
+        .bind(&env.api_key)
+        .bind(env.protected)
+        .bind(env.frozen)
+        .bind(env.sort_order)
+        .bind(&env.color)
+        .bind(&env.emoji)
+        .bind(env.cache_max_age)
+        .bind(env.cache_stale_while_revalidate)
+        .bind(&env.public_client_id)
+        .bind(env.require_approval)
+        .bind(env.created_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| constraint_error(e, || AppError::EnvironmentInUse(env.name.clone())))?;
+        for fv in values {
+            sqlx::query(
+                "INSERT INTO flag_values (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+            )
+            .bind(&fv.id)
+            .bind(&fv.flag_id)
+            .bind(&fv.environment_id)
+            .bind(fv.enabled)
+            .bind(fv.rollout_percentage)
+            .bind(&fv.value)
+            .bind(fv.updated_at)
+            .bind(fv.version)
+            .bind(&fv.reason)
+            .execute(&mut *tx)
+            .await?;
+            Self::record_flag_value_history(&mut tx, fv).await?;
+        }
+        for user in users {
+            sqlx::query(
+                "INSERT INTO flag_user_lists (flag_id, environment_id, user_id, list, created_at) VALUES ($1, $2, $3, $4, $5)",
+            )
+            .bind(&user.flag_id)
+            .bind(&user.environment_id)
+            .bind(&user.user_id)
+            .bind(&user.list)
+            .bind(user.created_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn purge_deleted_environments(&self, now: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM deleted_environments WHERE purge_after <= $1")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    // ============ Flags ============
+
+    async fn create_flag(&self, flag: &Flag) -> Result<()> {
+        Self::insert_flag(&mut *self.pool.acquire().await?, flag).await
+    }
+
+    async fn update_flag_ownership(&self, flag: &Flag) -> Result<()> {
+        sqlx::query("UPDATE flags SET owner = $1, repository = $2, code_path = $3 WHERE id = $4")
+            .bind(&flag.owner)
+            .bind(&flag.repository)
+            .bind(&flag.code_path)
+            .bind(&flag.id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_flag_defaults(&self, flag: &Flag) -> Result<()> {
+        sqlx::query("UPDATE flags SET default_enabled = $1, default_value = $2 WHERE id = $3")
+            .bind(flag.default_enabled)
+            .bind(&flag.default_value)
+            .bind(&flag.id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_flag_visibility(&self, flag: &Flag) -> Result<()> {
+        sqlx::query("UPDATE flags SET public = $1 WHERE id = $2")
+            .bind(flag.public)
+            .bind(&flag.id)
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 
     async fn get_flag_by_id(&self, id: &str) -> Result<Option<Flag>> {
         let flag = sqlx::query_as(
-            "SELECT id, project_id, key, name, description, created_at FROM flags WHERE id = $1",
+            "SELECT id, project_id, key, name, description, created_at, expires_at, owner, repository, code_path, flag_type, json_schema, default_enabled, default_value, public FROM flags WHERE id = $1",
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -272,7 +998,7 @@ impl Storage for PostgresStorage {
 
     async fn get_flag_by_key(&self, project_id: &str, key: &str) -> Result<Option<Flag>> {
         let flag = sqlx::query_as(
-            "SELECT id, project_id, key, name, description, created_at FROM flags WHERE project_id = $1 AND key = $2",
+            "SELECT id, project_id, key, name, description, created_at, expires_at, owner, repository, code_path, flag_type, json_schema, default_enabled, default_value, public FROM flags WHERE project_id = $1 AND key = $2",
         )
         .bind(project_id)
         .bind(key)
@@ -283,7 +1009,7 @@ impl Storage for PostgresStorage {
 
     async fn list_flags_by_project(&self, project_id: &str) -> Result<Vec<Flag>> {
         let flags = sqlx::query_as(
-            "SELECT id, project_id, key, name, description, created_at FROM flags WHERE project_id = $1 ORDER BY created_at DESC",
+            "SELECT id, project_id, key, name, description, created_at, expires_at, owner, repository, code_path, flag_type, json_schema, default_enabled, default_value, public FROM flags WHERE project_id = $1 ORDER BY created_at DESC",
         )
         .bind(project_id)
         .fetch_all(&self.pool)
@@ -291,20 +1017,39 @@ impl Storage for PostgresStorage {
         Ok(flags)
     }
 
+    async fn list_expired_flags(&self, now: DateTime<Utc>) -> Result<Vec<Flag>> {
+        let flags = sqlx::query_as(
+            "SELECT id, project_id, key, name, description, created_at, expires_at, owner, repository, code_path, flag_type, json_schema, default_enabled, default_value, public FROM flags WHERE expires_at <= $1 ORDER BY expires_at",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(flags)
+    }
+
     // ============ Flag Values ============
 
     async fn create_flag_value(&self, flag_value: &FlagValue) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
-            "INSERT INTO flag_values (id, flag_id, environment_id, enabled, rollout_percentage, updated_at) VALUES ($1, $2, $3, $4, $5, $6)",
+            "INSERT INTO flag_values (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
         )
         .bind(&flag_value.id)
         .bind(&flag_value.flag_id)
         .bind(&flag_value.environment_id)
         .bind(flag_value.enabled)
         .bind(flag_value.rollout_percentage)
+        .bind(&flag_value.value)
         .bind(flag_value.updated_at)
-        .execute(&self.pool)
-        .await?;
+        .bind(flag_value.version)
+        .bind(&flag_value.reason)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| constraint_error(e, || {
+            AppError::Conflict("The flag already has a value in this environment".to_string())
+        }))?;
+        Self::record_flag_value_history(&mut tx, flag_value).await?;
+        tx.commit().await?;
         Ok(())
     }
 
@@ -314,7 +1059,7 @@ impl Storage for PostgresStorage {
         environment_id: &str,
     ) -> Result<Option<FlagValue>> {
         let fv = sqlx::query_as(
-            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, updated_at FROM flag_values WHERE flag_id = $1 AND environment_id = $2",
+            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason FROM flag_values WHERE flag_id = $1 AND environment_id = $2",
         )
         .bind(flag_id)
         .bind(environment_id)
@@ -323,19 +1068,123 @@ impl Storage for PostgresStorage {
         Ok(fv)
     }
 
-    async fn update_flag_value(&self, flag_value: &FlagValue) -> Result<()> {
-        sqlx::query(
-            "UPDATE flag_values SET enabled = $1, rollout_percentage = $2, updated_at = $3 WHERE id = $4",
+    async fn get_flag_switch(
+        &self,
+        project_id: &str,
+        environment_id: &str,
+        key: &str,
+    ) -> Result<Option<FlagSwitch>> {
+        let switch = sqlx::query_as(
+            "SELECT f.id AS flag_id, f.expires_at, f.default_enabled, fv.enabled, fv.rollout_percentage FROM flags f LEFT JOIN flag_values fv ON fv.flag_id = f.id AND fv.environment_id = $1 WHERE f.project_id = $2 AND f.key = $3",
+        )
+        .bind(environment_id)
+        .bind(project_id)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(switch)
+    }
+
+    async fn update_flag_value(&self, flag_value: &FlagValue) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            "UPDATE flag_values SET enabled = $1, rollout_percentage = $2, value = $3, updated_at = $4, version = $5, reason = $6 WHERE id = $7 AND version = $8",
         )
         .bind(flag_value.enabled)
         .bind(flag_value.rollout_percentage)
+        .bind(&flag_value.value)
         .bind(flag_value.updated_at)
+        .bind(flag_value.version)
+        .bind(&flag_value.reason)
         .bind(&flag_value.id)
-        .execute(&self.pool)
+        .bind(flag_value.version - 1)
+        .execute(&mut *tx)
         .await?;
+        if result.rows_affected() != 1 {
+            return Ok(false);
+        }
+        Self::record_flag_value_history(&mut tx, flag_value).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn save_flag_values(&self, flag_values: &[FlagValue]) -> Result<bool> {
+        // Dropping the transaction without committing rolls everything back
+        let mut tx = self.pool.begin().await?;
+        for flag_value in flag_values {
+            let result = if flag_value.version == 1 {
+                sqlx::query(
+                    "INSERT INTO flag_values (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+                )
+                .bind(&flag_value.id)
+                .bind(&flag_value.flag_id)
+                .bind(&flag_value.environment_id)
+                .bind(flag_value.enabled)
+                .bind(flag_value.rollout_percentage)
+                .bind(&flag_value.value)
+                .bind(flag_value.updated_at)
+                .bind(flag_value.version)
+                .bind(&flag_value.reason)
+                .execute(&mut *tx)
+                .await?
+            } else {
+                sqlx::query(
+                    "UPDATE flag_values SET enabled = $1, rollout_percentage = $2, value = $3, updated_at = $4, version = $5, reason = $6 WHERE id = $7 AND version = $8",
+                )
+                .bind(flag_value.enabled)
+                .bind(flag_value.rollout_percentage)
+                .bind(&flag_value.value)
+                .bind(flag_value.updated_at)
+                .bind(flag_value.version)
+                .bind(&flag_value.reason)
+                .bind(&flag_value.id)
+                .bind(flag_value.version - 1)
+                .execute(&mut *tx)
+                .await?
+            };
+            if result.rows_affected() != 1 {
+                return Ok(false);
+            }
+            Self::record_flag_value_history(&mut tx, flag_value).await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn upsert_flag_values(&self, flag_values: &[FlagValue]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        Self::write_flag_values(&mut tx, flag_values).await?;
+        tx.commit().await?;
         Ok(())
     }
 
+    async fn list_flag_value_history(&self, flag_value_id: &str) -> Result<Vec<FlagValue>> {
+        let history = sqlx::query_as(
+            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason FROM flag_value_history WHERE id = $1 ORDER BY version DESC",
+        )
+        .bind(flag_value_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(history)
+    }
+
+    async fn list_flag_value_history_page(
+        &self,
+        flag_value_id: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<FlagValue>> {
+        let history = sqlx::query_as(
+            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason FROM flag_value_history WHERE id = $1 AND version < $2 ORDER BY version DESC LIMIT $3",
+        )
+        .bind(flag_value_id)
+        .bind(before.unwrap_or(i64::MAX))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(history)
+    }
+
     async fn list_flag_values_by_flag_ids(&self, flag_ids: &[String]) -> Result<Vec<FlagValue>> {
         if flag_ids.is_empty() {
             return Ok(vec![]);
@@ -348,7 +1197,7 @@ impl Storage for PostgresStorage {
             .map(|(i, _)| format!("${}", i + 1))
             .collect();
         let query_str = format!(
-            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, updated_at FROM flag_values WHERE flag_id IN ({})",
+            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason FROM flag_values WHERE flag_id IN ({})",
             placeholders.join(",")
         );
 
@@ -362,11 +1211,31 @@ impl Storage for PostgresStorage {
     }
 
     async fn delete_flag(&self, flag_id: &str) -> Result<()> {
-        // Delete flag values first (foreign key)
+        // Delete flag values and usage first (foreign key)
         sqlx::query("DELETE FROM flag_values WHERE flag_id = $1")
             .bind(flag_id)
             .execute(&self.pool)
             .await?;
+        sqlx::query("DELETE FROM flag_value_history WHERE flag_id = $1")
+            .bind(flag_id)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM flag_evaluations WHERE flag_id = $1")
+            .bind(flag_id)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM guards WHERE flag_id = $1")
+            .bind(flag_id)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM ramps WHERE flag_id = $1")
+            .bind(flag_id)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM flag_user_lists WHERE flag_id = $1")
+            .bind(flag_id)
+            .execute(&self.pool)
+            .await?;
 
         // Delete the flag
         sqlx::query("DELETE FROM flags WHERE id = $1")
@@ -377,108 +1246,1282 @@ impl Storage for PostgresStorage {
         Ok(())
     }
 
-    // ============ Migrations ============
+    // ============ Allowlists and denylists ============
 
-    async fn run_migrations(&self) -> Result<()> {
-        tracing::info!("Running database migrations (PostgreSQL)...");
+    async fn add_listed_users(&self, users: &[ListedUser]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for batch in users.chunks(UPSERT_BATCH_ROWS) {
+            let mut query = QueryBuilder::<Postgres>::new(
+                "INSERT INTO flag_user_lists (flag_id, environment_id, user_id, list, created_at) ",
+            );
+            query.push_values(batch, |mut row, user| {
+                row.push_bind(&user.flag_id)
+                    .push_bind(&user.environment_id)
+                    .push_bind(&user.user_id)
+                    .push_bind(&user.list)
+                    .push_bind(user.created_at);
+            });
+            // Users already on the list keep the time they were added
+            query.push(
+                " ON CONFLICT (flag_id, environment_id, user_id) DO UPDATE SET list = excluded.list, created_at = excluded.created_at WHERE flag_user_lists.list <> excluded.list",
+            );
+            query.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
 
-        // Create users table with username-based auth
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS users (
-                id TEXT PRIMARY KEY,
-                username TEXT UNIQUE NOT NULL,
-                password_hash TEXT NOT NULL,
-                email TEXT,
-                created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
-                updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
-            )
-            "#,
+    async fn remove_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+        user_ids: &[String],
+    ) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let mut removed = 0;
+        for batch in user_ids.chunks(UPSERT_BATCH_ROWS) {
+            let mut query =
+                QueryBuilder::<Postgres>::new("DELETE FROM flag_user_lists WHERE flag_id = ");
+            query
+                .push_bind(flag_id)
+                .push(" AND environment_id = ")
+                .push_bind(environment_id)
+                .push(" AND list = ")
+                .push_bind(list)
+                .push(" AND user_id IN (");
+            let mut ids = query.separated(", ");
+            for id in batch {
+                ids.push_bind(id);
+            }
+            ids.push_unseparated(")");
+            removed += query.build().execute(&mut *tx).await?.rows_affected();
+        }
+        tx.commit().await?;
+        Ok(removed)
+    }
+
+    async fn clear_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+    ) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM flag_user_lists WHERE flag_id = $1 AND environment_id = $2 AND list = $3",
         )
+        .bind(flag_id)
+        .bind(environment_id)
+        .bind(list)
         .execute(&self.pool)
         .await?;
+        Ok(result.rows_affected())
+    }
 
-        // Create api_keys table for user API keys
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS api_keys (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-                key_hash TEXT NOT NULL,
-                key_prefix TEXT NOT NULL,
-                name TEXT,
-                created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
-                revoked_at TIMESTAMP WITH TIME ZONE
-            )
-            "#,
+    async fn list_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>> {
+        let users = sqlx::query_as(
+            "SELECT flag_id, environment_id, user_id, list, created_at FROM flag_user_lists WHERE flag_id = $1 AND environment_id = $2",
         )
-        .execute(&self.pool)
+        .bind(flag_id)
+        .bind(environment_id)
+        .fetch_all(&self.pool)
         .await?;
+        Ok(users)
+    }
 
-        // Create projects table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS projects (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-                name TEXT NOT NULL,
-                api_key TEXT UNIQUE NOT NULL,
-                created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+    async fn list_listed_users_by_environment(
+        &self,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>> {
+        let users = sqlx::query_as(
+            "SELECT flag_id, environment_id, user_id, list, created_at FROM flag_user_lists WHERE environment_id = $1",
+        )
+        .bind(environment_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(users)
+    }
+
+    // ============ Usage ============
+
+    async fn record_evaluations(&self, counts: &[EvaluationCount]) -> Result<()> {
+        // Counts for flags deleted since they were recorded are dropped
+        for c in counts {
+            sqlx::query(
+                r#"
+                INSERT INTO flag_evaluations (flag_id, environment_id, day, count, last_evaluated_at)
+                SELECT $1, $2, $3, $4, $5 WHERE EXISTS (SELECT 1 FROM flags WHERE id = $1)
+                ON CONFLICT (flag_id, environment_id, day) DO UPDATE SET
+                count = flag_evaluations.count + EXCLUDED.count,
+                last_evaluated_at = GREATEST(flag_evaluations.last_evaluated_at, EXCLUDED.last_evaluated_at)
+                "#,
             )
-            "#,
+            .bind(&c.flag_id)
+            .bind(&c.environment_id)
+            .bind(c.day)
+            .bind(c.count)
+            .bind(c.last_evaluated_at)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn list_evaluation_counts(&self, flag_ids: &[String]) -> Result<Vec<EvaluationCount>> {
+        if flag_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let placeholders: Vec<String> = flag_ids
+            .iter()
+            .enumerate()
+            .map(|(i, _)| format!("${}", i + 1))
+            .collect();
+        let query_str = format!(
+            "SELECT flag_id, environment_id, day, count, last_evaluated_at FROM flag_evaluations WHERE flag_id IN ({}) ORDER BY day DESC",
+            placeholders.join(",")
+        );
+
+        let mut query = sqlx::query_as(&query_str);
+        for id in flag_ids {
+            query = query.bind(id);
+        }
+
+        let counts = query.fetch_all(&self.pool).await?;
+        Ok(counts)
+    }
+
+    // ============ Quotas ============
+
+    async fn get_project_quota(&self, project_id: &str) -> Result<Option<ProjectQuota>> {
+        let quota = sqlx::query_as(
+            "SELECT project_id, requests_per_minute, per_key_requests_per_minute, updated_at FROM project_quotas WHERE project_id = $1",
         )
-        .execute(&self.pool)
+        .bind(project_id)
+        .fetch_optional(&self.pool)
         .await?;
+        Ok(quota)
+    }
 
-        // Create environments table
+    async fn set_project_quota(&self, quota: &ProjectQuota) -> Result<()> {
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS environments (
-                id TEXT PRIMARY KEY,
-                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
-                name TEXT NOT NULL,
-                api_key TEXT UNIQUE NOT NULL,
-                created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
-                UNIQUE(project_id, name)
-            )
+            INSERT INTO project_quotas (project_id, requests_per_minute, per_key_requests_per_minute, updated_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (project_id) DO UPDATE SET
+            requests_per_minute = EXCLUDED.requests_per_minute,
+            per_key_requests_per_minute = EXCLUDED.per_key_requests_per_minute,
+            updated_at = EXCLUDED.updated_at
             "#,
         )
+        .bind(&quota.project_id)
+        .bind(quota.requests_per_minute)
+        .bind(quota.per_key_requests_per_minute)
+        .bind(quota.updated_at)
         .execute(&self.pool)
         .await?;
+        Ok(())
+    }
 
-        // Create flags table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS flags (
-                id TEXT PRIMARY KEY,
-                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
-                key TEXT NOT NULL,
+    // ============ Flag Templates ============
+
+    async fn list_flag_templates(&self, project_id: &str) -> Result<Vec<FlagTemplate>> {
+        let templates = sqlx::query_as(
+            "SELECT project_id, name, description, default_enabled, default_rollout, environments, updated_at FROM flag_templates WHERE project_id = $1 ORDER BY name",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(templates)
+    }
+
+    async fn get_flag_template(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<Option<FlagTemplate>> {
+        let template = sqlx::query_as(
+            "SELECT project_id, name, description, default_enabled, default_rollout, environments, updated_at FROM flag_templates WHERE project_id = $1 AND name = $2",
+        )
+        .bind(project_id)
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(template)
+    }
+
+    async fn set_flag_template(&self, template: &FlagTemplate) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO flag_templates (project_id, name, description, default_enabled, default_rollout, environments, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (project_id, name) DO UPDATE SET
+            description = EXCLUDED.description,
+            default_enabled = EXCLUDED.default_enabled,
+            default_rollout = EXCLUDED.default_rollout,
+            environments = EXCLUDED.environments,
+            updated_at = EXCLUDED.updated_at
+            "#,
+        )
+        .bind(&template.project_id)
+        .bind(&template.name)
+        .bind(&template.description)
+        .bind(template.default_enabled)
+        .bind(template.default_rollout)
+        .bind(&template.environments)
+        .bind(template.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_flag_template(&self, project_id: &str, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM flag_templates WHERE project_id = $1 AND name = $2")
+            .bind(project_id)
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ============ Flag Changes ============
+
+    async fn record_flag_changes(
+        &self,
+        project_id: &str,
+        keys: &[String],
+        deleted: bool,
+    ) -> Result<i64> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+        // The counter row stays locked until commit, so revisions become
+        // visible in order
+        let (revision,): (i64,) = sqlx::query_as(
+            "INSERT INTO flag_revisions (project_id, revision) VALUES ($1, 1) ON CONFLICT (project_id) DO UPDATE SET revision = flag_revisions.revision + 1 RETURNING revision",
+        )
+        .bind(project_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        for key in keys {
+            sqlx::query(
+                r#"
+                INSERT INTO flag_changes (project_id, flag_key, revision, deleted, changed_at)
+                VALUES ($1, $2, $3, $4, $5)
+                ON CONFLICT (project_id, flag_key) DO UPDATE SET
+                revision = EXCLUDED.revision,
+                deleted = EXCLUDED.deleted,
+                changed_at = EXCLUDED.changed_at
+                "#,
+            )
+            .bind(project_id)
+            .bind(key)
+            .bind(revision)
+            .bind(deleted)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(revision)
+    }
+
+    async fn get_flag_revision(&self, project_id: &str) -> Result<i64> {
+        let revision: Option<(i64,)> =
+            sqlx::query_as("SELECT revision FROM flag_revisions WHERE project_id = $1")
+                .bind(project_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(revision.map(|(r,)| r).unwrap_or(0))
+    }
+
+    async fn list_flag_changes(&self, project_id: &str, since: i64) -> Result<Vec<FlagChange>> {
+        let changes = sqlx::query_as(
+            "SELECT project_id, flag_key, revision, deleted, changed_at FROM flag_changes WHERE project_id = $1 AND revision > $2 ORDER BY revision",
+        )
+        .bind(project_id)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(changes)
+    }
+
+    // ============ Signing Keys ============
+
+    async fn create_signing_key(&self, key: &SigningKey) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO signing_keys (id, environment_id, private_key, public_key, created_at, retired_at) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&key.id)
+        .bind(&key.environment_id)
+        .bind(&key.private_key)
+        .bind(&key.public_key)
+        .bind(key.created_at)
+        .bind(key.retired_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_signing_keys(&self, environment_id: &str) -> Result<Vec<SigningKey>> {
+        let keys = sqlx::query_as(
+            "SELECT id, environment_id, private_key, public_key, created_at, retired_at FROM signing_keys WHERE environment_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(environment_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(keys)
+    }
+
+    async fn retire_signing_keys(&self, environment_id: &str, at: DateTime<Utc>) -> Result<()> {
+        sqlx::query(
+            "UPDATE signing_keys SET retired_at = $1 WHERE environment_id = $2 AND retired_at IS NULL",
+        )
+        .bind(at)
+        .bind(environment_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // ============ Change Requests ============
+
+    async fn create_change_request(&self, change: &ChangeRequest) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO change_requests (id, project_id, flag_key, environment, enabled, rollout_percentage, base_version, comment, status, proposed_by, reviewed_by, review_comment, created_at, reviewed_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
+        )
+        .bind(&change.id)
+        .bind(&change.project_id)
+        .bind(&change.flag_key)
+        .bind(&change.environment)
+        .bind(change.enabled)
+        .bind(change.rollout_percentage)
+        .bind(change.base_version)
+        .bind(&change.comment)
+        .bind(&change.status)
+        .bind(&change.proposed_by)
+        .bind(&change.reviewed_by)
+        .bind(&change.review_comment)
+        .bind(change.created_at)
+        .bind(change.reviewed_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_change_request(&self, id: &str) -> Result<Option<ChangeRequest>> {
+        let change = sqlx::query_as(
+            "SELECT id, project_id, flag_key, environment, enabled, rollout_percentage, base_version, comment, status, proposed_by, reviewed_by, review_comment, created_at, reviewed_at FROM change_requests WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(change)
+    }
+
+    async fn list_change_requests(
+        &self,
+        project_id: &str,
+        status: Option<&str>,
+    ) -> Result<Vec<ChangeRequest>> {
+        let changes = sqlx::query_as(
+            "SELECT id, project_id, flag_key, environment, enabled, rollout_percentage, base_version, comment, status, proposed_by, reviewed_by, review_comment, created_at, reviewed_at FROM change_requests WHERE project_id = $1 AND ($2 IS NULL OR status = $2) ORDER BY created_at DESC",
+        )
+        .bind(project_id)
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(changes)
+    }
+
+    async fn review_change_request(&self, change: &ChangeRequest) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE change_requests SET status = $1, reviewed_by = $2, review_comment = $3, reviewed_at = $4 WHERE id = $5 AND status = 'pending'",
+        )
+        .bind(&change.status)
+        .bind(&change.reviewed_by)
+        .bind(&change.review_comment)
+        .bind(change.reviewed_at)
+        .bind(&change.id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn reopen_change_request(&self, id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE change_requests SET status = 'pending', reviewed_by = NULL, review_comment = NULL, reviewed_at = NULL WHERE id = $1 AND status = 'approved'",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // ============ Ramps ============
+
+    async fn create_ramp(&self, ramp: &Ramp) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO ramps (id, project_id, flag_id, environment_id, target_percentage, step_percentage, interval_secs, current_percentage, status, next_step_at, reason, created_by, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
+        )
+        .bind(&ramp.id)
+        .bind(&ramp.project_id)
+        .bind(&ramp.flag_id)
+        .bind(&ramp.environment_id)
+        .bind(ramp.target_percentage)
+        .bind(ramp.step_percentage)
+        .bind(ramp.interval_secs)
+        .bind(ramp.current_percentage)
+        .bind(&ramp.status)
+        .bind(ramp.next_step_at)
+        .bind(&ramp.reason)
+        .bind(&ramp.created_by)
+        .bind(ramp.created_at)
+        .bind(ramp.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_ramp(&self, id: &str) -> Result<Option<Ramp>> {
+        let ramp = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, target_percentage, step_percentage, interval_secs, current_percentage, status, next_step_at, reason, created_by, created_at, updated_at FROM ramps WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(ramp)
+    }
+
+    async fn list_ramps(&self, project_id: &str) -> Result<Vec<Ramp>> {
+        let ramps = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, target_percentage, step_percentage, interval_secs, current_percentage, status, next_step_at, reason, created_by, created_at, updated_at FROM ramps WHERE project_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ramps)
+    }
+
+    async fn list_due_ramps(&self, now: DateTime<Utc>) -> Result<Vec<Ramp>> {
+        let ramps = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, target_percentage, step_percentage, interval_secs, current_percentage, status, next_step_at, reason, created_by, created_at, updated_at FROM ramps WHERE status = 'active' AND next_step_at <= $1 ORDER BY next_step_at",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ramps)
+    }
+
+    async fn update_ramp(&self, ramp: &Ramp, from_status: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE ramps SET current_percentage = $1, status = $2, next_step_at = $3, updated_at = $4 WHERE id = $5 AND status = $6",
+        )
+        .bind(ramp.current_percentage)
+        .bind(&ramp.status)
+        .bind(ramp.next_step_at)
+        .bind(ramp.updated_at)
+        .bind(&ramp.id)
+        .bind(from_status)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ============ Guards ============
+
+    async fn create_guard(&self, guard: &Guard) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO guards (id, project_id, flag_id, environment_id, ramp_id, url, token_hash, version, revert_enabled, revert_rollout_percentage, revert_value, window_secs, status, message, created_by, created_at, ended_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)",
+        )
+        .bind(&guard.id)
+        .bind(&guard.project_id)
+        .bind(&guard.flag_id)
+        .bind(&guard.environment_id)
+        .bind(&guard.ramp_id)
+        .bind(&guard.url)
+        .bind(&guard.token_hash)
+        .bind(guard.version)
+        .bind(guard.revert_enabled)
+        .bind(guard.revert_rollout_percentage)
+        .bind(&guard.revert_value)
+        .bind(guard.window_secs)
+        .bind(&guard.status)
+        .bind(&guard.message)
+        .bind(&guard.created_by)
+        .bind(guard.created_at)
+        .bind(guard.ended_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_guard(&self, id: &str) -> Result<Option<Guard>> {
+        let guard = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, ramp_id, url, token_hash, version, revert_enabled, revert_rollout_percentage, revert_value, window_secs, status, message, created_by, created_at, ended_at FROM guards WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(guard)
+    }
+
+    async fn get_guard_by_token_hash(&self, token_hash: &str) -> Result<Option<Guard>> {
+        let guard = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, ramp_id, url, token_hash, version, revert_enabled, revert_rollout_percentage, revert_value, window_secs, status, message, created_by, created_at, ended_at FROM guards WHERE token_hash = $1",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(guard)
+    }
+
+    async fn list_guards(&self, project_id: &str) -> Result<Vec<Guard>> {
+        let guards = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, ramp_id, url, token_hash, version, revert_enabled, revert_rollout_percentage, revert_value, window_secs, status, message, created_by, created_at, ended_at FROM guards WHERE project_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(guards)
+    }
+
+    async fn list_watching_guards(&self) -> Result<Vec<Guard>> {
+        let guards = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, ramp_id, url, token_hash, version, revert_enabled, revert_rollout_percentage, revert_value, window_secs, status, message, created_by, created_at, ended_at FROM guards WHERE status = 'watching' ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(guards)
+    }
+
+    async fn end_guard(&self, guard: &Guard) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE guards SET status = $1, message = $2, ended_at = $3 WHERE id = $4 AND status = 'watching'",
+        )
+        .bind(&guard.status)
+        .bind(&guard.message)
+        .bind(guard.ended_at)
+        .bind(&guard.id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ============ Project sharing ============
+
+    async fn set_project_member(&self, member: &ProjectMember) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO project_members (project_id, user_id, role, created_at) VALUES ($1, $2, $3, $4) ON CONFLICT (project_id, user_id) DO UPDATE SET role = excluded.role",
+        )
+        .bind(&member.project_id)
+        .bind(&member.user_id)
+        .bind(&member.role)
+        .bind(member.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_project_member(
+        &self,
+        project_id: &str,
+        user_id: &str,
+    ) -> Result<Option<ProjectMember>> {
+        let member = sqlx::query_as(
+            "SELECT project_id, user_id, role, created_at FROM project_members WHERE project_id = $1 AND user_id = $2",
+        )
+        .bind(project_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(member)
+    }
+
+    async fn list_project_members(&self, project_id: &str) -> Result<Vec<ProjectMember>> {
+        let members = sqlx::query_as(
+            "SELECT project_id, user_id, role, created_at FROM project_members WHERE project_id = $1 ORDER BY created_at",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(members)
+    }
+
+    async fn remove_project_member(&self, project_id: &str, user_id: &str) -> Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM project_members WHERE project_id = $1 AND user_id = $2")
+                .bind(project_id)
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_projects_shared_with(&self, user_id: &str) -> Result<Vec<Project>> {
+        let projects = sqlx::query_as(
+            "SELECT p.id, p.user_id, p.org_id, p.name, p.slug, p.api_key, p.created_at, p.require_change_reason FROM projects p JOIN project_members m ON m.project_id = p.id WHERE m.user_id = $1 ORDER BY p.created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(projects)
+    }
+
+    async fn create_invite_link(&self, link: &InviteLink) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO invite_links (id, project_id, token_hash, role, uses, created_by, created_at, expires_at, revoked_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(&link.id)
+        .bind(&link.project_id)
+        .bind(&link.token_hash)
+        .bind(&link.role)
+        .bind(link.uses)
+        .bind(&link.created_by)
+        .bind(link.created_at)
+        .bind(link.expires_at)
+        .bind(link.revoked_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_invite_link(&self, id: &str) -> Result<Option<InviteLink>> {
+        let link = sqlx::query_as(
+            "SELECT id, project_id, token_hash, role, uses, created_by, created_at, expires_at, revoked_at FROM invite_links WHERE id = $1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(link)
+    }
+
+    async fn get_invite_link_by_token_hash(&self, token_hash: &str) -> Result<Option<InviteLink>> {
+        let link = sqlx::query_as(
+            "SELECT id, project_id, token_hash, role, uses, created_by, created_at, expires_at, revoked_at FROM invite_links WHERE token_hash = $1",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(link)
+    }
+
+    async fn list_invite_links(&self, project_id: &str) -> Result<Vec<InviteLink>> {
+        let links = sqlx::query_as(
+            "SELECT id, project_id, token_hash, role, uses, created_by, created_at, expires_at, revoked_at FROM invite_links WHERE project_id = $1 ORDER BY created_at DESC",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(links)
+    }
+
+    async fn use_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE invite_links SET uses = uses + 1 WHERE id = $1 AND revoked_at IS NULL AND expires_at > $2",
+        )
+        .bind(id)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn revoke_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE invite_links SET revoked_at = $1 WHERE id = $2 AND revoked_at IS NULL",
+        )
+        .bind(now)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ============ Retention ============
+
+    async fn prune_flag_value_history(&self, before: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM flag_value_history WHERE updated_at < $1 AND NOT EXISTS (SELECT 1 FROM flag_values fv WHERE fv.id = flag_value_history.id AND fv.version = flag_value_history.version)",
+        )
+        .bind(before)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn prune_evaluation_counts(&self, before: NaiveDate) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM flag_evaluations WHERE day < $1")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    // ============ Migrations ============
+
+    async fn run_migrations(&self) -> Result<()> {
+        tracing::info!("Running database migrations (PostgreSQL)...");
+
+        // Create users table with username-based auth
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS users (
+                id TEXT PRIMARY KEY,
+                username TEXT UNIQUE NOT NULL,
+                password_hash TEXT NOT NULL,
+                email TEXT,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+                updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create api_keys table for user API keys
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS api_keys (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                key_hash TEXT NOT NULL,
+                key_prefix TEXT NOT NULL,
+                name TEXT,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+                revoked_at TIMESTAMP WITH TIME ZONE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create invites table (SIGNUP_MODE=invite)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS invites (
+                code TEXT PRIMARY KEY,
+                created_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                used_by TEXT,
+                used_at TIMESTAMP WITH TIME ZONE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create account_tokens table (password reset, email verification)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS account_tokens (
+                token_hash TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                purpose TEXT NOT NULL,
+                email TEXT NOT NULL,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                used_at TIMESTAMP WITH TIME ZONE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create projects table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS projects (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                api_key TEXT UNIQUE NOT NULL,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW()
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create environments table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS environments (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                api_key TEXT UNIQUE NOT NULL,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+                UNIQUE(project_id, name)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create flags table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flags (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                key TEXT NOT NULL,
+                name TEXT NOT NULL,
+                description TEXT,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+                UNIQUE(project_id, key)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create flag_values table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_values (
+                id TEXT PRIMARY KEY,
+                flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                enabled BOOLEAN NOT NULL DEFAULT FALSE,
+                rollout_percentage INTEGER NOT NULL DEFAULT 100,
+                updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
+                UNIQUE(flag_id, environment_id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create flag_value_history table (every version of every flag value)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_value_history (
+                id TEXT NOT NULL,
+                flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                enabled BOOLEAN NOT NULL,
+                rollout_percentage INTEGER NOT NULL,
+                value TEXT,
+                updated_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                version BIGINT NOT NULL,
+                PRIMARY KEY (id, version)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create flag_evaluations table (daily usage counts)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_evaluations (
+                flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                day DATE NOT NULL,
+                count BIGINT NOT NULL DEFAULT 0,
+                last_evaluated_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                PRIMARY KEY (flag_id, environment_id, day)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create organizations and membership tables
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS organizations (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                personal BOOLEAN NOT NULL DEFAULT FALSE,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS org_members (
+                org_id TEXT NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                role TEXT NOT NULL,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                PRIMARY KEY (org_id, user_id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Defaults the CLI picks up on login
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS user_preferences (
+                user_id TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                project_id TEXT,
+                environment TEXT,
+                display_name TEXT,
+                format TEXT,
+                updated_at TIMESTAMP WITH TIME ZONE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Per-project request quotas (set through the admin API)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS project_quotas (
+                project_id TEXT PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+                requests_per_minute BIGINT,
+                per_key_requests_per_minute BIGINT,
+                updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_templates (
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
                 name TEXT NOT NULL,
                 description TEXT,
-                created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
-                UNIQUE(project_id, key)
+                default_enabled BOOLEAN NOT NULL,
+                default_rollout INTEGER NOT NULL,
+                environments TEXT NOT NULL,
+                updated_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                PRIMARY KEY (project_id, name)
             )
             "#,
         )
         .execute(&self.pool)
         .await?;
 
-        // Create flag_values table
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS flag_values (
+            CREATE TABLE IF NOT EXISTS flag_revisions (
+                project_id TEXT PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+                revision BIGINT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_changes (
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                flag_key TEXT NOT NULL,
+                revision BIGINT NOT NULL,
+                deleted BOOLEAN NOT NULL,
+                changed_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                PRIMARY KEY (project_id, flag_key)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS signing_keys (
+                id TEXT PRIMARY KEY,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                private_key TEXT NOT NULL,
+                public_key TEXT NOT NULL,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                retired_at TIMESTAMP WITH TIME ZONE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS change_requests (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                flag_key TEXT NOT NULL,
+                environment TEXT NOT NULL,
+                enabled BOOLEAN,
+                rollout_percentage INTEGER,
+                base_version BIGINT NOT NULL,
+                comment TEXT,
+                status TEXT NOT NULL,
+                proposed_by TEXT NOT NULL,
+                reviewed_by TEXT,
+                review_comment TEXT,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                reviewed_at TIMESTAMP WITH TIME ZONE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS ramps (
                 id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
                 flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
                 environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
-                enabled BOOLEAN NOT NULL DEFAULT FALSE,
-                rollout_percentage INTEGER NOT NULL DEFAULT 100,
-                updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW(),
-                UNIQUE(flag_id, environment_id)
+                target_percentage INTEGER NOT NULL,
+                step_percentage INTEGER NOT NULL,
+                interval_secs BIGINT NOT NULL,
+                current_percentage INTEGER NOT NULL,
+                status TEXT NOT NULL,
+                next_step_at TIMESTAMP WITH TIME ZONE,
+                reason TEXT,
+                created_by TEXT NOT NULL,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                updated_at TIMESTAMP WITH TIME ZONE NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS guards (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                ramp_id TEXT REFERENCES ramps(id) ON DELETE CASCADE,
+                url TEXT,
+                token_hash TEXT NOT NULL UNIQUE,
+                version BIGINT NOT NULL,
+                revert_enabled BOOLEAN NOT NULL,
+                revert_rollout_percentage INTEGER NOT NULL,
+                revert_value TEXT,
+                window_secs BIGINT NOT NULL,
+                status TEXT NOT NULL,
+                message TEXT,
+                created_by TEXT NOT NULL,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                ended_at TIMESTAMP WITH TIME ZONE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS username_changes (
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                old_username TEXT NOT NULL,
+                new_username TEXT NOT NULL,
+                changed_at TIMESTAMP WITH TIME ZONE NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS project_members (
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                role TEXT NOT NULL,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                PRIMARY KEY (project_id, user_id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_user_lists (
+                flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                user_id TEXT NOT NULL,
+                list TEXT NOT NULL,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                PRIMARY KEY (flag_id, environment_id, user_id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS invite_links (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                token_hash TEXT NOT NULL UNIQUE,
+                role TEXT NOT NULL,
+                uses BIGINT NOT NULL DEFAULT 0,
+                created_by TEXT NOT NULL,
+                created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                expires_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                revoked_at TIMESTAMP WITH TIME ZONE
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Snapshots of deleted environments, kept until purge_after
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS deleted_environments (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                environment TEXT NOT NULL,
+                flag_values TEXT NOT NULL,
+                listed_users TEXT NOT NULL,
+                deleted_by TEXT NOT NULL,
+                deleted_at TIMESTAMP WITH TIME ZONE NOT NULL,
+                purge_after TIMESTAMP WITH TIME ZONE NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Columns added after the initial schema
+        if self
+            .add_column_if_missing(
+                "environments",
+                "protected",
+                "BOOLEAN NOT NULL DEFAULT FALSE",
+            )
+            .await?
+        {
+            // Existing production environments start out protected
+            sqlx::query("UPDATE environments SET protected = TRUE WHERE name = 'production'")
+                .execute(&self.pool)
+                .await?;
+        }
+
+        self.add_column_if_missing("flag_values", "version", "BIGINT NOT NULL DEFAULT 1")
+            .await?;
+
+        self.add_column_if_missing("api_keys", "org_id", "TEXT")
+            .await?;
+        // Keys from before scopes keep full access
+        self.add_column_if_missing("api_keys", "scope", "TEXT NOT NULL DEFAULT 'admin'")
+            .await?;
+        self.add_column_if_missing("flags", "expires_at", "TIMESTAMP WITH TIME ZONE")
+            .await?;
+        self.add_column_if_missing("projects", "org_id", "TEXT")
+            .await?;
+        self.add_column_if_missing("projects", "slug", "TEXT")
+            .await?;
+        self.add_column_if_missing("environments", "frozen", "BOOLEAN NOT NULL DEFAULT FALSE")
+            .await?;
+        if self
+            .add_column_if_missing("environments", "sort_order", "INTEGER NOT NULL DEFAULT 0")
+            .await?
+        {
+            // Existing environments keep the development, staging, production order
+            sqlx::query(
+                "UPDATE environments SET sort_order = CASE name WHEN 'development' THEN 0 WHEN 'staging' THEN 1 WHEN 'production' THEN 2 ELSE 3 END",
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+        self.add_column_if_missing("environments", "color", "TEXT")
+            .await?;
+        self.add_column_if_missing("environments", "emoji", "TEXT")
+            .await?;
+        self.add_column_if_missing("environments", "cache_max_age", "INTEGER")
+            .await?;
+        self.add_column_if_missing("environments", "cache_stale_while_revalidate", "INTEGER")
+            .await?;
+        self.add_column_if_missing("environments", "public_client_id", "TEXT")
+            .await?;
+        self.add_column_if_missing("flags", "owner", "TEXT").await?;
+        self.add_column_if_missing("flags", "repository", "TEXT")
+            .await?;
+        self.add_column_if_missing("flags", "code_path", "TEXT")
+            .await?;
+        self.add_column_if_missing("flags", "flag_type", "TEXT NOT NULL DEFAULT 'boolean'")
+            .await?;
+        self.add_column_if_missing("flags", "json_schema", "TEXT")
+            .await?;
+        self.add_column_if_missing("flag_values", "value", "TEXT")
+            .await?;
+        self.add_column_if_missing("flags", "default_enabled", "BOOLEAN NOT NULL DEFAULT FALSE")
+            .await?;
+        self.add_column_if_missing("flags", "default_value", "TEXT")
+            .await?;
+        self.add_column_if_missing("flags", "public", "BOOLEAN NOT NULL DEFAULT FALSE")
+            .await?;
+        self.add_column_if_missing("users", "email_verified_at", "TIMESTAMP WITH TIME ZONE")
+            .await?;
+        self.add_column_if_missing(
+            "projects",
+            "require_change_reason",
+            "BOOLEAN NOT NULL DEFAULT FALSE",
+        )
+        .await?;
+        self.add_column_if_missing("flag_values", "reason", "TEXT")
+            .await?;
+        self.add_column_if_missing("flag_value_history", "reason", "TEXT")
+            .await?;
+        self.add_column_if_missing("users", "session_epoch", "BIGINT NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing(
+            "environments",
+            "require_approval",
+            "BOOLEAN NOT NULL DEFAULT FALSE",
+        )
+        .await?;
+
+        // Give every user a personal org owning their projects (idempotent, so
+        // users created by an older binary are picked up on the next start)
+        sqlx::query(
+            r#"
+            INSERT INTO organizations (id, name, personal, created_at)
+            SELECT 'personal_' || u.id, u.username, TRUE, u.created_at
+            FROM users u
+            WHERE NOT EXISTS (SELECT 1 FROM organizations o WHERE o.id = 'personal_' || u.id)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO org_members (org_id, user_id, role, created_at)
+            SELECT 'personal_' || u.id, u.id, 'owner', u.created_at
+            FROM users u
+            WHERE NOT EXISTS (
+                SELECT 1 FROM org_members m WHERE m.org_id = 'personal_' || u.id AND m.user_id = u.id
             )
             "#,
         )
         .execute(&self.pool)
         .await?;
+        sqlx::query("UPDATE projects SET org_id = 'personal_' || user_id WHERE org_id IS NULL")
+            .execute(&self.pool)
+            .await?;
+        self.backfill_project_slugs().await?;
 
         // Create indexes
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_username ON users(username)")
@@ -490,12 +2533,23 @@ impl Storage for PostgresStorage {
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_api_keys_hash ON api_keys(key_hash)")
             .execute(&self.pool)
             .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_projects_org ON projects(org_id)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_org_members_user ON org_members(user_id)")
+            .execute(&self.pool)
+            .await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_projects_user ON projects(user_id)")
             .execute(&self.pool)
             .await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_projects_api_key ON projects(api_key)")
             .execute(&self.pool)
             .await?;
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_projects_user_slug ON projects(user_id, slug)",
+        )
+        .execute(&self.pool)
+        .await?;
         sqlx::query(
             "CREATE INDEX IF NOT EXISTS idx_environments_project ON environments(project_id)",
         )
@@ -504,14 +2558,108 @@ impl Storage for PostgresStorage {
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_environments_api_key ON environments(api_key)")
             .execute(&self.pool)
             .await?;
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_environments_public_client_id ON environments(public_client_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_change_requests_project ON change_requests(project_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_ramps_project ON ramps(project_id)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_ramps_due ON ramps(status, next_step_at)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_guards_project ON guards(project_id)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_guards_status ON guards(status)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_username_changes_old ON username_changes(old_username)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_username_changes_user ON username_changes(user_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_project_members_user ON project_members(user_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_invite_links_project ON invite_links(project_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_flag_user_lists_environment ON flag_user_lists(environment_id)",
+        )
+        .execute(&self.pool)
+        .await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_flags_project ON flags(project_id)")
             .execute(&self.pool)
             .await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_flag_values_flag ON flag_values(flag_id)")
             .execute(&self.pool)
             .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_flag_value_history_flag ON flag_value_history(flag_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Values written before history was kept start it at their current version
+        sqlx::query(
+            "INSERT INTO flag_value_history (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) SELECT id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason FROM flag_values ON CONFLICT DO NOTHING",
+        )
+        .execute(&self.pool)
+        .await?;
 
         tracing::info!("Migrations completed");
         Ok(())
     }
+
+    // ============ Backups ============
+
+    async fn backup(&self, _path: &Path) -> Result<()> {
+        Err(AppError::BadRequest(
+            "Backups are only built in for SQLite; use pg_dump for PostgreSQL".to_string(),
+        ))
+    }
+
+    // ============ Health ============
+
+    async fn health_check(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<StorageStats> {
+        let (users, projects, environments, flags, database_bytes): (i64, i64, i64, i64, i64) =
+            sqlx::query_as(
+                "SELECT (SELECT COUNT(*) FROM users), (SELECT COUNT(*) FROM projects),
+                        (SELECT COUNT(*) FROM environments), (SELECT COUNT(*) FROM flags),
+                        pg_database_size(current_database())",
+            )
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(StorageStats {
+            backend: "postgres",
+            users,
+            projects,
+            environments,
+            flags,
+            database_bytes: Some(database_bytes),
+        })
+    }
 }
@@ -1,30 +1,231 @@
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Utc};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions};
-use sqlx::SqlitePool;
+use sqlx::{Connection, QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use super::Storage;
-use crate::error::Result;
-use crate::models::{ApiKey, Environment, Flag, FlagValue, Project, User};
+use super::{constraint_error, Storage, STATEMENT_CACHE_CAPACITY, UPSERT_BATCH_ROWS};
+use crate::error::{AppError, Result};
+use crate::models::{
+    AccountToken, ApiKey, ChangeRequest, DeletedEnvironment, Environment, EvaluationCount, Flag,
+    FlagChange, FlagSwitch, FlagTemplate, FlagValue, Guard, Invite, InviteLink, ListedUser,
+    OrgMember, Organization, Project, ProjectMember, ProjectQuota, ProjectSummary, Ramp,
+    SigningKey, StorageStats, User, UserPreferences, UsernameChange,
+};
+use crate::slug;
 
 pub struct SqliteStorage {
     pool: SqlitePool,
 }
 
 impl SqliteStorage {
-    pub async fn new(database_url: &str) -> Result<Self> {
+    pub async fn new(database_url: &str, max_connections: u32) -> Result<Self> {
         let options = SqliteConnectOptions::from_str(database_url)?
             .create_if_missing(true)
-            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal);
+            .journal_mode(sqlx::sqlite::SqliteJournalMode::Wal)
+            .statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
 
+        // WAL lets the extra connections read while one of them writes
         let pool = SqlitePoolOptions::new()
-            .max_connections(5)
+            .max_connections(max_connections)
             .connect_with(options)
             .await?;
 
         Ok(Self { pool })
     }
+
+    /// Add a column to an existing table, returning whether it was missing
+    async fn add_column_if_missing(
+        &self,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<bool> {
+        let exists: bool =
+            sqlx::query_scalar("SELECT COUNT(*) > 0 FROM pragma_table_info(?) WHERE name = ?")
+                .bind(table)
+                .bind(column)
+                .fetch_one(&self.pool)
+                .await?;
+
+        if exists {
+            return Ok(false);
+        }
+
+        sqlx::query(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))
+        .execute(&self.pool)
+        .await?;
+        Ok(true)
+    }
+
+    /// Give projects created before slugs were stored one derived from their
+    /// name, oldest first so the oldest project keeps the plain slug
+    async fn backfill_project_slugs(&self) -> Result<()> {
+        let unslugged: Vec<(String, String, String)> = sqlx::query_as(
+            "SELECT id, user_id, name FROM projects WHERE slug IS NULL ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        for (id, user_id, name) in unslugged {
+            let taken: HashSet<String> = sqlx::query_scalar(
+                "SELECT slug FROM projects WHERE user_id = ? AND slug IS NOT NULL",
+            )
+            .bind(&user_id)
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .collect();
+
+            sqlx::query("UPDATE projects SET slug = ? WHERE id = ?")
+                .bind(slug::unique_slug(&name, &taken))
+                .bind(&id)
+                .execute(&self.pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Insert a project, on `conn` so it can join a transaction
+    async fn insert_project(conn: &mut SqliteConnection, project: &Project) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO projects (id, user_id, org_id, name, slug, api_key, created_at, require_change_reason) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&project.id)
+        .bind(&project.user_id)
+        .bind(&project.org_id)
+        .bind(&project.name)
+        .bind(&project.slug)
+        .bind(&project.api_key)
+        .bind(project.created_at)
+        .bind(project.require_change_reason)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Insert an environment, on `conn` so it can join a transaction
+    async fn insert_environment(conn: &mut SqliteConnection, env: &Environment) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO environments (id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&env.id)
+        .bind(&env.project_id)
+        .bind(&env.name)
+        .bind(&env.api_key)
+        .bind(env.protected)
+        .bind(env.frozen)
+        .bind(env.sort_order)
+        .bind(&env.color)
+        .bind(&env.emoji)
+        .bind(env.cache_max_age)
+        .bind(env.cache_stale_while_revalidate)
+        .bind(&env.public_client_id)
+        .bind(env.require_approval)
+        .bind(env.created_at)
+        .execute(conn)
+        .await
+        .map_err(|e| constraint_error(e, || AppError::EnvironmentInUse(env.name.clone())))?;
+        Ok(())
+    }
+
+    /// Insert a flag, on `conn` so it can join a transaction
+    async fn insert_flag(conn: &mut SqliteConnection, flag: &Flag) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO flags (id, project_id, key, name, description, created_at, expires_at, owner, repository, code_path, flag_type, json_schema, default_enabled, default_value, public) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&flag.id)
+        .bind(&flag.project_id)
+        .bind(&flag.key)
+        .bind(&flag.name)
+        .bind(&flag.description)
+        .bind(flag.created_at)
+        .bind(flag.expires_at)
+        .bind(&flag.owner)
+        .bind(&flag.repository)
+        .bind(&flag.code_path)
+        .bind(&flag.flag_type)
+        .bind(&flag.json_schema)
+        .bind(flag.default_enabled)
+        .bind(&flag.default_value)
+        .bind(flag.public)
+        .execute(conn)
+        .await
+        .map_err(|e| constraint_error(e, || AppError::DuplicateFlag(flag.key.clone())))?;
+        Ok(())
+    }
+
+    /// Upsert `flag_values` and record their history, on `conn` so it can
+    /// join a transaction
+    async fn write_flag_values(
+        conn: &mut SqliteConnection,
+        flag_values: &[FlagValue],
+    ) -> Result<()> {
+        for batch in flag_values.chunks(UPSERT_BATCH_ROWS) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO flag_values (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) ",
+            );
+            query.push_values(batch, |mut row, fv| {
+                row.push_bind(&fv.id)
+                    .push_bind(&fv.flag_id)
+                    .push_bind(&fv.environment_id)
+                    .push_bind(fv.enabled)
+                    .push_bind(fv.rollout_percentage)
+                    .push_bind(&fv.value)
+                    .push_bind(fv.updated_at)
+                    .push_bind(fv.version)
+                    .push_bind(&fv.reason);
+            });
+            // The stored row's id and version, so the history matches it
+            query.push(
+                " ON CONFLICT (flag_id, environment_id) DO UPDATE SET enabled = excluded.enabled, rollout_percentage = excluded.rollout_percentage, value = excluded.value, updated_at = excluded.updated_at, version = flag_values.version + 1, reason = excluded.reason RETURNING id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason",
+            );
+            let written: Vec<FlagValue> = query.build_query_as().fetch_all(&mut *conn).await?;
+
+            let mut history = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO flag_value_history (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) ",
+            );
+            history.push_values(&written, |mut row, fv| {
+                row.push_bind(&fv.id)
+                    .push_bind(&fv.flag_id)
+                    .push_bind(&fv.environment_id)
+                    .push_bind(fv.enabled)
+                    .push_bind(fv.rollout_percentage)
+                    .push_bind(&fv.value)
+                    .push_bind(fv.updated_at)
+                    .push_bind(fv.version)
+                    .push_bind(&fv.reason);
+            });
+            history.build().execute(&mut *conn).await?;
+        }
+        Ok(())
+    }
+
+    /// Record `flag_value` as written, in the same transaction as the write
+    async fn record_flag_value_history(
+        conn: &mut SqliteConnection,
+        flag_value: &FlagValue,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO flag_value_history (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&flag_value.id)
+        .bind(&flag_value.flag_id)
+        .bind(&flag_value.environment_id)
+        .bind(flag_value.enabled)
+        .bind(flag_value.rollout_percentage)
+        .bind(&flag_value.value)
+        .bind(flag_value.updated_at)
+        .bind(flag_value.version)
+        .bind(&flag_value.reason)
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -33,22 +234,25 @@ impl Storage for SqliteStorage {
 
     async fn create_user(&self, user: &User) -> Result<()> {
         sqlx::query(
-            "INSERT INTO users (id, username, password_hash, email, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO users (id, username, password_hash, email, email_verified_at, session_epoch, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(&user.id)
         .bind(&user.username)
         .bind(&user.password_hash)
         .bind(&user.email)
+        .bind(user.email_verified_at)
+        .bind(user.session_epoch)
         .bind(user.created_at)
         .bind(user.updated_at)
         .execute(&self.pool)
-        .await?;
+        .await
+        .map_err(|e| constraint_error(e, || AppError::UserAlreadyExists))?;
         Ok(())
     }
 
     async fn get_user_by_username(&self, username: &str) -> Result<Option<User>> {
         let user = sqlx::query_as(
-            "SELECT id, username, password_hash, email, created_at, updated_at FROM users WHERE username = ?",
+            "SELECT id, username, password_hash, email, email_verified_at, session_epoch, created_at, updated_at FROM users WHERE username = ?",
         )
         .bind(username)
         .fetch_optional(&self.pool)
@@ -58,7 +262,7 @@ impl Storage for SqliteStorage {
 
     async fn get_user_by_id(&self, id: &str) -> Result<Option<User>> {
         let user = sqlx::query_as(
-            "SELECT id, username, password_hash, email, created_at, updated_at FROM users WHERE id = ?",
+            "SELECT id, username, password_hash, email, email_verified_at, session_epoch, created_at, updated_at FROM users WHERE id = ?",
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -67,12 +271,33 @@ impl Storage for SqliteStorage {
     }
 
     async fn update_user(&self, user: &User) -> Result<()> {
-        sqlx::query("UPDATE users SET email = ?, updated_at = ? WHERE id = ?")
-            .bind(&user.email)
-            .bind(user.updated_at)
-            .bind(&user.id)
-            .execute(&self.pool)
-            .await?;
+        sqlx::query(
+            "UPDATE users SET email = ?, email_verified_at = ?, updated_at = ? WHERE id = ?",
+        )
+        .bind(&user.email)
+        .bind(user.email_verified_at)
+        .bind(user.updated_at)
+        .bind(&user.id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn set_password(
+        &self,
+        user_id: &str,
+        password_hash: &str,
+        bump_epoch: bool,
+    ) -> Result<()> {
+        sqlx::query(
+            "UPDATE users SET password_hash = ?, session_epoch = session_epoch + ?, updated_at = ? WHERE id = ?",
+        )
+        .bind(password_hash)
+        .bind(i64::from(bump_epoch))
+        .bind(Utc::now())
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
         Ok(())
     }
 
@@ -84,11 +309,87 @@ impl Storage for SqliteStorage {
         Ok(result.0 > 0)
     }
 
+    async fn rename_user(&self, change: &UsernameChange) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE users SET username = ?, updated_at = ? WHERE id = ?")
+            .bind(&change.new_username)
+            .bind(change.changed_at)
+            .bind(&change.user_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| constraint_error(e, || AppError::UserAlreadyExists))?;
+        sqlx::query(
+            "INSERT INTO username_changes (user_id, old_username, new_username, changed_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&change.user_id)
+        .bind(&change.old_username)
+        .bind(&change.new_username)
+        .bind(change.changed_at)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn list_username_changes(&self, user_id: &str) -> Result<Vec<UsernameChange>> {
+        let changes = sqlx::query_as(
+            "SELECT user_id, old_username, new_username, changed_at FROM username_changes WHERE user_id = ? ORDER BY changed_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(changes)
+    }
+
+    async fn get_username_change(&self, old_username: &str) -> Result<Option<UsernameChange>> {
+        let change = sqlx::query_as(
+            "SELECT user_id, old_username, new_username, changed_at FROM username_changes WHERE old_username = ? ORDER BY changed_at DESC LIMIT 1",
+        )
+        .bind(old_username)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(change)
+    }
+
+    async fn get_user_preferences(&self, user_id: &str) -> Result<Option<UserPreferences>> {
+        let preferences = sqlx::query_as(
+            "SELECT user_id, project_id, environment, display_name, format, updated_at FROM user_preferences WHERE user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(preferences)
+    }
+
+    async fn set_user_preferences(&self, preferences: &UserPreferences) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO user_preferences (user_id, project_id, environment, display_name, format, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT (user_id) DO UPDATE SET
+            project_id = excluded.project_id,
+            environment = excluded.environment,
+            display_name = excluded.display_name,
+            format = excluded.format,
+            updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&preferences.user_id)
+        .bind(&preferences.project_id)
+        .bind(&preferences.environment)
+        .bind(&preferences.display_name)
+        .bind(&preferences.format)
+        .bind(preferences.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
     // ============ API Keys ============
 
     async fn create_api_key(&self, api_key: &ApiKey) -> Result<()> {
         sqlx::query(
-            "INSERT INTO api_keys (id, user_id, key_hash, key_prefix, name, created_at, revoked_at) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO api_keys (id, user_id, key_hash, key_prefix, name, created_at, revoked_at, org_id, scope) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(&api_key.id)
         .bind(&api_key.user_id)
@@ -97,6 +398,8 @@ impl Storage for SqliteStorage {
         .bind(&api_key.name)
         .bind(api_key.created_at)
         .bind(api_key.revoked_at)
+        .bind(&api_key.org_id)
+        .bind(&api_key.scope)
         .execute(&self.pool)
         .await?;
         Ok(())
@@ -104,7 +407,7 @@ impl Storage for SqliteStorage {
 
     async fn get_api_key_by_hash(&self, key_hash: &str) -> Result<Option<ApiKey>> {
         let api_key = sqlx::query_as(
-            "SELECT id, user_id, key_hash, key_prefix, name, created_at, revoked_at FROM api_keys WHERE key_hash = ? AND revoked_at IS NULL",
+            "SELECT id, user_id, key_hash, key_prefix, name, created_at, revoked_at, org_id, scope FROM api_keys WHERE key_hash = ? AND revoked_at IS NULL",
         )
         .bind(key_hash)
         .fetch_optional(&self.pool)
@@ -114,7 +417,7 @@ impl Storage for SqliteStorage {
 
     async fn list_api_keys_by_user(&self, user_id: &str) -> Result<Vec<ApiKey>> {
         let keys = sqlx::query_as(
-            "SELECT id, user_id, key_hash, key_prefix, name, created_at, revoked_at FROM api_keys WHERE user_id = ? ORDER BY created_at DESC",
+            "SELECT id, user_id, key_hash, key_prefix, name, created_at, revoked_at, org_id, scope FROM api_keys WHERE user_id = ? ORDER BY created_at DESC",
         )
         .bind(user_id)
         .fetch_all(&self.pool)
@@ -131,25 +434,207 @@ impl Storage for SqliteStorage {
         Ok(())
     }
 
-    // ============ Projects ============
+    // ============ Invites ============
 
-    async fn create_project(&self, project: &Project) -> Result<()> {
+    async fn create_invite(&self, invite: &Invite) -> Result<()> {
         sqlx::query(
-            "INSERT INTO projects (id, user_id, name, api_key, created_at) VALUES (?, ?, ?, ?, ?)",
+            "INSERT INTO invites (code, created_by, created_at, expires_at) VALUES (?, ?, ?, ?)",
         )
-        .bind(&project.id)
-        .bind(&project.user_id)
-        .bind(&project.name)
-        .bind(&project.api_key)
-        .bind(project.created_at)
+        .bind(&invite.code)
+        .bind(&invite.created_by)
+        .bind(invite.created_at)
+        .bind(invite.expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn redeem_invite(&self, code: &str, user_id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE invites SET used_by = ?, used_at = ? WHERE code = ? AND used_at IS NULL AND expires_at > ?",
+        )
+            .bind(user_id)
+            .bind(now)
+            .bind(code)
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    // ============ Account tokens ============
+
+    async fn create_account_token(&self, token: &AccountToken) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO account_tokens (token_hash, user_id, purpose, email, created_at, expires_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&token.token_hash)
+        .bind(&token.user_id)
+        .bind(&token.purpose)
+        .bind(&token.email)
+        .bind(token.created_at)
+        .bind(token.expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn redeem_account_token(
+        &self,
+        token_hash: &str,
+        purpose: &str,
+        now: DateTime<Utc>,
+    ) -> Result<Option<AccountToken>> {
+        let result = sqlx::query(
+            "UPDATE account_tokens SET used_at = ? WHERE token_hash = ? AND purpose = ? AND used_at IS NULL AND expires_at > ?",
+        )
+        .bind(now)
+        .bind(token_hash)
+        .bind(purpose)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        if result.rows_affected() != 1 {
+            return Ok(None);
+        }
+
+        let token = sqlx::query_as(
+            "SELECT token_hash, user_id, purpose, email, created_at, expires_at, used_at FROM account_tokens WHERE token_hash = ?",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(token)
+    }
+
+    // ============ Organizations ============
+
+    async fn create_organization(&self, org: &Organization) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO organizations (id, name, personal, created_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&org.id)
+        .bind(&org.name)
+        .bind(org.personal)
+        .bind(org.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_organization(&self, id: &str) -> Result<Option<Organization>> {
+        let org =
+            sqlx::query_as("SELECT id, name, personal, created_at FROM organizations WHERE id = ?")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(org)
+    }
+
+    async fn list_organizations_by_user(&self, user_id: &str) -> Result<Vec<Organization>> {
+        let orgs = sqlx::query_as(
+            r#"
+            SELECT o.id, o.name, o.personal, o.created_at
+            FROM organizations o
+            JOIN org_members m ON m.org_id = o.id
+            WHERE m.user_id = ?
+            ORDER BY o.personal DESC, o.name
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(orgs)
+    }
+
+    async fn add_org_member(&self, member: &OrgMember) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO org_members (org_id, user_id, role, created_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&member.org_id)
+        .bind(&member.user_id)
+        .bind(&member.role)
+        .bind(member.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_org_member(&self, org_id: &str, user_id: &str) -> Result<Option<OrgMember>> {
+        let member = sqlx::query_as(
+            "SELECT org_id, user_id, role, created_at FROM org_members WHERE org_id = ? AND user_id = ?",
+        )
+        .bind(org_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(member)
+    }
+
+    async fn list_org_members(&self, org_id: &str) -> Result<Vec<OrgMember>> {
+        let members = sqlx::query_as(
+            "SELECT org_id, user_id, role, created_at FROM org_members WHERE org_id = ? ORDER BY created_at",
+        )
+        .bind(org_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(members)
+    }
+
+    async fn set_org_member_role(&self, org_id: &str, user_id: &str, role: &str) -> Result<bool> {
+        // One statement, so another owner can't be demoted or removed between
+        // the check and the write
+        let result = sqlx::query(
+            "UPDATE org_members SET role = ?1 WHERE org_id = ?2 AND user_id = ?3 AND (?1 = 'owner' OR role <> 'owner' OR EXISTS (SELECT 1 FROM org_members o WHERE o.org_id = org_members.org_id AND o.user_id <> org_members.user_id AND o.role = 'owner'))",
+        )
+        .bind(role)
+        .bind(org_id)
+        .bind(user_id)
         .execute(&self.pool)
         .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn remove_org_member(&self, org_id: &str, user_id: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "DELETE FROM org_members WHERE org_id = ? AND user_id = ? AND (role <> 'owner' OR EXISTS (SELECT 1 FROM org_members o WHERE o.org_id = org_members.org_id AND o.user_id <> org_members.user_id AND o.role = 'owner'))",
+        )
+        .bind(org_id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ============ Projects ============
+
+    async fn create_project(&self, project: &Project) -> Result<()> {
+        Self::insert_project(&mut *self.pool.acquire().await?, project).await
+    }
+
+    async fn create_project_with(
+        &self,
+        project: &Project,
+        environments: &[Environment],
+        flags: &[Flag],
+        flag_values: &[FlagValue],
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        Self::insert_project(&mut tx, project).await?;
+        for env in environments {
+            Self::insert_environment(&mut tx, env).await?;
+        }
+        for flag in flags {
+            Self::insert_flag(&mut tx, flag).await?;
+        }
+        Self::write_flag_values(&mut tx, flag_values).await?;
+        tx.commit().await?;
         Ok(())
     }
 
     async fn get_project_by_id(&self, id: &str) -> Result<Option<Project>> {
         let project = sqlx::query_as(
-            "SELECT id, user_id, name, api_key, created_at FROM projects WHERE id = ?",
+            "SELECT id, user_id, org_id, name, slug, api_key, created_at, require_change_reason FROM projects WHERE id = ?",
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -159,7 +644,7 @@ impl Storage for SqliteStorage {
 
     async fn get_project_by_api_key(&self, api_key: &str) -> Result<Option<Project>> {
         let project = sqlx::query_as(
-            "SELECT id, user_id, name, api_key, created_at FROM projects WHERE api_key = ?",
+            "SELECT id, user_id, org_id, name, slug, api_key, created_at, require_change_reason FROM projects WHERE api_key = ?",
         )
         .bind(api_key)
         .fetch_optional(&self.pool)
@@ -169,7 +654,7 @@ impl Storage for SqliteStorage {
 
     async fn list_projects_by_user(&self, user_id: &str) -> Result<Vec<Project>> {
         let projects = sqlx::query_as(
-            "SELECT id, user_id, name, api_key, created_at FROM projects WHERE user_id = ? ORDER BY created_at DESC",
+            "SELECT id, user_id, org_id, name, slug, api_key, created_at, require_change_reason FROM projects WHERE user_id = ? ORDER BY created_at DESC",
         )
         .bind(user_id)
         .fetch_all(&self.pool)
@@ -179,7 +664,7 @@ impl Storage for SqliteStorage {
 
     async fn get_first_project_by_user(&self, user_id: &str) -> Result<Option<Project>> {
         let project = sqlx::query_as(
-            "SELECT id, user_id, name, api_key, created_at FROM projects WHERE user_id = ? LIMIT 1",
+            "SELECT id, user_id, org_id, name, slug, api_key, created_at, require_change_reason FROM projects WHERE user_id = ? LIMIT 1",
         )
         .bind(user_id)
         .fetch_optional(&self.pool)
@@ -187,25 +672,75 @@ impl Storage for SqliteStorage {
         Ok(project)
     }
 
-    // ============ Environments ============
-
-    async fn create_environment(&self, env: &Environment) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO environments (id, project_id, name, api_key, created_at) VALUES (?, ?, ?, ?, ?)",
+    async fn list_projects_by_org(&self, org_id: &str) -> Result<Vec<Project>> {
+        let projects = sqlx::query_as(
+            "SELECT id, user_id, org_id, name, slug, api_key, created_at, require_change_reason FROM projects WHERE org_id = ? ORDER BY created_at DESC",
         )
-        .bind(&env.id)
-        .bind(&env.project_id)
-        .bind(&env.name)
-        .bind(&env.api_key)
-        .bind(env.created_at)
-        .execute(&self.pool)
+        .bind(org_id)
+        .fetch_all(&self.pool)
         .await?;
+        Ok(projects)
+    }
+
+    async fn update_project(&self, project: &Project) -> Result<()> {
+        sqlx::query("UPDATE projects SET require_change_reason = ? WHERE id = ?")
+            .bind(project.require_change_reason)
+            .bind(&project.id)
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 
+    async fn project_summary(
+        &self,
+        project_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<ProjectSummary> {
+        let (flags, last_changed_at): (i64, Option<DateTime<Utc>>) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM flags WHERE project_id = ?),
+                    (SELECT MAX(changed_at) FROM flag_changes WHERE project_id = ?)",
+        )
+        .bind(project_id)
+        .bind(project_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        // Flags without a value in an environment join as NULLs and count by
+        // their default
+        let environments = sqlx::query_as(
+            "SELECT e.name, e.created_at,
+                    COUNT(CASE WHEN COALESCE(fv.enabled, f.default_enabled) AND (f.expires_at IS NULL OR f.expires_at > ?) THEN 1 END) AS enabled,
+                    COUNT(CASE WHEN COALESCE(fv.enabled, f.default_enabled) AND (f.expires_at IS NULL OR f.expires_at > ?) AND fv.rollout_percentage < 100 THEN 1 END) AS partial_rollout,
+                    MAX(fv.updated_at) AS last_changed_at
+             FROM environments e
+             LEFT JOIN flags f ON f.project_id = e.project_id
+             LEFT JOIN flag_values fv ON fv.flag_id = f.id AND fv.environment_id = e.id
+             WHERE e.project_id = ?
+             GROUP BY e.id, e.name, e.created_at, e.sort_order
+             ORDER BY e.sort_order, e.created_at",
+        )
+        .bind(now)
+        .bind(now)
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(ProjectSummary {
+            flags,
+            last_changed_at,
+            environments,
+        })
+    }
+
+    // ============ Environments ============
+
+    async fn create_environment(&self, env: &Environment) -> Result<()> {
+        Self::insert_environment(&mut *self.pool.acquire().await?, env).await
+    }
+
     async fn get_environment_by_id(&self, id: &str) -> Result<Option<Environment>> {
         let env = sqlx::query_as(
-            "SELECT id, project_id, name, api_key, created_at FROM environments WHERE id = ?",
+            "SELECT id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at FROM environments WHERE id = ?",
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -215,7 +750,7 @@ impl Storage for SqliteStorage {
 
     async fn get_environment_by_api_key(&self, api_key: &str) -> Result<Option<Environment>> {
         let env = sqlx::query_as(
-            "SELECT id, project_id, name, api_key, created_at FROM environments WHERE api_key = ?",
+            "SELECT id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at FROM environments WHERE api_key = ?",
         )
         .bind(api_key)
         .fetch_optional(&self.pool)
@@ -223,13 +758,26 @@ impl Storage for SqliteStorage {
         Ok(env)
     }
 
+    async fn get_environment_by_public_client_id(
+        &self,
+        client_id: &str,
+    ) -> Result<Option<Environment>> {
+        let env = sqlx::query_as(
+            "SELECT id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at FROM environments WHERE public_client_id = ?",
+        )
+        .bind(client_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(env)
+    }
+
     async fn get_environment_by_name(
         &self,
         project_id: &str,
         name: &str,
     ) -> Result<Option<Environment>> {
         let env = sqlx::query_as(
-            "SELECT id, project_id, name, api_key, created_at FROM environments WHERE project_id = ? AND name = ?",
+            "SELECT id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at FROM environments WHERE project_id = ? AND name = ?",
         )
         .bind(project_id)
         .bind(name)
@@ -238,9 +786,28 @@ impl Storage for SqliteStorage {
         Ok(env)
     }
 
-    async fn list_environments_by_project(&self, project_id: &str) -> Result<Vec<Environment>> {
-        let envs = sqlx::query_as(
-            "SELECT id, project_id, name, api_key, created_at FROM environments WHERE project_id = ?",
+    async fn update_environment(&self, env: &Environment) -> Result<()> {
+        sqlx::query(
+            "UPDATE environments SET protected = ?, frozen = ?, sort_order = ?, color = ?, emoji = ?, cache_max_age = ?, cache_stale_while_revalidate = ?, public_client_id = ?, require_approval = ? WHERE id = ?",
+        )
+            .bind(env.protected)
+            .bind(env.frozen)
+            .bind(env.sort_order)
+            .bind(&env.color)
+            .bind(&env.emoji)
+            .bind(env.cache_max_age)
+            .bind(env.cache_stale_while_revalidate)
+            .bind(&env.public_client_id)
+            .bind(env.require_approval)
+            .bind(&env.id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_environments_by_project(&self, project_id: &str) -> Result<Vec<Environment>> {
+        let envs = sqlx::query_as(
+            "SELECT id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at FROM environments WHERE project_id = ? ORDER BY sort_order, created_at",
         )
         .bind(project_id)
         .fetch_all(&self.pool)
@@ -248,26 +815,175 @@ impl Storage for SqliteStorage {
         Ok(envs)
     }
 
-    // ============ Flags ============
+    // ============ Deleted environments ============
 
-    async fn create_flag(&self, flag: &Flag) -> Result<()> {
+    async fn delete_environment(
+        &self,
+        env: &Environment,
+        deleted: &DeletedEnvironment,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
-            "INSERT INTO flags (id, project_id, key, name, description, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO deleted_environments (id, project_id, name, environment, flag_values, listed_users, deleted_by, deleted_at, purge_after) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
-        .bind(&flag.id)
-        .bind(&flag.project_id)
-        .bind(&flag.key)
-        .bind(&flag.name)
-        .bind(&flag.description)
-        .bind(flag.created_at)
-        .execute(&self.pool)
+        .bind(&deleted.id)
+        .bind(&deleted.project_id)
+        .bind(&deleted.name)
+        .bind(&deleted.environment)
+        .bind(&deleted.flag_values)
+        .bind(&deleted.listed_users)
+        .bind(&deleted.deleted_by)
+        .bind(deleted.deleted_at)
+        .bind(deleted.purge_after)
+        .execute(&mut *tx)
+        .await?;
+        // Delete child rows first (foreign key)
+        for table in [
+            "flag_values",
+            "flag_value_history",
+            "flag_evaluations",
+            "flag_user_lists",
+            "signing_keys",
+            "ramps",
+            "guards",
+        ] {
+            sqlx::query(&format!("DELETE FROM {table} WHERE environment_id = ?"))
+                .bind(&env.id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM environments WHERE id = ?")
+            .bind(&env.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn list_deleted_environments(&self, project_id: &str) -> Result<Vec<DeletedEnvironment>> {
+        let deleted = sqlx::query_as(
+            "SELECT id, project_id, name, environment, flag_values, listed_users, deleted_by, deleted_at, purge_after FROM deleted_environments WHERE project_id = ? ORDER BY deleted_at DESC",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
         .await?;
+        Ok(deleted)
+    }
+
+    async fn restore_environment(
+        &self,
+        env: &Environment,
+        values: &[FlagValue],
+        users: &[ListedUser],
+    ) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let archived = sqlx::query("DELETE FROM deleted_environments WHERE id = ?")
+            .bind(&env.id)
+            .execute(&mut *tx)
+            .await?;
+        if archived.rows_affected() == 0 {
+            return Ok(false);
+        }
+        sqlx::query(
+            "INSERT INTO environments (id, project_id, name, api_key, protected, frozen, sort_order, color, emoji, cache_max_age, cache_stale_while_revalidate, public_client_id, require_approval, created_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&env.id)
+        .bind(&env.project_id)
+        .bind(&env.name)
+        .bind(&env.api_key)
+        .bind(env.protected)
+        .bind(env.frozen)
+        .bind(env.sort_order)
+        .bind(&env.color)
+        .bind(&env.emoji)
+        .bind(env.cache_max_age)
+        .bind(env.cache_stale_while_revalidate)
+        .bind(&env.public_client_id)
+        .bind(env.require_approval)
+        .bind(env.created_at)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| constraint_error(e, || AppError::EnvironmentInUse(env.name.clone())))?;
+        for fv in values {
+            sqlx::query(
+                "INSERT INTO flag_values (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            )
+            .bind(&fv.id)
+            .bind(&fv.flag_id)
+            .bind(&fv.environment_id)
+            .bind(fv.enabled)
+            .bind(fv.rollout_percentage)
+            .bind(&fv.value)
+            .bind(fv.updated_at)
+            .bind(fv.version)
+            .bind(&fv.reason)
+            .execute(&mut *tx)
+            .await?;
+            Self::record_flag_value_history(&mut tx, fv).await?;
+        }
+        for user in users {
+            sqlx::query(
+                "INSERT INTO flag_user_lists (flag_id, environment_id, user_id, list, created_at) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(&user.flag_id)
+            .bind(&user.environment_id)
+            .bind(&user.user_id)
+            .bind(&user.list)
+            .bind(user.created_at)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn purge_deleted_environments(&self, now: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM deleted_environments WHERE purge_after <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    // ============ Flags ============
+
+    async fn create_flag(&self, flag: &Flag) -> Result<()> {
+        Self::insert_flag(&mut *self.pool.acquire().await?, flag).await
+    }
+
+    async fn update_flag_ownership(&self, flag: &Flag) -> Result<()> {
+        sqlx::query("UPDATE flags SET owner = ?, repository = ?, code_path = ? WHERE id = ?")
+            .bind(&flag.owner)
+            .bind(&flag.repository)
+            .bind(&flag.code_path)
+            .bind(&flag.id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_flag_defaults(&self, flag: &Flag) -> Result<()> {
+        sqlx::query("UPDATE flags SET default_enabled = ?, default_value = ? WHERE id = ?")
+            .bind(flag.default_enabled)
+            .bind(&flag.default_value)
+            .bind(&flag.id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_flag_visibility(&self, flag: &Flag) -> Result<()> {
+        sqlx::query("UPDATE flags SET public = ? WHERE id = ?")
+            .bind(flag.public)
+            .bind(&flag.id)
+            .execute(&self.pool)
+            .await?;
         Ok(())
     }
 
     async fn get_flag_by_id(&self, id: &str) -> Result<Option<Flag>> {
         let flag = sqlx::query_as(
-            "SELECT id, project_id, key, name, description, created_at FROM flags WHERE id = ?",
+            "SELECT id, project_id, key, name, description, created_at, expires_at, owner, repository, code_path, flag_type, json_schema, default_enabled, default_value, public FROM flags WHERE id = ?",
         )
         .bind(id)
         .fetch_optional(&self.pool)
@@ -277,7 +993,7 @@ impl Storage for SqliteStorage {
 
     async fn get_flag_by_key(&self, project_id: &str, key: &str) -> Result<Option<Flag>> {
         let flag = sqlx::query_as(
-            "SELECT id, project_id, key, name, description, created_at FROM flags WHERE project_id = ? AND key = ?",
+            "SELECT id, project_id, key, name, description, created_at, expires_at, owner, repository, code_path, flag_type, json_schema, default_enabled, default_value, public FROM flags WHERE project_id = ? AND key = ?",
         )
         .bind(project_id)
         .bind(key)
@@ -288,7 +1004,7 @@ impl Storage for SqliteStorage {
 
     async fn list_flags_by_project(&self, project_id: &str) -> Result<Vec<Flag>> {
         let flags = sqlx::query_as(
-            "SELECT id, project_id, key, name, description, created_at FROM flags WHERE project_id = ? ORDER BY created_at DESC",
+            "SELECT id, project_id, key, name, description, created_at, expires_at, owner, repository, code_path, flag_type, json_schema, default_enabled, default_value, public FROM flags WHERE project_id = ? ORDER BY created_at DESC",
         )
         .bind(project_id)
         .fetch_all(&self.pool)
@@ -296,20 +1012,39 @@ impl Storage for SqliteStorage {
         Ok(flags)
     }
 
+    async fn list_expired_flags(&self, now: DateTime<Utc>) -> Result<Vec<Flag>> {
+        let flags = sqlx::query_as(
+            "SELECT id, project_id, key, name, description, created_at, expires_at, owner, repository, code_path, flag_type, json_schema, default_enabled, default_value, public FROM flags WHERE expires_at <= ? ORDER BY expires_at",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(flags)
+    }
+
     // ============ Flag Values ============
 
     async fn create_flag_value(&self, flag_value: &FlagValue) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
         sqlx::query(
-            "INSERT INTO flag_values (id, flag_id, environment_id, enabled, rollout_percentage, updated_at) VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO flag_values (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(&flag_value.id)
         .bind(&flag_value.flag_id)
         .bind(&flag_value.environment_id)
         .bind(flag_value.enabled)
         .bind(flag_value.rollout_percentage)
+        .bind(&flag_value.value)
         .bind(flag_value.updated_at)
-        .execute(&self.pool)
-        .await?;
+        .bind(flag_value.version)
+        .bind(&flag_value.reason)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| constraint_error(e, || {
+            AppError::Conflict("The flag already has a value in this environment".to_string())
+        }))?;
+        Self::record_flag_value_history(&mut tx, flag_value).await?;
+        tx.commit().await?;
         Ok(())
     }
 
@@ -319,7 +1054,7 @@ impl Storage for SqliteStorage {
         environment_id: &str,
     ) -> Result<Option<FlagValue>> {
         let fv = sqlx::query_as(
-            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, updated_at FROM flag_values WHERE flag_id = ? AND environment_id = ?",
+            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason FROM flag_values WHERE flag_id = ? AND environment_id = ?",
         )
         .bind(flag_id)
         .bind(environment_id)
@@ -328,19 +1063,123 @@ impl Storage for SqliteStorage {
         Ok(fv)
     }
 
-    async fn update_flag_value(&self, flag_value: &FlagValue) -> Result<()> {
-        sqlx::query(
-            "UPDATE flag_values SET enabled = ?, rollout_percentage = ?, updated_at = ? WHERE id = ?",
+    async fn get_flag_switch(
+        &self,
+        project_id: &str,
+        environment_id: &str,
+        key: &str,
+    ) -> Result<Option<FlagSwitch>> {
+        let switch = sqlx::query_as(
+            "SELECT f.id AS flag_id, f.expires_at, f.default_enabled, fv.enabled, fv.rollout_percentage FROM flags f LEFT JOIN flag_values fv ON fv.flag_id = f.id AND fv.environment_id = ? WHERE f.project_id = ? AND f.key = ?",
+        )
+        .bind(environment_id)
+        .bind(project_id)
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(switch)
+    }
+
+    async fn update_flag_value(&self, flag_value: &FlagValue) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query(
+            "UPDATE flag_values SET enabled = ?, rollout_percentage = ?, value = ?, updated_at = ?, version = ?, reason = ? WHERE id = ? AND version = ?",
         )
         .bind(flag_value.enabled)
         .bind(flag_value.rollout_percentage)
+        .bind(&flag_value.value)
         .bind(flag_value.updated_at)
+        .bind(flag_value.version)
+        .bind(&flag_value.reason)
         .bind(&flag_value.id)
-        .execute(&self.pool)
+        .bind(flag_value.version - 1)
+        .execute(&mut *tx)
         .await?;
+        if result.rows_affected() != 1 {
+            return Ok(false);
+        }
+        Self::record_flag_value_history(&mut tx, flag_value).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn save_flag_values(&self, flag_values: &[FlagValue]) -> Result<bool> {
+        // Dropping the transaction without committing rolls everything back
+        let mut tx = self.pool.begin().await?;
+        for flag_value in flag_values {
+            let result = if flag_value.version == 1 {
+                sqlx::query(
+                    "INSERT INTO flag_values (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                )
+                .bind(&flag_value.id)
+                .bind(&flag_value.flag_id)
+                .bind(&flag_value.environment_id)
+                .bind(flag_value.enabled)
+                .bind(flag_value.rollout_percentage)
+                .bind(&flag_value.value)
+                .bind(flag_value.updated_at)
+                .bind(flag_value.version)
+                .bind(&flag_value.reason)
+                .execute(&mut *tx)
+                .await?
+            } else {
+                sqlx::query(
+                    "UPDATE flag_values SET enabled = ?, rollout_percentage = ?, value = ?, updated_at = ?, version = ?, reason = ? WHERE id = ? AND version = ?",
+                )
+                .bind(flag_value.enabled)
+                .bind(flag_value.rollout_percentage)
+                .bind(&flag_value.value)
+                .bind(flag_value.updated_at)
+                .bind(flag_value.version)
+                .bind(&flag_value.reason)
+                .bind(&flag_value.id)
+                .bind(flag_value.version - 1)
+                .execute(&mut *tx)
+                .await?
+            };
+            if result.rows_affected() != 1 {
+                return Ok(false);
+            }
+            Self::record_flag_value_history(&mut tx, flag_value).await?;
+        }
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn upsert_flag_values(&self, flag_values: &[FlagValue]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        Self::write_flag_values(&mut tx, flag_values).await?;
+        tx.commit().await?;
         Ok(())
     }
 
+    async fn list_flag_value_history(&self, flag_value_id: &str) -> Result<Vec<FlagValue>> {
+        let history = sqlx::query_as(
+            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason FROM flag_value_history WHERE id = ? ORDER BY version DESC",
+        )
+        .bind(flag_value_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(history)
+    }
+
+    async fn list_flag_value_history_page(
+        &self,
+        flag_value_id: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<FlagValue>> {
+        let history = sqlx::query_as(
+            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason FROM flag_value_history WHERE id = ? AND version < ? ORDER BY version DESC LIMIT ?",
+        )
+        .bind(flag_value_id)
+        .bind(before.unwrap_or(i64::MAX))
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(history)
+    }
+
     async fn list_flag_values_by_flag_ids(&self, flag_ids: &[String]) -> Result<Vec<FlagValue>> {
         if flag_ids.is_empty() {
             return Ok(vec![]);
@@ -348,7 +1187,7 @@ impl Storage for SqliteStorage {
 
         let placeholders = flag_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
         let query_str = format!(
-            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, updated_at FROM flag_values WHERE flag_id IN ({placeholders})",
+            "SELECT id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason FROM flag_values WHERE flag_id IN ({placeholders})",
         );
 
         let mut query = sqlx::query_as(&query_str);
@@ -361,11 +1200,31 @@ impl Storage for SqliteStorage {
     }
 
     async fn delete_flag(&self, flag_id: &str) -> Result<()> {
-        // Delete flag values first (foreign key)
+        // Delete flag values and usage first (foreign key)
         sqlx::query("DELETE FROM flag_values WHERE flag_id = ?")
             .bind(flag_id)
             .execute(&self.pool)
             .await?;
+        sqlx::query("DELETE FROM flag_value_history WHERE flag_id = ?")
+            .bind(flag_id)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM flag_evaluations WHERE flag_id = ?")
+            .bind(flag_id)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM guards WHERE flag_id = ?")
+            .bind(flag_id)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM ramps WHERE flag_id = ?")
+            .bind(flag_id)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("DELETE FROM flag_user_lists WHERE flag_id = ?")
+            .bind(flag_id)
+            .execute(&self.pool)
+            .await?;
 
         // Delete the flag
         sqlx::query("DELETE FROM flags WHERE id = ?")
@@ -376,108 +1235,1275 @@ impl Storage for SqliteStorage {
         Ok(())
     }
 
-    // ============ Migrations ============
+    // ============ Allowlists and denylists ============
 
-    async fn run_migrations(&self) -> Result<()> {
-        tracing::info!("Running database migrations (SQLite)...");
+    async fn add_listed_users(&self, users: &[ListedUser]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for batch in users.chunks(UPSERT_BATCH_ROWS) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO flag_user_lists (flag_id, environment_id, user_id, list, created_at) ",
+            );
+            query.push_values(batch, |mut row, user| {
+                row.push_bind(&user.flag_id)
+                    .push_bind(&user.environment_id)
+                    .push_bind(&user.user_id)
+                    .push_bind(&user.list)
+                    .push_bind(user.created_at);
+            });
+            // Users already on the list keep the time they were added
+            query.push(
+                " ON CONFLICT (flag_id, environment_id, user_id) DO UPDATE SET list = excluded.list, created_at = excluded.created_at WHERE flag_user_lists.list <> excluded.list",
+            );
+            query.build().execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
 
-        // Create users table with username-based auth
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS users (
-                id TEXT PRIMARY KEY,
-                username TEXT UNIQUE NOT NULL,
-                password_hash TEXT NOT NULL,
-                email TEXT,
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
-            )
-            "#,
+    async fn remove_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+        user_ids: &[String],
+    ) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let mut removed = 0;
+        for batch in user_ids.chunks(UPSERT_BATCH_ROWS) {
+            let mut query =
+                QueryBuilder::<Sqlite>::new("DELETE FROM flag_user_lists WHERE flag_id = ");
+            query
+                .push_bind(flag_id)
+                .push(" AND environment_id = ")
+                .push_bind(environment_id)
+                .push(" AND list = ")
+                .push_bind(list)
+                .push(" AND user_id IN (");
+            let mut ids = query.separated(", ");
+            for id in batch {
+                ids.push_bind(id);
+            }
+            ids.push_unseparated(")");
+            removed += query.build().execute(&mut *tx).await?.rows_affected();
+        }
+        tx.commit().await?;
+        Ok(removed)
+    }
+
+    async fn clear_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+        list: &str,
+    ) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM flag_user_lists WHERE flag_id = ? AND environment_id = ? AND list = ?",
         )
+        .bind(flag_id)
+        .bind(environment_id)
+        .bind(list)
         .execute(&self.pool)
         .await?;
+        Ok(result.rows_affected())
+    }
 
-        // Create api_keys table for user API keys
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS api_keys (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-                key_hash TEXT NOT NULL,
-                key_prefix TEXT NOT NULL,
-                name TEXT,
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                revoked_at TEXT
-            )
-            "#,
+    async fn list_listed_users(
+        &self,
+        flag_id: &str,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>> {
+        let users = sqlx::query_as(
+            "SELECT flag_id, environment_id, user_id, list, created_at FROM flag_user_lists WHERE flag_id = ? AND environment_id = ?",
         )
-        .execute(&self.pool)
+        .bind(flag_id)
+        .bind(environment_id)
+        .fetch_all(&self.pool)
         .await?;
+        Ok(users)
+    }
 
-        // Create projects table
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS projects (
-                id TEXT PRIMARY KEY,
-                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
-                name TEXT NOT NULL,
-                api_key TEXT UNIQUE NOT NULL,
-                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+    async fn list_listed_users_by_environment(
+        &self,
+        environment_id: &str,
+    ) -> Result<Vec<ListedUser>> {
+        let users = sqlx::query_as(
+            "SELECT flag_id, environment_id, user_id, list, created_at FROM flag_user_lists WHERE environment_id = ?",
+        )
+        .bind(environment_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(users)
+    }
+
+    // ============ Usage ============
+
+    async fn record_evaluations(&self, counts: &[EvaluationCount]) -> Result<()> {
+        // Counts for flags deleted since they were recorded are dropped
+        for c in counts {
+            sqlx::query(
+                r#"
+                INSERT INTO flag_evaluations (flag_id, environment_id, day, count, last_evaluated_at)
+                SELECT ?, ?, ?, ?, ? WHERE EXISTS (SELECT 1 FROM flags WHERE id = ?)
+                ON CONFLICT (flag_id, environment_id, day) DO UPDATE SET
+                count = count + excluded.count,
+                last_evaluated_at = MAX(last_evaluated_at, excluded.last_evaluated_at)
+                "#,
             )
-            "#,
+            .bind(&c.flag_id)
+            .bind(&c.environment_id)
+            .bind(c.day)
+            .bind(c.count)
+            .bind(c.last_evaluated_at)
+            .bind(&c.flag_id)
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn list_evaluation_counts(&self, flag_ids: &[String]) -> Result<Vec<EvaluationCount>> {
+        if flag_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let placeholders = flag_ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+        let query_str = format!(
+            "SELECT flag_id, environment_id, day, count, last_evaluated_at FROM flag_evaluations WHERE flag_id IN ({placeholders}) ORDER BY day DESC",
+        );
+
+        let mut query = sqlx::query_as(&query_str);
+        for id in flag_ids {
+            query = query.bind(id);
+        }
+
+        let counts = query.fetch_all(&self.pool).await?;
+        Ok(counts)
+    }
+
+    // ============ Quotas ============
+
+    async fn get_project_quota(&self, project_id: &str) -> Result<Option<ProjectQuota>> {
+        let quota = sqlx::query_as(
+            "SELECT project_id, requests_per_minute, per_key_requests_per_minute, updated_at FROM project_quotas WHERE project_id = ?",
         )
-        .execute(&self.pool)
+        .bind(project_id)
+        .fetch_optional(&self.pool)
         .await?;
+        Ok(quota)
+    }
 
-        // Create environments table
+    async fn set_project_quota(&self, quota: &ProjectQuota) -> Result<()> {
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS environments (
-                id TEXT PRIMARY KEY,
-                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
-                name TEXT NOT NULL,
-                api_key TEXT UNIQUE NOT NULL,
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                UNIQUE(project_id, name)
-            )
+            INSERT INTO project_quotas (project_id, requests_per_minute, per_key_requests_per_minute, updated_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (project_id) DO UPDATE SET
+            requests_per_minute = excluded.requests_per_minute,
+            per_key_requests_per_minute = excluded.per_key_requests_per_minute,
+            updated_at = excluded.updated_at
             "#,
         )
+        .bind(&quota.project_id)
+        .bind(quota.requests_per_minute)
+        .bind(quota.per_key_requests_per_minute)
+        .bind(quota.updated_at)
         .execute(&self.pool)
         .await?;
+        Ok(())
+    }
 
-        // Create flags table
+    // ============ Flag Templates ============
+
+    async fn list_flag_templates(&self, project_id: &str) -> Result<Vec<FlagTemplate>> {
+        let templates = sqlx::query_as(
+            "SELECT project_id, name, description, default_enabled, default_rollout, environments, updated_at FROM flag_templates WHERE project_id = ? ORDER BY name",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(templates)
+    }
+
+    async fn get_flag_template(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<Option<FlagTemplate>> {
+        let template = sqlx::query_as(
+            "SELECT project_id, name, description, default_enabled, default_rollout, environments, updated_at FROM flag_templates WHERE project_id = ? AND name = ?",
+        )
+        .bind(project_id)
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(template)
+    }
+
+    async fn set_flag_template(&self, template: &FlagTemplate) -> Result<()> {
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS flags (
-                id TEXT PRIMARY KEY,
-                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
-                key TEXT NOT NULL,
-                name TEXT NOT NULL,
-                description TEXT,
-                created_at TEXT NOT NULL DEFAULT (datetime('now')),
-                UNIQUE(project_id, key)
-            )
+            INSERT INTO flag_templates (project_id, name, description, default_enabled, default_rollout, environments, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (project_id, name) DO UPDATE SET
+            description = excluded.description,
+            default_enabled = excluded.default_enabled,
+            default_rollout = excluded.default_rollout,
+            environments = excluded.environments,
+            updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(&template.project_id)
+        .bind(&template.name)
+        .bind(&template.description)
+        .bind(template.default_enabled)
+        .bind(template.default_rollout)
+        .bind(&template.environments)
+        .bind(template.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_flag_template(&self, project_id: &str, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM flag_templates WHERE project_id = ? AND name = ?")
+            .bind(project_id)
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ============ Flag Changes ============
+
+    async fn record_flag_changes(
+        &self,
+        project_id: &str,
+        keys: &[String],
+        deleted: bool,
+    ) -> Result<i64> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+        // The counter row stays locked until commit, so revisions become
+        // visible in order
+        let (revision,): (i64,) = sqlx::query_as(
+            "INSERT INTO flag_revisions (project_id, revision) VALUES (?, 1) ON CONFLICT (project_id) DO UPDATE SET revision = flag_revisions.revision + 1 RETURNING revision",
+        )
+        .bind(project_id)
+        .fetch_one(&mut *tx)
+        .await?;
+        for key in keys {
+            sqlx::query(
+                r#"
+                INSERT INTO flag_changes (project_id, flag_key, revision, deleted, changed_at)
+                VALUES (?, ?, ?, ?, ?)
+                ON CONFLICT (project_id, flag_key) DO UPDATE SET
+                revision = excluded.revision,
+                deleted = excluded.deleted,
+                changed_at = excluded.changed_at
+                "#,
+            )
+            .bind(project_id)
+            .bind(key)
+            .bind(revision)
+            .bind(deleted)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(revision)
+    }
+
+    async fn get_flag_revision(&self, project_id: &str) -> Result<i64> {
+        let revision: Option<(i64,)> =
+            sqlx::query_as("SELECT revision FROM flag_revisions WHERE project_id = ?")
+                .bind(project_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(revision.map(|(r,)| r).unwrap_or(0))
+    }
+
+    async fn list_flag_changes(&self, project_id: &str, since: i64) -> Result<Vec<FlagChange>> {
+        let changes = sqlx::query_as(
+            "SELECT project_id, flag_key, revision, deleted, changed_at FROM flag_changes WHERE project_id = ? AND revision > ? ORDER BY revision",
+        )
+        .bind(project_id)
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(changes)
+    }
+
+    // ============ Signing Keys ============
+
+    async fn create_signing_key(&self, key: &SigningKey) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO signing_keys (id, environment_id, private_key, public_key, created_at, retired_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&key.id)
+        .bind(&key.environment_id)
+        .bind(&key.private_key)
+        .bind(&key.public_key)
+        .bind(key.created_at)
+        .bind(key.retired_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_signing_keys(&self, environment_id: &str) -> Result<Vec<SigningKey>> {
+        let keys = sqlx::query_as(
+            "SELECT id, environment_id, private_key, public_key, created_at, retired_at FROM signing_keys WHERE environment_id = ? ORDER BY created_at DESC",
+        )
+        .bind(environment_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(keys)
+    }
+
+    async fn retire_signing_keys(&self, environment_id: &str, at: DateTime<Utc>) -> Result<()> {
+        sqlx::query(
+            "UPDATE signing_keys SET retired_at = ? WHERE environment_id = ? AND retired_at IS NULL",
+        )
+        .bind(at)
+        .bind(environment_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // ============ Change Requests ============
+
+    async fn create_change_request(&self, change: &ChangeRequest) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO change_requests (id, project_id, flag_key, environment, enabled, rollout_percentage, base_version, comment, status, proposed_by, reviewed_by, review_comment, created_at, reviewed_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&change.id)
+        .bind(&change.project_id)
+        .bind(&change.flag_key)
+        .bind(&change.environment)
+        .bind(change.enabled)
+        .bind(change.rollout_percentage)
+        .bind(change.base_version)
+        .bind(&change.comment)
+        .bind(&change.status)
+        .bind(&change.proposed_by)
+        .bind(&change.reviewed_by)
+        .bind(&change.review_comment)
+        .bind(change.created_at)
+        .bind(change.reviewed_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_change_request(&self, id: &str) -> Result<Option<ChangeRequest>> {
+        let change = sqlx::query_as(
+            "SELECT id, project_id, flag_key, environment, enabled, rollout_percentage, base_version, comment, status, proposed_by, reviewed_by, review_comment, created_at, reviewed_at FROM change_requests WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(change)
+    }
+
+    async fn list_change_requests(
+        &self,
+        project_id: &str,
+        status: Option<&str>,
+    ) -> Result<Vec<ChangeRequest>> {
+        let changes = sqlx::query_as(
+            "SELECT id, project_id, flag_key, environment, enabled, rollout_percentage, base_version, comment, status, proposed_by, reviewed_by, review_comment, created_at, reviewed_at FROM change_requests WHERE project_id = ? AND (? IS NULL OR status = ?) ORDER BY created_at DESC",
+        )
+        .bind(project_id)
+        .bind(status)
+        .bind(status)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(changes)
+    }
+
+    async fn review_change_request(&self, change: &ChangeRequest) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE change_requests SET status = ?, reviewed_by = ?, review_comment = ?, reviewed_at = ? WHERE id = ? AND status = 'pending'",
+        )
+        .bind(&change.status)
+        .bind(&change.reviewed_by)
+        .bind(&change.review_comment)
+        .bind(change.reviewed_at)
+        .bind(&change.id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn reopen_change_request(&self, id: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE change_requests SET status = 'pending', reviewed_by = NULL, review_comment = NULL, reviewed_at = NULL WHERE id = ? AND status = 'approved'",
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // ============ Ramps ============
+
+    async fn create_ramp(&self, ramp: &Ramp) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO ramps (id, project_id, flag_id, environment_id, target_percentage, step_percentage, interval_secs, current_percentage, status, next_step_at, reason, created_by, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&ramp.id)
+        .bind(&ramp.project_id)
+        .bind(&ramp.flag_id)
+        .bind(&ramp.environment_id)
+        .bind(ramp.target_percentage)
+        .bind(ramp.step_percentage)
+        .bind(ramp.interval_secs)
+        .bind(ramp.current_percentage)
+        .bind(&ramp.status)
+        .bind(ramp.next_step_at)
+        .bind(&ramp.reason)
+        .bind(&ramp.created_by)
+        .bind(ramp.created_at)
+        .bind(ramp.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_ramp(&self, id: &str) -> Result<Option<Ramp>> {
+        let ramp = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, target_percentage, step_percentage, interval_secs, current_percentage, status, next_step_at, reason, created_by, created_at, updated_at FROM ramps WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(ramp)
+    }
+
+    async fn list_ramps(&self, project_id: &str) -> Result<Vec<Ramp>> {
+        let ramps = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, target_percentage, step_percentage, interval_secs, current_percentage, status, next_step_at, reason, created_by, created_at, updated_at FROM ramps WHERE project_id = ? ORDER BY created_at DESC",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ramps)
+    }
+
+    async fn list_due_ramps(&self, now: DateTime<Utc>) -> Result<Vec<Ramp>> {
+        let ramps = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, target_percentage, step_percentage, interval_secs, current_percentage, status, next_step_at, reason, created_by, created_at, updated_at FROM ramps WHERE status = 'active' AND next_step_at <= ? ORDER BY next_step_at",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(ramps)
+    }
+
+    async fn update_ramp(&self, ramp: &Ramp, from_status: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE ramps SET current_percentage = ?, status = ?, next_step_at = ?, updated_at = ? WHERE id = ? AND status = ?",
+        )
+        .bind(ramp.current_percentage)
+        .bind(&ramp.status)
+        .bind(ramp.next_step_at)
+        .bind(ramp.updated_at)
+        .bind(&ramp.id)
+        .bind(from_status)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ============ Guards ============
+
+    async fn create_guard(&self, guard: &Guard) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO guards (id, project_id, flag_id, environment_id, ramp_id, url, token_hash, version, revert_enabled, revert_rollout_percentage, revert_value, window_secs, status, message, created_by, created_at, ended_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&guard.id)
+        .bind(&guard.project_id)
+        .bind(&guard.flag_id)
+        .bind(&guard.environment_id)
+        .bind(&guard.ramp_id)
+        .bind(&guard.url)
+        .bind(&guard.token_hash)
+        .bind(guard.version)
+        .bind(guard.revert_enabled)
+        .bind(guard.revert_rollout_percentage)
+        .bind(&guard.revert_value)
+        .bind(guard.window_secs)
+        .bind(&guard.status)
+        .bind(&guard.message)
+        .bind(&guard.created_by)
+        .bind(guard.created_at)
+        .bind(guard.ended_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_guard(&self, id: &str) -> Result<Option<Guard>> {
+        let guard = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, ramp_id, url, token_hash, version, revert_enabled, revert_rollout_percentage, revert_value, window_secs, status, message, created_by, created_at, ended_at FROM guards WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(guard)
+    }
+
+    async fn get_guard_by_token_hash(&self, token_hash: &str) -> Result<Option<Guard>> {
+        let guard = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, ramp_id, url, token_hash, version, revert_enabled, revert_rollout_percentage, revert_value, window_secs, status, message, created_by, created_at, ended_at FROM guards WHERE token_hash = ?",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(guard)
+    }
+
+    async fn list_guards(&self, project_id: &str) -> Result<Vec<Guard>> {
+        let guards = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, ramp_id, url, token_hash, version, revert_enabled, revert_rollout_percentage, revert_value, window_secs, status, message, created_by, created_at, ended_at FROM guards WHERE project_id = ? ORDER BY created_at DESC",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(guards)
+    }
+
+    async fn list_watching_guards(&self) -> Result<Vec<Guard>> {
+        let guards = sqlx::query_as(
+            "SELECT id, project_id, flag_id, environment_id, ramp_id, url, token_hash, version, revert_enabled, revert_rollout_percentage, revert_value, window_secs, status, message, created_by, created_at, ended_at FROM guards WHERE status = 'watching' ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(guards)
+    }
+
+    async fn end_guard(&self, guard: &Guard) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE guards SET status = ?, message = ?, ended_at = ? WHERE id = ? AND status = 'watching'",
+        )
+        .bind(&guard.status)
+        .bind(&guard.message)
+        .bind(guard.ended_at)
+        .bind(&guard.id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ============ Project sharing ============
+
+    async fn set_project_member(&self, member: &ProjectMember) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO project_members (project_id, user_id, role, created_at) VALUES (?, ?, ?, ?) ON CONFLICT (project_id, user_id) DO UPDATE SET role = excluded.role",
+        )
+        .bind(&member.project_id)
+        .bind(&member.user_id)
+        .bind(&member.role)
+        .bind(member.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_project_member(
+        &self,
+        project_id: &str,
+        user_id: &str,
+    ) -> Result<Option<ProjectMember>> {
+        let member = sqlx::query_as(
+            "SELECT project_id, user_id, role, created_at FROM project_members WHERE project_id = ? AND user_id = ?",
+        )
+        .bind(project_id)
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(member)
+    }
+
+    async fn list_project_members(&self, project_id: &str) -> Result<Vec<ProjectMember>> {
+        let members = sqlx::query_as(
+            "SELECT project_id, user_id, role, created_at FROM project_members WHERE project_id = ? ORDER BY created_at",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(members)
+    }
+
+    async fn remove_project_member(&self, project_id: &str, user_id: &str) -> Result<bool> {
+        let result =
+            sqlx::query("DELETE FROM project_members WHERE project_id = ? AND user_id = ?")
+                .bind(project_id)
+                .bind(user_id)
+                .execute(&self.pool)
+                .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_projects_shared_with(&self, user_id: &str) -> Result<Vec<Project>> {
+        let projects = sqlx::query_as(
+            "SELECT p.id, p.user_id, p.org_id, p.name, p.slug, p.api_key, p.created_at, p.require_change_reason FROM projects p JOIN project_members m ON m.project_id = p.id WHERE m.user_id = ? ORDER BY p.created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(projects)
+    }
+
+    async fn create_invite_link(&self, link: &InviteLink) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO invite_links (id, project_id, token_hash, role, uses, created_by, created_at, expires_at, revoked_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&link.id)
+        .bind(&link.project_id)
+        .bind(&link.token_hash)
+        .bind(&link.role)
+        .bind(link.uses)
+        .bind(&link.created_by)
+        .bind(link.created_at)
+        .bind(link.expires_at)
+        .bind(link.revoked_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_invite_link(&self, id: &str) -> Result<Option<InviteLink>> {
+        let link = sqlx::query_as(
+            "SELECT id, project_id, token_hash, role, uses, created_by, created_at, expires_at, revoked_at FROM invite_links WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(link)
+    }
+
+    async fn get_invite_link_by_token_hash(&self, token_hash: &str) -> Result<Option<InviteLink>> {
+        let link = sqlx::query_as(
+            "SELECT id, project_id, token_hash, role, uses, created_by, created_at, expires_at, revoked_at FROM invite_links WHERE token_hash = ?",
+        )
+        .bind(token_hash)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(link)
+    }
+
+    async fn list_invite_links(&self, project_id: &str) -> Result<Vec<InviteLink>> {
+        let links = sqlx::query_as(
+            "SELECT id, project_id, token_hash, role, uses, created_by, created_at, expires_at, revoked_at FROM invite_links WHERE project_id = ? ORDER BY created_at DESC",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(links)
+    }
+
+    async fn use_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE invite_links SET uses = uses + 1 WHERE id = ? AND revoked_at IS NULL AND expires_at > ?",
+        )
+        .bind(id)
+        .bind(now)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn revoke_invite_link(&self, id: &str, now: DateTime<Utc>) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE invite_links SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL",
+        )
+        .bind(now)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // ============ Retention ============
+
+    async fn prune_flag_value_history(&self, before: DateTime<Utc>) -> Result<u64> {
+        let result = sqlx::query(
+            "DELETE FROM flag_value_history WHERE updated_at < ? AND NOT EXISTS (SELECT 1 FROM flag_values fv WHERE fv.id = flag_value_history.id AND fv.version = flag_value_history.version)",
+        )
+        .bind(before)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn prune_evaluation_counts(&self, before: NaiveDate) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM flag_evaluations WHERE day < ?")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn vacuum(&self) -> Result<()> {
+        sqlx::query("VACUUM").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    // ============ Migrations ============
+
+    async fn run_migrations(&self) -> Result<()> {
+        tracing::info!("Running database migrations (SQLite)...");
+
+        // Create users table with username-based auth
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS users (
+                id TEXT PRIMARY KEY,
+                username TEXT UNIQUE NOT NULL,
+                password_hash TEXT NOT NULL,
+                email TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                updated_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create api_keys table for user API keys
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS api_keys (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                key_hash TEXT NOT NULL,
+                key_prefix TEXT NOT NULL,
+                name TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                revoked_at TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create invites table (SIGNUP_MODE=invite)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS invites (
+                code TEXT PRIMARY KEY,
+                created_by TEXT REFERENCES users(id) ON DELETE SET NULL,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL,
+                used_by TEXT,
+                used_at TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create account_tokens table (password reset, email verification)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS account_tokens (
+                token_hash TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                purpose TEXT NOT NULL,
+                email TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL,
+                used_at TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create projects table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS projects (
+                id TEXT PRIMARY KEY,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                api_key TEXT UNIQUE NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now'))
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create environments table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS environments (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                api_key TEXT UNIQUE NOT NULL,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                UNIQUE(project_id, name)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create flags table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flags (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                key TEXT NOT NULL,
+                name TEXT NOT NULL,
+                description TEXT,
+                created_at TEXT NOT NULL DEFAULT (datetime('now')),
+                UNIQUE(project_id, key)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create flag_values table
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_values (
+                id TEXT PRIMARY KEY,
+                flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                enabled INTEGER NOT NULL DEFAULT 0,
+                rollout_percentage INTEGER NOT NULL DEFAULT 100,
+                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
+                UNIQUE(flag_id, environment_id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create flag_value_history table (every version of every flag value)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_value_history (
+                id TEXT NOT NULL,
+                flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                enabled INTEGER NOT NULL,
+                rollout_percentage INTEGER NOT NULL,
+                value TEXT,
+                updated_at TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                PRIMARY KEY (id, version)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create flag_evaluations table (daily usage counts)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_evaluations (
+                flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                day TEXT NOT NULL,
+                count INTEGER NOT NULL DEFAULT 0,
+                last_evaluated_at TEXT NOT NULL,
+                PRIMARY KEY (flag_id, environment_id, day)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Create organizations and membership tables
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS organizations (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                personal INTEGER NOT NULL DEFAULT 0,
+                created_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS org_members (
+                org_id TEXT NOT NULL REFERENCES organizations(id) ON DELETE CASCADE,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                role TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (org_id, user_id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Defaults the CLI picks up on login
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS user_preferences (
+                user_id TEXT PRIMARY KEY REFERENCES users(id) ON DELETE CASCADE,
+                project_id TEXT,
+                environment TEXT,
+                display_name TEXT,
+                format TEXT,
+                updated_at TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Per-project request quotas (set through the admin API)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS project_quotas (
+                project_id TEXT PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+                requests_per_minute INTEGER,
+                per_key_requests_per_minute INTEGER,
+                updated_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_templates (
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                description TEXT,
+                default_enabled INTEGER NOT NULL,
+                default_rollout INTEGER NOT NULL,
+                environments TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (project_id, name)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_revisions (
+                project_id TEXT PRIMARY KEY REFERENCES projects(id) ON DELETE CASCADE,
+                revision INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_changes (
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                flag_key TEXT NOT NULL,
+                revision INTEGER NOT NULL,
+                deleted INTEGER NOT NULL,
+                changed_at TEXT NOT NULL,
+                PRIMARY KEY (project_id, flag_key)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS signing_keys (
+                id TEXT PRIMARY KEY,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                private_key TEXT NOT NULL,
+                public_key TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                retired_at TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS change_requests (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                flag_key TEXT NOT NULL,
+                environment TEXT NOT NULL,
+                enabled INTEGER,
+                rollout_percentage INTEGER,
+                base_version INTEGER NOT NULL,
+                comment TEXT,
+                status TEXT NOT NULL,
+                proposed_by TEXT NOT NULL,
+                reviewed_by TEXT,
+                review_comment TEXT,
+                created_at TEXT NOT NULL,
+                reviewed_at TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS ramps (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                target_percentage INTEGER NOT NULL,
+                step_percentage INTEGER NOT NULL,
+                interval_secs INTEGER NOT NULL,
+                current_percentage INTEGER NOT NULL,
+                status TEXT NOT NULL,
+                next_step_at TEXT,
+                reason TEXT,
+                created_by TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS guards (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
+                environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
+                ramp_id TEXT REFERENCES ramps(id) ON DELETE CASCADE,
+                url TEXT,
+                token_hash TEXT NOT NULL UNIQUE,
+                version INTEGER NOT NULL,
+                revert_enabled INTEGER NOT NULL,
+                revert_rollout_percentage INTEGER NOT NULL,
+                revert_value TEXT,
+                window_secs INTEGER NOT NULL,
+                status TEXT NOT NULL,
+                message TEXT,
+                created_by TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                ended_at TEXT
+            )
             "#,
         )
         .execute(&self.pool)
         .await?;
 
-        // Create flag_values table
         sqlx::query(
             r#"
-            CREATE TABLE IF NOT EXISTS flag_values (
-                id TEXT PRIMARY KEY,
+            CREATE TABLE IF NOT EXISTS username_changes (
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                old_username TEXT NOT NULL,
+                new_username TEXT NOT NULL,
+                changed_at TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS project_members (
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                user_id TEXT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+                role TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (project_id, user_id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS flag_user_lists (
                 flag_id TEXT NOT NULL REFERENCES flags(id) ON DELETE CASCADE,
                 environment_id TEXT NOT NULL REFERENCES environments(id) ON DELETE CASCADE,
-                enabled INTEGER NOT NULL DEFAULT 0,
-                rollout_percentage INTEGER NOT NULL DEFAULT 100,
-                updated_at TEXT NOT NULL DEFAULT (datetime('now')),
-                UNIQUE(flag_id, environment_id)
+                user_id TEXT NOT NULL,
+                list TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (flag_id, environment_id, user_id)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS invite_links (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                token_hash TEXT NOT NULL UNIQUE,
+                role TEXT NOT NULL,
+                uses INTEGER NOT NULL DEFAULT 0,
+                created_by TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL,
+                revoked_at TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Snapshots of deleted environments, kept until purge_after
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS deleted_environments (
+                id TEXT PRIMARY KEY,
+                project_id TEXT NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+                name TEXT NOT NULL,
+                environment TEXT NOT NULL,
+                flag_values TEXT NOT NULL,
+                listed_users TEXT NOT NULL,
+                deleted_by TEXT NOT NULL,
+                deleted_at TEXT NOT NULL,
+                purge_after TEXT NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Columns added after the initial schema
+        if self
+            .add_column_if_missing("environments", "protected", "INTEGER NOT NULL DEFAULT 0")
+            .await?
+        {
+            // Existing production environments start out protected
+            sqlx::query("UPDATE environments SET protected = 1 WHERE name = 'production'")
+                .execute(&self.pool)
+                .await?;
+        }
+
+        self.add_column_if_missing("flag_values", "version", "INTEGER NOT NULL DEFAULT 1")
+            .await?;
+
+        self.add_column_if_missing("api_keys", "org_id", "TEXT")
+            .await?;
+        // Keys from before scopes keep full access
+        self.add_column_if_missing("api_keys", "scope", "TEXT NOT NULL DEFAULT 'admin'")
+            .await?;
+        self.add_column_if_missing("flags", "expires_at", "TEXT")
+            .await?;
+        self.add_column_if_missing("projects", "org_id", "TEXT")
+            .await?;
+        self.add_column_if_missing("projects", "slug", "TEXT")
+            .await?;
+        self.add_column_if_missing("environments", "frozen", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        if self
+            .add_column_if_missing("environments", "sort_order", "INTEGER NOT NULL DEFAULT 0")
+            .await?
+        {
+            // Existing environments keep the development, staging, production order
+            sqlx::query(
+                "UPDATE environments SET sort_order = CASE name WHEN 'development' THEN 0 WHEN 'staging' THEN 1 WHEN 'production' THEN 2 ELSE 3 END",
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+        self.add_column_if_missing("environments", "color", "TEXT")
+            .await?;
+        self.add_column_if_missing("environments", "emoji", "TEXT")
+            .await?;
+        self.add_column_if_missing("environments", "cache_max_age", "INTEGER")
+            .await?;
+        self.add_column_if_missing("environments", "cache_stale_while_revalidate", "INTEGER")
+            .await?;
+        self.add_column_if_missing("environments", "public_client_id", "TEXT")
+            .await?;
+        self.add_column_if_missing("flags", "owner", "TEXT").await?;
+        self.add_column_if_missing("flags", "repository", "TEXT")
+            .await?;
+        self.add_column_if_missing("flags", "code_path", "TEXT")
+            .await?;
+        self.add_column_if_missing("flags", "flag_type", "TEXT NOT NULL DEFAULT 'boolean'")
+            .await?;
+        self.add_column_if_missing("flags", "json_schema", "TEXT")
+            .await?;
+        self.add_column_if_missing("flag_values", "value", "TEXT")
+            .await?;
+        self.add_column_if_missing("flags", "default_enabled", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing("flags", "default_value", "TEXT")
+            .await?;
+        self.add_column_if_missing("flags", "public", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing("users", "email_verified_at", "TEXT")
+            .await?;
+        self.add_column_if_missing(
+            "projects",
+            "require_change_reason",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+        self.add_column_if_missing("flag_values", "reason", "TEXT")
+            .await?;
+        self.add_column_if_missing("flag_value_history", "reason", "TEXT")
+            .await?;
+        self.add_column_if_missing("users", "session_epoch", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing(
+            "environments",
+            "require_approval",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+
+        // Give every user a personal org owning their projects (idempotent, so
+        // users created by an older binary are picked up on the next start)
+        sqlx::query(
+            r#"
+            INSERT INTO organizations (id, name, personal, created_at)
+            SELECT 'personal_' || u.id, u.username, 1, u.created_at
+            FROM users u
+            WHERE NOT EXISTS (SELECT 1 FROM organizations o WHERE o.id = 'personal_' || u.id)
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO org_members (org_id, user_id, role, created_at)
+            SELECT 'personal_' || u.id, u.id, 'owner', u.created_at
+            FROM users u
+            WHERE NOT EXISTS (
+                SELECT 1 FROM org_members m WHERE m.org_id = 'personal_' || u.id AND m.user_id = u.id
             )
             "#,
         )
         .execute(&self.pool)
         .await?;
+        sqlx::query("UPDATE projects SET org_id = 'personal_' || user_id WHERE org_id IS NULL")
+            .execute(&self.pool)
+            .await?;
+        self.backfill_project_slugs().await?;
 
         // Create indexes
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_users_username ON users(username)")
@@ -489,12 +2515,23 @@ impl Storage for SqliteStorage {
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_api_keys_hash ON api_keys(key_hash)")
             .execute(&self.pool)
             .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_projects_org ON projects(org_id)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_org_members_user ON org_members(user_id)")
+            .execute(&self.pool)
+            .await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_projects_user ON projects(user_id)")
             .execute(&self.pool)
             .await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_projects_api_key ON projects(api_key)")
             .execute(&self.pool)
             .await?;
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_projects_user_slug ON projects(user_id, slug)",
+        )
+        .execute(&self.pool)
+        .await?;
         sqlx::query(
             "CREATE INDEX IF NOT EXISTS idx_environments_project ON environments(project_id)",
         )
@@ -503,14 +2540,903 @@ impl Storage for SqliteStorage {
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_environments_api_key ON environments(api_key)")
             .execute(&self.pool)
             .await?;
+        sqlx::query(
+            "CREATE UNIQUE INDEX IF NOT EXISTS idx_environments_public_client_id ON environments(public_client_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_change_requests_project ON change_requests(project_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_ramps_project ON ramps(project_id)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_ramps_due ON ramps(status, next_step_at)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_guards_project ON guards(project_id)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_guards_status ON guards(status)")
+            .execute(&self.pool)
+            .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_username_changes_old ON username_changes(old_username)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_username_changes_user ON username_changes(user_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_project_members_user ON project_members(user_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_invite_links_project ON invite_links(project_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_flag_user_lists_environment ON flag_user_lists(environment_id)",
+        )
+        .execute(&self.pool)
+        .await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_flags_project ON flags(project_id)")
             .execute(&self.pool)
             .await?;
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_flag_values_flag ON flag_values(flag_id)")
             .execute(&self.pool)
             .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_flag_value_history_flag ON flag_value_history(flag_id)",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Values written before history was kept start it at their current version
+        sqlx::query(
+            "INSERT OR IGNORE INTO flag_value_history (id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason) SELECT id, flag_id, environment_id, enabled, rollout_percentage, value, updated_at, version, reason FROM flag_values",
+        )
+        .execute(&self.pool)
+        .await?;
 
         tracing::info!("Migrations completed");
         Ok(())
     }
+
+    // ============ Backups ============
+
+    async fn backup(&self, path: &Path) -> Result<()> {
+        // VACUUM INTO reads one snapshot, so concurrent writes don't tear the copy
+        sqlx::query("VACUUM INTO ?")
+            .bind(path.to_string_lossy().into_owned())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    // ============ Health ============
+
+    async fn health_check(&self) -> Result<()> {
+        sqlx::query("SELECT 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn stats(&self) -> Result<StorageStats> {
+        let (users, projects, environments, flags): (i64, i64, i64, i64) = sqlx::query_as(
+            "SELECT (SELECT COUNT(*) FROM users), (SELECT COUNT(*) FROM projects),
+                    (SELECT COUNT(*) FROM environments), (SELECT COUNT(*) FROM flags)",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        // Pages in the main file; the WAL isn't counted until it's checkpointed
+        let database_bytes: i64 = sqlx::query_scalar(
+            "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(StorageStats {
+            backend: "sqlite",
+            users,
+            projects,
+            environments,
+            flags,
+            database_bytes: Some(database_bytes),
+        })
+    }
+}
+
+/// Replace the database at `database_url` with the backup at `backup`, which
+/// is checked first. The replaced file is kept next to it with a
+/// `.pre-restore` suffix; returns its path. The server must be stopped.
+pub async fn restore(database_url: &str, backup: &Path) -> anyhow::Result<Option<PathBuf>> {
+    use anyhow::{bail, Context};
+
+    let options = SqliteConnectOptions::new().filename(backup).read_only(true);
+    let mut conn = SqliteConnection::connect_with(&options)
+        .await
+        .with_context(|| format!("Failed to open {}", backup.display()))?;
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_one(&mut conn)
+        .await
+        .with_context(|| format!("{} is not a SQLite database", backup.display()))?;
+    if integrity != "ok" {
+        bail!("{} is corrupt: {integrity}", backup.display());
+    }
+    let is_flaglite: bool = sqlx::query_scalar(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'flag_values'",
+    )
+    .fetch_one(&mut conn)
+    .await?;
+    if !is_flaglite {
+        bail!("{} is not a FlagLite database", backup.display());
+    }
+    conn.close().await?;
+
+    let target = SqliteConnectOptions::from_str(database_url)?
+        .get_filename()
+        .to_path_buf();
+    if target.as_os_str().is_empty() || target == Path::new(":memory:") {
+        bail!("DATABASE_URL doesn't name a database file");
+    }
+
+    // Copy next to the target first, so the final rename is atomic
+    let staged = with_suffix(&target, ".restoring");
+    std::fs::copy(backup, &staged)
+        .with_context(|| format!("Failed to copy the backup to {}", staged.display()))?;
+
+    let previous = if target.exists() {
+        let previous = with_suffix(&target, ".pre-restore");
+        std::fs::rename(&target, &previous)?;
+        Some(previous)
+    } else {
+        None
+    };
+    // The old WAL must not be replayed onto the restored file
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(with_suffix(&target, suffix));
+    }
+    std::fs::rename(&staged, &target)?;
+
+    Ok(previous)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::AppError;
+    use crate::models::{LIST_ALLOW, LIST_DENY};
+
+    async fn storage() -> SqliteStorage {
+        // One connection, since each opens its own in-memory database
+        let storage = SqliteStorage::new("sqlite::memory:", 1).await.unwrap();
+        storage.run_migrations().await.unwrap();
+        storage
+    }
+
+    fn user(id: &str, username: &str) -> User {
+        let now = Utc::now();
+        User {
+            id: id.to_string(),
+            username: username.to_string(),
+            password_hash: "hash".to_string(),
+            email: None,
+            email_verified_at: None,
+            session_epoch: 0,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn flag(id: &str, project_id: &str, key: &str) -> Flag {
+        Flag {
+            id: id.to_string(),
+            project_id: project_id.to_string(),
+            key: key.to_string(),
+            name: key.to_string(),
+            description: None,
+            created_at: Utc::now(),
+            expires_at: None,
+            owner: None,
+            repository: None,
+            code_path: None,
+            flag_type: "boolean".to_string(),
+            json_schema: None,
+            default_enabled: false,
+            default_value: None,
+            public: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_constraint_violations_map_to_domain_errors() {
+        let storage = storage().await;
+        storage.create_user(&user("u1", "alice")).await.unwrap();
+        let result = storage.create_user(&user("u2", "alice")).await;
+        assert!(matches!(result, Err(AppError::UserAlreadyExists)));
+
+        let result = storage
+            .create_flag(&flag("f0", "missing", "dark-mode"))
+            .await;
+        assert!(matches!(result, Err(AppError::BadRequest(_))));
+
+        storage
+            .create_project(&Project {
+                id: "p1".to_string(),
+                user_id: "u1".to_string(),
+                org_id: None,
+                name: "App".to_string(),
+                slug: "app".to_string(),
+                api_key: "ffl_proj_test".to_string(),
+                created_at: Utc::now(),
+                require_change_reason: false,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_flag(&flag("f1", "p1", "dark-mode"))
+            .await
+            .unwrap();
+        let result = storage.create_flag(&flag("f2", "p1", "dark-mode")).await;
+        assert!(matches!(result, Err(AppError::DuplicateFlag(key)) if key == "dark-mode"));
+    }
+
+    #[tokio::test]
+    async fn test_create_project_with_is_all_or_nothing() {
+        let storage = storage().await;
+        storage.create_user(&user("u1", "alice")).await.unwrap();
+        let project = Project {
+            id: "p1".to_string(),
+            user_id: "u1".to_string(),
+            org_id: None,
+            name: "App".to_string(),
+            slug: "app".to_string(),
+            api_key: "ffl_proj_test".to_string(),
+            created_at: Utc::now(),
+            require_change_reason: false,
+        };
+
+        // The second flag's key is taken, so the project isn't created either
+        let flags = [flag("f1", "p1", "dark-mode"), flag("f2", "p1", "dark-mode")];
+        let result = storage
+            .create_project_with(&project, &[], &flags, &[])
+            .await;
+        assert!(matches!(result, Err(AppError::DuplicateFlag(_))));
+        assert!(storage.get_project_by_id("p1").await.unwrap().is_none());
+        assert!(storage.get_flag_by_id("f1").await.unwrap().is_none());
+
+        storage
+            .create_project_with(&project, &[], &flags[..1], &[])
+            .await
+            .unwrap();
+        assert!(storage.get_flag_by_id("f1").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_update_user_keeps_the_password() {
+        let storage = storage().await;
+        let mut stale = user("u1", "alice");
+        storage.create_user(&stale).await.unwrap();
+
+        storage.set_password("u1", "new-hash", true).await.unwrap();
+        stale.email = Some("alice@example.com".to_string());
+        storage.update_user(&stale).await.unwrap();
+
+        let saved = storage.get_user_by_id("u1").await.unwrap().unwrap();
+        assert_eq!(saved.password_hash, "new-hash");
+        assert_eq!(saved.session_epoch, 1);
+        assert_eq!(saved.email.as_deref(), Some("alice@example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_rename_user_records_the_change() {
+        let storage = storage().await;
+        storage.create_user(&user("u1", "alice")).await.unwrap();
+        storage.create_user(&user("u2", "bob")).await.unwrap();
+
+        let change = |old: &str, new: &str, days_ago: i64| UsernameChange {
+            user_id: "u1".to_string(),
+            old_username: old.to_string(),
+            new_username: new.to_string(),
+            changed_at: Utc::now() - chrono::Duration::days(days_ago),
+        };
+
+        // A taken name leaves everything as it was
+        let result = storage.rename_user(&change("alice", "bob", 0)).await;
+        assert!(matches!(result, Err(AppError::UserAlreadyExists)));
+        assert!(storage
+            .get_username_change("alice")
+            .await
+            .unwrap()
+            .is_none());
+
+        storage
+            .rename_user(&change("alice", "carol", 40))
+            .await
+            .unwrap();
+        storage
+            .rename_user(&change("carol", "alice", 0))
+            .await
+            .unwrap();
+
+        let renamed = storage.get_user_by_id("u1").await.unwrap().unwrap();
+        assert_eq!(renamed.username, "alice");
+        let changes = storage.list_username_changes("u1").await.unwrap();
+        let names: Vec<_> = changes.iter().map(|c| c.new_username.as_str()).collect();
+        assert_eq!(names, ["alice", "carol"]);
+        let redirect = storage.get_username_change("carol").await.unwrap().unwrap();
+        assert_eq!(redirect.user_id, "u1");
+    }
+
+    #[tokio::test]
+    async fn test_org_keeps_an_owner() {
+        let storage = storage().await;
+        let now = Utc::now();
+        storage.create_user(&user("u1", "alice")).await.unwrap();
+        storage.create_user(&user("u2", "bob")).await.unwrap();
+        storage
+            .create_organization(&Organization {
+                id: "o1".to_string(),
+                name: "Acme".to_string(),
+                personal: false,
+                created_at: now,
+            })
+            .await
+            .unwrap();
+        for (user_id, role) in [("u1", "owner"), ("u2", "member")] {
+            storage
+                .add_org_member(&OrgMember {
+                    org_id: "o1".to_string(),
+                    user_id: user_id.to_string(),
+                    role: role.to_string(),
+                    created_at: now,
+                })
+                .await
+                .unwrap();
+        }
+
+        assert!(!storage
+            .set_org_member_role("o1", "u1", "member")
+            .await
+            .unwrap());
+        assert!(!storage.remove_org_member("o1", "u1").await.unwrap());
+        assert!(!storage.remove_org_member("o1", "u3").await.unwrap());
+
+        assert!(storage
+            .set_org_member_role("o1", "u2", "owner")
+            .await
+            .unwrap());
+        assert!(storage
+            .set_org_member_role("o1", "u1", "member")
+            .await
+            .unwrap());
+        assert!(storage.remove_org_member("o1", "u1").await.unwrap());
+        let members = storage.list_org_members("o1").await.unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].user_id, "u2");
+        assert_eq!(members[0].role, "owner");
+    }
+
+    #[tokio::test]
+    async fn test_invite_links_and_project_members() {
+        let storage = storage().await;
+        let now = Utc::now();
+        storage.create_user(&user("u1", "alice")).await.unwrap();
+        storage.create_user(&user("u2", "bob")).await.unwrap();
+        storage
+            .create_project(&Project {
+                id: "p1".to_string(),
+                user_id: "u1".to_string(),
+                org_id: None,
+                name: "App".to_string(),
+                slug: "app".to_string(),
+                api_key: "ffl_proj_test".to_string(),
+                created_at: now,
+                require_change_reason: false,
+            })
+            .await
+            .unwrap();
+
+        let link = |id: &str, expires_in: chrono::Duration| InviteLink {
+            id: id.to_string(),
+            project_id: "p1".to_string(),
+            token_hash: format!("hash-{id}"),
+            role: "member".to_string(),
+            uses: 0,
+            created_by: "u1".to_string(),
+            created_at: now,
+            expires_at: now + expires_in,
+            revoked_at: None,
+        };
+        storage
+            .create_invite_link(&link("l1", chrono::Duration::days(1)))
+            .await
+            .unwrap();
+        storage
+            .create_invite_link(&link("l2", chrono::Duration::seconds(-1)))
+            .await
+            .unwrap();
+
+        assert!(storage.use_invite_link("l1", now).await.unwrap());
+        assert!(!storage.use_invite_link("l2", now).await.unwrap());
+        assert!(storage.revoke_invite_link("l1", now).await.unwrap());
+        assert!(!storage.revoke_invite_link("l1", now).await.unwrap());
+        assert!(!storage.use_invite_link("l1", now).await.unwrap());
+        let used = storage
+            .get_invite_link_by_token_hash("hash-l1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(used.uses, 1);
+        assert!(used.revoked_at.is_some());
+
+        let member = |role: &str| ProjectMember {
+            project_id: "p1".to_string(),
+            user_id: "u2".to_string(),
+            role: role.to_string(),
+            created_at: now,
+        };
+        storage.set_project_member(&member("member")).await.unwrap();
+        storage.set_project_member(&member("owner")).await.unwrap();
+        let members = storage.list_project_members("p1").await.unwrap();
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].role, "owner");
+        let shared = storage.list_projects_shared_with("u2").await.unwrap();
+        assert_eq!(shared.len(), 1);
+        assert_eq!(shared[0].id, "p1");
+
+        assert!(storage.remove_project_member("p1", "u2").await.unwrap());
+        assert!(!storage.remove_project_member("p1", "u2").await.unwrap());
+        assert!(storage
+            .list_projects_shared_with("u2")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_project_summary_counts_by_environment() {
+        let storage = storage().await;
+        let now = Utc::now();
+        storage.create_user(&user("u1", "alice")).await.unwrap();
+        storage
+            .create_project(&Project {
+                id: "p1".to_string(),
+                user_id: "u1".to_string(),
+                org_id: None,
+                name: "App".to_string(),
+                slug: "app".to_string(),
+                api_key: "ffl_proj_test".to_string(),
+                created_at: now,
+                require_change_reason: false,
+            })
+            .await
+            .unwrap();
+        for (i, name) in ["development", "production"].into_iter().enumerate() {
+            storage
+                .create_environment(&Environment {
+                    id: format!("e{i}"),
+                    project_id: "p1".to_string(),
+                    name: name.to_string(),
+                    api_key: format!("ffl_env_{name}"),
+                    protected: false,
+                    frozen: false,
+                    sort_order: i as i32,
+                    color: None,
+                    emoji: None,
+                    cache_max_age: None,
+                    cache_stale_while_revalidate: None,
+                    public_client_id: None,
+                    require_approval: false,
+                    created_at: now,
+                })
+                .await
+                .unwrap();
+        }
+
+        // On by default everywhere
+        let mut on = flag("f1", "p1", "on");
+        on.default_enabled = true;
+        storage.create_flag(&on).await.unwrap();
+        // On for half the users in production only
+        storage
+            .create_flag(&flag("f2", "p1", "rollout"))
+            .await
+            .unwrap();
+        storage
+            .create_flag_value(&FlagValue {
+                id: "v1".to_string(),
+                flag_id: "f2".to_string(),
+                environment_id: "e1".to_string(),
+                enabled: true,
+                rollout_percentage: 50,
+                value: None,
+                updated_at: now,
+                version: 1,
+                reason: None,
+            })
+            .await
+            .unwrap();
+        // Expired, so off despite its default
+        let mut expired = flag("f3", "p1", "expired");
+        expired.default_enabled = true;
+        expired.expires_at = Some(now - chrono::Duration::hours(1));
+        storage.create_flag(&expired).await.unwrap();
+        storage
+            .record_flag_changes("p1", &["rollout".to_string()], false)
+            .await
+            .unwrap();
+
+        let summary = storage.project_summary("p1", now).await.unwrap();
+        assert_eq!(summary.flags, 3);
+        assert!(summary.last_changed_at.is_some());
+
+        let counts: Vec<_> = summary
+            .environments
+            .iter()
+            .map(|e| {
+                (
+                    e.name.as_str(),
+                    e.enabled,
+                    e.partial_rollout,
+                    e.last_changed_at,
+                )
+            })
+            .collect();
+        assert_eq!(
+            counts,
+            vec![("development", 1, 0, None), ("production", 2, 1, Some(now))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_history_pages_seek_by_version() {
+        let storage = storage().await;
+        let now = Utc::now();
+        storage.create_user(&user("u1", "alice")).await.unwrap();
+        storage
+            .create_project(&Project {
+                id: "p1".to_string(),
+                user_id: "u1".to_string(),
+                org_id: None,
+                name: "App".to_string(),
+                slug: "app".to_string(),
+                api_key: "ffl_proj_test".to_string(),
+                created_at: now,
+                require_change_reason: false,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_environment(&Environment {
+                id: "e1".to_string(),
+                project_id: "p1".to_string(),
+                name: "production".to_string(),
+                api_key: "ffl_env_production".to_string(),
+                protected: false,
+                frozen: false,
+                sort_order: 0,
+                color: None,
+                emoji: None,
+                cache_max_age: None,
+                cache_stale_while_revalidate: None,
+                public_client_id: None,
+                require_approval: false,
+                created_at: now,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_flag(&flag("f1", "p1", "dark-mode"))
+            .await
+            .unwrap();
+
+        let mut value = FlagValue {
+            id: "v1".to_string(),
+            flag_id: "f1".to_string(),
+            environment_id: "e1".to_string(),
+            enabled: true,
+            rollout_percentage: 100,
+            value: None,
+            updated_at: now,
+            version: 1,
+            reason: None,
+        };
+        storage.create_flag_value(&value).await.unwrap();
+        for _ in 0..4 {
+            value.version += 1;
+            value.enabled = !value.enabled;
+            assert!(storage.update_flag_value(&value).await.unwrap());
+        }
+
+        let versions = |page: Vec<FlagValue>| page.iter().map(|fv| fv.version).collect::<Vec<_>>();
+        let page = |before| storage.list_flag_value_history_page("v1", before, 2);
+        assert_eq!(versions(page(None).await.unwrap()), vec![5, 4]);
+        assert_eq!(versions(page(Some(4)).await.unwrap()), vec![3, 2]);
+        assert_eq!(versions(page(Some(2)).await.unwrap()), vec![1]);
+        assert!(page(Some(1)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_deleted_environment_restores_and_purges() {
+        let storage = storage().await;
+        let now = Utc::now();
+        storage.create_user(&user("u1", "alice")).await.unwrap();
+        storage
+            .create_project(&Project {
+                id: "p1".to_string(),
+                user_id: "u1".to_string(),
+                org_id: None,
+                name: "App".to_string(),
+                slug: "app".to_string(),
+                api_key: "ffl_proj_test".to_string(),
+                created_at: now,
+                require_change_reason: false,
+            })
+            .await
+            .unwrap();
+        let env = Environment {
+            id: "e1".to_string(),
+            project_id: "p1".to_string(),
+            name: "qa".to_string(),
+            api_key: "ffl_env_qa".to_string(),
+            protected: false,
+            frozen: false,
+            sort_order: 0,
+            color: None,
+            emoji: None,
+            cache_max_age: None,
+            cache_stale_while_revalidate: None,
+            public_client_id: None,
+            require_approval: false,
+            created_at: now,
+        };
+        storage.create_environment(&env).await.unwrap();
+        storage
+            .create_flag(&flag("f1", "p1", "dark-mode"))
+            .await
+            .unwrap();
+        let value = FlagValue {
+            id: "v1".to_string(),
+            flag_id: "f1".to_string(),
+            environment_id: "e1".to_string(),
+            enabled: true,
+            rollout_percentage: 40,
+            value: None,
+            updated_at: now,
+            version: 1,
+            reason: None,
+        };
+        storage.create_flag_value(&value).await.unwrap();
+
+        let deleted = DeletedEnvironment {
+            id: "e1".to_string(),
+            project_id: "p1".to_string(),
+            name: "qa".to_string(),
+            environment: "{}".to_string(),
+            flag_values: "[]".to_string(),
+            listed_users: "[]".to_string(),
+            deleted_by: "alice".to_string(),
+            deleted_at: now,
+            purge_after: now + chrono::Duration::days(30),
+        };
+        storage.delete_environment(&env, &deleted).await.unwrap();
+        assert!(storage.get_environment_by_id("e1").await.unwrap().is_none());
+        assert!(storage.get_flag_value("f1", "e1").await.unwrap().is_none());
+        assert_eq!(
+            storage.list_deleted_environments("p1").await.unwrap().len(),
+            1
+        );
+
+        // Not yet due
+        assert_eq!(storage.purge_deleted_environments(now).await.unwrap(), 0);
+        assert!(storage
+            .restore_environment(&env, &[value], &[])
+            .await
+            .unwrap());
+        let restored = storage.get_flag_value("f1", "e1").await.unwrap().unwrap();
+        assert_eq!(restored.rollout_percentage, 40);
+        assert_eq!(
+            storage.list_flag_value_history("v1").await.unwrap().len(),
+            1
+        );
+        // The archive went with the restore
+        assert!(!storage.restore_environment(&env, &[], &[]).await.unwrap());
+
+        storage.delete_environment(&env, &deleted).await.unwrap();
+        let later = deleted.purge_after;
+        assert_eq!(storage.purge_deleted_environments(later).await.unwrap(), 1);
+        assert!(storage
+            .list_deleted_environments("p1")
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_upsert_flag_values_bumps_existing_rows() {
+        let storage = storage().await;
+        let now = Utc::now();
+        storage.create_user(&user("u1", "alice")).await.unwrap();
+        storage
+            .create_project(&Project {
+                id: "p1".to_string(),
+                user_id: "u1".to_string(),
+                org_id: None,
+                name: "App".to_string(),
+                slug: "app".to_string(),
+                api_key: "ffl_proj_test".to_string(),
+                created_at: now,
+                require_change_reason: false,
+            })
+            .await
+            .unwrap();
+        for (id, name) in [("e1", "production"), ("e2", "staging")] {
+            storage
+                .create_environment(&Environment {
+                    id: id.to_string(),
+                    project_id: "p1".to_string(),
+                    name: name.to_string(),
+                    api_key: format!("ffl_env_{name}"),
+                    protected: false,
+                    frozen: false,
+                    sort_order: 0,
+                    color: None,
+                    emoji: None,
+                    cache_max_age: None,
+                    cache_stale_while_revalidate: None,
+                    public_client_id: None,
+                    require_approval: false,
+                    created_at: now,
+                })
+                .await
+                .unwrap();
+        }
+        storage
+            .create_flag(&flag("f1", "p1", "dark-mode"))
+            .await
+            .unwrap();
+
+        let value = |id: &str, environment_id: &str, enabled| FlagValue {
+            id: id.to_string(),
+            flag_id: "f1".to_string(),
+            environment_id: environment_id.to_string(),
+            enabled,
+            rollout_percentage: 100,
+            value: None,
+            updated_at: now,
+            version: 1,
+            reason: None,
+        };
+        storage
+            .upsert_flag_values(&[value("v1", "e1", false), value("v2", "e2", false)])
+            .await
+            .unwrap();
+        storage
+            .upsert_flag_values(&[value("v3", "e1", true)])
+            .await
+            .unwrap();
+
+        let mut values = storage
+            .list_flag_values_by_flag_ids(&["f1".to_string()])
+            .await
+            .unwrap();
+        values.sort_by(|a, b| a.id.cmp(&b.id));
+        let states: Vec<_> = values
+            .iter()
+            .map(|fv| (fv.id.as_str(), fv.enabled, fv.version))
+            .collect();
+        assert_eq!(states, vec![("v1", true, 2), ("v2", false, 1)]);
+
+        let history = storage.list_flag_value_history("v1").await.unwrap();
+        let versions: Vec<_> = history.iter().map(|fv| (fv.version, fv.enabled)).collect();
+        assert_eq!(versions, vec![(2, true), (1, false)]);
+    }
+
+    #[tokio::test]
+    async fn test_listed_users_move_between_lists() {
+        let storage = storage().await;
+        let now = Utc::now();
+        storage.create_user(&user("u1", "alice")).await.unwrap();
+        storage
+            .create_project(&Project {
+                id: "p1".to_string(),
+                user_id: "u1".to_string(),
+                org_id: None,
+                name: "App".to_string(),
+                slug: "app".to_string(),
+                api_key: "ffl_proj_test".to_string(),
+                created_at: now,
+                require_change_reason: false,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_environment(&Environment {
+                id: "e1".to_string(),
+                project_id: "p1".to_string(),
+                name: "production".to_string(),
+                api_key: "ffl_env_production".to_string(),
+                protected: false,
+                frozen: false,
+                sort_order: 0,
+                color: None,
+                emoji: None,
+                cache_max_age: None,
+                cache_stale_while_revalidate: None,
+                public_client_id: None,
+                require_approval: false,
+                created_at: now,
+            })
+            .await
+            .unwrap();
+        storage
+            .create_flag(&flag("f1", "p1", "dark-mode"))
+            .await
+            .unwrap();
+
+        let listed = |user_id: &str, list: &str| ListedUser {
+            flag_id: "f1".to_string(),
+            environment_id: "e1".to_string(),
+            user_id: user_id.to_string(),
+            list: list.to_string(),
+            created_at: now,
+        };
+        storage
+            .add_listed_users(&[listed("a", LIST_ALLOW), listed("b", LIST_ALLOW)])
+            .await
+            .unwrap();
+        // Denylisting an allowlisted user moves them
+        storage
+            .add_listed_users(&[listed("b", LIST_DENY), listed("c", LIST_DENY)])
+            .await
+            .unwrap();
+
+        let lists = |users: Vec<ListedUser>| {
+            let mut users: Vec<_> = users.into_iter().map(|u| (u.user_id, u.list)).collect();
+            users.sort();
+            users
+        };
+        assert_eq!(
+            lists(storage.list_listed_users("f1", "e1").await.unwrap()),
+            vec![
+                ("a".to_string(), LIST_ALLOW.to_string()),
+                ("b".to_string(), LIST_DENY.to_string()),
+                ("c".to_string(), LIST_DENY.to_string()),
+            ]
+        );
+
+        // Removing only touches the named list
+        let removed = storage
+            .remove_listed_users("f1", "e1", LIST_ALLOW, &["b".to_string(), "a".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(removed, 1);
+        assert_eq!(
+            storage
+                .clear_listed_users("f1", "e1", LIST_DENY)
+                .await
+                .unwrap(),
+            2
+        );
+        assert!(storage
+            .list_listed_users_by_environment("e1")
+            .await
+            .unwrap()
+            .is_empty());
+    }
 }
@@ -0,0 +1,73 @@
+//! "Did you mean" suggestions for mistyped flag keys
+
+/// Most suggestions returned for one key
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Keys close to `key`, closest first
+///
+/// A candidate is close if one contains the other or it is within a few
+/// single-character edits (about one per three characters, at least two).
+pub fn similar_keys<'a>(key: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let key = key.to_lowercase();
+    let max_distance = (key.chars().count() / 3).max(2);
+
+    let mut close: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let lower = candidate.to_lowercase();
+            if lower == key {
+                return None;
+            }
+            let distance = edit_distance(&key, &lower);
+            let contains = lower.contains(&key) || key.contains(&lower);
+            (distance <= max_distance || contains).then_some((distance, candidate))
+        })
+        .collect();
+
+    close.sort();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similar_keys() {
+        let keys = ["dark-mode", "dark-mode-v2", "new-checkout", "beta-banner"];
+
+        assert_eq!(similar_keys("drak-mode", keys), vec!["dark-mode"]);
+        assert_eq!(
+            similar_keys("dark", keys),
+            vec!["dark-mode", "dark-mode-v2"]
+        );
+        assert_eq!(similar_keys("checkout", keys), vec!["new-checkout"]);
+        assert!(similar_keys("pricing", keys).is_empty());
+    }
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+}
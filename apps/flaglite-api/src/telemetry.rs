@@ -0,0 +1,131 @@
+//! Logging and distributed tracing
+//!
+//! Each request runs in a span whose parent is taken from the caller's W3C
+//! `traceparent` header, so evaluations join the caller's trace. Spans are
+//! exported over OTLP/HTTP when `OTEL_EXPORTER_OTLP_ENDPOINT` is set; the
+//! exporter reads the standard `OTEL_EXPORTER_OTLP_*` variables itself.
+
+use axum::http::{HeaderMap, Request};
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{global, Context, KeyValue};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::TracerProvider;
+use opentelemetry_sdk::{runtime, Resource};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+
+const DEFAULT_SERVICE_NAME: &str = "flaglite-api";
+
+/// Flushes exported spans when dropped
+pub struct Telemetry {
+    provider: Option<TracerProvider>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Some(provider) = self.provider.take() {
+            if let Err(e) = provider.shutdown() {
+                eprintln!("Failed to flush traces: {e}");
+            }
+        }
+    }
+}
+
+/// Install the log subscriber, the trace context propagator and, if
+/// configured, the OTLP exporter
+pub fn init() -> anyhow::Result<Telemetry> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let provider = if otlp_configured() {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .build()?;
+        let service_name =
+            std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| DEFAULT_SERVICE_NAME.to_string());
+        Some(
+            TracerProvider::builder()
+                .with_batch_exporter(exporter, runtime::Tokio)
+                .with_resource(Resource::new([KeyValue::new("service.name", service_name)]))
+                .build(),
+        )
+    } else {
+        None
+    };
+
+    let otel = provider.as_ref().map(|provider| {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer(DEFAULT_SERVICE_NAME))
+    });
+
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| "flaglite=debug,tower_http=debug".into()),
+        )
+        .with(tracing_subscriber::fmt::layer())
+        .with(otel)
+        .init();
+
+    Ok(Telemetry { provider })
+}
+
+fn otlp_configured() -> bool {
+    [
+        "OTEL_EXPORTER_OTLP_ENDPOINT",
+        "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+    ]
+    .iter()
+    .any(|var| std::env::var(var).is_ok_and(|v| !v.is_empty()))
+}
+
+/// Span for one HTTP request, continuing the caller's trace if it sent a
+/// `traceparent` header
+pub fn make_span<B>(request: &Request<B>) -> Span {
+    let span = tracing::info_span!(
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        otel.name = %format!("{} {}", request.method(), request.uri().path()),
+        otel.kind = "server",
+    );
+    span.set_parent(remote_parent(request.headers()));
+    span
+}
+
+/// Trace context sent by the caller, or an empty context
+fn remote_parent(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TraceContextExt;
+
+    #[test]
+    fn test_traceparent_is_extracted() {
+        global::set_text_map_propagator(TraceContextPropagator::new());
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+
+        let parent = remote_parent(&headers);
+        let span_context = parent.span().span_context().clone();
+        assert!(span_context.is_remote());
+        assert_eq!(
+            span_context.trace_id().to_string(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+
+        assert!(!remote_parent(&HeaderMap::new())
+            .span()
+            .span_context()
+            .is_valid());
+    }
+}
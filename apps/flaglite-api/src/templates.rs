@@ -0,0 +1,174 @@
+//! Flag templates - the state a new flag starts in, per environment
+//!
+//! Every project has the built-in templates; a stored template of the same
+//! name replaces one for that project.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::models::{Environment, FlagTemplate};
+
+/// State of a new flag in one environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateState {
+    pub enabled: bool,
+    #[serde(default = "full_rollout")]
+    pub rollout: i32,
+}
+
+fn full_rollout() -> i32 {
+    100
+}
+
+impl Default for TemplateState {
+    fn default() -> Self {
+        TemplateState {
+            enabled: false,
+            rollout: full_rollout(),
+        }
+    }
+}
+
+/// A template with its environment states parsed
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    pub description: Option<String>,
+    /// State in environments `environments` doesn't name
+    pub default: TemplateState,
+    /// By environment name
+    pub environments: BTreeMap<String, TemplateState>,
+    pub builtin: bool,
+    /// None for built-ins
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl Template {
+    /// The state a new flag starts in in `env`
+    pub fn state_for(&self, env: &Environment) -> TemplateState {
+        self.environments
+            .get(&env.name)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+impl From<FlagTemplate> for Template {
+    fn from(t: FlagTemplate) -> Self {
+        Template {
+            name: t.name,
+            description: t.description,
+            default: TemplateState {
+                enabled: t.default_enabled,
+                rollout: t.default_rollout,
+            },
+            // Written by this server from a parsed map, so it only fails if edited by hand
+            environments: serde_json::from_str(&t.environments).unwrap_or_default(),
+            builtin: false,
+            updated_at: Some(t.updated_at),
+        }
+    }
+}
+
+/// The templates every project starts with
+pub fn builtin() -> Vec<Template> {
+    let on = |rollout| TemplateState {
+        enabled: true,
+        rollout,
+    };
+
+    vec![
+        Template {
+            name: "experiment".to_string(),
+            description: Some(
+                "On for everyone in development, at 0% in production to ramp up from".to_string(),
+            ),
+            default: TemplateState::default(),
+            environments: BTreeMap::from([
+                ("development".to_string(), on(100)),
+                ("production".to_string(), on(0)),
+            ]),
+            builtin: true,
+            updated_at: None,
+        },
+        Template {
+            name: "kill-switch".to_string(),
+            description: Some("Off in every environment until switched on".to_string()),
+            default: TemplateState::default(),
+            environments: BTreeMap::new(),
+            builtin: true,
+            updated_at: None,
+        },
+    ]
+}
+
+/// The built-in templates and the project's stored ones, by name; stored
+/// templates replace built-ins of the same name
+pub fn merge(stored: Vec<FlagTemplate>) -> Vec<Template> {
+    let mut templates: BTreeMap<String, Template> =
+        builtin().into_iter().map(|t| (t.name.clone(), t)).collect();
+    for t in stored {
+        templates.insert(t.name.clone(), t.into());
+    }
+    templates.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env(name: &str) -> Environment {
+        Environment {
+            id: name.to_string(),
+            project_id: "p".to_string(),
+            name: name.to_string(),
+            api_key: String::new(),
+            protected: false,
+            frozen: false,
+            sort_order: 0,
+            color: None,
+            emoji: None,
+            cache_max_age: None,
+            cache_stale_while_revalidate: None,
+            public_client_id: None,
+            require_approval: false,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_experiment_ramps_from_zero_in_production() {
+        let experiment = builtin()
+            .into_iter()
+            .find(|t| t.name == "experiment")
+            .unwrap();
+
+        let production = experiment.state_for(&env("production"));
+        assert!(production.enabled);
+        assert_eq!(production.rollout, 0);
+        assert_eq!(experiment.state_for(&env("development")).rollout, 100);
+        assert!(!experiment.state_for(&env("staging")).enabled);
+    }
+
+    #[test]
+    fn test_stored_template_replaces_builtin() {
+        let stored = FlagTemplate {
+            project_id: "p".to_string(),
+            name: "kill-switch".to_string(),
+            description: None,
+            default_enabled: true,
+            default_rollout: 100,
+            environments: r#"{"production": {"enabled": false}}"#.to_string(),
+            updated_at: Utc::now(),
+        };
+
+        let templates = merge(vec![stored]);
+        assert_eq!(templates.len(), 2);
+        let kill_switch = templates.iter().find(|t| t.name == "kill-switch").unwrap();
+        assert!(!kill_switch.builtin);
+        assert!(kill_switch.state_for(&env("staging")).enabled);
+        assert!(!kill_switch.state_for(&env("production")).enabled);
+        assert_eq!(kill_switch.state_for(&env("production")).rollout, 100);
+    }
+}
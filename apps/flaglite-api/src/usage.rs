@@ -0,0 +1,158 @@
+//! Flag evaluation counting
+//!
+//! Evaluations are tallied in memory and flushed to storage in batches, so the
+//! evaluation path never waits on a write.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::models::{AppState, EvaluationCount};
+use crate::storage::Storage;
+
+/// How often pending counts are written to storage
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+type CountKey = (String, String, NaiveDate);
+
+#[derive(Default)]
+pub struct UsageRecorder {
+    pending: Mutex<HashMap<CountKey, (i64, DateTime<Utc>)>>,
+}
+
+impl UsageRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count one evaluation of a flag in an environment
+    pub fn record(&self, flag_id: &str, environment_id: &str) {
+        let now = Utc::now();
+        let key = (
+            flag_id.to_string(),
+            environment_id.to_string(),
+            now.date_naive(),
+        );
+
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = pending.entry(key).or_insert((0, now));
+        entry.0 += 1;
+        entry.1 = now;
+    }
+
+    /// Counts not yet flushed for the given flags
+    pub fn pending(&self, flag_ids: &[String]) -> Vec<EvaluationCount> {
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending
+            .iter()
+            .filter(|((flag_id, _, _), _)| flag_ids.contains(flag_id))
+            .map(to_count)
+            .collect()
+    }
+
+    /// Take all pending counts, leaving the recorder empty
+    fn drain(&self) -> Vec<EvaluationCount> {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        pending
+            .drain()
+            .map(|entry| to_count((&entry.0, &entry.1)))
+            .collect()
+    }
+
+    /// Write pending counts to storage
+    pub async fn flush(&self, storage: &dyn Storage) {
+        let counts = self.drain();
+        if counts.is_empty() {
+            return;
+        }
+
+        // Usage is best-effort; a failed flush loses that batch rather than blocking evaluations
+        if let Err(e) = storage.record_evaluations(&counts).await {
+            tracing::warn!("Failed to record {} evaluation counts: {e}", counts.len());
+        }
+    }
+}
+
+/// The `flush-usage` job: write pending counts to storage. Counts wait in
+/// memory while the server is read-only
+pub async fn flush_job(state: AppState) -> anyhow::Result<()> {
+    if !state.maintenance.is_read_only() {
+        state.usage.flush(state.storage.as_ref()).await;
+    }
+    Ok(())
+}
+
+fn to_count(
+    ((flag_id, environment_id, day), (count, last)): (&CountKey, &(i64, DateTime<Utc>)),
+) -> EvaluationCount {
+    EvaluationCount {
+        flag_id: flag_id.clone(),
+        environment_id: environment_id.clone(),
+        day: *day,
+        count: *count,
+        last_evaluated_at: *last,
+    }
+}
+
+/// Merge stored and pending counts into one row per flag, environment, and day
+pub fn merge(counts: impl IntoIterator<Item = EvaluationCount>) -> Vec<EvaluationCount> {
+    let mut merged: HashMap<CountKey, EvaluationCount> = HashMap::new();
+    for c in counts {
+        let key = (c.flag_id.clone(), c.environment_id.clone(), c.day);
+        match merged.get_mut(&key) {
+            Some(existing) => {
+                existing.count += c.count;
+                existing.last_evaluated_at = existing.last_evaluated_at.max(c.last_evaluated_at);
+            }
+            None => {
+                merged.insert(key, c);
+            }
+        }
+    }
+
+    let mut merged: Vec<EvaluationCount> = merged.into_values().collect();
+    merged.sort_by_key(|m| std::cmp::Reverse(m.day));
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_accumulates_per_flag_and_environment() {
+        let recorder = UsageRecorder::new();
+        recorder.record("f1", "e1");
+        recorder.record("f1", "e1");
+        recorder.record("f1", "e2");
+        recorder.record("f2", "e1");
+
+        let mut counts = recorder.pending(&["f1".to_string()]);
+        counts.sort_by(|a, b| a.environment_id.cmp(&b.environment_id));
+        let totals: Vec<_> = counts
+            .iter()
+            .map(|c| (c.environment_id.as_str(), c.count))
+            .collect();
+        assert_eq!(totals, vec![("e1", 2), ("e2", 1)]);
+
+        assert_eq!(recorder.drain().len(), 3);
+        assert!(recorder.pending(&["f1".to_string()]).is_empty());
+    }
+
+    #[test]
+    fn test_merge_sums_matching_days() {
+        let now = Utc::now();
+        let count = |n| EvaluationCount {
+            flag_id: "f1".to_string(),
+            environment_id: "e1".to_string(),
+            day: now.date_naive(),
+            count: n,
+            last_evaluated_at: now,
+        };
+
+        let merged = merge(vec![count(3), count(4)]);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].count, 7);
+    }
+}
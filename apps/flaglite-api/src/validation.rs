@@ -0,0 +1,362 @@
+//! Request validation
+//!
+//! Handlers run every check before failing so a 422 response lists all
+//! invalid fields at once instead of one per round trip.
+
+use chrono::{DateTime, Utc};
+use flaglite_core::FlagKey;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::error::{AppError, Result};
+
+pub use flaglite_core::MAX_NAME_LENGTH;
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+pub const MIN_USERNAME_LENGTH: usize = 3;
+pub const MAX_USERNAME_LENGTH: usize = 32;
+/// Longest repository or code path accepted as flag metadata
+pub const MAX_REFERENCE_LENGTH: usize = 1024;
+/// Room for an emoji built from several code points (flags, skin tones)
+pub const MAX_EMOJI_LENGTH: usize = 8;
+/// Longest note on a change request or its review
+pub const MAX_COMMENT_LENGTH: usize = 1000;
+/// Longest environment name; names appear in URLs and CLI arguments
+pub const MAX_ENVIRONMENT_NAME_LENGTH: usize = 32;
+/// Longest a cache may keep an SDK response (a day)
+pub const MAX_CACHE_SECONDS: i32 = 86_400;
+
+/// One failed constraint on one request field
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    /// Machine-readable constraint name (`required`, `min_length`, `max_length`, `charset`, `format`, `range`, `schema`)
+    pub constraint: &'static str,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<FieldError>,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a failed check the builder methods don't cover
+    pub fn fail(&mut self, field: &str, constraint: &'static str, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.to_string(),
+            constraint,
+            message: message.into(),
+        });
+    }
+
+    /// Non-empty after trimming and at most `max` characters
+    pub fn name(&mut self, field: &str, label: &str, value: &str, max: usize) -> &mut Self {
+        let value = value.trim();
+        if value.is_empty() {
+            self.fail(field, "required", format!("{label} cannot be empty"));
+        } else if value.chars().count() > max {
+            self.fail(
+                field,
+                "max_length",
+                format!("{label} must be at most {max} characters"),
+            );
+        }
+        self
+    }
+
+    /// At most `max` characters; empty is allowed
+    pub fn max_length(&mut self, field: &str, label: &str, value: &str, max: usize) -> &mut Self {
+        if value.chars().count() > max {
+            self.fail(
+                field,
+                "max_length",
+                format!("{label} must be at most {max} characters"),
+            );
+        }
+        self
+    }
+
+    /// The rules shared with the client and CLI (see `FlagKey::parse`)
+    pub fn flag_key(&mut self, field: &str, key: &str) -> &mut Self {
+        if let Err(e) = FlagKey::parse(key) {
+            self.fail(field, e.constraint(), e.to_string());
+        }
+        self
+    }
+
+    /// Lowercase letters, digits and hyphens, starting with a letter
+    pub fn environment_name(&mut self, field: &str, name: &str) -> &mut Self {
+        let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+            && name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        if name.is_empty() {
+            self.fail(field, "required", "Environment name cannot be empty");
+        } else if name.chars().count() > MAX_ENVIRONMENT_NAME_LENGTH {
+            self.fail(
+                field,
+                "max_length",
+                format!(
+                    "Environment name must be at most {MAX_ENVIRONMENT_NAME_LENGTH} characters"
+                ),
+            );
+        } else if !valid {
+            self.fail(
+                field,
+                "format",
+                "Environment name must start with a letter and use only lowercase letters, digits and hyphens",
+            );
+        }
+        self
+    }
+
+    /// Expects the already trimmed and lowercased username
+    pub fn username(&mut self, field: &str, username: &str) -> &mut Self {
+        let len = username.chars().count();
+        if len < MIN_USERNAME_LENGTH {
+            self.fail(
+                field,
+                "min_length",
+                format!("Username must be at least {MIN_USERNAME_LENGTH} characters"),
+            );
+        } else if len > MAX_USERNAME_LENGTH {
+            self.fail(
+                field,
+                "max_length",
+                format!("Username must be at most {MAX_USERNAME_LENGTH} characters"),
+            );
+        } else if !is_identifier(username) {
+            self.fail(
+                field,
+                "charset",
+                "Username can only contain letters, numbers, hyphens, and underscores",
+            );
+        }
+        self
+    }
+
+    pub fn password(&mut self, field: &str, password: &str) -> &mut Self {
+        if password.len() < MIN_PASSWORD_LENGTH {
+            self.fail(
+                field,
+                "min_length",
+                format!("Password must be at least {MIN_PASSWORD_LENGTH} characters"),
+            );
+        }
+        self
+    }
+
+    /// Empty is allowed (clears the email)
+    pub fn email(&mut self, field: &str, email: &str) -> &mut Self {
+        if !email.is_empty() && !email.contains('@') {
+            self.fail(field, "format", "Invalid email format");
+        }
+        self
+    }
+
+    pub fn rollout(&mut self, field: &str, percentage: i32) -> &mut Self {
+        if !(0..=100).contains(&percentage) {
+            self.fail(
+                field,
+                "range",
+                "Rollout percentage must be between 0 and 100",
+            );
+        }
+        self
+    }
+
+    /// Empty is allowed (clears the color); otherwise `#rrggbb`
+    pub fn color(&mut self, field: &str, color: &str) -> &mut Self {
+        let valid = color
+            .strip_prefix('#')
+            .is_some_and(|hex| hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()));
+        if !color.is_empty() && !valid {
+            self.fail(field, "format", "Color must be a hex color like #22c55e");
+        }
+        self
+    }
+
+    /// Absent is allowed; a time must not already have passed
+    pub fn future(&mut self, field: &str, label: &str, at: Option<DateTime<Utc>>) -> &mut Self {
+        if at.is_some_and(|at| at <= Utc::now()) {
+            self.fail(field, "range", format!("{label} must be in the future"));
+        }
+        self
+    }
+
+    /// Absent is allowed; a number must be at least 1
+    pub fn positive(&mut self, field: &str, label: &str, value: Option<i64>) -> &mut Self {
+        if value.is_some_and(|v| v < 1) {
+            self.fail(field, "range", format!("{label} must be at least 1"));
+        }
+        self
+    }
+
+    /// Absent is allowed; a duration must be between 0 and `max` seconds
+    pub fn seconds(&mut self, field: &str, label: &str, value: Option<i32>, max: i32) -> &mut Self {
+        if value.is_some_and(|v| !(0..=max).contains(&v)) {
+            self.fail(
+                field,
+                "range",
+                format!("{label} must be between 0 and {max} seconds"),
+            );
+        }
+        self
+    }
+
+    /// A JSON Schema that compiles; remote `$ref`s are not resolved
+    pub fn json_schema(&mut self, field: &str, schema: &Value) -> &mut Self {
+        if let Err(e) = jsonschema::validator_for(schema) {
+            self.fail(field, "schema", format!("Invalid JSON Schema: {e}"));
+        }
+        self
+    }
+
+    /// `value` matches `schema`, with one error per violation named by where it
+    /// is in the value (`value/limits/max`). A schema that doesn't compile was
+    /// rejected when it was attached, so it is skipped here.
+    pub fn matches_schema(&mut self, field: &str, schema: &Value, value: &Value) -> &mut Self {
+        let Ok(validator) = jsonschema::validator_for(schema) else {
+            return self;
+        };
+        let violations: Vec<(String, String)> = validator
+            .iter_errors(value)
+            .map(|e| (format!("{field}{}", e.instance_path), e.to_string()))
+            .collect();
+        for (path, message) in violations {
+            self.fail(&path, "schema", message);
+        }
+        self
+    }
+
+    /// `Err(AppError::Validation)` if any check failed
+    pub fn finish(&mut self) -> Result<()> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AppError::Validation(std::mem::take(&mut self.errors)))
+        }
+    }
+}
+
+fn is_identifier(value: &str) -> bool {
+    value
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(result: Result<()>) -> Vec<(String, &'static str)> {
+        match result {
+            Err(AppError::Validation(errors)) => errors
+                .into_iter()
+                .map(|e| (e.field, e.constraint))
+                .collect(),
+            other => panic!("expected validation error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_collects_every_invalid_field() {
+        let result = Validator::new()
+            .flag_key("key", "bad key!")
+            .name("name", "Flag name", "   ", MAX_NAME_LENGTH)
+            .rollout("rollout_percentage", 150)
+            .future(
+                "expires_at",
+                "Expiry",
+                Some(Utc::now() - chrono::Duration::days(1)),
+            )
+            .positive("requests_per_minute", "Quota", Some(0))
+            .color("color", "green")
+            .seconds(
+                "cache_max_age",
+                "Cache max age",
+                Some(-1),
+                MAX_CACHE_SECONDS,
+            )
+            .environment_name("environment", "QA")
+            .finish();
+
+        assert_eq!(
+            fields(result),
+            vec![
+                ("key".to_string(), "charset"),
+                ("name".to_string(), "required"),
+                ("rollout_percentage".to_string(), "range"),
+                ("expires_at".to_string(), "range"),
+                ("requests_per_minute".to_string(), "range"),
+                ("color".to_string(), "format"),
+                ("cache_max_age".to_string(), "range"),
+                ("environment".to_string(), "format"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_valid_input_passes() {
+        assert!(Validator::new()
+            .flag_key("key", "new-checkout_2")
+            .username("username", "alice")
+            .password("password", "longenough")
+            .email("email", "")
+            .rollout("rollout_percentage", 0)
+            .future("expires_at", "Expiry", None)
+            .positive("requests_per_minute", "Quota", Some(1))
+            .finish()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "color": { "type": "string" },
+                "limits": {
+                    "type": "object",
+                    "properties": { "max": { "type": "integer", "minimum": 1 } }
+                }
+            },
+            "required": ["color"]
+        });
+
+        assert!(Validator::new()
+            .json_schema("json_schema", &schema)
+            .matches_schema("value", &schema, &serde_json::json!({ "color": "blue" }))
+            .finish()
+            .is_ok());
+
+        let result = Validator::new()
+            .matches_schema(
+                "value",
+                &schema,
+                &serde_json::json!({ "color": 3, "limits": { "max": 0 } }),
+            )
+            .finish();
+        let mut errors = fields(result);
+        errors.sort();
+        assert_eq!(
+            errors,
+            vec![
+                ("value/color".to_string(), "schema"),
+                ("value/limits/max".to_string(), "schema"),
+            ]
+        );
+
+        let result = Validator::new()
+            .json_schema(
+                "json_schema",
+                &serde_json::json!({ "type": "no-such-type" }),
+            )
+            .finish();
+        assert_eq!(fields(result), vec![("json_schema".to_string(), "schema")]);
+    }
+}
@@ -0,0 +1,248 @@
+//! API versions
+//!
+//! Handlers always build the newest response shapes. A client asks for a
+//! version with a `/v2` path prefix or `Accept: application/vnd.flaglite.v2+json`;
+//! anything else is version 1, which is what existing CLIs and SDKs send.
+//! Endpoints whose responses changed since version 1 list their [`Change`]s
+//! in the route table, and a response to an older version has each newer
+//! change undone, so a field added, renamed or retyped later never reaches a
+//! client that doesn't know it. Every response says which version answered in
+//! `x-flaglite-api-version`.
+
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+
+use crate::error::AppError;
+
+/// Response header naming the version that answered
+pub const VERSION_HEADER: &str = "x-flaglite-api-version";
+
+/// A version of the API's request and response shapes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiVersion {
+    #[default]
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub const LATEST: ApiVersion = ApiVersion::V2;
+
+    pub fn number(self) -> u32 {
+        match self {
+            ApiVersion::V1 => 1,
+            ApiVersion::V2 => 2,
+        }
+    }
+
+    fn from_number(number: u32) -> Option<Self> {
+        match number {
+            1 => Some(ApiVersion::V1),
+            2 => Some(ApiVersion::V2),
+            _ => None,
+        }
+    }
+
+    /// The version a request asks for; Err names a version this server
+    /// doesn't have
+    fn requested(request: &Request) -> Result<Self, u32> {
+        if request.uri().path().starts_with("/v2/") {
+            return Ok(ApiVersion::V2);
+        }
+        let accept = request
+            .headers()
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(|media_type| {
+                media_type
+                    .trim()
+                    .strip_prefix("application/vnd.flaglite.v")?
+                    .split(';')
+                    .next()?
+                    .strip_suffix("+json")?
+                    .parse::<u32>()
+                    .ok()
+            });
+        match accept {
+            Some(number) => Self::from_number(number).ok_or(number),
+            None => Ok(ApiVersion::V1),
+        }
+    }
+}
+
+/// The `/v2` path an endpoint is also served at, if it's versioned
+pub fn v2_path(path: &str) -> Option<String> {
+    path.strip_prefix("/v1/").map(|rest| format!("/v2/{rest}"))
+}
+
+/// A change to an endpoint's response made in `version`
+#[derive(Debug, Clone, Copy)]
+pub struct Change {
+    pub version: ApiVersion,
+    /// What's different from the version before, for the docs
+    pub description: &'static str,
+    /// Turn a successful response of `version` into the version before's
+    pub undo: fn(&mut Value),
+}
+
+/// Version 2: each environment's state says when and why it was last set
+pub const ENVIRONMENT_REASONS: Change = Change {
+    version: ApiVersion::V2,
+    description: "Each entry of `environments` adds `reason` (string?) and `updated_at` (datetime?): why and when the flag's value there was last written",
+    undo: |value| {
+        for_each_item(value, |flag| {
+            if let Some(environments) = flag.get_mut("environments").and_then(Value::as_object_mut)
+            {
+                for state in environments.values_mut().filter_map(Value::as_object_mut) {
+                    state.remove("reason");
+                    state.remove("updated_at");
+                }
+            }
+        })
+    },
+};
+
+/// `f` on `value`, or on each element if it's an array
+fn for_each_item(value: &mut Value, mut f: impl FnMut(&mut Value)) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(f),
+        item => f(item),
+    }
+}
+
+/// Middleware on every route: note the requested version for [`downgrade`]
+/// and name it in the response
+pub async fn negotiate(mut request: Request, next: Next) -> Response {
+    let version = match ApiVersion::requested(&request) {
+        Ok(version) => version,
+        Err(number) => {
+            return AppError::BadRequest(format!(
+                "API version {number} doesn't exist; this server speaks versions 1 to {}",
+                ApiVersion::LATEST.number()
+            ))
+            .into_response()
+        }
+    };
+    request.extensions_mut().insert(version);
+
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(VERSION_HEADER, HeaderValue::from(version.number()));
+    response
+}
+
+/// Middleware on routes with [`Change`]s: undo the ones newer than the
+/// requested version, newest first
+pub async fn downgrade(
+    State(changes): State<&'static [Change]>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let version = request
+        .extensions()
+        .get::<ApiVersion>()
+        .copied()
+        .unwrap_or_default();
+    let response = next.run(request).await;
+
+    let mut undo: Vec<&Change> = changes.iter().filter(|c| c.version > version).collect();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|t| t.as_bytes().starts_with(b"application/json"));
+    if undo.is_empty() || !response.status().is_success() || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return AppError::Internal(format!("Failed to read the response: {e}")).into_response()
+        }
+    };
+    let Ok(mut value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    undo.sort_by_key(|c| std::cmp::Reverse(c.version));
+    for change in undo {
+        (change.undo)(&mut value);
+    }
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(value.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(path: &str, accept: Option<&str>) -> Request {
+        let mut builder = Request::builder().uri(path);
+        if let Some(accept) = accept {
+            builder = builder.header(header::ACCEPT, accept);
+        }
+        builder.body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_requested_version() {
+        let requested = |path, accept| ApiVersion::requested(&request(path, accept));
+
+        assert_eq!(requested("/v1/projects", None), Ok(ApiVersion::V1));
+        assert_eq!(requested("/v2/projects", None), Ok(ApiVersion::V2));
+        assert_eq!(
+            requested("/v1/projects", Some("application/json")),
+            Ok(ApiVersion::V1)
+        );
+        assert_eq!(
+            requested(
+                "/v1/projects",
+                Some("text/html, application/vnd.flaglite.v2+json; q=0.9")
+            ),
+            Ok(ApiVersion::V2)
+        );
+        assert_eq!(
+            requested("/v1/projects", Some("application/vnd.flaglite.v7+json")),
+            Err(7)
+        );
+        assert_eq!(v2_path("/v1/flags/:key").as_deref(), Some("/v2/flags/:key"));
+        assert_eq!(v2_path("/health"), None);
+    }
+
+    #[test]
+    fn test_environment_reasons_undo() {
+        let v2 = json!({
+            "key": "dark-mode",
+            "environments": {
+                "production": {
+                    "enabled": true,
+                    "rollout": 100,
+                    "version": 3,
+                    "reason": "launch",
+                    "updated_at": "2026-10-01T12:00:00Z"
+                }
+            }
+        });
+
+        let mut flag = v2.clone();
+        (ENVIRONMENT_REASONS.undo)(&mut flag);
+        assert_eq!(
+            flag["environments"]["production"],
+            json!({"enabled": true, "rollout": 100, "version": 3})
+        );
+
+        let mut flags = json!([v2]);
+        (ENVIRONMENT_REASONS.undo)(&mut flags);
+        assert_eq!(flags[0], flag);
+    }
+}
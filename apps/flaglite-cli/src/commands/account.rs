@@ -0,0 +1,263 @@
+//! Account commands: email verification, renaming, password reset and saved
+//! preferences
+
+use crate::config::Config;
+use crate::output::Output;
+use anyhow::Result;
+use colored::Colorize;
+use dialoguer::{Input, Password};
+use flaglite_client::{
+    FlagLiteClient, FlagLiteError, PasswordResetRequest, ResetPasswordRequest,
+    UpdatePreferencesRequest, UpdateUserRequest, VerifyEmailRequest,
+};
+
+/// Create an authenticated client from config
+fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
+    let client = config.client()?;
+
+    // Prefer API key over token
+    if let Some(api_key) = &config.api_key {
+        Ok(client.with_api_key(api_key))
+    } else if let Some(token) = &config.token {
+        Ok(client.with_token(token))
+    } else {
+        Err(FlagLiteError::NotAuthenticated.into())
+    }
+}
+
+/// Set the email password resets are sent to; an empty string clears it
+pub async fn set_email(config: &Config, output: &Output, email: String) -> Result<()> {
+    let client = client_from_config(config)?;
+    let user = client
+        .update_me(&UpdateUserRequest {
+            email: Some(email),
+            ..Default::default()
+        })
+        .await?;
+
+    if output.is_json() {
+        return output.json(&user);
+    }
+
+    match &user.email {
+        Some(email) if !user.email_verified => output.success(&format!(
+            "Email set to {email}. Run 'flaglite account verify-email' to verify it."
+        )),
+        Some(email) => output.success(&format!("Email set to {email}")),
+        None => output.success("Email cleared"),
+    }
+
+    Ok(())
+}
+
+/// Send a verification email, or confirm the address with the token from one
+pub async fn verify_email(config: &Config, output: &Output, token: Option<String>) -> Result<()> {
+    let Some(token) = token else {
+        let client = client_from_config(config)?;
+        client.send_verification_email().await?;
+        output.success("Verification email sent. Run 'flaglite account verify-email --token <token>' with the token from it.");
+        return Ok(());
+    };
+
+    // Tokens identify the user, so no login is needed
+    let user = config
+        .client()?
+        .verify_email(&VerifyEmailRequest { token })
+        .await?;
+
+    if output.is_json() {
+        return output.json(&user);
+    }
+
+    output.success(&format!(
+        "Verified {}",
+        user.email.as_deref().unwrap_or_default()
+    ));
+
+    Ok(())
+}
+
+/// Change the username; the old one stays reserved for this account
+pub async fn rename(config: &mut Config, output: &Output, username: String) -> Result<()> {
+    let client = client_from_config(config)?;
+    let user = client
+        .update_me(&UpdateUserRequest {
+            username: Some(username),
+            ..Default::default()
+        })
+        .await
+        .map_err(|e| match e {
+            FlagLiteError::RateLimited { retry_after } => {
+                let days = retry_after.div_ceil(24 * 60 * 60);
+                anyhow::Error::new(e).context(format!(
+                    "Your username was changed recently; it can be changed again in {days} day(s)"
+                ))
+            }
+            e => e.into(),
+        })?;
+
+    let previous = config.username.replace(user.username.clone());
+    if let Some(warning) = config.save_credentials()? {
+        output.warn(&warning);
+    }
+
+    if output.is_json() {
+        return output.json(&user);
+    }
+
+    match previous.filter(|p| *p != user.username) {
+        Some(previous) => output.success(&format!(
+            "Renamed {previous} to {}. {previous} stays reserved for you.",
+            user.username
+        )),
+        None => output.success(&format!("Username is {}", user.username)),
+    }
+
+    Ok(())
+}
+
+/// Ask for a password reset email
+pub async fn request_reset(
+    config: &Config,
+    output: &Output,
+    username: Option<String>,
+) -> Result<()> {
+    let username = match username.or_else(|| config.username.clone()) {
+        Some(username) => username,
+        None => {
+            output.require_prompt("Pass --username to request a reset.")?;
+            Input::new().with_prompt("Username").interact_text()?
+        }
+    };
+
+    config
+        .client()?
+        .request_password_reset(&PasswordResetRequest { username })
+        .await?;
+
+    // The server doesn't say whether anything was sent
+    output.success(
+        "If the account has a verified email, a reset token is on its way. \
+         Run 'flaglite account reset --token <token>' with it.",
+    );
+
+    Ok(())
+}
+
+/// Choose a new password with a token from a reset email
+pub async fn reset(
+    config: &Config,
+    output: &Output,
+    token: String,
+    password: Option<String>,
+    revoke_api_keys: bool,
+) -> Result<()> {
+    let password = match password {
+        Some(password) => password,
+        None => {
+            output.require_prompt("Pass --password to reset without prompting.")?;
+            let password: String = Password::new().with_prompt("New password").interact()?;
+            let confirm: String = Password::new()
+                .with_prompt("Confirm new password")
+                .interact()?;
+            if password != confirm {
+                return Err(
+                    FlagLiteError::ValidationError("Passwords do not match".to_string()).into(),
+                );
+            }
+            password
+        }
+    };
+
+    config
+        .client()?
+        .reset_password(&ResetPasswordRequest {
+            token,
+            password,
+            revoke_api_keys,
+        })
+        .await?;
+
+    output.success(
+        "Password changed and every session signed out. Run 'flaglite login' with the new password.",
+    );
+    if revoke_api_keys {
+        output.info("Your API keys were revoked too.");
+    }
+
+    Ok(())
+}
+
+/// Show the defaults saved with the account, which `login` applies
+pub async fn preferences(config: &Config, output: &Output) -> Result<()> {
+    let client = client_from_config(config)?;
+    let preferences = client.preferences().await?;
+
+    if output.is_json() {
+        return output.json(&preferences);
+    }
+
+    println!("{}", "Preferences".bold().underline());
+    for (label, value) in [
+        ("Project:", &preferences.project_id),
+        ("Environment:", &preferences.environment),
+        ("Display name:", &preferences.display_name),
+        ("Format:", &preferences.format),
+    ] {
+        let value = value.as_deref().map_or("(not set)".dimmed(), |v| v.cyan());
+        println!("  {} {value}", label.dimmed());
+    }
+
+    Ok(())
+}
+
+/// Save one preference with the account; an empty value clears it
+pub async fn set_preference(
+    config: &Config,
+    output: &Output,
+    key: String,
+    value: String,
+) -> Result<()> {
+    let mut req = UpdatePreferencesRequest::default();
+    let name = match key.to_lowercase().replace('-', "_").as_str() {
+        "project" | "project_id" => {
+            req.project_id = Some(value.clone());
+            "project"
+        }
+        "environment" | "env" => {
+            req.environment = Some(value.clone());
+            "environment"
+        }
+        "display_name" => {
+            req.display_name = Some(value.clone());
+            "display_name"
+        }
+        "format" => {
+            req.format = Some(value.clone());
+            "format"
+        }
+        _ => {
+            return Err(FlagLiteError::ValidationError(format!(
+                "Unknown preference '{key}'; expected project, environment, display_name or format"
+            ))
+            .into())
+        }
+    };
+
+    let client = client_from_config(config)?;
+    let preferences = client.update_preferences(&req).await?;
+
+    if output.is_json() {
+        return output.json(&preferences);
+    }
+
+    if value.trim().is_empty() {
+        output.success(&format!("Cleared {name}"));
+    } else {
+        output.success(&format!(
+            "Saved {name}. 'flaglite login' applies it on any machine."
+        ));
+    }
+
+    Ok(())
+}
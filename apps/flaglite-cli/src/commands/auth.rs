@@ -1,10 +1,15 @@
 //! Authentication commands
 
-use crate::config::Config;
+use crate::config::{Config, ConfigKey};
 use crate::output::Output;
 use anyhow::Result;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use colored::Colorize;
 use dialoguer::{Input, Password};
-use flaglite_client::FlagLiteClient;
+use flaglite_client::{FlagLiteClient, FlagLiteError, User};
+use serde::{Deserialize, Serialize};
 
 /// Sign up for FlagLite
 pub async fn signup(
@@ -12,14 +17,13 @@ pub async fn signup(
     output: &Output,
     cli_username: Option<String>,
     cli_password: Option<String>,
+    invite_code: Option<String>,
 ) -> Result<()> {
     // Determine if we're in interactive mode
     let is_interactive = cli_password.is_none();
 
-    if is_interactive && output.is_json() {
-        return Err(anyhow::anyhow!(
-            "Interactive signup not supported with --format=json. Use --password for non-interactive mode."
-        ));
+    if is_interactive {
+        output.require_prompt("Pass --password (and optionally --username) to sign up.")?;
     }
 
     let (username, password) = if is_interactive {
@@ -40,7 +44,9 @@ pub async fn signup(
             Password::new().with_prompt("Confirm password").interact()?;
 
         if password != password_confirm {
-            return Err(anyhow::anyhow!("Passwords do not match"));
+            return Err(
+                FlagLiteError::ValidationError("Passwords do not match".to_string()).into(),
+            );
         }
 
         (username, password)
@@ -51,12 +57,20 @@ pub async fn signup(
     };
 
     if password.len() < 8 {
-        return Err(anyhow::anyhow!("Password must be at least 8 characters"));
+        return Err(FlagLiteError::ValidationError(
+            "Password must be at least 8 characters".to_string(),
+        )
+        .into());
     }
 
     // Call signup endpoint
-    let client = FlagLiteClient::new(&config.api_url);
-    let response = client.signup(username.as_deref(), &password).await?;
+    let client = config.client()?;
+    let response = output
+        .with_spinner(
+            "Creating account...",
+            client.signup(username.as_deref(), &password, invite_code.as_deref()),
+        )
+        .await?;
 
     // Save credentials
     config.token = Some(response.token);
@@ -68,7 +82,9 @@ pub async fn signup(
         config.project_id = Some(project.id.to_string());
     }
 
-    config.save_credentials()?;
+    if let Some(warning) = config.save_credentials()? {
+        output.warn(&warning);
+    }
 
     if output.is_json() {
         // JSON output for scripting
@@ -77,7 +93,7 @@ pub async fn signup(
             "api_key": response.api_key.key,
             "user_id": response.user.id,
         });
-        println!("{}", serde_json::to_string_pretty(&json)?);
+        output.json(&json)?;
     } else {
         output.success(&format!(
             "Account created successfully!\n  Username: {}\n  API Key: {}",
@@ -94,18 +110,27 @@ pub async fn login(
     output: &Output,
     cli_username: Option<String>,
     cli_password: Option<String>,
+    api_key: Option<String>,
 ) -> Result<()> {
+    if let Some(api_key) = api_key {
+        if cli_username.is_some() || cli_password.is_some() {
+            return Err(FlagLiteError::ValidationError(
+                "Pass either --api-key or --username and --password, not both".to_string(),
+            )
+            .into());
+        }
+        return login_with_api_key(config, output, api_key).await;
+    }
+
     // Determine if we're in interactive mode
     let is_interactive = cli_username.is_none() || cli_password.is_none();
 
-    if is_interactive && config.is_authenticated() && !output.is_json() {
-        output.warn("You are already logged in. This will replace your current session.");
+    if is_interactive {
+        output.require_prompt("Pass --username and --password to log in.")?;
     }
 
-    if is_interactive && output.is_json() {
-        return Err(anyhow::anyhow!(
-            "Interactive login not supported with --format=json. Use --username and --password."
-        ));
+    if is_interactive && config.is_authenticated() {
+        output.warn("You are already logged in. This will replace your current session.");
     }
 
     let (username, password) = if is_interactive {
@@ -129,22 +154,131 @@ pub async fn login(
     };
 
     // Authenticate
-    let client = FlagLiteClient::new(&config.api_url);
-    let response = client.login(&username, &password).await?;
+    let client = config.client()?;
+    let response = output
+        .with_spinner("Logging in...", client.login(&username, &password))
+        .await?;
 
-    // Save credentials
+    // Save credentials; a stored API key would otherwise take precedence
+    let client = client.with_token(&response.token);
     config.token = Some(response.token);
+    config.api_key = None;
     config.username = Some(response.user.username.clone());
-    config.save_credentials()?;
+    if let Some(warning) = config.save_credentials()? {
+        output.warn(&warning);
+    }
+
+    let display_name = apply_preferences(output, &client).await;
+    print_logged_in(output, &response.user, display_name)
+}
+
+/// Log in with an existing user API key instead of a password
+///
+/// `-` reads the key from stdin, keeping it out of shell history.
+async fn login_with_api_key(config: &mut Config, output: &Output, api_key: String) -> Result<()> {
+    let api_key = if api_key == "-" {
+        let mut line = String::new();
+        std::io::stdin().read_line(&mut line)?;
+        line.trim().to_string()
+    } else {
+        api_key.trim().to_string()
+    };
+
+    if !api_key.starts_with(USER_API_KEY_PREFIX) {
+        return Err(FlagLiteError::ValidationError(format!(
+            "Expected a user API key starting with '{USER_API_KEY_PREFIX}'"
+        ))
+        .into());
+    }
+
+    // Checks the key before anything is saved
+    let client = config.client()?.with_api_key(&api_key);
+    let user = output
+        .with_spinner("Checking API key...", client.whoami())
+        .await?;
+
+    config.api_key = Some(api_key);
+    config.token = None;
+    config.username = Some(user.username.clone());
+    if let Some(warning) = config.save_credentials()? {
+        output.warn(&warning);
+    }
+
+    let display_name = apply_preferences(output, &client).await;
+    print_logged_in(output, &user, display_name)
+}
 
+/// Save the defaults kept with the account to the user config, so a fresh
+/// machine starts out like the others, and return the display name. A repo's
+/// .flaglite.toml still overrides them; failing to load them doesn't fail
+/// the login.
+async fn apply_preferences(output: &Output, client: &FlagLiteClient) -> Option<String> {
+    let preferences = match client.preferences().await {
+        Ok(preferences) => preferences,
+        Err(e) => {
+            output.warn(&format!("Could not load your saved preferences: {e}"));
+            return None;
+        }
+    };
+
+    let values: Vec<(ConfigKey, String)> = [
+        (ConfigKey::Project, &preferences.project_id),
+        (ConfigKey::Environment, &preferences.environment),
+        (ConfigKey::Format, &preferences.format),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| value.clone().map(|value| (key, value)))
+    .collect();
+
+    if !values.is_empty() {
+        match save_user_defaults(&values) {
+            Ok(()) => {
+                let applied: Vec<String> = values
+                    .iter()
+                    .map(|(key, value)| format!("{} = {value}", key.name()))
+                    .collect();
+                output.info(&format!(
+                    "Applied your saved preferences: {}",
+                    applied.join(", ")
+                ));
+            }
+            Err(e) => output.warn(&format!("Could not apply your saved preferences: {e:#}")),
+        }
+    }
+
+    preferences.display_name
+}
+
+fn save_user_defaults(values: &[(ConfigKey, String)]) -> Result<()> {
+    let mut user = Config::load_user()?;
+    for (key, value) in values {
+        user.set(*key, Some(value.clone()));
+    }
+    user.save()?;
+
+    // The project is also kept with the credentials, which take precedence
+    if values.iter().any(|(key, _)| *key == ConfigKey::Project) {
+        user.save_credentials()?;
+    }
+    Ok(())
+}
+
+/// Prefix of the API keys users and automation create; project and
+/// environment keys can't authenticate the CLI
+const USER_API_KEY_PREFIX: &str = "flg_";
+
+fn print_logged_in(output: &Output, user: &User, display_name: Option<String>) -> Result<()> {
     if output.is_json() {
         let json = serde_json::json!({
-            "username": response.user.username,
-            "user_id": response.user.id,
+            "username": user.username,
+            "user_id": user.id,
+            "display_name": display_name,
         });
-        println!("{}", serde_json::to_string_pretty(&json)?);
+        output.json(&json)?;
+    } else if let Some(display_name) = display_name {
+        output.success(&format!("Logged in as {display_name} ({})", user.username));
     } else {
-        output.success(&format!("Logged in as {}", response.user.username));
+        output.success(&format!("Logged in as {}", user.username));
     }
 
     Ok(())
@@ -158,7 +292,7 @@ pub async fn logout(config: &mut Config, output: &Output) -> Result<()> {
     }
 
     config.clear_auth();
-    Config::delete_credentials()?;
+    config.delete_credentials()?;
 
     output.success("Logged out");
 
@@ -166,13 +300,17 @@ pub async fn logout(config: &mut Config, output: &Output) -> Result<()> {
 }
 
 /// Show current user
-pub async fn whoami(config: &Config, output: &Output) -> Result<()> {
+pub async fn whoami(config: &Config, output: &Output, local: bool) -> Result<()> {
+    if local {
+        return whoami_local(config, output);
+    }
+
     let token = config.require_token()?;
 
     let client = if config.api_key.is_some() {
-        FlagLiteClient::new(&config.api_url).with_api_key(token)
+        config.client()?.with_api_key(token)
     } else {
-        FlagLiteClient::new(&config.api_url).with_token(token)
+        config.client()?.with_token(token)
     };
 
     let user = client.whoami().await?;
@@ -181,3 +319,157 @@ pub async fn whoami(config: &Config, output: &Output) -> Result<()> {
 
     Ok(())
 }
+
+/// Characters of an API key shown by `whoami --local`
+const KEY_PREFIX_LEN: usize = 12;
+
+/// What `whoami --local` reports, from config and credentials only
+#[derive(Debug, Serialize)]
+struct LocalIdentity {
+    api_url: String,
+    username: Option<String>,
+    /// Start of the API key in use; the rest is never printed
+    api_key_prefix: Option<String>,
+    /// `FLAGLITE_API_KEY`, `credentials` or `keyring`
+    api_key_source: Option<&'static str>,
+    token_expires_at: Option<DateTime<Utc>>,
+    token_expired: Option<bool>,
+    project_id: Option<String>,
+    environment: String,
+    credentials_path: String,
+}
+
+/// The JWT claims `whoami --local` reads
+#[derive(Debug, Deserialize)]
+struct TokenClaims {
+    exp: i64,
+}
+
+/// Decode a JWT's claims without verifying its signature
+fn decode_claims(token: &str) -> Option<TokenClaims> {
+    let payload = token.split('.').nth(1)?;
+    let bytes = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('=')).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+/// Show who the CLI would authenticate as, without a network call
+fn whoami_local(config: &Config, output: &Output) -> Result<()> {
+    let env_key = std::env::var("FLAGLITE_API_KEY").is_ok_and(|k| !k.is_empty());
+    let expires_at = config
+        .token
+        .as_deref()
+        .and_then(decode_claims)
+        .and_then(|claims| DateTime::from_timestamp(claims.exp, 0));
+
+    let identity = LocalIdentity {
+        api_url: config.api_url.clone(),
+        username: config.username.clone(),
+        api_key_prefix: config
+            .api_key
+            .as_ref()
+            .map(|key| key.chars().take(KEY_PREFIX_LEN).collect()),
+        api_key_source: config.api_key.as_ref().map(|_| {
+            if env_key {
+                "FLAGLITE_API_KEY"
+            } else if config.secrets_in_keyring {
+                "keyring"
+            } else {
+                "credentials"
+            }
+        }),
+        token_expires_at: expires_at,
+        token_expired: expires_at.map(|at| at <= Utc::now()),
+        project_id: config.project_id.clone(),
+        environment: config.get_environment().to_string(),
+        credentials_path: Config::credentials_path()?.display().to_string(),
+    };
+
+    if output.is_json() {
+        return output.json(&identity);
+    }
+
+    let unset = || "-".dimmed().to_string();
+    println!("{}", "Local Credentials".bold().underline());
+    println!("  {} {}", "API URL:".dimmed(), identity.api_url.cyan());
+    println!(
+        "  {} {}",
+        "Username:".dimmed(),
+        identity.username.clone().unwrap_or_else(unset)
+    );
+    println!(
+        "  {} {}",
+        "API key:".dimmed(),
+        match (&identity.api_key_prefix, identity.api_key_source) {
+            (Some(prefix), Some(source)) => format!("{prefix}… (from {source})"),
+            _ => unset(),
+        }
+    );
+    let token = match (&config.token, identity.token_expires_at) {
+        (None, _) => unset(),
+        (Some(_), None) => "present, expiry unreadable".yellow().to_string(),
+        (Some(_), Some(at)) if at <= Utc::now() => {
+            format!("{} {}", "expired".red(), at.format("%Y-%m-%d %H:%M UTC"))
+        }
+        (Some(_), Some(at)) => format!("expires {}", at.format("%Y-%m-%d %H:%M UTC")),
+    };
+    println!("  {} {}", "Token:".dimmed(), token);
+    println!(
+        "  {} {}",
+        "Project:".dimmed(),
+        identity.project_id.clone().unwrap_or_else(unset)
+    );
+    println!("  {} {}", "Environment:".dimmed(), identity.environment);
+    println!(
+        "  {} {}",
+        "Credentials:".dimmed(),
+        identity.credentials_path.dimmed()
+    );
+
+    if !config.is_authenticated() {
+        println!();
+        output.info("Not logged in. Run 'flaglite login' or set FLAGLITE_API_KEY.");
+    }
+
+    Ok(())
+}
+
+/// Create an invite code for someone else to sign up with
+pub async fn invite(config: &Config, output: &Output) -> Result<()> {
+    let token = config.require_token()?;
+
+    let client = if config.api_key.is_some() {
+        config.client()?.with_api_key(token)
+    } else {
+        config.client()?.with_token(token)
+    };
+
+    let invite = client.create_invite().await?;
+
+    if output.is_json() {
+        output.json(&invite)?;
+    } else {
+        output.success(&format!(
+            "Invite code: {}\n  Expires: {}\n  Sign up with: flaglite signup --invite-code {}",
+            invite.code,
+            invite.expires_at.format("%Y-%m-%d %H:%M UTC"),
+            invite.code
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_claims() {
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"sub":"u1","username":"alice","exp":1700000000}"#);
+        let token = format!("eyJhbGciOiJIUzI1NiJ9.{payload}.signature");
+        assert_eq!(decode_claims(&token).unwrap().exp, 1_700_000_000);
+
+        assert!(decode_claims("not-a-jwt").is_none());
+        assert!(decode_claims("a.!!!.c").is_none());
+    }
+}
@@ -0,0 +1,90 @@
+//! Change request commands: flag changes that wait for a project admin's approval
+
+use crate::config::Config;
+use crate::output::Output;
+use anyhow::Result;
+use flaglite_client::{CreateChangeRequest, FlagLiteClient, FlagLiteError};
+
+/// Create an authenticated client from config
+fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
+    let client = config.client()?;
+
+    // Prefer API key over token
+    if let Some(api_key) = &config.api_key {
+        Ok(client.with_api_key(api_key))
+    } else if let Some(token) = &config.token {
+        Ok(client.with_token(token))
+    } else {
+        Err(FlagLiteError::NotAuthenticated.into())
+    }
+}
+
+/// Propose turning a flag on or off, or changing its rollout, in the current environment
+pub async fn propose(
+    config: &Config,
+    output: &Output,
+    key: String,
+    enabled: Option<bool>,
+    percentage: Option<i32>,
+    comment: Option<String>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let req = CreateChangeRequest {
+        flag_key: key,
+        environment: config.get_environment().to_string(),
+        enabled,
+        rollout_percentage: percentage,
+        comment,
+    };
+    let change = client.create_change_request(project_id, &req).await?;
+
+    if output.is_json() {
+        return output.json(&change);
+    }
+    output.success(&format!(
+        "Proposed change {} to '{}' in {}; it applies once approved",
+        change.id, change.flag_key, change.environment
+    ));
+    Ok(())
+}
+
+/// List the current project's change requests
+pub async fn list(config: &Config, output: &Output, status: Option<String>) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let changes = client
+        .list_change_requests(project_id, status.as_deref())
+        .await?;
+    output.print_change_requests(&changes)
+}
+
+/// Approve a change request, applying it, or reject it
+pub async fn review(
+    config: &Config,
+    output: &Output,
+    id: String,
+    approve: bool,
+    comment: Option<String>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+
+    let change = client
+        .review_change_request(&id, approve, comment.as_deref())
+        .await?;
+
+    if output.is_json() {
+        return output.json(&change);
+    }
+    if approve {
+        output.success(&format!(
+            "Approved and applied change {} to '{}' in {}",
+            change.id, change.flag_key, change.environment
+        ));
+    } else {
+        output.success(&format!("Rejected change {}", change.id));
+    }
+    Ok(())
+}
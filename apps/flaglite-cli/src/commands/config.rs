@@ -0,0 +1,209 @@
+//! Config get/set/unset commands
+
+use crate::commands::init::repo_root;
+use crate::config::{Config, ConfigKey, RepoConfig, REPO_CONFIG_FILE};
+use crate::output::{Output, OutputFormat};
+use anyhow::{Context, Result};
+use flaglite_client::FlagLiteError;
+use std::path::PathBuf;
+
+/// Where `config set` and `config unset` write
+enum Scope {
+    /// ~/.config/flaglite/config.toml
+    Global,
+    /// The repo's .flaglite.toml
+    Repo(PathBuf),
+}
+
+impl Scope {
+    /// The user config with --global, otherwise the nearest .flaglite.toml,
+    /// or a new one at the root of the current git repository
+    fn resolve(config: &Config, global: bool) -> Result<Self> {
+        if global {
+            return Ok(Scope::Global);
+        }
+        if let Some(path) = &config.repo_config {
+            return Ok(Scope::Repo(path.clone()));
+        }
+        let cwd = std::env::current_dir().context("Could not determine current directory")?;
+        Ok(Scope::Repo(repo_root(&cwd).join(REPO_CONFIG_FILE)))
+    }
+
+    fn path(&self) -> Result<PathBuf> {
+        match self {
+            Scope::Global => Config::config_path(),
+            Scope::Repo(path) => Ok(path.clone()),
+        }
+    }
+}
+
+fn parse_key(key: &str) -> Result<ConfigKey> {
+    key.parse()
+        .map_err(|e: String| FlagLiteError::ValidationError(e).into())
+}
+
+/// Reject values the CLI couldn't use, so a typo fails here rather than on
+/// the next command
+fn validate(key: ConfigKey, value: &str) -> Result<String> {
+    let value = value.trim();
+    let invalid = |msg: String| -> Result<String> {
+        Err(FlagLiteError::ValidationError(format!("Invalid {}: {msg}", key.name())).into())
+    };
+
+    if value.is_empty() {
+        return invalid("value must not be empty".into());
+    }
+
+    match key {
+        ConfigKey::ApiUrl => match reqwest::Url::parse(value) {
+            Ok(url) if matches!(url.scheme(), "http" | "https") => {
+                Ok(value.trim_end_matches('/').to_string())
+            }
+            Ok(_) => invalid(format!("{value} must be an http or https URL")),
+            Err(e) => invalid(format!("{value} is not a URL ({e})")),
+        },
+        ConfigKey::Project | ConfigKey::Environment => {
+            if value.chars().any(char::is_whitespace) {
+                return invalid(format!("'{value}' must not contain whitespace"));
+            }
+            Ok(value.to_string())
+        }
+        ConfigKey::Format => match value.parse::<OutputFormat>() {
+            Ok(_) => Ok(value.to_lowercase()),
+            Err(e) => invalid(e),
+        },
+    }
+}
+
+/// Write `value` for `key` (None removes it) in the chosen scope
+fn write(scope: &Scope, key: ConfigKey, value: Option<String>) -> Result<()> {
+    match scope {
+        Scope::Global => {
+            let mut user = Config::load_user()?;
+            user.set(key, value);
+            user.save()?;
+            // The project and API URL are also kept with the credentials,
+            // which take precedence when loading
+            if matches!(key, ConfigKey::ApiUrl | ConfigKey::Project)
+                && Config::credentials_path()?.exists()
+            {
+                user.save_credentials()?;
+            }
+            Ok(())
+        }
+        Scope::Repo(path) => {
+            let mut repo = if path.exists() {
+                RepoConfig::load(path)?
+            } else {
+                RepoConfig::default()
+            };
+            repo.set(key, value);
+            repo.save(path)
+        }
+    }
+}
+
+/// Print the value in effect for `key`
+pub fn get(config: &Config, output: &Output, key: String) -> Result<()> {
+    let key = parse_key(&key)?;
+    let value = config.get(key);
+
+    if output.is_json() {
+        return output.json(&serde_json::json!({
+            "key": key.name(),
+            "value": value,
+        }));
+    }
+
+    if let Some(value) = value {
+        println!("{value}");
+    }
+    Ok(())
+}
+
+/// Validate and store a value
+pub fn set(
+    config: &Config,
+    output: &Output,
+    key: String,
+    value: String,
+    global: bool,
+) -> Result<()> {
+    let key = parse_key(&key)?;
+    let value = validate(key, &value)?;
+    let scope = Scope::resolve(config, global)?;
+    let path = scope.path()?;
+
+    write(&scope, key, Some(value.clone()))?;
+
+    if output.is_json() {
+        output.json(&serde_json::json!({
+            "key": key.name(),
+            "value": value,
+            "path": path,
+        }))?;
+    } else {
+        output.success(&format!(
+            "Set {} = {value} in {}",
+            key.name(),
+            path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// Remove a stored value, falling back to the next scope or the default
+pub fn unset(config: &Config, output: &Output, key: String, global: bool) -> Result<()> {
+    let key = parse_key(&key)?;
+    let scope = Scope::resolve(config, global)?;
+    let path = scope.path()?;
+
+    if let Scope::Repo(repo_path) = &scope {
+        if !repo_path.exists() {
+            return Err(FlagLiteError::ValidationError(format!(
+                "No {REPO_CONFIG_FILE} here. Use --global to unset {} in the user config.",
+                key.name()
+            ))
+            .into());
+        }
+    }
+
+    write(&scope, key, None)?;
+
+    if output.is_json() {
+        output.json(&serde_json::json!({
+            "key": key.name(),
+            "path": path,
+        }))?;
+    } else {
+        output.success(&format!("Unset {} in {}", key.name(), path.display()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_aliases() {
+        assert_eq!(parse_key("api-url").unwrap(), ConfigKey::ApiUrl);
+        assert_eq!(parse_key("project_id").unwrap(), ConfigKey::Project);
+        assert_eq!(parse_key("env").unwrap(), ConfigKey::Environment);
+        assert!(parse_key("token").is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(
+            validate(ConfigKey::ApiUrl, "https://flags.example.com/").unwrap(),
+            "https://flags.example.com"
+        );
+        assert!(validate(ConfigKey::ApiUrl, "ftp://flags.example.com").is_err());
+        assert!(validate(ConfigKey::ApiUrl, "not a url").is_err());
+        assert_eq!(validate(ConfigKey::Format, "JSON").unwrap(), "json");
+        assert!(validate(ConfigKey::Format, "xml").is_err());
+        assert!(validate(ConfigKey::Environment, "my env").is_err());
+        assert!(validate(ConfigKey::Project, " ").is_err());
+    }
+}
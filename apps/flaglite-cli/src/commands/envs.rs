@@ -1,13 +1,48 @@
 //! Environment management commands
 
-use crate::config::Config;
+use crate::commands::flags::{ask_reason, confirm_protected};
+use crate::config::{Config, ConfirmAction};
 use crate::output::Output;
 use anyhow::Result;
-use flaglite_client::FlagLiteClient;
+use dialoguer::Confirm;
+use flaglite_client::{
+    CopyEnvironmentRequest, CopyEnvironmentResponse, CreateEnvironmentRequest, FlagLiteClient,
+    FlagLiteError, UpdateEnvironmentRequest,
+};
+
+/// An environment of a named project, written `project/env`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectEnv {
+    pub project: String,
+    pub environment: String,
+}
+
+impl std::fmt::Display for ProjectEnv {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.project, self.environment)
+    }
+}
+
+/// Parse `project/env`, where the project is an ID or slug
+pub fn parse_project_env(s: &str) -> std::result::Result<ProjectEnv, String> {
+    match s.split_once('/') {
+        Some((project, environment))
+            if !project.is_empty() && !environment.is_empty() && !environment.contains('/') =>
+        {
+            Ok(ProjectEnv {
+                project: project.to_string(),
+                environment: environment.to_string(),
+            })
+        }
+        _ => Err(format!(
+            "expected PROJECT/ENV, like my-api/production, got '{s}'"
+        )),
+    }
+}
 
 /// Create an authenticated client from config
 fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
-    let client = FlagLiteClient::new(&config.api_url);
+    let client = config.client()?;
 
     // Prefer API key over token
     if let Some(api_key) = &config.api_key {
@@ -15,9 +50,7 @@ fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
     } else if let Some(token) = &config.token {
         Ok(client.with_token(token))
     } else {
-        Err(anyhow::anyhow!(
-            "Not logged in. Run `flaglite signup` or `flaglite login`"
-        ))
+        Err(FlagLiteError::NotAuthenticated.into())
     }
 }
 
@@ -33,6 +66,37 @@ pub async fn list(config: &Config, output: &Output) -> Result<()> {
     Ok(())
 }
 
+/// Add an environment, optionally starting from another one's flag values
+pub async fn create(
+    config: &Config,
+    output: &Output,
+    name: String,
+    copy_from: Option<String>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let req = CreateEnvironmentRequest { name, copy_from };
+    let env = client.create_environment(project_id, &req).await?;
+
+    if output.is_json() {
+        return output.json(&env);
+    }
+
+    match &req.copy_from {
+        Some(source) => output.success(&format!(
+            "Created environment '{}' with the flag values of '{source}'",
+            env.name
+        )),
+        None => output.success(&format!("Created environment '{}'", env.name)),
+    }
+    if let Some(api_key) = &env.api_key {
+        output.info(&format!("SDK key: {api_key}"));
+    }
+
+    Ok(())
+}
+
 /// Set the default environment
 pub async fn use_env(config: &mut Config, output: &Output, name: String) -> Result<()> {
     let client = client_from_config(config)?;
@@ -51,11 +115,452 @@ pub async fn use_env(config: &mut Config, output: &Output, name: String) -> Resu
             output.success(&format!("Now using environment: {env_name}"));
         }
         None => {
-            return Err(anyhow::anyhow!(
+            return Err(anyhow::Error::new(FlagLiteError::EnvironmentNotFound(
+                name.clone(),
+            ))
+            .context(format!(
                 "Environment '{name}' not found. Run 'flaglite envs list' to see available environments.",
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Turn protection on or off for an environment
+pub async fn set_protected(
+    config: &Config,
+    output: &Output,
+    name: String,
+    protected: bool,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let req = UpdateEnvironmentRequest {
+        protected: Some(protected),
+        ..Default::default()
+    };
+    let env = client.update_environment(project_id, &name, req).await?;
+
+    if output.is_json() {
+        return output.json(&env);
+    }
+
+    if env.protected {
+        output.success(&format!(
+            "Environment '{}' is now protected. Flag changes need --confirm-production.",
+            env.name
+        ));
+    } else {
+        output.success(&format!(
+            "Environment '{}' is no longer protected.",
+            env.name
+        ));
+    }
+
+    Ok(())
+}
+
+/// Set an environment's display color and emoji, or its SDK cache settings
+pub async fn update(
+    config: &Config,
+    output: &Output,
+    name: String,
+    color: Option<String>,
+    emoji: Option<String>,
+    cache_max_age: Option<i32>,
+    cache_stale: Option<i32>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let req = UpdateEnvironmentRequest {
+        color,
+        emoji,
+        cache_max_age,
+        cache_stale_while_revalidate: cache_stale,
+        ..Default::default()
+    };
+    let env = client.update_environment(project_id, &name, req).await?;
+
+    if output.is_json() {
+        return output.json(&env);
+    }
+
+    output.success(&format!("Updated environment '{}'", env.name));
+
+    Ok(())
+}
+
+/// Set the order environments are listed in
+pub async fn reorder(config: &Config, output: &Output, names: Vec<String>) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let envs = client.reorder_environments(project_id, &names).await?;
+
+    output.print_environments(&envs, config.environment.as_deref())?;
+
+    Ok(())
+}
+
+/// Make an environment public or private again
+pub async fn set_public(
+    config: &Config,
+    output: &Output,
+    name: String,
+    public: bool,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let req = UpdateEnvironmentRequest {
+        public: Some(public),
+        ..Default::default()
+    };
+    let env = client.update_environment(project_id, &name, req).await?;
+
+    if output.is_json() {
+        return output.json(&env);
+    }
+
+    match &env.public_client_id {
+        Some(client_id) => {
+            output.success(&format!("Environment '{}' is public.", env.name));
+            output.info(&format!("Public client ID: {client_id}"));
+            output.info(&format!(
+                "Flags marked public ('flaglite flags update KEY --public') evaluate without a key at {}/v1/public/{client_id}/flags",
+                client.base_url()
             ));
         }
+        None => output.success(&format!(
+            "Environment '{}' is private; its public client ID no longer works.",
+            env.name
+        )),
     }
 
     Ok(())
 }
+
+/// Freeze or unfreeze an environment's flags
+pub async fn set_frozen(
+    config: &Config,
+    output: &Output,
+    name: String,
+    frozen: bool,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let env = client
+        .set_environment_frozen(project_id, &name, frozen)
+        .await?;
+
+    if output.is_json() {
+        return output.json(&env);
+    }
+
+    if env.frozen {
+        output.success(&format!(
+            "Environment '{}' is frozen. Flag changes there are rejected until you run 'flaglite envs unfreeze {}'.",
+            env.name, env.name
+        ));
+    } else {
+        output.success(&format!("Environment '{}' is unfrozen.", env.name));
+    }
+
+    Ok(())
+}
+
+/// Show whether flag changes in an environment need approval, or set it
+pub async fn approval(
+    config: &Config,
+    output: &Output,
+    name: String,
+    required: Option<bool>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let approval = match required {
+        Some(required) => {
+            client
+                .set_environment_approval(project_id, &name, required)
+                .await?
+        }
+        None => client.environment_approval(project_id, &name).await?,
+    };
+
+    if output.is_json() {
+        return output.json(&approval);
+    }
+
+    let message = if approval.required {
+        format!(
+            "Flag changes in '{}' need approval. Propose them with 'flaglite flags propose'.",
+            approval.environment
+        )
+    } else {
+        format!(
+            "Flag changes in '{}' don't need approval.",
+            approval.environment
+        )
+    };
+    if required.is_some() {
+        output.success(&message);
+    } else {
+        output.info(&message);
+    }
+
+    Ok(())
+}
+
+/// Delete an environment, keeping it restorable until the server purges it
+pub async fn delete(config: &Config, output: &Output, name: String, force: bool) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    if config.asks(ConfirmAction::DeleteEnvironment) && !output.is_json() {
+        output.require_prompt(&config.confirm_hint(
+            ConfirmAction::DeleteEnvironment,
+            "Pass --yes to delete without confirmation.",
+        ))?;
+
+        let confirmed = Confirm::new()
+            .with_prompt(format!(
+                "Delete environment '{name}'? Its SDK key stops working until it is restored."
+            ))
+            .default(false)
+            .interact()?;
+
+        if !confirmed {
+            output.info("Deletion cancelled.");
+            return Ok(());
+        }
+    }
+
+    let deleted = match client.delete_environment(project_id, &name, force).await {
+        Err(FlagLiteError::ProtectedEnvironment(env)) => {
+            return Err(FlagLiteError::ValidationError(format!(
+                "Environment '{env}' is protected; pass --force to delete it (project owners only)"
+            ))
+            .into());
+        }
+        result => result?,
+    };
+
+    if output.is_json() {
+        return output.json(&deleted);
+    }
+
+    output.success(&format!(
+        "Environment '{}' deleted with {} flag value(s).",
+        deleted.name, deleted.flag_values
+    ));
+    output.info(&format!(
+        "Restore it with 'flaglite envs restore {}' until {}.",
+        deleted.name,
+        deleted.purge_after.format("%Y-%m-%d")
+    ));
+
+    Ok(())
+}
+
+/// List deleted environments that can still be restored
+pub async fn list_deleted(config: &Config, output: &Output) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let deleted = client.list_deleted_environments(project_id).await?;
+
+    output.print_deleted_environments(&deleted)
+}
+
+/// Restore the most recently deleted environment with this name
+pub async fn restore(config: &Config, output: &Output, name: String) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let env = client.restore_environment(project_id, &name).await?;
+
+    if output.is_json() {
+        return output.json(&env);
+    }
+
+    output.success(&format!(
+        "Environment '{}' restored with its SDK key and flag values.",
+        env.name
+    ));
+
+    Ok(())
+}
+
+/// Replace an environment's snapshot signing key
+pub async fn rotate_signing_key(config: &Config, output: &Output, name: String) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let keys = client.rotate_signing_key(project_id, &name).await?;
+
+    if output.is_json() {
+        return output.json(&keys);
+    }
+
+    if let Some(key) = keys.keys.first() {
+        output.success(&format!(
+            "Signing key for '{name}' rotated. New snapshots are signed with {}.",
+            key.kid
+        ));
+    }
+    if keys.keys.len() > 1 {
+        output.info("Previous keys stay valid for 7 days so cached snapshots still verify.");
+    }
+
+    Ok(())
+}
+
+/// Copy one environment's flag values into another, usually of another
+/// project, for the flag keys both projects have
+pub async fn copy(
+    config: &Config,
+    output: &Output,
+    from: ProjectEnv,
+    to: ProjectEnv,
+    dry_run: bool,
+    confirm_production: bool,
+    reason: Option<String>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+
+    let mut req = CopyEnvironmentRequest {
+        from_project: from.project.clone(),
+        from_environment: from.environment.clone(),
+        to_project: to.project.clone(),
+        to_environment: to.environment.clone(),
+        confirm: config.confirms_production(confirm_production),
+        reason,
+        dry_run: true,
+    };
+    let preview = client.copy_environment(&req).await?;
+    if dry_run {
+        return print_copy(output, &preview);
+    }
+
+    let changes = preview.flags.iter().filter(|f| f.changed).count();
+    if changes == 0 {
+        return print_copy(output, &preview);
+    }
+    let action = format!("Copy {changes} flag value(s) from {from} to {to}");
+
+    if config.asks(ConfirmAction::BulkUpdate) && !output.is_json() {
+        output.require_prompt(&config.confirm_hint(
+            ConfirmAction::BulkUpdate,
+            "Pass --yes to apply without confirmation.",
+        ))?;
+
+        print_copy(output, &preview)?;
+
+        let confirmed = Confirm::new()
+            .with_prompt(format!("{action}?"))
+            .default(false)
+            .interact()?;
+        if !confirmed {
+            output.info("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    req.dry_run = false;
+    let resp = loop {
+        match client.copy_environment(&req).await {
+            Err(FlagLiteError::ProtectedEnvironment(env))
+                if !req.confirm && output.can_prompt() =>
+            {
+                if !confirm_protected(&env, &action)? {
+                    output.info("Cancelled.");
+                    return Ok(());
+                }
+                req.confirm = true;
+            }
+            Err(FlagLiteError::ReasonRequired(env))
+                if req.reason.is_none() && output.can_prompt() =>
+            {
+                req.reason = Some(ask_reason(&env)?);
+            }
+            result => break result?,
+        }
+    };
+
+    print_copy(output, &resp)
+}
+
+/// The flags copied, then what didn't match
+fn print_copy(output: &Output, resp: &CopyEnvironmentResponse) -> Result<()> {
+    if output.is_json() {
+        return output.json(resp);
+    }
+
+    let from = format!("{}/{}", resp.from_project, resp.from_environment);
+    let to = format!("{}/{}", resp.to_project, resp.to_environment);
+    for f in resp.flags.iter().filter(|f| f.changed) {
+        let state = if f.enabled { "on" } else { "off" };
+        println!("  {} → {state}, {}%", f.key, f.rollout);
+    }
+
+    let changed = resp.flags.iter().filter(|f| f.changed).count();
+    let unchanged = resp.flags.len() - changed;
+    let mut message = if resp.dry_run {
+        format!("Would copy {changed} flag value(s) from {from} to {to}")
+    } else {
+        format!("Copied {changed} flag value(s) from {from} to {to}")
+    };
+    if unchanged > 0 {
+        message.push_str(&format!(" ({unchanged} already the same)"));
+    }
+    if resp.dry_run {
+        output.info(&message);
+    } else {
+        output.success(&message);
+    }
+
+    if !resp.missing_in_target.is_empty() {
+        output.warn(&format!(
+            "Not in {}: {}",
+            resp.to_project,
+            resp.missing_in_target.join(", ")
+        ));
+    }
+    if !resp.missing_in_source.is_empty() {
+        output.warn(&format!(
+            "Not in {}, left unchanged: {}",
+            resp.from_project,
+            resp.missing_in_source.join(", ")
+        ));
+    }
+    for skipped in &resp.skipped {
+        output.warn(&format!("Skipped {}: {}", skipped.key, skipped.reason));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_project_env() {
+        assert_eq!(
+            parse_project_env("my-api/production"),
+            Ok(ProjectEnv {
+                project: "my-api".to_string(),
+                environment: "production".to_string(),
+            })
+        );
+        assert!(parse_project_env("production").is_err());
+        assert!(parse_project_env("/production").is_err());
+        assert!(parse_project_env("my-api/").is_err());
+        assert!(parse_project_env("a/b/c").is_err());
+    }
+}
@@ -1,14 +1,25 @@
 //! Flag management commands
 
-use crate::config::Config;
+use crate::commands::guards;
+use crate::config::{Config, ConfirmAction};
 use crate::output::Output;
 use anyhow::Result;
-use dialoguer::Confirm;
-use flaglite_client::{CreateFlagRequest, FlagLiteClient, FlagType};
+use chrono::{DateTime, Utc};
+use dialoguer::{Confirm, Input};
+use flaglite_client::{
+    AssignmentsRequest, AssignmentsResponse, BatchUpdateFlagsRequest, BatchUpdateFlagsResponse,
+    CreateFlagRequest, FlagLiteClient, FlagLiteError, FlagType, FlagWithState, GuardSpec,
+    Overrides, RollbackFlagRequest, SetFlagStateRequest, UpdateFlagRequest, UpdateUserListRequest,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 /// Create an authenticated client from config
 fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
-    let client = FlagLiteClient::new(&config.api_url);
+    let client = config.client()?;
 
     // Prefer API key over token
     if let Some(api_key) = &config.api_key {
@@ -16,19 +27,38 @@ fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
     } else if let Some(token) = &config.token {
         Ok(client.with_token(token))
     } else {
-        Err(anyhow::anyhow!(
-            "Not logged in. Run `flaglite signup` or `flaglite login`"
-        ))
+        Err(FlagLiteError::NotAuthenticated.into())
     }
 }
 
-/// List all flags in the current project
-pub async fn list(config: &Config, output: &Output) -> Result<()> {
+/// List all flags in the current project, optionally only one owner's or repository's
+pub async fn list(
+    config: &Config,
+    output: &Output,
+    owner: Option<String>,
+    repo: Option<String>,
+) -> Result<()> {
     let client = client_from_config(config)?;
     let project_id = config.require_project()?;
     let env = config.get_environment();
 
-    let flags = client.list_flags(project_id, Some(env)).await?;
+    let flags: Vec<FlagWithState> = client
+        .list_flags(project_id, Some(env))
+        .await?
+        .into_iter()
+        .filter(|f| {
+            owner.as_deref().is_none_or(|owner| {
+                f.flag
+                    .owner
+                    .as_deref()
+                    .is_some_and(|o| o.eq_ignore_ascii_case(owner))
+            })
+        })
+        .filter(|f| {
+            repo.as_deref()
+                .is_none_or(|repo| f.flag.repository.as_deref() == Some(repo))
+        })
+        .collect();
 
     if !output.is_json() {
         output.info(&format!("Flags in environment: {env}"));
@@ -39,7 +69,52 @@ pub async fn list(config: &Config, output: &Output) -> Result<()> {
     Ok(())
 }
 
+/// List flags past their expiry date
+pub async fn expired(config: &Config, output: &Output) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let flags: Vec<FlagWithState> = client
+        .list_flags(project_id, Some(config.get_environment()))
+        .await?
+        .into_iter()
+        .filter(|f| f.flag.expired)
+        .collect();
+
+    if flags.is_empty() && !output.is_json() {
+        output.success("No expired flags.");
+        return Ok(());
+    }
+
+    output.print_flags(&flags)?;
+    if !output.is_json() {
+        output.info("Expired flags evaluate to off. Remove the code that checks them, then run 'flaglite flags delete <key>'.");
+    }
+
+    Ok(())
+}
+
+/// List the local overrides SDK clients started from this shell would apply
+pub fn overrides(output: &Output) -> Result<()> {
+    output.print_overrides(&Overrides::from_env())
+}
+
+/// Parse `--expires`: a date (start of that day, UTC) or an RFC 3339 timestamp
+fn parse_expiry(value: &str) -> Result<DateTime<Utc>> {
+    if let Ok(at) = DateTime::parse_from_rfc3339(value) {
+        return Ok(at.with_timezone(&Utc));
+    }
+    if let Ok(day) = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(day.and_time(chrono::NaiveTime::MIN).and_utc());
+    }
+    Err(FlagLiteError::ValidationError(format!(
+        "Invalid expiry: '{value}'. Use a date (2026-12-31) or an RFC 3339 time (2026-12-31T18:00:00Z)",
+    ))
+    .into())
+}
+
 /// Create a new flag
+#[allow(clippy::too_many_arguments)]
 pub async fn create(
     config: &Config,
     output: &Output,
@@ -48,9 +123,17 @@ pub async fn create(
     description: Option<String>,
     flag_type: String,
     enabled: bool,
+    expires: Option<String>,
+    owner: Option<String>,
+    repository: Option<String>,
+    code_path: Option<String>,
+    schema: Option<PathBuf>,
+    default_enabled: bool,
+    default_value: Option<String>,
+    template: Option<String>,
 ) -> Result<()> {
-    let client = client_from_config(config)?;
-    let project_id = config.require_project()?;
+    let expires_at = expires.as_deref().map(parse_expiry).transpose()?;
+    let default_value = parse_json_arg("--default-value", default_value)?;
 
     // Parse flag type
     let flag_type = match flag_type.to_lowercase().as_str() {
@@ -59,12 +142,24 @@ pub async fn create(
         "number" | "num" | "int" | "float" => FlagType::Number,
         "json" | "object" => FlagType::Json,
         _ => {
-            return Err(anyhow::anyhow!(
+            return Err(FlagLiteError::ValidationError(format!(
                 "Invalid flag type: '{flag_type}'. Use: boolean, string, number, or json",
-            ));
+            ))
+            .into());
         }
     };
 
+    let json_schema = match schema {
+        Some(_) if !matches!(flag_type, FlagType::Json) => {
+            return Err(FlagLiteError::ValidationError(
+                "--schema only applies to json flags (--flag-type json)".to_string(),
+            )
+            .into());
+        }
+        Some(path) => Some(read_schema(&path)?),
+        None => None,
+    };
+
     // Default name to key if not provided
     let name = name.unwrap_or_else(|| {
         // Convert key to title case: my_feature -> My Feature
@@ -87,49 +182,847 @@ pub async fn create(
         description,
         flag_type,
         enabled,
+        expires_at,
+        owner,
+        repository,
+        code_path,
+        json_schema,
+        default_enabled,
+        default_value,
     };
+    // Bad input is reported before login or project selection is checked
+    req.validate()?;
 
-    let flag = client.create_flag(project_id, req).await?;
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let flag = match template {
+        Some(template) => {
+            client
+                .create_flag_from_template(project_id, &template, req)
+                .await?
+        }
+        None => client.create_flag(project_id, req).await?,
+    };
 
     output.print_flag_created(&flag)?;
 
     Ok(())
 }
 
+/// Parse a JSON command-line value such as `--value`
+fn parse_json_arg(arg: &str, value: Option<String>) -> Result<Option<serde_json::Value>> {
+    value
+        .map(|v| {
+            serde_json::from_str(&v).map_err(|e| {
+                FlagLiteError::ValidationError(format!("{arg} is not valid JSON: {e}")).into()
+            })
+        })
+        .transpose()
+}
+
+/// Read and parse a JSON Schema file; the server checks that it is a valid schema
+fn read_schema(path: &Path) -> Result<serde_json::Value> {
+    let contents = std::fs::read_to_string(path).map_err(|e| {
+        FlagLiteError::ValidationError(format!("Cannot read {}: {e}", path.display()))
+    })?;
+    serde_json::from_str(&contents).map_err(|e| {
+        FlagLiteError::ValidationError(format!("{} is not valid JSON: {e}", path.display())).into()
+    })
+}
+
+/// Set a flag's owner, repository, code path, defaults, or visibility
+#[allow(clippy::too_many_arguments)]
+pub async fn update(
+    config: &Config,
+    output: &Output,
+    key: String,
+    owner: Option<String>,
+    repository: Option<String>,
+    code_path: Option<String>,
+    default_enabled: Option<bool>,
+    default_value: Option<String>,
+    clear_default_value: bool,
+    public: Option<bool>,
+) -> Result<()> {
+    let req = UpdateFlagRequest {
+        owner,
+        repository,
+        code_path,
+        default_enabled,
+        default_value: parse_json_arg("--default-value", default_value)?,
+        clear_default_value,
+        public,
+    };
+    if req.owner.is_none()
+        && req.repository.is_none()
+        && req.code_path.is_none()
+        && req.default_enabled.is_none()
+        && req.default_value.is_none()
+        && !req.clear_default_value
+        && req.public.is_none()
+    {
+        return Err(FlagLiteError::ValidationError(
+            "Nothing to update. Pass --owner, --repo, --code-path, --default-on/--default-off, --default-value, or --public/--private".to_string(),
+        )
+        .into());
+    }
+
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let flag = client.update_flag(project_id, &key, req).await?;
+
+    output.print_flag_updated(&flag)?;
+
+    Ok(())
+}
+
 /// Get flag details
-pub async fn get(config: &Config, output: &Output, key: String) -> Result<()> {
+pub async fn get(config: &Config, output: &Output, key: String, all_envs: bool) -> Result<()> {
     let client = client_from_config(config)?;
     let project_id = config.require_project()?;
     let env = config.get_environment();
 
     let flag = client.get_flag(project_id, &key, Some(env)).await?;
 
-    output.print_flag(&flag)?;
+    if all_envs {
+        let envs = client.list_environments(project_id).await?;
+        output.print_flag_environments(&flag, &envs)?;
+    } else {
+        output.print_flag(&flag, env)?;
+    }
 
     Ok(())
 }
 
+/// Find flags by key or name across every project the user can access,
+/// or the --org organization's projects
+pub async fn search(config: &Config, output: &Output, query: String) -> Result<()> {
+    let client = client_from_config(config)?;
+
+    let results = client
+        .search_flags(&query, config.org_id.as_deref())
+        .await?;
+    output.print_search_results(&results, &query)?;
+
+    Ok(())
+}
+
+/// Show evaluation counts for a flag
+pub async fn stats(config: &Config, output: &Output, key: String) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let stats = client.flag_stats(project_id, &key).await?;
+    let envs = client.list_environments(project_id).await?;
+
+    output.print_flag_stats(&stats, &envs)?;
+
+    Ok(())
+}
+
+/// Most user IDs sent per assignments request (the server's limit)
+const ASSIGNMENT_BATCH_SIZE: usize = 10_000;
+
+/// Print each user's bucket and result for a flag in the current environment
+pub async fn assignments(
+    config: &Config,
+    output: &Output,
+    key: String,
+    users_file: PathBuf,
+) -> Result<()> {
+    let user_ids = read_user_ids(&users_file)?;
+    if user_ids.is_empty() {
+        return Err(FlagLiteError::ValidationError(format!(
+            "No user IDs in {}",
+            users_file.display()
+        ))
+        .into());
+    }
+
+    let resp = fetch_assignments(config, &key, &user_ids).await?;
+    output.print_assignments(&resp.assignments)
+}
+
+/// The user IDs in a file, one per line ("-" reads stdin)
+fn read_user_ids(path: &Path) -> Result<Vec<String>> {
+    let contents = if path.as_os_str() == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path).map_err(|e| {
+            FlagLiteError::ValidationError(format!("Cannot read {}: {e}", path.display()))
+        })?
+    };
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Show a flag's allowlist or denylist (`list`) in the current environment
+pub async fn user_list(config: &Config, output: &Output, list: &str, key: String) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let env = config.get_environment();
+
+    let resp = client.user_list(project_id, &key, list, env).await?;
+    output.print_user_list(&resp)
+}
+
+/// Users to put on or take off a flag's allowlist or denylist
+pub struct UserListChange {
+    pub users: Vec<String>,
+    pub file: Option<PathBuf>,
+    /// Take the users off instead of adding them
+    pub remove: bool,
+    /// Empty the list (before adding any users)
+    pub clear: bool,
+}
+
+/// Change a flag's allowlist or denylist (`list`) in the current environment
+///
+/// Protected environments and required reasons are handled as in [`toggle`].
+pub async fn change_user_list(
+    config: &Config,
+    output: &Output,
+    list: &str,
+    key: String,
+    change: UserListChange,
+    confirm_production: bool,
+    mut reason: Option<String>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let env = config.get_environment();
+    let mut confirm_production = config.confirms_production(confirm_production);
+
+    let mut users = change.users;
+    if let Some(file) = &change.file {
+        users.extend(read_user_ids(file)?);
+    }
+    if users.is_empty() && !change.clear {
+        return Err(
+            FlagLiteError::ValidationError("Give users with --user or --file".to_string()).into(),
+        );
+    }
+    let (add, remove) = if change.remove {
+        (Vec::new(), users)
+    } else {
+        (users, Vec::new())
+    };
+
+    let resp = loop {
+        let req = UpdateUserListRequest {
+            environment: env.to_string(),
+            add: add.clone(),
+            remove: remove.clone(),
+            clear: change.clear,
+            confirm: confirm_production,
+            reason: reason.clone(),
+        };
+        match client.update_user_list(project_id, &key, list, &req).await {
+            Err(FlagLiteError::ProtectedEnvironment(_))
+                if !confirm_production && output.can_prompt() =>
+            {
+                if !confirm_protected(env, &format!("Change the {list} of '{key}'"))? {
+                    output.info("Change cancelled.");
+                    return Ok(());
+                }
+                confirm_production = true;
+            }
+            Err(FlagLiteError::ReasonRequired(_)) if reason.is_none() && output.can_prompt() => {
+                reason = Some(ask_reason(env)?);
+            }
+            result => break result?,
+        }
+    };
+
+    if output.is_json() {
+        return output.json(&resp);
+    }
+    output.success(&format!(
+        "{} added and {} removed: the {list} of '{key}' in {env} has {} user(s)",
+        resp.added.unwrap_or_default(),
+        resp.removed.unwrap_or_default(),
+        resp.user_ids.len()
+    ));
+    Ok(())
+}
+
+/// Each user's result for a flag in the current environment, asked for in
+/// batches the server accepts
+async fn fetch_assignments(
+    config: &Config,
+    key: &str,
+    user_ids: &[String],
+) -> Result<AssignmentsResponse> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let env = config.get_environment();
+
+    let mut all: Option<AssignmentsResponse> = None;
+    for batch in user_ids.chunks(ASSIGNMENT_BATCH_SIZE) {
+        let req = AssignmentsRequest {
+            environment: env.to_string(),
+            user_ids: batch.to_vec(),
+        };
+        let resp = client.flag_assignments(project_id, key, &req).await?;
+        match &mut all {
+            Some(all) => all.assignments.extend(resp.assignments),
+            None => all = Some(resp),
+        }
+    }
+    all.ok_or_else(|| FlagLiteError::ValidationError("No user IDs given".to_string()).into())
+}
+
+/// The user IDs a fixture is generated for; fixed so fixtures regenerated
+/// later can be compared line by line
+fn fixture_user_ids(samples: usize) -> Vec<String> {
+    (1..=samples).map(|i| format!("user-{i}")).collect()
+}
+
+/// What a flag serves a set of synthetic users, for application test suites
+/// to check their SDK buckets users the same way
+#[derive(Debug, Serialize)]
+struct EvaluationFixture {
+    key: String,
+    environment: String,
+    rollout_percentage: i32,
+    /// How buckets are computed, so a fixture says what it pins down
+    bucketing: &'static str,
+    samples: usize,
+    enabled: usize,
+    assignments: Vec<flaglite_client::Assignment>,
+}
+
+/// Write a JSON fixture of a flag's results for `samples` synthetic users
+/// in the current environment, to `out` or stdout
+pub async fn fixtures(
+    config: &Config,
+    output: &Output,
+    key: String,
+    samples: usize,
+    out: Option<PathBuf>,
+) -> Result<()> {
+    if samples == 0 {
+        return Err(
+            FlagLiteError::ValidationError("--samples must be at least 1".to_string()).into(),
+        );
+    }
+
+    let resp = fetch_assignments(config, &key, &fixture_user_ids(samples)).await?;
+    let fixture = EvaluationFixture {
+        key: resp.key,
+        environment: resp.environment,
+        rollout_percentage: resp.rollout_percentage,
+        bucketing: "murmur3_32(\"<key>:<user_id>\", seed 0) % 100 < rollout_percentage",
+        samples,
+        enabled: resp.assignments.iter().filter(|a| a.enabled).count(),
+        assignments: resp.assignments,
+    };
+    let contents = serde_json::to_string_pretty(&fixture)? + "\n";
+
+    let Some(out) = out else {
+        print!("{contents}");
+        return Ok(());
+    };
+    std::fs::write(&out, contents).map_err(|e| {
+        FlagLiteError::ValidationError(format!("Cannot write {}: {e}", out.display()))
+    })?;
+    if output.is_json() {
+        return output.json(&serde_json::json!({
+            "path": out,
+            "samples": fixture.samples,
+            "enabled": fixture.enabled,
+        }));
+    }
+    output.success(&format!(
+        "Wrote {} evaluation(s) of '{}' in {} to {} ({} enabled)",
+        fixture.samples,
+        fixture.key,
+        fixture.environment,
+        out.display(),
+        fixture.enabled
+    ));
+    Ok(())
+}
+
 /// Toggle a flag
-pub async fn toggle(config: &Config, output: &Output, key: String) -> Result<()> {
+///
+/// Protected environments need `--confirm-production`, or an interactive yes
+/// when the server rejects the unconfirmed change. A reason the project
+/// requires is asked for when `--reason` isn't given. With `guard`, the
+/// toggle is reverted if the guard hears it's unhealthy.
+pub async fn toggle(
+    config: &Config,
+    output: &Output,
+    key: String,
+    expected_version: Option<i64>,
+    confirm_production: bool,
+    mut reason: Option<String>,
+    guard: Option<GuardSpec>,
+) -> Result<()> {
     let client = client_from_config(config)?;
     let project_id = config.require_project()?;
     let env = config.get_environment();
+    let mut confirm_production = config.confirms_production(confirm_production);
 
-    let flag = client.toggle_flag(project_id, &key, env).await?;
+    let flag = loop {
+        match client
+            .toggle_flag(
+                project_id,
+                &key,
+                env,
+                confirm_production,
+                expected_version,
+                reason.as_deref(),
+            )
+            .await
+        {
+            Err(FlagLiteError::ProtectedEnvironment(_))
+                if !confirm_production && output.can_prompt() =>
+            {
+                if !confirm_protected(env, &format!("Toggle '{key}'"))? {
+                    output.info("Toggle cancelled.");
+                    return Ok(());
+                }
+                confirm_production = true;
+            }
+            Err(FlagLiteError::ReasonRequired(_)) if reason.is_none() && output.can_prompt() => {
+                reason = Some(ask_reason(env)?);
+            }
+            result => break result?,
+        }
+    };
 
     let status = if flag.enabled { "enabled" } else { "disabled" };
     output.success(&format!("Flag '{key}' is now {status} in {env}"));
 
+    if let Some(spec) = guard {
+        let version = flag.environments.get(env).map_or(0, |state| state.version);
+        guards::watch(config, output, &key, version, spec).await?;
+    }
+
+    Ok(())
+}
+
+/// Show a page of a flag's versions in the current environment, or stream
+/// every version as NDJSON
+pub async fn history(
+    config: &Config,
+    output: &Output,
+    key: String,
+    limit: Option<u32>,
+    cursor: Option<String>,
+    export: bool,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let env = config.get_environment();
+
+    if export {
+        let mut export = client.export_flag_history(project_id, &key, env).await?;
+        let mut stdout = std::io::stdout().lock();
+        while let Some(chunk) = export.next_chunk().await? {
+            stdout.write_all(&chunk)?;
+        }
+        stdout.flush()?;
+        return Ok(());
+    }
+
+    let history = client
+        .flag_history_page(project_id, &key, env, limit, cursor.as_deref())
+        .await?;
+    output.print_flag_history(&history)
+}
+
+/// Restore a flag to an earlier version in the current environment
+///
+/// Without `to`, undoes the most recent change.
+pub async fn rollback(
+    config: &Config,
+    output: &Output,
+    key: String,
+    to: Option<i64>,
+    expected_version: Option<i64>,
+    confirm_production: bool,
+    reason: Option<String>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let env = config.get_environment();
+
+    let mut req = RollbackFlagRequest {
+        environment: env.to_string(),
+        to_version: to,
+        expected_version,
+        confirm: config.confirms_production(confirm_production),
+        reason,
+    };
+
+    let resp = loop {
+        match client.rollback_flag(project_id, &key, &req).await {
+            Err(FlagLiteError::ProtectedEnvironment(_)) if !req.confirm && output.can_prompt() => {
+                if !confirm_protected(env, &format!("Roll back '{key}'"))? {
+                    output.info("Rollback cancelled.");
+                    return Ok(());
+                }
+                req.confirm = true;
+            }
+            Err(FlagLiteError::ReasonRequired(_))
+                if req.reason.is_none() && output.can_prompt() =>
+            {
+                req.reason = Some(ask_reason(env)?);
+            }
+            result => break result?,
+        }
+    };
+
+    if output.is_json() {
+        return output.json(&resp);
+    }
+
+    let state = if resp.current.enabled { "on" } else { "off" };
+    if resp.changed {
+        output.success(&format!(
+            "Flag '{key}' rolled back to version {} in {env}: {state}, {}% (now version {})",
+            resp.restored_version, resp.current.rollout_percentage, resp.current.version
+        ));
+    } else {
+        output.info(&format!(
+            "Flag '{key}' already matches version {} in {env}: {state}, {}%",
+            resp.restored_version, resp.current.rollout_percentage
+        ));
+    }
+
+    Ok(())
+}
+
+/// Turn a flag on or off in the current environment
+///
+/// Unlike toggle, the outcome doesn't depend on the flag's current state.
+#[allow(clippy::too_many_arguments)]
+pub async fn set_state(
+    config: &Config,
+    output: &Output,
+    key: String,
+    enabled: bool,
+    value: Option<String>,
+    expected_version: Option<i64>,
+    confirm_production: bool,
+    reason: Option<String>,
+) -> Result<()> {
+    let value = parse_json_arg("--value", value)?;
+
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let env = config.get_environment();
+
+    let mut req = SetFlagStateRequest {
+        enabled,
+        environment: env.to_string(),
+        expected_version,
+        confirm: config.confirms_production(confirm_production),
+        value,
+        reason,
+    };
+    let action = if enabled { "Enable" } else { "Disable" };
+
+    let flag = loop {
+        match client.set_flag_state(project_id, &key, &req).await {
+            Err(FlagLiteError::ProtectedEnvironment(_)) if !req.confirm && output.can_prompt() => {
+                if !confirm_protected(env, &format!("{action} '{key}'"))? {
+                    output.info("Cancelled.");
+                    return Ok(());
+                }
+                req.confirm = true;
+            }
+            Err(FlagLiteError::ReasonRequired(_))
+                if req.reason.is_none() && output.can_prompt() =>
+            {
+                req.reason = Some(ask_reason(env)?);
+            }
+            result => break result?,
+        }
+    };
+
+    if output.is_json() {
+        return output.json(&flag);
+    }
+
+    let status = if flag.enabled { "enabled" } else { "disabled" };
+    output.success(&format!("Flag '{key}' is {status} in {env}"));
+
+    Ok(())
+}
+
+/// Which flags `--match` selects
+pub enum KeyPattern {
+    /// `*` matches any run of characters, `?` exactly one
+    Glob(String),
+    Regex(regex::Regex),
+}
+
+impl KeyPattern {
+    pub fn new(pattern: &str, regex: bool) -> Result<Self> {
+        if !regex {
+            return Ok(Self::Glob(pattern.to_string()));
+        }
+        regex::Regex::new(pattern).map(Self::Regex).map_err(|e| {
+            FlagLiteError::ValidationError(format!("Invalid --match regex: {e}")).into()
+        })
+    }
+
+    /// Globs must match the whole key; regexes match anywhere unless anchored
+    pub fn matches(&self, key: &str) -> bool {
+        match self {
+            Self::Glob(glob) => glob_matches(glob.as_bytes(), key.as_bytes()),
+            Self::Regex(regex) => regex.is_match(key),
+        }
+    }
+}
+
+fn glob_matches(glob: &[u8], key: &[u8]) -> bool {
+    let (mut g, mut k) = (0, 0);
+    // Where to resume after the last `*` if the rest fails to match
+    let mut backtrack = None;
+
+    while k < key.len() {
+        match glob.get(g) {
+            Some(b'*') => {
+                backtrack = Some((g, k));
+                g += 1;
+            }
+            Some(&c) if c == b'?' || c == key[k] => {
+                g += 1;
+                k += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    g = star + 1;
+                    k = matched + 1;
+                    backtrack = Some((star, k));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    glob[g..].iter().all(|&c| c == b'*')
+}
+
+/// What a bulk change does to each flag
+#[derive(Clone, Copy)]
+pub enum BulkChange {
+    Enabled(bool),
+    Rollout(i32),
+}
+
+impl BulkChange {
+    fn describe(self) -> String {
+        match self {
+            Self::Enabled(true) => "Turn on".to_string(),
+            Self::Enabled(false) => "Turn off".to_string(),
+            Self::Rollout(percentage) => format!("Set rollout to {percentage}% for"),
+        }
+    }
+}
+
+/// Apply one change to every flag whose key matches `pattern`
+///
+/// The server applies it in a single transaction, so either every matched
+/// flag changes or none does.
+#[allow(clippy::too_many_arguments)]
+pub async fn bulk_update(
+    config: &Config,
+    output: &Output,
+    pattern: String,
+    regex: bool,
+    change: BulkChange,
+    confirm_production: bool,
+    reason: Option<String>,
+) -> Result<()> {
+    let matcher = KeyPattern::new(&pattern, regex)?;
+
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let env = config.get_environment();
+
+    let mut flags: Vec<FlagWithState> = client
+        .list_flags(project_id, Some(env))
+        .await?
+        .into_iter()
+        .filter(|f| matcher.matches(&f.flag.key))
+        .collect();
+    flags.sort_by(|a, b| a.flag.key.cmp(&b.flag.key));
+
+    if flags.is_empty() {
+        return Err(FlagLiteError::ValidationError(format!("No flags match '{pattern}'")).into());
+    }
+
+    let action = format!("{} {} flag(s) in {env}", change.describe(), flags.len());
+
+    if config.asks(ConfirmAction::BulkUpdate) && !output.is_json() {
+        output.require_prompt(&config.confirm_hint(
+            ConfirmAction::BulkUpdate,
+            "Pass --yes to apply without confirmation.",
+        ))?;
+
+        output.info(&format!("Flags matching '{pattern}':"));
+        for f in &flags {
+            let state = if f.enabled { "on" } else { "off" };
+            println!("  {} ({state})", f.flag.key);
+        }
+
+        let confirmed = Confirm::new()
+            .with_prompt(format!("{action}?"))
+            .default(false)
+            .interact()?;
+
+        if !confirmed {
+            output.info("Cancelled.");
+            return Ok(());
+        }
+    }
+
+    let (enabled, rollout_percentage) = match change {
+        BulkChange::Enabled(enabled) => (Some(enabled), None),
+        BulkChange::Rollout(percentage) => (None, Some(percentage)),
+    };
+    let req = BatchUpdateFlagsRequest {
+        environment: env.to_string(),
+        keys: flags.into_iter().map(|f| f.flag.key).collect(),
+        enabled,
+        rollout_percentage,
+        confirm: config.confirms_production(confirm_production),
+        reason,
+    };
+
+    let Some(resp) = apply_batch(&client, output, project_id, req, &action).await? else {
+        output.info("Cancelled.");
+        return Ok(());
+    };
+
+    print_batch(output, &resp)
+}
+
+/// Set a single flag's rollout percentage in the current environment
+pub async fn rollout(
+    config: &Config,
+    output: &Output,
+    key: String,
+    percentage: i32,
+    confirm_production: bool,
+    reason: Option<String>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let env = config.get_environment();
+
+    let req = BatchUpdateFlagsRequest {
+        environment: env.to_string(),
+        keys: vec![key.clone()],
+        enabled: None,
+        rollout_percentage: Some(percentage),
+        confirm: config.confirms_production(confirm_production),
+        reason,
+    };
+    let action = format!("Set rollout of '{key}' to {percentage}%");
+
+    let Some(resp) = apply_batch(&client, output, project_id, req, &action).await? else {
+        output.info("Cancelled.");
+        return Ok(());
+    };
+
+    print_batch(output, &resp)
+}
+
+/// Send a batch update, asking before retrying in a protected environment
+/// and for a reason when the project requires one. Returns `None` if the
+/// user declines.
+async fn apply_batch(
+    client: &FlagLiteClient,
+    output: &Output,
+    project_id: &str,
+    mut req: BatchUpdateFlagsRequest,
+    action: &str,
+) -> Result<Option<BatchUpdateFlagsResponse>> {
+    loop {
+        match client.batch_update_flags(project_id, &req).await {
+            Err(FlagLiteError::ProtectedEnvironment(env))
+                if !req.confirm && output.can_prompt() =>
+            {
+                if !confirm_protected(&env, action)? {
+                    return Ok(None);
+                }
+                req.confirm = true;
+            }
+            Err(FlagLiteError::ReasonRequired(env))
+                if req.reason.is_none() && output.can_prompt() =>
+            {
+                req.reason = Some(ask_reason(&env)?);
+            }
+            result => return Ok(Some(result?)),
+        }
+    }
+}
+
+fn print_batch(output: &Output, resp: &BatchUpdateFlagsResponse) -> Result<()> {
+    if output.is_json() {
+        return output.json(resp);
+    }
+
+    for f in resp.flags.iter().filter(|f| f.changed) {
+        let state = if f.enabled { "on" } else { "off" };
+        println!("  {} → {state}, {}%", f.key, f.rollout);
+    }
+
+    let changed = resp.flags.iter().filter(|f| f.changed).count();
+    let unchanged = resp.flags.len() - changed;
+    let mut message = format!("Updated {changed} flag(s) in {}", resp.environment);
+    if unchanged > 0 {
+        message.push_str(&format!(" ({unchanged} already in that state)"));
+    }
+    output.success(&message);
+
     Ok(())
 }
 
+pub fn confirm_protected(env: &str, action: &str) -> Result<bool> {
+    Ok(Confirm::new()
+        .with_prompt(format!(
+            "'{env}' is a protected environment. {action} anyway?"
+        ))
+        .default(false)
+        .interact()?)
+}
+
+/// Ask for the reason a project requires for changes to `env`
+pub fn ask_reason(env: &str) -> Result<String> {
+    Ok(Input::new()
+        .with_prompt(format!("Reason for changing '{env}'"))
+        .validate_with(|reason: &String| {
+            if reason.trim().is_empty() {
+                Err("This project requires a reason")
+            } else {
+                Ok(())
+            }
+        })
+        .interact_text()?)
+}
+
 /// Delete a flag
-pub async fn delete(config: &Config, output: &Output, key: String, yes: bool) -> Result<()> {
+pub async fn delete(config: &Config, output: &Output, key: String) -> Result<()> {
     let client = client_from_config(config)?;
     let project_id = config.require_project()?;
 
-    // Confirm deletion unless --yes flag is provided
-    if !yes && !output.is_json() {
+    if config.asks(ConfirmAction::DeleteFlag) && !output.is_json() {
+        output.require_prompt(&config.confirm_hint(
+            ConfirmAction::DeleteFlag,
+            "Pass --yes to delete without confirmation.",
+        ))?;
+
         let confirmed = Confirm::new()
             .with_prompt(format!(
                 "Are you sure you want to delete flag '{key}'? This cannot be undone.",
@@ -149,3 +1042,411 @@ pub async fn delete(config: &Config, output: &Output, key: String, yes: bool) ->
 
     Ok(())
 }
+
+/// A single flag state change seen while watching
+#[derive(Debug, Serialize, PartialEq)]
+struct FlagChange {
+    event: &'static str,
+    key: String,
+    enabled: Option<bool>,
+    environment: String,
+    at: DateTime<Utc>,
+}
+
+/// Compare two polls and describe what changed, ordered by flag key
+fn diff_flags(
+    previous: &BTreeMap<String, bool>,
+    current: &BTreeMap<String, bool>,
+    environment: &str,
+    at: DateTime<Utc>,
+) -> Vec<FlagChange> {
+    let mut changes = Vec::new();
+
+    for (key, &enabled) in current {
+        let event = match previous.get(key) {
+            None => "created",
+            Some(&was) if was != enabled => {
+                if enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                }
+            }
+            Some(_) => continue,
+        };
+        changes.push(FlagChange {
+            event,
+            key: key.clone(),
+            enabled: Some(enabled),
+            environment: environment.to_string(),
+            at,
+        });
+    }
+
+    for key in previous.keys().filter(|k| !current.contains_key(*k)) {
+        changes.push(FlagChange {
+            event: "deleted",
+            key: key.clone(),
+            enabled: None,
+            environment: environment.to_string(),
+            at,
+        });
+    }
+
+    changes.sort_by(|a, b| a.key.cmp(&b.key));
+    changes
+}
+
+fn flag_states(flags: &[FlagWithState]) -> BTreeMap<String, bool> {
+    flags
+        .iter()
+        .map(|f| (f.flag.key.clone(), f.enabled))
+        .collect()
+}
+
+/// Poll flags and report changes until interrupted
+///
+/// Pretty output redraws the flag table on every poll; JSON output prints one
+/// compact object per change so it can be piped into `jq` or a log.
+pub async fn watch(config: &Config, output: &Output, interval: u64) -> Result<()> {
+    if interval == 0 {
+        return Err(
+            FlagLiteError::ValidationError("--interval must be at least 1 second".into()).into(),
+        );
+    }
+
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let env = config.get_environment();
+
+    // Fail fast on bad credentials or project before entering the loop
+    let flags = client.list_flags(project_id, Some(env)).await?;
+    let mut previous = flag_states(&flags);
+    let mut recent: Vec<FlagChange> = Vec::new();
+
+    if !output.is_json() {
+        redraw(output, env, &flags, &recent)?;
+    }
+
+    let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+    ticker.tick().await;
+
+    loop {
+        ticker.tick().await;
+
+        let flags = match client.list_flags(project_id, Some(env)).await {
+            Ok(flags) => flags,
+            Err(e) => {
+                // Keep watching through transient failures
+                if output.is_json() {
+                    eprintln!("{e}");
+                } else {
+                    output.warn(&format!("Poll failed, retrying: {e}"));
+                }
+                continue;
+            }
+        };
+
+        let current = flag_states(&flags);
+        let changes = diff_flags(&previous, &current, env, Utc::now());
+        previous = current;
+
+        if output.is_json() {
+            for change in &changes {
+                output.json_line(change)?;
+            }
+            continue;
+        }
+
+        recent.extend(changes);
+        let overflow = recent.len().saturating_sub(10);
+        recent.drain(..overflow);
+        redraw(output, env, &flags, &recent)?;
+    }
+}
+
+fn redraw(
+    output: &Output,
+    env: &str,
+    flags: &[FlagWithState],
+    recent: &[FlagChange],
+) -> Result<()> {
+    // Clear the screen and move the cursor home
+    print!("\x1B[2J\x1B[H");
+    output.info(&format!(
+        "Watching flags in environment: {env} (updated {}, Ctrl+C to stop)",
+        Utc::now().format("%H:%M:%S")
+    ));
+    output.print_flags(flags)?;
+
+    if !recent.is_empty() {
+        println!();
+        println!("Recent changes:");
+        for change in recent.iter().rev() {
+            println!(
+                "  {}  {:<8}  {}",
+                change.at.format("%H:%M:%S"),
+                change.event,
+                change.key
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// A watched flag's state in one environment
+#[derive(Debug, Clone, PartialEq)]
+struct WatchedState {
+    enabled: bool,
+    rollout: i32,
+    value: Option<serde_json::Value>,
+}
+
+/// A change to a watched flag, as `flags notify --format json` prints it
+#[derive(Debug, Serialize)]
+struct WatchedChange<'a> {
+    key: &'a str,
+    environment: &'a str,
+    change: String,
+    at: DateTime<Utc>,
+}
+
+fn watched_states(flags: &[FlagWithState], env: &str) -> BTreeMap<String, WatchedState> {
+    flags
+        .iter()
+        .map(|f| {
+            let state = WatchedState {
+                enabled: f.enabled,
+                rollout: f.environments.get(env).map_or(100, |state| state.rollout),
+                value: f.value.clone(),
+            };
+            (f.flag.key.clone(), state)
+        })
+        .collect()
+}
+
+/// What changed between two states of a flag, or None if nothing did
+fn describe_change(before: Option<&WatchedState>, after: Option<&WatchedState>) -> Option<String> {
+    let (before, after) = match (before, after) {
+        (Some(before), Some(after)) => (before, after),
+        (Some(_), None) => return Some("deleted".to_string()),
+        (None, Some(after)) => {
+            let state = if after.enabled { "on" } else { "off" };
+            return Some(format!("created, {state}"));
+        }
+        (None, None) => return None,
+    };
+
+    let mut parts = Vec::new();
+    if before.enabled != after.enabled {
+        parts.push(
+            if after.enabled {
+                "turned on"
+            } else {
+                "turned off"
+            }
+            .to_string(),
+        );
+    }
+    if before.rollout != after.rollout {
+        parts.push(format!("rollout {}% → {}%", before.rollout, after.rollout));
+    }
+    if before.value != after.value {
+        let value = after
+            .value
+            .as_ref()
+            .map_or_else(|| "none".to_string(), |v| v.to_string());
+        parts.push(format!("value → {value}"));
+    }
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+/// Poll the given flags and raise a desktop notification whenever one of them
+/// changes in the current environment, until interrupted
+pub async fn notify(
+    config: &Config,
+    output: &Output,
+    keys: Vec<String>,
+    interval: Duration,
+) -> Result<()> {
+    crate::notifications::check()?;
+
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let env = config.get_environment();
+
+    // Fail fast on bad credentials, project or keys before running unattended
+    let flags = client.list_flags(project_id, Some(env)).await?;
+    let mut previous = watched_states(&flags, env);
+    if let Some(key) = keys.iter().find(|key| !previous.contains_key(*key)) {
+        return Err(FlagLiteError::FlagNotFound {
+            key: key.clone(),
+            suggestions: Vec::new(),
+        }
+        .into());
+    }
+    previous.retain(|key, _| keys.contains(key));
+
+    output.info(&format!(
+        "Notifying on changes to {} in {env} every {}s (Ctrl+C to stop)",
+        keys.join(", "),
+        interval.as_secs()
+    ));
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+
+        // Keep watching through transient failures
+        let flags = match client.list_flags(project_id, Some(env)).await {
+            Ok(flags) => flags,
+            Err(e) => {
+                eprintln!("Poll failed, retrying: {e}");
+                continue;
+            }
+        };
+        let mut current = watched_states(&flags, env);
+        current.retain(|key, _| keys.contains(key));
+
+        for key in &keys {
+            let Some(change) = describe_change(previous.get(key), current.get(key)) else {
+                continue;
+            };
+            if output.is_json() {
+                output.json_line(&WatchedChange {
+                    key,
+                    environment: env,
+                    change: change.clone(),
+                    at: Utc::now(),
+                })?;
+            } else {
+                output.info(&format!(
+                    "{}  {key} in {env}: {change}",
+                    Utc::now().format("%H:%M:%S")
+                ));
+            }
+            if let Err(e) = crate::notifications::show(&format!("{key} changed in {env}"), &change)
+            {
+                eprintln!("Failed to show a notification: {e:#}");
+            }
+        }
+        previous = current;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_user_ids() {
+        assert_eq!(fixture_user_ids(3), ["user-1", "user-2", "user-3"]);
+        assert!(fixture_user_ids(0).is_empty());
+    }
+
+    fn states(entries: &[(&str, bool)]) -> BTreeMap<String, bool> {
+        entries.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_diff_reports_toggles_creates_and_deletes() {
+        let previous = states(&[("a", false), ("b", true), ("c", true)]);
+        let current = states(&[("a", true), ("b", true), ("d", false)]);
+
+        let events: Vec<_> = diff_flags(&previous, &current, "staging", Utc::now())
+            .into_iter()
+            .map(|c| (c.event, c.key))
+            .collect();
+
+        assert_eq!(
+            events,
+            vec![
+                ("enabled", "a".to_string()),
+                ("deleted", "c".to_string()),
+                ("created", "d".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_describe_change_names_what_changed() {
+        let state = |enabled, rollout, value: Option<serde_json::Value>| WatchedState {
+            enabled,
+            rollout,
+            value,
+        };
+        let before = state(true, 10, None);
+
+        assert_eq!(describe_change(Some(&before), Some(&before)), None);
+        assert_eq!(
+            describe_change(Some(&before), Some(&state(true, 25, None))).as_deref(),
+            Some("rollout 10% → 25%")
+        );
+        assert_eq!(
+            describe_change(
+                Some(&before),
+                Some(&state(false, 10, Some(serde_json::json!("blue"))))
+            )
+            .as_deref(),
+            Some("turned off, value → \"blue\"")
+        );
+        assert_eq!(
+            describe_change(None, Some(&before)).as_deref(),
+            Some("created, on")
+        );
+        assert_eq!(
+            describe_change(Some(&before), None).as_deref(),
+            Some("deleted")
+        );
+    }
+
+    #[test]
+    fn test_parse_expiry() {
+        assert_eq!(
+            parse_expiry("2026-12-31").unwrap().to_rfc3339(),
+            "2026-12-31T00:00:00+00:00"
+        );
+        assert_eq!(
+            parse_expiry("2026-12-31T18:00:00+02:00")
+                .unwrap()
+                .to_rfc3339(),
+            "2026-12-31T16:00:00+00:00"
+        );
+        assert!(parse_expiry("next week").is_err());
+    }
+
+    #[test]
+    fn test_glob_matches_whole_key() {
+        let glob = KeyPattern::new("checkout-*", false).unwrap();
+        assert!(glob.matches("checkout-v2"));
+        assert!(glob.matches("checkout-"));
+        assert!(!glob.matches("new-checkout-v2"));
+
+        let glob = KeyPattern::new("*-banner-?", false).unwrap();
+        assert!(glob.matches("spring-banner-1"));
+        assert!(glob.matches("a-banner-banner-2"));
+        assert!(!glob.matches("spring-banner-10"));
+
+        assert!(KeyPattern::new("exact", false).unwrap().matches("exact"));
+        assert!(!KeyPattern::new("exact", false).unwrap().matches("exactly"));
+    }
+
+    #[test]
+    fn test_regex_pattern() {
+        let regex = KeyPattern::new("^checkout-(v1|v2)$", true).unwrap();
+        assert!(regex.matches("checkout-v1"));
+        assert!(!regex.matches("checkout-v3"));
+        assert!(KeyPattern::new("checkout-(", true).is_err());
+    }
+
+    #[test]
+    fn test_diff_unchanged_is_empty() {
+        let flags = states(&[("a", true)]);
+        assert!(diff_flags(&flags, &flags, "dev", Utc::now()).is_empty());
+    }
+}
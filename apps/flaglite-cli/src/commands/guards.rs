@@ -0,0 +1,175 @@
+//! Guard commands: revert a flag change on the server if it turns out unhealthy
+
+use crate::config::Config;
+use crate::output::Output;
+use anyhow::Result;
+use flaglite_client::{
+    CreateGuardRequest, FlagLiteClient, FlagLiteError, Guard, GuardReport, GuardSpec,
+};
+use std::time::Duration;
+
+/// How long a guard watches when `--guard-window` isn't given
+const DEFAULT_WINDOW: Duration = Duration::from_secs(30 * 60);
+
+/// Create an authenticated client from config
+fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
+    let client = config.client()?;
+
+    // Prefer API key over token
+    if let Some(api_key) = &config.api_key {
+        Ok(client.with_api_key(api_key))
+    } else if let Some(token) = &config.token {
+        Ok(client.with_token(token))
+    } else {
+        Err(FlagLiteError::NotAuthenticated.into())
+    }
+}
+
+/// The guard asked for by `--guard [URL]` and `--guard-window`, if any
+pub fn spec(guard: Option<Option<String>>, window: Option<Duration>) -> Option<GuardSpec> {
+    guard.map(|url| GuardSpec {
+        url,
+        window_secs: window.unwrap_or(DEFAULT_WINDOW).as_secs() as i64,
+    })
+}
+
+/// What a guard watches and how it ended, in a sentence
+pub fn describe(guard: &Guard) -> String {
+    let watched = match &guard.ramp_id {
+        Some(ramp) => format!(
+            "ramp {ramp} of '{}' in {}",
+            guard.flag_key, guard.environment
+        ),
+        None => format!(
+            "version {} of '{}' in {}",
+            guard.version, guard.flag_key, guard.environment
+        ),
+    };
+    match (guard.status.as_str(), guard.watching_until) {
+        ("watching", Some(until)) => format!(
+            "Guarding {watched} until {}",
+            until.format("%Y-%m-%d %H:%M:%S UTC")
+        ),
+        ("watching", None) => format!("Guarding {watched} while the ramp runs"),
+        ("tripped", _) => format!(
+            "Tripped on {watched}: {}",
+            guard.message.as_deref().unwrap_or("unhealthy")
+        ),
+        (status, _) => format!("Guard on {watched} {status}"),
+    }
+}
+
+/// Tell the user where the new guard looks for trouble, and how to report it
+pub fn created(output: &Output, guard: &Guard) {
+    output.success(&format!("{} (guard {})", describe(guard), guard.id));
+    if let Some(url) = &guard.url {
+        output.info(&format!("Polling {url}"));
+    }
+    if let Some(token) = &guard.token {
+        output.info(&format!(
+            "Report trouble with: flaglite guards report {token} --message \"...\""
+        ));
+    }
+}
+
+/// Guard the version of `key` a change just produced in the current environment
+pub async fn watch(
+    config: &Config,
+    output: &Output,
+    key: &str,
+    version: i64,
+    spec: GuardSpec,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let req = CreateGuardRequest {
+        environment: config.get_environment().to_string(),
+        version: Some(version),
+        spec,
+    };
+    let guard = client.create_guard(project_id, key, &req).await?;
+
+    if output.is_json() {
+        return output.json(&guard);
+    }
+    created(output, &guard);
+    Ok(())
+}
+
+/// List the current project's guards
+pub async fn list(config: &Config, output: &Output, status: Option<String>) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let guards = client.list_guards(project_id, status.as_deref()).await?;
+    output.print_guards(&guards)
+}
+
+/// Stop a guard watching
+pub async fn cancel(config: &Config, output: &Output, id: String) -> Result<()> {
+    let client = client_from_config(config)?;
+
+    let guard = client.cancel_guard(&id).await?;
+
+    if output.is_json() {
+        return output.json(&guard);
+    }
+    output.success(&format!(
+        "Cancelled guard {}: {}",
+        guard.id,
+        describe(&guard)
+    ));
+    Ok(())
+}
+
+/// Push a health report with a guard's token; needs no login
+pub async fn report(
+    config: &Config,
+    output: &Output,
+    token: String,
+    healthy: bool,
+    message: Option<String>,
+) -> Result<()> {
+    let client = config.client()?;
+
+    let guard = client
+        .report_to_guard(&GuardReport {
+            token,
+            healthy,
+            message,
+        })
+        .await?;
+
+    if output.is_json() {
+        return output.json(&guard);
+    }
+    if guard.status == "tripped" {
+        output.success(&describe(&guard));
+    } else {
+        output.info(&describe(&guard));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spec() {
+        assert!(spec(None, None).is_none());
+
+        let push_only = spec(Some(None), None).unwrap();
+        assert_eq!(push_only.url, None);
+        assert_eq!(push_only.window_secs, 1800);
+
+        let polled = spec(
+            Some(Some("https://example.com/health".to_string())),
+            Some(Duration::from_secs(600)),
+        )
+        .unwrap();
+        assert_eq!(polled.url.as_deref(), Some("https://example.com/health"));
+        assert_eq!(polled.window_secs, 600);
+    }
+}
@@ -0,0 +1,90 @@
+//! Per-repo configuration
+
+use crate::commands::projects::resolve_project;
+use crate::config::{Config, RepoConfig, REPO_CONFIG_FILE};
+use crate::output::Output;
+use anyhow::{Context, Result};
+use flaglite_client::{FlagLiteClient, FlagLiteError};
+use std::path::{Path, PathBuf};
+
+/// Create an authenticated client from config
+fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
+    let client = config.client()?;
+
+    // Prefer API key over token
+    if let Some(api_key) = &config.api_key {
+        Ok(client.with_api_key(api_key))
+    } else if let Some(token) = &config.token {
+        Ok(client.with_token(token))
+    } else {
+        Err(FlagLiteError::NotAuthenticated.into())
+    }
+}
+
+/// Root of the git repository containing `dir`, or `dir` itself outside one
+pub fn repo_root(dir: &Path) -> PathBuf {
+    dir.ancestors()
+        .find(|d| d.join(".git").exists())
+        .unwrap_or(dir)
+        .to_path_buf()
+}
+
+/// Write .flaglite.toml pinning the current project, environment and API URL
+pub async fn init(config: &Config, output: &Output, force: bool) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project = config.require_project().context(
+        "Pick the project for this repo with 'flaglite init --project <id|slug>' or 'flaglite projects use' first",
+    )?;
+
+    // Store the full ID even if a slug or prefix was given
+    let project = resolve_project(&client, project).await?;
+    let project_id = project.id.to_string();
+
+    let env = config.get_environment();
+    let envs = client.list_environments(&project_id).await?;
+    if !envs.iter().any(|e| e.name == env || e.slug == env) {
+        return Err(
+            anyhow::Error::new(FlagLiteError::EnvironmentNotFound(env.to_string())).context(
+                format!(
+                    "Environment '{env}' not found in project {}. Run 'flaglite envs list' to see available environments.",
+                    project.name
+                ),
+            ),
+        );
+    }
+
+    let cwd = std::env::current_dir().context("Could not determine current directory")?;
+    let path = repo_root(&cwd).join(REPO_CONFIG_FILE);
+    if path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists. Use --force to overwrite it.",
+            path.display()
+        );
+    }
+
+    let repo = RepoConfig {
+        api_url: Some(config.api_url.clone()),
+        project_id: Some(project_id),
+        environment: Some(env.to_string()),
+        format: None,
+        scan_patterns: Default::default(),
+    };
+    repo.save(&path)?;
+
+    if output.is_json() {
+        output.json(&serde_json::json!({
+            "path": path,
+            "api_url": repo.api_url,
+            "project_id": repo.project_id,
+            "environment": repo.environment,
+        }))?;
+    } else {
+        output.success(&format!("Wrote {}", path.display()));
+        output.info(&format!(
+            "Commands run in this repo now use project {} ({env})",
+            project.name
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! API key commands
+
+use crate::config::Config;
+use crate::output::Output;
+use anyhow::Result;
+use flaglite_client::{CreateApiKeyRequest, FlagLiteClient, FlagLiteError};
+
+/// Create an authenticated client from config
+fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
+    let client = config.client()?;
+
+    // Prefer API key over token
+    if let Some(api_key) = &config.api_key {
+        Ok(client.with_api_key(api_key))
+    } else if let Some(token) = &config.token {
+        Ok(client.with_token(token))
+    } else {
+        Err(FlagLiteError::NotAuthenticated.into())
+    }
+}
+
+/// List the user's API keys
+pub async fn list(config: &Config, output: &Output) -> Result<()> {
+    let client = client_from_config(config)?;
+    let keys = client.list_api_keys().await?;
+    output.print_api_keys(&keys)
+}
+
+/// Create an API key, e.g. a read-only one for a dashboard
+pub async fn create(
+    config: &Config,
+    output: &Output,
+    name: Option<String>,
+    scope: Option<String>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let key = client
+        .create_api_key(&CreateApiKeyRequest { name, scope })
+        .await?;
+    output.print_created_key(&key)
+}
@@ -1,6 +1,17 @@
 //! CLI command implementations
 
+pub mod account;
 pub mod auth;
+pub mod change_requests;
+pub mod config;
 pub mod envs;
 pub mod flags;
+pub mod guards;
+pub mod init;
+pub mod keys;
+pub mod orgs;
 pub mod projects;
+pub mod ramps;
+pub mod scan;
+pub mod sync;
+pub mod templates;
@@ -0,0 +1,185 @@
+//! Organization commands
+
+use crate::config::Config;
+use crate::output::Output;
+use anyhow::Result;
+use flaglite_client::{
+    AddOrgMemberRequest, CreateApiKeyRequest, CreateOrgRequest, FlagLiteClient, FlagLiteError,
+    OrgMember, Organization, UpdateOrgMemberRequest,
+};
+
+/// Create an authenticated client from config
+fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
+    let client = config.client()?;
+
+    // Prefer API key over token
+    if let Some(api_key) = &config.api_key {
+        Ok(client.with_api_key(api_key))
+    } else if let Some(token) = &config.token {
+        Ok(client.with_token(token))
+    } else {
+        Err(FlagLiteError::NotAuthenticated.into())
+    }
+}
+
+/// Find an org by full ID, exact name, or ID prefix
+fn find_org<'a>(orgs: &'a [Organization], org: &str) -> Option<&'a Organization> {
+    orgs.iter()
+        .find(|o| o.id == org || o.name == org)
+        .or_else(|| orgs.iter().find(|o| o.id.starts_with(org)))
+}
+
+/// The current org, or an error telling the user to pick one
+fn require_org(config: &Config) -> Result<&str> {
+    config.org_id.as_deref().ok_or_else(|| {
+        anyhow::anyhow!("No organization selected. Run 'flaglite orgs switch <org>' first.")
+    })
+}
+
+/// List organizations the user belongs to
+pub async fn list(config: &Config, output: &Output) -> Result<()> {
+    let client = client_from_config(config)?;
+    let orgs = client.list_orgs().await?;
+
+    output.print_orgs(&orgs, config.org_id.as_deref())?;
+
+    Ok(())
+}
+
+/// Create an organization and switch to it
+pub async fn create(config: &mut Config, output: &Output, name: String) -> Result<()> {
+    let client = client_from_config(config)?;
+    let org = client.create_org(CreateOrgRequest { name }).await?;
+
+    config.org_id = Some(org.id.clone());
+    config.save()?;
+
+    if output.is_json() {
+        output.json(&org)?;
+    } else {
+        output.success(&format!("Created organization {} ({})", org.name, org.id));
+        output.info("New projects will be created in it. Invite teammates with: flaglite orgs add-member <username>");
+    }
+
+    Ok(())
+}
+
+/// Set the current organization
+pub async fn switch(config: &mut Config, output: &Output, org: String) -> Result<()> {
+    let client = client_from_config(config)?;
+    let orgs = client.list_orgs().await?;
+
+    let found = find_org(&orgs, &org).ok_or_else(|| {
+        anyhow::anyhow!(
+            "Organization '{org}' not found. Run 'flaglite orgs list' to see your organizations."
+        )
+    })?;
+
+    config.org_id = Some(found.id.clone());
+    config.save()?;
+
+    output.success(&format!("Now using organization: {}", found.name));
+    if !output.is_json() {
+        output.info("Pick a project with: flaglite projects use <project>");
+    }
+
+    Ok(())
+}
+
+/// List members of the current organization
+pub async fn members(config: &Config, output: &Output) -> Result<()> {
+    let client = client_from_config(config)?;
+    let members = client.list_org_members(require_org(config)?).await?;
+
+    output.print_org_members(&members)?;
+
+    Ok(())
+}
+
+/// Add an existing user to the current organization
+pub async fn add_member(
+    config: &Config,
+    output: &Output,
+    username: String,
+    role: Option<String>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let member = client
+        .add_org_member(require_org(config)?, AddOrgMemberRequest { username, role })
+        .await?;
+
+    if output.is_json() {
+        output.json(&member)?;
+    } else {
+        output.success(&format!("Added {} as {}", member.username, member.role));
+    }
+
+    Ok(())
+}
+
+/// The current org's member with this username or user ID
+async fn find_member(client: &FlagLiteClient, org_id: &str, user: &str) -> Result<OrgMember> {
+    client
+        .list_org_members(org_id)
+        .await?
+        .into_iter()
+        .find(|m| m.username == user || m.user_id == user)
+        .ok_or_else(|| anyhow::anyhow!("'{user}' isn't a member of this organization"))
+}
+
+/// Change the role of a member of the current organization
+pub async fn set_role(
+    config: &Config,
+    output: &Output,
+    username: String,
+    role: String,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let org_id = require_org(config)?;
+    let member = find_member(&client, org_id, &username).await?;
+    let member = client
+        .update_org_member(org_id, &member.user_id, &UpdateOrgMemberRequest { role })
+        .await?;
+
+    if output.is_json() {
+        output.json(&member)?;
+    } else {
+        output.success(&format!("{} is now {}", member.username, member.role));
+    }
+
+    Ok(())
+}
+
+/// Remove a member from the current organization
+pub async fn remove_member(config: &Config, output: &Output, username: String) -> Result<()> {
+    let client = client_from_config(config)?;
+    let org_id = require_org(config)?;
+    let member = find_member(&client, org_id, &username).await?;
+    let member = client.remove_org_member(org_id, &member.user_id).await?;
+
+    if output.is_json() {
+        output.json(&member)?;
+    } else {
+        output.success(&format!(
+            "Removed {} from the organization",
+            member.username
+        ));
+    }
+
+    Ok(())
+}
+
+/// Create an API key that only reaches the current organization's projects
+pub async fn create_key(
+    config: &Config,
+    output: &Output,
+    name: Option<String>,
+    scope: Option<String>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let key = client
+        .create_org_api_key(require_org(config)?, &CreateApiKeyRequest { name, scope })
+        .await?;
+
+    output.print_created_key(&key)
+}
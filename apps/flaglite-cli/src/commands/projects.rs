@@ -3,11 +3,16 @@
 use crate::config::Config;
 use crate::output::Output;
 use anyhow::Result;
-use flaglite_client::{CreateProjectRequest, FlagLiteClient};
+use flaglite_client::{
+    CloneProjectRequest, CreateInviteLinkRequest, CreateProjectRequest, FlagLiteClient,
+    FlagLiteError, Project, UpdateProjectRequest,
+};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 /// Create an authenticated client from config
 fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
-    let client = FlagLiteClient::new(&config.api_url);
+    let client = config.client()?;
 
     // Prefer API key over token
     if let Some(api_key) = &config.api_key {
@@ -15,16 +20,14 @@ fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
     } else if let Some(token) = &config.token {
         Ok(client.with_token(token))
     } else {
-        Err(anyhow::anyhow!(
-            "Not logged in. Run `flaglite signup` or `flaglite login`"
-        ))
+        Err(FlagLiteError::NotAuthenticated.into())
     }
 }
 
 /// List all projects
 pub async fn list(config: &Config, output: &Output) -> Result<()> {
     let client = client_from_config(config)?;
-    let projects = client.list_projects().await?;
+    let projects = client.list_projects(config.org_id.as_deref()).await?;
 
     output.print_projects(&projects, config.project_id.as_deref())?;
 
@@ -40,7 +43,11 @@ pub async fn create(
 ) -> Result<()> {
     let client = client_from_config(config)?;
 
-    let req = CreateProjectRequest { name, description };
+    let req = CreateProjectRequest {
+        name,
+        description,
+        org_id: config.org_id.clone(),
+    };
     let project = client.create_project(req).await?;
 
     output.print_project(&project)?;
@@ -55,27 +62,404 @@ pub async fn create(
     Ok(())
 }
 
+/// Find a project by full ID or slug, resolved by the server, or by ID prefix
+pub async fn resolve_project(client: &FlagLiteClient, project: &str) -> Result<Project> {
+    match client.get_project(project).await {
+        Ok(found) => return Ok(found),
+        Err(FlagLiteError::ProjectNotFound(_)) => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let projects = client.list_projects(None).await?;
+    projects
+        .into_iter()
+        .find(|p| p.id.to_string().starts_with(project))
+        .ok_or_else(|| {
+            anyhow::Error::new(FlagLiteError::ProjectNotFound(project.to_string())).context(
+                format!(
+                    "Project '{project}' not found. Run 'flaglite projects list' to see available projects.",
+                ),
+            )
+        })
+}
+
+/// Clone a project's environments and flags into a new project
+pub async fn clone(
+    config: &Config,
+    output: &Output,
+    project: String,
+    name: String,
+    with_values: bool,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let source = resolve_project(&client, &project).await?;
+
+    let req = CloneProjectRequest {
+        name,
+        include_values: with_values,
+    };
+    let cloned = output
+        .with_spinner(
+            "Cloning project...",
+            client.clone_project(&source.id.to_string(), req),
+        )
+        .await?;
+
+    output.print_project(&cloned)?;
+
+    if !output.is_json() {
+        output.info(&format!(
+            "Cloned from {}. Set as default with: flaglite projects use {}",
+            source.slug, cloned.slug
+        ));
+    }
+
+    Ok(())
+}
+
+/// Download a project as a `.tar.gz` archive
+pub async fn export(
+    config: &Config,
+    output: &Output,
+    project: Option<String>,
+    file: Option<PathBuf>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project = match project {
+        Some(project) => resolve_project(&client, &project).await?,
+        None => client.get_project(config.require_project()?).await?,
+    };
+
+    let archive = output
+        .with_spinner(
+            "Exporting project...",
+            client.export_project(&project.id.to_string()),
+        )
+        .await?;
+    let file = file.unwrap_or_else(|| PathBuf::from(format!("{}.tar.gz", project.slug)));
+    std::fs::write(&file, &archive).map_err(|e| {
+        FlagLiteError::ValidationError(format!("Cannot write {}: {e}", file.display()))
+    })?;
+
+    if output.is_json() {
+        output.json(&serde_json::json!({
+            "project_id": project.id,
+            "slug": project.slug,
+            "file": file,
+            "bytes": archive.len(),
+        }))?;
+    } else {
+        output.success(&format!(
+            "Exported {} to {} ({} bytes)",
+            project.slug,
+            file.display(),
+            archive.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Create a project from an archive made by `projects export`
+pub async fn import(
+    config: &Config,
+    output: &Output,
+    file: &Path,
+    name: Option<String>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let archive = std::fs::read(file).map_err(|e| {
+        FlagLiteError::ValidationError(format!("Cannot read {}: {e}", file.display()))
+    })?;
+
+    let project = output
+        .with_spinner(
+            "Importing project...",
+            client.import_project(archive, name.as_deref(), config.org_id.as_deref()),
+        )
+        .await?;
+
+    output.print_project(&project)?;
+
+    if !output.is_json() {
+        output.info(&format!(
+            "Imported from {}. Set as default with: flaglite projects use {}",
+            file.display(),
+            project.slug
+        ));
+    }
+
+    Ok(())
+}
+
+/// Show flag counts per environment for a project
+pub async fn stats(config: &Config, output: &Output, project: Option<String>) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project = match project {
+        Some(project) => resolve_project(&client, &project).await?.id.to_string(),
+        None => config.require_project()?.to_string(),
+    };
+
+    let stats = client.project_stats(&project).await?;
+    output.print_project_stats(&stats)?;
+
+    Ok(())
+}
+
+/// Change a project's settings
+pub async fn update(
+    config: &Config,
+    output: &Output,
+    project: Option<String>,
+    require_change_reason: Option<bool>,
+) -> Result<()> {
+    if require_change_reason.is_none() {
+        return Err(FlagLiteError::ValidationError(
+            "Nothing to update. Pass --require-change-reason".to_string(),
+        )
+        .into());
+    }
+
+    let client = client_from_config(config)?;
+    let project = match project {
+        Some(project) => resolve_project(&client, &project).await?.id.to_string(),
+        None => config.require_project()?.to_string(),
+    };
+
+    let req = UpdateProjectRequest {
+        require_change_reason,
+    };
+    let updated = client.update_project(&project, &req).await?;
+
+    if output.is_json() {
+        return output.json(&updated);
+    }
+
+    let reasons = if updated.require_change_reason {
+        "required"
+    } else {
+        "optional"
+    };
+    output.success(&format!(
+        "Updated project {}: reasons for changes to protected environments are {reasons}",
+        updated.slug
+    ));
+
+    Ok(())
+}
+
 /// Set the default project
 pub async fn use_project(config: &mut Config, output: &Output, project: String) -> Result<()> {
     let client = client_from_config(config)?;
-    let projects = client.list_projects().await?;
+    let p = resolve_project(&client, &project).await?;
 
-    let found = projects.iter().find(|p| {
-        p.id.to_string() == project || p.slug == project || p.id.to_string().starts_with(&project)
-    });
+    config.project_id = Some(p.id.to_string());
+    config.save()?;
+    if let Some(warning) = config.save_project_to_credentials()? {
+        output.warn(&warning);
+    }
+    output.success(&format!("Now using project: {} ({})", p.name, p.slug));
+
+    Ok(())
+}
+
+/// The project named on the command line, else the current one
+async fn project_or_current(
+    client: &FlagLiteClient,
+    config: &Config,
+    project: Option<String>,
+) -> Result<String> {
+    match project {
+        Some(project) => Ok(resolve_project(client, &project).await?.id.to_string()),
+        None => Ok(config.require_project()?.to_string()),
+    }
+}
 
-    match found {
-        Some(p) => {
-            config.project_id = Some(p.id.to_string());
-            config.save()?;
-            output.success(&format!("Now using project: {} ({})", p.name, p.slug));
-        }
-        None => {
-            return Err(anyhow::anyhow!(
-                "Project '{project}' not found. Run 'flaglite projects list' to see available projects.",
-            ));
-        }
+/// Parse how long an invite link works: `14d`, or an interval like `12h`
+pub fn parse_expiry(s: &str) -> std::result::Result<Duration, String> {
+    match s.strip_suffix('d') {
+        Some(days) => days
+            .parse::<u64>()
+            .ok()
+            .filter(|days| *days > 0)
+            .map(|days| Duration::from_secs(days * 24 * 60 * 60))
+            .ok_or_else(|| format!("expected a duration like 12h or 14d, got '{s}'")),
+        None => super::sync::parse_interval(s),
     }
+}
+
+/// The token in an invite link, or the token itself
+fn join_token(link: &str) -> &str {
+    let link = link.trim().trim_end_matches('/');
+    link.rsplit('/').next().unwrap_or(link)
+}
+
+/// Create an invite link that shares a project with whoever redeems it
+pub async fn share(
+    config: &Config,
+    output: &Output,
+    project: Option<String>,
+    role: Option<String>,
+    expires: Option<Duration>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project = project_or_current(&client, config, project).await?;
+
+    let req = CreateInviteLinkRequest {
+        role,
+        expires_in_secs: expires.map(|d| d.as_secs() as i64),
+    };
+    let link = client.create_invite_link(&project, &req).await?;
+
+    if output.is_json() {
+        return output.json(&link);
+    }
+
+    let token = link.token.as_deref().unwrap_or_default();
+    output.success(&format!(
+        "Created invite link {} ({}, expires {})",
+        link.id,
+        link.role,
+        link.expires_at.format("%Y-%m-%d %H:%M:%S UTC")
+    ));
+    println!("{}/v1/join/{token}", config.api_url.trim_end_matches('/'));
+    output.info(&format!(
+        "Anyone signed in can join with: flaglite join {token}"
+    ));
 
     Ok(())
 }
+
+/// List a project's invite links
+pub async fn links(config: &Config, output: &Output, project: Option<String>) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project = project_or_current(&client, config, project).await?;
+
+    let links = client.list_invite_links(&project).await?;
+    output.print_invite_links(&links)
+}
+
+/// Stop an invite link working
+pub async fn revoke_link(
+    config: &Config,
+    output: &Output,
+    project: Option<String>,
+    id: String,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project = project_or_current(&client, config, project).await?;
+
+    let link = client.revoke_invite_link(&project, &id).await?;
+
+    if output.is_json() {
+        return output.json(&link);
+    }
+    output.success(&format!(
+        "Revoked invite link {} after {} use(s); users who joined keep access",
+        link.id, link.uses
+    ));
+
+    Ok(())
+}
+
+/// List the users a project was shared with by invite link
+pub async fn members(config: &Config, output: &Output, project: Option<String>) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project = project_or_current(&client, config, project).await?;
+
+    let members = client.list_project_members(&project).await?;
+    output.print_project_members(&members)
+}
+
+/// Take back the access an invite link gave someone
+pub async fn remove_member(
+    config: &Config,
+    output: &Output,
+    project: Option<String>,
+    user: String,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project = project_or_current(&client, config, project).await?;
+
+    let members = client.list_project_members(&project).await?;
+    let Some(member) = members
+        .iter()
+        .find(|m| m.username == user || m.user_id == user)
+    else {
+        anyhow::bail!(
+            "'{user}' didn't join this project with an invite link (see `flaglite projects members`)"
+        );
+    };
+    let removed = client
+        .remove_project_member(&project, &member.user_id)
+        .await?;
+
+    if output.is_json() {
+        return output.json(&removed);
+    }
+    output.success(&format!(
+        "Removed {} ({}) from the project",
+        removed.username, removed.role
+    ));
+
+    Ok(())
+}
+
+/// Redeem an invite link and switch to its project
+pub async fn join(config: &mut Config, output: &Output, link: String) -> Result<()> {
+    let client = client_from_config(config)?;
+    let joined = client.join_project(join_token(&link)).await?;
+    let project = &joined.project;
+
+    config.project_id = Some(project.id.to_string());
+    config.save()?;
+    if let Some(warning) = config.save_project_to_credentials()? {
+        output.warn(&warning);
+    }
+
+    if output.is_json() {
+        return output.json(&joined);
+    }
+
+    match (&joined.role, joined.joined) {
+        (Some(role), true) => output.success(&format!(
+            "Joined project {} ({}) as {role}",
+            project.name, project.slug
+        )),
+        _ => output.success(&format!(
+            "You already have project {} ({})",
+            project.name, project.slug
+        )),
+    }
+    output.info(&format!(
+        "Now using project: {} ({})",
+        project.name, project.slug
+    ));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_expiry() {
+        assert_eq!(
+            parse_expiry("14d"),
+            Ok(Duration::from_secs(14 * 24 * 60 * 60))
+        );
+        assert_eq!(parse_expiry("12h"), Ok(Duration::from_secs(12 * 60 * 60)));
+        assert!(parse_expiry("0d").is_err());
+        assert!(parse_expiry("d").is_err());
+    }
+
+    #[test]
+    fn test_join_token() {
+        assert_eq!(join_token("ffl_join_abc"), "ffl_join_abc");
+        assert_eq!(
+            join_token("https://api.flaglite.dev/v1/join/ffl_join_abc/"),
+            "ffl_join_abc"
+        );
+    }
+}
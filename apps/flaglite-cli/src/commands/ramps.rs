@@ -0,0 +1,166 @@
+//! Ramp commands: raise a flag's rollout step by step on the server
+
+use crate::commands::flags::{ask_reason, confirm_protected};
+use crate::commands::guards;
+use crate::config::Config;
+use crate::output::Output;
+use anyhow::Result;
+use flaglite_client::{
+    CreateRampRequest, FlagLiteClient, FlagLiteError, GuardSpec, Ramp, RampAction,
+};
+use std::time::Duration;
+
+/// Create an authenticated client from config
+fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
+    let client = config.client()?;
+
+    // Prefer API key over token
+    if let Some(api_key) = &config.api_key {
+        Ok(client.with_api_key(api_key))
+    } else if let Some(token) = &config.token {
+        Ok(client.with_token(token))
+    } else {
+        Err(FlagLiteError::NotAuthenticated.into())
+    }
+}
+
+/// `secs` as the largest whole unit, like 90s, 15m or 2h
+pub fn format_interval(secs: i64) -> String {
+    match secs {
+        s if s % 3600 == 0 => format!("{}h", s / 3600),
+        s if s % 60 == 0 => format!("{}m", s / 60),
+        s => format!("{s}s"),
+    }
+}
+
+/// Where a ramp has got to, in a sentence
+pub fn describe(ramp: &Ramp) -> String {
+    let progress = format!(
+        "'{}' in {} is at {}% of {}%",
+        ramp.flag_key, ramp.environment, ramp.current_percentage, ramp.target_percentage
+    );
+    match (ramp.status.as_str(), ramp.next_step_at) {
+        ("active", Some(at)) => format!(
+            "{progress}; next step of {} points at {} (every {})",
+            ramp.step_percentage,
+            at.format("%Y-%m-%d %H:%M:%S UTC"),
+            format_interval(ramp.interval_secs)
+        ),
+        (status, _) => format!("{progress} ({status})"),
+    }
+}
+
+/// Start ramping a flag in the current environment, optionally guarded so the
+/// ramp is aborted and reverted if it turns out unhealthy
+#[allow(clippy::too_many_arguments)]
+pub async fn start(
+    config: &Config,
+    output: &Output,
+    key: String,
+    to: i32,
+    step: i32,
+    interval: Duration,
+    confirm_production: bool,
+    reason: Option<String>,
+    guard: Option<GuardSpec>,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let mut req = CreateRampRequest {
+        environment: config.get_environment().to_string(),
+        target_percentage: to,
+        step_percentage: step,
+        interval_secs: interval.as_secs() as i64,
+        confirm: config.confirms_production(confirm_production),
+        reason,
+        guard,
+    };
+    let action = format!("Ramp '{key}' to {to}%");
+    let ramp = loop {
+        match client.create_ramp(project_id, &key, &req).await {
+            Err(FlagLiteError::ProtectedEnvironment(env))
+                if !req.confirm && output.can_prompt() =>
+            {
+                if !confirm_protected(&env, &action)? {
+                    output.info("Cancelled.");
+                    return Ok(());
+                }
+                req.confirm = true;
+            }
+            Err(FlagLiteError::ReasonRequired(env))
+                if req.reason.is_none() && output.can_prompt() =>
+            {
+                req.reason = Some(ask_reason(&env)?);
+            }
+            result => break result?,
+        }
+    };
+
+    if output.is_json() {
+        return output.json(&ramp);
+    }
+    output.success(&format!("Started ramp {}: {}", ramp.id, describe(&ramp)));
+    if let Some(guard) = &ramp.guard {
+        guards::created(output, guard);
+    }
+    Ok(())
+}
+
+/// List the current project's ramps
+pub async fn list(config: &Config, output: &Output, status: Option<String>) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let ramps = client.list_ramps(project_id, status.as_deref()).await?;
+    output.print_ramps(&ramps)
+}
+
+/// Show how far a ramp has got
+pub async fn status(config: &Config, output: &Output, id: String) -> Result<()> {
+    let client = client_from_config(config)?;
+
+    let ramp = client.get_ramp(&id).await?;
+
+    if output.is_json() {
+        return output.json(&ramp);
+    }
+    println!("{}", describe(&ramp));
+    Ok(())
+}
+
+/// Pause, resume or abort a ramp
+pub async fn control(
+    config: &Config,
+    output: &Output,
+    id: String,
+    action: RampAction,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+
+    let ramp = client.control_ramp(&id, action).await?;
+
+    if output.is_json() {
+        return output.json(&ramp);
+    }
+    let done = match action {
+        RampAction::Pause => "Paused",
+        RampAction::Resume => "Resumed",
+        RampAction::Abort => "Aborted",
+    };
+    output.success(&format!("{done} ramp {}: {}", ramp.id, describe(&ramp)));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_interval() {
+        assert_eq!(format_interval(90), "90s");
+        assert_eq!(format_interval(900), "15m");
+        assert_eq!(format_interval(7200), "2h");
+        assert_eq!(format_interval(5400), "90m");
+    }
+}
@@ -0,0 +1,337 @@
+//! `flags scan`: find the flags source code uses and compare with the server
+//!
+//! Each language has patterns for the SDK calls that read a flag, with the key
+//! as the first capture group. Keys the patterns find that the project doesn't
+//! have were likely deleted while still in use. Flags the project has that no
+//! file mentions are candidates for deletion; to keep that list conservative,
+//! a key counts as used wherever it appears as a quoted string, not only in a
+//! pattern match.
+//!
+//! A repository's .flaglite.toml can replace the patterns per file extension:
+//!
+//! ```toml
+//! [scan_patterns]
+//! rb = ['flags\.enabled\?\(\s*"([A-Za-z0-9_-]+)"']
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use flaglite_client::{FlagLiteClient, FlagLiteError};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::config::{Config, RepoConfig};
+use crate::output::Output;
+
+/// Directories that hold dependencies or build output rather than the
+/// project's own code
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "vendor", "dist", "build"];
+
+/// Larger files are generated or bundled, and skipped
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// SDK calls per file extension; see the SDK docs for the method names
+const DEFAULT_PATTERNS: &[(&[&str], &str)] = &[
+    (
+        &["rs"],
+        r#"\b(?:enabled|enabled_for_user|is_enabled|evaluate)\s*\(\s*"([A-Za-z0-9_-]+)""#,
+    ),
+    (
+        &["go"],
+        r#"\b(?:Enabled|EnabledWithError|Evaluate)\s*\(\s*"([A-Za-z0-9_-]+)""#,
+    ),
+    (
+        &["go"],
+        r#"\bEnabledWithContext\s*\(\s*\w+\s*,\s*"([A-Za-z0-9_-]+)""#,
+    ),
+    (
+        &["py"],
+        r#"\b(?:enabled|enabled_sync|enabled_for_user|evaluate)\s*\(\s*['"]([A-Za-z0-9_-]+)['"]"#,
+    ),
+    (
+        &["js", "jsx", "mjs", "cjs", "ts", "tsx"],
+        r#"\b(?:enabled|isEnabled|evaluate|useFlag)\s*\(\s*['"`]([A-Za-z0-9_-]+)['"`]"#,
+    ),
+];
+
+/// Where a key was found
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct Location {
+    pub path: String,
+    pub line: usize,
+}
+
+/// A flag key and where code uses it
+#[derive(Debug, Clone, Serialize)]
+pub struct ScannedFlag {
+    pub key: String,
+    pub locations: Vec<Location>,
+}
+
+/// How the code and the project's flags compare
+#[derive(Debug, Serialize)]
+pub struct ScanReport {
+    pub files_scanned: usize,
+    /// Used in code and present in the project
+    pub referenced: Vec<ScannedFlag>,
+    /// Used in code but not in the project: deleted, or never created
+    pub missing_on_server: Vec<ScannedFlag>,
+    /// In the project but not found in any scanned file
+    pub unreferenced: Vec<String>,
+}
+
+/// Compiled patterns by file extension
+struct Patterns(BTreeMap<String, Vec<Regex>>);
+
+impl Patterns {
+    /// The defaults, with each extension in `overrides` replaced
+    fn new(overrides: &BTreeMap<String, Vec<String>>) -> Result<Self> {
+        let mut sources: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (extensions, pattern) in DEFAULT_PATTERNS {
+            for ext in *extensions {
+                sources
+                    .entry(ext.to_string())
+                    .or_default()
+                    .push(pattern.to_string());
+            }
+        }
+        for (ext, patterns) in overrides {
+            sources.insert(ext.trim_start_matches('.').to_string(), patterns.clone());
+        }
+
+        let mut compiled = BTreeMap::new();
+        for (ext, patterns) in sources {
+            let regexes = patterns
+                .iter()
+                .map(|pattern| {
+                    let regex = Regex::new(pattern).map_err(|e| {
+                        FlagLiteError::ValidationError(format!(
+                            "Invalid scan pattern for .{ext} files: {e}"
+                        ))
+                    })?;
+                    if regex.captures_len() < 2 {
+                        return Err(FlagLiteError::ValidationError(format!(
+                            "Scan pattern for .{ext} files needs a capture group for the key: {pattern}"
+                        )));
+                    }
+                    Ok(regex)
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            compiled.insert(ext, regexes);
+        }
+        Ok(Self(compiled))
+    }
+
+    fn for_file(&self, path: &Path) -> Option<&[Regex]> {
+        let ext = path.extension()?.to_str()?;
+        self.0.get(ext).map(Vec::as_slice)
+    }
+}
+
+/// Compare the flag keys used under `src` with the current project's flags
+pub async fn scan(config: &Config, output: &Output, src: PathBuf) -> Result<()> {
+    if !src.is_dir() {
+        return Err(FlagLiteError::ValidationError(format!(
+            "{} is not a directory",
+            src.display()
+        ))
+        .into());
+    }
+
+    let overrides = match &config.repo_config {
+        Some(path) => RepoConfig::load(path)?.scan_patterns,
+        None => BTreeMap::new(),
+    };
+    let patterns = Patterns::new(&overrides)?;
+
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let flags = client.list_flags(project_id, None).await?;
+    let known: BTreeSet<String> = flags.into_iter().map(|f| f.flag.key).collect();
+
+    let mut files = Vec::new();
+    collect_files(&src, &patterns, &mut files)?;
+    files.sort();
+
+    let mut found: BTreeMap<String, Vec<Location>> = BTreeMap::new();
+    let mut mentioned = BTreeSet::new();
+    let mut files_scanned = 0;
+    for path in &files {
+        // Binary or non-UTF-8 files can't hold a key we'd recognize
+        let Ok(content) = fs::read_to_string(path) else {
+            continue;
+        };
+        files_scanned += 1;
+        let display = path
+            .strip_prefix(&src)
+            .unwrap_or(path)
+            .display()
+            .to_string();
+        let regexes = patterns.for_file(path).unwrap_or_default();
+        for (key, line) in find_keys(&content, regexes) {
+            found.entry(key).or_default().push(Location {
+                path: display.clone(),
+                line,
+            });
+        }
+        mentioned.extend(known.iter().filter(|key| quotes(&content, key)).cloned());
+    }
+
+    let (referenced, missing_on_server): (Vec<ScannedFlag>, Vec<ScannedFlag>) = found
+        .into_iter()
+        .map(|(key, locations)| ScannedFlag { key, locations })
+        .partition(|flag| known.contains(&flag.key));
+    let unreferenced = known
+        .into_iter()
+        .filter(|key| !mentioned.contains(key))
+        .filter(|key| !referenced.iter().any(|f| &f.key == key))
+        .collect();
+
+    output.print_scan_report(&ScanReport {
+        files_scanned,
+        referenced,
+        missing_on_server,
+        unreferenced,
+    })
+}
+
+/// Files under `dir` that some pattern applies to, skipping hidden and
+/// dependency directories
+fn collect_files(dir: &Path, patterns: &Patterns, files: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry?;
+        let path = entry.path();
+        let name = entry.file_name();
+        let name = name.to_string_lossy();
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            if !name.starts_with('.') && !SKIPPED_DIRS.contains(&name.as_ref()) {
+                collect_files(&path, patterns, files)?;
+            }
+        } else if file_type.is_file()
+            && patterns.for_file(&path).is_some()
+            && entry.metadata()?.len() <= MAX_FILE_BYTES
+        {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Keys the patterns match in `content`, with their 1-based line numbers
+fn find_keys(content: &str, patterns: &[Regex]) -> Vec<(String, usize)> {
+    let mut keys = Vec::new();
+    for pattern in patterns {
+        for captures in pattern.captures_iter(content) {
+            let Some(key) = captures.get(1) else {
+                continue;
+            };
+            let line = content[..key.start()].matches('\n').count() + 1;
+            keys.push((key.as_str().to_string(), line));
+        }
+    }
+    keys.sort_by_key(|(_, line)| *line);
+    keys
+}
+
+/// `key` appears in `content` as a quoted string
+fn quotes(content: &str, key: &str) -> bool {
+    ['"', '\'', '`']
+        .iter()
+        .any(|q| content.contains(&format!("{q}{key}{q}")))
+}
+
+/// Create an authenticated client from config
+fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
+    let client = config.client()?;
+
+    if let Some(api_key) = &config.api_key {
+        Ok(client.with_api_key(api_key))
+    } else if let Some(token) = &config.token {
+        Ok(client.with_token(token))
+    } else {
+        Err(FlagLiteError::NotAuthenticated.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn defaults() -> Patterns {
+        Patterns::new(&BTreeMap::new()).unwrap()
+    }
+
+    fn keys(patterns: &Patterns, file: &str, content: &str) -> Vec<(String, usize)> {
+        find_keys(
+            content,
+            patterns.for_file(Path::new(file)).unwrap_or_default(),
+        )
+    }
+
+    #[test]
+    fn test_default_patterns_find_sdk_calls() {
+        let patterns = defaults();
+        assert_eq!(
+            keys(
+                &patterns,
+                "main.rs",
+                "let on = client.enabled(\"new-checkout\").await;\nlet label = \"not-a-flag\";\n",
+            ),
+            vec![("new-checkout".to_string(), 1)]
+        );
+        assert_eq!(
+            keys(
+                &patterns,
+                "app.tsx",
+                "\nif (useFlag('dark_mode')) {}\nflags.enabled(`beta`)",
+            ),
+            vec![("dark_mode".to_string(), 2), ("beta".to_string(), 3)]
+        );
+        assert_eq!(
+            keys(
+                &patterns,
+                "main.go",
+                r#"client.EnabledWithContext(ctx, "my-flag")"#
+            ),
+            vec![("my-flag".to_string(), 1)]
+        );
+        assert!(patterns.for_file(Path::new("README.md")).is_none());
+    }
+
+    #[test]
+    fn test_repo_patterns_replace_the_defaults_per_extension() {
+        let overrides = BTreeMap::from([
+            (
+                "rb".to_string(),
+                vec![r#"flags\.enabled\?\(\s*"([A-Za-z0-9_-]+)""#.to_string()],
+            ),
+            (
+                ".py".to_string(),
+                vec![r#"feature\("([a-z-]+)"\)"#.to_string()],
+            ),
+        ]);
+        let patterns = Patterns::new(&overrides).unwrap();
+
+        assert_eq!(
+            keys(&patterns, "app.rb", r#"if flags.enabled?("beta")"#),
+            vec![("beta".to_string(), 1)]
+        );
+        assert!(keys(&patterns, "app.py", r#"flags.enabled("beta")"#).is_empty());
+        assert_eq!(keys(&patterns, "app.py", r#"feature("beta")"#).len(), 1);
+
+        let without_group = BTreeMap::from([("rb".to_string(), vec!["enabled".to_string()])]);
+        assert!(Patterns::new(&without_group).is_err());
+    }
+
+    #[test]
+    fn test_quotes_needs_the_whole_key() {
+        assert!(quotes("const FLAG = 'new-checkout';", "new-checkout"));
+        assert!(!quotes("\"new-checkout-v2\"", "new-checkout"));
+    }
+}
@@ -0,0 +1,267 @@
+//! `sync`: keep a local JSON file in step with an environment's flags
+//!
+//! For apps with no SDK: they read the file at startup, and again whenever
+//! the process is sent SIGHUP. The file is only rewritten when a flag
+//! changed, and always by writing a temporary file next to it and renaming it
+//! over the old one, so a reader never sees half a snapshot. It has no
+//! timestamp, so identical flags always produce identical bytes.
+//!
+//! ```json
+//! {
+//!   "project": "my-project",
+//!   "environment": "production",
+//!   "flags": {
+//!     "new-checkout": {
+//!       "enabled": true,
+//!       "rollout_percentage": 25,
+//!       "value": null
+//!     }
+//!   }
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use flaglite_client::{FlagLiteClient, FlagLiteError, FlagWithState};
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::output::Output;
+
+/// What to signal when the file changes
+pub enum SignalTarget {
+    Pid(u32),
+    /// Read on every change, so a restarted process is still found
+    PidFile(PathBuf),
+}
+
+/// The file's contents
+#[derive(Debug, Serialize)]
+struct Snapshot<'a> {
+    project: &'a str,
+    environment: &'a str,
+    flags: BTreeMap<&'a str, SnapshotFlag<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct SnapshotFlag<'a> {
+    enabled: bool,
+    rollout_percentage: i32,
+    value: &'a Option<serde_json::Value>,
+}
+
+/// Parse an interval like `30`, `30s`, `5m` or `1h` (bare numbers are seconds)
+pub fn parse_interval(s: &str) -> std::result::Result<Duration, String> {
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("expected a duration like 30s, 5m or 1h, got '{s}'"))?;
+    let seconds = match unit {
+        "s" => number,
+        "m" => number.saturating_mul(60),
+        "h" => number.saturating_mul(60 * 60),
+        _ => return Err(format!("unknown unit '{unit}'; use s, m or h")),
+    };
+    if seconds == 0 {
+        return Err("the interval must be at least 1 second".to_string());
+    }
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Write the current environment's flags to `out`, then again every
+/// `interval` whenever they change, until interrupted (or once with `once`)
+pub async fn sync(
+    config: &Config,
+    output: &Output,
+    out: PathBuf,
+    interval: Duration,
+    signal: Option<SignalTarget>,
+    once: bool,
+) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+    let env = config.get_environment();
+
+    // Fail fast on bad credentials, project or path before running unattended
+    let flags = client.list_flags(project_id, Some(env)).await?;
+    let changed = write_if_changed(&out, &render(project_id, env, &flags)?)?;
+    report(output, &out, flags.len(), changed);
+    if changed {
+        send_signal(output, signal.as_ref());
+    }
+    if once {
+        return Ok(());
+    }
+
+    output.info(&format!(
+        "Syncing {env} to {} every {}s (Ctrl+C to stop)",
+        out.display(),
+        interval.as_secs()
+    ));
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        }
+
+        // The file keeps the last good snapshot until the server is back
+        let flags = match client.list_flags(project_id, Some(env)).await {
+            Ok(flags) => flags,
+            Err(e) => {
+                eprintln!("Sync failed, keeping the last snapshot: {e}");
+                continue;
+            }
+        };
+        let written =
+            render(project_id, env, &flags).and_then(|json| write_if_changed(&out, &json));
+        match written {
+            Ok(true) => {
+                report(output, &out, flags.len(), true);
+                send_signal(output, signal.as_ref());
+            }
+            Ok(false) => {}
+            Err(e) => eprintln!("Failed to write {}: {e:#}", out.display()),
+        }
+    }
+}
+
+/// The snapshot file for `flags`, ending in a newline
+fn render(project: &str, environment: &str, flags: &[FlagWithState]) -> Result<String> {
+    let snapshot = Snapshot {
+        project,
+        environment,
+        flags: flags
+            .iter()
+            .map(|f| {
+                let rollout_percentage = f
+                    .environments
+                    .get(environment)
+                    .map_or(100, |state| state.rollout);
+                let flag = SnapshotFlag {
+                    enabled: f.enabled,
+                    rollout_percentage,
+                    value: &f.value,
+                };
+                (f.flag.key.as_str(), flag)
+            })
+            .collect(),
+    };
+    Ok(serde_json::to_string_pretty(&snapshot)? + "\n")
+}
+
+/// Atomically replace `path` with `contents` unless it already holds them;
+/// returns whether it was written
+fn write_if_changed(path: &Path, contents: &str) -> Result<bool> {
+    if fs::read(path).is_ok_and(|existing| existing == contents.as_bytes()) {
+        return Ok(false);
+    }
+
+    // Same directory, so the rename can't cross filesystems
+    let name = path
+        .file_name()
+        .with_context(|| format!("{} is not a file path", path.display()))?;
+    let tmp = path.with_file_name(format!(".{}.tmp", name.to_string_lossy()));
+    fs::write(&tmp, contents).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| {
+        let _ = fs::remove_file(&tmp);
+        format!("Failed to replace {}", path.display())
+    })?;
+    Ok(true)
+}
+
+fn report(output: &Output, out: &Path, count: usize, changed: bool) {
+    if changed {
+        output.success(&format!("Wrote {count} flag(s) to {}", out.display()));
+    } else {
+        output.info(&format!("{} is up to date", out.display()));
+    }
+}
+
+/// Send SIGHUP to the target, warning rather than failing if it's gone
+fn send_signal(output: &Output, target: Option<&SignalTarget>) {
+    let pid = match target {
+        None => return,
+        Some(SignalTarget::Pid(pid)) => *pid,
+        Some(SignalTarget::PidFile(path)) => {
+            let pid = fs::read_to_string(path).map(|pid| pid.trim().parse::<u32>());
+            match pid {
+                Ok(Ok(pid)) => pid,
+                Ok(Err(_)) => {
+                    eprintln!("Not signalling: {} holds no PID", path.display());
+                    return;
+                }
+                Err(e) => {
+                    eprintln!("Not signalling: failed to read {}: {e}", path.display());
+                    return;
+                }
+            }
+        }
+    };
+
+    match Command::new("kill")
+        .args(["-HUP", &pid.to_string()])
+        .status()
+    {
+        Ok(status) if status.success() => output.info(&format!("Sent SIGHUP to {pid}")),
+        Ok(_) => eprintln!("Failed to send SIGHUP to {pid}"),
+        Err(e) => eprintln!("Failed to run kill: {e}"),
+    }
+}
+
+/// Create an authenticated client from config
+fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
+    let client = config.client()?;
+
+    if let Some(api_key) = &config.api_key {
+        Ok(client.with_api_key(api_key))
+    } else if let Some(token) = &config.token {
+        Ok(client.with_token(token))
+    } else {
+        Err(FlagLiteError::NotAuthenticated.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_interval_units() {
+        assert_eq!(parse_interval("30"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_interval("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_interval("5m"), Ok(Duration::from_secs(300)));
+        assert_eq!(parse_interval("1h"), Ok(Duration::from_secs(3600)));
+        assert!(parse_interval("0s").is_err());
+        assert!(parse_interval("5d").is_err());
+        assert!(parse_interval("s").is_err());
+        assert!(parse_interval("").is_err());
+    }
+
+    #[test]
+    fn test_write_if_changed_only_writes_new_contents() {
+        let dir = std::env::temp_dir().join(format!("flaglite-sync-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("flags.json");
+        let _ = fs::remove_file(&path);
+
+        assert!(write_if_changed(&path, "{}\n").unwrap());
+        assert!(!write_if_changed(&path, "{}\n").unwrap());
+        assert!(write_if_changed(&path, "{\"a\": 1}\n").unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{\"a\": 1}\n");
+        // Nothing is left behind next to the file
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
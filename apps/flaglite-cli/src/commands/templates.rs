@@ -0,0 +1,126 @@
+//! Flag template commands
+
+use crate::config::Config;
+use crate::output::Output;
+use anyhow::{Context, Result};
+use flaglite_client::{FlagLiteClient, FlagLiteError, SetTemplateRequest, TemplateState};
+use std::collections::BTreeMap;
+
+/// Create an authenticated client from config
+fn client_from_config(config: &Config) -> Result<FlagLiteClient> {
+    let client = config.client()?;
+
+    // Prefer API key over token
+    if let Some(api_key) = &config.api_key {
+        Ok(client.with_api_key(api_key))
+    } else if let Some(token) = &config.token {
+        Ok(client.with_token(token))
+    } else {
+        Err(FlagLiteError::NotAuthenticated.into())
+    }
+}
+
+/// Parse `on`, `off` or `on:<rollout>`
+fn parse_state(spec: &str) -> Result<TemplateState> {
+    let (state, rollout) = match spec.split_once(':') {
+        Some((state, rollout)) => {
+            let rollout = rollout
+                .trim()
+                .trim_end_matches('%')
+                .parse()
+                .with_context(|| format!("Invalid rollout in '{spec}'"))?;
+            (state, rollout)
+        }
+        None => (spec, 100),
+    };
+    let enabled = match state.trim().to_lowercase().as_str() {
+        "on" | "true" => true,
+        "off" | "false" => false,
+        _ => {
+            return Err(FlagLiteError::ValidationError(format!(
+                "Invalid state '{spec}'. Use on, off or on:<rollout %>"
+            ))
+            .into())
+        }
+    };
+    Ok(TemplateState { enabled, rollout })
+}
+
+/// List built-in and stored templates
+pub async fn list(config: &Config, output: &Output) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let templates = client.list_templates(project_id).await?;
+    output.print_templates(&templates)
+}
+
+/// Create or replace a template
+pub async fn set(
+    config: &Config,
+    output: &Output,
+    name: String,
+    description: Option<String>,
+    default: Option<String>,
+    envs: Vec<String>,
+) -> Result<()> {
+    let default = match default {
+        Some(spec) => parse_state(&spec)?,
+        None => parse_state("off")?,
+    };
+    let mut environments = BTreeMap::new();
+    for spec in envs {
+        let (env, state) = spec.split_once('=').ok_or_else(|| {
+            FlagLiteError::ValidationError(format!(
+                "Invalid --state '{spec}'. Use <environment>=on, off or on:<rollout %>"
+            ))
+        })?;
+        environments.insert(env.trim().to_string(), parse_state(state)?);
+    }
+
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    let req = SetTemplateRequest {
+        description,
+        default,
+        environments,
+    };
+    let template = client.set_template(project_id, &name, req).await?;
+
+    if output.is_json() {
+        return output.json(&template);
+    }
+    output.success(&format!("Saved template '{}'", template.name));
+    Ok(())
+}
+
+/// Delete a stored template
+pub async fn delete(config: &Config, output: &Output, name: String) -> Result<()> {
+    let client = client_from_config(config)?;
+    let project_id = config.require_project()?;
+
+    client.delete_template(project_id, &name).await?;
+    output.success(&format!("Deleted template '{name}'"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_state() {
+        let on = parse_state("on").unwrap();
+        assert!(on.enabled);
+        assert_eq!(on.rollout, 100);
+
+        let ramp = parse_state("on:25%").unwrap();
+        assert!(ramp.enabled);
+        assert_eq!(ramp.rollout, 25);
+
+        assert!(!parse_state("OFF").unwrap().enabled);
+        assert!(parse_state("maybe").is_err());
+        assert!(parse_state("on:lots").is_err());
+    }
+}
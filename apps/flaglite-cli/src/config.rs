@@ -1,12 +1,22 @@
 //! Configuration management for FlagLite CLI
 
+use crate::secrets;
+use crate::timings::Timings;
 use anyhow::{Context, Result};
+use flaglite_client::{FlagLiteClient, FlagLiteError};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::PathBuf;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
 
 const DEFAULT_API_URL: &str = "https://api.flaglite.dev";
 
+/// Per-repo config file, discovered by walking up from the working directory
+pub const REPO_CONFIG_FILE: &str = ".flaglite.toml";
+
 /// CLI configuration stored in ~/.config/flaglite/config.toml
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
@@ -30,15 +40,272 @@ pub struct Config {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub project_id: Option<String>,
 
+    /// Current organization; None shows projects from all of them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<String>,
+
     /// Default environment
     #[serde(skip_serializing_if = "Option::is_none")]
     pub environment: Option<String>,
+
+    /// Extra CA certificate bundle (PEM) to trust, e.g. for TLS-intercepting proxies
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_cert: Option<PathBuf>,
+
+    /// Proxy URL for all API requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+
+    /// Output format used when --format isn't given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+
+    /// Where `login` keeps the API key and token
+    #[serde(default, skip_serializing_if = "CredentialsBackend::is_file")]
+    pub credentials_backend: CredentialsBackend,
+
+    /// Which operations ask for confirmation, and which of those --yes may skip
+    #[serde(default, skip_serializing_if = "ConfirmPolicy::is_default")]
+    pub confirm: ConfirmPolicy,
+
+    /// --yes was given
+    #[serde(skip)]
+    pub yes: bool,
+
+    /// The API key and token were read from the OS keyring
+    #[serde(skip)]
+    pub secrets_in_keyring: bool,
+
+    /// Log every HTTP request to stderr (--verbose)
+    #[serde(skip)]
+    pub verbose: bool,
+
+    /// Record every HTTP request for the --timings summary
+    #[serde(skip)]
+    pub timings: Option<Arc<Timings>>,
+
+    /// The .flaglite.toml in effect, if any
+    #[serde(skip)]
+    pub repo_config: Option<PathBuf>,
+
+    /// Why the credentials file was ignored, if it couldn't be parsed
+    #[serde(skip)]
+    pub credentials_warning: Option<String>,
+
+    /// User-level values the repo config overrode, so saving doesn't copy
+    /// one repo's settings into every other repo
+    #[serde(skip)]
+    user_values: RepoConfig,
+}
+
+/// Where the API key and token are stored
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CredentialsBackend {
+    /// ~/.flaglite/credentials.json, readable only by the user
+    #[default]
+    File,
+    /// The OS credential store; other credentials stay in the file
+    Keyring,
+}
+
+impl CredentialsBackend {
+    fn is_file(&self) -> bool {
+        *self == CredentialsBackend::File
+    }
+}
+
+/// An operation that asks before going ahead
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfirmAction {
+    /// `flags delete`
+    DeleteFlag,
+    /// `envs delete`
+    DeleteEnvironment,
+    /// Changing every flag a `--match` selects
+    BulkUpdate,
+    /// Retrying a change a protected environment rejected
+    ProtectedEnvironment,
+}
+
+impl ConfirmAction {
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfirmAction::DeleteFlag => "delete_flag",
+            ConfirmAction::DeleteEnvironment => "delete_environment",
+            ConfirmAction::BulkUpdate => "bulk_update",
+            ConfirmAction::ProtectedEnvironment => "protected_environment",
+        }
+    }
+}
+
+/// The `[confirm]` table of the user config
+///
+/// Each operation set to false goes ahead without asking. `yes` lists the
+/// ones --yes may skip; the rest still ask, or fail when nobody can answer,
+/// so automation passing --yes only gets past the prompts allowed here.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConfirmPolicy {
+    pub delete_flag: bool,
+    pub delete_environment: bool,
+    pub bulk_update: bool,
+    pub protected_environment: bool,
+    pub yes: Vec<ConfirmAction>,
+}
+
+impl Default for ConfirmPolicy {
+    /// Everything asks; --yes skips all but the protected environment prompt
+    fn default() -> Self {
+        Self {
+            delete_flag: true,
+            delete_environment: true,
+            bulk_update: true,
+            protected_environment: true,
+            yes: vec![
+                ConfirmAction::DeleteFlag,
+                ConfirmAction::DeleteEnvironment,
+                ConfirmAction::BulkUpdate,
+            ],
+        }
+    }
+}
+
+impl ConfirmPolicy {
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Whether to ask before `action`, given whether --yes was passed
+    pub fn asks(&self, action: ConfirmAction, yes: bool) -> bool {
+        let required = match action {
+            ConfirmAction::DeleteFlag => self.delete_flag,
+            ConfirmAction::DeleteEnvironment => self.delete_environment,
+            ConfirmAction::BulkUpdate => self.bulk_update,
+            ConfirmAction::ProtectedEnvironment => self.protected_environment,
+        };
+        required && !(yes && self.yes.contains(&action))
+    }
+}
+
+/// Settings pinned for one repository in .flaglite.toml (written by `flaglite init`)
+///
+/// Each key that is set overrides the user config for commands run anywhere
+/// inside the repository.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct RepoConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// `flags scan` patterns by file extension, replacing the defaults for it
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub scan_patterns: BTreeMap<String, Vec<String>>,
+}
+
+/// Keys `flaglite config get/set/unset` accept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigKey {
+    ApiUrl,
+    Project,
+    Environment,
+    Format,
+}
+
+impl ConfigKey {
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigKey::ApiUrl => "api_url",
+            ConfigKey::Project => "project",
+            ConfigKey::Environment => "environment",
+            ConfigKey::Format => "format",
+        }
+    }
+}
+
+impl FromStr for ConfigKey {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "_").as_str() {
+            "api_url" => Ok(ConfigKey::ApiUrl),
+            "project" | "project_id" => Ok(ConfigKey::Project),
+            "environment" | "env" => Ok(ConfigKey::Environment),
+            "format" => Ok(ConfigKey::Format),
+            _ => Err(format!(
+                "Unknown config key: {s}. Use 'api_url', 'project', 'environment' or 'format'."
+            )),
+        }
+    }
+}
+
+impl RepoConfig {
+    /// Pin `key` to `value`, or stop pinning it with None
+    pub fn set(&mut self, key: ConfigKey, value: Option<String>) {
+        match key {
+            ConfigKey::ApiUrl => self.api_url = value,
+            ConfigKey::Project => self.project_id = value,
+            ConfigKey::Environment => self.environment = value,
+            ConfigKey::Format => self.format = value,
+        }
+    }
+
+    /// Nearest .flaglite.toml in `dir` or one of its parents
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors()
+            .map(|d| d.join(REPO_CONFIG_FILE))
+            .find(|path| path.is_file())
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = toml::to_string_pretty(self).context("Failed to serialize repo config")?;
+        fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
 }
 
 fn default_api_url() -> String {
     DEFAULT_API_URL.to_string()
 }
 
+/// Replace `path` with `content` atomically: write a temp file beside it, only
+/// readable by the owner (0600), and rename it into place
+fn write_private(path: &Path, content: &str) -> std::io::Result<()> {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", std::process::id()));
+    let tmp = path.with_file_name(name);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let written = options.open(&tmp).and_then(|mut file| {
+        file.write_all(content.as_bytes())?;
+        file.sync_all()
+    });
+    match written.and_then(|()| fs::rename(&tmp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&tmp);
+            Err(e)
+        }
+    }
+}
+
 /// Credentials stored in ~/.flaglite/credentials.json
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Credentials {
@@ -81,8 +348,67 @@ impl Config {
         Ok(Self::credentials_dir()?.join("credentials.json"))
     }
 
+    /// Lock serializing writers of the credentials file across processes,
+    /// held until the returned file is dropped
+    ///
+    /// A separate file, since writes replace credentials.json.
+    fn lock_credentials(shared: bool) -> Result<fs::File> {
+        let dir = Self::credentials_dir()?;
+        fs::create_dir_all(&dir).with_context(|| {
+            format!("Failed to create credentials directory: {}", dir.display())
+        })?;
+
+        let path = dir.join("credentials.lock");
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let locked = if shared {
+            file.lock_shared()
+        } else {
+            file.lock()
+        };
+        locked.with_context(|| format!("Failed to lock {}", path.display()))?;
+        Ok(file)
+    }
+
+    fn read_credentials(path: &Path) -> Result<Option<Credentials>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read credentials from {}", path.display()))?;
+        let creds = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse credentials from {}", path.display()))?;
+        Ok(Some(creds))
+    }
+
     /// Load config from disk, or return defaults
     pub fn load() -> Result<Self> {
+        let mut config = Self::load_user()?;
+
+        // Repo config overrides the user's config and credentials
+        let cwd = std::env::current_dir().context("Could not determine current directory")?;
+        if let Some(path) = RepoConfig::find(&cwd) {
+            config.apply_repo_config(path)?;
+        }
+
+        // Apply env var overrides for API key
+        if let Ok(key) = std::env::var("FLAGLITE_API_KEY") {
+            if !key.is_empty() {
+                config.api_key = Some(key);
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Load only the user config and credentials, ignoring .flaglite.toml
+    /// and environment overrides
+    pub fn load_user() -> Result<Self> {
         let path = Self::config_path()?;
 
         let mut config = if path.exists() {
@@ -97,13 +423,6 @@ impl Config {
         // Load credentials
         config.load_credentials()?;
 
-        // Apply env var overrides for API key
-        if let Ok(key) = std::env::var("FLAGLITE_API_KEY") {
-            if !key.is_empty() {
-                config.api_key = Some(key);
-            }
-        }
-
         Ok(config)
     }
 
@@ -111,19 +430,53 @@ impl Config {
     fn load_credentials(&mut self) -> Result<()> {
         let path = Self::credentials_path()?;
 
-        if !path.exists() {
+        // Writes are atomic, so a bad parse is a file damaged some other way
+        // (or by an older CLI); read it once more under the lock before giving
+        // up, then carry on logged out so `login` can replace it
+        let creds = match Self::read_credentials(&path) {
+            Ok(creds) => creds,
+            Err(_) => {
+                let _lock = Self::lock_credentials(true)?;
+                match Self::read_credentials(&path) {
+                    Ok(creds) => creds,
+                    Err(e) => {
+                        self.credentials_warning = Some(format!(
+                            "Ignoring unreadable credentials file {} ({:#}). Run 'flaglite login' to replace it.",
+                            path.display(),
+                            e
+                        ));
+                        None
+                    }
+                }
+            }
+        };
+        let Some(creds) = creds else {
             return Ok(());
-        }
-
-        let content = fs::read_to_string(&path)
-            .with_context(|| format!("Failed to read credentials from {}", path.display()))?;
-
-        let creds: Credentials = serde_json::from_str(&content)
-            .with_context(|| format!("Failed to parse credentials from {}", path.display()))?;
+        };
 
         self.token = creds.token;
         self.api_key = creds.api_key;
         self.username = creds.username;
+
+        // Secrets saved to the file before switching backends keep working
+        // until the next login moves them
+        if self.credentials_backend == CredentialsBackend::Keyring
+            && self.token.is_none()
+            && self.api_key.is_none()
+        {
+            match secrets::load() {
+                Ok(stored) => {
+                    self.secrets_in_keyring = stored.api_key.is_some() || stored.token.is_some();
+                    self.api_key = stored.api_key;
+                    self.token = stored.token;
+                }
+                Err(e) => {
+                    self.credentials_warning = Some(format!(
+                        "Could not read credentials from the OS keyring ({e:#}). Run 'flaglite login' to sign in again."
+                    ));
+                }
+            }
+        }
         self.project_id = creds.project_id;
 
         // Use api_url from credentials if set
@@ -134,8 +487,68 @@ impl Config {
         Ok(())
     }
 
+    fn apply_repo_config(&mut self, path: PathBuf) -> Result<()> {
+        let repo = RepoConfig::load(&path)?;
+
+        if let Some(api_url) = repo.api_url {
+            self.user_values.api_url = Some(std::mem::replace(&mut self.api_url, api_url));
+        }
+        if let Some(project_id) = repo.project_id {
+            self.user_values.project_id = self.project_id.replace(project_id);
+        }
+        if let Some(environment) = repo.environment {
+            self.user_values.environment = self.environment.replace(environment);
+        }
+        if let Some(format) = repo.format {
+            self.user_values.format = self.format.replace(format);
+        }
+
+        self.repo_config = Some(path);
+        Ok(())
+    }
+
+    /// Split into the user-level config and, inside a repo, the updated repo
+    /// config: keys the repo pins are saved back to its .flaglite.toml
+    fn split_repo_config(&self) -> Result<(Config, Option<(&Path, RepoConfig)>)> {
+        let Some(path) = &self.repo_config else {
+            return Ok((self.clone(), None));
+        };
+
+        let mut user = self.clone();
+        let mut repo = RepoConfig::load(path)?;
+        if repo.api_url.is_some() {
+            repo.api_url = Some(self.api_url.clone());
+            user.api_url = self
+                .user_values
+                .api_url
+                .clone()
+                .unwrap_or_else(default_api_url);
+        }
+        if repo.project_id.is_some() {
+            repo.project_id = self.project_id.clone();
+            user.project_id = self.user_values.project_id.clone();
+        }
+        if repo.environment.is_some() {
+            repo.environment = self.environment.clone();
+            user.environment = self.user_values.environment.clone();
+        }
+        if repo.format.is_some() {
+            repo.format = self.format.clone();
+            user.format = self.user_values.format.clone();
+        }
+
+        Ok((user, Some((path.as_path(), repo))))
+    }
+
     /// Save config to disk (not credentials)
+    ///
+    /// Inside a repo with .flaglite.toml, the keys it pins are written there instead.
     pub fn save(&self) -> Result<()> {
+        let (user, repo) = self.split_repo_config()?;
+        if let Some((path, repo)) = repo {
+            repo.save(path)?;
+        }
+
         let dir = Self::config_dir()?;
         let path = Self::config_path()?;
 
@@ -145,7 +558,7 @@ impl Config {
                 .with_context(|| format!("Failed to create config directory: {}", dir.display()))?;
         }
 
-        let content = toml::to_string_pretty(self).context("Failed to serialize config")?;
+        let content = toml::to_string_pretty(&user).context("Failed to serialize config")?;
 
         fs::write(&path, content)
             .with_context(|| format!("Failed to write config to {}", path.display()))?;
@@ -153,48 +566,85 @@ impl Config {
         Ok(())
     }
 
-    /// Save credentials to ~/.flaglite/credentials.json
-    pub fn save_credentials(&self) -> Result<()> {
-        let dir = Self::credentials_dir()?;
+    /// Update the default project kept with the credentials, which take
+    /// precedence over config.toml when loading
+    ///
+    /// Reloads the stored credentials first, so an API key from the
+    /// environment isn't written to disk.
+    pub fn save_project_to_credentials(&self) -> Result<Option<String>> {
+        if !Self::credentials_path()?.exists() {
+            return Ok(None);
+        }
+        let (user, _) = self.split_repo_config()?;
+        let mut stored = Self::load_user()?;
+        stored.project_id = user.project_id;
+        stored.save_credentials()
+    }
+
+    /// Save credentials to ~/.flaglite/credentials.json, with the API key and
+    /// token in the OS keyring instead if that's the configured backend
+    ///
+    /// Safe to run from parallel processes: writers take turns, and each
+    /// replaces the file in one rename so readers never see half of it.
+    ///
+    /// Returns a warning if the keyring couldn't be used and the secrets went
+    /// to the file after all.
+    pub fn save_credentials(&self) -> Result<Option<String>> {
+        let _lock = Self::lock_credentials(false)?;
         let path = Self::credentials_path()?;
 
-        // Create directory if needed
-        if !dir.exists() {
-            fs::create_dir_all(&dir).with_context(|| {
-                format!("Failed to create credentials directory: {}", dir.display())
-            })?;
-        }
+        // Keep a default project another process saved meanwhile; a damaged
+        // file is simply replaced
+        let existing = Self::read_credentials(&path)
+            .ok()
+            .flatten()
+            .unwrap_or_default();
 
-        let creds = Credentials {
-            api_url: Some(self.api_url.clone()),
+        let (user, _) = self.split_repo_config()?;
+        let mut creds = Credentials {
+            api_url: Some(user.api_url),
             api_key: self.api_key.clone(),
             username: self.username.clone(),
             token: self.token.clone(),
-            project_id: self.project_id.clone(),
+            project_id: user.project_id.or(existing.project_id),
         };
 
+        let mut warning = None;
+        if self.credentials_backend == CredentialsBackend::Keyring {
+            let stored = secrets::store(&secrets::Secrets {
+                api_key: creds.api_key.clone(),
+                token: creds.token.clone(),
+            });
+            match stored {
+                Ok(()) => {
+                    creds.api_key = None;
+                    creds.token = None;
+                }
+                Err(e) => {
+                    warning = Some(format!(
+                        "Could not use the OS keyring ({e:#}); saved the credentials to {} instead",
+                        path.display()
+                    ));
+                }
+            }
+        }
+
         let content =
             serde_json::to_string_pretty(&creds).context("Failed to serialize credentials")?;
-
-        fs::write(&path, &content)
+        write_private(&path, &content)
             .with_context(|| format!("Failed to write credentials to {}", path.display()))?;
-
-        // Set restrictive permissions (0600)
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(&path)?.permissions();
-            perms.set_mode(0o600);
-            fs::set_permissions(&path, perms)?;
-        }
-
-        Ok(())
+        Ok(warning)
     }
 
-    /// Delete credentials file
-    pub fn delete_credentials() -> Result<()> {
+    /// Delete credentials file, and the keyring entries with that backend
+    pub fn delete_credentials(&self) -> Result<()> {
+        let _lock = Self::lock_credentials(false)?;
         let path = Self::credentials_path()?;
 
+        if self.credentials_backend == CredentialsBackend::Keyring {
+            secrets::delete().context("Failed to remove credentials from the OS keyring")?;
+        }
+
         if path.exists() {
             fs::remove_file(&path)
                 .with_context(|| format!("Failed to delete credentials at {}", path.display()))?;
@@ -214,16 +664,68 @@ impl Config {
         if let Some(key) = &self.api_key {
             return Ok(key);
         }
-        self.token.as_deref().ok_or_else(|| {
-            anyhow::anyhow!("Not logged in. Run `flaglite signup` or `flaglite login`")
-        })
+        self.token
+            .as_deref()
+            .ok_or_else(|| FlagLiteError::NotAuthenticated.into())
     }
 
     /// Get the project ID, or error if not set
     pub fn require_project(&self) -> Result<&str> {
-        self.project_id.as_deref().ok_or_else(|| {
-            anyhow::anyhow!("No project selected. Run 'flaglite projects use <id>' first.")
-        })
+        self.project_id
+            .as_deref()
+            .ok_or_else(|| FlagLiteError::NoProjectSelected.into())
+    }
+
+    /// Build an unauthenticated client using the configured network settings
+    pub fn client(&self) -> Result<FlagLiteClient> {
+        let mut builder = FlagLiteClient::builder(&self.api_url)
+            .user_agent(concat!("flaglite-cli/", env!("CARGO_PKG_VERSION")));
+
+        if let Some(path) = &self.ca_cert {
+            let pem = fs::read(path).with_context(|| {
+                format!("Failed to read CA certificate from {}", path.display())
+            })?;
+            builder = builder.add_root_certificate_pem(pem);
+        }
+
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(proxy);
+        }
+
+        if self.verbose || self.timings.is_some() {
+            let verbose = self.verbose;
+            let timings = self.timings.clone();
+            builder = builder.on_request(move |request| {
+                if verbose {
+                    crate::output::Output::log_request(request);
+                }
+                if let Some(timings) = &timings {
+                    timings.record(request);
+                }
+            });
+        }
+
+        Ok(builder.build()?)
+    }
+
+    /// Current value of a `flaglite config` key
+    pub fn get(&self, key: ConfigKey) -> Option<&str> {
+        match key {
+            ConfigKey::ApiUrl => Some(&self.api_url),
+            ConfigKey::Project => self.project_id.as_deref(),
+            ConfigKey::Environment => self.environment.as_deref(),
+            ConfigKey::Format => self.format.as_deref(),
+        }
+    }
+
+    /// Set a `flaglite config` key; None restores the default
+    pub fn set(&mut self, key: ConfigKey, value: Option<String>) {
+        match key {
+            ConfigKey::ApiUrl => self.api_url = value.unwrap_or_else(default_api_url),
+            ConfigKey::Project => self.project_id = value,
+            ConfigKey::Environment => self.environment = value,
+            ConfigKey::Format => self.format = value,
+        }
     }
 
     /// Get the environment, defaulting to "development"
@@ -231,6 +733,30 @@ impl Config {
         self.environment.as_deref().unwrap_or("development")
     }
 
+    /// Whether to ask before `action`, under the `[confirm]` policy and --yes
+    pub fn asks(&self, action: ConfirmAction) -> bool {
+        self.confirm.asks(action, self.yes)
+    }
+
+    /// What to tell the user when `action` needs confirmation but prompts are
+    /// disabled: `hint`, or why the --yes they passed didn't skip it
+    pub fn confirm_hint(&self, action: ConfirmAction, hint: &str) -> String {
+        if self.yes {
+            format!(
+                "--yes doesn't skip {} here; add it to `yes` under [confirm] in the config to allow that.",
+                action.name()
+            )
+        } else {
+            hint.to_string()
+        }
+    }
+
+    /// Whether to send changes to protected environments confirmed: with
+    /// --confirm-production, or when the policy doesn't ask for them
+    pub fn confirms_production(&self, confirm_production: bool) -> bool {
+        confirm_production || !self.asks(ConfirmAction::ProtectedEnvironment)
+    }
+
     /// Clear authentication (for logout)
     pub fn clear_auth(&mut self) {
         self.token = None;
@@ -247,7 +773,46 @@ impl Default for Config {
             api_key: None,
             username: None,
             project_id: None,
+            org_id: None,
             environment: None,
+            ca_cert: None,
+            proxy: None,
+            format: None,
+            credentials_backend: CredentialsBackend::File,
+            confirm: ConfirmPolicy::default(),
+            yes: false,
+            secrets_in_keyring: false,
+            verbose: false,
+            timings: None,
+            repo_config: None,
+            credentials_warning: None,
+            user_values: RepoConfig::default(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_confirm_policy_defaults_and_allowlist() {
+        let config: Config = toml::from_str("").unwrap();
+        let policy = &config.confirm;
+        assert!(policy.asks(ConfirmAction::DeleteFlag, false));
+        assert!(!policy.asks(ConfirmAction::DeleteFlag, true));
+        assert!(!policy.asks(ConfirmAction::BulkUpdate, true));
+        assert!(policy.asks(ConfirmAction::ProtectedEnvironment, true));
+        // The default policy isn't written back to the file
+        assert!(!toml::to_string(&config).unwrap().contains("[confirm]"));
+
+        let config: Config =
+            toml::from_str("[confirm]\ndelete_flag = false\nyes = [\"protected_environment\"]\n")
+                .unwrap();
+        let policy = &config.confirm;
+        assert!(!policy.asks(ConfirmAction::DeleteFlag, false));
+        assert!(policy.asks(ConfirmAction::BulkUpdate, true));
+        assert!(!policy.asks(ConfirmAction::ProtectedEnvironment, true));
+        assert!(toml::from_str::<Config>("[confirm]\nyes = [\"delete_project\"]\n").is_err());
+    }
+}
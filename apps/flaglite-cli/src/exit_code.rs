@@ -0,0 +1,137 @@
+//! Process exit codes for CLI error categories
+//!
+//! Scripts can branch on these instead of parsing error messages.
+
+use flaglite_client::FlagLiteError;
+
+/// Any error that doesn't fit a more specific category
+pub const GENERAL: i32 = 1;
+
+/// Not logged in, or credentials were rejected by the server
+pub const AUTH: i32 = 2;
+
+/// Project, flag, or environment does not exist
+pub const NOT_FOUND: i32 = 3;
+
+/// Invalid input, rejected locally or by the server, an unconfirmed change
+/// to a protected environment, a change to a frozen environment, or a
+/// conflicting concurrent change. Also unknown commands and bad arguments,
+/// which clap would otherwise exit with 2, the code of [`AUTH`]
+pub const VALIDATION: i32 = 4;
+
+/// Server unreachable, rate limited, or temporarily unavailable
+pub const NETWORK: i32 = 5;
+
+/// Exit code reference appended to `--help`
+pub const HELP: &str = "Exit codes:
+  0  Success
+  1  General error
+  2  Authentication required or credentials rejected
+  3  Project, flag, or environment not found
+  4  Invalid input or usage, unconfirmed protected change, frozen environment, or edit conflict
+  5  Network error, rate limit, or server unavailable";
+
+/// Print a command-line parsing error and exit: `--help` and `--version`
+/// with 0, anything else with [`VALIDATION`]
+pub fn exit_usage(error: clap::Error) -> ! {
+    if !error.use_stderr() {
+        error.exit();
+    }
+    let _ = error.print();
+    std::process::exit(VALIDATION)
+}
+
+/// The name of an exit code's category, for machine-readable errors
+pub fn name(code: i32) -> &'static str {
+    match code {
+        AUTH => "auth",
+        NOT_FOUND => "not_found",
+        VALIDATION => "validation",
+        NETWORK => "network",
+        _ => "general",
+    }
+}
+
+/// Map an error to its exit code by looking for a `FlagLiteError` in the chain
+pub fn for_error(error: &anyhow::Error) -> i32 {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<FlagLiteError>())
+        .map(for_client_error)
+        .unwrap_or(GENERAL)
+}
+
+fn for_client_error(error: &FlagLiteError) -> i32 {
+    match error {
+        FlagLiteError::NotAuthenticated | FlagLiteError::InvalidCredentials => AUTH,
+        FlagLiteError::ProjectNotFound(_)
+        | FlagLiteError::FlagNotFound { .. }
+        | FlagLiteError::EnvironmentNotFound(_) => NOT_FOUND,
+        FlagLiteError::NoProjectSelected
+        | FlagLiteError::ValidationError(_)
+        | FlagLiteError::InvalidFields(_)
+        | FlagLiteError::Conflict(_)
+        | FlagLiteError::ProtectedEnvironment(_)
+        | FlagLiteError::ReasonRequired(_)
+        | FlagLiteError::EnvironmentFrozen(_) => VALIDATION,
+        FlagLiteError::NetworkError(_) | FlagLiteError::RateLimited { .. } => NETWORK,
+        FlagLiteError::ApiError { status, .. } => for_status(*status),
+        FlagLiteError::InvalidResponse(_) => GENERAL,
+    }
+}
+
+fn for_status(status: u16) -> i32 {
+    match status {
+        401 | 403 => AUTH,
+        404 => NOT_FOUND,
+        400 | 409 | 422 | 423 => VALIDATION,
+        429 | 502 | 503 | 504 => NETWORK,
+        _ => GENERAL,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_errors_map_to_categories() {
+        let cases = [
+            (FlagLiteError::NotAuthenticated, AUTH),
+            (
+                FlagLiteError::FlagNotFound {
+                    key: "x".into(),
+                    suggestions: vec![],
+                },
+                NOT_FOUND,
+            ),
+            (FlagLiteError::ValidationError("bad".into()), VALIDATION),
+            (FlagLiteError::Conflict("stale".into()), VALIDATION),
+            (FlagLiteError::NetworkError("refused".into()), NETWORK),
+            (
+                FlagLiteError::ApiError {
+                    status: 409,
+                    message: "exists".into(),
+                },
+                VALIDATION,
+            ),
+            (FlagLiteError::InvalidResponse("eof".into()), GENERAL),
+        ];
+
+        for (error, expected) in cases {
+            assert_eq!(for_error(&error.into()), expected);
+        }
+    }
+
+    #[test]
+    fn test_context_does_not_hide_category() {
+        let error = anyhow::Error::new(FlagLiteError::EnvironmentNotFound("qa".into()))
+            .context("Run 'flaglite envs list' to see available environments.");
+        assert_eq!(for_error(&error), NOT_FOUND);
+    }
+
+    #[test]
+    fn test_untyped_errors_are_general() {
+        assert_eq!(for_error(&anyhow::anyhow!("boom")), GENERAL);
+    }
+}
@@ -4,11 +4,21 @@
 
 mod commands;
 mod config;
+mod exit_code;
+mod notifications;
 mod output;
+mod secrets;
+mod suggestions;
+mod timings;
 
 use anyhow::Result;
-use clap::{Parser, Subcommand};
-use commands::{auth, envs, flags, projects};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use commands::{
+    account, auth, change_requests, envs, flags, guards, init, keys, orgs, projects, ramps, scan,
+    sync, templates,
+};
+use flaglite_client::{FlagLiteError, RampAction};
+use std::sync::Arc;
 
 #[derive(Parser)]
 #[command(
@@ -20,29 +30,69 @@ use commands::{auth, envs, flags, projects};
                   Get started:\n  \
                   flaglite signup\n  \
                   flaglite projects list\n  \
-                  flaglite flags list"
+                  flaglite flags list",
+    after_help = exit_code::HELP
 )]
 struct Cli {
-    /// Output format
-    #[arg(long, global = true, default_value = "pretty")]
-    format: output::OutputFormat,
+    /// Output format: pretty, json, json-v2 (json in a status envelope), csv or tsv
+    /// (csv/tsv apply to list commands); defaults to the `format` config key, then pretty
+    #[arg(long, global = true)]
+    format: Option<output::OutputFormat>,
 
     /// API base URL (overrides config)
     #[arg(long, global = true, env = "FLAGLITE_API_URL")]
     api_url: Option<String>,
 
-    /// API key for authentication
+    /// API key for authentication (with `login`, saved to credentials)
     #[arg(long, global = true, env = "FLAGLITE_API_KEY")]
     api_key: Option<String>,
 
-    /// Project ID (overrides config)
+    /// Project ID or slug (overrides config)
     #[arg(long, short = 'p', global = true, env = "FLAGLITE_PROJECT")]
     project: Option<String>,
 
+    /// Organization ID (overrides config)
+    #[arg(long, global = true, env = "FLAGLITE_ORG")]
+    org: Option<String>,
+
     /// Environment (overrides config)
     #[arg(long, short = 'e', global = true, env = "FLAGLITE_ENV")]
     env: Option<String>,
 
+    /// Additional CA certificate bundle (PEM) to trust
+    #[arg(long, global = true, env = "FLAGLITE_CA_CERT")]
+    ca_cert: Option<std::path::PathBuf>,
+
+    /// Proxy URL for API requests (overrides HTTPS_PROXY)
+    #[arg(long, global = true, env = "FLAGLITE_PROXY")]
+    proxy: Option<String>,
+
+    /// Never prompt; fail if a command needs input (implied when stdin isn't a terminal)
+    #[arg(long, global = true, env = "FLAGLITE_NO_INPUT")]
+    no_input: bool,
+
+    /// Skip the confirmation prompts the `[confirm]` config lets --yes skip
+    /// (by default, deleting a flag and changing flags matched by --match)
+    #[arg(long, short = 'y', global = true, env = "FLAGLITE_YES")]
+    yes: bool,
+
+    /// Log each HTTP request (method, URL, status, duration) to stderr
+    #[arg(long, short = 'v', global = true, env = "FLAGLITE_VERBOSE")]
+    verbose: bool,
+
+    /// Print results only, without success, info or warning messages
+    #[arg(long, short = 'q', global = true, env = "FLAGLITE_QUIET")]
+    quiet: bool,
+
+    /// After the command, summarize the API calls it made: count, durations, retries
+    #[arg(long, global = true, env = "FLAGLITE_TIMINGS")]
+    timings: bool,
+
+    /// Print only this value of the JSON result, e.g. `.api_key` or `.[0].key`;
+    /// strings are printed without quotes
+    #[arg(long, global = true, value_name = "PATH")]
+    output: Option<output::JsonPath>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -57,9 +107,12 @@ enum Commands {
         /// Password (for non-interactive use)
         #[arg(long)]
         password: Option<String>,
+        /// Invite code (required when the server is invite-only)
+        #[arg(long, env = "FLAGLITE_INVITE_CODE")]
+        invite_code: Option<String>,
     },
 
-    /// Authenticate with FlagLite
+    /// Authenticate with FlagLite, with a password or `--api-key flg_...`
     Login {
         /// Username
         #[arg(long, short)]
@@ -73,31 +126,433 @@ enum Commands {
     Logout,
 
     /// Show current user information
-    Whoami,
+    Whoami {
+        /// Show stored credentials and settings without contacting the server
+        #[arg(long)]
+        local: bool,
+    },
 
-    /// Manage projects
+    /// Create a single-use invite code for an invite-only server (organization owners only)
+    Invite,
+
+    /// Join a project with an invite link (see `projects share`) and switch to it
+    Join {
+        /// The link, or just its token
+        link: String,
+    },
+
+    /// Pin this repo to a project and environment (writes .flaglite.toml)
+    Init {
+        /// Overwrite an existing .flaglite.toml
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Email verification and password reset
+    #[command(subcommand)]
+    Account(AccountCommands),
+
+    /// Manage organizations
+    #[command(subcommand)]
+    Orgs(OrgsCommands),
+
+    /// Manage your API keys
     #[command(subcommand)]
+    Keys(KeysCommands),
+
+    /// Manage projects
+    #[command(subcommand, visible_alias = "p")]
     Projects(ProjectsCommands),
 
     /// Manage feature flags
-    #[command(subcommand)]
+    #[command(subcommand, visible_alias = "f")]
     Flags(FlagsCommands),
 
     /// Manage environments
-    #[command(subcommand)]
+    #[command(subcommand, visible_alias = "e")]
     Envs(EnvsCommands),
 
+    /// Manage flag templates (per-environment starting states for new flags)
+    #[command(subcommand)]
+    Templates(TemplatesCommands),
+
+    /// Review flag changes waiting for approval (see `flags propose`)
+    #[command(subcommand, visible_alias = "cr")]
+    ChangeRequests(ChangeRequestsCommands),
+
+    /// Follow and control rollout ramps (see `flags ramp`)
+    #[command(subcommand)]
+    Ramps(RampsCommands),
+
+    /// Follow, cancel and report to guards (see `flags toggle --guard`)
+    #[command(subcommand)]
+    Guards(GuardsCommands),
+
+    /// Toggle a flag on/off (shortcut for `flags toggle`)
+    #[command(visible_alias = "t")]
+    Toggle {
+        /// Flag key
+        key: String,
+        /// Only apply if the flag is still at this version (see `flags get --format json`)
+        #[arg(long)]
+        expected_version: Option<i64>,
+        /// Apply the change even if the environment is protected
+        #[arg(long)]
+        confirm_production: bool,
+        /// Why the change is made, kept in the flag's history
+        #[arg(long)]
+        reason: Option<String>,
+    },
+
+    /// Find flags by key or name across all your projects
+    Search {
+        /// Part of a flag key or name (case-insensitive)
+        query: String,
+    },
+
+    /// Keep a local JSON file updated with an environment's flags, for apps without an SDK
+    Sync {
+        /// File to write; replaced atomically whenever a flag changes
+        #[arg(long, value_name = "PATH")]
+        out: std::path::PathBuf,
+        /// Time between polls, like 30s, 5m or 1h
+        #[arg(long, default_value = "30s", value_parser = sync::parse_interval)]
+        interval: std::time::Duration,
+        /// Send SIGHUP to this process after each change
+        #[arg(long, value_name = "PID")]
+        signal_pid: Option<u32>,
+        /// Send SIGHUP to the process whose PID is in this file after each change
+        #[arg(long, value_name = "PATH", conflicts_with = "signal_pid")]
+        signal_pid_file: Option<std::path::PathBuf>,
+        /// Write the file once and exit instead of running as a daemon
+        #[arg(long)]
+        once: bool,
+    },
+
     /// Show or edit configuration
     Config {
         /// Show config file path
         #[arg(long)]
         path: bool,
+        #[command(subcommand)]
+        action: Option<ConfigCommands>,
+    },
+}
+
+#[derive(Subcommand)]
+enum UserListCommands {
+    /// Put users on the list, moving them off the other list
+    Add {
+        /// Flag key
+        key: String,
+        /// User ID (repeatable)
+        #[arg(long = "user", value_name = "ID")]
+        users: Vec<String>,
+        /// File with one user ID per line ("-" for stdin)
+        #[arg(long, value_name = "FILE")]
+        file: Option<std::path::PathBuf>,
+        /// Apply the change even if the environment is protected
+        #[arg(long)]
+        confirm_production: bool,
+        /// Why the change is made
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Take users off the list
+    Remove {
+        /// Flag key
+        key: String,
+        /// User ID (repeatable)
+        #[arg(long = "user", value_name = "ID")]
+        users: Vec<String>,
+        /// File with one user ID per line ("-" for stdin)
+        #[arg(long, value_name = "FILE")]
+        file: Option<std::path::PathBuf>,
+        /// Apply the change even if the environment is protected
+        #[arg(long)]
+        confirm_production: bool,
+        /// Why the change is made
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Show the users on the list
+    #[command(visible_alias = "ls")]
+    List {
+        /// Flag key
+        key: String,
+    },
+    /// Take every user off the list
+    Clear {
+        /// Flag key
+        key: String,
+        /// Apply the change even if the environment is protected
+        #[arg(long)]
+        confirm_production: bool,
+        /// Why the change is made
+        #[arg(long)]
+        reason: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the value in effect for a key
+    Get {
+        /// api_url, project, environment or format
+        key: String,
+    },
+    /// Set a key in .flaglite.toml, or the user config with --global
+    Set {
+        /// api_url, project, environment or format
+        key: String,
+        value: String,
+        /// Write the user config instead of this repo's .flaglite.toml
+        #[arg(long)]
+        global: bool,
+    },
+    /// Remove a key from .flaglite.toml, or the user config with --global
+    Unset {
+        /// api_url, project, environment or format
+        key: String,
+        /// Edit the user config instead of this repo's .flaglite.toml
+        #[arg(long)]
+        global: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AccountCommands {
+    /// Set the email password resets are sent to ("" clears it)
+    SetEmail {
+        /// Email address
+        email: String,
+    },
+    /// Change your username (once every 30 days)
+    Rename {
+        /// New username
+        username: String,
+    },
+    /// Send a verification email, or confirm your address with --token
+    VerifyEmail {
+        /// Token from the verification email
+        #[arg(long)]
+        token: Option<String>,
+    },
+    /// Email a password reset token to the account's verified address
+    RequestReset {
+        /// Account to reset (default: the logged-in user)
+        #[arg(long, short)]
+        username: Option<String>,
+    },
+    /// Set a new password with a token from a reset email
+    Reset {
+        /// Token from the reset email
+        #[arg(long)]
+        token: String,
+        /// New password (for non-interactive use)
+        #[arg(long)]
+        password: Option<String>,
+        /// Also revoke your API keys, in case they leaked with the password
+        #[arg(long)]
+        revoke_api_keys: bool,
+    },
+    /// Show the defaults saved with your account, applied on login
+    Preferences,
+    /// Save a default with your account ("" clears it)
+    SetPreference {
+        /// project, environment, display_name or format
+        key: String,
+        value: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum OrgsCommands {
+    /// List your organizations
+    #[command(visible_alias = "ls")]
+    List,
+    /// Create an organization and switch to it
+    Create {
+        /// Organization name
+        name: String,
+    },
+    /// Set the current organization
+    Switch {
+        /// Organization ID or name
+        org: String,
+    },
+    /// List members of the current organization, or change them
+    Members {
+        #[command(subcommand)]
+        command: Option<OrgMembersCommands>,
+    },
+    /// Add an existing user to the current organization
+    AddMember {
+        /// Username of the user to add
+        username: String,
+        /// Role: member or owner
+        #[arg(long)]
+        role: Option<String>,
+    },
+    /// Create an API key limited to the current organization
+    CreateKey {
+        /// Key name
+        #[arg(long, short)]
+        name: Option<String>,
+        /// Scope: read, write or admin (default)
+        #[arg(long)]
+        scope: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum OrgMembersCommands {
+    /// Remove a member from the current organization (owners only)
+    Remove {
+        /// Username or user ID
+        username: String,
+    },
+    /// Change a member's role (owners only)
+    SetRole {
+        /// Username or user ID
+        username: String,
+        /// Role: member or owner
+        role: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommands {
+    /// List your API keys, including revoked ones
+    #[command(visible_alias = "ls")]
+    List,
+    /// Create an API key; shown once
+    Create {
+        /// Key name
+        #[arg(long, short)]
+        name: Option<String>,
+        /// Scope: read (no changes), write (no account or key management)
+        /// or admin (default)
+        #[arg(long)]
+        scope: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum GuardsCommands {
+    /// List the current project's guards, newest first
+    #[command(visible_alias = "ls")]
+    List {
+        /// Only guards with this status: watching, passed, tripped, superseded or cancelled
+        #[arg(long)]
+        status: Option<String>,
+    },
+    /// Stop a guard watching, leaving the flag as it is
+    Cancel {
+        /// Guard ID
+        id: String,
+    },
+    /// Report trouble to a guard, reverting its change; needs only the guard's token
+    Report {
+        /// Token printed when the guard was created (ffl_guard_...)
+        token: String,
+        /// What's wrong, recorded in the audit log
+        #[arg(long)]
+        message: Option<String>,
+        /// Report that all is well instead, which changes nothing
+        #[arg(long, conflicts_with = "message")]
+        healthy: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum RampsCommands {
+    /// List the current project's ramps, newest first
+    #[command(visible_alias = "ls")]
+    List {
+        /// Only ramps with this status: active, paused, completed or aborted
+        #[arg(long)]
+        status: Option<String>,
+    },
+    /// Show how far a ramp has got
+    Status {
+        /// Ramp ID
+        id: String,
+    },
+    /// Stop a ramp's steps, leaving the rollout as it is
+    Pause {
+        /// Ramp ID
+        id: String,
+    },
+    /// Carry on a paused ramp from the flag's current rollout
+    Resume {
+        /// Ramp ID
+        id: String,
+    },
+    /// End a ramp for good, leaving the rollout where it got to
+    Abort {
+        /// Ramp ID
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum ChangeRequestsCommands {
+    /// List the current project's change requests, newest first
+    #[command(visible_alias = "ls")]
+    List {
+        /// Only requests with this status: pending, approved or rejected
+        #[arg(long)]
+        status: Option<String>,
+    },
+    /// Approve a change request, applying the change
+    Approve {
+        /// Change request ID
+        id: String,
+        /// Note recorded with the approval
+        #[arg(long)]
+        comment: Option<String>,
+    },
+    /// Reject a change request, or withdraw your own
+    Reject {
+        /// Change request ID
+        id: String,
+        /// Note recorded with the rejection
+        #[arg(long)]
+        comment: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TemplatesCommands {
+    /// List built-in and stored templates
+    #[command(visible_alias = "ls")]
+    List,
+    /// Create or replace a template
+    Set {
+        /// Template name (lowercase letters, numbers and hyphens)
+        name: String,
+        /// Description
+        #[arg(long, short)]
+        description: Option<String>,
+        /// State in environments not given with --state: on, off or on:<rollout %>
+        #[arg(long, value_name = "STATE")]
+        default: Option<String>,
+        /// State in one environment, e.g. production=on:0 (repeatable)
+        #[arg(long = "state", value_name = "ENV=STATE")]
+        envs: Vec<String>,
+    },
+    /// Delete a stored template
+    Delete {
+        /// Template name
+        name: String,
     },
 }
 
 #[derive(Subcommand)]
 enum ProjectsCommands {
     /// List all projects
+    #[command(visible_alias = "ls")]
     List,
     /// Create a new project
     Create {
@@ -112,12 +567,97 @@ enum ProjectsCommands {
         /// Project ID or slug
         project: String,
     },
+    /// Clone a project's environments and flags into a new project
+    Clone {
+        /// Source project ID or slug
+        project: String,
+        /// Name for the new project
+        #[arg(long, short)]
+        name: String,
+        /// Also copy each environment's flag values (enabled, rollout)
+        #[arg(long)]
+        with_values: bool,
+    },
+    /// Save a project's environments, flags and their values as a .tar.gz archive
+    Export {
+        /// Project ID or slug (default: the current project)
+        project: Option<String>,
+        /// Where to write the archive (default: <slug>.tar.gz)
+        #[arg(long, short)]
+        file: Option<std::path::PathBuf>,
+    },
+    /// Create a new project from an archive made by `projects export`
+    Import {
+        /// The archive
+        file: std::path::PathBuf,
+        /// Name for the new project (default: the archived project's)
+        #[arg(long, short)]
+        name: Option<String>,
+    },
+    /// Show flag counts per environment: enabled, at partial rollout, last changed
+    Stats {
+        /// Project ID or slug (default: the current project)
+        project: Option<String>,
+    },
+    /// Change project settings
+    Update {
+        /// Project ID or slug (default: the current project)
+        project: Option<String>,
+        /// Make changes to protected environments give a reason (--reason or a prompt); turning it off is for project owners only
+        #[arg(long, value_name = "BOOL")]
+        require_change_reason: Option<bool>,
+    },
+    /// Create an invite link anyone signed in can join the project with (see `join`)
+    Share {
+        /// Project ID or slug (default: the current project)
+        project: Option<String>,
+        /// Role people who join get: member (default) or owner
+        #[arg(long)]
+        role: Option<String>,
+        /// How long the link works, like 1h or 14d (default 7d, at most 30d)
+        #[arg(long, value_name = "DURATION", value_parser = projects::parse_expiry)]
+        expires: Option<std::time::Duration>,
+    },
+    /// List a project's invite links
+    Links {
+        /// Project ID or slug (default: the current project)
+        project: Option<String>,
+    },
+    /// Revoke an invite link; people who already joined keep access
+    RevokeLink {
+        /// Link ID (see `projects links`)
+        id: String,
+        /// Project ID or slug (default: the current project)
+        #[arg(long, short)]
+        project: Option<String>,
+    },
+    /// List the people who joined a project with an invite link
+    Members {
+        /// Project ID or slug (default: the current project)
+        project: Option<String>,
+    },
+    /// Take back the access an invite link gave someone
+    RemoveMember {
+        /// Username or user ID (see `projects members`)
+        user: String,
+        /// Project ID or slug (default: the current project)
+        #[arg(long, short)]
+        project: Option<String>,
+    },
 }
 
 #[derive(Subcommand)]
 enum FlagsCommands {
     /// List all flags in the current project
-    List,
+    #[command(visible_alias = "ls")]
+    List {
+        /// Only flags with this owner
+        #[arg(long)]
+        owner: Option<String>,
+        /// Only flags read by this repository
+        #[arg(long)]
+        repo: Option<String>,
+    },
     /// Create a new flag
     Create {
         /// Flag key (unique identifier)
@@ -134,69 +674,540 @@ enum FlagsCommands {
         /// Enable flag immediately
         #[arg(long)]
         enabled: bool,
+        /// Turn the flag off everywhere at this time (YYYY-MM-DD or RFC 3339)
+        #[arg(long)]
+        expires: Option<String>,
+        /// Person or team responsible for the flag
+        #[arg(long)]
+        owner: Option<String>,
+        /// Repository that reads the flag
+        #[arg(long)]
+        repo: Option<String>,
+        /// Where in the repository the flag is read (e.g. src/checkout.rs)
+        #[arg(long)]
+        code_path: Option<String>,
+        /// JSON Schema file that values of a json flag must match
+        #[arg(long, value_name = "FILE")]
+        schema: Option<std::path::PathBuf>,
+        /// Start on in environments without a value, including ones created later
+        #[arg(long)]
+        default_on: bool,
+        /// Value served where the flag is on but the environment sets none (JSON)
+        #[arg(long, value_name = "JSON")]
+        default_value: Option<String>,
+        /// Start in each environment's state from this template (see `flaglite templates list`)
+        #[arg(long, conflicts_with_all = ["enabled", "default_on"])]
+        template: Option<String>,
     },
-    /// Get details for a specific flag
+    /// Set a flag's owner, code references or defaults (an empty value clears an owner or reference)
+    Update {
+        /// Flag key
+        key: String,
+        /// Person or team responsible for the flag
+        #[arg(long)]
+        owner: Option<String>,
+        /// Repository that reads the flag
+        #[arg(long)]
+        repo: Option<String>,
+        /// Where in the repository the flag is read
+        #[arg(long)]
+        code_path: Option<String>,
+        /// On in environments without a value
+        #[arg(long, conflicts_with = "default_off")]
+        default_on: bool,
+        /// Off in environments without a value
+        #[arg(long)]
+        default_off: bool,
+        /// Value served where the flag is on but the environment sets none (JSON)
+        #[arg(long, value_name = "JSON", conflicts_with = "clear_default_value")]
+        default_value: Option<String>,
+        /// Remove the default value
+        #[arg(long)]
+        clear_default_value: bool,
+        /// Let public environments serve the flag without a key
+        #[arg(long, conflicts_with = "private")]
+        public: bool,
+        /// Stop public environments serving the flag
+        #[arg(long)]
+        private: bool,
+    },
+    /// List flags past their expiry date, which evaluate to off
+    Expired,
+    /// List local overrides (FLAGLITE_OVERRIDE_<FLAG_KEY>, FLAGLITE_OVERRIDES_FILE)
+    /// that SDK clients started from this shell apply
+    Overrides,
+    /// Get details for a specific flag in the current environment (-e to pick another)
     Get {
         /// Flag key
         key: String,
+
+        /// Show enabled, rollout, and value in every environment
+        #[arg(long)]
+        all_envs: bool,
+    },
+    /// Show how often a flag is evaluated, per environment
+    Stats {
+        /// Flag key
+        key: String,
     },
-    /// Toggle a flag on/off
+    /// Export each user's bucket and result for a flag as CSV (for experiments)
+    Assignments {
+        /// Flag key
+        key: String,
+        /// File with one user ID per line ("-" for stdin)
+        #[arg(long, value_name = "FILE")]
+        users_file: std::path::PathBuf,
+    },
+    /// Write a JSON fixture of what a flag serves synthetic users, for asserting SDK bucketing in tests
+    Fixtures {
+        /// Flag key
+        key: String,
+        /// How many users (user-1, user-2, ...) to evaluate for
+        #[arg(long, default_value_t = 1000)]
+        samples: usize,
+        /// File to write (default: stdout)
+        #[arg(long, value_name = "FILE")]
+        out: Option<std::path::PathBuf>,
+    },
+    /// Users who get a flag in the current environment while it's on, whatever the rollout
+    Allowlist {
+        #[command(subcommand)]
+        action: UserListCommands,
+    },
+    /// Users who never get a flag in the current environment
+    Denylist {
+        #[command(subcommand)]
+        action: UserListCommands,
+    },
+    /// Toggle a flag on/off, or turn every flag matching --match on or off
     Toggle {
+        /// Flag key
+        #[arg(required_unless_present = "pattern")]
+        key: Option<String>,
+        /// Only apply if the flag is still at this version (see `flags get --format json`)
+        #[arg(long, conflicts_with = "pattern")]
+        expected_version: Option<i64>,
+        /// Apply the change even if the environment is protected
+        #[arg(long)]
+        confirm_production: bool,
+        /// Why the change is made, kept in the flag's history
+        #[arg(long)]
+        reason: Option<String>,
+        /// Change every flag whose key matches this glob (`*`, `?`)
+        #[arg(long = "match", value_name = "PATTERN", conflicts_with = "key")]
+        pattern: Option<String>,
+        /// Treat --match as a regular expression
+        #[arg(long, requires = "pattern")]
+        regex: bool,
+        /// Turn the matched flags on
+        #[arg(long, requires = "pattern", conflicts_with = "off")]
+        on: bool,
+        /// Turn the matched flags off
+        #[arg(long, requires = "pattern")]
+        off: bool,
+        /// Revert the change if it turns out unhealthy: polls URL if given, and
+        /// prints a token to push reports with
+        #[arg(long, value_name = "URL", num_args = 0..=1, conflicts_with = "pattern")]
+        guard: Option<Option<String>>,
+        /// How long the guard watches, like 30m or 2h (default 30m)
+        #[arg(long, value_name = "DURATION", requires = "guard", value_parser = sync::parse_interval)]
+        guard_window: Option<std::time::Duration>,
+    },
+    /// Set the rollout percentage of a flag, or of every flag matching --match
+    Rollout {
+        /// Flag key
+        #[arg(required_unless_present = "pattern")]
+        key: Option<String>,
+        /// Percentage of users who get the flag (0-100)
+        #[arg(long)]
+        percentage: i32,
+        /// Apply the change even if the environment is protected
+        #[arg(long)]
+        confirm_production: bool,
+        /// Why the change is made, kept in the flag's history
+        #[arg(long)]
+        reason: Option<String>,
+        /// Change every flag whose key matches this glob (`*`, `?`)
+        #[arg(long = "match", value_name = "PATTERN", conflicts_with = "key")]
+        pattern: Option<String>,
+        /// Treat --match as a regular expression
+        #[arg(long, requires = "pattern")]
+        regex: bool,
+    },
+    /// Turn a flag on in the current environment
+    Enable {
+        /// Flag key
+        key: String,
+        /// JSON value to serve while enabled (checked against the flag's schema)
+        #[arg(long)]
+        value: Option<String>,
+        /// Only apply if the flag is still at this version (see `flags get --format json`)
+        #[arg(long)]
+        expected_version: Option<i64>,
+        /// Apply the change even if the environment is protected
+        #[arg(long)]
+        confirm_production: bool,
+        /// Why the change is made, kept in the flag's history
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Turn a flag off in the current environment
+    Disable {
+        /// Flag key
+        key: String,
+        /// Only apply if the flag is still at this version (see `flags get --format json`)
+        #[arg(long)]
+        expected_version: Option<i64>,
+        /// Apply the change even if the environment is protected
+        #[arg(long)]
+        confirm_production: bool,
+        /// Why the change is made, kept in the flag's history
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Propose a change to a flag in the current environment, applied once a
+    /// project admin approves it
+    Propose {
+        /// Flag key
+        key: String,
+        /// Turn the flag on
+        #[arg(long, conflicts_with = "disable")]
+        enable: bool,
+        /// Turn the flag off
+        #[arg(long)]
+        disable: bool,
+        /// Percentage of users who get the flag (0-100)
+        #[arg(long, required_unless_present_any = ["enable", "disable"])]
+        percentage: Option<i32>,
+        /// Why the change is needed, for the reviewer
+        #[arg(long, short)]
+        comment: Option<String>,
+    },
+    /// Raise a flag's rollout in the current environment step by step; the
+    /// server takes each step, so the ramp carries on without the CLI
+    Ramp {
         /// Flag key
         key: String,
+        /// Rollout percentage to end at
+        #[arg(long, default_value_t = 100)]
+        to: i32,
+        /// Percentage points to add each step
+        #[arg(long, default_value_t = 10)]
+        step: i32,
+        /// Time between steps, like 15m or 1h (at least 1m)
+        #[arg(long, default_value = "15m", value_parser = sync::parse_interval)]
+        interval: std::time::Duration,
+        /// Start the ramp even if the environment is protected
+        #[arg(long)]
+        confirm_production: bool,
+        /// Why the flag is ramped, kept in its history with each step
+        #[arg(long)]
+        reason: Option<String>,
+        /// Abort the ramp and revert to the rollout before it if it turns out unhealthy: polls URL if given, and
+        /// prints a token to push reports with
+        #[arg(long, value_name = "URL", num_args = 0..=1)]
+        guard: Option<Option<String>>,
+        /// How long the guard watches after the ramp ends, like 30m or 2h (default 30m)
+        #[arg(long, value_name = "DURATION", requires = "guard", value_parser = sync::parse_interval)]
+        guard_window: Option<std::time::Duration>,
+    },
+    /// Show the recorded versions of a flag in the current environment, newest first
+    History {
+        /// Flag key
+        key: String,
+        /// Versions to show (1-1000); the server's default is 100
+        #[arg(long)]
+        limit: Option<u32>,
+        /// Continue from an earlier page (its `next_cursor`)
+        #[arg(long)]
+        cursor: Option<String>,
+        /// Stream every version to stdout as NDJSON, for archiving or analysis
+        #[arg(long, conflicts_with_all = ["limit", "cursor"])]
+        export: bool,
+    },
+    /// Restore a flag to an earlier version in the current environment
+    Rollback {
+        /// Flag key
+        key: String,
+        /// Version to restore (see `flags history`); defaults to the one before the current version
+        #[arg(long, value_name = "VERSION")]
+        to: Option<i64>,
+        /// Only apply if the flag is still at this version (see `flags history`)
+        #[arg(long)]
+        expected_version: Option<i64>,
+        /// Apply the change even if the environment is protected
+        #[arg(long)]
+        confirm_production: bool,
+        /// Why the change is made, kept in the flag's history
+        #[arg(long)]
+        reason: Option<String>,
     },
     /// Delete a flag
+    #[command(visible_alias = "rm")]
     Delete {
         /// Flag key
         key: String,
-        /// Skip confirmation
-        #[arg(long, short = 'y')]
-        yes: bool,
+    },
+    /// Watch flags and report state changes as they happen
+    Watch {
+        /// Seconds between polls
+        #[arg(long, short, default_value_t = 2)]
+        interval: u64,
+    },
+    /// Raise a desktop notification whenever one of the given flags changes
+    Notify {
+        /// Flag keys to watch
+        #[arg(required = true)]
+        keys: Vec<String>,
+        /// Time between polls, like 30s, 5m or 1h
+        #[arg(long, short, default_value = "10s", value_parser = sync::parse_interval)]
+        interval: std::time::Duration,
+    },
+    /// Find flag keys used in source code and compare them with the project's flags
+    Scan {
+        /// Directory to search
+        #[arg(long, default_value = ".")]
+        src: std::path::PathBuf,
     },
 }
 
 #[derive(Subcommand)]
 enum EnvsCommands {
     /// List all environments
+    #[command(visible_alias = "ls")]
     List,
+    /// Add an environment to the current project
+    Create {
+        /// Environment name (lowercase letters, digits and hyphens)
+        name: String,
+        /// Start with this environment's flag values instead of none
+        #[arg(long, value_name = "ENV")]
+        copy_from: Option<String>,
+    },
     /// Set the default environment
     Use {
         /// Environment name or slug
         name: String,
     },
+    /// Require confirmation for flag changes in an environment
+    Protect {
+        /// Environment name
+        name: String,
+    },
+    /// Allow flag changes in an environment without confirmation (project owners only)
+    Unprotect {
+        /// Environment name
+        name: String,
+    },
+    /// Set an environment's display color and emoji, or how long its SDK responses may be cached
+    Update {
+        /// Environment name
+        name: String,
+        /// Hex color like #22c55e (empty to clear)
+        #[arg(long)]
+        color: Option<String>,
+        /// Emoji shown next to the name (empty to clear)
+        #[arg(long)]
+        emoji: Option<String>,
+        /// Seconds CDNs and proxies may cache flag evaluations (0 turns caching off)
+        #[arg(long, value_name = "SECONDS")]
+        cache_max_age: Option<i32>,
+        /// Seconds past that a cache may serve a stale answer while it refetches
+        #[arg(long, value_name = "SECONDS")]
+        cache_stale: Option<i32>,
+    },
+    /// Set the order environments are listed in (unlisted ones follow)
+    Reorder {
+        /// Environment names, first to last
+        #[arg(required = true)]
+        names: Vec<String>,
+    },
+    /// Reject all flag changes in an environment (change-freeze windows)
+    Freeze {
+        /// Environment name
+        name: String,
+    },
+    /// Allow flag changes in a frozen environment again (project owners only)
+    Unfreeze {
+        /// Environment name
+        name: String,
+    },
+    /// Show whether flag changes in an environment need approval, or set it
+    /// (project owners only); changes there then go through 'flags propose'
+    Approval {
+        /// Environment name
+        name: String,
+        /// Require approval (true) or allow direct changes again (false)
+        #[arg(long, value_name = "BOOL")]
+        required: Option<bool>,
+    },
+    /// Let anyone evaluate an environment's public flags, without a key (project owners only)
+    Publish {
+        /// Environment name
+        name: String,
+    },
+    /// Stop keyless evaluation in an environment and revoke its public client ID
+    Unpublish {
+        /// Environment name
+        name: String,
+    },
+    /// Delete an environment; it can be restored with 'envs restore' until
+    /// the server purges it (30 days by default)
+    Delete {
+        /// Environment name
+        name: String,
+        /// Delete a protected environment such as production (project owners only)
+        #[arg(long)]
+        force: bool,
+    },
+    /// List deleted environments that can still be restored
+    Deleted,
+    /// Bring back a deleted environment with its keys and flag values
+    Restore {
+        /// Environment name
+        name: String,
+    },
+    /// Replace the key that signs flag snapshots for browser and mobile SDKs
+    RotateSigningKey {
+        /// Environment name
+        name: String,
+    },
+    /// Copy an environment's flag values into another project's, for the flag keys both have
+    Copy {
+        /// Source, as PROJECT/ENV (project ID or slug)
+        #[arg(long, value_name = "PROJECT/ENV", value_parser = envs::parse_project_env)]
+        from: envs::ProjectEnv,
+        /// Target, as PROJECT/ENV (project ID or slug)
+        #[arg(long, value_name = "PROJECT/ENV", value_parser = envs::parse_project_env)]
+        to: envs::ProjectEnv,
+        /// Show what would change and what doesn't match, without changing anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Apply the change even if the target environment is protected
+        #[arg(long)]
+        confirm_production: bool,
+        /// Why the change is made, kept in the flags' history
+        #[arg(long)]
+        reason: Option<String>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli = Cli::parse();
-    let output = output::Output::new(cli.format);
+    let matches = Cli::command()
+        .try_get_matches()
+        .unwrap_or_else(|e| exit_code::exit_usage(e));
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| exit_code::exit_usage(e));
 
     // Load config
     let mut config = config::Config::load()?;
 
+    // --format wins over the configured preference
+    let format = cli
+        .format
+        .or_else(|| config.format.as_deref().and_then(|f| f.parse().ok()))
+        .unwrap_or_default();
+    let output = output::Output::new(format, cli.no_input, cli.verbose)
+        .quiet(cli.quiet)
+        .select(cli.output.clone())
+        .command(command_path(&matches));
+    config.verbose = cli.verbose;
+    config.yes = cli.yes;
+    config.timings = cli.timings.then(|| Arc::new(timings::Timings::new()));
+    if let Some(warning) = &config.credentials_warning {
+        output.warn(warning);
+    }
+
     // Apply CLI overrides
     if let Some(url) = cli.api_url {
         config.api_url = url;
     }
-    if let Some(key) = cli.api_key {
+    if let Some(key) = cli.api_key.clone() {
         config.api_key = Some(key);
     }
     if let Some(project) = cli.project {
         config.project_id = Some(project);
     }
+    if let Some(org) = cli.org {
+        config.org_id = Some(org);
+    }
     if let Some(env) = cli.env {
         config.environment = Some(env);
     }
+    if let Some(ca_cert) = cli.ca_cert {
+        config.ca_cert = Some(ca_cert);
+    }
+    if let Some(proxy) = cli.proxy {
+        config.proxy = Some(proxy);
+    }
 
     let result = match cli.command {
-        Commands::Signup { username, password } => {
-            auth::signup(&mut config, &output, username, password).await
-        }
+        Commands::Signup {
+            username,
+            password,
+            invite_code,
+        } => auth::signup(&mut config, &output, username, password, invite_code).await,
         Commands::Login { username, password } => {
-            auth::login(&mut config, &output, username, password).await
+            auth::login(&mut config, &output, username, password, cli.api_key).await
         }
         Commands::Logout => auth::logout(&mut config, &output).await,
-        Commands::Whoami => auth::whoami(&config, &output).await,
+        Commands::Whoami { local } => auth::whoami(&config, &output, local).await,
+        Commands::Invite => auth::invite(&config, &output).await,
+        Commands::Join { link } => projects::join(&mut config, &output, link).await,
+
+        Commands::Init { force } => init::init(&config, &output, force).await,
+
+        Commands::Account(cmd) => match cmd {
+            AccountCommands::SetEmail { email } => {
+                account::set_email(&config, &output, email).await
+            }
+            AccountCommands::Rename { username } => {
+                account::rename(&mut config, &output, username).await
+            }
+            AccountCommands::VerifyEmail { token } => {
+                account::verify_email(&config, &output, token).await
+            }
+            AccountCommands::RequestReset { username } => {
+                account::request_reset(&config, &output, username).await
+            }
+            AccountCommands::Reset {
+                token,
+                password,
+                revoke_api_keys,
+            } => account::reset(&config, &output, token, password, revoke_api_keys).await,
+            AccountCommands::Preferences => account::preferences(&config, &output).await,
+            AccountCommands::SetPreference { key, value } => {
+                account::set_preference(&config, &output, key, value).await
+            }
+        },
+
+        Commands::Orgs(cmd) => match cmd {
+            OrgsCommands::List => orgs::list(&config, &output).await,
+            OrgsCommands::Create { name } => orgs::create(&mut config, &output, name).await,
+            OrgsCommands::Switch { org } => orgs::switch(&mut config, &output, org).await,
+            OrgsCommands::Members { command } => match command {
+                None => orgs::members(&config, &output).await,
+                Some(OrgMembersCommands::Remove { username }) => {
+                    orgs::remove_member(&config, &output, username).await
+                }
+                Some(OrgMembersCommands::SetRole { username, role }) => {
+                    orgs::set_role(&config, &output, username, role).await
+                }
+            },
+            OrgsCommands::AddMember { username, role } => {
+                orgs::add_member(&config, &output, username, role).await
+            }
+            OrgsCommands::CreateKey { name, scope } => {
+                orgs::create_key(&config, &output, name, scope).await
+            }
+        },
+
+        Commands::Keys(cmd) => match cmd {
+            KeysCommands::List => keys::list(&config, &output).await,
+            KeysCommands::Create { name, scope } => {
+                keys::create(&config, &output, name, scope).await
+            }
+        },
 
         Commands::Projects(cmd) => match cmd {
             ProjectsCommands::List => projects::list(&config, &output).await,
@@ -206,41 +1217,553 @@ async fn main() -> Result<()> {
             ProjectsCommands::Use { project } => {
                 projects::use_project(&mut config, &output, project).await
             }
+            ProjectsCommands::Clone {
+                project,
+                name,
+                with_values,
+            } => projects::clone(&config, &output, project, name, with_values).await,
+            ProjectsCommands::Export { project, file } => {
+                projects::export(&config, &output, project, file).await
+            }
+            ProjectsCommands::Import { file, name } => {
+                projects::import(&config, &output, &file, name).await
+            }
+            ProjectsCommands::Stats { project } => projects::stats(&config, &output, project).await,
+            ProjectsCommands::Update {
+                project,
+                require_change_reason,
+            } => projects::update(&config, &output, project, require_change_reason).await,
+            ProjectsCommands::Share {
+                project,
+                role,
+                expires,
+            } => projects::share(&config, &output, project, role, expires).await,
+            ProjectsCommands::Links { project } => projects::links(&config, &output, project).await,
+            ProjectsCommands::RevokeLink { id, project } => {
+                projects::revoke_link(&config, &output, project, id).await
+            }
+            ProjectsCommands::Members { project } => {
+                projects::members(&config, &output, project).await
+            }
+            ProjectsCommands::RemoveMember { user, project } => {
+                projects::remove_member(&config, &output, project, user).await
+            }
         },
 
         Commands::Flags(cmd) => match cmd {
-            FlagsCommands::List => flags::list(&config, &output).await,
+            FlagsCommands::List { owner, repo } => flags::list(&config, &output, owner, repo).await,
             FlagsCommands::Create {
                 key,
                 name,
                 description,
                 flag_type,
                 enabled,
-            } => flags::create(&config, &output, key, name, description, flag_type, enabled).await,
-            FlagsCommands::Get { key } => flags::get(&config, &output, key).await,
-            FlagsCommands::Toggle { key } => flags::toggle(&config, &output, key).await,
-            FlagsCommands::Delete { key, yes } => flags::delete(&config, &output, key, yes).await,
+                expires,
+                owner,
+                repo,
+                code_path,
+                schema,
+                default_on,
+                default_value,
+                template,
+            } => {
+                flags::create(
+                    &config,
+                    &output,
+                    key,
+                    name,
+                    description,
+                    flag_type,
+                    enabled,
+                    expires,
+                    owner,
+                    repo,
+                    code_path,
+                    schema,
+                    default_on,
+                    default_value,
+                    template,
+                )
+                .await
+            }
+            FlagsCommands::Update {
+                key,
+                owner,
+                repo,
+                code_path,
+                default_on,
+                default_off,
+                default_value,
+                clear_default_value,
+                public,
+                private,
+            } => {
+                flags::update(
+                    &config,
+                    &output,
+                    key,
+                    owner,
+                    repo,
+                    code_path,
+                    (default_on || default_off).then_some(default_on),
+                    default_value,
+                    clear_default_value,
+                    (public || private).then_some(public),
+                )
+                .await
+            }
+            FlagsCommands::Expired => flags::expired(&config, &output).await,
+            FlagsCommands::Overrides => flags::overrides(&output),
+            FlagsCommands::Get { key, all_envs } => {
+                flags::get(&config, &output, key, all_envs).await
+            }
+            FlagsCommands::Stats { key } => flags::stats(&config, &output, key).await,
+            FlagsCommands::Fixtures { key, samples, out } => {
+                flags::fixtures(&config, &output, key, samples, out).await
+            }
+            FlagsCommands::Assignments { key, users_file } => {
+                flags::assignments(&config, &output, key, users_file).await
+            }
+            FlagsCommands::Allowlist { action } => {
+                user_list(&config, &output, "allowlist", action).await
+            }
+            FlagsCommands::Denylist { action } => {
+                user_list(&config, &output, "denylist", action).await
+            }
+            FlagsCommands::Toggle {
+                key,
+                expected_version,
+                confirm_production,
+                reason,
+                pattern,
+                regex,
+                on,
+                off,
+                guard,
+                guard_window,
+            } => match (key, pattern) {
+                (_, Some(pattern)) => match on.then_some(true).or(off.then_some(false)) {
+                    Some(enabled) => {
+                        flags::bulk_update(
+                            &config,
+                            &output,
+                            pattern,
+                            regex,
+                            flags::BulkChange::Enabled(enabled),
+                            confirm_production,
+                            reason,
+                        )
+                        .await
+                    }
+                    None => Err(FlagLiteError::ValidationError(
+                        "Pass --on or --off with --match".to_string(),
+                    )
+                    .into()),
+                },
+                (Some(key), None) => {
+                    flags::toggle(
+                        &config,
+                        &output,
+                        key,
+                        expected_version,
+                        confirm_production,
+                        reason,
+                        guards::spec(guard, guard_window),
+                    )
+                    .await
+                }
+                (None, None) => unreachable!("clap requires a key or --match"),
+            },
+            FlagsCommands::Rollout {
+                key,
+                percentage,
+                confirm_production,
+                reason,
+                pattern,
+                regex,
+            } => match (key, pattern) {
+                (_, Some(pattern)) => {
+                    let change = flags::BulkChange::Rollout(percentage);
+                    flags::bulk_update(
+                        &config,
+                        &output,
+                        pattern,
+                        regex,
+                        change,
+                        confirm_production,
+                        reason,
+                    )
+                    .await
+                }
+                (Some(key), None) => {
+                    flags::rollout(
+                        &config,
+                        &output,
+                        key,
+                        percentage,
+                        confirm_production,
+                        reason,
+                    )
+                    .await
+                }
+                (None, None) => unreachable!("clap requires a key or --match"),
+            },
+            FlagsCommands::Enable {
+                key,
+                value,
+                expected_version,
+                confirm_production,
+                reason,
+            } => {
+                flags::set_state(
+                    &config,
+                    &output,
+                    key,
+                    true,
+                    value,
+                    expected_version,
+                    confirm_production,
+                    reason,
+                )
+                .await
+            }
+            FlagsCommands::Disable {
+                key,
+                expected_version,
+                confirm_production,
+                reason,
+            } => {
+                flags::set_state(
+                    &config,
+                    &output,
+                    key,
+                    false,
+                    None,
+                    expected_version,
+                    confirm_production,
+                    reason,
+                )
+                .await
+            }
+            FlagsCommands::Propose {
+                key,
+                enable,
+                disable,
+                percentage,
+                comment,
+            } => {
+                let enabled = (enable || disable).then_some(enable);
+                change_requests::propose(&config, &output, key, enabled, percentage, comment).await
+            }
+            FlagsCommands::Ramp {
+                key,
+                to,
+                step,
+                interval,
+                confirm_production,
+                reason,
+                guard,
+                guard_window,
+            } => {
+                ramps::start(
+                    &config,
+                    &output,
+                    key,
+                    to,
+                    step,
+                    interval,
+                    confirm_production,
+                    reason,
+                    guards::spec(guard, guard_window),
+                )
+                .await
+            }
+            FlagsCommands::History {
+                key,
+                limit,
+                cursor,
+                export,
+            } => flags::history(&config, &output, key, limit, cursor, export).await,
+            FlagsCommands::Rollback {
+                key,
+                to,
+                expected_version,
+                confirm_production,
+                reason,
+            } => {
+                flags::rollback(
+                    &config,
+                    &output,
+                    key,
+                    to,
+                    expected_version,
+                    confirm_production,
+                    reason,
+                )
+                .await
+            }
+            FlagsCommands::Delete { key } => flags::delete(&config, &output, key).await,
+            FlagsCommands::Scan { src } => scan::scan(&config, &output, src).await,
+            FlagsCommands::Watch { interval } => flags::watch(&config, &output, interval).await,
+            FlagsCommands::Notify { keys, interval } => {
+                flags::notify(&config, &output, keys, interval).await
+            }
         },
 
         Commands::Envs(cmd) => match cmd {
             EnvsCommands::List => envs::list(&config, &output).await,
+            EnvsCommands::Create { name, copy_from } => {
+                envs::create(&config, &output, name, copy_from).await
+            }
             EnvsCommands::Use { name } => envs::use_env(&mut config, &output, name).await,
+            EnvsCommands::Protect { name } => {
+                envs::set_protected(&config, &output, name, true).await
+            }
+            EnvsCommands::Unprotect { name } => {
+                envs::set_protected(&config, &output, name, false).await
+            }
+            EnvsCommands::Update {
+                name,
+                color,
+                emoji,
+                cache_max_age,
+                cache_stale,
+            } => {
+                envs::update(
+                    &config,
+                    &output,
+                    name,
+                    color,
+                    emoji,
+                    cache_max_age,
+                    cache_stale,
+                )
+                .await
+            }
+            EnvsCommands::Reorder { names } => envs::reorder(&config, &output, names).await,
+            EnvsCommands::Freeze { name } => envs::set_frozen(&config, &output, name, true).await,
+            EnvsCommands::Unfreeze { name } => {
+                envs::set_frozen(&config, &output, name, false).await
+            }
+            EnvsCommands::Approval { name, required } => {
+                envs::approval(&config, &output, name, required).await
+            }
+            EnvsCommands::Publish { name } => envs::set_public(&config, &output, name, true).await,
+            EnvsCommands::Unpublish { name } => {
+                envs::set_public(&config, &output, name, false).await
+            }
+            EnvsCommands::Delete { name, force } => {
+                envs::delete(&config, &output, name, force).await
+            }
+            EnvsCommands::Deleted => envs::list_deleted(&config, &output).await,
+            EnvsCommands::Restore { name } => envs::restore(&config, &output, name).await,
+            EnvsCommands::RotateSigningKey { name } => {
+                envs::rotate_signing_key(&config, &output, name).await
+            }
+            EnvsCommands::Copy {
+                from,
+                to,
+                dry_run,
+                confirm_production,
+                reason,
+            } => {
+                envs::copy(
+                    &config,
+                    &output,
+                    from,
+                    to,
+                    dry_run,
+                    confirm_production,
+                    reason,
+                )
+                .await
+            }
         },
 
-        Commands::Config { path } => {
-            if path {
-                println!("{}", config::Config::config_path()?.display());
-            } else {
-                output.print_config(&config)?;
+        Commands::Templates(cmd) => match cmd {
+            TemplatesCommands::List => templates::list(&config, &output).await,
+            TemplatesCommands::Set {
+                name,
+                description,
+                default,
+                envs,
+            } => templates::set(&config, &output, name, description, default, envs).await,
+            TemplatesCommands::Delete { name } => templates::delete(&config, &output, name).await,
+        },
+
+        Commands::Guards(cmd) => match cmd {
+            GuardsCommands::List { status } => guards::list(&config, &output, status).await,
+            GuardsCommands::Cancel { id } => guards::cancel(&config, &output, id).await,
+            GuardsCommands::Report {
+                token,
+                message,
+                healthy,
+            } => guards::report(&config, &output, token, healthy, message).await,
+        },
+
+        Commands::Ramps(cmd) => match cmd {
+            RampsCommands::List { status } => ramps::list(&config, &output, status).await,
+            RampsCommands::Status { id } => ramps::status(&config, &output, id).await,
+            RampsCommands::Pause { id } => {
+                ramps::control(&config, &output, id, RampAction::Pause).await
+            }
+            RampsCommands::Resume { id } => {
+                ramps::control(&config, &output, id, RampAction::Resume).await
+            }
+            RampsCommands::Abort { id } => {
+                ramps::control(&config, &output, id, RampAction::Abort).await
+            }
+        },
+
+        Commands::ChangeRequests(cmd) => match cmd {
+            ChangeRequestsCommands::List { status } => {
+                change_requests::list(&config, &output, status).await
+            }
+            ChangeRequestsCommands::Approve { id, comment } => {
+                change_requests::review(&config, &output, id, true, comment).await
+            }
+            ChangeRequestsCommands::Reject { id, comment } => {
+                change_requests::review(&config, &output, id, false, comment).await
             }
-            Ok(())
+        },
+
+        Commands::Toggle {
+            key,
+            expected_version,
+            confirm_production,
+            reason,
+        } => {
+            flags::toggle(
+                &config,
+                &output,
+                key,
+                expected_version,
+                confirm_production,
+                reason,
+                None,
+            )
+            .await
+        }
+
+        Commands::Search { query } => flags::search(&config, &output, query).await,
+
+        Commands::Sync {
+            out,
+            interval,
+            signal_pid,
+            signal_pid_file,
+            once,
+        } => {
+            let signal = signal_pid
+                .map(sync::SignalTarget::Pid)
+                .or(signal_pid_file.map(sync::SignalTarget::PidFile));
+            sync::sync(&config, &output, out, interval, signal, once).await
         }
+
+        Commands::Config { path, action } => match action {
+            Some(ConfigCommands::Get { key }) => commands::config::get(&config, &output, key),
+            Some(ConfigCommands::Set { key, value, global }) => {
+                commands::config::set(&config, &output, key, value, global)
+            }
+            Some(ConfigCommands::Unset { key, global }) => {
+                commands::config::unset(&config, &output, key, global)
+            }
+            None if path => {
+                println!("{}", config::Config::config_path()?.display());
+                Ok(())
+            }
+            None => output.print_config(&config),
+        },
     };
 
+    match &result {
+        Ok(()) => output.finish(),
+        Err(e) => output.print_error(e),
+    }
+    if let Some(timings) = &config.timings {
+        timings.print();
+    }
     if let Err(e) = result {
-        output.print_error(&e);
-        std::process::exit(1);
+        std::process::exit(exit_code::for_error(&e));
     }
 
     Ok(())
 }
+
+/// Run a `flags allowlist` or `flags denylist` subcommand
+async fn user_list(
+    config: &config::Config,
+    output: &output::Output,
+    list: &str,
+    action: UserListCommands,
+) -> Result<()> {
+    let (key, change, confirm_production, reason) = match action {
+        UserListCommands::List { key } => {
+            return flags::user_list(config, output, list, key).await;
+        }
+        UserListCommands::Add {
+            key,
+            users,
+            file,
+            confirm_production,
+            reason,
+        } => {
+            let change = flags::UserListChange {
+                users,
+                file,
+                remove: false,
+                clear: false,
+            };
+            (key, change, confirm_production, reason)
+        }
+        UserListCommands::Remove {
+            key,
+            users,
+            file,
+            confirm_production,
+            reason,
+        } => {
+            let change = flags::UserListChange {
+                users,
+                file,
+                remove: true,
+                clear: false,
+            };
+            (key, change, confirm_production, reason)
+        }
+        UserListCommands::Clear {
+            key,
+            confirm_production,
+            reason,
+        } => {
+            let change = flags::UserListChange {
+                users: Vec::new(),
+                file: None,
+                remove: false,
+                clear: true,
+            };
+            (key, change, confirm_production, reason)
+        }
+    };
+    flags::change_user_list(
+        config,
+        output,
+        list,
+        key,
+        change,
+        confirm_production,
+        reason,
+    )
+    .await
+}
+
+/// The subcommands that were run, e.g. `flags list`
+fn command_path(matches: &clap::ArgMatches) -> String {
+    let mut names = Vec::new();
+    let mut matches = matches;
+    while let Some((name, sub)) = matches.subcommand() {
+        names.push(name);
+        matches = sub;
+    }
+    names.join(" ")
+}
@@ -0,0 +1,40 @@
+//! Desktop notifications (Notification Center on macOS, toasts on Windows, the
+//! freedesktop notification service on Linux), for `flags notify`
+//!
+//! Needs the `notifications` feature, on by default; without it `check` fails
+//! with a hint to rebuild.
+
+use anyhow::Result;
+
+/// Fail early if this build can't show notifications
+pub fn check() -> Result<()> {
+    #[cfg(feature = "notifications")]
+    return Ok(());
+    #[cfg(not(feature = "notifications"))]
+    unavailable()
+}
+
+/// Show a notification with a one-line summary and a body
+pub fn show(summary: &str, body: &str) -> Result<()> {
+    #[cfg(feature = "notifications")]
+    {
+        notify_rust::Notification::new()
+            .appname("flaglite")
+            .summary(summary)
+            .body(body)
+            .show()?;
+        Ok(())
+    }
+    #[cfg(not(feature = "notifications"))]
+    {
+        let _ = (summary, body);
+        unavailable()
+    }
+}
+
+#[cfg(not(feature = "notifications"))]
+fn unavailable<T>() -> Result<T> {
+    anyhow::bail!(
+        "this build of flaglite lacks the `notifications` feature (cargo install flaglite --features notifications)"
+    )
+}
@@ -1,11 +1,26 @@
 //! Output formatting for FlagLite CLI
 
+use crate::commands::ramps;
+use crate::commands::scan::{ScanReport, ScannedFlag};
 use crate::config::Config;
+use crate::exit_code;
+use crate::suggestions;
 use anyhow::Result;
+use chrono::{DateTime, Utc};
 use colored::*;
-use flaglite_client::{Environment, Flag, FlagWithState, Project, User};
+use flaglite_client::{
+    ApiKey, ApiKeyCreated, Assignment, ChangeRequest, DeletedEnvironment, Environment, Flag,
+    FlagHistory, FlagLiteError, FlagSearchResult, FlagStats, FlagTemplate, FlagWithState, Guard,
+    InviteLink, OrgMember, Organization, Overrides, Project, ProjectMember, ProjectStats, Ramp,
+    RequestLog, User, UserList,
+};
+use indicatif::{ProgressBar, ProgressStyle};
 use serde::Serialize;
+use std::future::Future;
+use std::io::IsTerminal;
 use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use tabled::{settings::Style, Table, Tabled};
 
 /// Output format
@@ -14,6 +29,13 @@ pub enum OutputFormat {
     #[default]
     Pretty,
     Json,
+    /// JSON wrapped in an envelope that says whether the command succeeded,
+    /// with the error and metadata in fixed places (see [`Envelope`])
+    JsonV2,
+    /// Comma-separated rows for list commands; other commands print JSON
+    Csv,
+    /// Tab-separated rows for list commands; other commands print JSON
+    Tsv,
 }
 
 impl FromStr for OutputFormat {
@@ -23,51 +45,350 @@ impl FromStr for OutputFormat {
         match s.to_lowercase().as_str() {
             "pretty" | "table" => Ok(OutputFormat::Pretty),
             "json" => Ok(OutputFormat::Json),
-            _ => Err(format!("Unknown format: {s}. Use 'pretty' or 'json'.")),
+            "json-v2" => Ok(OutputFormat::JsonV2),
+            "csv" => Ok(OutputFormat::Csv),
+            "tsv" => Ok(OutputFormat::Tsv),
+            _ => Err(format!(
+                "Unknown format: {s}. Use 'pretty', 'json', 'json-v2', 'csv' or 'tsv'."
+            )),
         }
     }
 }
 
+/// Where `--output` finds the one value to print in a command's JSON result:
+/// `.api_key`, `.environments.production.enabled`, `.[0].key`
+#[derive(Debug, Clone, PartialEq)]
+pub struct JsonPath {
+    source: String,
+    segments: Vec<PathSegment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum PathSegment {
+    Field(String),
+    Index(usize),
+}
+
+impl FromStr for JsonPath {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid =
+            || format!("Invalid path: {s}. Use fields and indexes like .api_key or .[0].key");
+        let rest = s.strip_prefix('.').unwrap_or(s);
+
+        let mut segments = Vec::new();
+        for part in rest.split('.').filter(|_| !rest.is_empty()) {
+            let (field, mut indexes) = part.split_at(part.find('[').unwrap_or(part.len()));
+            if !field.is_empty() {
+                segments.push(PathSegment::Field(field.to_string()));
+            } else if indexes.is_empty() {
+                return Err(invalid());
+            }
+            while !indexes.is_empty() {
+                let (index, after) = indexes
+                    .strip_prefix('[')
+                    .and_then(|i| i.split_once(']'))
+                    .ok_or_else(invalid)?;
+                segments.push(PathSegment::Index(index.parse().map_err(|_| invalid())?));
+                indexes = after;
+            }
+        }
+
+        Ok(Self {
+            source: s.to_string(),
+            segments,
+        })
+    }
+}
+
+impl JsonPath {
+    /// The value at the path, if the result has one there
+    fn select<'a>(&self, value: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+        self.segments
+            .iter()
+            .try_fold(value, |value, segment| match segment {
+                PathSegment::Field(field) => value.get(field),
+                PathSegment::Index(index) => value.get(index),
+            })
+    }
+}
+
+/// `--format json-v2`: every command prints exactly one document to stdout,
+/// success or not
+///
+/// ```json
+/// {
+///   "ok": true,
+///   "data": [{"key": "new-checkout", "enabled": true}],
+///   "error": null,
+///   "meta": {"command": "flags list", "cli_version": "0.1.1", "duration_ms": 84, "warnings": []}
+/// }
+/// ```
+///
+/// `data` is what `--format json` prints (null for commands without a
+/// result, an array for the rare command with several). On failure `ok` is
+/// false and `error` has `message`, `code` (`general`, `auth`, `not_found`,
+/// `validation` or `network`) and `exit_code`, plus `fields` or
+/// `suggestions` when the error has them. `flags watch` prints one compact
+/// envelope per change instead.
+struct Envelope {
+    command: String,
+    started: Instant,
+    data: Mutex<Vec<serde_json::Value>>,
+    warnings: Mutex<Vec<String>>,
+}
+
+impl Envelope {
+    fn meta(&self) -> serde_json::Value {
+        serde_json::json!({
+            "command": self.command,
+            "cli_version": env!("CARGO_PKG_VERSION"),
+            "duration_ms": self.started.elapsed().as_millis() as u64,
+            "warnings": *self.warnings.lock().unwrap_or_else(|e| e.into_inner()),
+        })
+    }
+
+    fn print(&self, data: serde_json::Value, error: Option<serde_json::Value>) {
+        let envelope = serde_json::json!({
+            "ok": error.is_none(),
+            "data": data,
+            "error": error,
+            "meta": self.meta(),
+        });
+        println!("{}", serde_json::to_string_pretty(&envelope).unwrap());
+    }
+}
+
 /// Output handler
 pub struct Output {
     format: OutputFormat,
+    /// --quiet: results only, without success, info or warning messages
+    quiet: bool,
+    /// --output: print only this value of a command's JSON result
+    select: Option<JsonPath>,
+    /// Prompts are allowed: no --no-input, and stdin is a terminal
+    interactive: bool,
+    /// Spinners are shown: pretty format, stderr is a terminal, and no
+    /// --verbose request log to interleave with
+    spinners: bool,
+    /// Set for --format json-v2: results are collected and printed once
+    envelope: Option<Envelope>,
 }
 
 impl Output {
-    pub fn new(format: OutputFormat) -> Self {
-        Self { format }
+    pub fn new(format: OutputFormat, no_input: bool, verbose: bool) -> Self {
+        Self {
+            format,
+            quiet: false,
+            select: None,
+            interactive: !no_input && std::io::stdin().is_terminal(),
+            spinners: matches!(format, OutputFormat::Pretty)
+                && !verbose
+                && std::io::stderr().is_terminal(),
+            envelope: None,
+        }
+    }
+
+    /// Name the command being run (`flags list`), which starts its clock;
+    /// with --format json-v2 both go in the envelope's `meta`
+    pub fn command(mut self, command: String) -> Self {
+        if matches!(self.format, OutputFormat::JsonV2) {
+            self.envelope = Some(Envelope {
+                command,
+                started: Instant::now(),
+                data: Mutex::default(),
+                warnings: Mutex::default(),
+            });
+        }
+        self
     }
 
+    /// The envelope, unless --output prints a bare value instead
+    fn envelope(&self) -> Option<&Envelope> {
+        self.envelope.as_ref().filter(|_| self.select.is_none())
+    }
+
+    /// Print the json-v2 envelope for a command that succeeded; other
+    /// formats have already printed everything
+    pub fn finish(&self) {
+        let Some(envelope) = self.envelope() else {
+            return;
+        };
+        let mut data =
+            std::mem::take(&mut *envelope.data.lock().unwrap_or_else(|e| e.into_inner()));
+        let data = match data.len() {
+            0 => serde_json::Value::Null,
+            1 => data.remove(0),
+            _ => serde_json::Value::Array(data),
+        };
+        envelope.print(data, None);
+    }
+
+    /// Leave out messages and spinners, for scripts that only want results
+    pub fn quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self.spinners &= !quiet;
+        self
+    }
+
+    /// Print only the value at `path` of each JSON result, unquoted if it's
+    /// a string. Implies JSON output, so commands build their JSON results.
+    pub fn select(mut self, path: Option<JsonPath>) -> Self {
+        if path.is_some() {
+            self.spinners = false;
+        }
+        self.select = path;
+        self
+    }
+
+    /// Await `future` behind a spinner, so slow calls don't look hung
+    pub async fn with_spinner<F: Future>(&self, message: &str, future: F) -> F::Output {
+        if !self.spinners {
+            return future.await;
+        }
+
+        let spinner = ProgressBar::new_spinner()
+            .with_style(ProgressStyle::with_template("{spinner:.cyan} {msg}").unwrap())
+            .with_message(message.to_string());
+        spinner.enable_steady_tick(Duration::from_millis(100));
+        let result = future.await;
+        spinner.finish_and_clear();
+        result
+    }
+
+    /// Whether commands may prompt the user
+    pub fn can_prompt(&self) -> bool {
+        !self.is_json() && self.interactive
+    }
+
+    /// Fail fast instead of prompting when nobody can answer; `hint` says
+    /// which flags supply the input instead
+    pub fn require_prompt(&self, hint: &str) -> Result<()> {
+        if self.can_prompt() {
+            return Ok(());
+        }
+
+        let why = if self.is_json() {
+            "machine-readable --format"
+        } else {
+            "--no-input or no terminal"
+        };
+        Err(FlagLiteError::ValidationError(format!(
+            "Input required, but prompts are disabled ({why}). {hint}"
+        ))
+        .into())
+    }
+
+    /// True for every machine-readable format, since CSV/TSV fall back to JSON
+    /// for anything that isn't a list, and with --output
     pub fn is_json(&self) -> bool {
-        matches!(self.format, OutputFormat::Json)
+        !matches!(self.format, OutputFormat::Pretty) || self.select.is_some()
+    }
+
+    /// Print a list as CSV/TSV rows under a header if that's the format.
+    /// Returns false (printing nothing) for other formats.
+    fn delimited<T>(&self, items: &[T], header: &[&str], row: impl Fn(&T) -> Vec<String>) -> bool {
+        let delimiter = match self.format {
+            _ if self.select.is_some() => return false,
+            OutputFormat::Csv => ',',
+            OutputFormat::Tsv => '\t',
+            _ => return false,
+        };
+
+        let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+        println!("{}", delimited_line(&header, delimiter));
+        for item in items {
+            println!("{}", delimited_line(&row(item), delimiter));
+        }
+        true
     }
 
     /// Print a success message
     pub fn success(&self, message: &str) {
-        if !self.is_json() {
+        if !self.is_json() && !self.quiet {
             println!("{} {}", "✓".green().bold(), message);
         }
     }
 
     /// Print an info message
     pub fn info(&self, message: &str) {
-        if !self.is_json() {
+        if !self.is_json() && !self.quiet {
             println!("{} {}", "ℹ".blue().bold(), message);
         }
     }
 
     /// Print a warning message
     pub fn warn(&self, message: &str) {
-        if !self.is_json() {
+        if let Some(envelope) = self.envelope() {
+            let mut warnings = envelope.warnings.lock().unwrap_or_else(|e| e.into_inner());
+            warnings.push(message.to_string());
+        }
+        if !self.is_json() && !self.quiet {
             println!("{} {}", "⚠".yellow().bold(), message);
         }
     }
 
     /// Print an error
     pub fn print_error(&self, error: &anyhow::Error) {
-        if self.is_json() {
-            let err = serde_json::json!({ "error": error.to_string() });
+        let invalid_fields =
+            error
+                .chain()
+                .find_map(|cause| match cause.downcast_ref::<FlagLiteError>() {
+                    Some(FlagLiteError::InvalidFields(fields)) => Some(fields),
+                    _ => None,
+                });
+
+        let suggestions =
+            error
+                .chain()
+                .find_map(|cause| match cause.downcast_ref::<FlagLiteError>() {
+                    Some(FlagLiteError::FlagNotFound { suggestions, .. }) => Some(suggestions),
+                    _ => None,
+                });
+
+        let next_steps = suggestions::for_error(error);
+
+        if let Some(envelope) = self.envelope() {
+            let code = exit_code::for_error(error);
+            let mut err = serde_json::json!({
+                "message": error.to_string(),
+                "code": exit_code::name(code),
+                "exit_code": code,
+            });
+            if let Some(fields) = invalid_fields {
+                err["message"] = "Validation failed".into();
+                err["fields"] = serde_json::json!(fields);
+            } else if let Some(suggestions) = suggestions {
+                err["suggestions"] = serde_json::json!(suggestions);
+            }
+            if !next_steps.is_empty() {
+                err["next_steps"] = serde_json::json!(next_steps);
+            }
+            envelope.print(serde_json::Value::Null, Some(err));
+            return;
+        }
+
+        // A script capturing an --output value gets errors on stderr instead
+        if self.is_json() && self.select.is_none() {
+            let mut err = match (invalid_fields, suggestions) {
+                (Some(fields), _) => {
+                    serde_json::json!({ "error": "Validation failed", "fields": fields })
+                }
+                (None, Some(suggestions)) => {
+                    serde_json::json!({ "error": error.to_string(), "suggestions": suggestions })
+                }
+                (None, None) => serde_json::json!({ "error": error.to_string() }),
+            };
+            if !next_steps.is_empty() {
+                err["next_steps"] = serde_json::json!(next_steps);
+            }
             println!("{}", serde_json::to_string_pretty(&err).unwrap());
+        } else if let Some(fields) = invalid_fields {
+            eprintln!("{} Validation failed", "✗".red().bold());
+            for field in fields {
+                eprintln!("  {} {}", format!("{}:", field.field).bold(), field.message);
+            }
         } else {
             eprintln!("{} {}", "✗".red().bold(), error);
 
@@ -75,12 +396,89 @@ impl Output {
             for cause in error.chain().skip(1) {
                 eprintln!("  {} {}", "caused by:".dimmed(), cause);
             }
+
+            for step in &next_steps {
+                eprintln!(
+                    "  {} {}  {}",
+                    "run:".dimmed(),
+                    step.command.bold(),
+                    format!("({})", step.description).dimmed()
+                );
+            }
         }
     }
 
+    /// Print one line of the --verbose request log to stderr
+    pub fn log_request(request: &RequestLog) {
+        let outcome = match (&request.status, &request.error) {
+            (Some(status), _) if status.is_success() => status.to_string().green(),
+            (Some(status), _) => status.to_string().red(),
+            (None, Some(error)) => error.red(),
+            (None, None) => "no response".red(),
+        };
+        let auth = request
+            .authorization
+            .as_deref()
+            .map(|auth| format!(" (Authorization: {auth})"))
+            .unwrap_or_default();
+
+        eprintln!(
+            "{} {} {} {} {}{}",
+            "→".dimmed(),
+            request.method.as_str().bold(),
+            request.url,
+            outcome,
+            format!("{}ms", request.duration.as_millis()).dimmed(),
+            auth.dimmed()
+        );
+    }
+
     /// Print JSON output
     pub fn json<T: Serialize + ?Sized>(&self, value: &T) -> Result<()> {
-        println!("{}", serde_json::to_string_pretty(value)?);
+        if let Some(envelope) = self.envelope() {
+            let value = serde_json::to_value(value)?;
+            envelope
+                .data
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(value);
+            return Ok(());
+        }
+        let Some(path) = &self.select else {
+            println!("{}", serde_json::to_string_pretty(value)?);
+            return Ok(());
+        };
+
+        let value = serde_json::to_value(value)?;
+        match path.select(&value) {
+            Some(serde_json::Value::String(s)) => println!("{s}"),
+            Some(selected) => println!("{selected}"),
+            None => {
+                return Err(FlagLiteError::ValidationError(format!(
+                    "--output {}: the result has no such value",
+                    path.source
+                ))
+                .into())
+            }
+        }
+        Ok(())
+    }
+
+    /// Print one compact JSON line of a stream, e.g. `flags watch`; in its
+    /// own envelope with --format json-v2
+    pub fn json_line<T: Serialize + ?Sized>(&self, value: &T) -> Result<()> {
+        match self.envelope() {
+            Some(envelope) => {
+                let line = serde_json::json!({
+                    "ok": true,
+                    "data": value,
+                    "error": null,
+                    "meta": envelope.meta(),
+                });
+                println!("{line}");
+            }
+            None => println!("{}", serde_json::to_string(value)?),
+        }
         Ok(())
     }
 
@@ -93,7 +491,12 @@ impl Output {
         println!("{}", "User Information".bold().underline());
         println!("  {} {}", "Username:".dimmed(), user.username.cyan());
         if let Some(email) = &user.email {
-            println!("  {} {}", "Email:".dimmed(), email);
+            let status = if user.email_verified {
+                "verified".green()
+            } else {
+                "unverified".yellow()
+            };
+            println!("  {} {} ({status})", "Email:".dimmed(), email);
         }
         println!("  {} {}", "ID:".dimmed(), user.id.dimmed());
         println!(
@@ -107,6 +510,22 @@ impl Output {
 
     /// Print project list
     pub fn print_projects(&self, projects: &[Project], current: Option<&str>) -> Result<()> {
+        if self.delimited(
+            projects,
+            &["id", "name", "slug", "org_id", "created_at"],
+            |p| {
+                vec![
+                    p.id.to_string(),
+                    p.name.clone(),
+                    p.slug.clone(),
+                    p.org_id.clone().unwrap_or_default(),
+                    p.created_at.to_rfc3339(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
         if self.is_json() {
             return self.json(projects);
         }
@@ -171,47 +590,79 @@ impl Output {
         Ok(())
     }
 
-    /// Print environment list
-    pub fn print_environments(&self, envs: &[Environment], current: Option<&str>) -> Result<()> {
+    pub fn print_project_stats(&self, stats: &ProjectStats) -> Result<()> {
+        if self.delimited(
+            &stats.environments,
+            &[
+                "environment",
+                "enabled",
+                "enabled_percentage",
+                "partial_rollout",
+                "last_changed_at",
+                "api_key_created_at",
+            ],
+            |e| {
+                vec![
+                    e.name.clone(),
+                    e.enabled.to_string(),
+                    e.enabled_percentage.to_string(),
+                    e.partial_rollout.to_string(),
+                    e.last_changed_at
+                        .map(|at| at.to_rfc3339())
+                        .unwrap_or_default(),
+                    e.api_key_created_at.to_rfc3339(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
         if self.is_json() {
-            return self.json(envs);
+            return self.json(stats);
         }
 
-        if envs.is_empty() {
-            self.info("No environments found.");
-            return Ok(());
-        }
+        let now = Utc::now();
+        let changed = |at: Option<DateTime<Utc>>| {
+            at.map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "never".to_string())
+        };
+
+        println!("{} {}", stats.slug.bold(), "usage".dimmed());
+        println!("  {} {}", "Flags:".dimmed(), stats.flags);
+        println!(
+            "  {} {}",
+            "Last change:".dimmed(),
+            changed(stats.last_changed_at)
+        );
+        println!(
+            "  {} {}",
+            "Project key age:".dimmed(),
+            age(stats.api_key_created_at, now)
+        );
+        println!();
 
         #[derive(Tabled)]
-        struct EnvRow {
-            #[tabled(rename = "")]
-            current: String,
-            #[tabled(rename = "Name")]
-            name: String,
-            #[tabled(rename = "Slug")]
-            slug: String,
-            #[tabled(rename = "Production")]
-            production: String,
+        struct StatsRow {
+            #[tabled(rename = "Environment")]
+            environment: String,
+            #[tabled(rename = "Enabled")]
+            enabled: String,
+            #[tabled(rename = "Partial Rollout")]
+            partial_rollout: i64,
+            #[tabled(rename = "Last Changed")]
+            last_changed: String,
+            #[tabled(rename = "Key Age")]
+            key_age: String,
         }
 
-        let rows: Vec<_> = envs
+        let rows: Vec<_> = stats
+            .environments
             .iter()
-            .map(|e| {
-                let is_current = current.is_some_and(|c| c == e.name || c == e.slug);
-                EnvRow {
-                    current: if is_current {
-                        "→".green().to_string()
-                    } else {
-                        "".to_string()
-                    },
-                    name: e.name.clone(),
-                    slug: e.slug.clone(),
-                    production: if e.is_production {
-                        "●".red().to_string()
-                    } else {
-                        "".to_string()
-                    },
-                }
+            .map(|e| StatsRow {
+                environment: e.name.clone(),
+                enabled: format!("{} ({}%)", e.enabled, e.enabled_percentage),
+                partial_rollout: e.partial_rollout,
+                last_changed: changed(e.last_changed_at),
+                key_age: age(e.api_key_created_at, now),
             })
             .collect();
 
@@ -221,43 +672,63 @@ impl Output {
         Ok(())
     }
 
-    /// Print flag list
-    pub fn print_flags(&self, flags: &[FlagWithState]) -> Result<()> {
+    /// Print organization list
+    pub fn print_orgs(&self, orgs: &[Organization], current: Option<&str>) -> Result<()> {
+        if self.delimited(
+            orgs,
+            &["id", "name", "role", "personal", "created_at"],
+            |o| {
+                vec![
+                    o.id.clone(),
+                    o.name.clone(),
+                    o.role.clone(),
+                    o.personal.to_string(),
+                    o.created_at.to_rfc3339(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
         if self.is_json() {
-            return self.json(flags);
+            return self.json(orgs);
         }
 
-        if flags.is_empty() {
-            self.info("No flags found. Create one with 'flaglite flags create <key>'");
+        if orgs.is_empty() {
+            self.info("No organizations found. Create one with 'flaglite orgs create <name>'");
             return Ok(());
         }
 
         #[derive(Tabled)]
-        struct FlagRow {
-            #[tabled(rename = "Status")]
-            status: String,
-            #[tabled(rename = "Key")]
-            key: String,
+        struct OrgRow {
+            #[tabled(rename = "")]
+            current: String,
+            #[tabled(rename = "ID")]
+            id: String,
             #[tabled(rename = "Name")]
             name: String,
-            #[tabled(rename = "Type")]
-            flag_type: String,
-            #[tabled(rename = "Updated")]
-            updated: String,
+            #[tabled(rename = "Role")]
+            role: String,
+            #[tabled(rename = "Personal")]
+            personal: String,
         }
 
-        let rows: Vec<_> = flags
+        let rows: Vec<_> = orgs
             .iter()
-            .map(|f| FlagRow {
-                status: if f.enabled {
-                    "●".green().to_string()
+            .map(|o| OrgRow {
+                current: if current == Some(o.id.as_str()) {
+                    "→".green().to_string()
                 } else {
-                    "○".dimmed().to_string()
+                    "".to_string()
+                },
+                id: o.id.clone(),
+                name: o.name.clone(),
+                role: o.role.clone(),
+                personal: if o.personal {
+                    "✓".to_string()
+                } else {
+                    "".to_string()
                 },
-                key: f.flag.key.clone(),
-                name: f.flag.name.clone(),
-                flag_type: f.flag.flag_type.to_string(),
-                updated: f.flag.updated_at.format("%Y-%m-%d %H:%M").to_string(),
             })
             .collect();
 
@@ -267,79 +738,1416 @@ impl Output {
         Ok(())
     }
 
-    /// Print flag details
-    pub fn print_flag(&self, flag: &FlagWithState) -> Result<()> {
-        if self.is_json() {
-            return self.json(flag);
+    /// Print organization members
+    pub fn print_org_members(&self, members: &[OrgMember]) -> Result<()> {
+        if self.delimited(members, &["username", "role", "created_at"], |m| {
+            vec![
+                m.username.clone(),
+                m.role.clone(),
+                m.created_at.to_rfc3339(),
+            ]
+        }) {
+            return Ok(());
         }
 
-        let status = if flag.enabled {
-            "ENABLED".green().bold()
-        } else {
-            "DISABLED".red().bold()
-        };
-
-        println!("{} {}", flag.flag.key.bold(), status);
-        println!();
-        println!("  {} {}", "Name:".dimmed(), flag.flag.name);
-        println!("  {} {}", "Type:".dimmed(), flag.flag.flag_type);
-
-        if let Some(desc) = &flag.flag.description {
-            println!("  {} {}", "Description:".dimmed(), desc);
+        if self.is_json() {
+            return self.json(members);
         }
 
-        if let Some(value) = &flag.value {
-            println!(
-                "  {} {}",
-                "Value:".dimmed(),
-                serde_json::to_string(value).unwrap_or_default().cyan()
-            );
+        #[derive(Tabled)]
+        struct MemberRow {
+            #[tabled(rename = "Username")]
+            username: String,
+            #[tabled(rename = "Role")]
+            role: String,
+            #[tabled(rename = "Joined")]
+            joined: String,
         }
 
-        println!("  {} {}", "ID:".dimmed(), flag.flag.id.to_string().dimmed());
-        println!(
-            "  {} {}",
-            "Created:".dimmed(),
-            flag.flag.created_at.format("%Y-%m-%d %H:%M")
-        );
-        println!(
-            "  {} {}",
-            "Updated:".dimmed(),
-            flag.flag.updated_at.format("%Y-%m-%d %H:%M")
-        );
+        let rows: Vec<_> = members
+            .iter()
+            .map(|m| MemberRow {
+                username: m.username.clone(),
+                role: m.role.clone(),
+                joined: m.created_at.format("%Y-%m-%d").to_string(),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
 
         Ok(())
     }
 
-    /// Print a single flag (without state)
-    pub fn print_flag_created(&self, flag: &Flag) -> Result<()> {
+    /// Print the user's API keys
+    pub fn print_api_keys(&self, keys: &[ApiKey]) -> Result<()> {
+        if self.delimited(
+            keys,
+            &[
+                "id",
+                "key_prefix",
+                "name",
+                "scope",
+                "org_id",
+                "created_at",
+                "revoked_at",
+            ],
+            |k| {
+                vec![
+                    k.id.clone(),
+                    k.key_prefix.clone(),
+                    k.name.clone().unwrap_or_default(),
+                    k.scope.clone(),
+                    k.org_id.clone().unwrap_or_default(),
+                    k.created_at.to_rfc3339(),
+                    k.revoked_at.map(|at| at.to_rfc3339()).unwrap_or_default(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
         if self.is_json() {
-            return self.json(flag);
+            return self.json(keys);
         }
 
-        println!("{}", "Flag Created".bold().green());
-        println!("  {} {}", "Key:".dimmed(), flag.key.cyan());
-        println!("  {} {}", "Name:".dimmed(), flag.name);
-        println!("  {} {}", "Type:".dimmed(), flag.flag_type);
-        if let Some(desc) = &flag.description {
-            println!("  {} {}", "Description:".dimmed(), desc);
+        if keys.is_empty() {
+            self.info("No API keys found. Create one with 'flaglite keys create'");
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct KeyRow {
+            #[tabled(rename = "Key")]
+            prefix: String,
+            #[tabled(rename = "Name")]
+            name: String,
+            #[tabled(rename = "Scope")]
+            scope: String,
+            #[tabled(rename = "Org")]
+            org: String,
+            #[tabled(rename = "Created")]
+            created: String,
+            #[tabled(rename = "Status")]
+            status: String,
         }
 
+        let rows: Vec<_> = keys
+            .iter()
+            .map(|k| KeyRow {
+                prefix: format!("{}…", k.key_prefix),
+                name: k.name.clone().unwrap_or_else(|| "-".to_string()),
+                scope: k.scope.clone(),
+                org: k
+                    .org_id
+                    .as_deref()
+                    .map(|id| id.chars().take(8).collect())
+                    .unwrap_or_else(|| "all".to_string()),
+                created: k.created_at.format("%Y-%m-%d").to_string(),
+                status: match k.revoked_at {
+                    Some(at) => format!("revoked {}", at.format("%Y-%m-%d"))
+                        .dimmed()
+                        .to_string(),
+                    None => "active".green().to_string(),
+                },
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
         Ok(())
     }
 
-    /// Print config
-    pub fn print_config(&self, config: &Config) -> Result<()> {
+    /// Print a newly created API key, which is only shown this once
+    pub fn print_created_key(&self, key: &ApiKeyCreated) -> Result<()> {
         if self.is_json() {
-            // Don't expose sensitive info in JSON output
-            let safe = serde_json::json!({
-                "api_url": config.api_url,
-                "project_id": config.project_id,
-                "environment": config.environment,
-                "authenticated": config.is_authenticated(),
-                "username": config.username,
-            });
-            return self.json(&safe);
+            return self.json(key);
+        }
+        self.success(&format!("API key ({}): {}", key.scope, key.key));
+        self.warn("Save it now; it won't be shown again.");
+        Ok(())
+    }
+
+    /// Print deleted environments that can still be restored
+    pub fn print_deleted_environments(&self, deleted: &[DeletedEnvironment]) -> Result<()> {
+        if self.delimited(
+            deleted,
+            &[
+                "id",
+                "name",
+                "flag_values",
+                "deleted_by",
+                "deleted_at",
+                "purge_after",
+            ],
+            |d| {
+                vec![
+                    d.id.clone(),
+                    d.name.clone(),
+                    d.flag_values.to_string(),
+                    d.deleted_by.clone(),
+                    d.deleted_at.to_rfc3339(),
+                    d.purge_after.to_rfc3339(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(deleted);
+        }
+
+        if deleted.is_empty() {
+            self.info("No deleted environments to restore");
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct DeletedRow {
+            #[tabled(rename = "Name")]
+            name: String,
+            #[tabled(rename = "Flag values")]
+            flag_values: usize,
+            #[tabled(rename = "Deleted by")]
+            deleted_by: String,
+            #[tabled(rename = "Deleted")]
+            deleted_at: String,
+            #[tabled(rename = "Restorable until")]
+            purge_after: String,
+        }
+
+        let rows: Vec<_> = deleted
+            .iter()
+            .map(|d| DeletedRow {
+                name: d.name.clone(),
+                flag_values: d.flag_values,
+                deleted_by: d.deleted_by.clone(),
+                deleted_at: d.deleted_at.format("%Y-%m-%d %H:%M").to_string(),
+                purge_after: d.purge_after.format("%Y-%m-%d %H:%M").to_string(),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    /// Print environment list
+    pub fn print_environments(&self, envs: &[Environment], current: Option<&str>) -> Result<()> {
+        if self.delimited(
+            envs,
+            &[
+                "id",
+                "name",
+                "slug",
+                "production",
+                "protected",
+                "frozen",
+                "color",
+                "emoji",
+            ],
+            |e| {
+                vec![
+                    e.id.to_string(),
+                    e.name.clone(),
+                    e.slug.clone(),
+                    e.is_production.to_string(),
+                    e.protected.to_string(),
+                    e.frozen.to_string(),
+                    e.color.clone().unwrap_or_default(),
+                    e.emoji.clone().unwrap_or_default(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(envs);
+        }
+
+        if envs.is_empty() {
+            self.info("No environments found.");
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct EnvRow {
+            #[tabled(rename = "")]
+            current: String,
+            #[tabled(rename = "Name")]
+            name: String,
+            #[tabled(rename = "Slug")]
+            slug: String,
+            #[tabled(rename = "Production")]
+            production: String,
+            #[tabled(rename = "Protected")]
+            protected: String,
+            #[tabled(rename = "Frozen")]
+            frozen: String,
+        }
+
+        let rows: Vec<_> = envs
+            .iter()
+            .map(|e| {
+                let is_current = current.is_some_and(|c| c == e.name || c == e.slug);
+                EnvRow {
+                    current: if is_current {
+                        "→".green().to_string()
+                    } else {
+                        "".to_string()
+                    },
+                    name: env_label(e),
+                    slug: e.slug.clone(),
+                    production: if e.is_production {
+                        "●".red().to_string()
+                    } else {
+                        "".to_string()
+                    },
+                    protected: if e.protected {
+                        "✓".yellow().to_string()
+                    } else {
+                        "".to_string()
+                    },
+                    frozen: if e.frozen {
+                        "❄".cyan().to_string()
+                    } else {
+                        "".to_string()
+                    },
+                }
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    /// Print flag templates
+    pub fn print_templates(&self, templates: &[FlagTemplate]) -> Result<()> {
+        let environments = |t: &FlagTemplate| {
+            t.environments
+                .iter()
+                .map(|(env, state)| format!("{env}={}", switch_state(state.enabled, state.rollout)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        if self.delimited(
+            templates,
+            &["name", "default", "environments", "builtin", "description"],
+            |t| {
+                vec![
+                    t.name.clone(),
+                    switch_state(t.default.enabled, t.default.rollout),
+                    environments(t),
+                    t.builtin.to_string(),
+                    t.description.clone().unwrap_or_default(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(templates);
+        }
+
+        #[derive(Tabled)]
+        struct TemplateRow {
+            #[tabled(rename = "Name")]
+            name: String,
+            #[tabled(rename = "Default")]
+            default: String,
+            #[tabled(rename = "Environments")]
+            environments: String,
+            #[tabled(rename = "Description")]
+            description: String,
+        }
+
+        let rows: Vec<_> = templates
+            .iter()
+            .map(|t| TemplateRow {
+                name: if t.builtin {
+                    format!("{} {}", t.name, "(built-in)".dimmed())
+                } else {
+                    t.name.clone()
+                },
+                default: switch_state(t.default.enabled, t.default.rollout),
+                environments: environments(t),
+                description: t.description.clone().unwrap_or_default(),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    /// Print flags found by `flaglite search`
+    pub fn print_search_results(&self, results: &[FlagSearchResult], query: &str) -> Result<()> {
+        let environments = |r: &FlagSearchResult| {
+            let mut states: Vec<_> = r.environments.iter().collect();
+            states.sort_by_key(|(env, _)| *env);
+            states
+                .into_iter()
+                .map(|(env, state)| format!("{env}={}", switch_state(state.enabled, state.rollout)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        if self.delimited(results, &["project", "key", "name", "environments"], |r| {
+            vec![
+                r.project_slug.clone(),
+                r.key.clone(),
+                r.name.clone(),
+                environments(r),
+            ]
+        }) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(results);
+        }
+
+        if results.is_empty() {
+            self.info(&format!("No flags match '{query}'"));
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct SearchRow {
+            #[tabled(rename = "Project")]
+            project: String,
+            #[tabled(rename = "Key")]
+            key: String,
+            #[tabled(rename = "Name")]
+            name: String,
+            #[tabled(rename = "Environments")]
+            environments: String,
+        }
+
+        let rows: Vec<_> = results
+            .iter()
+            .map(|r| SearchRow {
+                project: r.project_slug.clone(),
+                key: r.key.clone(),
+                name: r.name.clone(),
+                environments: environments(r),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    /// Print flag list
+    pub fn print_flags(&self, flags: &[FlagWithState]) -> Result<()> {
+        if self.delimited(
+            flags,
+            &[
+                "key",
+                "name",
+                "type",
+                "enabled",
+                "updated_at",
+                "last_evaluated_at",
+                "expires_at",
+                "expired",
+                "owner",
+                "repository",
+                "code_path",
+            ],
+            |f| {
+                vec![
+                    f.flag.key.clone(),
+                    f.flag.name.clone(),
+                    f.flag.flag_type.to_string(),
+                    f.enabled.to_string(),
+                    f.flag.updated_at.to_rfc3339(),
+                    timestamp(f.last_evaluated_at),
+                    timestamp(f.flag.expires_at),
+                    f.flag.expired.to_string(),
+                    f.flag.owner.clone().unwrap_or_default(),
+                    f.flag.repository.clone().unwrap_or_default(),
+                    f.flag.code_path.clone().unwrap_or_default(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(flags);
+        }
+
+        if flags.is_empty() {
+            self.info("No flags found. Create one with 'flaglite flags create <key>'");
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct FlagRow {
+            #[tabled(rename = "Status")]
+            status: String,
+            #[tabled(rename = "Key")]
+            key: String,
+            #[tabled(rename = "Name")]
+            name: String,
+            #[tabled(rename = "Type")]
+            flag_type: String,
+            #[tabled(rename = "Owner")]
+            owner: String,
+            #[tabled(rename = "Updated")]
+            updated: String,
+            #[tabled(rename = "Last Evaluated")]
+            last_evaluated: String,
+        }
+
+        let rows: Vec<_> = flags
+            .iter()
+            .map(|f| FlagRow {
+                status: if f.flag.expired {
+                    "expired".yellow().to_string()
+                } else if f.enabled {
+                    "●".green().to_string()
+                } else {
+                    "○".dimmed().to_string()
+                },
+                key: f.flag.key.clone(),
+                name: f.flag.name.clone(),
+                flag_type: f.flag.flag_type.to_string(),
+                owner: match &f.flag.owner {
+                    Some(owner) => owner.clone(),
+                    None => "-".dimmed().to_string(),
+                },
+                updated: f.flag.updated_at.format("%Y-%m-%d %H:%M").to_string(),
+                last_evaluated: match f.last_evaluated_at {
+                    Some(at) => at.format("%Y-%m-%d %H:%M").to_string(),
+                    None => "never".dimmed().to_string(),
+                },
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    /// Print flag details
+    pub fn print_flag(&self, flag: &FlagWithState, env: &str) -> Result<()> {
+        if self.is_json() {
+            return self.json(flag);
+        }
+
+        let status = if flag.flag.expired {
+            "EXPIRED".yellow().bold()
+        } else if flag.enabled {
+            "ENABLED".green().bold()
+        } else {
+            "DISABLED".red().bold()
+        };
+
+        println!(
+            "{} {} {}",
+            flag.flag.key.bold(),
+            status,
+            format!("in {env}").dimmed()
+        );
+        println!();
+        println!("  {} {}", "Name:".dimmed(), flag.flag.name);
+        println!("  {} {}", "Type:".dimmed(), flag.flag.flag_type);
+        if let Some(schema) = &flag.flag.json_schema {
+            println!("  {} {}", "Schema:".dimmed(), schema.to_string().dimmed());
+        }
+        print_default(&flag.flag);
+
+        if let Some(desc) = &flag.flag.description {
+            println!("  {} {}", "Description:".dimmed(), desc);
+        }
+        print_ownership(&flag.flag);
+
+        if let Some(value) = &flag.value {
+            println!(
+                "  {} {}",
+                "Value:".dimmed(),
+                serde_json::to_string(value).unwrap_or_default().cyan()
+            );
+        }
+
+        println!("  {} {}", "ID:".dimmed(), flag.flag.id.to_string().dimmed());
+        println!(
+            "  {} {}",
+            "Created:".dimmed(),
+            flag.flag.created_at.format("%Y-%m-%d %H:%M")
+        );
+        println!(
+            "  {} {}",
+            "Updated:".dimmed(),
+            flag.flag.updated_at.format("%Y-%m-%d %H:%M")
+        );
+        if let Some(at) = flag.flag.expires_at {
+            println!("  {} {}", "Expires:".dimmed(), at.format("%Y-%m-%d %H:%M"));
+        }
+        println!(
+            "  {} {}",
+            "Last evaluated:".dimmed(),
+            flag.last_evaluated_at
+                .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|| "never".to_string())
+        );
+
+        Ok(())
+    }
+
+    /// Print a flag's state in every environment, in the project's
+    /// environment order
+    pub fn print_flag_environments(
+        &self,
+        flag: &FlagWithState,
+        envs: &[Environment],
+    ) -> Result<()> {
+        #[derive(Serialize)]
+        struct EnvState<'a> {
+            environment: &'a str,
+            enabled: bool,
+            rollout: i32,
+            value: Option<&'a serde_json::Value>,
+        }
+
+        let mut states: Vec<_> = flag
+            .environments
+            .iter()
+            .map(|(name, state)| EnvState {
+                environment: name,
+                enabled: state.enabled,
+                rollout: state.rollout,
+                value: state.value.as_ref(),
+            })
+            .collect();
+        states.sort_by_key(|s| env_position(envs, s.environment));
+
+        if self.delimited(
+            &states,
+            &["environment", "enabled", "rollout", "value"],
+            |s| {
+                vec![
+                    s.environment.to_string(),
+                    s.enabled.to_string(),
+                    s.rollout.to_string(),
+                    s.value.map(|v| v.to_string()).unwrap_or_default(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(&states);
+        }
+
+        println!("{} {}", flag.flag.key.bold(), flag.flag.name.dimmed());
+        println!();
+
+        if states.is_empty() {
+            self.info("No environments found.");
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct EnvStateRow {
+            #[tabled(rename = "Environment")]
+            environment: String,
+            #[tabled(rename = "Enabled")]
+            enabled: String,
+            #[tabled(rename = "Rollout")]
+            rollout: String,
+            #[tabled(rename = "Value")]
+            value: String,
+        }
+
+        let rows: Vec<_> = states
+            .iter()
+            .map(|s| EnvStateRow {
+                environment: env_name_label(envs, s.environment),
+                enabled: if s.enabled {
+                    "●".green().to_string()
+                } else {
+                    "○".dimmed().to_string()
+                },
+                rollout: format!("{}%", s.rollout),
+                value: s
+                    .value
+                    .map(|v| serde_json::to_string(v).unwrap_or_default())
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    /// Print evaluation stats for a flag
+    /// Assignments as CSV unless JSON or TSV was asked for, since they are
+    /// meant for spreadsheets and analysis tools rather than reading
+    pub fn print_assignments(&self, assignments: &[Assignment]) -> Result<()> {
+        let delimiter = match self.format {
+            OutputFormat::Json | OutputFormat::JsonV2 => return self.json(assignments),
+            OutputFormat::Tsv => '\t',
+            OutputFormat::Pretty | OutputFormat::Csv => ',',
+        };
+
+        let header: Vec<String> = ["user_id", "bucket", "enabled", "reason", "value"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        println!("{}", delimited_line(&header, delimiter));
+        for a in assignments {
+            let row = vec![
+                a.user_id.clone(),
+                a.bucket.to_string(),
+                a.enabled.to_string(),
+                a.reason.clone(),
+                a.value.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+            ];
+            println!("{}", delimited_line(&row, delimiter));
+        }
+        Ok(())
+    }
+
+    /// A flag's allowlist or denylist, one user ID per row
+    pub fn print_user_list(&self, list: &UserList) -> Result<()> {
+        if self.delimited(&list.user_ids, &["user_id"], |id| vec![id.clone()]) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(list);
+        }
+
+        if list.user_ids.is_empty() {
+            self.info(&format!(
+                "The {} of '{}' in {} is empty",
+                list.list, list.key, list.environment
+            ));
+            return Ok(());
+        }
+
+        println!(
+            "{} {} {}",
+            list.key.bold(),
+            list.list,
+            format!("in {} ({} users)", list.environment, list.user_ids.len()).dimmed()
+        );
+        for id in &list.user_ids {
+            println!("  {id}");
+        }
+        Ok(())
+    }
+
+    pub fn print_flag_stats(&self, stats: &FlagStats, environments: &[Environment]) -> Result<()> {
+        if self.is_json() {
+            return self.json(stats);
+        }
+
+        println!("{} {}", stats.key.bold(), "evaluations".dimmed());
+        println!();
+
+        #[derive(Tabled)]
+        struct StatsRow {
+            #[tabled(rename = "Environment")]
+            environment: String,
+            #[tabled(rename = "Total")]
+            total: i64,
+            #[tabled(rename = "Today")]
+            today: i64,
+            #[tabled(rename = "Last Evaluated")]
+            last_evaluated: String,
+        }
+
+        let today = chrono::Utc::now().date_naive();
+        let mut envs: Vec<_> = stats.environments.iter().collect();
+        envs.sort_by_key(|(name, _)| env_position(environments, name));
+
+        let rows: Vec<_> = envs
+            .into_iter()
+            .map(|(name, env)| StatsRow {
+                environment: env_name_label(environments, name),
+                total: env.total,
+                today: env
+                    .daily
+                    .iter()
+                    .filter(|d| d.day == today)
+                    .map(|d| d.count)
+                    .sum(),
+                last_evaluated: env
+                    .last_evaluated_at
+                    .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_else(|| "never".to_string()),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    pub fn print_flag_history(&self, history: &FlagHistory) -> Result<()> {
+        if self.is_json() {
+            return self.json(history);
+        }
+
+        if history.versions.is_empty() {
+            self.info(&format!(
+                "'{}' has never been set in {}",
+                history.key, history.environment
+            ));
+            return Ok(());
+        }
+
+        println!(
+            "{} {}",
+            history.key.bold(),
+            format!("history in {}", history.environment).dimmed()
+        );
+        println!();
+
+        #[derive(Tabled)]
+        struct VersionRow {
+            #[tabled(rename = "Version")]
+            version: i64,
+            #[tabled(rename = "Status")]
+            status: String,
+            #[tabled(rename = "Rollout")]
+            rollout: String,
+            #[tabled(rename = "Value")]
+            value: String,
+            #[tabled(rename = "Changed")]
+            changed_at: String,
+            #[tabled(rename = "Reason")]
+            reason: String,
+        }
+
+        let rows: Vec<_> = history
+            .versions
+            .iter()
+            .map(|v| VersionRow {
+                version: v.version,
+                status: if v.enabled { "on" } else { "off" }.to_string(),
+                rollout: format!("{}%", v.rollout_percentage),
+                value: v.value.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+                changed_at: v.changed_at.format("%Y-%m-%d %H:%M:%S").to_string(),
+                reason: v.reason.clone().unwrap_or_default(),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        if let Some(cursor) = &history.next_cursor {
+            println!();
+            self.info(&format!(
+                "Older versions: flaglite flags history {} -e {} --cursor {cursor}",
+                history.key, history.environment
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn print_scan_report(&self, report: &ScanReport) -> Result<()> {
+        let locations = |flag: &ScannedFlag| {
+            flag.locations
+                .iter()
+                .map(|l| format!("{}:{}", l.path, l.line))
+                .collect::<Vec<_>>()
+        };
+
+        let mut rows: Vec<(&str, &str, Vec<String>)> = Vec::new();
+        for flag in &report.missing_on_server {
+            rows.push((&flag.key, "missing_on_server", locations(flag)));
+        }
+        for key in &report.unreferenced {
+            rows.push((key, "unreferenced", Vec::new()));
+        }
+        for flag in &report.referenced {
+            rows.push((&flag.key, "referenced", locations(flag)));
+        }
+        if self.delimited(
+            &rows,
+            &["key", "status", "locations"],
+            |(key, status, at)| vec![key.to_string(), status.to_string(), at.join(" ")],
+        ) {
+            return Ok(());
+        }
+        if self.is_json() {
+            return self.json(report);
+        }
+
+        self.info(&format!("Scanned {} files", report.files_scanned));
+        if !report.missing_on_server.is_empty() {
+            println!();
+            println!(
+                "{}",
+                format!(
+                    "Used in code but not in the project ({}):",
+                    report.missing_on_server.len()
+                )
+                .yellow()
+                .bold()
+            );
+            for flag in &report.missing_on_server {
+                println!(
+                    "  {}  {}",
+                    flag.key.bold(),
+                    locations(flag).join(", ").dimmed()
+                );
+            }
+        }
+        if !report.unreferenced.is_empty() {
+            println!();
+            println!(
+                "{}",
+                format!(
+                    "Not found in code, candidates for deletion ({}):",
+                    report.unreferenced.len()
+                )
+                .bold()
+            );
+            for key in &report.unreferenced {
+                println!("  {key}");
+            }
+        }
+        println!();
+        self.success(&format!(
+            "{} flag(s) used in code and present in the project",
+            report.referenced.len()
+        ));
+
+        Ok(())
+    }
+
+    pub fn print_change_requests(&self, changes: &[ChangeRequest]) -> Result<()> {
+        let change = |c: &ChangeRequest| {
+            let mut parts = Vec::new();
+            if let Some(enabled) = c.enabled {
+                parts.push(if enabled { "on" } else { "off" }.to_string());
+            }
+            if let Some(percentage) = c.rollout_percentage {
+                parts.push(format!("{percentage}%"));
+            }
+            parts.join(", ")
+        };
+
+        if self.delimited(
+            changes,
+            &[
+                "id",
+                "flag",
+                "environment",
+                "change",
+                "status",
+                "proposed_by",
+                "reviewed_by",
+                "created_at",
+            ],
+            |c| {
+                vec![
+                    c.id.clone(),
+                    c.flag_key.clone(),
+                    c.environment.clone(),
+                    change(c),
+                    c.status.clone(),
+                    c.proposed_by.clone(),
+                    c.reviewed_by.clone().unwrap_or_default(),
+                    c.created_at.to_rfc3339(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(changes);
+        }
+
+        if changes.is_empty() {
+            self.info("No change requests");
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct ChangeRow {
+            #[tabled(rename = "ID")]
+            id: String,
+            #[tabled(rename = "Flag")]
+            flag: String,
+            #[tabled(rename = "Environment")]
+            environment: String,
+            #[tabled(rename = "Change")]
+            change: String,
+            #[tabled(rename = "Status")]
+            status: String,
+            #[tabled(rename = "Proposed by")]
+            proposed_by: String,
+            #[tabled(rename = "Comment")]
+            comment: String,
+        }
+
+        let rows: Vec<_> = changes
+            .iter()
+            .map(|c| ChangeRow {
+                id: c.id.clone(),
+                flag: c.flag_key.clone(),
+                environment: c.environment.clone(),
+                change: change(c),
+                status: match c.status.as_str() {
+                    "pending" => c.status.yellow().to_string(),
+                    "approved" => c.status.green().to_string(),
+                    _ => c.status.dimmed().to_string(),
+                },
+                proposed_by: c.proposed_by.clone(),
+                comment: c.comment.clone().unwrap_or_default(),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    pub fn print_ramps(&self, ramps: &[Ramp]) -> Result<()> {
+        if self.delimited(
+            ramps,
+            &[
+                "id",
+                "flag",
+                "environment",
+                "rollout",
+                "target",
+                "step",
+                "interval_secs",
+                "status",
+                "next_step_at",
+                "created_by",
+            ],
+            |r| {
+                vec![
+                    r.id.clone(),
+                    r.flag_key.clone(),
+                    r.environment.clone(),
+                    r.current_percentage.to_string(),
+                    r.target_percentage.to_string(),
+                    r.step_percentage.to_string(),
+                    r.interval_secs.to_string(),
+                    r.status.clone(),
+                    r.next_step_at.map(|at| at.to_rfc3339()).unwrap_or_default(),
+                    r.created_by.clone(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(ramps);
+        }
+
+        if ramps.is_empty() {
+            self.info("No ramps");
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct RampRow {
+            #[tabled(rename = "ID")]
+            id: String,
+            #[tabled(rename = "Flag")]
+            flag: String,
+            #[tabled(rename = "Environment")]
+            environment: String,
+            #[tabled(rename = "Rollout")]
+            rollout: String,
+            #[tabled(rename = "Step")]
+            step: String,
+            #[tabled(rename = "Status")]
+            status: String,
+            #[tabled(rename = "Next step")]
+            next_step: String,
+        }
+
+        let rows: Vec<_> = ramps
+            .iter()
+            .map(|r| RampRow {
+                id: r.id.clone(),
+                flag: r.flag_key.clone(),
+                environment: r.environment.clone(),
+                rollout: format!("{}% → {}%", r.current_percentage, r.target_percentage),
+                step: format!(
+                    "+{} every {}",
+                    r.step_percentage,
+                    ramps::format_interval(r.interval_secs)
+                ),
+                status: match r.status.as_str() {
+                    "active" => r.status.green().to_string(),
+                    "paused" => r.status.yellow().to_string(),
+                    _ => r.status.dimmed().to_string(),
+                },
+                next_step: r
+                    .next_step_at
+                    .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                    .unwrap_or_default(),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    pub fn print_invite_links(&self, links: &[InviteLink]) -> Result<()> {
+        if self.delimited(
+            links,
+            &["id", "role", "status", "uses", "expires_at", "created_by"],
+            |l| {
+                vec![
+                    l.id.clone(),
+                    l.role.clone(),
+                    l.status.clone(),
+                    l.uses.to_string(),
+                    l.expires_at.to_rfc3339(),
+                    l.created_by.clone(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(links);
+        }
+
+        if links.is_empty() {
+            self.info("No invite links");
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct LinkRow {
+            #[tabled(rename = "ID")]
+            id: String,
+            #[tabled(rename = "Role")]
+            role: String,
+            #[tabled(rename = "Status")]
+            status: String,
+            #[tabled(rename = "Uses")]
+            uses: i64,
+            #[tabled(rename = "Expires")]
+            expires: String,
+            #[tabled(rename = "Created by")]
+            created_by: String,
+        }
+
+        let rows: Vec<_> = links
+            .iter()
+            .map(|l| LinkRow {
+                id: l.id.clone(),
+                role: l.role.clone(),
+                status: match l.status.as_str() {
+                    "active" => l.status.green().to_string(),
+                    _ => l.status.dimmed().to_string(),
+                },
+                uses: l.uses,
+                expires: l.expires_at.format("%Y-%m-%d %H:%M").to_string(),
+                created_by: l.created_by.clone(),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    pub fn print_project_members(&self, members: &[ProjectMember]) -> Result<()> {
+        if self.delimited(members, &["username", "role", "created_at"], |m| {
+            vec![
+                m.username.clone(),
+                m.role.clone(),
+                m.created_at.to_rfc3339(),
+            ]
+        }) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(members);
+        }
+
+        if members.is_empty() {
+            self.info("The project isn't shared with anyone by invite link");
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct MemberRow {
+            #[tabled(rename = "Username")]
+            username: String,
+            #[tabled(rename = "Role")]
+            role: String,
+            #[tabled(rename = "Joined")]
+            joined: String,
+        }
+
+        let rows: Vec<_> = members
+            .iter()
+            .map(|m| MemberRow {
+                username: m.username.clone(),
+                role: m.role.clone(),
+                joined: m.created_at.format("%Y-%m-%d").to_string(),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    pub fn print_guards(&self, guards: &[Guard]) -> Result<()> {
+        if self.delimited(
+            guards,
+            &[
+                "id",
+                "flag",
+                "environment",
+                "version",
+                "ramp_id",
+                "url",
+                "status",
+                "watching_until",
+                "message",
+                "created_by",
+            ],
+            |g| {
+                vec![
+                    g.id.clone(),
+                    g.flag_key.clone(),
+                    g.environment.clone(),
+                    g.version.to_string(),
+                    g.ramp_id.clone().unwrap_or_default(),
+                    g.url.clone().unwrap_or_default(),
+                    g.status.clone(),
+                    g.watching_until
+                        .map(|at| at.to_rfc3339())
+                        .unwrap_or_default(),
+                    g.message.clone().unwrap_or_default(),
+                    g.created_by.clone(),
+                ]
+            },
+        ) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(guards);
+        }
+
+        if guards.is_empty() {
+            self.info("No guards");
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct GuardRow {
+            #[tabled(rename = "ID")]
+            id: String,
+            #[tabled(rename = "Flag")]
+            flag: String,
+            #[tabled(rename = "Environment")]
+            environment: String,
+            #[tabled(rename = "Watching")]
+            watching: String,
+            #[tabled(rename = "Status")]
+            status: String,
+            #[tabled(rename = "Until")]
+            until: String,
+        }
+
+        let rows: Vec<_> = guards
+            .iter()
+            .map(|g| GuardRow {
+                id: g.id.clone(),
+                flag: g.flag_key.clone(),
+                environment: g.environment.clone(),
+                watching: match (&g.ramp_id, &g.url) {
+                    (Some(ramp), _) => format!("ramp {ramp}"),
+                    (None, Some(url)) => format!("v{} via {url}", g.version),
+                    (None, None) => format!("v{}", g.version),
+                },
+                status: match g.status.as_str() {
+                    "watching" => g.status.green().to_string(),
+                    "tripped" => g.status.red().to_string(),
+                    _ => g.status.dimmed().to_string(),
+                },
+                // Why it tripped, in place of a time it no longer watches to
+                until: match g.status.as_str() {
+                    "watching" => g
+                        .watching_until
+                        .map(|at| at.format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_else(|| "end of ramp".to_string()),
+                    "tripped" => g.message.clone().unwrap_or_default(),
+                    _ => String::new(),
+                },
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    /// Print local flag overrides and the ones that couldn't be read
+    pub fn print_overrides(&self, overrides: &Overrides) -> Result<()> {
+        let active: Vec<_> = overrides.iter().collect();
+        if self.delimited(&active, &["key", "enabled", "value", "source"], |o| {
+            vec![
+                o.key.clone(),
+                o.enabled.to_string(),
+                o.value.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+                o.source.clone(),
+            ]
+        }) {
+            return Ok(());
+        }
+
+        if self.is_json() {
+            return self.json(&serde_json::json!({
+                "overrides": active,
+                "invalid": overrides.invalid(),
+            }));
+        }
+
+        for invalid in overrides.invalid() {
+            self.warn(&format!("Ignoring {}: {}", invalid.source, invalid.error));
+        }
+
+        if active.is_empty() {
+            self.info("No flag overrides set. Force a flag with FLAGLITE_OVERRIDE_<FLAG_KEY>=true|false or a FLAGLITE_OVERRIDES_FILE");
+            return Ok(());
+        }
+
+        #[derive(Tabled)]
+        struct OverrideRow {
+            #[tabled(rename = "Key")]
+            key: String,
+            #[tabled(rename = "Status")]
+            status: String,
+            #[tabled(rename = "Value")]
+            value: String,
+            #[tabled(rename = "Source")]
+            source: String,
+        }
+
+        let rows: Vec<_> = active
+            .iter()
+            .map(|o| OverrideRow {
+                key: o.key.clone(),
+                status: if o.enabled { "on" } else { "off" }.to_string(),
+                value: o.value.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+                source: o.source.clone(),
+            })
+            .collect();
+
+        let table = Table::new(rows).with(Style::rounded()).to_string();
+        println!("{table}");
+
+        Ok(())
+    }
+
+    /// Print a single flag (without state)
+    pub fn print_flag_created(&self, flag: &Flag) -> Result<()> {
+        if self.is_json() {
+            return self.json(flag);
+        }
+
+        println!("{}", "Flag Created".bold().green());
+        println!("  {} {}", "Key:".dimmed(), flag.key.cyan());
+        println!("  {} {}", "Name:".dimmed(), flag.name);
+        println!("  {} {}", "Type:".dimmed(), flag.flag_type);
+        if let Some(desc) = &flag.description {
+            println!("  {} {}", "Description:".dimmed(), desc);
+        }
+        if let Some(at) = flag.expires_at {
+            println!("  {} {}", "Expires:".dimmed(), at.format("%Y-%m-%d %H:%M"));
+        }
+        print_ownership(flag);
+
+        Ok(())
+    }
+
+    /// Print a flag's ownership metadata after an update
+    pub fn print_flag_updated(&self, flag: &Flag) -> Result<()> {
+        if self.is_json() {
+            return self.json(flag);
+        }
+
+        println!("{}", "Flag Updated".bold().green());
+        println!("  {} {}", "Key:".dimmed(), flag.key.cyan());
+        let unset = || "-".dimmed().to_string();
+        println!(
+            "  {} {}",
+            "Owner:".dimmed(),
+            flag.owner.clone().unwrap_or_else(unset)
+        );
+        println!(
+            "  {} {}",
+            "Repository:".dimmed(),
+            flag.repository.clone().unwrap_or_else(unset)
+        );
+        println!(
+            "  {} {}",
+            "Code path:".dimmed(),
+            flag.code_path.clone().unwrap_or_else(unset)
+        );
+        print_default(flag);
+        if flag.public {
+            println!("  {} {}", "Public:".dimmed(), "yes".yellow());
+        }
+
+        Ok(())
+    }
+
+    /// Print config
+    pub fn print_config(&self, config: &Config) -> Result<()> {
+        if self.is_json() {
+            // Don't expose sensitive info in JSON output
+            let safe = serde_json::json!({
+                "api_url": config.api_url,
+                "project_id": config.project_id,
+                "org_id": config.org_id,
+                "environment": config.environment,
+                "format": config.format,
+                "authenticated": config.is_authenticated(),
+                "username": config.username,
+                "repo_config": config.repo_config,
+            });
+            return self.json(&safe);
         }
 
         println!("{}", "Configuration".bold().underline());
@@ -356,6 +2164,11 @@ impl Output {
         if let Some(username) = &config.username {
             println!("  {} {}", "Username:".dimmed(), username);
         }
+        println!(
+            "  {} {}",
+            "Organization:".dimmed(),
+            config.org_id.as_deref().unwrap_or("-")
+        );
         println!(
             "  {} {}",
             "Project:".dimmed(),
@@ -366,18 +2179,214 @@ impl Output {
             "Environment:".dimmed(),
             config.environment.as_deref().unwrap_or("development")
         );
+        println!(
+            "  {} {}",
+            "Format:".dimmed(),
+            config.format.as_deref().unwrap_or("pretty")
+        );
         println!();
         println!(
             "  {} {}",
             "Config file:".dimmed(),
             Config::config_path()?.display()
         );
+        if let Some(path) = &config.repo_config {
+            println!("  {} {}", "Repo config:".dimmed(), path.display());
+        }
         println!(
             "  {} {}",
             "Credentials:".dimmed(),
             Config::credentials_path()?.display()
         );
+        if config.secrets_in_keyring {
+            println!("  {} OS keyring", "Secrets:".dimmed());
+        }
 
         Ok(())
     }
 }
+
+/// One CSV (RFC 4180 quoting) or TSV (backslash escapes) line
+/// `on`, `off` or `on 25%`
+fn switch_state(enabled: bool, rollout: i32) -> String {
+    match (enabled, rollout) {
+        (false, _) => "off".to_string(),
+        (true, 100) => "on".to_string(),
+        (true, rollout) => format!("on {rollout}%"),
+    }
+}
+
+/// The environment's name in its color, after its emoji
+fn env_label(env: &Environment) -> String {
+    let name = match env.color.as_deref().and_then(parse_hex_color) {
+        Some((r, g, b)) => env.name.truecolor(r, g, b).to_string(),
+        None => env.name.clone(),
+    };
+    match &env.emoji {
+        Some(emoji) => format!("{emoji} {name}"),
+        None => name,
+    }
+}
+
+/// Where `name` sorts among the project's environments: by their order,
+/// then by name for any the list doesn't have
+fn env_position<'a>(envs: &[Environment], name: &'a str) -> (usize, &'a str) {
+    let index = envs.iter().position(|e| e.name == name);
+    (index.unwrap_or(envs.len()), name)
+}
+
+/// `env_label` for the environment called `name`, or the bare name
+fn env_name_label(envs: &[Environment], name: &str) -> String {
+    envs.iter()
+        .find(|e| e.name == name)
+        .map_or_else(|| name.to_string(), env_label)
+}
+
+/// Whole days since `at`, e.g. `12 days`
+fn age(at: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    match (now - at).num_days() {
+        1 => "1 day".to_string(),
+        days => format!("{} days", days.max(0)),
+    }
+}
+
+/// `#rrggbb` as RGB
+fn parse_hex_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = color.strip_prefix('#').filter(|h| h.len() == 6)?;
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some((channel(0)?, channel(2)?, channel(4)?))
+}
+
+fn delimited_line(fields: &[String], delimiter: char) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| match delimiter {
+            '\t' => field
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n")
+                .replace('\r', "\\r"),
+            _ if field.contains([delimiter, '"', '\n', '\r']) => {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            }
+            _ => field.clone(),
+        })
+        .collect();
+    fields.join(&delimiter.to_string())
+}
+
+/// Owner, repository, and code path lines for the flags that have them
+fn print_ownership(flag: &Flag) {
+    if let Some(owner) = &flag.owner {
+        println!("  {} {}", "Owner:".dimmed(), owner);
+    }
+    if let Some(repository) = &flag.repository {
+        println!("  {} {}", "Repository:".dimmed(), repository);
+    }
+    if let Some(code_path) = &flag.code_path {
+        println!("  {} {}", "Code path:".dimmed(), code_path);
+    }
+}
+
+/// State (and value) in environments where the flag has no value
+fn print_default(flag: &Flag) {
+    let state = if flag.default_enabled { "on" } else { "off" };
+    match &flag.default_value {
+        Some(value) => println!("  {} {state} ({value})", "Default:".dimmed()),
+        None => println!("  {} {state}", "Default:".dimmed()),
+    }
+}
+
+/// Timestamps in CSV/TSV are RFC 3339; missing values are empty
+fn timestamp(at: Option<DateTime<Utc>>) -> String {
+    at.map(|at| at.to_rfc3339()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(fields: &[&str], delimiter: char) -> String {
+        let fields: Vec<String> = fields.iter().map(|f| f.to_string()).collect();
+        delimited_line(&fields, delimiter)
+    }
+
+    #[test]
+    fn test_csv_quoting() {
+        assert_eq!(line(&["a", "b c"], ','), "a,b c");
+        assert_eq!(
+            line(&["a,b", "say \"hi\""], ','),
+            "\"a,b\",\"say \"\"hi\"\"\""
+        );
+        assert_eq!(line(&["two\nlines", ""], ','), "\"two\nlines\",");
+    }
+
+    #[test]
+    fn test_tsv_escaping() {
+        assert_eq!(line(&["a,b", "c"], '\t'), "a,b\tc");
+        assert_eq!(line(&["x\ty", "1\n2"], '\t'), "x\\ty\t1\\n2");
+    }
+
+    #[test]
+    fn test_json_path_selects_values() {
+        let value = serde_json::json!({
+            "api_key": "flg_abc",
+            "flags": [{"key": "dark-mode", "enabled": true}],
+        });
+        let select = |path: &str| path.parse::<JsonPath>().unwrap().select(&value).cloned();
+
+        assert_eq!(select(".api_key"), Some(serde_json::json!("flg_abc")));
+        assert_eq!(select("api_key"), Some(serde_json::json!("flg_abc")));
+        assert_eq!(select(".flags[0].enabled"), Some(serde_json::json!(true)));
+        assert_eq!(select(".flags[1]"), None);
+        assert_eq!(select(".missing"), None);
+        assert_eq!(select("."), Some(value.clone()));
+
+        let list = serde_json::json!([{"key": "a"}, {"key": "b"}]);
+        let path: JsonPath = ".[1].key".parse().unwrap();
+        assert_eq!(path.select(&list), Some(&serde_json::json!("b")));
+
+        for invalid in [".flags[x]", ".flags[0", "..key", ".a.[0]x"] {
+            assert!(invalid.parse::<JsonPath>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_json_v2_collects_results_for_the_envelope() {
+        let output =
+            Output::new("json-v2".parse().unwrap(), true, false).command("flags list".to_string());
+        assert!(output.is_json());
+        output.json(&serde_json::json!([{"key": "a"}])).unwrap();
+        output.warn("careful");
+
+        let envelope = output.envelope().unwrap();
+        assert_eq!(envelope.data.lock().unwrap().len(), 1);
+        let meta = envelope.meta();
+        assert_eq!(meta["command"], "flags list");
+        assert_eq!(meta["warnings"], serde_json::json!(["careful"]));
+
+        // Other formats have no envelope, and --output prints the bare value
+        let output = Output::new(OutputFormat::Json, true, false).command("flags list".into());
+        assert!(output.envelope().is_none());
+        let output = Output::new(OutputFormat::JsonV2, true, false)
+            .select(Some(".key".parse().unwrap()))
+            .command("flags get".into());
+        assert!(output.envelope().is_none());
+    }
+
+    #[test]
+    fn test_age_in_days() {
+        let now = Utc::now();
+        assert_eq!(age(now, now), "0 days");
+        assert_eq!(age(now - chrono::Duration::hours(30), now), "1 day");
+        assert_eq!(age(now - chrono::Duration::days(400), now), "400 days");
+    }
+
+    #[test]
+    fn test_parse_hex_color() {
+        assert_eq!(parse_hex_color("#22c55e"), Some((0x22, 0xc5, 0x5e)));
+        assert_eq!(parse_hex_color("22c55e"), None);
+        assert_eq!(parse_hex_color("#22c55"), None);
+        assert_eq!(parse_hex_color("#zzzzzz"), None);
+    }
+}
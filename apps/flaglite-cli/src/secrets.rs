@@ -0,0 +1,83 @@
+//! The OS credential store (macOS Keychain, Windows Credential Manager, Secret
+//! Service on Linux), for the API key and token with `credentials_backend =
+//! "keyring"`
+//!
+//! Needs the `keyring` feature; without it every call fails with a hint to
+//! rebuild, and the CLI falls back to credentials.json.
+
+use anyhow::Result;
+
+/// Service name the entries are stored under
+#[cfg(feature = "keyring")]
+const SERVICE: &str = "flaglite";
+
+/// The secrets `login` stores
+#[derive(Debug, Default)]
+pub struct Secrets {
+    pub api_key: Option<String>,
+    pub token: Option<String>,
+}
+
+#[cfg(feature = "keyring")]
+fn entry(name: &str) -> Result<keyring::Entry> {
+    Ok(keyring::Entry::new(SERVICE, name)?)
+}
+
+#[cfg(feature = "keyring")]
+fn read(name: &str) -> Result<Option<String>> {
+    match entry(name)?.get_password() {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(feature = "keyring")]
+fn write(name: &str, secret: Option<&str>) -> Result<()> {
+    let entry = entry(name)?;
+    match secret {
+        Some(secret) => entry.set_password(secret)?,
+        None => match entry.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {}
+            Err(e) => return Err(e.into()),
+        },
+    }
+    Ok(())
+}
+
+/// Read the stored secrets; missing entries are None
+pub fn load() -> Result<Secrets> {
+    #[cfg(feature = "keyring")]
+    return Ok(Secrets {
+        api_key: read("api_key")?,
+        token: read("token")?,
+    });
+    #[cfg(not(feature = "keyring"))]
+    unavailable()
+}
+
+/// Replace the stored secrets; None removes an entry
+pub fn store(secrets: &Secrets) -> Result<()> {
+    #[cfg(feature = "keyring")]
+    {
+        write("api_key", secrets.api_key.as_deref())?;
+        write("token", secrets.token.as_deref())
+    }
+    #[cfg(not(feature = "keyring"))]
+    {
+        let _ = secrets;
+        unavailable()
+    }
+}
+
+/// Remove the stored secrets
+pub fn delete() -> Result<()> {
+    store(&Secrets::default())
+}
+
+#[cfg(not(feature = "keyring"))]
+fn unavailable<T>() -> Result<T> {
+    anyhow::bail!(
+        "this build of flaglite lacks the `keyring` feature (cargo install flaglite --features keyring)"
+    )
+}
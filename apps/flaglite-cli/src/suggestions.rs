@@ -0,0 +1,142 @@
+//! Next-step commands for common errors
+//!
+//! `print_error` shows these under the error, so a user who isn't logged in
+//! or hasn't picked a project sees the command that fixes it. They are picked
+//! by the `FlagLiteError` in the chain, like exit codes.
+
+use flaglite_client::FlagLiteError;
+use serde::Serialize;
+
+/// A command that may fix an error, and what it does
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Suggestion {
+    pub command: String,
+    pub description: &'static str,
+}
+
+impl Suggestion {
+    fn new(command: impl Into<String>, description: &'static str) -> Self {
+        Self {
+            command: command.into(),
+            description,
+        }
+    }
+}
+
+/// The commands to suggest for an error, leaving out ones its message
+/// already names
+pub fn for_error(error: &anyhow::Error) -> Vec<Suggestion> {
+    let Some(client_error) = error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<FlagLiteError>())
+    else {
+        return Vec::new();
+    };
+
+    let message = format!("{error:#}");
+    for_client_error(client_error)
+        .into_iter()
+        .filter(|s| !message.contains(&s.command))
+        .collect()
+}
+
+fn for_client_error(error: &FlagLiteError) -> Vec<Suggestion> {
+    match error {
+        FlagLiteError::NotAuthenticated => vec![
+            Suggestion::new("flaglite login", "log in to an existing account"),
+            Suggestion::new("flaglite signup", "create an account"),
+        ],
+        FlagLiteError::InvalidCredentials => vec![
+            Suggestion::new("flaglite login", "log in again if your session expired"),
+            Suggestion::new(
+                "flaglite account request-reset --username <username>",
+                "reset a forgotten password",
+            ),
+        ],
+        FlagLiteError::ApiError { status: 403, .. } => vec![Suggestion::new(
+            "flaglite whoami",
+            "check which account and API key are in use",
+        )],
+        FlagLiteError::NoProjectSelected => vec![
+            Suggestion::new("flaglite projects list", "find the project's ID"),
+            Suggestion::new("flaglite projects use <id>", "select it for later commands"),
+        ],
+        FlagLiteError::ProjectNotFound(_) => vec![Suggestion::new(
+            "flaglite projects list",
+            "see the projects you can use",
+        )],
+        FlagLiteError::FlagNotFound { key, suggestions } => {
+            let mut next = Vec::new();
+            if let Some(closest) = suggestions.first() {
+                next.push(Suggestion::new(
+                    format!("flaglite flags get {closest}"),
+                    "show the closest match",
+                ));
+            }
+            next.push(Suggestion::new(
+                "flaglite flags list",
+                "see the project's flags",
+            ));
+            next.push(Suggestion::new(
+                format!("flaglite flags create {key}"),
+                "create the flag",
+            ));
+            next
+        }
+        FlagLiteError::EnvironmentNotFound(_) => vec![Suggestion::new(
+            "flaglite envs list",
+            "see the project's environments",
+        )],
+        FlagLiteError::NetworkError(_) => vec![Suggestion::new(
+            "flaglite config get api_url",
+            "check which server the CLI talks to",
+        )],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commands(error: anyhow::Error) -> Vec<String> {
+        for_error(&error).into_iter().map(|s| s.command).collect()
+    }
+
+    #[test]
+    fn test_no_project_selected_suggests_listing_projects() {
+        // The message already says to run `projects use`
+        assert_eq!(
+            commands(FlagLiteError::NoProjectSelected.into()),
+            vec!["flaglite projects list"]
+        );
+    }
+
+    #[test]
+    fn test_flag_not_found_suggests_closest_key() {
+        let error = FlagLiteError::FlagNotFound {
+            key: "dark-mod".into(),
+            suggestions: vec!["dark-mode".into()],
+        };
+        assert_eq!(
+            commands(error.into()),
+            vec![
+                "flaglite flags get dark-mode",
+                "flaglite flags list",
+                "flaglite flags create dark-mod",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_context_naming_the_command_drops_it() {
+        let error = anyhow::Error::new(FlagLiteError::EnvironmentNotFound("qa".into()))
+            .context("Run 'flaglite envs list' to see available environments.");
+        assert!(commands(error).is_empty());
+    }
+
+    #[test]
+    fn test_untyped_errors_have_no_suggestions() {
+        assert!(commands(anyhow::anyhow!("boom")).is_empty());
+    }
+}
@@ -0,0 +1,245 @@
+//! --timings: a summary of the API calls a command made
+//!
+//! Calls are grouped by method and path. Sending the same request again (after
+//! a confirmation prompt, say) counts as a retry, and many calls to paths that
+//! differ only in their last segment are pointed out, since that's usually a
+//! loop that one request could replace.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use colored::Colorize;
+use flaglite_client::RequestLog;
+
+/// Calls to one path shape (`GET /v1/projects/x/flags/*`) worth a warning
+const N_PLUS_ONE_CALLS: usize = 5;
+
+#[derive(Debug, Clone)]
+struct Call {
+    method: String,
+    /// URL path, without the query
+    path: String,
+    /// Path and query, to tell retries from calls that only share a path
+    target: String,
+    failed: bool,
+    duration: Duration,
+}
+
+/// Calls recorded by the client's request hook while a command runs
+#[derive(Debug)]
+pub struct Timings {
+    started: Instant,
+    calls: Mutex<Vec<Call>>,
+}
+
+/// Calls with the same method and path
+#[derive(Debug, PartialEq)]
+struct Group {
+    method: String,
+    path: String,
+    calls: usize,
+    retries: usize,
+    failed: usize,
+    total: Duration,
+    slowest: Duration,
+}
+
+impl Timings {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            calls: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn record(&self, request: &RequestLog) {
+        let (path, target) = match reqwest::Url::parse(&request.url) {
+            Ok(url) => {
+                let target = match url.query() {
+                    Some(query) => format!("{}?{query}", url.path()),
+                    None => url.path().to_string(),
+                };
+                (url.path().to_string(), target)
+            }
+            Err(_) => (request.url.clone(), request.url.clone()),
+        };
+        let call = Call {
+            method: request.method.to_string(),
+            path,
+            target,
+            failed: !request.status.is_some_and(|status| status.is_success()),
+            duration: request.duration,
+        };
+        self.calls
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(call);
+    }
+
+    /// Print the summary to stderr, so it never mixes with a command's output
+    pub fn print(&self) {
+        let calls = self.calls.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        let groups = group(&calls);
+        let total: Duration = calls.iter().map(|c| c.duration).sum();
+        let retries: usize = groups.iter().map(|g| g.retries).sum();
+
+        eprintln!();
+        eprintln!(
+            "{} {} API call(s), {} in requests, {} in total{}",
+            "Timings:".bold(),
+            calls.len(),
+            millis(total),
+            millis(self.started.elapsed()),
+            match retries {
+                0 => String::new(),
+                n => format!(", {n} retried"),
+            }
+        );
+
+        let width = groups
+            .iter()
+            .map(|g| g.method.len() + g.path.len() + 1)
+            .max()
+            .unwrap_or(0);
+        for g in &groups {
+            let mut notes = Vec::new();
+            if g.retries > 0 {
+                notes.push(format!("{} retried", g.retries));
+            }
+            if g.failed > 0 {
+                notes.push(format!("{} failed", g.failed).red().to_string());
+            }
+            eprintln!(
+                "  {:<width$}  {:>3}x  {:>7}  {}{}",
+                format!("{} {}", g.method, g.path),
+                g.calls,
+                millis(g.total),
+                format!("slowest {}", millis(g.slowest)).dimmed(),
+                if notes.is_empty() {
+                    String::new()
+                } else {
+                    format!("  {}", notes.join(", "))
+                },
+            );
+        }
+
+        for (shape, count) in repeated_shapes(&calls) {
+            eprintln!(
+                "{} {count} calls to {shape}: could one request fetch them all?",
+                "⚠".yellow().bold()
+            );
+        }
+    }
+}
+
+/// Calls grouped by method and path, in the order each was first made
+fn group(calls: &[Call]) -> Vec<Group> {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut seen = HashSet::new();
+    for call in calls {
+        let retried = !seen.insert((call.method.as_str(), call.target.as_str()));
+        let index = match groups
+            .iter()
+            .position(|g| g.method == call.method && g.path == call.path)
+        {
+            Some(index) => index,
+            None => {
+                groups.push(Group {
+                    method: call.method.clone(),
+                    path: call.path.clone(),
+                    calls: 0,
+                    retries: 0,
+                    failed: 0,
+                    total: Duration::ZERO,
+                    slowest: Duration::ZERO,
+                });
+                groups.len() - 1
+            }
+        };
+        let g = &mut groups[index];
+        g.calls += 1;
+        g.retries += usize::from(retried);
+        g.failed += usize::from(call.failed);
+        g.total += call.duration;
+        g.slowest = g.slowest.max(call.duration);
+    }
+    groups
+}
+
+/// Path shapes called for at least [`N_PLUS_ONE_CALLS`] different last segments
+fn repeated_shapes(calls: &[Call]) -> Vec<(String, usize)> {
+    let mut shapes: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+    for call in calls {
+        if let Some((parent, _)) = call.path.rsplit_once('/') {
+            shapes
+                .entry(format!("{} {parent}/*", call.method))
+                .or_default()
+                .insert(&call.path);
+        }
+    }
+    shapes
+        .into_iter()
+        .filter(|(_, paths)| paths.len() >= N_PLUS_ONE_CALLS)
+        .map(|(shape, paths)| (shape, paths.len()))
+        .collect()
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{}ms", duration.as_millis())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(method: &str, target: &str, millis: u64) -> Call {
+        Call {
+            method: method.to_string(),
+            path: target.split('?').next().unwrap().to_string(),
+            target: target.to_string(),
+            failed: false,
+            duration: Duration::from_millis(millis),
+        }
+    }
+
+    #[test]
+    fn test_groups_count_retries_of_the_same_request() {
+        let calls = [
+            call("GET", "/v1/projects/p/flags?environment=production", 40),
+            call(
+                "POST",
+                "/v1/projects/p/flags/a/toggle?environment=production",
+                10,
+            ),
+            call(
+                "POST",
+                "/v1/projects/p/flags/a/toggle?environment=production",
+                30,
+            ),
+            call("GET", "/v1/projects/p/flags?environment=staging", 20),
+        ];
+        let groups = group(&calls);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].path, "/v1/projects/p/flags");
+        assert_eq!((groups[0].calls, groups[0].retries), (2, 0));
+        assert_eq!(groups[0].total, Duration::from_millis(60));
+        assert_eq!((groups[1].calls, groups[1].retries), (2, 1));
+        assert_eq!(groups[1].slowest, Duration::from_millis(30));
+    }
+
+    #[test]
+    fn test_repeated_shapes_flag_per_item_loops() {
+        let mut calls: Vec<Call> = (0..N_PLUS_ONE_CALLS)
+            .map(|i| call("GET", &format!("/v1/projects/p/flags/flag-{i}"), 5))
+            .collect();
+        calls.push(call("GET", "/v1/projects/p/flags", 5));
+
+        assert_eq!(
+            repeated_shapes(&calls),
+            vec![("GET /v1/projects/p/flags/*".to_string(), N_PLUS_ONE_CALLS)]
+        );
+        assert!(repeated_shapes(&calls[1..]).is_empty());
+    }
+}
@@ -0,0 +1,204 @@
+//! Blocking client for synchronous programs
+//!
+//! Enabled with the `blocking` feature. [`FlagLiteClient`] wraps the async
+//! client with a private single-threaded runtime, so callers need neither
+//! `async` nor a runtime of their own. Flag checks have methods of their own;
+//! anything else goes through [`FlagLiteClient::call`].
+//!
+//! ```no_run
+//! use flaglite_client::blocking::FlagLiteClient;
+//!
+//! let client = FlagLiteClient::new("https://api.flaglite.dev").with_api_key("ffl_env_xxxxx");
+//! if client.evaluate("new-checkout", Some("user-123")).unwrap().enabled {
+//!     // ...
+//! }
+//!
+//! let flags = client.call(|c| c.list_flags("my-app", Some("production"))).unwrap();
+//! ```
+//!
+//! Like `reqwest::blocking`, its methods panic when called from within an
+//! async runtime; use the async client there.
+
+use std::future::Future;
+
+use flaglite_core::{FlagChanges, FlagEvaluation, FlagLiteError, FlagSet, FlagWithState, User};
+use tokio::runtime::{Builder, Runtime};
+
+use crate::overrides::Overrides;
+use crate::store::FlagStore;
+
+/// FlagLite API client whose methods block until the response arrives
+pub struct FlagLiteClient {
+    inner: crate::FlagLiteClient,
+    runtime: Runtime,
+}
+
+impl From<crate::FlagLiteClient> for FlagLiteClient {
+    /// Block on an async client, e.g. one from [`crate::FlagLiteClientBuilder`]
+    fn from(inner: crate::FlagLiteClient) -> Self {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("Failed to start the blocking client's runtime");
+        Self { inner, runtime }
+    }
+}
+
+impl FlagLiteClient {
+    /// Create a new client with the given base URL
+    pub fn new(base_url: impl Into<String>) -> Self {
+        crate::FlagLiteClient::new(base_url).into()
+    }
+
+    /// Set the authentication token (JWT)
+    pub fn with_token(self, token: impl Into<String>) -> Self {
+        self.map(|inner| inner.with_token(token))
+    }
+
+    /// Set the API key for authentication
+    pub fn with_api_key(self, api_key: impl Into<String>) -> Self {
+        self.map(|inner| inner.with_api_key(api_key))
+    }
+
+    /// Replace the overrides read from the environment
+    pub fn with_overrides(self, overrides: Overrides) -> Self {
+        self.map(|inner| inner.with_overrides(overrides))
+    }
+
+    fn map(self, f: impl FnOnce(crate::FlagLiteClient) -> crate::FlagLiteClient) -> Self {
+        Self {
+            inner: f(self.inner),
+            runtime: self.runtime,
+        }
+    }
+
+    /// Get the base URL
+    pub fn base_url(&self) -> &str {
+        self.inner.base_url()
+    }
+
+    /// Local overrides [`FlagLiteClient::evaluate`] applies
+    pub fn overrides(&self) -> &Overrides {
+        self.inner.overrides()
+    }
+
+    /// Run any async client method to completion, e.g.
+    /// `client.call(|c| c.delete_flag("my-app", "old-banner"))`
+    pub fn call<'a, F, Fut, T>(&'a self, f: F) -> T
+    where
+        F: FnOnce(&'a crate::FlagLiteClient) -> Fut,
+        Fut: Future<Output = T>,
+    {
+        self.runtime.block_on(f(&self.inner))
+    }
+
+    /// See [`crate::FlagLiteClient::evaluate`]
+    pub fn evaluate(
+        &self,
+        key: &str,
+        user_id: Option<&str>,
+    ) -> Result<FlagEvaluation, FlagLiteError> {
+        self.call(|c| c.evaluate(key, user_id))
+    }
+
+    /// See [`crate::FlagLiteClient::evaluate_flags`]
+    pub fn evaluate_flags<F: FlagSet>(&self, user_id: Option<&str>) -> Result<F, FlagLiteError> {
+        self.call(|c| c.evaluate_flags(user_id))
+    }
+
+    /// See [`crate::FlagLiteClient::flag_changes`]
+    pub fn flag_changes(&self, since: i64) -> Result<FlagChanges, FlagLiteError> {
+        self.call(|c| c.flag_changes(since))
+    }
+
+    /// See [`crate::FlagLiteClient::sync_changes`]
+    pub fn sync_changes(&self, store: &mut FlagStore) -> Result<usize, FlagLiteError> {
+        self.call(|c| c.sync_changes(store))
+    }
+
+    /// See [`crate::FlagLiteClient::list_flags`]
+    pub fn list_flags(
+        &self,
+        project_id: &str,
+        environment: Option<&str>,
+    ) -> Result<Vec<FlagWithState>, FlagLiteError> {
+        self.call(|c| c.list_flags(project_id, environment))
+    }
+
+    /// See [`crate::FlagLiteClient::get_flag`]
+    pub fn get_flag(
+        &self,
+        project_id: &str,
+        key: &str,
+        environment: Option<&str>,
+    ) -> Result<FlagWithState, FlagLiteError> {
+        self.call(|c| c.get_flag(project_id, key, environment))
+    }
+
+    /// See [`crate::FlagLiteClient::whoami`]
+    pub fn whoami(&self) -> Result<User, FlagLiteError> {
+        self.call(|c| c.whoami())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unreachable() -> FlagLiteClient {
+        // Nothing listens on port 1, so every request fails to connect
+        FlagLiteClient::new("http://127.0.0.1:1")
+            .with_api_key("ffl_env_test")
+            .with_overrides(Overrides::from_vars([(
+                "FLAGLITE_OVERRIDE_DARK_MODE".to_string(),
+                "true".to_string(),
+            )]))
+    }
+
+    #[test]
+    fn test_evaluate_without_a_runtime() {
+        let client = unreachable();
+        assert!(client.evaluate("dark-mode", None).unwrap().enabled);
+        assert!(matches!(
+            client.evaluate("other", None),
+            Err(FlagLiteError::NetworkError(_))
+        ));
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn test_calls_block_until_the_response() {
+        use crate::mock::MockServer;
+        use serde_json::json;
+
+        // The server runs on its own runtime's threads while this one blocks
+        let server_runtime = Runtime::new().unwrap();
+        let server = server_runtime.block_on(MockServer::start());
+        server.mock(
+            "GET",
+            "/v1/changes",
+            200,
+            json!({
+                "project_id": "00000000-0000-0000-0000-000000000001",
+                "environment_id": "00000000-0000-0000-0000-000000000002",
+                "revision": 3,
+                "full": true,
+                "flags": []
+            }),
+        );
+        server.mock(
+            "DELETE",
+            "/v1/projects/:project_id/flags/:key",
+            204,
+            json!(null),
+        );
+
+        let client = FlagLiteClient::from(server.client());
+        let mut store = FlagStore::new();
+        client.sync_changes(&mut store).unwrap();
+        assert_eq!(store.revision(), 3);
+
+        client.call(|c| c.delete_flag("p1", "old-banner")).unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+}
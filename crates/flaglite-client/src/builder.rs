@@ -0,0 +1,231 @@
+//! Builder for clients that need custom networking (proxies, private CAs,
+//! connection pooling, pinned DNS)
+
+use flaglite_core::FlagLiteError;
+use reqwest::{Certificate, Client, Identity, Proxy};
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{FlagLiteClient, RequestHook, RequestLog};
+
+const DEFAULT_USER_AGENT: &str = concat!("flaglite-client/", env!("CARGO_PKG_VERSION"));
+
+/// Configures the HTTP layer of a [`FlagLiteClient`]
+///
+/// Proxies from `HTTP_PROXY`/`HTTPS_PROXY` are honoured unless an explicit
+/// proxy is set here.
+#[derive(Clone)]
+pub struct FlagLiteClientBuilder {
+    base_url: String,
+    root_certificates: Vec<Vec<u8>>,
+    identity: Option<Vec<u8>>,
+    proxy: Option<String>,
+    user_agent: String,
+    on_request: Option<RequestHook>,
+    pool_idle_timeout: Option<Option<Duration>>,
+    pool_max_idle_per_host: Option<usize>,
+    tcp_keepalive: Option<Duration>,
+    resolve: BTreeMap<String, Vec<IpAddr>>,
+}
+
+impl std::fmt::Debug for FlagLiteClientBuilder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FlagLiteClientBuilder")
+            .field("base_url", &self.base_url)
+            .field("root_certificates", &self.root_certificates.len())
+            .field("identity", &self.identity.is_some())
+            .field("proxy", &self.proxy)
+            .field("user_agent", &self.user_agent)
+            .field("on_request", &self.on_request.is_some())
+            .field("pool_idle_timeout", &self.pool_idle_timeout)
+            .field("pool_max_idle_per_host", &self.pool_max_idle_per_host)
+            .field("tcp_keepalive", &self.tcp_keepalive)
+            .field("resolve", &self.resolve)
+            .finish()
+    }
+}
+
+impl FlagLiteClientBuilder {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            root_certificates: Vec::new(),
+            identity: None,
+            proxy: None,
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            on_request: None,
+            pool_idle_timeout: None,
+            pool_max_idle_per_host: None,
+            tcp_keepalive: None,
+            resolve: BTreeMap::new(),
+        }
+    }
+
+    /// Trust the certificates in a PEM bundle in addition to the built-in roots
+    pub fn add_root_certificate_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.root_certificates.push(pem.into());
+        self
+    }
+
+    /// Present a client certificate; the PEM must hold the certificate chain and private key
+    pub fn client_identity_pem(mut self, pem: impl Into<Vec<u8>>) -> Self {
+        self.identity = Some(pem.into());
+        self
+    }
+
+    /// Send all requests through this proxy (e.g. `http://proxy.corp:3128`)
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+
+    /// Override the `User-Agent` header
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = user_agent.into();
+        self
+    }
+
+    /// Call `hook` after every request, e.g. to log traffic while debugging
+    pub fn on_request(mut self, hook: impl Fn(&RequestLog) + Send + Sync + 'static) -> Self {
+        self.on_request = Some(Arc::new(hook));
+        self
+    }
+
+    /// Close pooled connections idle for this long (default 90s); `None`
+    /// keeps them until the server closes them
+    pub fn pool_idle_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.pool_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Keep at most this many idle connections per host (default unlimited);
+    /// 0 opens a new connection for every request
+    pub fn pool_max_idle_per_host(mut self, max: usize) -> Self {
+        self.pool_max_idle_per_host = Some(max);
+        self
+    }
+
+    /// Send TCP keepalive probes on idle connections at this interval, so
+    /// NATs and load balancers don't drop them silently (default 15s)
+    pub fn tcp_keepalive(mut self, interval: Duration) -> Self {
+        self.tcp_keepalive = Some(interval);
+        self
+    }
+
+    /// Connect to `ip` for `host` instead of looking it up in DNS
+    ///
+    /// Calling this again for the same host adds another address to try.
+    /// The port still comes from the base URL, and TLS still checks the
+    /// certificate against `host`.
+    pub fn resolve(mut self, host: impl Into<String>, ip: IpAddr) -> Self {
+        self.resolve.entry(host.into()).or_default().push(ip);
+        self
+    }
+
+    pub fn build(self) -> Result<FlagLiteClient, FlagLiteError> {
+        let mut builder = Client::builder().user_agent(self.user_agent);
+
+        if let Some(timeout) = self.pool_idle_timeout {
+            builder = builder.pool_idle_timeout(timeout);
+        }
+        if let Some(max) = self.pool_max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max);
+        }
+        if let Some(interval) = self.tcp_keepalive {
+            builder = builder.tcp_keepalive(interval);
+        }
+        for (host, ips) in &self.resolve {
+            // The resolver ignores the port; requests use the URL's
+            let addrs: Vec<_> = ips.iter().map(|ip| SocketAddr::new(*ip, 0)).collect();
+            builder = builder.resolve_to_addrs(host, &addrs);
+        }
+
+        for pem in &self.root_certificates {
+            let certs = Certificate::from_pem_bundle(pem).map_err(|e| {
+                FlagLiteError::ValidationError(format!("Invalid CA certificate: {e}"))
+            })?;
+            for cert in certs {
+                builder = builder.add_root_certificate(cert);
+            }
+        }
+
+        if let Some(pem) = &self.identity {
+            let identity = Identity::from_pem(pem).map_err(|e| {
+                FlagLiteError::ValidationError(format!("Invalid client certificate: {e}"))
+            })?;
+            builder = builder.identity(identity);
+        }
+
+        if let Some(url) = &self.proxy {
+            let proxy = Proxy::all(url).map_err(|e| {
+                FlagLiteError::ValidationError(format!("Invalid proxy URL '{url}': {e}"))
+            })?;
+            builder = builder.proxy(proxy);
+        }
+
+        // Certificates are only parsed here, so a bad PEM body surfaces now
+        let client = builder.build().map_err(|e| {
+            FlagLiteError::ValidationError(format!("Invalid TLS or proxy settings: {e}"))
+        })?;
+
+        Ok(FlagLiteClient::with_http_client(
+            client,
+            self.base_url,
+            self.on_request,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_proxy_is_rejected() {
+        let result = FlagLiteClientBuilder::new("http://localhost:3000")
+            .proxy("http://exa mple.com")
+            .build();
+        assert!(matches!(result, Err(FlagLiteError::ValidationError(_))));
+    }
+
+    #[test]
+    fn test_invalid_ca_certificate_is_rejected() {
+        let result = FlagLiteClientBuilder::new("http://localhost:3000")
+            .add_root_certificate_pem(
+                "-----BEGIN CERTIFICATE-----\ngarbage\n-----END CERTIFICATE-----\n",
+            )
+            .build();
+        assert!(matches!(result, Err(FlagLiteError::ValidationError(_))));
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_resolve_overrides_dns() {
+        use crate::mock::{MockServer, MOCK_TOKEN};
+        use serde_json::json;
+
+        let server = MockServer::start().await;
+        server.mock(
+            "DELETE",
+            "/v1/projects/:project_id/flags/:key",
+            204,
+            json!(null),
+        );
+
+        // `.invalid` never resolves, so only the override can reach the server
+        let url = server.url().replace("127.0.0.1", "flaglite.invalid");
+        let client = FlagLiteClientBuilder::new(url)
+            .resolve("flaglite.invalid", IpAddr::from([127, 0, 0, 1]))
+            .pool_idle_timeout(Some(Duration::from_secs(5)))
+            .pool_max_idle_per_host(1)
+            .tcp_keepalive(Duration::from_secs(30))
+            .build()
+            .unwrap()
+            .with_token(MOCK_TOKEN);
+
+        client.delete_flag("p1", "old-banner").await.unwrap();
+        assert_eq!(server.requests().len(), 1);
+    }
+}
@@ -1,10 +1,33 @@
 //! FlagLite API client
 
 use flaglite_core::{
-    ApiErrorResponse, AuthResponse, CreateFlagRequest, CreateProjectRequest, Environment, Flag,
-    FlagLiteError, FlagWithState, PaginatedResponse, Project, SignupRequest, SignupResponse, User,
+    AddOrgMemberRequest, ApiErrorResponse, ApiKey, ApiKeyCreated, AssignmentsRequest,
+    AssignmentsResponse, AuthResponse, BatchUpdateFlagsRequest, BatchUpdateFlagsResponse,
+    ChangeRequest, CloneProjectRequest, CopyEnvironmentRequest, CopyEnvironmentResponse,
+    CreateApiKeyRequest, CreateChangeRequest, CreateEnvironmentRequest, CreateFlagRequest,
+    CreateGuardRequest, CreateInviteLinkRequest, CreateOrgRequest, CreateProjectRequest,
+    CreateRampRequest, DeletedEnvironment, Environment, EnvironmentApproval, FieldError, Flag,
+    FlagChanges, FlagEvaluation, FlagHistory, FlagLiteError, FlagSearchResult, FlagSet, FlagStats,
+    FlagTemplate, FlagWithState, Guard, GuardReport, Invite, InviteLink, JoinResponse, OrgMember,
+    Organization, PaginatedResponse, PasswordResetRequest, Project, ProjectMember, ProjectStats,
+    Ramp, RampAction, RegisterFlagsRequest, RegisterFlagsResponse, ReorderEnvironmentsRequest,
+    ResetPasswordRequest, RollbackFlagRequest, RollbackFlagResponse, SetEnvironmentApprovalRequest,
+    SetFlagStateRequest, SetTemplateRequest, SigningKeys, SignupRequest, SignupResponse,
+    UpdateEnvironmentRequest, UpdateFlagRequest, UpdateOrgMemberRequest, UpdatePreferencesRequest,
+    UpdateProjectRequest, UpdateUserListRequest, UpdateUserRequest, User, UserList,
+    UserPreferences, UsernameChange, VerifyEmailRequest,
 };
-use reqwest::{Client, StatusCode};
+use reqwest::{Client, Method, StatusCode};
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::overrides::Overrides;
+use crate::request::{Request, RequestHook};
+use crate::store::FlagStore;
+use crate::FlagLiteClientBuilder;
+
+/// Seconds the server would like pollers to wait before the next request
+const POLL_INTERVAL_HEADER: &str = "x-poll-interval";
 
 /// FlagLite API client
 pub struct FlagLiteClient {
@@ -12,16 +35,33 @@ pub struct FlagLiteClient {
     base_url: String,
     token: Option<String>,
     api_key: Option<String>,
+    on_request: Option<RequestHook>,
+    overrides: Overrides,
 }
 
 impl FlagLiteClient {
     /// Create a new client with the given base URL
     pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(Client::new(), base_url, None)
+    }
+
+    /// Start building a client with custom TLS, proxy, or user-agent settings
+    pub fn builder(base_url: impl Into<String>) -> FlagLiteClientBuilder {
+        FlagLiteClientBuilder::new(base_url)
+    }
+
+    pub(crate) fn with_http_client(
+        client: Client,
+        base_url: impl Into<String>,
+        on_request: Option<RequestHook>,
+    ) -> Self {
         Self {
-            client: Client::new(),
+            client,
             base_url: base_url.into().trim_end_matches('/').to_string(),
             token: None,
             api_key: None,
+            on_request,
+            overrides: Overrides::from_env(),
         }
     }
 
@@ -37,11 +77,32 @@ impl FlagLiteClient {
         self
     }
 
+    /// Replace the overrides read from the environment, e.g. with
+    /// `Overrides::default()` to ignore them
+    pub fn with_overrides(mut self, overrides: Overrides) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
     /// Get the base URL
     pub fn base_url(&self) -> &str {
         &self.base_url
     }
 
+    /// Local overrides [`FlagLiteClient::evaluate`] applies
+    pub fn overrides(&self) -> &Overrides {
+        &self.overrides
+    }
+
+    /// Start a request; with the `otel` feature it carries the current span's
+    /// trace context as a `traceparent` header
+    fn request(&self, method: Method, url: &str) -> Request {
+        let request = self.client.request(method, url);
+        #[cfg(feature = "otel")]
+        let request = request.headers(crate::otel::trace_headers());
+        Request::new(request, self.on_request.clone())
+    }
+
     fn auth_header(&self) -> Result<String, FlagLiteError> {
         // Prefer API key over token
         if let Some(key) = &self.api_key {
@@ -58,17 +119,48 @@ impl FlagLiteClient {
             return FlagLiteError::InvalidCredentials;
         }
 
-        if status == StatusCode::TOO_MANY_REQUESTS {
-            return FlagLiteError::RateLimited { retry_after: 60 };
-        }
-
         if let Ok(err) = serde_json::from_str::<ApiErrorResponse>(body) {
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                let retry_after = err
+                    .details
+                    .as_ref()
+                    .and_then(|d| d["retry_after"].as_u64())
+                    .unwrap_or(60);
+                return FlagLiteError::RateLimited { retry_after };
+            }
+
+            if status == StatusCode::LOCKED {
+                let environment = err
+                    .details
+                    .as_ref()
+                    .and_then(|d| d["environment"].as_str())
+                    .unwrap_or_default();
+                return FlagLiteError::EnvironmentFrozen(environment.to_string());
+            }
+
+            if status == StatusCode::CONFLICT {
+                return FlagLiteError::Conflict(err.error);
+            }
+
+            if status == StatusCode::UNPROCESSABLE_ENTITY {
+                let fields = err
+                    .details
+                    .and_then(|d| serde_json::from_value::<Vec<FieldError>>(d).ok());
+                if let Some(fields) = fields.filter(|f| !f.is_empty()) {
+                    return FlagLiteError::InvalidFields(fields);
+                }
+            }
+
             return FlagLiteError::ApiError {
                 status: status.as_u16(),
                 message: err.error,
             };
         }
 
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return FlagLiteError::RateLimited { retry_after: 60 };
+        }
+
         FlagLiteError::ApiError {
             status: status.as_u16(),
             message: body.to_string(),
@@ -78,20 +170,23 @@ impl FlagLiteClient {
     // === Auth ===
 
     /// Signup with optional username and password
+    ///
+    /// `invite_code` is only needed when the server is invite-only.
     pub async fn signup(
         &self,
         username: Option<&str>,
         password: &str,
+        invite_code: Option<&str>,
     ) -> Result<SignupResponse, FlagLiteError> {
         let url = format!("{}/v1/auth/signup", self.base_url);
         let req = SignupRequest {
             username: username.map(|s| s.to_string()),
             password: password.to_string(),
+            invite_code: invite_code.map(|s| s.to_string()),
         };
 
         let resp = self
-            .client
-            .post(&url)
+            .request(Method::POST, &url)
             .json(&req)
             .send()
             .await
@@ -123,8 +218,7 @@ impl FlagLiteClient {
         };
 
         let resp = self
-            .client
-            .post(&url)
+            .request(Method::POST, &url)
             .json(&req)
             .send()
             .await
@@ -149,8 +243,7 @@ impl FlagLiteClient {
         let auth = self.auth_header()?;
 
         let resp = self
-            .client
-            .get(&url)
+            .request(Method::GET, &url)
             .header("Authorization", auth)
             .send()
             .await
@@ -169,17 +262,16 @@ impl FlagLiteClient {
         serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
     }
 
-    // === Projects ===
-
-    /// List all projects
-    pub async fn list_projects(&self) -> Result<Vec<Project>, FlagLiteError> {
-        let url = format!("{}/v1/projects", self.base_url);
+    /// Change the current user's email or username; a new address starts
+    /// unverified
+    pub async fn update_me(&self, req: &UpdateUserRequest) -> Result<User, FlagLiteError> {
+        let url = format!("{}/v1/auth/me", self.base_url);
         let auth = self.auth_header()?;
 
         let resp = self
-            .client
-            .get(&url)
+            .request(Method::PATCH, &url)
             .header("Authorization", auth)
+            .json(req)
             .send()
             .await
             .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
@@ -194,27 +286,42 @@ impl FlagLiteClient {
             return Err(self.handle_error(status, &body).await);
         }
 
-        // Try paginated first, then plain array
-        if let Ok(paginated) = serde_json::from_str::<PaginatedResponse<Project>>(&body) {
-            return Ok(paginated.data);
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// The current user's username changes, newest first
+    pub async fn list_username_changes(&self) -> Result<Vec<UsernameChange>, FlagLiteError> {
+        let url = format!("{}/v1/auth/me/username-changes", self.base_url);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
         }
 
         serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
     }
 
-    /// Create a new project
-    pub async fn create_project(
-        &self,
-        req: CreateProjectRequest,
-    ) -> Result<Project, FlagLiteError> {
-        let url = format!("{}/v1/projects", self.base_url);
+    /// The current user's saved CLI defaults
+    pub async fn preferences(&self) -> Result<UserPreferences, FlagLiteError> {
+        let url = format!("{}/v1/auth/preferences", self.base_url);
         let auth = self.auth_header()?;
 
         let resp = self
-            .client
-            .post(&url)
+            .request(Method::GET, &url)
             .header("Authorization", auth)
-            .json(&req)
             .send()
             .await
             .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
@@ -232,20 +339,18 @@ impl FlagLiteClient {
         serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
     }
 
-    // === Environments ===
-
-    /// List environments for a project
-    pub async fn list_environments(
+    /// Set or clear some of the current user's saved CLI defaults
+    pub async fn update_preferences(
         &self,
-        project_id: &str,
-    ) -> Result<Vec<Environment>, FlagLiteError> {
-        let url = format!("{}/v1/projects/{}/environments", self.base_url, project_id);
+        req: &UpdatePreferencesRequest,
+    ) -> Result<UserPreferences, FlagLiteError> {
+        let url = format!("{}/v1/auth/preferences", self.base_url);
         let auth = self.auth_header()?;
 
         let resp = self
-            .client
-            .get(&url)
+            .request(Method::PATCH, &url)
             .header("Authorization", auth)
+            .json(req)
             .send()
             .await
             .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
@@ -260,30 +365,110 @@ impl FlagLiteClient {
             return Err(self.handle_error(status, &body).await);
         }
 
-        if let Ok(paginated) = serde_json::from_str::<PaginatedResponse<Environment>>(&body) {
-            return Ok(paginated.data);
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Email a verification token to the current user's address
+    pub async fn send_verification_email(&self) -> Result<(), FlagLiteError> {
+        let url = format!("{}/v1/auth/me/verify-email", self.base_url);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+
+        if !status.is_success() {
+            let body = resp
+                .text()
+                .await
+                .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        Ok(())
+    }
+
+    /// Confirm an email address with a token from a verification email
+    pub async fn verify_email(&self, req: &VerifyEmailRequest) -> Result<User, FlagLiteError> {
+        let url = format!("{}/v1/auth/verify-email", self.base_url);
+
+        let resp = self
+            .request(Method::POST, &url)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
         }
 
         serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
     }
 
-    // === Flags ===
+    /// Ask for a password reset email
+    ///
+    /// Succeeds even if the user doesn't exist or has no verified email, in
+    /// which case nothing is sent.
+    pub async fn request_password_reset(
+        &self,
+        req: &PasswordResetRequest,
+    ) -> Result<(), FlagLiteError> {
+        let url = format!("{}/v1/auth/password-reset", self.base_url);
+        self.post_unauthenticated(&url, req).await
+    }
 
-    /// List flags for a project (optionally in a specific environment)
-    pub async fn list_flags(
+    /// Set a new password with a token from a reset email
+    pub async fn reset_password(&self, req: &ResetPasswordRequest) -> Result<(), FlagLiteError> {
+        let url = format!("{}/v1/auth/password-reset/confirm", self.base_url);
+        self.post_unauthenticated(&url, req).await
+    }
+
+    /// POST without credentials, expecting no response body
+    async fn post_unauthenticated<T: serde::Serialize>(
         &self,
-        project_id: &str,
-        environment: Option<&str>,
-    ) -> Result<Vec<FlagWithState>, FlagLiteError> {
-        let mut url = format!("{}/v1/projects/{}/flags", self.base_url, project_id);
-        if let Some(env) = environment {
-            url = format!("{url}?environment={env}");
+        url: &str,
+        req: &T,
+    ) -> Result<(), FlagLiteError> {
+        let resp = self
+            .request(Method::POST, url)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+
+        if !status.is_success() {
+            let body = resp
+                .text()
+                .await
+                .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+            return Err(self.handle_error(status, &body).await);
         }
+
+        Ok(())
+    }
+
+    /// Create a single-use invite code for invite-only signup (organization
+    /// owners only)
+    pub async fn create_invite(&self) -> Result<Invite, FlagLiteError> {
+        let url = format!("{}/v1/invites", self.base_url);
         let auth = self.auth_header()?;
 
         let resp = self
-            .client
-            .get(&url)
+            .request(Method::POST, &url)
             .header("Authorization", auth)
             .send()
             .await
@@ -299,30 +484,46 @@ impl FlagLiteClient {
             return Err(self.handle_error(status, &body).await);
         }
 
-        if let Ok(paginated) = serde_json::from_str::<PaginatedResponse<FlagWithState>>(&body) {
-            return Ok(paginated.data);
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// List the user's API keys, newest first
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>, FlagLiteError> {
+        let url = format!("{}/v1/auth/api-keys", self.base_url);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
         }
 
         serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
     }
 
-    /// Get a specific flag
-    pub async fn get_flag(
+    /// Create a user API key, e.g. a read-only one for a dashboard
+    pub async fn create_api_key(
         &self,
-        project_id: &str,
-        key: &str,
-        environment: Option<&str>,
-    ) -> Result<FlagWithState, FlagLiteError> {
-        let mut url = format!("{}/v1/projects/{}/flags/{}", self.base_url, project_id, key);
-        if let Some(env) = environment {
-            url = format!("{url}?environment={env}");
-        }
+        req: &CreateApiKeyRequest,
+    ) -> Result<ApiKeyCreated, FlagLiteError> {
+        let url = format!("{}/v1/auth/api-keys", self.base_url);
         let auth = self.auth_header()?;
 
         let resp = self
-            .client
-            .get(&url)
+            .request(Method::POST, &url)
             .header("Authorization", auth)
+            .json(req)
             .send()
             .await
             .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
@@ -333,10 +534,33 @@ impl FlagLiteClient {
             .await
             .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
 
-        if status == StatusCode::NOT_FOUND {
-            return Err(FlagLiteError::FlagNotFound(key.to_string()));
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
         }
 
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    // === Organizations ===
+
+    /// List organizations the caller belongs to
+    pub async fn list_orgs(&self) -> Result<Vec<Organization>, FlagLiteError> {
+        let url = format!("{}/v1/orgs", self.base_url);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
         if !status.is_success() {
             return Err(self.handle_error(status, &body).await);
         }
@@ -344,18 +568,13 @@ impl FlagLiteClient {
         serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
     }
 
-    /// Create a new flag
-    pub async fn create_flag(
-        &self,
-        project_id: &str,
-        req: CreateFlagRequest,
-    ) -> Result<Flag, FlagLiteError> {
-        let url = format!("{}/v1/projects/{}/flags", self.base_url, project_id);
+    /// Create an organization owned by the caller
+    pub async fn create_org(&self, req: CreateOrgRequest) -> Result<Organization, FlagLiteError> {
+        let url = format!("{}/v1/orgs", self.base_url);
         let auth = self.auth_header()?;
 
         let resp = self
-            .client
-            .post(&url)
+            .request(Method::POST, &url)
             .header("Authorization", auth)
             .json(&req)
             .send()
@@ -375,22 +594,13 @@ impl FlagLiteClient {
         serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
     }
 
-    /// Toggle a flag's enabled state
-    pub async fn toggle_flag(
-        &self,
-        project_id: &str,
-        key: &str,
-        environment: &str,
-    ) -> Result<FlagWithState, FlagLiteError> {
-        let url = format!(
-            "{}/v1/projects/{}/flags/{}/toggle?environment={}",
-            self.base_url, project_id, key, environment
-        );
+    /// List members of an organization
+    pub async fn list_org_members(&self, org_id: &str) -> Result<Vec<OrgMember>, FlagLiteError> {
+        let url = format!("{}/v1/orgs/{}/members", self.base_url, org_id);
         let auth = self.auth_header()?;
 
         let resp = self
-            .client
-            .post(&url)
+            .request(Method::GET, &url)
             .header("Authorization", auth)
             .send()
             .await
@@ -402,10 +612,6 @@ impl FlagLiteClient {
             .await
             .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
 
-        if status == StatusCode::NOT_FOUND {
-            return Err(FlagLiteError::FlagNotFound(key.to_string()));
-        }
-
         if !status.is_success() {
             return Err(self.handle_error(status, &body).await);
         }
@@ -413,33 +619,2422 @@ impl FlagLiteClient {
         serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
     }
 
-    /// Delete a flag
-    pub async fn delete_flag(&self, project_id: &str, key: &str) -> Result<(), FlagLiteError> {
-        let url = format!("{}/v1/projects/{}/flags/{}", self.base_url, project_id, key);
+    /// Add an existing user to an organization (owners only)
+    pub async fn add_org_member(
+        &self,
+        org_id: &str,
+        req: AddOrgMemberRequest,
+    ) -> Result<OrgMember, FlagLiteError> {
+        let url = format!("{}/v1/orgs/{}/members", self.base_url, org_id);
         let auth = self.auth_header()?;
 
         let resp = self
-            .client
-            .delete(&url)
+            .request(Method::POST, &url)
             .header("Authorization", auth)
+            .json(&req)
             .send()
             .await
             .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
 
         let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
 
-        if status == StatusCode::NOT_FOUND {
-            return Err(FlagLiteError::FlagNotFound(key.to_string()));
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
         }
 
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Change an organization member's role
+    pub async fn update_org_member(
+        &self,
+        org_id: &str,
+        user_id: &str,
+        req: &UpdateOrgMemberRequest,
+    ) -> Result<OrgMember, FlagLiteError> {
+        let url = format!("{}/v1/orgs/{}/members/{}", self.base_url, org_id, user_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::PATCH, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
         if !status.is_success() {
-            let body = resp
-                .text()
-                .await
-                .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
             return Err(self.handle_error(status, &body).await);
         }
 
-        Ok(())
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Remove a member from an organization
+    pub async fn remove_org_member(
+        &self,
+        org_id: &str,
+        user_id: &str,
+    ) -> Result<OrgMember, FlagLiteError> {
+        let url = format!("{}/v1/orgs/{}/members/{}", self.base_url, org_id, user_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::DELETE, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Create a user API key that can only reach one organization's projects
+    pub async fn create_org_api_key(
+        &self,
+        org_id: &str,
+        req: &CreateApiKeyRequest,
+    ) -> Result<ApiKeyCreated, FlagLiteError> {
+        let url = format!("{}/v1/orgs/{}/api-keys", self.base_url, org_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    // === Projects ===
+
+    /// List projects, across all of the caller's organizations unless `org_id` is given
+    pub async fn list_projects(&self, org_id: Option<&str>) -> Result<Vec<Project>, FlagLiteError> {
+        let mut url = format!("{}/v1/projects", self.base_url);
+        if let Some(org_id) = org_id {
+            url.push_str(&format!("?org_id={org_id}"));
+        }
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        // Try paginated first, then plain array
+        if let Ok(paginated) = serde_json::from_str::<PaginatedResponse<Project>>(&body) {
+            return Ok(paginated.data);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Get a project by ID or slug
+    pub async fn get_project(&self, project: &str) -> Result<Project, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}", self.base_url, project);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::ProjectNotFound(project.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Get flag counts per environment for a project (ID or slug)
+    pub async fn project_stats(&self, project: &str) -> Result<ProjectStats, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/stats", self.base_url, project);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::ProjectNotFound(project.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Create a new project
+    pub async fn create_project(
+        &self,
+        req: CreateProjectRequest,
+    ) -> Result<Project, FlagLiteError> {
+        let url = format!("{}/v1/projects", self.base_url);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Update a project's settings
+    pub async fn update_project(
+        &self,
+        project: &str,
+        req: &UpdateProjectRequest,
+    ) -> Result<Project, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}", self.base_url, project);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::PATCH, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::ProjectNotFound(project.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Clone a project's environments and flags into a new project
+    pub async fn clone_project(
+        &self,
+        project_id: &str,
+        req: CloneProjectRequest,
+    ) -> Result<Project, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/clone", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::ProjectNotFound(project_id.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Download a project as a `.tar.gz` archive, for [`Self::import_project`]
+    pub async fn export_project(&self, project_id: &str) -> Result<Vec<u8>, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/export", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::ProjectNotFound(project_id.to_string()));
+        }
+        if !status.is_success() {
+            let body = resp
+                .text()
+                .await
+                .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        resp.bytes()
+            .await
+            .map(|bytes| bytes.to_vec())
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))
+    }
+
+    /// Create a project from an archive made by [`Self::export_project`],
+    /// named `name` or the archived project's name, in `org_id` or the
+    /// caller's personal organization
+    pub async fn import_project(
+        &self,
+        archive: Vec<u8>,
+        name: Option<&str>,
+        org_id: Option<&str>,
+    ) -> Result<Project, FlagLiteError> {
+        let url = format!("{}/v1/import", self.base_url);
+        let auth = self.auth_header()?;
+
+        let mut request = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .header("Content-Type", "application/gzip")
+            .body(archive);
+        if let Some(name) = name {
+            request = request.query(&[("name", name)]);
+        }
+        if let Some(org_id) = org_id {
+            request = request.query(&[("org_id", org_id)]);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    // === Evaluation ===
+
+    /// Evaluate a flag with an SDK key (`ffl_env_` or `ffl_proj_`), bucketing
+    /// by `user_id` for percentage rollouts
+    ///
+    /// A local override for the flag replaces the server's result, and is
+    /// served even if the request fails, e.g. for a flag not created yet.
+    pub async fn evaluate(
+        &self,
+        key: &str,
+        user_id: Option<&str>,
+    ) -> Result<FlagEvaluation, FlagLiteError> {
+        match self.evaluate_remote(key, user_id).await {
+            Ok(evaluation) => Ok(self.overrides.apply(evaluation)),
+            Err(_) if self.overrides.get(key).is_some() => {
+                Ok(self.overrides.apply(FlagEvaluation {
+                    key: key.to_string(),
+                    enabled: false,
+                    value: None,
+                    overridden: false,
+                }))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Evaluate every flag in a [`FlagSet`] (see
+    /// [`define_flags!`](flaglite_core::define_flags)), bucketing by `user_id`
+    ///
+    /// A flag the project doesn't have yet gets the set's default, so code can
+    /// ship before its flags are created; other errors are returned.
+    pub async fn evaluate_flags<F: FlagSet>(
+        &self,
+        user_id: Option<&str>,
+    ) -> Result<F, FlagLiteError> {
+        let mut evaluations = HashMap::new();
+        for definition in F::definitions() {
+            match self.evaluate(&definition.key, user_id).await {
+                Ok(evaluation) => {
+                    evaluations.insert(definition.key, evaluation);
+                }
+                Err(FlagLiteError::FlagNotFound { .. }) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(F::from_evaluations(&evaluations))
+    }
+
+    async fn evaluate_remote(
+        &self,
+        key: &str,
+        user_id: Option<&str>,
+    ) -> Result<FlagEvaluation, FlagLiteError> {
+        let url = format!("{}/v1/flags/{}/evaluate", self.base_url, key);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .query(&[("user_id", user_id)])
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(flag_not_found(key, &body));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Flags changed since `since`, a `revision` from an earlier call (0 for
+    /// every flag), with an SDK key
+    pub async fn flag_changes(&self, since: i64) -> Result<FlagChanges, FlagLiteError> {
+        Ok(self.flag_changes_with_interval(since).await?.0)
+    }
+
+    /// [`Self::flag_changes`] and the poll interval the server asked for in
+    /// `X-Poll-Interval`, if it sent one
+    pub(crate) async fn flag_changes_with_interval(
+        &self,
+        since: i64,
+    ) -> Result<(FlagChanges, Option<Duration>), FlagLiteError> {
+        let url = format!("{}/v1/changes", self.base_url);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .query(&[("since", since)])
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let interval = resp
+            .headers()
+            .get(POLL_INTERVAL_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        let changes = serde_json::from_str(&body)
+            .map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))?;
+        Ok((changes, interval))
+    }
+
+    /// Bring `store` up to date, fetching only the flags changed since its
+    /// last sync. Returns how many flags were added, changed or removed.
+    pub async fn sync_changes(&self, store: &mut FlagStore) -> Result<usize, FlagLiteError> {
+        let changes = self.flag_changes(store.revision()).await?;
+        Ok(store.apply(changes))
+    }
+
+    // === Environments ===
+
+    /// List environments for a project
+    pub async fn list_environments(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<Environment>, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/environments", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        if let Ok(paginated) = serde_json::from_str::<PaginatedResponse<Environment>>(&body) {
+            return Ok(paginated.data);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Add an environment to a project, optionally starting from another
+    /// environment's flag values
+    pub async fn create_environment(
+        &self,
+        project_id: &str,
+        req: &CreateEnvironmentRequest,
+    ) -> Result<Environment, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/environments", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            if let Some(source) = &req.copy_from {
+                return Err(FlagLiteError::EnvironmentNotFound(source.clone()));
+            }
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Update an environment's settings (e.g. protection)
+    pub async fn update_environment(
+        &self,
+        project_id: &str,
+        name: &str,
+        req: UpdateEnvironmentRequest,
+    ) -> Result<Environment, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/environments/{}",
+            self.base_url, project_id, name
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::PATCH, &url)
+            .header("Authorization", auth)
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::EnvironmentNotFound(name.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Set the order environments are listed in, returning them in the new order
+    pub async fn reorder_environments(
+        &self,
+        project_id: &str,
+        order: &[String],
+    ) -> Result<Vec<Environment>, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/environments", self.base_url, project_id);
+        let auth = self.auth_header()?;
+        let req = ReorderEnvironmentsRequest {
+            order: order.to_vec(),
+        };
+
+        let resp = self
+            .request(Method::PUT, &url)
+            .header("Authorization", auth)
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Copy an environment's flag values into another environment, usually of
+    /// another project, for every flag key both have
+    ///
+    /// All-or-nothing like a batch update; with `dry_run` nothing changes and
+    /// the response says what would.
+    pub async fn copy_environment(
+        &self,
+        req: &CopyEnvironmentRequest,
+    ) -> Result<CopyEnvironmentResponse, FlagLiteError> {
+        let url = format!("{}/v1/environments/copy", self.base_url);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::PRECONDITION_REQUIRED {
+            return Err(precondition_required(&req.to_environment, &body));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Freeze or unfreeze an environment's flag values
+    pub async fn set_environment_frozen(
+        &self,
+        project_id: &str,
+        name: &str,
+        frozen: bool,
+    ) -> Result<Environment, FlagLiteError> {
+        let action = if frozen { "freeze" } else { "unfreeze" };
+        let url = format!(
+            "{}/v1/projects/{}/environments/{}/{}",
+            self.base_url, project_id, name, action
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::EnvironmentNotFound(name.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Whether flag changes in an environment need approval
+    pub async fn environment_approval(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<EnvironmentApproval, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/environments/{}/approval",
+            self.base_url, project_id, name
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::EnvironmentNotFound(name.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Require approval for flag changes in an environment, or stop requiring
+    /// it (project owners only)
+    pub async fn set_environment_approval(
+        &self,
+        project_id: &str,
+        name: &str,
+        required: bool,
+    ) -> Result<EnvironmentApproval, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/environments/{}/approval",
+            self.base_url, project_id, name
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::PUT, &url)
+            .header("Authorization", auth)
+            .json(&SetEnvironmentApprovalRequest { required })
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::EnvironmentNotFound(name.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Replace the key that signs an environment's SDK snapshots; returns the
+    /// keys SDKs should now accept
+    pub async fn rotate_signing_key(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<SigningKeys, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/environments/{}/signing-key/rotate",
+            self.base_url, project_id, name
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::EnvironmentNotFound(name.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Delete an environment; it can be restored until its archive is
+    /// purged. Protected environments need `force`, and a project owner.
+    pub async fn delete_environment(
+        &self,
+        project_id: &str,
+        name: &str,
+        force: bool,
+    ) -> Result<DeletedEnvironment, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/environments/{}?force={}",
+            self.base_url, project_id, name, force
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::DELETE, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::EnvironmentNotFound(name.to_string()));
+        }
+
+        if status == StatusCode::PRECONDITION_REQUIRED {
+            return Err(FlagLiteError::ProtectedEnvironment(name.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Deleted environments that can still be restored, most recent first
+    pub async fn list_deleted_environments(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<DeletedEnvironment>, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/deleted-environments",
+            self.base_url, project_id
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Restore the most recently deleted environment with this name, with
+    /// its keys and flag values
+    pub async fn restore_environment(
+        &self,
+        project_id: &str,
+        name: &str,
+    ) -> Result<Environment, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/environments/{}/restore",
+            self.base_url, project_id, name
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    // === Flags ===
+
+    /// List flags for a project (optionally in a specific environment)
+    pub async fn list_flags(
+        &self,
+        project_id: &str,
+        environment: Option<&str>,
+    ) -> Result<Vec<FlagWithState>, FlagLiteError> {
+        let mut url = format!("{}/v1/projects/{}/flags", self.base_url, project_id);
+        if let Some(env) = environment {
+            url = format!("{url}?environment={env}");
+        }
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        if let Ok(paginated) = serde_json::from_str::<PaginatedResponse<FlagWithState>>(&body) {
+            return Ok(paginated.data);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Get a specific flag
+    pub async fn get_flag(
+        &self,
+        project_id: &str,
+        key: &str,
+        environment: Option<&str>,
+    ) -> Result<FlagWithState, FlagLiteError> {
+        let mut url = format!("{}/v1/projects/{}/flags/{}", self.base_url, project_id, key);
+        if let Some(env) = environment {
+            url = format!("{url}?environment={env}");
+        }
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(flag_not_found(key, &body));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Find flags whose key or name contains `query` in every project the
+    /// user can access, or only in one organization's projects
+    pub async fn search_flags(
+        &self,
+        query: &str,
+        org_id: Option<&str>,
+    ) -> Result<Vec<FlagSearchResult>, FlagLiteError> {
+        let url = format!("{}/v1/search", self.base_url);
+        let auth = self.auth_header()?;
+
+        let mut request = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .query(&[("q", query)]);
+        if let Some(org_id) = org_id {
+            request = request.query(&[("org_id", org_id)]);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Get evaluation counts for a flag
+    pub async fn flag_stats(
+        &self,
+        project_id: &str,
+        key: &str,
+    ) -> Result<FlagStats, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/flags/{}/stats",
+            self.base_url, project_id, key
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(flag_not_found(key, &body));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Each user's bucket and result for a flag in one environment
+    pub async fn flag_assignments(
+        &self,
+        project_id: &str,
+        key: &str,
+        req: &AssignmentsRequest,
+    ) -> Result<AssignmentsResponse, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/flags/{}/assignments",
+            self.base_url, project_id, key
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            let code = serde_json::from_str::<ApiErrorResponse>(&body)
+                .ok()
+                .and_then(|err| err.code);
+            return Err(match code.as_deref() {
+                Some("flag_not_found") => flag_not_found(key, &body),
+                _ => FlagLiteError::EnvironmentNotFound(req.environment.clone()),
+            });
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// A flag's allowlist or denylist (`list` is `allowlist` or `denylist`)
+    /// in one environment
+    pub async fn user_list(
+        &self,
+        project_id: &str,
+        key: &str,
+        list: &str,
+        environment: &str,
+    ) -> Result<UserList, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/flags/{}/{}?environment={}",
+            self.base_url, project_id, key, list, environment
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        self.user_list_response(resp, key, environment).await
+    }
+
+    /// Add users to or remove them from a flag's allowlist or denylist
+    pub async fn update_user_list(
+        &self,
+        project_id: &str,
+        key: &str,
+        list: &str,
+        req: &UpdateUserListRequest,
+    ) -> Result<UserList, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/flags/{}/{}",
+            self.base_url, project_id, key, list
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::PATCH, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        self.user_list_response(resp, key, &req.environment).await
+    }
+
+    async fn user_list_response(
+        &self,
+        resp: reqwest::Response,
+        key: &str,
+        environment: &str,
+    ) -> Result<UserList, FlagLiteError> {
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            let code = serde_json::from_str::<ApiErrorResponse>(&body)
+                .ok()
+                .and_then(|err| err.code);
+            return Err(match code.as_deref() {
+                Some("flag_not_found") => flag_not_found(key, &body),
+                _ => FlagLiteError::EnvironmentNotFound(environment.to_string()),
+            });
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Turn many flags on or off, or set their rollout, in one environment
+    ///
+    /// All-or-nothing: if any key is unknown or any flag changes concurrently,
+    /// nothing is updated.
+    pub async fn batch_update_flags(
+        &self,
+        project_id: &str,
+        req: &BatchUpdateFlagsRequest,
+    ) -> Result<BatchUpdateFlagsResponse, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/flags", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::PATCH, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            let err = serde_json::from_str::<ApiErrorResponse>(&body).ok();
+            return Err(match err.as_ref().and_then(|err| err.code.as_deref()) {
+                Some("flag_not_found") => {
+                    // The server names the first key it couldn't find
+                    let key = err
+                        .as_ref()
+                        .and_then(|err| err.details.as_ref())
+                        .and_then(|details| details["key"].as_str())
+                        .unwrap_or_default();
+                    flag_not_found(key, &body)
+                }
+                _ => FlagLiteError::EnvironmentNotFound(req.environment.clone()),
+            });
+        }
+
+        if status == StatusCode::PRECONDITION_REQUIRED {
+            return Err(precondition_required(&req.environment, &body));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Create the flags in a [`FlagSet`] that the project doesn't have yet,
+    /// serving the set's defaults; with a user token or API key
+    ///
+    /// Existing flags are left as they are, so this is safe to call on every
+    /// start.
+    pub async fn register_flags<F: FlagSet>(
+        &self,
+        project_id: &str,
+    ) -> Result<RegisterFlagsResponse, FlagLiteError> {
+        let req = RegisterFlagsRequest {
+            flags: F::definitions()
+                .iter()
+                .map(|definition| definition.create_request())
+                .collect(),
+        };
+        for flag in &req.flags {
+            flag.validate()?;
+        }
+
+        let url = format!("{}/v1/projects/{}/flags", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::PUT, &url)
+            .header("Authorization", auth)
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Create a new flag
+    pub async fn create_flag(
+        &self,
+        project_id: &str,
+        req: CreateFlagRequest,
+    ) -> Result<Flag, FlagLiteError> {
+        // Same rules as the server, without the round trip
+        req.validate()?;
+
+        let url = format!("{}/v1/projects/{}/flags", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Create a flag in the state the template gives each environment
+    pub async fn create_flag_from_template(
+        &self,
+        project_id: &str,
+        template: &str,
+        req: CreateFlagRequest,
+    ) -> Result<Flag, FlagLiteError> {
+        req.validate()?;
+
+        let url = format!(
+            "{}/v1/projects/{}/templates/{}/flags",
+            self.base_url, project_id, template
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Built-in and stored flag templates of a project
+    pub async fn list_templates(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<FlagTemplate>, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/templates", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Create or replace a flag template
+    pub async fn set_template(
+        &self,
+        project_id: &str,
+        name: &str,
+        req: SetTemplateRequest,
+    ) -> Result<FlagTemplate, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/templates/{}",
+            self.base_url, project_id, name
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::PUT, &url)
+            .header("Authorization", auth)
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Delete a stored flag template
+    pub async fn delete_template(&self, project_id: &str, name: &str) -> Result<(), FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/templates/{}",
+            self.base_url, project_id, name
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::DELETE, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp
+                .text()
+                .await
+                .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        Ok(())
+    }
+
+    /// Set a flag's owner, repository, or code path
+    pub async fn update_flag(
+        &self,
+        project_id: &str,
+        key: &str,
+        req: UpdateFlagRequest,
+    ) -> Result<Flag, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/flags/{}", self.base_url, project_id, key);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::PATCH, &url)
+            .header("Authorization", auth)
+            .json(&req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(flag_not_found(key, &body));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Toggle a flag's enabled state
+    ///
+    /// Protected environments reject the change unless `confirm` is set, and
+    /// with [`FlagLiteError::ReasonRequired`] if the project wants a `reason`.
+    ///
+    /// With `expected_version`, the toggle fails with [`FlagLiteError::Conflict`]
+    /// if someone else changed the flag since that version was read.
+    pub async fn toggle_flag(
+        &self,
+        project_id: &str,
+        key: &str,
+        environment: &str,
+        confirm: bool,
+        expected_version: Option<i64>,
+        reason: Option<&str>,
+    ) -> Result<FlagWithState, FlagLiteError> {
+        let mut url = format!(
+            "{}/v1/projects/{}/flags/{}/toggle?environment={}",
+            self.base_url, project_id, key, environment
+        );
+        if confirm {
+            url.push_str("&confirm=true");
+        }
+        let auth = self.auth_header()?;
+
+        let mut request = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth);
+        if let Some(reason) = reason {
+            request = request.query(&[("reason", reason)]);
+        }
+        if let Some(version) = expected_version {
+            request = request.header("If-Match", format!("\"{version}\""));
+        }
+
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(flag_not_found(key, &body));
+        }
+
+        if status == StatusCode::PRECONDITION_REQUIRED {
+            return Err(precondition_required(environment, &body));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Set a flag on or off in one environment
+    ///
+    /// Safe to retry: a flag already in the requested state is left as is.
+    pub async fn set_flag_state(
+        &self,
+        project_id: &str,
+        key: &str,
+        req: &SetFlagStateRequest,
+    ) -> Result<FlagWithState, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/flags/{}/state",
+            self.base_url, project_id, key
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::PUT, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(flag_not_found(key, &body));
+        }
+
+        if status == StatusCode::PRECONDITION_REQUIRED {
+            return Err(precondition_required(&req.environment, &body));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Every recorded version of a flag's value in one environment, newest
+    /// first, following the server's pages
+    pub async fn flag_history(
+        &self,
+        project_id: &str,
+        key: &str,
+        environment: &str,
+    ) -> Result<FlagHistory, FlagLiteError> {
+        let mut history = self
+            .flag_history_page(project_id, key, environment, None, None)
+            .await?;
+        while let Some(cursor) = history.next_cursor.take() {
+            let page = self
+                .flag_history_page(project_id, key, environment, None, Some(&cursor))
+                .await?;
+            history.versions.extend(page.versions);
+            history.next_cursor = page.next_cursor;
+        }
+        Ok(history)
+    }
+
+    /// One page of a flag's history: up to `limit` versions (the server's
+    /// default when None) older than `cursor`, a previous page's `next_cursor`
+    pub async fn flag_history_page(
+        &self,
+        project_id: &str,
+        key: &str,
+        environment: &str,
+        limit: Option<u32>,
+        cursor: Option<&str>,
+    ) -> Result<FlagHistory, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/flags/{}/history",
+            self.base_url, project_id, key
+        );
+        let auth = self.auth_header()?;
+
+        let mut query = vec![("environment", environment.to_string())];
+        if let Some(limit) = limit {
+            query.push(("limit", limit.to_string()));
+        }
+        if let Some(cursor) = cursor {
+            query.push(("cursor", cursor.to_string()));
+        }
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .query(&query)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(history_not_found(key, environment, &body));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Every version of a flag's value in one environment as NDJSON, newest
+    /// first, read a chunk at a time so the history is never held in memory
+    pub async fn export_flag_history(
+        &self,
+        project_id: &str,
+        key: &str,
+        environment: &str,
+    ) -> Result<HistoryExport, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/flags/{}/history",
+            self.base_url, project_id, key
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .header("Accept", "application/x-ndjson")
+            .query(&[("environment", environment)])
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp
+                .text()
+                .await
+                .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+            if status == StatusCode::NOT_FOUND {
+                return Err(history_not_found(key, environment, &body));
+            }
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        Ok(HistoryExport { resp })
+    }
+
+    /// Restore a flag's value in one environment to an earlier version
+    pub async fn rollback_flag(
+        &self,
+        project_id: &str,
+        key: &str,
+        req: &RollbackFlagRequest,
+    ) -> Result<RollbackFlagResponse, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/flags/{}/rollback",
+            self.base_url, project_id, key
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            let code = serde_json::from_str::<ApiErrorResponse>(&body)
+                .ok()
+                .and_then(|err| err.code);
+            if code.as_deref() == Some("flag_not_found") {
+                return Err(flag_not_found(key, &body));
+            }
+        }
+
+        if status == StatusCode::PRECONDITION_REQUIRED {
+            return Err(precondition_required(&req.environment, &body));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    // === Change requests ===
+
+    /// Propose a flag change; nothing changes until it is approved
+    pub async fn create_change_request(
+        &self,
+        project_id: &str,
+        req: &CreateChangeRequest,
+    ) -> Result<ChangeRequest, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/change-requests",
+            self.base_url, project_id
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            let err = serde_json::from_str::<ApiErrorResponse>(&body).ok();
+            return Err(match err.and_then(|err| err.code) {
+                Some(code) if code == "flag_not_found" => flag_not_found(&req.flag_key, &body),
+                _ => FlagLiteError::EnvironmentNotFound(req.environment.clone()),
+            });
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// A project's change requests, newest first, optionally only those with `status`
+    pub async fn list_change_requests(
+        &self,
+        project_id: &str,
+        status: Option<&str>,
+    ) -> Result<Vec<ChangeRequest>, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/change-requests",
+            self.base_url, project_id
+        );
+        let auth = self.auth_header()?;
+
+        let mut request = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth);
+        if let Some(status) = status {
+            request = request.query(&[("status", status)]);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::ProjectNotFound(project_id.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Approve a change request, applying it, or reject it
+    pub async fn review_change_request(
+        &self,
+        id: &str,
+        approve: bool,
+        comment: Option<&str>,
+    ) -> Result<ChangeRequest, FlagLiteError> {
+        let action = if approve { "approve" } else { "reject" };
+        let url = format!("{}/v1/change-requests/{}/{}", self.base_url, id, action);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(&serde_json::json!({ "comment": comment }))
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    // === Ramps ===
+
+    /// Start raising a flag's rollout step by step; the first step is taken right away
+    pub async fn create_ramp(
+        &self,
+        project_id: &str,
+        key: &str,
+        req: &CreateRampRequest,
+    ) -> Result<Ramp, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/flags/{}/ramps",
+            self.base_url, project_id, key
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            let err = serde_json::from_str::<ApiErrorResponse>(&body).ok();
+            return Err(match err.and_then(|err| err.code) {
+                Some(code) if code == "flag_not_found" => flag_not_found(key, &body),
+                _ => FlagLiteError::EnvironmentNotFound(req.environment.clone()),
+            });
+        }
+
+        if status == StatusCode::PRECONDITION_REQUIRED {
+            return Err(precondition_required(&req.environment, &body));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// A project's ramps, newest first, optionally only those with `status`
+    pub async fn list_ramps(
+        &self,
+        project_id: &str,
+        status: Option<&str>,
+    ) -> Result<Vec<Ramp>, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/ramps", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let mut request = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth);
+        if let Some(status) = status {
+            request = request.query(&[("status", status)]);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::ProjectNotFound(project_id.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// A ramp and how far it has got
+    pub async fn get_ramp(&self, id: &str) -> Result<Ramp, FlagLiteError> {
+        let url = format!("{}/v1/ramps/{}", self.base_url, id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Pause, resume or abort a ramp
+    pub async fn control_ramp(&self, id: &str, action: RampAction) -> Result<Ramp, FlagLiteError> {
+        let url = format!("{}/v1/ramps/{}/{}", self.base_url, id, action.as_str());
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    // === Guards ===
+
+    /// Revert a flag's latest change in one environment if a health check or
+    /// a pushed report says it's unhealthy
+    pub async fn create_guard(
+        &self,
+        project_id: &str,
+        key: &str,
+        req: &CreateGuardRequest,
+    ) -> Result<Guard, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/flags/{}/guards",
+            self.base_url, project_id, key
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            let err = serde_json::from_str::<ApiErrorResponse>(&body).ok();
+            return Err(match err.and_then(|err| err.code) {
+                Some(code) if code == "flag_not_found" => flag_not_found(key, &body),
+                _ => FlagLiteError::EnvironmentNotFound(req.environment.clone()),
+            });
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// A project's guards, newest first, optionally only those with `status`
+    pub async fn list_guards(
+        &self,
+        project_id: &str,
+        status: Option<&str>,
+    ) -> Result<Vec<Guard>, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/guards", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let mut request = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth);
+        if let Some(status) = status {
+            request = request.query(&[("status", status)]);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::ProjectNotFound(project_id.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Stop a guard watching, leaving the flag as it is
+    pub async fn cancel_guard(&self, id: &str) -> Result<Guard, FlagLiteError> {
+        let url = format!("{}/v1/guards/{}/cancel", self.base_url, id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Push a health report to a guard; an unhealthy one trips it. Needs
+    /// only the guard's token, no credentials.
+    pub async fn report_to_guard(&self, report: &GuardReport) -> Result<Guard, FlagLiteError> {
+        let url = format!("{}/v1/guards/report", self.base_url);
+
+        let resp = self
+            .request(Method::POST, &url)
+            .json(report)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    // === Project sharing ===
+
+    /// Create a link that shares the project; its token is only returned here
+    pub async fn create_invite_link(
+        &self,
+        project_id: &str,
+        req: &CreateInviteLinkRequest,
+    ) -> Result<InviteLink, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/invite-links", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .json(req)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::ProjectNotFound(project_id.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// A project's invite links, newest first
+    pub async fn list_invite_links(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<InviteLink>, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/invite-links", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::ProjectNotFound(project_id.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Stop an invite link working; users who joined with it keep access
+    pub async fn revoke_invite_link(
+        &self,
+        project_id: &str,
+        id: &str,
+    ) -> Result<InviteLink, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/invite-links/{}",
+            self.base_url, project_id, id
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::DELETE, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Users a project was shared with by invite link
+    pub async fn list_project_members(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<ProjectMember>, FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/members", self.base_url, project_id);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::GET, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if status == StatusCode::NOT_FOUND {
+            return Err(FlagLiteError::ProjectNotFound(project_id.to_string()));
+        }
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Take back the access an invite link gave a user
+    pub async fn remove_project_member(
+        &self,
+        project_id: &str,
+        user_id: &str,
+    ) -> Result<ProjectMember, FlagLiteError> {
+        let url = format!(
+            "{}/v1/projects/{}/members/{}",
+            self.base_url, project_id, user_id
+        );
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::DELETE, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Redeem an invite link's token for access to its project
+    pub async fn join_project(&self, token: &str) -> Result<JoinResponse, FlagLiteError> {
+        let url = format!("{}/v1/join/{}", self.base_url, token);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::POST, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        if !status.is_success() {
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        serde_json::from_str(&body).map_err(|e| FlagLiteError::InvalidResponse(e.to_string()))
+    }
+
+    /// Delete a flag
+    pub async fn delete_flag(&self, project_id: &str, key: &str) -> Result<(), FlagLiteError> {
+        let url = format!("{}/v1/projects/{}/flags/{}", self.base_url, project_id, key);
+        let auth = self.auth_header()?;
+
+        let resp = self
+            .request(Method::DELETE, &url)
+            .header("Authorization", auth)
+            .send()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+
+        let status = resp.status();
+
+        if !status.is_success() {
+            let body = resp
+                .text()
+                .await
+                .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+            if status == StatusCode::NOT_FOUND {
+                return Err(flag_not_found(key, &body));
+            }
+            return Err(self.handle_error(status, &body).await);
+        }
+
+        Ok(())
+    }
+}
+
+/// A 428 for `environment`: either it's protected and the change wasn't
+/// confirmed, or the project wants a reason for the change
+fn precondition_required(environment: &str, body: &str) -> FlagLiteError {
+    let code = serde_json::from_str::<ApiErrorResponse>(body)
+        .ok()
+        .and_then(|err| err.code);
+    match code.as_deref() {
+        Some("reason_required") => FlagLiteError::ReasonRequired(environment.to_string()),
+        _ => FlagLiteError::ProtectedEnvironment(environment.to_string()),
+    }
+}
+
+/// A 404 for `key`, with the server's suggestions of similar keys
+/// A streamed flag history export, from [`FlagLiteClient::export_flag_history`]
+pub struct HistoryExport {
+    resp: reqwest::Response,
+}
+
+impl HistoryExport {
+    /// The next chunk of NDJSON; None once the export is complete. Chunks
+    /// may split a line.
+    pub async fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, FlagLiteError> {
+        let chunk = self
+            .resp
+            .chunk()
+            .await
+            .map_err(|e| FlagLiteError::NetworkError(e.to_string()))?;
+        Ok(chunk.map(|chunk| chunk.to_vec()))
+    }
+}
+
+/// A 404 from the history endpoint: the flag, or else the environment
+fn history_not_found(key: &str, environment: &str, body: &str) -> FlagLiteError {
+    let code = serde_json::from_str::<ApiErrorResponse>(body)
+        .ok()
+        .and_then(|err| err.code);
+    match code.as_deref() {
+        Some("flag_not_found") => flag_not_found(key, body),
+        _ => FlagLiteError::EnvironmentNotFound(environment.to_string()),
+    }
+}
+
+fn flag_not_found(key: &str, body: &str) -> FlagLiteError {
+    let suggestions = serde_json::from_str::<ApiErrorResponse>(body)
+        .ok()
+        .and_then(|err| err.details)
+        .and_then(|details| serde_json::from_value(details["suggestions"].clone()).ok())
+        .unwrap_or_default();
+    FlagLiteError::FlagNotFound {
+        key: key.to_string(),
+        suggestions,
     }
 }
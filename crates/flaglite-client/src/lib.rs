@@ -1,10 +1,61 @@
 //! FlagLite HTTP Client
 //!
 //! This crate provides an HTTP client for interacting with the FlagLite API.
+//!
+//! The `mock` feature adds [`mock::MockServer`], a local stand-in for the API
+//! to unit test code that uses the client.
+//!
+//! The `blocking` feature adds [`blocking::FlagLiteClient`], which mirrors the
+//! client's flag checks as plain blocking calls for synchronous programs.
+//!
+//! The `otel` feature sends the current `tracing` span's OpenTelemetry context
+//! with every request as a W3C `traceparent` header, so calls to FlagLite show
+//! up in the caller's distributed traces. Install a propagator with
+//! `opentelemetry::global::set_text_map_propagator` for headers to be sent.
+//!
+//! [`FlagLiteClient::evaluate`] honours local overrides from
+//! `FLAGLITE_OVERRIDE_<FLAG_KEY>` variables and `FLAGLITE_OVERRIDES_FILE`; see
+//! [`overrides`].
+//!
+//! [`FlagLiteClient::sync_changes`] keeps a [`FlagStore`] of an environment's
+//! flags current by fetching only what changed since the previous call, for
+//! applications that poll many flags. The `poller` feature adds
+//! [`poller::Poller`], which does that polling in the background at the
+//! interval the server asks for, with jitter, backoff on errors, and hooks to
+//! observe sync health.
+//!
+//! Flags can be defined in code with [`define_flags!`], which declares a
+//! struct of typed flags with their defaults.
+//! [`FlagLiteClient::register_flags`] creates the ones the project is missing,
+//! and [`FlagLiteClient::evaluate_flags`] fills the struct, with the defaults
+//! for flags that are off or not created yet.
+//!
+//! [`FlagLiteClientBuilder::on_request`] reports every request (method, URL,
+//! status, duration, redacted credentials) to a callback, for debug logging.
+//! It also tunes connection reuse ([`FlagLiteClientBuilder::pool_idle_timeout`],
+//! [`FlagLiteClientBuilder::tcp_keepalive`]) and pins hostnames to addresses
+//! with [`FlagLiteClientBuilder::resolve`], for split-DNS and air-gapped
+//! deployments.
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod builder;
 mod client;
+#[cfg(feature = "mock")]
+pub mod mock;
+#[cfg(feature = "otel")]
+mod otel;
+pub mod overrides;
+#[cfg(feature = "poller")]
+pub mod poller;
+mod request;
+pub mod store;
 
-pub use client::FlagLiteClient;
+pub use builder::FlagLiteClientBuilder;
+pub use client::{FlagLiteClient, HistoryExport};
+pub use overrides::{Override, Overrides};
+pub use request::{RequestHook, RequestLog};
+pub use store::FlagStore;
 
 // Re-export core types for convenience
 pub use flaglite_core::*;
@@ -0,0 +1,420 @@
+//! In-process stand-in for the FlagLite API, for unit tests of code that uses
+//! [`FlagLiteClient`]
+//!
+//! Enabled with the `mock` feature. A [`MockServer`] listens on a local port
+//! and answers each request with the response registered for its method and
+//! path, so the real client (auth headers, error mapping, JSON decoding) runs
+//! unchanged without a FlagLite server.
+//!
+//! ```no_run
+//! use flaglite_client::mock::MockServer;
+//! use serde_json::json;
+//!
+//! # async fn example() {
+//! let server = MockServer::start().await;
+//! server.mock("DELETE", "/v1/projects/:project_id/flags/:key", 204, json!(null));
+//! server.mock_error("GET", "/v1/projects/:project_id/flags/:key", 404, "Flag not found");
+//!
+//! let client = server.client();
+//! client.delete_flag("p1", "old-banner").await.unwrap();
+//! assert!(client.get_flag("p1", "old-banner", None).await.is_err());
+//! assert_eq!(server.requests().len(), 2);
+//! # }
+//! ```
+
+use reqwest::StatusCode;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::{FlagLiteClient, Overrides};
+
+/// Token the client from [`MockServer::client`] sends
+pub const MOCK_TOKEN: &str = "mock-token";
+
+/// A request the mock server received
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    pub query: Option<String>,
+    pub authorization: Option<String>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    /// The body parsed as JSON, or `Null` if it is empty or not JSON
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).unwrap_or_default()
+    }
+}
+
+struct MockResponse {
+    method: String,
+    /// Route-style pattern; `:name` segments match any one segment
+    path: String,
+    status: u16,
+    /// None for an empty body
+    body: Option<String>,
+    headers: Vec<(String, String)>,
+}
+
+#[derive(Default)]
+struct State {
+    responses: Vec<MockResponse>,
+    requests: Vec<RecordedRequest>,
+}
+
+/// A local HTTP server that replays registered responses
+///
+/// Requests with no registered response get a 404. The server stops when
+/// dropped.
+pub struct MockServer {
+    url: String,
+    state: Arc<Mutex<State>>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Start listening on a free local port
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("Failed to bind mock server");
+        let addr = listener
+            .local_addr()
+            .expect("Failed to read mock server address");
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let task = tokio::spawn({
+            let state = state.clone();
+            async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle_connection(stream, state.clone()));
+                }
+            }
+        });
+
+        Self {
+            url: format!("http://{addr}"),
+            state,
+            task,
+        }
+    }
+
+    /// Base URL to point a client at
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// A client for this server, authenticated with [`MOCK_TOKEN`]
+    ///
+    /// Flag overrides in the environment are ignored, so tests don't depend
+    /// on the shell they run in; set them with [`FlagLiteClient::with_overrides`].
+    pub fn client(&self) -> FlagLiteClient {
+        FlagLiteClient::new(&self.url)
+            .with_token(MOCK_TOKEN)
+            .with_overrides(Overrides::default())
+    }
+
+    /// Answer `method path` with `status` and `body` as JSON
+    ///
+    /// `path` may use `:name` segments as in the API docs. A `null` body is
+    /// sent as an empty response. Later registrations for the same request win.
+    pub fn mock(&self, method: &str, path: &str, status: u16, body: impl Serialize) {
+        self.mock_with_headers(method, path, status, body, &[]);
+    }
+
+    /// Like [`Self::mock`], with extra response headers such as `Retry-After`
+    pub fn mock_with_headers(
+        &self,
+        method: &str,
+        path: &str,
+        status: u16,
+        body: impl Serialize,
+        headers: &[(&str, &str)],
+    ) {
+        let body = serde_json::to_value(body).expect("Mock body must serialize to JSON");
+        self.lock().responses.push(MockResponse {
+            method: method.to_uppercase(),
+            path: path.to_string(),
+            status,
+            body: (!body.is_null()).then(|| body.to_string()),
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+        });
+    }
+
+    /// Answer `method path` with an API error response
+    pub fn mock_error(&self, method: &str, path: &str, status: u16, message: &str) {
+        self.mock(
+            method,
+            path,
+            status,
+            serde_json::json!({ "error": message }),
+        );
+    }
+
+    /// Every request received so far, oldest first
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.lock().requests.clone()
+    }
+
+    /// Forget registered responses and recorded requests
+    pub fn reset(&self) {
+        *self.lock() = State::default();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Serve one request, then close the connection
+async fn handle_connection(mut stream: TcpStream, state: Arc<Mutex<State>>) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+
+    let (status, body, headers) = {
+        let mut state = state.lock().unwrap_or_else(|e| e.into_inner());
+        let found = state
+            .responses
+            .iter()
+            .rev()
+            .find(|r| r.method == request.method && path_matches(&r.path, &request.path))
+            .map(|r| (r.status, r.body.clone(), r.headers.clone()));
+        state.requests.push(request.clone());
+        found.unwrap_or_else(|| {
+            let message = format!("No mock for {} {}", request.method, request.path);
+            (
+                404,
+                Some(serde_json::json!({ "error": message }).to_string()),
+                Vec::new(),
+            )
+        })
+    };
+
+    let body = body.unwrap_or_default();
+    let reason = StatusCode::from_u16(status)
+        .ok()
+        .and_then(|s| s.canonical_reason())
+        .unwrap_or("Unknown");
+    let headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}: {value}\r\n"))
+        .collect();
+    let response = format!(
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n{headers}Connection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Read one HTTP/1.1 request; None if the connection closed early or it is malformed
+async fn read_request(stream: &mut TcpStream) -> Option<RecordedRequest> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let header_end = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut lines = head.lines();
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let target = request_line.next()?;
+
+    let mut content_length = 0;
+    let mut authorization = None;
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse().ok()?;
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.to_string());
+            }
+        }
+    }
+
+    let mut body = buf[header_end..].to_vec();
+    while body.len() < content_length {
+        let n = stream.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        body.extend_from_slice(&chunk[..n]);
+    }
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())),
+        None => (target.to_string(), None),
+    };
+
+    Some(RecordedRequest {
+        method,
+        path,
+        query,
+        authorization,
+        body: String::from_utf8_lossy(&body).to_string(),
+    })
+}
+
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.trim_end_matches('/').split('/').collect();
+    let path: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+    pattern.len() == path.len()
+        && pattern
+            .iter()
+            .zip(&path)
+            .all(|(p, s)| p.starts_with(':') || p == s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FlagLiteError;
+    use serde_json::json;
+
+    #[test]
+    fn test_path_matches() {
+        assert!(path_matches(
+            "/v1/projects/:id/flags",
+            "/v1/projects/p1/flags"
+        ));
+        assert!(path_matches("/v1/auth/me", "/v1/auth/me/"));
+        assert!(!path_matches(
+            "/v1/projects/:id/flags",
+            "/v1/projects/p1/flags/x"
+        ));
+        assert!(!path_matches("/v1/projects/:id", "/v1/orgs/p1"));
+    }
+
+    #[tokio::test]
+    async fn test_replays_responses_and_records_requests() {
+        let server = MockServer::start().await;
+        server.mock(
+            "GET",
+            "/v1/auth/me",
+            200,
+            json!({
+                "id": "u1",
+                "username": "alice",
+                "created_at": "2026-01-01T00:00:00Z"
+            }),
+        );
+        server.mock_error("DELETE", "/v1/projects/:project_id/flags/:key", 500, "boom");
+
+        let client = server.client();
+        let user = client.whoami().await.unwrap();
+        assert_eq!(user.username, "alice");
+
+        let err = client.delete_flag("p1", "dark-mode").await.unwrap_err();
+        assert!(
+            matches!(err, FlagLiteError::ApiError { status: 500, ref message } if message == "boom"),
+            "{err:?}"
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[0].authorization.as_deref(),
+            Some("Bearer mock-token")
+        );
+        assert_eq!(requests[1].path, "/v1/projects/p1/flags/dark-mode");
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_applies_overrides() {
+        let server = MockServer::start().await;
+        server.mock(
+            "GET",
+            "/v1/flags/:key/evaluate",
+            200,
+            json!({"key": "new-checkout", "enabled": false}),
+        );
+
+        let client = server.client().with_overrides(Overrides::from_vars([
+            (
+                "FLAGLITE_OVERRIDE_NEW_CHECKOUT".to_string(),
+                "true".to_string(),
+            ),
+            (
+                "FLAGLITE_OVERRIDE_UNRELEASED".to_string(),
+                "true".to_string(),
+            ),
+        ]));
+        let result = client.evaluate("new-checkout", Some("u+1")).await.unwrap();
+        assert!(result.enabled && result.overridden);
+        assert_eq!(server.requests()[0].query.as_deref(), Some("user_id=u%2B1"));
+
+        // Served even though the server doesn't know the flag
+        server.reset();
+        assert!(client.evaluate("unreleased", None).await.unwrap().enabled);
+        assert!(client.evaluate("other", None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_evaluate_flags_defaults_missing_flags() {
+        crate::define_flags! {
+            struct Flags {
+                dark_mode: bool = false,
+                max_items: i64 = 10,
+            }
+        }
+
+        let server = MockServer::start().await;
+        server.mock(
+            "GET",
+            "/v1/flags/dark-mode/evaluate",
+            200,
+            json!({"key": "dark-mode", "enabled": true}),
+        );
+        server.mock_error("GET", "/v1/flags/max-items/evaluate", 404, "Flag not found");
+        server.mock(
+            "PUT",
+            "/v1/projects/:project_id/flags",
+            200,
+            json!({"created": ["max-items"], "existing": ["dark-mode"]}),
+        );
+
+        let client = server.client();
+        let flags: Flags = client.evaluate_flags(Some("u1")).await.unwrap();
+        assert_eq!(
+            flags,
+            Flags {
+                dark_mode: true,
+                max_items: 10
+            }
+        );
+
+        let registered = client.register_flags::<Flags>("p1").await.unwrap();
+        assert_eq!(registered.created, ["max-items"]);
+        let body = server.requests()[2].json();
+        assert_eq!(body["flags"][1]["flag_type"], "number");
+        assert_eq!(body["flags"][1]["default_value"], 10);
+
+        server.mock_error("GET", "/v1/flags/:key/evaluate", 500, "boom");
+        assert!(client.evaluate_flags::<Flags>(None).await.is_err());
+    }
+}
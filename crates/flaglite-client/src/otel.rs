@@ -0,0 +1,17 @@
+//! W3C trace context propagation for outgoing requests
+
+use opentelemetry::global;
+use opentelemetry_http::HeaderInjector;
+use reqwest::header::HeaderMap;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// `traceparent`/`tracestate` for the current span, using the globally
+/// installed propagator; empty when none is installed or no span is active
+pub(crate) fn trace_headers() -> HeaderMap {
+    let context = tracing::Span::current().context();
+    let mut headers = HeaderMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&context, &mut HeaderInjector(&mut headers))
+    });
+    headers
+}
@@ -0,0 +1,254 @@
+//! Local flag overrides, to force flags during development without touching
+//! the server
+//!
+//! `FLAGLITE_OVERRIDE_<FLAG_KEY>=true|false` forces one flag. The flag key is
+//! upper-cased with everything but letters and digits turned into `_`, so
+//! `FLAGLITE_OVERRIDE_NEW_CHECKOUT` covers `new-checkout`.
+//!
+//! `FLAGLITE_OVERRIDES_FILE` names a JSON object of flag keys to `true` or
+//! `false`, or to a value to serve with the flag on:
+//!
+//! ```json
+//! {"new-checkout": true, "banner-text": "Free shipping this week"}
+//! ```
+//!
+//! Environment variables win over the file.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use flaglite_core::FlagEvaluation;
+
+/// Prefix of the variables that override one flag each
+pub const OVERRIDE_VAR_PREFIX: &str = "FLAGLITE_OVERRIDE_";
+
+/// Variable naming a JSON file of overrides
+pub const OVERRIDES_FILE_VAR: &str = "FLAGLITE_OVERRIDES_FILE";
+
+/// A forced result for one flag
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Override {
+    /// Flag key as written in the file, or the variable's suffix
+    pub key: String,
+    pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<Value>,
+    /// Variable or file the override came from
+    pub source: String,
+}
+
+/// Overrides that couldn't be read, kept so they can be reported instead of
+/// silently ignored
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InvalidOverride {
+    pub source: String,
+    pub error: String,
+}
+
+/// The set of active overrides, applied by [`FlagLiteClient::evaluate`](crate::FlagLiteClient::evaluate)
+#[derive(Debug, Clone, Default)]
+pub struct Overrides {
+    /// By normalized flag key
+    flags: BTreeMap<String, Override>,
+    invalid: Vec<InvalidOverride>,
+}
+
+impl Overrides {
+    /// Read overrides from this process's environment
+    pub fn from_env() -> Self {
+        Self::from_vars(std::env::vars())
+    }
+
+    /// Read overrides from `(name, value)` pairs, as from [`std::env::vars`]
+    pub fn from_vars(vars: impl IntoIterator<Item = (String, String)>) -> Self {
+        let mut overrides = Self::default();
+        let mut flag_vars = Vec::new();
+
+        for (name, value) in vars {
+            if name == OVERRIDES_FILE_VAR {
+                overrides.load_file(Path::new(&value));
+            } else if let Some(key) = name.strip_prefix(OVERRIDE_VAR_PREFIX) {
+                flag_vars.push((key.to_string(), name.clone(), value));
+            }
+        }
+
+        // After the file, so variables win
+        for (key, name, value) in flag_vars {
+            let enabled = match value.trim().to_ascii_lowercase().as_str() {
+                "true" => true,
+                "false" => false,
+                _ => {
+                    overrides.invalid.push(InvalidOverride {
+                        source: name,
+                        error: format!("expected true or false, got '{value}'"),
+                    });
+                    continue;
+                }
+            };
+            overrides.insert(Override {
+                key,
+                enabled,
+                value: None,
+                source: name,
+            });
+        }
+
+        overrides
+    }
+
+    fn load_file(&mut self, path: &Path) {
+        let source = path.display().to_string();
+        let parsed = std::fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                serde_json::from_str::<BTreeMap<String, Value>>(&contents)
+                    .map_err(|e| format!("expected a JSON object of flag keys: {e}"))
+            });
+
+        let entries = match parsed {
+            Ok(entries) => entries,
+            Err(error) => {
+                self.invalid.push(InvalidOverride { source, error });
+                return;
+            }
+        };
+
+        for (key, value) in entries {
+            let (enabled, value) = match value {
+                Value::Bool(enabled) => (enabled, None),
+                Value::Null => {
+                    self.invalid.push(InvalidOverride {
+                        source: format!("{source} ({key})"),
+                        error: "expected true, false or a value to serve".to_string(),
+                    });
+                    continue;
+                }
+                value => (true, Some(value)),
+            };
+            self.insert(Override {
+                key,
+                enabled,
+                value,
+                source: source.clone(),
+            });
+        }
+    }
+
+    /// Add or replace the override for `over.key`
+    pub fn insert(&mut self, over: Override) {
+        self.flags.insert(normalize(&over.key), over);
+    }
+
+    /// The override for a flag, if any
+    pub fn get(&self, flag_key: &str) -> Option<&Override> {
+        self.flags.get(&normalize(flag_key))
+    }
+
+    /// Active overrides, ordered by flag key
+    pub fn iter(&self) -> impl Iterator<Item = &Override> {
+        self.flags.values()
+    }
+
+    /// Overrides that were set but couldn't be read
+    pub fn invalid(&self) -> &[InvalidOverride] {
+        &self.invalid
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty()
+    }
+
+    /// Replace the server's result for a flag with its override, if any
+    pub fn apply(&self, evaluation: FlagEvaluation) -> FlagEvaluation {
+        match self.get(&evaluation.key) {
+            Some(over) => FlagEvaluation {
+                key: evaluation.key,
+                enabled: over.enabled,
+                value: over.value.clone(),
+                overridden: true,
+            },
+            None => evaluation,
+        }
+    }
+}
+
+/// How a flag key appears in a variable name
+fn normalize(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn evaluation(key: &str, enabled: bool) -> FlagEvaluation {
+        FlagEvaluation {
+            key: key.to_string(),
+            enabled,
+            value: None,
+            overridden: false,
+        }
+    }
+
+    #[test]
+    fn test_env_overrides_match_normalized_keys() {
+        let overrides = Overrides::from_vars(vars(&[
+            ("FLAGLITE_OVERRIDE_NEW_CHECKOUT", "true"),
+            ("FLAGLITE_OVERRIDE_DARK_MODE", "False"),
+            ("FLAGLITE_OVERRIDE_BROKEN", "yes"),
+            ("PATH", "/usr/bin"),
+        ]));
+
+        let result = overrides.apply(evaluation("new-checkout", false));
+        assert!(result.enabled && result.overridden);
+        assert!(!overrides.apply(evaluation("dark.mode", true)).enabled);
+
+        let untouched = overrides.apply(evaluation("other", true));
+        assert!(untouched.enabled && !untouched.overridden);
+
+        assert_eq!(overrides.iter().count(), 2);
+        assert_eq!(overrides.invalid().len(), 1);
+        assert_eq!(overrides.invalid()[0].source, "FLAGLITE_OVERRIDE_BROKEN");
+    }
+
+    #[test]
+    fn test_file_overrides_yield_to_env() {
+        let path =
+            std::env::temp_dir().join(format!("flaglite-overrides-{}.json", std::process::id()));
+        std::fs::write(
+            &path,
+            r#"{"new-checkout": true, "banner-text": "Free shipping", "gone": null}"#,
+        )
+        .unwrap();
+
+        let overrides = Overrides::from_vars(vars(&[
+            ("FLAGLITE_OVERRIDE_NEW_CHECKOUT", "false"),
+            ("FLAGLITE_OVERRIDES_FILE", path.to_str().unwrap()),
+        ]));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(!overrides.get("new-checkout").unwrap().enabled);
+        let banner = overrides.apply(evaluation("banner-text", false));
+        assert!(banner.enabled);
+        assert_eq!(banner.value, Some(Value::from("Free shipping")));
+        assert_eq!(overrides.invalid().len(), 1);
+    }
+}
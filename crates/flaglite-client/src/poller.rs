@@ -0,0 +1,290 @@
+//! Background polling that keeps a [`FlagStore`] current
+//!
+//! Enabled with the `poller` feature. A [`Poller`] calls `GET /v1/changes` on
+//! a timer, waiting as long as the server's `X-Poll-Interval` header asks (or
+//! the configured interval when it sends none). Each wait is spread by a
+//! random jitter so a fleet of instances restarted together doesn't poll in
+//! lockstep, and failed polls back off exponentially up to a limit.
+//!
+//! ```no_run
+//! use flaglite_client::poller::Poller;
+//! use flaglite_client::FlagLiteClient;
+//!
+//! # async fn example() {
+//! let client = FlagLiteClient::new("https://api.flaglite.dev").with_api_key("ffl_env_xxxxx");
+//! let poller = Poller::new(client)
+//!     .on_update(|store, changed| println!("{changed} flag(s) changed, {} total", store.len()))
+//!     .on_error(|err, failures, retry_in| {
+//!         eprintln!("flag sync failed {failures} time(s) in a row: {err}; retrying in {retry_in:?}")
+//!     });
+//! let store = poller.store();
+//! poller.spawn();
+//!
+//! let on = store
+//!     .read()
+//!     .unwrap()
+//!     .get("new-checkout")
+//!     .and_then(|flag| flag.value.as_ref())
+//!     .is_some_and(|value| value.enabled);
+//! # }
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use flaglite_core::FlagLiteError;
+use tokio::task::JoinHandle;
+
+use crate::store::FlagStore;
+use crate::FlagLiteClient;
+
+/// Wait between polls when the server doesn't say
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Longest wait after repeated failures
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Each wait is spread by up to this fraction either way
+const DEFAULT_JITTER: f64 = 0.1;
+
+/// Called after a poll that changed the store, with how many flags changed
+pub type UpdateHook = Box<dyn Fn(&FlagStore, usize) + Send + Sync>;
+
+/// Called after a failed poll, with the consecutive failure count and the
+/// wait before the next attempt
+pub type ErrorHook = Box<dyn Fn(&FlagLiteError, u32, Duration) + Send + Sync>;
+
+/// Polls for flag changes into a shared [`FlagStore`]
+pub struct Poller {
+    client: FlagLiteClient,
+    store: Arc<RwLock<FlagStore>>,
+    interval: Duration,
+    max_backoff: Duration,
+    jitter: f64,
+    /// Last interval the server asked for
+    server_interval: Option<Duration>,
+    /// Failed polls since the last success
+    failures: u32,
+    on_update: Option<UpdateHook>,
+    on_error: Option<ErrorHook>,
+}
+
+impl Poller {
+    pub fn new(client: FlagLiteClient) -> Self {
+        Self {
+            client,
+            store: Arc::default(),
+            interval: DEFAULT_INTERVAL,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            jitter: DEFAULT_JITTER,
+            server_interval: None,
+            failures: 0,
+            on_update: None,
+            on_error: None,
+        }
+    }
+
+    /// Wait between polls until the server sends `X-Poll-Interval` (30s by default)
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Longest wait after repeated failures (5 minutes by default)
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Spread each wait by up to this fraction either way (0.1 by default;
+    /// 0 disables jitter)
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Call `hook` after each poll that added, changed or removed flags
+    pub fn on_update(mut self, hook: impl Fn(&FlagStore, usize) + Send + Sync + 'static) -> Self {
+        self.on_update = Some(Box::new(hook));
+        self
+    }
+
+    /// Call `hook` after each failed poll
+    pub fn on_error(
+        mut self,
+        hook: impl Fn(&FlagLiteError, u32, Duration) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_error = Some(Box::new(hook));
+        self
+    }
+
+    /// The store this poller fills; it is empty until the first poll succeeds
+    pub fn store(&self) -> Arc<RwLock<FlagStore>> {
+        self.store.clone()
+    }
+
+    /// Failed polls since the last success
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Poll once and return how long to wait before the next poll
+    pub async fn poll(&mut self) -> Duration {
+        let revision = self.read().revision();
+        match self.client.flag_changes_with_interval(revision).await {
+            Ok((changes, interval)) => {
+                self.failures = 0;
+                self.server_interval = interval.or(self.server_interval);
+                let mut store = self.store.write().unwrap_or_else(|e| e.into_inner());
+                let changed = store.apply(changes);
+                if changed > 0 {
+                    if let Some(hook) = &self.on_update {
+                        hook(&store, changed);
+                    }
+                }
+                drop(store);
+                jittered(self.base_interval(), self.jitter, random_fraction())
+            }
+            Err(err) => {
+                self.failures = self.failures.saturating_add(1);
+                let mut delay = jittered(
+                    backoff(self.base_interval(), self.failures, self.max_backoff),
+                    self.jitter,
+                    random_fraction(),
+                );
+                if let FlagLiteError::RateLimited { retry_after } = err {
+                    delay = delay.max(Duration::from_secs(retry_after));
+                }
+                if let Some(hook) = &self.on_error {
+                    hook(&err, self.failures, delay);
+                }
+                delay
+            }
+        }
+    }
+
+    /// Poll until the future is dropped
+    pub async fn run(mut self) {
+        loop {
+            let delay = self.poll().await;
+            tokio::time::sleep(delay).await;
+        }
+    }
+
+    /// Poll on a background task; abort the handle to stop
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(self.run())
+    }
+
+    fn base_interval(&self) -> Duration {
+        self.server_interval.unwrap_or(self.interval)
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, FlagStore> {
+        self.store.read().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// `interval` doubled for each failure after the first, capped at `max`
+fn backoff(interval: Duration, failures: u32, max: Duration) -> Duration {
+    let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+    interval.saturating_mul(factor).min(max.max(interval))
+}
+
+/// `delay` moved by up to `jitter` of itself either way; `fraction` in 0..1
+/// picks where in that range
+fn jittered(delay: Duration, jitter: f64, fraction: f64) -> Duration {
+    delay.mul_f64(1.0 - jitter + 2.0 * jitter * fraction)
+}
+
+/// A random number in 0..1, from the randomly seeded std hasher
+fn random_fraction() -> f64 {
+    let bits = RandomState::new().build_hasher().finish();
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_limit() {
+        let second = Duration::from_secs(1);
+        let max = Duration::from_secs(10);
+        assert_eq!(backoff(second, 1, max), second);
+        assert_eq!(backoff(second, 2, max), 2 * second);
+        assert_eq!(backoff(second, 4, max), 8 * second);
+        assert_eq!(backoff(second, 5, max), max);
+        assert_eq!(backoff(second, u32::MAX, max), max);
+        // A server interval longer than the limit is still honoured
+        assert_eq!(backoff(60 * second, 3, max), 60 * second);
+    }
+
+    #[test]
+    fn test_jitter_spreads_both_ways() {
+        let delay = Duration::from_secs(10);
+        assert_eq!(jittered(delay, 0.1, 0.0), Duration::from_secs(9));
+        assert_eq!(jittered(delay, 0.1, 0.5), delay);
+        assert_eq!(jittered(delay, 0.1, 1.0), Duration::from_secs(11));
+        assert_eq!(jittered(delay, 0.0, 0.9), delay);
+        assert!((0..100).all(|_| (0.0..1.0).contains(&random_fraction())));
+    }
+
+    #[cfg(feature = "mock")]
+    #[tokio::test]
+    async fn test_poll_follows_server_interval_and_backs_off() {
+        use crate::mock::MockServer;
+        use serde_json::json;
+        use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+        let server = MockServer::start().await;
+        server.mock_with_headers(
+            "GET",
+            "/v1/changes",
+            200,
+            json!({
+                "project_id": "00000000-0000-0000-0000-000000000001",
+                "environment_id": "00000000-0000-0000-0000-000000000002",
+                "revision": 3,
+                "full": true,
+                "flags": [{
+                    "key": "dark-mode",
+                    "value": {"enabled": true, "rollout_percentage": 100},
+                    "default_enabled": false
+                }]
+            }),
+            &[("X-Poll-Interval", "45")],
+        );
+
+        let updates = Arc::new(AtomicUsize::new(0));
+        let failures = Arc::new(AtomicU32::new(0));
+        let mut poller = Poller::new(server.client())
+            .interval(Duration::from_secs(5))
+            .jitter(0.0)
+            .on_update({
+                let updates = updates.clone();
+                move |_, changed| {
+                    updates.fetch_add(changed, Ordering::SeqCst);
+                }
+            })
+            .on_error({
+                let failures = failures.clone();
+                move |_, count, _| failures.store(count, Ordering::SeqCst)
+            });
+
+        assert_eq!(poller.poll().await, Duration::from_secs(45));
+        assert_eq!(updates.load(Ordering::SeqCst), 1);
+        assert!(poller.store().read().unwrap().get("dark-mode").is_some());
+        assert_eq!(server.requests()[0].query.as_deref(), Some("since=0"));
+
+        server.mock_error("GET", "/v1/changes", 500, "boom");
+        assert_eq!(poller.poll().await, Duration::from_secs(45));
+        assert_eq!(poller.poll().await, Duration::from_secs(90));
+        assert_eq!(failures.load(Ordering::SeqCst), 2);
+        assert_eq!(server.requests()[2].query.as_deref(), Some("since=3"));
+        // The store keeps its flags while the server is failing
+        assert_eq!(poller.store().read().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+//! Outgoing requests and the hook that observes them
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use reqwest::header::AUTHORIZATION;
+use reqwest::{Method, RequestBuilder, Response, StatusCode};
+use serde::Serialize;
+
+/// Characters of a credential kept when it's logged
+const VISIBLE_CREDENTIAL_CHARS: usize = 8;
+
+/// One HTTP exchange, reported to the hook set with
+/// [`FlagLiteClientBuilder::on_request`](crate::FlagLiteClientBuilder::on_request)
+#[derive(Debug, Clone)]
+pub struct RequestLog {
+    pub method: Method,
+    pub url: String,
+    /// `Authorization` header with the credential cut short, e.g. `Bearer flg_a1b2…`
+    pub authorization: Option<String>,
+    /// None if no response arrived
+    pub status: Option<StatusCode>,
+    /// Until the response headers arrived, or the request failed
+    pub duration: Duration,
+    pub error: Option<String>,
+}
+
+/// Called after every request the client sends
+pub type RequestHook = Arc<dyn Fn(&RequestLog) + Send + Sync>;
+
+/// A request being built; reports the exchange to the hook, if any, when sent
+pub(crate) struct Request {
+    builder: RequestBuilder,
+    hook: Option<RequestHook>,
+}
+
+impl Request {
+    pub(crate) fn new(builder: RequestBuilder, hook: Option<RequestHook>) -> Self {
+        Self { builder, hook }
+    }
+
+    pub(crate) fn header(self, name: &'static str, value: impl AsRef<str>) -> Self {
+        Self {
+            builder: self.builder.header(name, value.as_ref()),
+            ..self
+        }
+    }
+
+    pub(crate) fn query<T: Serialize + ?Sized>(self, query: &T) -> Self {
+        Self {
+            builder: self.builder.query(query),
+            ..self
+        }
+    }
+
+    pub(crate) fn json<T: Serialize + ?Sized>(self, body: &T) -> Self {
+        Self {
+            builder: self.builder.json(body),
+            ..self
+        }
+    }
+
+    pub(crate) fn body(self, body: Vec<u8>) -> Self {
+        Self {
+            builder: self.builder.body(body),
+            ..self
+        }
+    }
+
+    pub(crate) async fn send(self) -> reqwest::Result<Response> {
+        let Some(hook) = self.hook else {
+            return self.builder.send().await;
+        };
+
+        let (client, request) = self.builder.build_split();
+        let request = request?;
+        let method = request.method().clone();
+        let url = request.url().to_string();
+        let authorization = request
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .map(redact);
+
+        let started = Instant::now();
+        let result = client.execute(request).await;
+        hook(&RequestLog {
+            method,
+            url,
+            authorization,
+            status: result.as_ref().ok().map(Response::status),
+            duration: started.elapsed(),
+            error: result.as_ref().err().map(ToString::to_string),
+        });
+        result
+    }
+}
+
+/// Keep the scheme and the start of the credential, which is enough to tell
+/// keys apart without leaking them
+fn redact(authorization: &str) -> String {
+    let (scheme, credential) = authorization.split_once(' ').unwrap_or(("", authorization));
+    let visible: String = credential.chars().take(VISIBLE_CREDENTIAL_CHARS).collect();
+    let redacted = if visible.len() < credential.len() {
+        format!("{visible}…")
+    } else {
+        visible
+    };
+
+    if scheme.is_empty() {
+        redacted
+    } else {
+        format!("{scheme} {redacted}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_keeps_scheme_and_prefix() {
+        assert_eq!(redact("Bearer flg_a1b2c3d4e5f6g7h8"), "Bearer flg_a1b2…");
+        assert_eq!(redact("Bearer short"), "Bearer short");
+        assert_eq!(redact("secretsecret"), "secretse…");
+    }
+}
@@ -0,0 +1,137 @@
+//! Local copy of an environment's flags, kept current by
+//! [`FlagLiteClient::sync_changes`](crate::FlagLiteClient::sync_changes)
+//!
+//! The first sync fetches every flag; later ones fetch only the flags changed
+//! since the store's revision, so polling stays cheap with thousands of flags.
+
+use std::collections::HashMap;
+
+use flaglite_core::{FlagChanges, FlagSnapshot};
+
+/// An environment's flags by key, as of `revision`
+#[derive(Debug, Clone, Default)]
+pub struct FlagStore {
+    revision: i64,
+    flags: HashMap<String, FlagSnapshot>,
+}
+
+impl FlagStore {
+    /// An empty store; the first sync fills it
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Project revision the store is current to; 0 before the first sync
+    pub fn revision(&self) -> i64 {
+        self.revision
+    }
+
+    pub fn get(&self, key: &str) -> Option<&FlagSnapshot> {
+        self.flags.get(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &FlagSnapshot> {
+        self.flags.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.flags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.flags.is_empty()
+    }
+
+    /// Apply a response from `GET /v1/changes`. Returns how many flags were
+    /// added, changed or removed.
+    pub fn apply(&mut self, changes: FlagChanges) -> usize {
+        let mut changed = 0;
+
+        if changes.full {
+            let before = self.flags.len();
+            self.flags
+                .retain(|key, _| changes.flags.iter().any(|f| &f.key == key));
+            changed += before - self.flags.len();
+        }
+        for key in &changes.deleted {
+            if self.flags.remove(key).is_some() {
+                changed += 1;
+            }
+        }
+        for flag in changes.flags {
+            if self.flags.get(&flag.key) != Some(&flag) {
+                self.flags.insert(flag.key.clone(), flag);
+                changed += 1;
+            }
+        }
+
+        self.revision = changes.revision;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flaglite_core::FlagSnapshotValue;
+    use serde_json::json;
+
+    fn flag(key: &str, enabled: bool) -> FlagSnapshot {
+        FlagSnapshot {
+            key: key.to_string(),
+            expires_at: None,
+            value: Some(FlagSnapshotValue {
+                enabled,
+                rollout_percentage: 100,
+                value: None,
+                allowlist: Vec::new(),
+                denylist: Vec::new(),
+            }),
+            default_enabled: false,
+            default_value: None,
+        }
+    }
+
+    fn changes(
+        revision: i64,
+        full: bool,
+        flags: Vec<FlagSnapshot>,
+        deleted: &[&str],
+    ) -> FlagChanges {
+        serde_json::from_value(json!({
+            "project_id": "00000000-0000-0000-0000-000000000000",
+            "environment_id": "00000000-0000-0000-0000-000000000000",
+            "revision": revision,
+            "full": full,
+            "flags": flags,
+            "deleted": deleted,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_apply_incremental_and_full_changes() {
+        let mut store = FlagStore::new();
+        let applied = store.apply(changes(
+            3,
+            true,
+            vec![flag("a", true), flag("b", false), flag("c", false)],
+            &[],
+        ));
+        assert_eq!(applied, 3);
+        assert_eq!(store.revision(), 3);
+
+        // Only the changed flag and the deletion are sent
+        let applied = store.apply(changes(5, false, vec![flag("b", true)], &["c"]));
+        assert_eq!(applied, 2);
+        assert!(store.get("b").unwrap().value.as_ref().unwrap().enabled);
+        assert!(store.get("c").is_none());
+        assert_eq!(store.len(), 2);
+
+        // A full response drops flags it doesn't list
+        let applied = store.apply(changes(1, true, vec![flag("a", true)], &[]));
+        assert_eq!(applied, 1);
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.revision(), 1);
+    }
+}
@@ -0,0 +1,331 @@
+//! Flags defined in code
+//!
+//! [`define_flags!`](crate::define_flags) declares a struct with one typed
+//! field per flag, defaulting to the value the code falls back on. The struct
+//! implements [`FlagSet`], which gives the definitions to register with the
+//! server (creating the flags the project is missing) and builds the struct
+//! from evaluations, so call sites read fields instead of passing string keys.
+//!
+//! ```
+//! use flaglite_core::{define_flags, FlagSet};
+//!
+//! define_flags! {
+//!     /// Everything the checkout service reads
+//!     pub struct CheckoutFlags {
+//!         dark_mode: bool = false,
+//!         max_items: i64 = 10,
+//!         /// Keys are field names with `-` for `_`, unless given
+//!         banner as "checkout_banner": String = "Welcome".to_string(),
+//!     }
+//! }
+//!
+//! let keys: Vec<String> = CheckoutFlags::definitions().into_iter().map(|d| d.key).collect();
+//! assert_eq!(keys, ["dark-mode", "max-items", "checkout_banner"]);
+//! assert_eq!(CheckoutFlags::default().max_items, 10);
+//! ```
+
+use std::collections::HashMap;
+
+use crate::{CreateFlagRequest, FlagEvaluation, FlagType};
+
+/// A Rust type a flag can be read as
+pub trait FlagValueType: Sized {
+    const FLAG_TYPE: FlagType;
+
+    fn to_json(&self) -> serde_json::Value;
+
+    fn from_json(value: &serde_json::Value) -> Option<Self>;
+
+    /// The value an evaluation serves, if it is on and of this type
+    fn from_evaluation(evaluation: &FlagEvaluation) -> Option<Self> {
+        if !evaluation.enabled {
+            return None;
+        }
+        evaluation.value.as_ref().and_then(Self::from_json)
+    }
+}
+
+/// Boolean flags are their on/off state
+impl FlagValueType for bool {
+    const FLAG_TYPE: FlagType = FlagType::Boolean;
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::Value::Bool(*self)
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        value.as_bool()
+    }
+
+    fn from_evaluation(evaluation: &FlagEvaluation) -> Option<Self> {
+        Some(evaluation.enabled)
+    }
+}
+
+impl FlagValueType for String {
+    const FLAG_TYPE: FlagType = FlagType::String;
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::Value::String(self.clone())
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        value.as_str().map(str::to_string)
+    }
+}
+
+impl FlagValueType for i64 {
+    const FLAG_TYPE: FlagType = FlagType::Number;
+
+    fn to_json(&self) -> serde_json::Value {
+        (*self).into()
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        value.as_i64()
+    }
+}
+
+impl FlagValueType for f64 {
+    const FLAG_TYPE: FlagType = FlagType::Number;
+
+    fn to_json(&self) -> serde_json::Value {
+        (*self).into()
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        value.as_f64()
+    }
+}
+
+impl FlagValueType for serde_json::Value {
+    const FLAG_TYPE: FlagType = FlagType::Json;
+
+    fn to_json(&self) -> serde_json::Value {
+        self.clone()
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+/// One flag as the code defines it
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlagDefinition {
+    pub key: String,
+    pub flag_type: FlagType,
+    /// What the code uses when the flag is off or missing
+    pub default: serde_json::Value,
+}
+
+impl FlagDefinition {
+    pub fn new<T: FlagValueType>(key: impl Into<String>, default: &T) -> Self {
+        Self {
+            key: key.into(),
+            flag_type: T::FLAG_TYPE,
+            default: default.to_json(),
+        }
+    }
+
+    /// The request that creates the flag serving the code's default everywhere:
+    /// a boolean flag on or off as its default says, any other flag on with the
+    /// default as its value
+    pub fn create_request(&self) -> CreateFlagRequest {
+        let (enabled, default_value) = match self.default {
+            serde_json::Value::Bool(enabled) if self.flag_type == FlagType::Boolean => {
+                (enabled, None)
+            }
+            _ => (true, Some(self.default.clone())),
+        };
+        CreateFlagRequest {
+            key: self.key.clone(),
+            name: self.key.clone(),
+            description: None,
+            flag_type: self.flag_type,
+            enabled,
+            expires_at: None,
+            owner: None,
+            repository: None,
+            code_path: None,
+            json_schema: None,
+            default_enabled: enabled,
+            default_value,
+        }
+    }
+}
+
+/// A struct of typed flags, declared with [`define_flags!`](crate::define_flags)
+pub trait FlagSet: Default {
+    /// Every flag in the set, in declaration order
+    fn definitions() -> Vec<FlagDefinition>;
+
+    /// The set as `evaluations` (by key) serve it; a flag that is off,
+    /// missing, or of another type keeps its default
+    fn from_evaluations(evaluations: &HashMap<String, FlagEvaluation>) -> Self;
+}
+
+/// The flag key for a field without an explicit one
+#[doc(hidden)]
+pub fn field_key(field: &str) -> String {
+    field.replace('_', "-")
+}
+
+/// Declare a struct of typed flags with their defaults; see [`definitions`](crate::definitions)
+///
+/// The struct derives `Debug`, `Clone` and `PartialEq`, and implements
+/// `Default` with the declared values and [`FlagSet`](crate::FlagSet).
+#[macro_export]
+macro_rules! define_flags {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_attr:meta])*
+                $field:ident $(as $key:literal)? : $ty:ty = $default:expr
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, PartialEq)]
+        $vis struct $name {
+            $(
+                $(#[$field_attr])*
+                pub $field: $ty,
+            )*
+        }
+
+        impl ::std::default::Default for $name {
+            fn default() -> Self {
+                Self {
+                    $($field: $default,)*
+                }
+            }
+        }
+
+        impl $crate::FlagSet for $name {
+            fn definitions() -> ::std::vec::Vec<$crate::FlagDefinition> {
+                let defaults = <Self as ::std::default::Default>::default();
+                ::std::vec![
+                    $(
+                        $crate::FlagDefinition::new(
+                            $crate::define_flags!(@key $field $($key)?),
+                            &defaults.$field,
+                        ),
+                    )*
+                ]
+            }
+
+            fn from_evaluations(
+                evaluations: &::std::collections::HashMap<
+                    ::std::string::String,
+                    $crate::FlagEvaluation,
+                >,
+            ) -> Self {
+                let defaults = <Self as ::std::default::Default>::default();
+                Self {
+                    $(
+                        $field: evaluations
+                            .get(&$crate::define_flags!(@key $field $($key)?))
+                            .and_then(<$ty as $crate::FlagValueType>::from_evaluation)
+                            .unwrap_or(defaults.$field),
+                    )*
+                }
+            }
+        }
+    };
+    (@key $field:ident) => {
+        $crate::definitions::field_key(stringify!($field))
+    };
+    (@key $field:ident $key:literal) => {
+        ::std::string::String::from($key)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    define_flags! {
+        struct TestFlags {
+            dark_mode: bool = false,
+            beta as "beta_access": bool = true,
+            max_items: i64 = 10,
+            ratio: f64 = 0.5,
+            banner: String = "Welcome".to_string(),
+            layout: serde_json::Value = json!({"columns": 2}),
+        }
+    }
+
+    fn evaluation(key: &str, enabled: bool, value: Option<serde_json::Value>) -> FlagEvaluation {
+        FlagEvaluation {
+            key: key.to_string(),
+            enabled,
+            value,
+            overridden: false,
+        }
+    }
+
+    #[test]
+    fn test_definitions_register_the_code_defaults() {
+        let definitions = TestFlags::definitions();
+        let keys: Vec<&str> = definitions.iter().map(|d| d.key.as_str()).collect();
+        assert_eq!(
+            keys,
+            [
+                "dark-mode",
+                "beta_access",
+                "max-items",
+                "ratio",
+                "banner",
+                "layout"
+            ]
+        );
+
+        let requests: Vec<CreateFlagRequest> = definitions
+            .iter()
+            .map(FlagDefinition::create_request)
+            .collect();
+        assert_eq!(requests[0].flag_type, FlagType::Boolean);
+        assert!(!requests[0].enabled && requests[0].default_value.is_none());
+        assert!(requests[1].enabled && requests[1].default_enabled);
+        assert_eq!(requests[2].flag_type, FlagType::Number);
+        assert!(requests[2].enabled);
+        assert_eq!(requests[2].default_value, Some(json!(10)));
+        assert_eq!(requests[4].default_value, Some(json!("Welcome")));
+        assert_eq!(requests[5].flag_type, FlagType::Json);
+        assert!(requests.iter().all(|r| r.validate().is_ok()));
+    }
+
+    #[test]
+    fn test_from_evaluations_falls_back_to_defaults() {
+        let evaluations: HashMap<String, FlagEvaluation> = [
+            evaluation("dark-mode", true, None),
+            evaluation("beta_access", false, None),
+            evaluation("max-items", true, Some(json!(25))),
+            // Off: the code's default, whatever the value
+            evaluation("ratio", false, Some(json!(0.9))),
+            // The wrong type is ignored
+            evaluation("banner", true, Some(json!(42))),
+        ]
+        .into_iter()
+        .map(|e| (e.key.clone(), e))
+        .collect();
+
+        let flags = TestFlags::from_evaluations(&evaluations);
+        assert_eq!(
+            flags,
+            TestFlags {
+                dark_mode: true,
+                beta: false,
+                max_items: 25,
+                ..TestFlags::default()
+            }
+        );
+        assert_eq!(
+            TestFlags::from_evaluations(&HashMap::new()),
+            TestFlags::default()
+        );
+    }
+}
@@ -2,6 +2,8 @@
 
 use thiserror::Error;
 
+use crate::FieldError;
+
 #[derive(Error, Debug)]
 pub enum FlagLiteError {
     #[error("Authentication required. Run 'flaglite login' first.")]
@@ -13,12 +15,34 @@ pub enum FlagLiteError {
     #[error("Project not found: {0}")]
     ProjectNotFound(String),
 
-    #[error("Flag not found: {0}")]
-    FlagNotFound(String),
+    /// `suggestions` are keys in the project close to `key`
+    #[error("Flag not found: {key}{}", did_you_mean(.suggestions))]
+    FlagNotFound {
+        key: String,
+        suggestions: Vec<String>,
+    },
 
     #[error("Environment not found: {0}")]
     EnvironmentNotFound(String),
 
+    #[error(
+        "Environment '{0}' is protected. Re-run with --confirm-production to apply the change."
+    )]
+    ProtectedEnvironment(String),
+
+    /// The project requires a reason for changes to this protected environment
+    #[error("Changes to environment '{0}' need a reason in this project. Re-run with --reason.")]
+    ReasonRequired(String),
+
+    #[error(
+        "Environment '{0}' is frozen. Run 'flaglite envs unfreeze {0}' once the freeze is over."
+    )]
+    EnvironmentFrozen(String),
+
+    /// Someone else changed the resource since it was read (HTTP 409)
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("No project selected. Run 'flaglite projects use <id>' first.")]
     NoProjectSelected,
 
@@ -33,4 +57,26 @@ pub enum FlagLiteError {
 
     #[error("Rate limited. Please try again in {retry_after} seconds.")]
     RateLimited { retry_after: u64 },
+
+    #[error("{0}")]
+    ValidationError(String),
+
+    #[error("Validation failed: {}", list_fields(.0))]
+    InvalidFields(Vec<FieldError>),
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!(". Did you mean: {}?", suggestions.join(", "))
+    }
+}
+
+fn list_fields(fields: &[FieldError]) -> String {
+    fields
+        .iter()
+        .map(|f| format!("{}: {}", f.field, f.message))
+        .collect::<Vec<_>>()
+        .join("; ")
 }
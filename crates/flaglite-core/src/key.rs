@@ -0,0 +1,148 @@
+//! Flag key rules
+//!
+//! The server, the client and the CLI all check keys with [`FlagKey::parse`],
+//! so a bad key is rejected locally with the same message the server would
+//! send back.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::{FieldError, FlagLiteError};
+
+pub const MAX_FLAG_KEY_LENGTH: usize = 255;
+
+/// Why a string isn't a valid flag key
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlagKeyError {
+    #[error("Flag key cannot be empty")]
+    Empty,
+
+    #[error("Flag key must be at most {MAX_FLAG_KEY_LENGTH} characters")]
+    TooLong,
+
+    #[error("Flag key can only contain alphanumeric characters, hyphens, and underscores")]
+    InvalidCharacter,
+}
+
+impl FlagKeyError {
+    /// Constraint name used in 422 `details` (`required`, `max_length`, `charset`)
+    pub fn constraint(self) -> &'static str {
+        match self {
+            FlagKeyError::Empty => "required",
+            FlagKeyError::TooLong => "max_length",
+            FlagKeyError::InvalidCharacter => "charset",
+        }
+    }
+
+    /// The error as the server reports it for `field`
+    pub fn field_error(self, field: &str) -> FieldError {
+        FieldError {
+            field: field.to_string(),
+            constraint: self.constraint().to_string(),
+            message: self.to_string(),
+        }
+    }
+}
+
+/// Reported like the server's 422 for the `key` field
+impl From<FlagKeyError> for FlagLiteError {
+    fn from(e: FlagKeyError) -> Self {
+        FlagLiteError::InvalidFields(vec![e.field_error("key")])
+    }
+}
+
+/// A flag key that satisfies the key rules
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct FlagKey(String);
+
+impl FlagKey {
+    pub fn parse(key: impl Into<String>) -> Result<Self, FlagKeyError> {
+        let key = key.into();
+        if key.is_empty() {
+            return Err(FlagKeyError::Empty);
+        }
+        if key.chars().count() > MAX_FLAG_KEY_LENGTH {
+            return Err(FlagKeyError::TooLong);
+        }
+        if !key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(FlagKeyError::InvalidCharacter);
+        }
+        Ok(FlagKey(key))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl FromStr for FlagKey {
+    type Err = FlagKeyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        FlagKey::parse(s)
+    }
+}
+
+impl TryFrom<String> for FlagKey {
+    type Error = FlagKeyError;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        FlagKey::parse(s)
+    }
+}
+
+impl From<FlagKey> for String {
+    fn from(key: FlagKey) -> Self {
+        key.0
+    }
+}
+
+impl AsRef<str> for FlagKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for FlagKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            FlagKey::parse("new-checkout_2").unwrap().as_str(),
+            "new-checkout_2"
+        );
+        assert_eq!(FlagKey::parse(""), Err(FlagKeyError::Empty));
+        assert_eq!(
+            FlagKey::parse("bad key!"),
+            Err(FlagKeyError::InvalidCharacter)
+        );
+        assert_eq!(
+            FlagKey::parse("x".repeat(MAX_FLAG_KEY_LENGTH + 1)),
+            Err(FlagKeyError::TooLong)
+        );
+    }
+
+    #[test]
+    fn test_deserialize_validates() {
+        assert!(serde_json::from_str::<FlagKey>(r#""dark-mode""#).is_ok());
+        assert!(serde_json::from_str::<FlagKey>(r#""dark mode""#).is_err());
+    }
+}
@@ -2,8 +2,12 @@
 //!
 //! This crate provides common types used by both the CLI and API server.
 
+pub mod definitions;
 pub mod error;
+pub mod key;
 pub mod types;
 
+pub use definitions::{FlagDefinition, FlagSet, FlagValueType};
 pub use error::FlagLiteError;
+pub use key::{FlagKey, FlagKeyError, MAX_FLAG_KEY_LENGTH};
 pub use types::*;
@@ -1,9 +1,12 @@
 //! Shared types for FlagLite
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use uuid::Uuid;
 
+use crate::{FlagKey, FlagLiteError};
+
 /// User information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
@@ -11,6 +14,8 @@ pub struct User {
     pub username: String,
     #[serde(default)]
     pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: bool,
     pub created_at: DateTime<Utc>,
 }
 
@@ -22,6 +27,12 @@ pub struct Project {
     #[serde(default)]
     pub description: Option<String>,
     pub slug: String,
+    /// Organization that owns the project
+    #[serde(default)]
+    pub org_id: Option<String>,
+    /// Changes to protected environments must give a reason
+    #[serde(default)]
+    pub require_change_reason: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -32,6 +43,24 @@ pub struct CreateProjectRequest {
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    /// Defaults to the caller's personal organization
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub org_id: Option<String>,
+}
+
+/// Request to update a project's settings; omitted fields are unchanged
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateProjectRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub require_change_reason: Option<bool>,
+}
+
+/// Request to clone a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CloneProjectRequest {
+    pub name: String,
+    #[serde(default)]
+    pub include_values: bool,
 }
 
 /// Environment within a project
@@ -43,9 +72,122 @@ pub struct Environment {
     pub project_id: Uuid,
     #[serde(default)]
     pub is_production: bool,
+    /// Changes to protected environments must be explicitly confirmed
+    #[serde(default)]
+    pub protected: bool,
+    /// Flag changes in frozen environments are rejected until unfrozen
+    #[serde(default)]
+    pub frozen: bool,
+    /// Lists show environments by ascending sort order
+    #[serde(default)]
+    pub sort_order: i32,
+    /// Display color, `#rrggbb`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    /// Seconds CDNs and proxies may cache SDK responses; None for the defaults
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_max_age: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_stale_while_revalidate: Option<i32>,
+    /// SDK key for this environment (`ffl_env_`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Set while the environment is public: its public flags can be
+    /// evaluated without a key under `/v1/public/<id>/flags`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_client_id: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
+/// Request to add an environment to a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateEnvironmentRequest {
+    /// Lowercase letters, digits and hyphens
+    pub name: String,
+    /// Environment whose flag values the new one starts with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub copy_from: Option<String>,
+}
+
+/// Request to update an environment's settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateEnvironmentRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protected: Option<bool>,
+    /// `#rrggbb`; empty clears it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Empty clears it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    /// Seconds SDK responses may be cached; 0 turns caching off
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_max_age: Option<i32>,
+    /// Seconds past the max age a cache may serve a response while refetching
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_stale_while_revalidate: Option<i32>,
+    /// Serve public flags without a key; turning it off revokes the client ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public: Option<bool>,
+}
+
+/// Request to set the order environments are listed in
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReorderEnvironmentsRequest {
+    /// Environment names, first to last; ones left out follow in their current order
+    pub order: Vec<String>,
+}
+
+/// A deleted environment that can still be restored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletedEnvironment {
+    pub id: String,
+    pub name: String,
+    /// Flags that had a value in the environment when it was deleted
+    #[serde(default)]
+    pub flag_values: usize,
+    /// Users on its allowlists and denylists
+    #[serde(default)]
+    pub listed_users: usize,
+    /// Username of whoever deleted it
+    pub deleted_by: String,
+    pub deleted_at: DateTime<Utc>,
+    /// When it can no longer be restored
+    pub purge_after: DateTime<Utc>,
+}
+
+/// Public key that verifies an environment's signed flag snapshots (a JWK)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningKey {
+    pub kid: String,
+    pub kty: String,
+    pub crv: String,
+    pub alg: String,
+    /// Base64url Ed25519 public key
+    pub x: String,
+}
+
+/// Keys SDKs accept signed snapshots from (a JWK set)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SigningKeys {
+    pub keys: Vec<SigningKey>,
+}
+
+/// Whether flag changes in an environment must go through change requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentApproval {
+    pub environment: String,
+    pub required: bool,
+}
+
+/// Request to require approval for an environment's flag changes, or stop
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetEnvironmentApprovalRequest {
+    pub required: bool,
+}
+
 /// Feature flag
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Flag {
@@ -58,6 +200,32 @@ pub struct Flag {
     pub project_id: Uuid,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Temporary flags evaluate to off after this
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub expired: bool,
+    /// Person or team responsible for the flag
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// Repository that reads the flag
+    #[serde(default)]
+    pub repository: Option<String>,
+    /// Where in the repository the flag is read
+    #[serde(default)]
+    pub code_path: Option<String>,
+    /// Values of a json flag must match this schema
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<serde_json::Value>,
+    /// State in environments where the flag has no value
+    #[serde(default)]
+    pub default_enabled: bool,
+    /// Served when the flag is on and the environment sets no value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<serde_json::Value>,
+    /// Public environments serve it without a key
+    #[serde(default)]
+    pub public: bool,
 }
 
 /// Flag state in an environment
@@ -79,6 +247,608 @@ pub struct FlagWithState {
     pub enabled: bool,
     #[serde(default)]
     pub value: Option<serde_json::Value>,
+    /// Most recent SDK evaluation in any environment
+    #[serde(default)]
+    pub last_evaluated_at: Option<DateTime<Utc>>,
+    /// State in every environment, keyed by environment name
+    #[serde(default)]
+    pub environments: HashMap<String, FlagEnvironmentState>,
+}
+
+/// A flag's state in one environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagEnvironmentState {
+    pub enabled: bool,
+    pub rollout: i32,
+    /// Pass as `expected_version` to guard against concurrent edits; 0 if never set
+    #[serde(default)]
+    pub version: i64,
+    #[serde(default)]
+    pub value: Option<serde_json::Value>,
+}
+
+/// A flag found by searching across projects
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagSearchResult {
+    pub project_id: String,
+    pub project_slug: String,
+    pub project_name: String,
+    pub key: String,
+    pub name: String,
+    /// State in every environment of the flag's project, keyed by name
+    #[serde(default)]
+    pub environments: HashMap<String, FlagEnvironmentState>,
+}
+
+/// Request to turn a flag on or off in one environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetFlagStateRequest {
+    pub enabled: bool,
+    pub environment: String,
+    /// Fail with a conflict unless the flag value is still at this version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<i64>,
+    /// Acknowledge that the environment is protected
+    #[serde(default)]
+    pub confirm: bool,
+    /// Value to serve while enabled; must match the flag's JSON Schema
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    /// Why the change is made, kept in the flag's history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Request to set many flags at once in one environment
+///
+/// Applied in a single transaction: either every flag changes or none does.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchUpdateFlagsRequest {
+    pub environment: String,
+    pub keys: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rollout_percentage: Option<i32>,
+    /// Acknowledge that the environment is protected
+    #[serde(default)]
+    pub confirm: bool,
+    /// Why the flags are changed, kept in their history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// One flag's state after a batch update
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchUpdatedFlag {
+    pub key: String,
+    /// False if the flag was already in the requested state
+    pub changed: bool,
+    pub enabled: bool,
+    pub rollout: i32,
+    pub version: i64,
+}
+
+/// Result of a batch update, in request order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchUpdateFlagsResponse {
+    pub environment: String,
+    pub flags: Vec<BatchUpdatedFlag>,
+}
+
+/// Request to copy one environment's flag values into another, usually of
+/// another project, for every flag key both projects have
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CopyEnvironmentRequest {
+    /// Project ID or slug
+    pub from_project: String,
+    pub from_environment: String,
+    /// Project ID or slug
+    pub to_project: String,
+    pub to_environment: String,
+    /// Acknowledge that the target environment is protected
+    #[serde(default)]
+    pub confirm: bool,
+    /// Why the flags are changed, kept in their history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Report what would change without changing anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A flag both projects have that wasn't copied, and why
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedFlag {
+    pub key: String,
+    pub reason: String,
+}
+
+/// Result of copying an environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CopyEnvironmentResponse {
+    pub from_project: String,
+    pub from_environment: String,
+    pub to_project: String,
+    pub to_environment: String,
+    pub dry_run: bool,
+    /// Target flags given the source's state, in the target's order
+    pub flags: Vec<BatchUpdatedFlag>,
+    /// Source flags the target project has no flag for
+    pub missing_in_target: Vec<String>,
+    /// Target flags the source project has no flag for; left unchanged
+    pub missing_in_source: Vec<String>,
+    /// Flags of another type, or whose value the target's schema rejects
+    pub skipped: Vec<SkippedFlag>,
+}
+
+/// Flags to create unless the project already has them, e.g. from
+/// [`FlagSet::definitions`](crate::FlagSet::definitions)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterFlagsRequest {
+    pub flags: Vec<CreateFlagRequest>,
+}
+
+/// Which registered flags were created, in request order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegisterFlagsResponse {
+    pub created: Vec<String>,
+    /// Already in the project, and left as they were
+    pub existing: Vec<String>,
+}
+
+/// A proposed flag change, applied once a project admin approves it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeRequest {
+    pub id: String,
+    pub project_id: String,
+    pub flag_key: String,
+    pub environment: String,
+    #[serde(default)]
+    pub enabled: Option<bool>,
+    #[serde(default)]
+    pub rollout_percentage: Option<i32>,
+    /// Version of the flag's value when proposed
+    pub base_version: i64,
+    #[serde(default)]
+    pub comment: Option<String>,
+    /// `pending`, `approved` or `rejected`
+    pub status: String,
+    /// Username of the proposer
+    pub proposed_by: String,
+    #[serde(default)]
+    pub reviewed_by: Option<String>,
+    #[serde(default)]
+    pub review_comment: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+/// Request to propose a flag change for approval
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateChangeRequest {
+    pub flag_key: String,
+    pub environment: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rollout_percentage: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// A plan raising a flag's rollout in one environment step by step
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Ramp {
+    pub id: String,
+    pub project_id: String,
+    pub flag_key: String,
+    pub environment: String,
+    pub target_percentage: i32,
+    /// Percentage points added each step
+    pub step_percentage: i32,
+    pub interval_secs: i64,
+    /// Rollout the ramp last set
+    pub current_percentage: i32,
+    /// `active`, `paused`, `completed` or `aborted`
+    pub status: String,
+    /// When an active ramp takes its next step
+    #[serde(default)]
+    pub next_step_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Username of whoever started the ramp
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// The ramp's guard; only returned when the ramp is created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub guard: Option<Guard>,
+}
+
+/// Request to start ramping a flag's rollout
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateRampRequest {
+    pub environment: String,
+    pub target_percentage: i32,
+    pub step_percentage: i32,
+    pub interval_secs: i64,
+    /// Acknowledge that the environment is protected
+    #[serde(default)]
+    pub confirm: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Revert the flag, and abort the ramp, if this guard hears it's unhealthy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub guard: Option<GuardSpec>,
+}
+
+/// What to do with a running ramp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RampAction {
+    Pause,
+    Resume,
+    Abort,
+}
+
+impl RampAction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RampAction::Pause => "pause",
+            RampAction::Resume => "resume",
+            RampAction::Abort => "abort",
+        }
+    }
+}
+
+/// How a guard watches a change: the health check it polls, if any, and for
+/// how long
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardSpec {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Seconds to watch for, from the change or the end of its ramp
+    pub window_secs: i64,
+}
+
+/// Request to guard a flag's latest change in one environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateGuardRequest {
+    pub environment: String,
+    /// Version the change produced; the server refuses if the flag moved on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<i64>,
+    #[serde(flatten)]
+    pub spec: GuardSpec,
+}
+
+/// A watch on one flag change, which reverts it if it turns out unhealthy
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Guard {
+    pub id: String,
+    pub project_id: String,
+    pub flag_key: String,
+    pub environment: String,
+    #[serde(default)]
+    pub ramp_id: Option<String>,
+    /// Health check the server polls
+    #[serde(default)]
+    pub url: Option<String>,
+    pub version: i64,
+    /// State the flag is reverted to if the guard trips
+    pub revert_enabled: bool,
+    pub revert_rollout_percentage: i32,
+    #[serde(default)]
+    pub revert_value: Option<serde_json::Value>,
+    pub window_secs: i64,
+    /// `watching`, `passed`, `tripped`, `superseded` or `cancelled`
+    pub status: String,
+    /// Why the guard tripped
+    #[serde(default)]
+    pub message: Option<String>,
+    /// When the guard stops watching; None while its ramp is still running
+    #[serde(default)]
+    pub watching_until: Option<DateTime<Utc>>,
+    /// Username of whoever set the guard up
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub ended_at: Option<DateTime<Utc>>,
+    /// Token unhealthy reports are pushed with; only returned when the guard
+    /// is created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// A health report pushed to a guard
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GuardReport {
+    pub token: String,
+    pub healthy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// Share a project with whoever redeems the link
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateInviteLinkRequest {
+    /// `member` (the server's default) or `owner`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_in_secs: Option<i64>,
+}
+
+/// A link sharing a project with any signed-in user who redeems it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InviteLink {
+    pub id: String,
+    pub project_id: String,
+    /// Role redeemers get: `member` or `owner`
+    pub role: String,
+    /// `active`, `expired` or `revoked`
+    pub status: String,
+    /// How many users joined with it
+    pub uses: i64,
+    /// Username of whoever created it
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default)]
+    pub revoked_at: Option<DateTime<Utc>>,
+    /// Only returned when the link is created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+}
+
+/// A user a project was shared with by invite link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectMember {
+    pub user_id: String,
+    pub username: String,
+    /// `member` or `owner`
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// The project an invite link was redeemed for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JoinResponse {
+    pub project: Project,
+    /// None when the project was already reachable through its organization
+    #[serde(default)]
+    pub role: Option<String>,
+    /// False when the link changed nothing
+    pub joined: bool,
+}
+
+/// One recorded version of a flag's value in an environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagVersion {
+    pub version: i64,
+    pub enabled: bool,
+    pub rollout_percentage: i32,
+    #[serde(default)]
+    pub value: Option<serde_json::Value>,
+    pub changed_at: DateTime<Utc>,
+    /// Why the version was written, when the writer said
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+/// A flag's versions in one environment, newest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagHistory {
+    pub key: String,
+    pub environment: String,
+    pub versions: Vec<FlagVersion>,
+    /// Cursor for the next, older page; None on the last page
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+/// Request to restore a flag's value to an earlier version
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackFlagRequest {
+    pub environment: String,
+    /// Version to restore; the one before the current version when absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to_version: Option<i64>,
+    /// Fail with a conflict unless the flag value is still at this version
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_version: Option<i64>,
+    /// Acknowledge that the environment is protected
+    #[serde(default)]
+    pub confirm: bool,
+    /// Why the flag is rolled back, kept in its history
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// A flag's value after a rollback
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackFlagResponse {
+    pub key: String,
+    pub environment: String,
+    /// The version whose state was restored
+    pub restored_version: i64,
+    /// False if the flag already had that state
+    pub changed: bool,
+    #[serde(flatten)]
+    pub current: FlagVersion,
+}
+
+/// Change to a flag's allowlist or denylist in one environment
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateUserListRequest {
+    pub environment: String,
+    /// Users to put on the list; users on the other list move to this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub add: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove: Vec<String>,
+    /// Empty the list before adding
+    #[serde(default)]
+    pub clear: bool,
+    /// Acknowledge that the environment is protected
+    #[serde(default)]
+    pub confirm: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// A flag's allowlist or denylist in one environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserList {
+    pub key: String,
+    pub environment: String,
+    /// `allowlist` or `denylist`
+    pub list: String,
+    pub user_ids: Vec<String>,
+    /// Users an update put on the list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added: Option<usize>,
+    /// Users an update took off the list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removed: Option<usize>,
+}
+
+/// Request for the experiment assignments of a batch of users
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssignmentsRequest {
+    pub environment: String,
+    pub user_ids: Vec<String>,
+}
+
+/// What one user gets from a flag
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Assignment {
+    pub user_id: String,
+    /// Stable 0-99 bucket; users below the rollout percentage are in
+    pub bucket: i32,
+    pub enabled: bool,
+    /// Why, e.g. `in_rollout`, `out_of_rollout`, `disabled`
+    pub reason: String,
+    #[serde(default)]
+    pub value: Option<serde_json::Value>,
+}
+
+/// Assignments for a flag in one environment, in request order
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssignmentsResponse {
+    pub key: String,
+    pub environment: String,
+    pub rollout_percentage: i32,
+    pub assignments: Vec<Assignment>,
+}
+
+/// A flag's result for an SDK key, as served to applications
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagEvaluation {
+    pub key: String,
+    pub enabled: bool,
+    #[serde(default)]
+    pub value: Option<serde_json::Value>,
+    /// Set by a local override rather than the server
+    #[serde(default)]
+    pub overridden: bool,
+}
+
+/// A flag's state in an environment, as served to SDKs that evaluate locally
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlagSnapshotValue {
+    pub enabled: bool,
+    pub rollout_percentage: i32,
+    #[serde(default)]
+    pub value: Option<serde_json::Value>,
+    /// Users the flag is on for while enabled, whatever their bucket; sorted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowlist: Vec<String>,
+    /// Users the flag is off for; sorted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub denylist: Vec<String>,
+}
+
+/// Everything needed to evaluate one flag locally
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FlagSnapshot {
+    pub key: String,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// None if the flag has no value in the environment; `default_enabled` applies
+    pub value: Option<FlagSnapshotValue>,
+    #[serde(default)]
+    pub default_enabled: bool,
+    #[serde(default)]
+    pub default_value: Option<serde_json::Value>,
+}
+
+/// Flags changed in an SDK key's environment since a revision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagChanges {
+    pub project_id: Uuid,
+    pub environment_id: Uuid,
+    /// Pass as `since` on the next poll
+    pub revision: i64,
+    /// `flags` is every flag; drop any held that aren't in it
+    pub full: bool,
+    pub flags: Vec<FlagSnapshot>,
+    /// Keys of flags deleted since the revision
+    #[serde(default)]
+    pub deleted: Vec<String>,
+}
+
+/// Evaluation counts for one day
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DailyCount {
+    pub day: NaiveDate,
+    pub count: i64,
+}
+
+/// Evaluation usage of a flag in one environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EnvironmentStats {
+    pub total: i64,
+    pub last_evaluated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub daily: Vec<DailyCount>,
+}
+
+/// Evaluation usage of a flag across environments
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagStats {
+    pub key: String,
+    pub total: i64,
+    pub last_evaluated_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub environments: std::collections::HashMap<String, EnvironmentStats>,
+}
+
+/// Flag usage of a project in one environment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectEnvironmentStats {
+    pub name: String,
+    pub enabled: i64,
+    /// Share of the project's flags enabled here, 0-100
+    pub enabled_percentage: f64,
+    /// Flags on for only part of the users
+    pub partial_rollout: i64,
+    pub last_changed_at: Option<DateTime<Utc>>,
+    pub api_key_created_at: DateTime<Utc>,
+}
+
+/// Flag usage summary of a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProjectStats {
+    pub project_id: String,
+    pub slug: String,
+    pub flags: i64,
+    pub last_changed_at: Option<DateTime<Utc>>,
+    /// When the project key (ffl_proj_*) was issued
+    pub api_key_created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub environments: Vec<ProjectEnvironmentStats>,
 }
 
 /// Type of feature flag
@@ -113,18 +883,172 @@ pub struct CreateFlagRequest {
     pub flag_type: FlagType,
     #[serde(default)]
     pub enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_path: Option<String>,
+    /// JSON Schema for the values of a json flag
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json_schema: Option<serde_json::Value>,
+    /// State in environments the flag has no value in, e.g. ones created later
+    #[serde(default)]
+    pub default_enabled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<serde_json::Value>,
+}
+
+/// Request to change a flag's ownership metadata, defaults or visibility
+///
+/// Omitted fields are left alone; an empty string clears an ownership field.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateFlagRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_enabled: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_value: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub clear_default_value: bool,
+    /// Let public environments serve the flag without a key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public: Option<bool>,
+}
+
+/// State a flag created from a template starts in, in one environment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TemplateState {
+    pub enabled: bool,
+    #[serde(default = "full_rollout")]
+    pub rollout: i32,
+}
+
+fn full_rollout() -> i32 {
+    100
+}
+
+/// Per-environment starting state for new flags
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlagTemplate {
+    pub name: String,
+    pub description: Option<String>,
+    /// State in environments `environments` doesn't name
+    pub default: TemplateState,
+    /// By environment name
+    #[serde(default)]
+    pub environments: BTreeMap<String, TemplateState>,
+    /// Available in every project; a stored template of the same name replaces it
+    #[serde(default)]
+    pub builtin: bool,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Request to create or replace a template
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetTemplateRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub default: TemplateState,
+    pub environments: BTreeMap<String, TemplateState>,
 }
 
 fn default_flag_type() -> FlagType {
     FlagType::Boolean
 }
 
+/// Longest name the server accepts for flags, projects and orgs
+pub const MAX_NAME_LENGTH: usize = 255;
+
+impl CreateFlagRequest {
+    /// The server's checks on `key` and `name`, reported the way its 422 is
+    pub fn validate(&self) -> Result<(), FlagLiteError> {
+        let mut fields = Vec::new();
+        if let Err(e) = FlagKey::parse(self.key.as_str()) {
+            fields.push(e.field_error("key"));
+        }
+        if self.name.chars().count() > MAX_NAME_LENGTH {
+            fields.push(FieldError {
+                field: "name".to_string(),
+                constraint: "max_length".to_string(),
+                message: format!("Flag name must be at most {MAX_NAME_LENGTH} characters"),
+            });
+        }
+
+        if fields.is_empty() {
+            Ok(())
+        } else {
+            Err(FlagLiteError::InvalidFields(fields))
+        }
+    }
+}
+
 /// Signup request
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignupRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub username: Option<String>,
     pub password: String,
+    /// Required when the server runs with SIGNUP_MODE=invite
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub invite_code: Option<String>,
+}
+
+/// Single-use signup invite
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invite {
+    pub code: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Organization that owns projects, as seen by one of its members
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Organization {
+    pub id: String,
+    pub name: String,
+    /// Created with the user; can't have other members
+    #[serde(default)]
+    pub personal: bool,
+    /// The caller's role: `owner` or `member`
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request to create an organization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateOrgRequest {
+    pub name: String,
+}
+
+/// Member of an organization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrgMember {
+    pub user_id: String,
+    pub username: String,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Request to add an existing user to an organization
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddOrgMemberRequest {
+    pub username: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+}
+
+/// Request to change an organization member's role
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateOrgMemberRequest {
+    /// `owner` or `member`
+    pub role: String,
 }
 
 /// API key info (only shown on creation)
@@ -135,9 +1059,44 @@ pub struct ApiKeyCreated {
     pub key_prefix: String,
     #[serde(default)]
     pub name: Option<String>,
+    /// read, write or admin
+    #[serde(default = "admin_scope")]
+    pub scope: String,
     pub created_at: DateTime<Utc>,
 }
 
+/// Servers from before scopes only issued full-access keys
+fn admin_scope() -> String {
+    "admin".to_string()
+}
+
+/// A user API key in listings; the key itself is never shown again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub key_prefix: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// read, write or admin
+    pub scope: String,
+    /// Set for keys limited to one organization
+    #[serde(default)]
+    pub org_id: Option<String>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Request to create a user API key
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CreateApiKeyRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// read, write or admin (the default)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scope: Option<String>,
+}
+
 /// Signup response
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SignupResponse {
@@ -157,6 +1116,75 @@ pub struct LoginRequest {
     pub password: String,
 }
 
+/// Change the current user's email (empty clears it) or username; None
+/// leaves one alone
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdateUserRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+}
+
+/// One of the current user's username changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UsernameChange {
+    pub old_username: String,
+    pub new_username: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+/// Defaults the CLI applies on login, saved with the account
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UserPreferences {
+    #[serde(default)]
+    pub project_id: Option<String>,
+    #[serde(default)]
+    pub environment: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// CLI output format: pretty, json, csv or tsv
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// Change saved preferences; None leaves one alone and empty clears it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UpdatePreferencesRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+}
+
+/// Ask for a password reset email
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PasswordResetRequest {
+    pub username: String,
+}
+
+/// Set a new password with a token from a reset email
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResetPasswordRequest {
+    pub token: String,
+    pub password: String,
+    /// Also revoke the account's API keys
+    #[serde(default)]
+    pub revoke_api_keys: bool,
+}
+
+/// Confirm an email address with a token from a verification email
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyEmailRequest {
+    pub token: String,
+}
+
 /// Authentication response (login)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthResponse {
@@ -174,6 +1202,15 @@ pub struct ApiErrorResponse {
     pub details: Option<serde_json::Value>,
 }
 
+/// A request field the server rejected, from a 422 response's `details`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldError {
+    pub field: String,
+    /// Machine-readable constraint name (e.g. `max_length`, `charset`)
+    pub constraint: String,
+    pub message: String,
+}
+
 /// Paginated response wrapper
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PaginatedResponse<T> {
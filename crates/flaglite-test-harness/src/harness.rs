@@ -2,13 +2,23 @@
 //!
 //! Spawns actual server processes and runs CLI commands as subprocesses
 //! to test the full stack end-to-end.
+//!
+//! With `FLAGLITE_E2E_SHARED=1`, tests in one binary share a single server
+//! and database instead of starting one each. Tests stay independent because
+//! every user gets a unique username and its own HOME directory, and projects
+//! belong to the user that created them.
+//!
+//! With `FLAGLITE_E2E_DATABASE_URL` (e.g. a Postgres URL), every server uses
+//! that database instead of a fresh SQLite file, so the same suite checks
+//! another storage backend. Tests rely on the same namespacing as shared mode.
 
 use std::fs::{self, File};
 use std::io::Read as _;
 use std::net::TcpListener;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Output, Stdio};
 use std::time::Duration;
+use tokio::sync::{Mutex, OnceCell};
 use tokio::time::sleep;
 
 // ═══════════════════════════════════════════════════════════════════════════
@@ -18,7 +28,7 @@ use tokio::time::sleep;
 /// Test harness that manages server lifecycle and provides test utilities.
 ///
 /// Creates isolated test environments with:
-/// - Its own SQLite database
+/// - Its own SQLite database (or the shared one, see the module docs)
 /// - Server process running on a random port
 /// - Test users with isolated HOME directories
 pub struct TestHarness {
@@ -34,12 +44,85 @@ pub struct TestHarness {
     server_process: Option<Child>,
     /// Server port
     port: u16,
-    /// Database URL (SQLite)
+    /// Database URL (SQLite, or `FLAGLITE_E2E_DATABASE_URL`)
     database_url: String,
     /// Server stdout log file path (for diagnostics)
     server_stdout_path: PathBuf,
     /// Server stderr log file path (for diagnostics)
     server_stderr_path: PathBuf,
+    /// Extra environment variables for the server (e.g. SIGNUP_MODE)
+    server_env: Vec<(String, String)>,
+    /// Using the binary's shared server, which this harness must not stop
+    shared: bool,
+}
+
+/// JWT secret shared by the server and `flaglite-api` admin commands
+const JWT_SECRET: &str = "test-jwt-secret-for-e2e-tests-12345";
+
+/// The server every shared-mode harness in this binary talks to
+static SHARED_SERVER: OnceCell<SharedServer> = OnceCell::const_new();
+
+struct SharedServer {
+    server_url: String,
+    port: u16,
+    database_url: String,
+    /// Parent of each test's directory
+    base_dir: PathBuf,
+    server_stdout_path: PathBuf,
+    server_stderr_path: PathBuf,
+    pid: u32,
+}
+
+impl SharedServer {
+    async fn start() -> Result<Self, Box<dyn std::error::Error>> {
+        let mut host = TestHarness::with_env("shared", &[]).await?;
+        // Taken so dropping `host` leaves it running; stopped at exit instead
+        let process = host
+            .server_process
+            .take()
+            .ok_or("Shared server did not start")?;
+
+        #[cfg(unix)]
+        unsafe {
+            libc::atexit(stop_shared_server);
+        }
+
+        Ok(Self {
+            server_url: host.server_url.clone(),
+            port: host.port,
+            database_url: host.database_url.clone(),
+            base_dir: host.test_dir.clone(),
+            server_stdout_path: host.server_stdout_path.clone(),
+            server_stderr_path: host.server_stderr_path.clone(),
+            pid: process.id(),
+        })
+    }
+}
+
+/// Stop the shared server when the test binary exits
+#[cfg(unix)]
+extern "C" fn stop_shared_server() {
+    if let Some(server) = SHARED_SERVER.get() {
+        unsafe {
+            libc::kill(server.pid as i32, libc::SIGTERM);
+        }
+    }
+}
+
+/// Held while a server starts against an external database, so two servers
+/// don't run migrations on it at once
+static EXTERNAL_STARTUP: Mutex<()> = Mutex::const_new(());
+
+/// The database from `FLAGLITE_E2E_DATABASE_URL`, used instead of SQLite
+fn external_database_url() -> Option<String> {
+    std::env::var("FLAGLITE_E2E_DATABASE_URL")
+        .ok()
+        .filter(|url| !url.is_empty())
+}
+
+/// Whether `FLAGLITE_E2E_SHARED` asks for one server per test binary
+fn shared_mode() -> bool {
+    std::env::var("FLAGLITE_E2E_SHARED").is_ok_and(|v| v == "1" || v == "true")
 }
 
 impl TestHarness {
@@ -51,7 +134,58 @@ impl TestHarness {
     /// 3. Find an available port
     /// 4. Start the flaglite-api server
     /// 5. Wait for the server to be ready
+    ///
+    /// In shared mode (`FLAGLITE_E2E_SHARED=1`) steps 2-5 happen once per
+    /// test binary and later tests reuse that server.
     pub async fn new(test_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        if shared_mode() {
+            Self::shared(test_name).await
+        } else {
+            Self::isolated(test_name).await
+        }
+    }
+
+    /// Create a test harness with its own server, even in shared mode.
+    ///
+    /// For tests that stop the server or depend on a database nobody else writes to.
+    pub async fn isolated(test_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_env(test_name, &[]).await
+    }
+
+    /// Create a test harness that uses the binary's shared server.
+    async fn shared(test_name: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (flaglite_api_bin, flaglite_bin) = get_binary_paths()?;
+        let server = SHARED_SERVER.get_or_try_init(SharedServer::start).await?;
+
+        let test_dir = server.base_dir.join(test_name);
+        if test_dir.exists() {
+            fs::remove_dir_all(&test_dir)?;
+        }
+        fs::create_dir_all(&test_dir)?;
+
+        Ok(Self {
+            server_url: server.server_url.clone(),
+            test_dir,
+            flaglite_bin,
+            flaglite_api_bin,
+            server_process: None,
+            port: server.port,
+            database_url: server.database_url.clone(),
+            server_stdout_path: server.server_stdout_path.clone(),
+            server_stderr_path: server.server_stderr_path.clone(),
+            server_env: Vec::new(),
+            shared: true,
+        })
+    }
+
+    /// Create a test harness whose server runs with extra environment variables.
+    ///
+    /// Always starts a dedicated server, since the settings would leak into
+    /// other tests in shared mode.
+    pub async fn with_env(
+        test_name: &str,
+        server_env: &[(&str, &str)],
+    ) -> Result<Self, Box<dyn std::error::Error>> {
         // Get binary paths
         let (flaglite_api_bin, flaglite_bin) = get_binary_paths()?;
 
@@ -66,9 +200,11 @@ impl TestHarness {
         // Find available port
         let port = find_available_port()?;
 
-        // Setup SQLite database
-        let db_path = test_dir.join("test.db");
-        let database_url = format!("sqlite://{}?mode=rwc", db_path.display());
+        // Setup SQLite database, unless the run targets another one
+        let database_url = external_database_url().unwrap_or_else(|| {
+            let db_path = test_dir.join("test.db");
+            format!("sqlite://{}?mode=rwc", db_path.display())
+        });
 
         // Create log file paths
         let server_stdout_path = test_dir.join("server_stdout.log");
@@ -84,6 +220,11 @@ impl TestHarness {
             database_url,
             server_stdout_path,
             server_stderr_path,
+            server_env: server_env
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            shared: false,
         };
 
         // Start the server
@@ -92,21 +233,23 @@ impl TestHarness {
         Ok(harness)
     }
 
-    /// Start the server process
-    async fn start_server(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+    /// Start the API server, also to bring it back after [`TestHarness::stop_server`]
+    pub async fn start_server(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let _server_addr = format!("0.0.0.0:{}", self.port);
+        let _startup = match external_database_url() {
+            Some(_) => Some(EXTERNAL_STARTUP.lock().await),
+            None => None,
+        };
 
         // Create files to capture server output
         let stdout_file = File::create(&self.server_stdout_path)?;
         let stderr_file = File::create(&self.server_stderr_path)?;
 
-        // Generate a JWT secret for testing
-        let jwt_secret = "test-jwt-secret-for-e2e-tests-12345";
-
         let server = Command::new(&self.flaglite_api_bin)
             .env("DATABASE_URL", &self.database_url)
-            .env("JWT_SECRET", jwt_secret)
+            .env("JWT_SECRET", JWT_SECRET)
             .env("RUST_LOG", "flaglite=debug")
+            .envs(self.server_env.iter().cloned())
             .args([
                 "serve",
                 "--port",
@@ -226,11 +369,87 @@ impl TestHarness {
         }
     }
 
+    /// Run a `flaglite-api` admin command against the test database.
+    pub fn exec_api(&self, args: &[&str]) -> CommandResult {
+        let output = Command::new(&self.flaglite_api_bin)
+            .env("DATABASE_URL", &self.database_url)
+            .env("JWT_SECRET", JWT_SECRET)
+            .env("RUST_LOG", "error")
+            .args(args)
+            .output()
+            .expect("Failed to execute flaglite-api command");
+
+        CommandResult::new(output)
+    }
+
+    /// Start `flaglite-api relay` against this server and wait until it has
+    /// fetched the flags for `api_key`.
+    pub async fn start_relay(&self, api_key: &str) -> Result<Relay, Box<dyn std::error::Error>> {
+        let port = find_available_port()?;
+        let log = File::create(self.test_dir.join(format!("relay_{port}.log")))?;
+
+        let process = Command::new(&self.flaglite_api_bin)
+            .env("RUST_LOG", "flaglite=debug")
+            .args([
+                "relay",
+                "--upstream",
+                &self.server_url,
+                "--api-key",
+                api_key,
+                "--interval",
+                "1",
+                "--port",
+                &port.to_string(),
+                "--host",
+                "127.0.0.1",
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::from(log))
+            .spawn()?;
+
+        let relay = Relay {
+            url: format!("http://127.0.0.1:{port}"),
+            process,
+        };
+
+        // /health turns OK once the first fetch from upstream has succeeded
+        let client = reqwest::Client::new();
+        for _ in 0..100 {
+            sleep(Duration::from_millis(100)).await;
+            if let Ok(resp) = client.get(format!("{}/health", relay.url)).send().await {
+                if resp.status().is_success() {
+                    return Ok(relay);
+                }
+            }
+        }
+        Err("Relay did not become ready within 10 seconds".into())
+    }
+
+    /// Stop the API server, e.g. to check what keeps working without it
+    ///
+    /// Panics on a shared-mode harness; use [`TestHarness::isolated`].
+    pub fn stop_server(&mut self) {
+        assert!(
+            !self.shared,
+            "stop_server would stop the server other tests share; create the harness with TestHarness::isolated"
+        );
+        if let Some(mut server) = self.server_process.take() {
+            let _ = server.kill();
+            let _ = server.wait();
+        }
+    }
+
     /// Get the test directory path
     pub fn test_dir(&self) -> &PathBuf {
         &self.test_dir
     }
 
+    /// Everything the server has logged to stdout so far, e.g. emails it
+    /// would have sent without SMTP
+    pub fn server_stdout(&self) -> String {
+        fs::read_to_string(&self.server_stdout_path).unwrap_or_default()
+    }
+
     /// Get the database URL
     pub fn database_url(&self) -> &str {
         &self.database_url
@@ -258,6 +477,19 @@ impl Drop for TestHarness {
     }
 }
 
+/// A running `flaglite-api relay`, killed on drop
+pub struct Relay {
+    pub url: String,
+    process: Child,
+}
+
+impl Drop for Relay {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+    }
+}
+
 // ═══════════════════════════════════════════════════════════════════════════
 // Test User
 // ═══════════════════════════════════════════════════════════════════════════
@@ -295,6 +527,20 @@ impl TestUser {
         CommandResult::new(output)
     }
 
+    /// Execute a flaglite CLI command from another working directory.
+    pub fn exec_in(&self, dir: &Path, args: &[&str]) -> CommandResult {
+        let output = Command::new(&self.flaglite_bin)
+            .current_dir(dir)
+            .env("HOME", &self.home_dir)
+            .env("FLAGLITE_API_URL", &self.server_url)
+            .env("XDG_CONFIG_HOME", self.home_dir.join(".config"))
+            .args(args)
+            .output()
+            .expect("Failed to execute command");
+
+        CommandResult::new(output)
+    }
+
     /// Execute a flaglite CLI command with JSON output format.
     pub fn exec_json(&self, args: &[&str]) -> CommandResult {
         let mut full_args = vec!["--format", "json"];
@@ -519,6 +765,32 @@ impl TestUser {
         Ok(())
     }
 
+    /// Clone a project via CLI.
+    pub fn projects_clone(
+        &self,
+        project: &str,
+        name: &str,
+        with_values: bool,
+    ) -> Result<ProjectInfo, String> {
+        let mut args = vec!["projects", "clone", project, "--name", name];
+        if with_values {
+            args.push("--with-values");
+        }
+
+        let result = self.exec_json(&args);
+
+        if result.failed() {
+            return Err(format!(
+                "Projects clone failed: {} {}",
+                result.stdout(),
+                result.stderr()
+            ));
+        }
+
+        serde_json::from_str(&result.stdout())
+            .map_err(|e| format!("Failed to parse cloned project: {e}"))
+    }
+
     // ─────────────────────────────────────────────────────────────────────────
     // Flag Commands
     // ─────────────────────────────────────────────────────────────────────────
@@ -850,7 +1122,7 @@ pub struct EnvInfo {
 // ═══════════════════════════════════════════════════════════════════════════
 
 /// Find an available TCP port.
-fn find_available_port() -> Result<u16, Box<dyn std::error::Error>> {
+pub fn find_available_port() -> Result<u16, Box<dyn std::error::Error>> {
     let listener = TcpListener::bind("127.0.0.1:0")?;
     let port = listener.local_addr()?.port();
     drop(listener);
@@ -871,9 +1143,8 @@ fn get_binary_paths() -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>>
         }
     }
 
-    // Get workspace root from CARGO_MANIFEST_DIR
-    // The e2e-tests crate is at: workspace/apps/e2e-tests
-    // So binaries are at: workspace/target/debug/
+    // Cargo runs tests with CARGO_MANIFEST_DIR set to the crate under test,
+    // e.g. workspace/apps/e2e-tests, so binaries are at workspace/target/debug/
     let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").map(PathBuf::from).ok();
 
     let cwd = std::env::current_dir()?;
@@ -881,12 +1152,15 @@ fn get_binary_paths() -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>>
     // Build list of potential base directories
     let mut base_dirs: Vec<PathBuf> = Vec::new();
 
-    // From manifest directory (apps/e2e-tests), go up to workspace root
+    // From manifest directory, go up to the workspace root
     if let Some(ref manifest) = manifest_dir {
         // apps/e2e-tests -> apps -> workspace
         if let Some(workspace) = manifest.parent().and_then(|p| p.parent()) {
             base_dirs.push(workspace.to_path_buf());
         }
+        if let Some(parent) = manifest.parent() {
+            base_dirs.push(parent.to_path_buf());
+        }
         base_dirs.push(manifest.clone());
     }
 
@@ -901,16 +1175,23 @@ fn get_binary_paths() -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>>
         }
     }
 
-    // Binary paths relative to workspace root
+    // A CARGO_TARGET_DIR is searched first, then each base's target/
+    let target_dirs: Vec<PathBuf> = std::env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .into_iter()
+        .chain(base_dirs.iter().map(|base| base.join("target")))
+        .collect();
+
+    // Binary paths relative to the target directory
     let candidates = [
-        ("target/debug/flaglite-api", "target/debug/flaglite"),
-        ("target/release/flaglite-api", "target/release/flaglite"),
+        ("debug/flaglite-api", "debug/flaglite"),
+        ("release/flaglite-api", "release/flaglite"),
     ];
 
-    for base in &base_dirs {
+    for target in &target_dirs {
         for (api_rel, cli_rel) in &candidates {
-            let api_path = base.join(api_rel);
-            let cli_path = base.join(cli_rel);
+            let api_path = target.join(api_rel);
+            let cli_path = target.join(cli_rel);
 
             if api_path.exists() && cli_path.exists() {
                 return Ok((api_path.canonicalize()?, cli_path.canonicalize()?));
@@ -919,12 +1200,12 @@ fn get_binary_paths() -> Result<(PathBuf, PathBuf), Box<dyn std::error::Error>>
     }
 
     // Build helpful error message
-    let searched: Vec<String> = base_dirs
+    let searched: Vec<String> = target_dirs
         .iter()
-        .flat_map(|base| {
+        .flat_map(|target| {
             candidates
                 .iter()
-                .map(move |(api, _)| format!("{}/{}", base.display(), api))
+                .map(move |(api, _)| format!("{}/{}", target.display(), api))
         })
         .collect();
 
@@ -0,0 +1,48 @@
+//! Black-box test harness for FlagLite
+//!
+//! [`TestHarness`] starts a real `flaglite-api` server on a free port with its
+//! own SQLite database, and [`TestUser`]s run the `flaglite` CLI against it as
+//! subprocesses, each with an isolated HOME so their credentials don't mix.
+//! Tests check what the commands print, the way a user would see it. This is
+//! the harness FlagLite's own e2e tests use; services that embed
+//! `flaglite-api` can use it to test their deployment the same way.
+//!
+//! ```no_run
+//! use flaglite_test_harness::{unique_flag_key, TestHarness, TEST_PASSWORD};
+//!
+//! # async fn example() {
+//! let harness = TestHarness::new("checkout").await.unwrap();
+//! let user = harness.create_user("alice");
+//! user.signup(None, TEST_PASSWORD).unwrap();
+//!
+//! let project = &user.projects_list().unwrap()[0];
+//! user.projects_use(&project.id).unwrap();
+//! let key = unique_flag_key();
+//! user.flags_create(&key, None, None, true).unwrap();
+//! assert!(user.flags_get(&key).unwrap().enabled);
+//! # }
+//! ```
+//!
+//! ## Binaries
+//!
+//! The harness runs already built binaries: `FLAGLITE_API_BIN` and
+//! `FLAGLITE_CLI_BIN` when both are set, otherwise `flaglite-api` and
+//! `flaglite` from `target/debug` or `target/release` of the workspace being
+//! tested (or `CARGO_TARGET_DIR`). Build them first with `cargo build --bins`.
+//!
+//! ## Environment Variables
+//!
+//! - `FLAGLITE_API_BIN` - Path to flaglite-api binary (optional)
+//! - `FLAGLITE_CLI_BIN` - Path to flaglite CLI binary (optional)
+//! - `FLAGLITE_E2E_SERVER_TIMEOUT_SECS` - Server startup timeout (default: 30)
+//! - `FLAGLITE_E2E_SHARED` - One server per test binary, see [`TestHarness::new`]
+//! - `FLAGLITE_E2E_DATABASE_URL` - Run every server against this database
+
+pub mod harness;
+pub mod utils;
+
+pub use harness::{
+    find_available_port, CommandResult, EnvInfo, FlagInfo, ProjectInfo, Relay, SignupInfo,
+    TestHarness, TestUser, WhoamiInfo,
+};
+pub use utils::*;
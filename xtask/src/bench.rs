@@ -0,0 +1,269 @@
+//! `cargo xtask bench` - load test of flag evaluation
+//!
+//! Builds the API server in release mode, starts it on a fresh database,
+//! signs up, creates one flag and then keeps `concurrency` connections busy
+//! with `GET /v1/flags/:key/evaluate` for `duration` seconds. Prints the
+//! throughput and latency percentiles, so runs before and after a change can
+//! be compared.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::net::TcpListener;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use crate::run_cargo;
+
+/// Flag every request evaluates
+const FLAG_KEY: &str = "bench-flag";
+
+/// Requests made before measuring, to fill the pool and statement caches
+const WARMUP: Duration = Duration::from_secs(1);
+
+pub struct BenchOptions {
+    pub concurrency: usize,
+    pub duration: Duration,
+    pub database_url: Option<String>,
+    pub http2: bool,
+}
+
+/// The API server under test, stopped on drop
+struct Server {
+    child: Child,
+    url: String,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// What one connection measured
+#[derive(Default)]
+struct Sample {
+    latencies: Vec<Duration>,
+    errors: usize,
+}
+
+pub fn run(options: BenchOptions) -> Result<()> {
+    println!("🔨 Building flaglite-api (release)...");
+    run_cargo(&["build", "--release", "--package", "flaglite-api"])?;
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(bench(options))
+}
+
+async fn bench(options: BenchOptions) -> Result<()> {
+    let data_dir = std::env::temp_dir().join(format!("flaglite-bench-{}", std::process::id()));
+    std::fs::create_dir_all(&data_dir)?;
+    let database_url = options
+        .database_url
+        .clone()
+        .unwrap_or_else(|| format!("sqlite:{}?mode=rwc", data_dir.join("bench.db").display()));
+
+    let server = start_server(&database_url)?;
+    let result = drive(&server, &options).await;
+    drop(server);
+    let _ = std::fs::remove_dir_all(&data_dir);
+    result
+}
+
+fn start_server(database_url: &str) -> Result<Server> {
+    // Let the OS pick a free port
+    let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+
+    let child = Command::new(target_dir().join("release").join("flaglite-api"))
+        .args(["serve", "--host", "127.0.0.1", "--port", &port.to_string()])
+        .env("DATABASE_URL", database_url)
+        .env("JWT_SECRET", "flaglite-bench-secret")
+        .env("RUST_LOG", "warn")
+        .stdout(Stdio::null())
+        .spawn()
+        .context("Failed to start flaglite-api")?;
+
+    Ok(Server {
+        child,
+        url: format!("http://127.0.0.1:{port}"),
+    })
+}
+
+fn target_dir() -> PathBuf {
+    match std::env::var_os("CARGO_TARGET_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .parent()
+            .expect("xtask lives in the workspace")
+            .join("target"),
+    }
+}
+
+async fn drive(server: &Server, options: &BenchOptions) -> Result<()> {
+    let mut builder = reqwest::Client::builder().pool_max_idle_per_host(options.concurrency);
+    if options.http2 {
+        builder = builder.http2_prior_knowledge();
+    }
+    let client = builder.build()?;
+
+    wait_until_healthy(&client, &server.url).await?;
+    let api_key = setup(&client, &server.url).await?;
+    let url = format!("{}/v1/flags/{FLAG_KEY}/evaluate", server.url);
+
+    println!(
+        "🏁 {} connections for {}s ({})...",
+        options.concurrency,
+        options.duration.as_secs(),
+        if options.http2 { "HTTP/2" } else { "HTTP/1.1" }
+    );
+    load(&client, &url, &api_key, options.concurrency, WARMUP).await;
+    let started = Instant::now();
+    let sample = load(
+        &client,
+        &url,
+        &api_key,
+        options.concurrency,
+        options.duration,
+    )
+    .await;
+
+    report(sample, started.elapsed())
+}
+
+async fn wait_until_healthy(client: &reqwest::Client, url: &str) -> Result<()> {
+    for _ in 0..100 {
+        if let Ok(response) = client.get(format!("{url}/health")).send().await {
+            if response.status().is_success() {
+                return Ok(());
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    anyhow::bail!("flaglite-api didn't become healthy at {url}")
+}
+
+/// Sign up, create and enable the flag, and return the production SDK key
+async fn setup(client: &reqwest::Client, url: &str) -> Result<String> {
+    let signup: Value = client
+        .post(format!("{url}/v1/auth/signup"))
+        .json(&json!({ "password": "flaglite-bench-password" }))
+        .send()
+        .await?
+        .error_for_status()
+        .context("Signup failed")?
+        .json()
+        .await?;
+
+    let token = signup["token"]
+        .as_str()
+        .context("Signup returned no token")?;
+    let project_id = signup["project"]["id"]
+        .as_str()
+        .context("Signup returned no project")?;
+    let api_key = signup["environments"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|env| env["name"] == "production")
+        .and_then(|env| env["api_key"].as_str())
+        .context("Signup returned no production environment")?
+        .to_string();
+
+    let flags = format!("{url}/v1/projects/{project_id}/flags");
+    client
+        .post(&flags)
+        .bearer_auth(token)
+        .json(&json!({ "key": FLAG_KEY, "name": "Bench flag" }))
+        .send()
+        .await?
+        .error_for_status()
+        .context("Creating the flag failed")?;
+    client
+        .put(format!("{flags}/{FLAG_KEY}/state"))
+        .bearer_auth(token)
+        .json(&json!({ "enabled": true, "environment": "production", "confirm": true }))
+        .send()
+        .await?
+        .error_for_status()
+        .context("Enabling the flag failed")?;
+
+    Ok(api_key)
+}
+
+/// Evaluate from `concurrency` tasks until `duration` has passed
+async fn load(
+    client: &reqwest::Client,
+    url: &str,
+    api_key: &str,
+    concurrency: usize,
+    duration: Duration,
+) -> Sample {
+    let deadline = Instant::now() + duration;
+    let tasks: Vec<_> = (0..concurrency)
+        .map(|worker| {
+            let client = client.clone();
+            let url = url.to_string();
+            let api_key = api_key.to_string();
+            tokio::spawn(async move {
+                let mut sample = Sample::default();
+                let mut n = 0u64;
+                while Instant::now() < deadline {
+                    let user_id = format!("user-{worker}-{n}");
+                    n += 1;
+                    let started = Instant::now();
+                    let ok = client
+                        .get(&url)
+                        .bearer_auth(&api_key)
+                        .query(&[("user_id", user_id)])
+                        .send()
+                        .await
+                        .is_ok_and(|r| r.status().is_success());
+                    if ok {
+                        sample.latencies.push(started.elapsed());
+                    } else {
+                        sample.errors += 1;
+                    }
+                }
+                sample
+            })
+        })
+        .collect();
+
+    let mut total = Sample::default();
+    for task in tasks {
+        if let Ok(sample) = task.await {
+            total.latencies.extend(sample.latencies);
+            total.errors += sample.errors;
+        }
+    }
+    total
+}
+
+fn report(mut sample: Sample, elapsed: Duration) -> Result<()> {
+    if sample.latencies.is_empty() {
+        anyhow::bail!("Every request failed ({} errors)", sample.errors);
+    }
+    sample.latencies.sort();
+
+    let percentile = |p: f64| {
+        let i = ((sample.latencies.len() - 1) as f64 * p).round() as usize;
+        sample.latencies[i]
+    };
+
+    println!();
+    println!(
+        "  requests   {} ({} errors)",
+        sample.latencies.len(),
+        sample.errors
+    );
+    println!(
+        "  throughput {:.0} req/s",
+        sample.latencies.len() as f64 / elapsed.as_secs_f64()
+    );
+    println!("  p50        {:?}", percentile(0.50));
+    println!("  p90        {:?}", percentile(0.90));
+    println!("  p99        {:?}", percentile(0.99));
+    println!("  max        {:?}", percentile(1.0));
+    Ok(())
+}
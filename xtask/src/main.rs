@@ -2,9 +2,12 @@
 //!
 //! Usage: `cargo xtask <command>`
 
+mod bench;
+
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use std::process::{Command, ExitStatus};
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "xtask")]
@@ -38,6 +41,25 @@ enum Commands {
 
     /// Run tests with coverage (requires cargo-llvm-cov)
     Coverage,
+
+    /// Load-test flag evaluation against a release build of the API server
+    Bench {
+        /// Connections kept busy at once
+        #[arg(short, long, default_value = "32")]
+        concurrency: usize,
+
+        /// Seconds to measure for
+        #[arg(short, long, default_value = "10")]
+        duration: u64,
+
+        /// DATABASE_URL for the server (default: a fresh SQLite file)
+        #[arg(long)]
+        database_url: Option<String>,
+
+        /// Use HTTP/2 without TLS (h2c) instead of HTTP/1.1
+        #[arg(long)]
+        http2: bool,
+    },
 }
 
 fn main() -> Result<()> {
@@ -49,6 +71,17 @@ fn main() -> Result<()> {
         Commands::Lint => cmd_lint(),
         Commands::Test { package } => cmd_test(package),
         Commands::Coverage => cmd_coverage(),
+        Commands::Bench {
+            concurrency,
+            duration,
+            database_url,
+            http2,
+        } => bench::run(bench::BenchOptions {
+            concurrency,
+            duration: Duration::from_secs(duration),
+            database_url,
+            http2,
+        }),
     }
 }
 